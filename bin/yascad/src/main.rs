@@ -1,42 +1,648 @@
-use std::{fs::File, path::PathBuf, process::exit};
+use std::{fs::{self, File}, io::BufWriter, iter::zip, path::{Path, PathBuf}, process::exit, rc::Rc, slice};
 
-use clap::Parser as ClapParser;
+use clap::{ArgAction, CommandFactory, Parser as ClapParser, Subcommand, ValueEnum, error::ErrorKind};
+use log::LevelFilter;
 use miette::Diagnostic;
-use yascad_lang::{InputSource, LangError, build_model};
-use manifold_rs::ext::MeshGLExt;
+use serde::Serialize;
+use yascad_lang::{BuildFingerprint, BuildOptions, BuiltModel, ExportPlacement, ExportPrecision, InputSource, LangError, Manifold, MeshGL, PLA_DENSITY, SceneGroup, ScenePart, SourceDiagnostic, Unit, Vec3, all_builtins, build_with_options, export_rescale_factor, format_builtin_reference, format_profile, format_scene_dump, merge_groups, openscad, render_plain, Centering, UpAxis, export::{TextStlOptions, ThreeMfObject, write_3mf, write_binary_stl_from_meshgls, write_stl_from_meshgl, write_svg}};
 
 #[derive(ClapParser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// Path to the input file
-    #[arg(short)]
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the input file. Give more than once to combine several files into one assembly -
+    /// each is built separately, so they can't see each other's definitions.
+    #[arg(short, required = true)]
+    input: Vec<PathBuf>,
+
+    /// Move the geometry of an input by this much, written as `x,y,z`. If given, there must be one
+    /// for each input, in the same order.
+    #[arg(long, value_parser = parse_offset, allow_hyphen_values = true)]
+    offset: Vec<Vec3<f64>>,
 
     /// Path to the output file
-    #[arg(short)]
-    output: PathBuf,
+    #[arg(short, required_unless_present_any = ["split_parts", "dump_scene", "stats"])]
+    output: Option<PathBuf>,
+
+    /// Write each top-level part to its own STL file in this directory, instead of a single output
+    /// file. A `manifest.json` is also written, describing where each part came from.
+    #[arg(long, conflicts_with = "output")]
+    split_parts: Option<PathBuf>,
+
+    /// Print a description of every geometry in the scene, instead of writing any output file.
+    #[arg(long, conflicts_with_all = ["output", "split_parts"])]
+    dump_scene: bool,
+
+    /// Maximum number of decimal places to write in STL files. By default, numbers are written in
+    /// the shortest form which preserves their exact value.
+    #[arg(long)]
+    stl_precision: Option<usize>,
+
+    /// Write STL files in binary form, which is much smaller than text. Binary STL files can only
+    /// contain one solid, so named parts are written together without their names.
+    #[arg(long, conflicts_with = "stl_precision")]
+    binary: bool,
+
+    /// Write the outline of the model where it crosses this height, instead of the whole model.
+    /// Requires `--format svg`.
+    #[arg(long, conflicts_with_all = ["split_parts", "dump_scene"])]
+    slice_z: Option<f64>,
+
+    /// Print the volume of each input, with a rough estimate of its mass and how long it would
+    /// take to 3D print. Estimates come from the volume and surface area alone, assuming typical
+    /// settings, so they're only useful for comparing versions of a model. Also prints the
+    /// fingerprint which exported files are marked with.
+    #[arg(long)]
+    stats: bool,
+
+    /// Density of the material for `--stats`, in g/cm³. Defaults to PLA.
+    #[arg(long, requires = "stats", value_parser = parse_density, default_value_t = PLA_DENSITY)]
+    density: f64,
+
+    /// Print how long each top-level statement and each call to a module or operator took to
+    /// build, slowest first.
+    #[arg(long)]
+    profile: bool,
+
+    /// Don't warn about numbers which look like they use a comma as a decimal separator, like
+    /// `10,5`.
+    #[arg(long)]
+    no_locale_hints: bool,
+
+    /// Don't load the prelude, so that modules like `hexagon` aren't defined.
+    #[arg(long)]
+    no_prelude: bool,
+
+    /// Fail if building any input produces warnings, like for a CI check.
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// Log more detail about what happens while building, to stderr. Give twice for even more.
+    /// This also lists the bindings in scope when a name is undefined.
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Which axis points upwards in the output file. Models are built Z-up, but some tools expect
+    /// Y-up instead.
+    #[arg(long, value_parser = parse_up_axis, default_value = "z", conflicts_with_all = ["slice_z", "dump_scene"])]
+    up: UpAxis,
+
+    /// Move the model so that the centre of its bounding box is at the origin. The distance it was
+    /// moved is printed to stderr.
+    #[arg(long, conflicts_with_all = ["on_plate", "slice_z", "dump_scene"])]
+    center: bool,
+
+    /// Like `--center`, but with the bottom of the model at Z=0, ready to print.
+    #[arg(long, conflicts_with_all = ["slice_z", "dump_scene"])]
+    on_plate: bool,
+
+    /// Multiply every coordinate in the output file by this, like 0.001 to export a millimetre
+    /// model for a tool which expects metres. The model itself isn't affected. 3MF files record
+    /// their unit, so it's changed to match - the scaled coordinates must be in a unit which 3MF
+    /// supports.
+    #[arg(long, value_parser = parse_scale, default_value_t = 1.0, conflicts_with_all = ["slice_z", "dump_scene"])]
+    scale_output: f64,
+
+    /// Scale the output by a power of ten so that its coordinates can be written accurately, for
+    /// very small or very large models. The factor is printed to stderr and added to the name of
+    /// each solid, so that it can be undone. Only supported for STL output.
+    #[arg(long, conflicts_with_all = ["slice_z", "dump_scene"])]
+    rescale_for_export: bool,
+
+    /// Format of the output file
+    #[arg(long, value_enum, default_value_t = OutputFormat::Stl)]
+    format: OutputFormat,
+
+    /// How to print errors and warnings
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Fancy, global = true)]
+    error_format: ErrorFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// A 3D model, with a separate solid for each named part
+    Stl,
+
+    /// A 3D model, with a separate object for each named part
+    #[value(name = "3mf")]
+    ThreeMf,
+
+    /// A 2D outline, used with `--slice-z`
+    Svg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorFormat {
+    /// With the surrounding source and any hints
+    Fancy,
+
+    /// One `file:line:column: message` line each, for editors and other tools to read
+    Short,
+}
+
+/// How STL files are written.
+#[derive(Clone, Copy, Debug)]
+enum StlEncoding {
+    Text(TextStlOptions),
+    Binary,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Convert an OpenSCAD file into yascad source, on a best-effort basis
+    Convert {
+        /// Path to the OpenSCAD file
+        #[arg(short)]
+        input: PathBuf,
+
+        /// Path to write the yascad source to - if omitted, it's printed instead
+        #[arg(short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print a reference of every built-in module and operator, with their parameters
+    Builtins {
+        /// Print the reference as JSON, for tools like editors to read
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Describes the files written by `--split-parts`.
+#[derive(Serialize, Debug)]
+struct PartsManifest {
+    /// SHA-256 hash of the source which the parts were built from. See [`BuildFingerprint`].
+    source_sha256: String,
+
+    /// Version of yascad which built the parts.
+    version: &'static str,
+
+    parts: Vec<PartsManifestEntry>,
+}
+
+/// A built-in module or operator, as printed by `yascad builtins --json`.
+#[derive(Serialize, Debug)]
+struct BuiltinReferenceEntry {
+    name: &'static str,
+
+    /// Either "module" or "operator".
+    kind: &'static str,
+
+    summary: &'static str,
+    parameters: Vec<BuiltinReferenceParameter>,
+}
+
+#[derive(Serialize, Debug)]
+struct BuiltinReferenceParameter {
+    name: String,
+    summary: &'static str,
+
+    /// The default value as it would be written in source, or null if the parameter is required.
+    default: Option<String>,
+
+    /// Whether the argument can only be given by name.
+    named_only: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct PartsManifestEntry {
+    /// Name of the STL file, relative to the manifest.
+    file: String,
+
+    /// Name of the source file which the part was defined in.
+    source: String,
+
+    /// Line of the source file which the part was defined on.
+    line: usize,
 }
 
 fn main() {
     let args = Args::parse();
-    let source = InputSource::new_file(args.input).unwrap();
 
-    match build_model(source) {
-        Ok(model) => {
-            let stl = model.meshgl().to_stl("YASCADExport");
+    // Info is where output requested by the model itself goes, like `__debug`, so it's always shown
+    let level = match args.verbose {
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+
+    match args.command {
+        Some(Command::Convert { input, output }) => {
+            convert_openscad(&input, output.as_deref(), args.error_format);
+            return;
+        }
+        Some(Command::Builtins { json }) => {
+            print_builtins(json);
+            return;
+        }
+        None => {},
+    }
+
+    match (args.format, args.slice_z) {
+        (OutputFormat::Stl | OutputFormat::ThreeMf, Some(_)) =>
+            abort_with_usage_error(ErrorKind::ArgumentConflict, "`--slice-z` produces a 2D outline, so requires `--format svg`"),
+        (OutputFormat::Svg, None) =>
+            abort_with_usage_error(ErrorKind::ArgumentConflict, "SVG output is only supported for slices - use `--slice-z`"),
+        _ => {},
+    }
+
+    if args.rescale_for_export && args.format != OutputFormat::Stl {
+        abort_with_usage_error(ErrorKind::ArgumentConflict, "`--rescale-for-export` records the factor in STL solid names, so requires `--format stl`");
+    }
+
+    if args.binary && args.format != OutputFormat::Stl {
+        abort_with_usage_error(ErrorKind::ArgumentConflict, "`--binary` requires `--format stl`");
+    }
+
+    if args.dump_scene && args.input.len() > 1 {
+        abort_with_usage_error(ErrorKind::ArgumentConflict, "`--dump-scene` only supports a single input");
+    }
 
-            let mut file = File::create(args.output).unwrap();
-            stl.write_text_stl(&mut file).unwrap();
+    let offsets = if args.offset.is_empty() {
+        vec![Vec3::new(0.0, 0.0, 0.0); args.input.len()]
+    } else if args.offset.len() == args.input.len() {
+        args.offset
+    } else {
+        abort_with_usage_error(ErrorKind::WrongNumberOfValues, &format!(
+            "got {} offsets for {} inputs - give one `--offset` for each `-i`", args.offset.len(), args.input.len(),
+        ))
+    };
+
+    let stl_encoding = if args.binary {
+        StlEncoding::Binary
+    } else {
+        StlEncoding::Text(TextStlOptions { precision: args.stl_precision })
+    };
+    let placement = ExportPlacement {
+        centering: match (args.center, args.on_plate) {
+            (true, _) => Some(Centering::Center),
+            (_, true) => Some(Centering::OnPlate),
+            _ => None,
+        },
+        scale: args.scale_output,
+        up: args.up,
+    };
+
+    let options = BuildOptions { profile: args.profile, locale_hints: !args.no_locale_hints, prelude: !args.no_prelude, deny_warnings: args.deny_warnings, verbose_errors: args.verbose > 0, ..BuildOptions::default() };
+    let models = args.input.iter()
+        .map(|input| build_or_abort(InputSource::new_file(input).unwrap(), options.clone(), args.error_format))
+        .collect::<Vec<_>>();
+    let fingerprint = BuildFingerprint::combine(&models.iter().map(BuiltModel::fingerprint).collect::<Vec<_>>());
+
+    if args.stats {
+        for (input, model) in zip(&args.input, &models) {
+            println!("{}: {}", input.display(), model.print_estimate(args.density));
+            println!("{}: built from SHA-256 {} by YASCAD {}", input.display(), model.fingerprint().source_sha256(), model.fingerprint().version);
+            if placement.scale != 1.0 {
+                println!("{}: exported coordinates are scaled by {}", input.display(), placement.scale);
+            }
         }
+    }
+
+    if args.dump_scene {
+        println!("{}", format_scene_dump(&models[0].scene()));
+    } else if let Some(out_dir) = args.split_parts {
+        let mut parts = zip(&models, &offsets)
+            .flat_map(|(model, offset)| model.parts().into_iter().map(|part| ScenePart {
+                manifold: part.manifold.translate(offset.x, offset.y, offset.z),
+                ..part
+            }))
+            .collect::<Vec<_>>();
+        place_for_export(parts.iter_mut().map(|part| &mut part.manifold).collect(), &placement);
+        let rescale = rescale_for_export(parts.iter_mut().map(|part| &mut part.manifold).collect(), args.rescale_for_export);
+        write_split_parts(&out_dir, parts, stl_encoding, rescale, fingerprint);
+    } else if let Some(output) = args.output {
+        let mut groups = merge_groups(zip(models.iter().map(BuiltModel::groups), offsets));
+        place_for_export(groups.iter_mut().map(|group| &mut group.manifold).collect(), &placement);
+        let rescale = rescale_for_export(groups.iter_mut().map(|group| &mut group.manifold).collect(), args.rescale_for_export);
 
-        Err(LangError::Tokenize(errors)) => abort_with_errors(errors),
-        Err(LangError::Parser(errors)) => abort_with_errors(errors),
-        Err(LangError::Runtime(error)) => abort_with_errors(vec![error]),
+        match (args.format, args.slice_z) {
+            (_, Some(z)) => {
+                let manifold = groups.iter().fold(Manifold::new(), |result, group| result.union(&group.manifold));
+                write_slice(&manifold, z, &output);
+            }
+            (OutputFormat::ThreeMf, _) => write_3mf_groups(&groups, three_mf_unit(common_units(&models), placement.scale), &output, fingerprint),
+            _ => write_stl_groups(&groups, &output, stl_encoding, rescale, fingerprint),
+        }
+    }
+}
+
+/// Move exported geometry into place, all together, and say what was done to it so that the
+/// output's coordinates can be related back to the model.
+fn place_for_export(manifolds: Vec<&mut Manifold>, placement: &ExportPlacement) {
+    // Printed in the same format as `--offset`, so that the same placement can be used again
+    if let Some(offset) = placement.apply(manifolds) {
+        eprintln!("Moved the model by {},{},{}", offset.x, offset.y, offset.z);
+    }
+    if placement.scale != 1.0 {
+        eprintln!("Scaled the output by {}", placement.scale);
     }
 }
 
-fn abort_with_errors<E: Diagnostic + Send + Sync + 'static>(errors: Vec<E>) -> ! {
+/// If enabled, scale exported geometry into a range which can be written accurately. Returns the
+/// factor it was scaled by, which is 1 if it wasn't.
+fn rescale_for_export(manifolds: Vec<&mut Manifold>, enabled: bool) -> f64 {
+    if !enabled {
+        return 1.0;
+    }
+
+    let factor = export_rescale_factor(manifolds.iter().map(|manifold| &**manifold));
+    if factor != 1.0 {
+        for manifold in manifolds {
+            *manifold = manifold.scale(factor, factor, factor);
+        }
+        eprintln!("Rescaled the output by {factor} for export");
+    }
+    factor
+}
+
+/// The name of a solid in an STL file, noting any rescaling so that it can be undone.
+fn solid_name(name: &str, rescale: f64) -> String {
+    if rescale == 1.0 {
+        name.to_owned()
+    } else {
+        format!("{name} (scaled by {rescale})")
+    }
+}
+
+/// The name of a solid in a text STL file, followed by the fingerprint of what it was built from.
+fn text_solid_name(name: &str, rescale: f64, fingerprint: BuildFingerprint) -> String {
+    format!("{} {fingerprint}", solid_name(name, rescale))
+}
+
+/// Print a warning if rounding a mesh's coordinates for export might distort it.
+fn check_export_precision(mesh: &MeshGL, decimal_places: Option<usize>, name: &str) {
+    let Some(precision) = ExportPrecision::measure(mesh, decimal_places)
+        .filter(ExportPrecision::is_lossy)
+    else { return };
+
+    // Limiting decimal places can be fixed by scaling, but floats are just as precise at any size
+    let hint = if precision.rounding_error > precision.largest_coordinate * f32::EPSILON as f64 {
+        "try a higher `--stl-precision`, or `--rescale-for-export`"
+    } else {
+        "if it's far from the origin, try `--center`"
+    };
+    eprintln!(
+        "Warning: \"{name}\" has an edge of length {}, which is close to the rounding error of up to {} when its coordinates are written, so it may come out distorted or cracked - {hint}",
+        precision.shortest_edge, precision.rounding_error,
+    );
+}
+
+fn parse_up_axis(value: &str) -> Result<UpAxis, String> {
+    UpAxis::from_name(value).ok_or_else(|| "expected `y` or `z`".to_owned())
+}
+
+/// Parse a `--scale-output` factor, which must be positive - anything else would collapse or
+/// mirror the model.
+fn parse_scale(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(scale) if scale > 0.0 && scale.is_finite() => Ok(scale),
+        Ok(_) => Err("must be a positive number".to_owned()),
+        Err(_) => Err(format!("\"{value}\" is not a number")),
+    }
+}
+
+/// Parse a `--density`, which must be positive.
+fn parse_density(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(density) if density > 0.0 && density.is_finite() => Ok(density),
+        Ok(_) => Err("must be a positive number".to_owned()),
+        Err(_) => Err(format!("\"{value}\" is not a number")),
+    }
+}
+
+/// Parse an `--offset` value, like `10,0,-5`.
+fn parse_offset(value: &str) -> Result<Vec3<f64>, String> {
+    let components = value.split(',')
+        .map(|component| component.trim().parse::<f64>()
+            .map_err(|_| format!("\"{}\" is not a number", component.trim())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let [x, y, z] = components[..]
+    else { return Err(format!("expected 3 numbers separated by commas, got {}", components.len())) };
+    Ok(Vec3::new(x, y, z))
+}
+
+/// The units shared by all of the models, exiting if they don't agree - there's no one unit to label
+/// the combined output with.
+fn common_units(models: &[BuiltModel]) -> Unit {
+    let units = models[0].units();
+    if models.iter().any(|model| model.units() != units) {
+        abort_with_usage_error(ErrorKind::ValueValidation, "all inputs must declare the same `units` to be combined into a 3MF file");
+    }
+    units
+}
+
+/// The 3MF unit which exported coordinates are measured in, for a model measured in `units` whose
+/// output was scaled by `scale`. Like `--scale-output 0.001` turning millimetres into metres, the
+/// scaled coordinates must be in one of the units 3MF supports, or this exits.
+fn three_mf_unit(units: Unit, scale: f64) -> &'static str {
+    const THREE_MF_UNITS: [(&str, f64); 6] = [
+        ("micron", 0.001), ("millimeter", 1.0), ("centimeter", 10.0),
+        ("inch", 25.4), ("foot", 304.8), ("meter", 1000.0),
+    ];
+
+    let millimetres = units.in_millimetres() / scale;
+    THREE_MF_UNITS.iter()
+        .find(|(_, size)| ((millimetres - size) / size).abs() < 1e-9)
+        .map(|(name, _)| *name)
+        .unwrap_or_else(|| abort_with_usage_error(
+            ErrorKind::ValueValidation,
+            &format!("3MF files must be measured in a standard unit, but scaling {} by {scale} gives a unit of {millimetres}mm - choose a `--scale-output` which gives microns, millimetres, centimetres, inches, feet or metres", units.name()),
+        ))
+}
+
+/// Build a source file, exiting if it has errors. Warnings, and the profile if profiling is
+/// enabled, are printed to stderr so that they don't mix with any other output.
+fn build_or_abort(source: InputSource, options: BuildOptions, error_format: ErrorFormat) -> BuiltModel {
+    let model = build_with_options(source, options)
+        .unwrap_or_else(|error| abort_with_lang_error(error, error_format));
+
+    for warning in model.warnings() {
+        match error_format {
+            ErrorFormat::Fancy => eprintln!("{:?}", miette::Report::new(warning.clone())),
+            ErrorFormat::Short => eprintln!("{}", render_plain(warning).short()),
+        }
+    }
+
+    if let Some(profile) = model.profile() {
+        eprintln!("{}", format_profile(profile));
+    }
+    model
+}
+
+/// Write each group as its own solid. Unnamed geometry gets a default name.
+fn write_stl_groups(groups: &[SceneGroup], output: &Path, encoding: StlEncoding, rescale: f64, fingerprint: BuildFingerprint) {
+    let mut file = BufWriter::new(File::create(output).unwrap());
+
+    let solids = groups.iter()
+        .map(|group| (group.name.as_deref().unwrap_or("YASCADExport"), group.manifold.meshgl()))
+        .collect::<Vec<_>>();
+    match encoding {
+        StlEncoding::Text(stl_options) => {
+            // Still write a (blank) solid if there's no geometry at all
+            if groups.is_empty() {
+                write_stl_from_meshgl(&Manifold::new().meshgl(), &text_solid_name("YASCADExport", rescale, fingerprint), &mut file, &stl_options).unwrap();
+            }
+
+            for (name, mesh) in &solids {
+                check_export_precision(mesh, stl_options.precision, name);
+                write_stl_from_meshgl(mesh, &text_solid_name(name, rescale, fingerprint), &mut file, &stl_options).unwrap();
+            }
+        }
+        StlEncoding::Binary => {
+            for (name, mesh) in &solids {
+                check_export_precision(mesh, None, name);
+            }
+
+            let meshes = solids.into_iter().map(|(_, mesh)| mesh).collect::<Vec<_>>();
+            let header = fingerprint.binary_stl_header(&solid_name("YASCADExport", rescale));
+            write_binary_stl_from_meshgls(&meshes, &header, &mut file).unwrap();
+        }
+    }
+}
+
+fn write_3mf_groups(groups: &[SceneGroup], unit: &str, output: &Path, fingerprint: BuildFingerprint) {
+    let meshes = groups.iter()
+        .map(|group| group.manifold.meshgl())
+        .collect::<Vec<_>>();
+    for (group, mesh) in zip(groups, &meshes) {
+        check_export_precision(mesh, None, group.name.as_deref().unwrap_or("YASCADExport"));
+    }
+
+    let objects = zip(groups, &meshes)
+        .map(|(group, mesh)| ThreeMfObject { name: group.name.as_deref(), mesh })
+        .collect::<Vec<_>>();
+
+    let application = format!("YASCAD {}", fingerprint.version);
+    let description = format!("Built from source with SHA-256 hash {}", fingerprint.source_sha256());
+    let metadata = [("Application", application.as_str()), ("Description", description.as_str())];
+
+    let mut file = BufWriter::new(File::create(output).unwrap());
+    write_3mf(&objects, unit, &metadata, &mut file).unwrap();
+}
+
+fn write_split_parts(out_dir: &Path, parts: Vec<ScenePart>, encoding: StlEncoding, rescale: f64, fingerprint: BuildFingerprint) {
+    fs::create_dir_all(out_dir).unwrap();
+
+    // Pad part numbers so that the files sort correctly
+    let digits = parts.len().to_string().len().max(2);
+
+    let mut manifest = PartsManifest {
+        source_sha256: fingerprint.source_sha256(),
+        version: fingerprint.version,
+        parts: vec![],
+    };
+    for (i, part) in parts.into_iter().enumerate() {
+        let file_name = format!("part_{:0digits$}.stl", i + 1);
+
+        let mut file = BufWriter::new(File::create(out_dir.join(&file_name)).unwrap());
+        let name = format!("YASCADPart{}", i + 1);
+        let mesh = part.manifold.meshgl();
+        match encoding {
+            StlEncoding::Text(stl_options) => {
+                check_export_precision(&mesh, stl_options.precision, &name);
+                write_stl_from_meshgl(&mesh, &text_solid_name(&name, rescale, fingerprint), &mut file, &stl_options).unwrap();
+            }
+            StlEncoding::Binary => {
+                check_export_precision(&mesh, None, &name);
+                let header = fingerprint.binary_stl_header(&solid_name(&name, rescale));
+                write_binary_stl_from_meshgls(slice::from_ref(&mesh), &header, &mut file).unwrap();
+            }
+        }
+
+        manifest.parts.push(PartsManifestEntry {
+            file: file_name,
+            source: part.provenance.source.origin().name(),
+            line: part.provenance.start_line(),
+        });
+    }
+
+    let manifest_file = File::create(out_dir.join("manifest.json")).unwrap();
+    serde_json::to_writer_pretty(manifest_file, &manifest).unwrap();
+}
+
+fn write_slice(model: &Manifold, z: f64, output: &Path) {
+    let bounding_box = model.bounding_box();
+    let (min_z, max_z) = (bounding_box.min_point().z, bounding_box.max_point().z);
+    if !(min_z..=max_z).contains(&z) {
+        abort_with_usage_error(ErrorKind::ValueValidation, &format!(
+            "`--slice-z {z}` is outside of the model, which spans from Z={min_z} to Z={max_z}"
+        ));
+    }
+
+    let mut file = BufWriter::new(File::create(output).unwrap());
+    write_svg(&model.slice(z), &mut file).unwrap();
+}
+
+fn convert_openscad(input: &Path, output: Option<&Path>, error_format: ErrorFormat) {
+    let source = InputSource::new_file(input).unwrap();
+
+    match openscad::convert_to_source(Rc::new(source)) {
+        Ok(converted) => {
+            if let Some(output) = output {
+                fs::write(output, converted).unwrap();
+            } else {
+                print!("{converted}");
+            }
+        },
+        Err(errors) => abort_with_errors(errors, error_format),
+    }
+}
+
+fn print_builtins(json: bool) {
+    if !json {
+        print!("{}", format_builtin_reference(all_builtins()));
+        return;
+    }
+
+    let entries = all_builtins()
+        .map(|builtin| BuiltinReferenceEntry {
+            name: builtin.name,
+            kind: builtin.kind.describe(),
+            summary: builtin.summary,
+            parameters: builtin.parameters.into_iter()
+                .map(|parameter| BuiltinReferenceParameter {
+                    name: parameter.name,
+                    summary: parameter.summary,
+                    default: parameter.default,
+                    named_only: parameter.named_only,
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+}
+
+fn abort_with_usage_error(kind: ErrorKind, message: &str) -> ! {
+    Args::command().error(kind, message).exit()
+}
+
+fn abort_with_lang_error(error: LangError, error_format: ErrorFormat) -> ! {
+    match error {
+        LangError::Tokenize(errors) => abort_with_errors(errors, error_format),
+        LangError::Parser(errors) => abort_with_errors(errors, error_format),
+        LangError::Runtime(error) => abort_with_errors(vec![error], error_format),
+        LangError::Warnings(warnings) => {
+            eprintln!("Failing because of warnings, as `--deny-warnings` was given");
+            abort_with_errors(warnings, error_format)
+        },
+    }
+}
+
+fn abort_with_errors<E: Diagnostic + SourceDiagnostic + Send + Sync + 'static>(errors: Vec<E>, error_format: ErrorFormat) -> ! {
     for error in errors {
-        println!("{:?}", miette::Report::new(error));
+        match error_format {
+            ErrorFormat::Fancy => println!("{:?}", miette::Report::new(error)),
+            ErrorFormat::Short => println!("{}", render_plain(&error).short()),
+        }
     }
     exit(1);
 }
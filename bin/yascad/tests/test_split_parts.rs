@@ -0,0 +1,60 @@
+use std::{fs, path::Path, process::Command};
+
+/// The lowest and highest coordinates of any vertex in a text STL file.
+fn stl_bounds(path: &Path) -> ([f64; 3], [f64; 3]) {
+    let stl = fs::read_to_string(path).unwrap();
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for vertex in stl.lines().filter_map(|line| line.trim().strip_prefix("vertex ")) {
+        for (axis, value) in vertex.split_whitespace().enumerate() {
+            let value = value.parse::<f64>().unwrap();
+            min[axis] = min[axis].min(value);
+            max[axis] = max[axis].max(value);
+        }
+    }
+    (min, max)
+}
+
+#[test]
+fn test_split_parts() {
+    let dir = std::env::temp_dir().join(format!("yascad-test-split-parts-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("model.yascad"), "
+        cube(10);
+
+        translate([20, 0, 0])
+        cube([5, 6, 7]);
+
+        translate([0, 30, 0])
+        cube(2);
+    ").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_yascad"))
+        .arg("-i").arg(dir.join("model.yascad"))
+        .arg("--split-parts").arg(dir.join("parts"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Each part is written to its own file, and nothing else
+    let parts = dir.join("parts");
+    let mut files = fs::read_dir(&parts).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, vec!["manifest.json", "part_01.stl", "part_02.stl", "part_03.stl"]);
+
+    assert_eq!(stl_bounds(&parts.join("part_01.stl")), ([0.0, 0.0, 0.0], [10.0, 10.0, 10.0]));
+    assert_eq!(stl_bounds(&parts.join("part_02.stl")), ([20.0, 0.0, 0.0], [25.0, 6.0, 7.0]));
+    assert_eq!(stl_bounds(&parts.join("part_03.stl")), ([0.0, 30.0, 0.0], [2.0, 32.0, 2.0]));
+
+    // The manifest says which line of the source each file came from
+    let manifest = serde_json::from_str::<serde_json::Value>(&fs::read_to_string(parts.join("manifest.json")).unwrap()).unwrap();
+    let entries = manifest["parts"].as_array().unwrap().iter()
+        .map(|part| (part["file"].as_str().unwrap(), part["line"].as_u64().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![("part_01.stl", 2), ("part_02.stl", 4), ("part_03.stl", 7)]);
+    assert!(manifest["parts"][0]["source"].as_str().unwrap().ends_with("model.yascad"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,29 +1,459 @@
-use std::{fs::File, path::PathBuf};
+use std::{collections::BTreeMap, fs::File, path::{Path, PathBuf}, sync::Mutex, time::Duration};
 
-use manifold_rs::ext::MeshGLExt;
 use miette::Diagnostic;
-use yascad_lang::{build_model, InputSource, LangError};
+use serde::Serialize;
+use serde_json::Value;
+use session::Session;
+use tauri::{AppHandle, Emitter, Manager};
+use yascad_lang::{build_with_options, BuildOptions, Centering, ExportPlacement, FileAccessPolicy, InputSource, LangError, MeshGL, PLA_DENSITY, StepCapture, UpAxis, export::{MeshGLExt, TextStlOptions, write_stl_from_meshgl}};
 
+mod session;
+
+/// Steps beyond this many only have a summary, to limit memory usage.
+const MAX_STEP_MESHES: usize = 100;
+
+/// The longest any single operator can take while building the preview, so that pathological
+/// geometry produces an error rather than freezing the preview.
+const PREVIEW_OPERATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Roughly the most memory the preview's geometry can take up, so that a huge model produces an
+/// error rather than taking the whole app down when it runs out.
+const PREVIEW_MEMORY_BUDGET: usize = 4_000_000_000;
+
+/// Edges where faces meet at more than this many degrees are shaded sharply in the preview.
+const PREVIEW_SHARP_ANGLE: f32 = 60.0;
+
+/// How long the editor must be left alone before its session is autosaved, so that typing doesn't
+/// write a file for every keystroke.
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct RenderedPreview {
+    #[serde(flatten)]
+    mesh: RenderedMesh,
+
+    /// Geometry from patterns, which is drawn by repeating one mesh rather than being included in
+    /// `mesh`.
+    instances: Vec<RenderedInstances>,
+
+    /// Every part of the model, in the same order each time the same source is rendered.
+    parts: Vec<RenderedPart>,
+
+    /// Roughly how many bytes of memory the model's geometry took up, and the most it's allowed,
+    /// for showing how close the model is to the limit.
+    memory_estimate: usize,
+    memory_budget: usize,
+
+    /// If requested, which primitive each triangle of `mesh` came from, as an index into
+    /// `sources`. Triangles which can't be traced to a primitive are `null`.
+    triangle_sources: Option<Vec<Option<usize>>>,
+
+    /// Every primitive which `triangle_sources` refers to, as a 1-indexed line and column.
+    sources: Vec<RenderedSource>,
+}
+
+#[derive(Serialize, PartialEq)]
+struct RenderedSource {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Serialize)]
+struct RenderedMesh {
+    stl: String,
+
+    /// A normal for each corner of each triangle in the STL, in the same order, flattened into
+    /// x/y/z components. STLs can only describe flat faces, so these make curves look smooth.
+    normals: Vec<f32>,
+}
+
+#[derive(Serialize)]
+struct RenderedInstances {
+    #[serde(flatten)]
+    mesh: RenderedMesh,
+
+    /// Where to draw each copy of the mesh, as column-major 4x4 matrices.
+    matrices: Vec<[f64; 16]>,
+
+    /// The [`RenderedPart::part_id`] of the part which this is.
+    part_id: String,
+}
+
+#[derive(Serialize)]
+struct RenderedPart {
+    /// Identifies the part across renders, as long as the code which produced it isn't edited.
+    /// This is a string because it wouldn't fit exactly in a JavaScript number.
+    part_id: String,
+
+    /// The name given to this part by `part`, if any.
+    name: Option<String>,
+
+    /// Where the part came from in the source, as a 1-indexed line and column.
+    line: usize,
+    column: usize,
+}
+
+/// Format a part's ID for the frontend.
+fn part_id(id: u64) -> String {
+    format!("{id:016x}")
+}
+
+impl RenderedMesh {
+    fn new(mesh: &MeshGL, name: &str) -> Self {
+        let mut stl_bytes = vec![];
+        write_stl_from_meshgl(mesh, name, &mut stl_bytes, &TextStlOptions::default()).unwrap();
+        let stl = String::from_utf8(stl_bytes).unwrap();
+
+        let normals = mesh.compute_corner_normals(PREVIEW_SHARP_ANGLE)
+            .into_iter()
+            .flat_map(|normal| [normal.x, normal.y, normal.z])
+            .collect();
+
+        Self { stl, normals }
+    }
+}
+
+/// Create a source from the editor's contents. If the editor has a file open, `path` is its path,
+/// so that relative paths in the code can be resolved like they would be for the saved file.
+fn editor_source(code: &str, path: Option<&str>) -> InputSource {
+    match path.and_then(|path| Path::new(path).parent()) {
+        Some(base_dir) => InputSource::new_string_with_base(code.to_owned(), base_dir),
+        None => InputSource::new_string(code.to_owned()),
+    }
+}
+
+/// Which files the editor's code may read. Only files within the open file's directory are allowed,
+/// and none at all if the code hasn't been saved.
+fn editor_file_access(path: Option<&str>) -> FileAccessPolicy {
+    match path.and_then(|path| Path::new(path).parent()) {
+        Some(base_dir) => FileAccessPolicy::AllowList(vec![base_dir.to_owned()]),
+        None => FileAccessPolicy::DenyAll,
+    }
+}
+
+/// Parse the axis which should point upwards, as passed to a command. Defaults to Z-up.
+fn up_axis(up: Option<&str>) -> Result<UpAxis, String> {
+    match up {
+        Some(name) => UpAxis::from_name(name).ok_or_else(|| format!("unknown up axis \"{name}\"")),
+        None => Ok(UpAxis::default()),
+    }
+}
+
+/// Build a preview of the model. If `trace_sources` is true, the preview also says which primitive
+/// each triangle came from - see [`RenderedPreview::triangle_sources`].
 #[tauri::command]
-fn render_preview(code: &str) -> Result<String, String> {
-    let source = InputSource::new_string(code.to_owned());
+fn render_preview(code: &str, path: Option<&str>, up: Option<&str>, trace_sources: Option<bool>) -> Result<RenderedPreview, String> {
+    let source = editor_source(code, path);
+    let up = up_axis(up)?;
 
-    let res = build_model(source);
-    println!("{res:?}");
-    match res {
+    // The preview doesn't need to be as accurate as an export
+    let options = BuildOptions {
+        operation_timeout: Some(PREVIEW_OPERATION_TIMEOUT),
+        memory_budget: Some(PREVIEW_MEMORY_BUDGET),
+        file_access: editor_file_access(path),
+        track_originals: trace_sources.unwrap_or(false),
+        ..BuildOptions::preview()
+    };
+    match build_with_options(source, options) {
         Ok(model) => {
-            let stl = model.meshgl().to_stl("YASCADPreview");
+            let preview = model.preview();
+
+            // Each instance is placed by its matrix, so converting the matrix is enough
+            let instances = preview.instances.iter()
+                .map(|instances| RenderedInstances {
+                    mesh: RenderedMesh::new(&instances.source.meshgl(), "YASCADPreviewInstance"),
+                    matrices: instances.transforms.iter()
+                        .map(|transform| transform.then(&up.transform()).to_column_major_4x4())
+                        .collect(),
+                    part_id: part_id(instances.id),
+                })
+                .collect();
+
+            let parts = preview.parts.iter()
+                .map(|part| RenderedPart {
+                    part_id: part_id(part.id),
+                    name: part.name.clone(),
+                    line: part.provenance.start_line(),
+                    column: part.provenance.start_column(),
+                })
+                .collect();
+
+            let memory_estimate = model.estimated_memory();
+
+            let mesh = up.apply(&preview.manifold).meshgl();
+
+            let mut sources = vec![];
+            let triangle_sources = trace_sources.unwrap_or(false).then(|| {
+                model.triangle_provenance(&mesh)
+                    .into_iter()
+                    .map(|provenance| provenance.map(|provenance| {
+                        let source = RenderedSource { line: provenance.start_line(), column: provenance.start_column() };
+                        sources.iter().position(|existing| existing == &source).unwrap_or_else(|| {
+                            sources.push(source);
+                            sources.len() - 1
+                        })
+                    }))
+                    .collect()
+            });
+
+            Ok(RenderedPreview { mesh: RenderedMesh::new(&mesh, "YASCADPreview"), instances, parts, memory_estimate, memory_budget: PREVIEW_MEMORY_BUDGET, triangle_sources, sources })
+        }
+
+        Err(LangError::Tokenize(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Parser(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Runtime(error)) => Err(flatten_miette_errors(vec![error])),
+        Err(LangError::Warnings(warnings)) => Err(flatten_miette_errors(warnings)),
+    }
+}
+
+/// Roughly estimate the model's volume, mass and print time, assuming PLA, as text. This combines
+/// all of the model's geometry, including patterns which the preview draws as instances, so it's
+/// only done when asked for rather than with every preview.
+#[tauri::command]
+fn render_print_estimate(code: &str, path: Option<&str>) -> Result<String, String> {
+    let source = editor_source(code, path);
+    let options = BuildOptions {
+        operation_timeout: Some(PREVIEW_OPERATION_TIMEOUT),
+        memory_budget: Some(PREVIEW_MEMORY_BUDGET),
+        file_access: editor_file_access(path),
+        ..BuildOptions::preview()
+    };
+
+    match build_with_options(source, options) {
+        Ok(model) => Ok(model.print_estimate(PLA_DENSITY).to_string()),
+
+        Err(LangError::Tokenize(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Parser(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Runtime(error)) => Err(flatten_miette_errors(vec![error])),
+        Err(LangError::Warnings(warnings)) => Err(flatten_miette_errors(warnings)),
+    }
+}
+
+/// Build an STL of the whole model, at the same quality as the preview. If `center` is given, the
+/// model is moved to the origin first - see [`Centering::from_name`]. If `scale` is given, every
+/// coordinate in the STL is multiplied by it.
+#[tauri::command]
+fn export_stl(code: &str, path: Option<&str>, up: Option<&str>, center: Option<&str>, scale: Option<f64>) -> Result<String, String> {
+    let source = editor_source(code, path);
+    let placement = ExportPlacement {
+        centering: center
+            .map(|name| Centering::from_name(name).ok_or_else(|| format!("unknown centering \"{name}\"")))
+            .transpose()?,
+        scale: match scale {
+            Some(scale) if scale > 0.0 && scale.is_finite() => scale,
+            Some(scale) => return Err(format!("output scale must be a positive number, got {scale}")),
+            None => 1.0,
+        },
+        up: up_axis(up)?,
+    };
+
+    let options = BuildOptions { file_access: editor_file_access(path), ..BuildOptions::preview() };
+    match build_with_options(source, options) {
+        Ok(model) => {
+            let mut manifold = model.manifold();
+            if let Some(offset) = placement.apply(vec![&mut manifold]) {
+                log::info!("moved the model by {},{},{}", offset.x, offset.y, offset.z);
+            }
+            if placement.scale != 1.0 {
+                log::info!("scaled the output by {}", placement.scale);
+            }
+
             let mut stl_bytes = vec![];
-            stl.write_text_stl(&mut stl_bytes).unwrap();
-            let stl_text = String::from_utf8(stl_bytes).unwrap();
+            let name = format!("YASCADPreview {}", model.fingerprint());
+            write_stl_from_meshgl(&manifold.meshgl(), &name, &mut stl_bytes, &TextStlOptions::default()).unwrap();
+            Ok(String::from_utf8(stl_bytes).unwrap())
+        }
+
+        Err(LangError::Tokenize(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Parser(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Runtime(error)) => Err(flatten_miette_errors(vec![error])),
+        Err(LangError::Warnings(warnings)) => Err(flatten_miette_errors(warnings)),
+    }
+}
+
+/// One operator application, for the step-through timeline.
+#[derive(Serialize)]
+struct RenderedStep {
+    operator: String,
+    line: usize,
+    column: usize,
+
+    /// An STL of the geometry at this step, if it was captured.
+    stl: Option<String>,
+}
+
+#[tauri::command]
+fn render_steps(code: &str, path: Option<&str>) -> Result<Vec<RenderedStep>, String> {
+    let source = editor_source(code, path);
+    let options = BuildOptions {
+        step_capture: StepCapture::Mesh { max_meshes: MAX_STEP_MESHES },
+        file_access: editor_file_access(path),
+        ..BuildOptions::preview()
+    };
+
+    match build_with_options(source, options) {
+        Ok(model) => {
+            let steps = model.steps().iter()
+                .map(|step| {
+                    let stl = step.mesh.as_ref().map(|mesh| {
+                        let mut stl_bytes = vec![];
+                        write_stl_from_meshgl(mesh, "YASCADStep", &mut stl_bytes, &TextStlOptions::default()).unwrap();
+                        String::from_utf8(stl_bytes).unwrap()
+                    });
+
+                    RenderedStep {
+                        operator: step.operator.clone(),
+                        line: step.span.start_line(),
+                        column: step.span.start_column(),
+                        stl,
+                    }
+                })
+                .collect();
 
-            Ok(stl_text)
+            Ok(steps)
         }
 
         Err(LangError::Tokenize(errors)) => Err(flatten_miette_errors(errors)),
         Err(LangError::Parser(errors)) => Err(flatten_miette_errors(errors)),
         Err(LangError::Runtime(error)) => Err(flatten_miette_errors(vec![error])),
+        Err(LangError::Warnings(warnings)) => Err(flatten_miette_errors(warnings)),
+    }
+}
+
+#[derive(Serialize)]
+struct RenderedProfileEntry {
+    what: String,
+    line: usize,
+    column: usize,
+    calls: usize,
+    milliseconds: f64,
+    geometry_operations: usize,
+}
+
+/// Build the code at full quality, timing each part of it. Entries are sorted slowest first.
+#[tauri::command]
+fn render_profile(code: &str, path: Option<&str>) -> Result<Vec<RenderedProfileEntry>, String> {
+    let source = editor_source(code, path);
+    let options = BuildOptions { profile: true, file_access: editor_file_access(path), ..BuildOptions::default() };
+
+    match build_with_options(source, options) {
+        Ok(model) => {
+            let profile = model.profile().expect("profiling was enabled");
+            let entries = profile.entries_by_duration().into_iter()
+                .map(|entry| RenderedProfileEntry {
+                    what: entry.kind.to_string(),
+                    line: entry.span.start_line(),
+                    column: entry.span.start_column(),
+                    calls: entry.count,
+                    milliseconds: entry.duration.as_secs_f64() * 1000.0,
+                    geometry_operations: entry.geometry_operations,
+                })
+                .collect();
+
+            Ok(entries)
+        }
+
+        Err(LangError::Tokenize(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Parser(errors)) => Err(flatten_miette_errors(errors)),
+        Err(LangError::Runtime(error)) => Err(flatten_miette_errors(vec![error])),
+        Err(LangError::Warnings(warnings)) => Err(flatten_miette_errors(warnings)),
+    }
+}
+
+/// Collect the editor's state into a session. `metadata_json` is stored as-is for the frontend.
+fn editor_session(code: String, parameters: Option<BTreeMap<String, Value>>, metadata_json: &str) -> Result<Session, String> {
+    let metadata = serde_json::from_str(metadata_json).map_err(|e| format!("invalid session metadata: {e}"))?;
+    Ok(Session { code, parameters: parameters.unwrap_or_default(), metadata })
+}
+
+#[tauri::command]
+fn save_session(path: &str, code: String, parameters: Option<BTreeMap<String, Value>>, metadata_json: &str) -> Result<(), String> {
+    session::save(Path::new(path), &editor_session(code, parameters, metadata_json)?)
+}
+
+#[tauri::command]
+fn load_session(path: &str) -> Result<Session, String> {
+    session::load(Path::new(path))
+}
+
+/// Counts autosave requests, so that a pending autosave can tell whether a newer one has replaced
+/// it.
+#[derive(Default)]
+struct AutosaveState {
+    latest: Mutex<u64>,
+}
+
+fn autosave_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("autosave.json"))
+}
+
+/// Save the session to the platform's data directory, once there have been no more calls for
+/// [`AUTOSAVE_DELAY`]. Restore it with [`load_autosave`].
+#[tauri::command]
+fn autosave(app: AppHandle, code: String, parameters: Option<BTreeMap<String, Value>>, metadata_json: &str) -> Result<(), String> {
+    let session = editor_session(code, parameters, metadata_json)?;
+    let generation = {
+        let mut latest = app.state::<AutosaveState>().latest.lock().unwrap();
+        *latest += 1;
+        *latest
+    };
+
+    std::thread::spawn(move || {
+        std::thread::sleep(AUTOSAVE_DELAY);
+        if *app.state::<AutosaveState>().latest.lock().unwrap() != generation {
+            return;
+        }
+
+        match autosave_path(&app).and_then(|path| session::save(&path, &session)) {
+            Ok(()) => log::debug!("autosaved session"),
+            Err(e) => log::warn!("autosave failed: {e}"),
+        }
+    });
+    Ok(())
+}
+
+/// The last autosaved session, if there is one.
+#[tauri::command]
+fn load_autosave(app: AppHandle) -> Result<Option<Session>, String> {
+    let path = autosave_path(&app)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    session::load(&path).map(Some)
+}
+
+#[derive(Serialize, Clone)]
+struct LogEvent {
+    level: String,
+    target: String,
+    message: String,
+}
+
+/// Forwards log records to the frontend as `log` events, so that they appear in the webview's
+/// console - release builds have no terminal to print them to.
+struct EventLogger {
+    app: AppHandle,
+}
+
+impl log::Log for EventLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // Tauri's own logging could end up back here while emitting
+        metadata.target().starts_with("yascad") || metadata.target().starts_with("manifold_rs")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let _ = self.app.emit("log", LogEvent {
+            level: record.level().to_string(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+        });
     }
+
+    fn flush(&self) {}
 }
 
 fn flatten_miette_errors<E: Diagnostic + Send + Sync + 'static>(errors: Vec<E>) -> String {
@@ -37,10 +467,19 @@ fn flatten_miette_errors<E: Diagnostic + Send + Sync + 'static>(errors: Vec<E>)
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        .manage(AutosaveState::default())
+        .setup(|app| {
+            log::set_boxed_logger(Box::new(EventLogger { app: app.handle().clone() }))?;
+            log::set_max_level(if cfg!(debug_assertions) { log::LevelFilter::Debug } else { log::LevelFilter::Info });
+            Ok(())
+        })
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![render_preview])
+        .invoke_handler(tauri::generate_handler![
+            render_preview, render_print_estimate, render_steps, render_profile, export_stl,
+            save_session, load_session, autosave, load_autosave,
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
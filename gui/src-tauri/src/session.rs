@@ -0,0 +1,117 @@
+//! Saving the editor's state to a file, so that it can be picked up again after a restart.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The version of the session file format written by this build.
+///
+/// Bump this whenever [`Session`] changes in a way older files can't be read as, and add a step to
+/// [`migrate`] which upgrades files from the previous version.
+pub const SESSION_VERSION: u64 = 1;
+
+/// Everything needed to restore the editor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// The model source in the editor, which may not have been saved anywhere else.
+    pub code: String,
+
+    /// Values given to the model's parameters by the customizer, overriding those in the code.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Value>,
+
+    /// Anything else which the frontend wants back, like the camera position. This is opaque here.
+    #[serde(default)]
+    pub metadata: Value,
+}
+
+#[derive(Serialize)]
+struct SessionFile<'a> {
+    version: u64,
+
+    #[serde(flatten)]
+    session: &'a Session,
+}
+
+pub fn save(path: &Path, session: &Session) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&SessionFile { version: SESSION_VERSION, session })
+        .map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("could not create {}: {e}", parent.display()))?;
+    }
+    fs::write(path, json).map_err(|e| format!("could not write {}: {e}", path.display()))
+}
+
+pub fn load(path: &Path) -> Result<Session, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("could not read {}: {e}", path.display()))?;
+    parse(&json)
+}
+
+/// Read the contents of a session file, upgrading it first if it came from an older version.
+pub fn parse(json: &str) -> Result<Session, String> {
+    let file: Value = serde_json::from_str(json).map_err(|e| format!("not a session file: {e}"))?;
+    let version = file.get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| "not a session file: no version".to_owned())?;
+
+    let file = migrate(file, version)?;
+    serde_json::from_value(file).map_err(|e| format!("invalid session file: {e}"))
+}
+
+/// Upgrade a session file from `version` to [`SESSION_VERSION`].
+fn migrate(file: Value, version: u64) -> Result<Value, String> {
+    match version {
+        SESSION_VERSION => Ok(file),
+
+        // Future versions add an arm here for the previous version, which updates `file` and
+        // recurses with `version + 1`
+        _ if version > SESSION_VERSION =>
+            Err(format!("this session was saved by a newer version of YASCAD (format version {version})")),
+        _ => Err(format!("unknown session format version {version}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, env, fs};
+
+    use serde_json::json;
+
+    use super::{Session, load, parse, save};
+
+    #[test]
+    fn test_round_trip() {
+        let session = Session {
+            code: "// Größe in mm – ünïcødé ✓\ncube([10, 20, 30]);\n".to_owned(),
+            parameters: BTreeMap::from([
+                ("width".to_owned(), json!(12.5)),
+                ("label".to_owned(), json!("Деталь")),
+            ]),
+            metadata: json!({ "camera": { "position": [1, 2, 3], "zoom": 1.5 } }),
+        };
+
+        let path = env::temp_dir().join(format!("yascad-session-test-{}", std::process::id())).join("session.json");
+        save(&path, &session).unwrap();
+        let loaded = load(&path);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(loaded, Ok(session));
+    }
+
+    #[test]
+    fn test_missing_fields_default() {
+        let session = parse(r#"{ "version": 1, "code": "sphere(5);" }"#).unwrap();
+        assert_eq!(session, Session { code: "sphere(5);".to_owned(), parameters: BTreeMap::new(), metadata: json!(null) });
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(
+            parse(r#"{ "version": 2, "code": "" }"#),
+            Err("this session was saved by a newer version of YASCAD (format version 2)".to_owned()),
+        );
+        assert_eq!(parse(r#"{ "version": 0, "code": "" }"#), Err("unknown session format version 0".to_owned()));
+        assert_eq!(parse(r#"{ "code": "" }"#), Err("not a session file: no version".to_owned()));
+    }
+}
@@ -1,6 +1,6 @@
 import { useCallback, useRef, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import RenderCanvas from "./components/RenderCanvas";
+import RenderCanvas, { Preview } from "./components/RenderCanvas";
 import { save } from "@tauri-apps/plugin-dialog";
 import { writeTextFile } from "@tauri-apps/plugin-fs";
 import useKeyboardShortcut from "./hooks/useKeyboardShortcut";
@@ -9,29 +9,43 @@ import ModelEditor from "./components/ModelEditor";
 import { PanelGroup, Panel, PanelResizeHandle } from "react-resizable-panels";
 
 function App() {
-  const [lastStl, setLastStl] = useState("");
+  const [lastPreview, setLastPreview] = useState<Preview | undefined>(undefined);
   const [stlError, setStlError] = useState<string | null>(null);
   const [stlDirty, setStlDirty] = useState(true);
+  const [printEstimate, setPrintEstimate] = useState<string | null>(null);
 
   const editorRef = useRef<editor.IStandaloneCodeEditor | null>(null);
+  const pathRef = useRef<string | null>(null);
+  const editorPathChange = useCallback((path: string | null) => {
+    pathRef.current = path;
+  }, []);
+
   function editorChange(editor: editor.IStandaloneCodeEditor) {
     if (!editorRef.current) {
       editorRef.current = editor;
     }
 
     setStlDirty(true);
+    setPrintEstimate(null);
+
+    // Debounced by the backend, so this is fine to call on every change
+    invoke("autosave", {
+      code: editor.getValue(),
+      metadataJson: JSON.stringify({ path: pathRef.current }),
+    }).catch((e) => console.error("autosave failed:", e));
   }  
 
   const resetModelEditorState = useCallback(() => {
-    setLastStl("");
+    setLastPreview(undefined);
     setStlError(null);
     setStlDirty(true);
+    setPrintEstimate(null);
   }, []);
 
   const renderPreview = useCallback(async () => {
     const code = editorRef.current!.getValue();
     try {
-      setLastStl(await invoke("render_preview", { code }));
+      setLastPreview(await invoke<Preview>("render_preview", { code, path: pathRef.current }));
     } catch (e) {
       setStlError(String(e));
       return;
@@ -40,6 +54,16 @@ function App() {
     setStlDirty(false);
   }, []);
 
+  // This combines all of the model's geometry, which is too slow to do for every preview
+  const estimatePrint = useCallback(async () => {
+    const code = editorRef.current!.getValue();
+    try {
+      setPrintEstimate(await invoke<string>("render_print_estimate", { code, path: pathRef.current }));
+    } catch (e) {
+      setStlError(String(e));
+    }
+  }, []);
+
   const exportStl = useCallback(async () => {
     const file = await save({
       filters: [
@@ -53,8 +77,10 @@ function App() {
       return;
     }
 
-    await writeTextFile(file, lastStl);
-  }, [lastStl]);
+    // The preview leaves out instanced geometry, so build the whole model
+    const code = editorRef.current!.getValue();
+    await writeTextFile(file, await invoke<string>("export_stl", { code, path: pathRef.current }));
+  }, []);
 
   useKeyboardShortcut({ key: "F5" }, renderPreview, [renderPreview]);
 
@@ -66,6 +92,7 @@ function App() {
             className="flex-1"
             onChange={editorChange}
             onReset={resetModelEditorState}
+            onPathChange={editorPathChange}
           />
           <div className="flex flex-row p-[5px] gap-[5px]">
             <button className="flex-2" onClick={renderPreview}>Render (F5)</button>
@@ -78,8 +105,18 @@ function App() {
         <Panel className="flex flex-col" defaultSize={50}>
           <div id="output-model" className="flex-1 min-h-0">
             {/* Important: the canvas must remain mounted all the time */}
-            <RenderCanvas stl={lastStl} />
+            <RenderCanvas preview={lastPreview} />
           </div>
+
+          {lastPreview && (
+            <div id="output-stats" className="font-mono text-left text-sm px-[5px]">
+              {printEstimate ?? <button onClick={estimatePrint} disabled={stlDirty}>Estimate print</button>}
+              <div>
+                Memory: <meter min={0} max={lastPreview.memory_budget} value={lastPreview.memory_estimate} />
+                {" "}{(lastPreview.memory_estimate / 1_000_000).toFixed(1)} MB
+              </div>
+            </div>
+          )}
           
           <div id="output-messages" className={"font-mono text-left whitespace-break-spaces " + (stlError ? "flex-1" : "hidden")}>
             {stlError || "Build messages will be shown here."}
@@ -7,9 +7,10 @@ import { readTextFile, writeTextFile } from "@tauri-apps/plugin-fs";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import useKeyboardShortcut from "../hooks/useKeyboardShortcut";
 
-export default function ModelEditor({ onChange, onReset, ...props }: {
+export default function ModelEditor({ onChange, onReset, onPathChange, ...props }: {
   onChange: (editor: editor.IStandaloneCodeEditor) => any,
   onReset: () => any,
+  onPathChange: (path: string | null) => any,
 } & Omit<ComponentProps<"div">, "onChange">) {
   const editorRef = useRef<editor.IStandaloneCodeEditor | null>(null);
   function editorDidMount(editor: editor.IStandaloneCodeEditor, _: Monaco) {
@@ -29,6 +30,11 @@ export default function ModelEditor({ onChange, onReset, ...props }: {
 
   const [currentPath, setCurrentPath] = useState<string | null>(null);
 
+  // Relative paths in the model are resolved against the directory of the open file
+  useEffect(() => {
+    onPathChange(currentPath);
+  }, [currentPath, onPathChange]);
+
   // Update window state based on the state of the editor.
   // It's a bit cheeky to do this within the `ModelEditor` component, but the interface for this
   // editor would be a bit weird if we lifted state out.
@@ -1,18 +1,49 @@
 import { PerspectiveCamera, OrbitControls, Grid, Line } from "@react-three/drei";
 import { Canvas, useLoader } from "@react-three/fiber";
 import { STLLoader } from "three/examples/jsm/Addons.js";
-import { Suspense, useMemo } from "react";
+import { Suspense, useEffect, useMemo, useRef } from "react";
 import * as THREE from "three";
 
 // Make Z up
 THREE.Object3D.DEFAULT_UP.set(0, 0, 1);
 
-export default function RenderCanvas({ stl }: { stl: string | undefined }) {
-  // Three.js `STLLoader` throws an exception when an STL has no triangles.
-  //
-  // Guard against this with a completely rubbish heuristic, by checking for the text "facet normal"
-  // in the STL text, which appears for every triangle.
-  const stlHasTriangles = useMemo(() => stl?.includes("facet normal"), [stl])
+/** A mesh as an STL, with a normal for each triangle corner to make curves look smooth. */
+export type PreviewMesh = { stl: string, normals: number[] };
+
+/** A part of the model, which keeps its `part_id` across renders unless its code is edited. */
+export type PreviewPart = { part_id: string, name: string | null, line: number, column: number };
+
+/** The result of `render_preview`. */
+export type Preview = PreviewMesh & {
+  /** Meshes from patterns. Each is drawn once for each column-major matrix. */
+  instances: (PreviewMesh & { matrices: number[][], part_id: string })[],
+
+  /** Every part of the model, in a stable order. */
+  parts: PreviewPart[],
+
+  /** Roughly how many bytes of memory the model's geometry took up, and the most it's allowed. */
+  memory_estimate: number,
+  memory_budget: number,
+
+  /**
+   * Only if requested with `traceSources`: for each triangle of the mesh, the index in `sources` of
+   * the primitive it came from, or null if it can't be traced.
+   */
+  triangle_sources: (number | null)[] | null,
+
+  /** The primitives which `triangle_sources` refers to. */
+  sources: { line: number, column: number }[],
+};
+
+// Three.js `STLLoader` throws an exception when an STL has no triangles.
+//
+// Guard against this with a completely rubbish heuristic, by checking for the text "facet normal"
+// in the STL text, which appears for every triangle.
+function stlHasTriangles(stl: string) {
+  return stl.includes("facet normal");
+}
+
+export default function RenderCanvas({ preview }: { preview: Preview | undefined }) {
 
   return (
     <Canvas>
@@ -30,18 +61,51 @@ export default function RenderCanvas({ stl }: { stl: string | undefined }) {
         side={2 /* Double-sided */} />
       <Axes />
 
-      {stl && stlHasTriangles &&
+      {preview && stlHasTriangles(preview.stl) &&
         <Suspense>
-          <STL stl={stl} />
+          <STL mesh={preview} />
         </Suspense>
       }
+      {preview?.instances.filter(instances => stlHasTriangles(instances.stl)).map(instances =>
+        <Suspense key={instances.part_id}>
+          <STL mesh={instances} matrices={instances.matrices} />
+        </Suspense>
+      )}
     </Canvas>
   )
 }
 
-function STL({ stl }: { stl: string }) {
+function STL({ mesh: { stl, normals }, matrices }: { mesh: PreviewMesh, matrices?: number[][] }) {
   const stlDataUri = useMemo(() => `data:text/plain;base64,${btoa(stl)}`, [stl]);
   const stlAsset = useLoader(STLLoader, stlDataUri);
+  const instancedMeshRef = useRef<THREE.InstancedMesh>(null);
+
+  // `STLLoader` gives every triangle corner its own vertex, in the same order as the normals, so
+  // they can replace the flat per-face normals directly
+  useEffect(() => {
+    if (normals.length === stlAsset.getAttribute("position").array.length) {
+      stlAsset.setAttribute("normal", new THREE.Float32BufferAttribute(normals, 3));
+    }
+  }, [stlAsset, normals]);
+
+  useEffect(() => {
+    const instancedMesh = instancedMeshRef.current;
+    if (!instancedMesh || !matrices) {
+      return;
+    }
+
+    matrices.forEach((matrix, i) => instancedMesh.setMatrixAt(i, new THREE.Matrix4().fromArray(matrix)));
+    instancedMesh.instanceMatrix.needsUpdate = true;
+    instancedMesh.computeBoundingSphere();
+  }, [matrices]);
+
+  if (matrices) {
+    return (
+      <instancedMesh ref={instancedMeshRef} args={[stlAsset, undefined, matrices.length]}>
+        <meshStandardMaterial color="orange" />
+      </instancedMesh>
+    )
+  }
 
   return (
     <mesh geometry={stlAsset}>
@@ -1,7 +1,13 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
+import { listen } from "@tauri-apps/api/event";
 import App from "./App";
 
+// Log records forwarded from the backend, like the output of `__debug`
+type LogEvent = { level: "ERROR" | "WARN" | "INFO" | "DEBUG" | "TRACE", target: string, message: string };
+const consoleMethods = { ERROR: console.error, WARN: console.warn, INFO: console.info, DEBUG: console.debug, TRACE: console.debug };
+listen<LogEvent>("log", ({ payload }) => consoleMethods[payload.level](`[${payload.target}] ${payload.message}`));
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <App />
@@ -4,13 +4,14 @@
 export default {
   keywords: [
     // Language-level keywords
-    'operator', 'module', 'it', 'for', 'if', 'else', 'true', 'false', 'null', 'undef',
+    'operator', 'module', 'function', 'it', 'for', 'if', 'else', 'true', 'false', 'null', 'undef', 'units',
+    'include', 'use', 'set',
 
     // Not really keywords, but important/special enough to highlight like one
-    'buffer', 'children', 'copy',
+    'buffer', 'children', 'copy', 'part',
   ],
 
-  operators: ['=', '+', '-', '*', '/', '==', '<', '>', '<=', '>='],
+  operators: ['=', '+', '-', '*', '/', '%', '==', '<', '>', '<=', '>=', '&&', '||', '!', '?', ':'],
 
   symbols:  /[=><!~?:&|+\-*\/\^%]+/,
 
@@ -29,6 +30,10 @@ export default {
       [/@symbols/, { cases: { '@operators': 'operator',
                               '@default'  : '' } } ],
 
+      // strings
+      [/"([^"\\]|\\.)*"/, 'string'],
+      [/"([^"\\]|\\.)*$/, 'string.invalid'],
+
       // numbers
       [/\d*\.\d+([eE][\-+]?\d+)?/, 'number.float'],
       [/\d+/, 'number'],
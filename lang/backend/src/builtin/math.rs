@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use yascad_frontend::InputSourceSpan;
+
+use crate::{EvaluatedParameters, Interpreter, RuntimeError, RuntimeErrorKind, builtin::ModuleDefinition, object::Object};
+
+/// Defines a module which takes one number, `x`, and returns the result of `function` on it.
+macro_rules! unary_definition {
+    ($name:literal, $summary:literal, $x_doc:literal, $function:expr) => {
+        ModuleDefinition {
+            name: $name,
+            summary: $summary,
+            parameter_docs: &[("x", $x_doc)],
+            parameters: EvaluatedParameters::required(vec!["x".to_owned()]),
+            action: &|interpreter, arguments, _, span| {
+                let x = number_argument(interpreter, &arguments, "x", &span)?;
+                Ok(Object::Number($function(x)))
+            },
+        }
+    };
+}
+
+/// The sine of an angle in degrees.
+///
+/// Multiples of 30° give exact results, so that placing things around a circle doesn't leave them a
+/// rounding error away from where they'd be expected, like `cos(90)` not quite being 0.
+fn sin_degrees(degrees: f64) -> f64 {
+    let degrees = degrees.rem_euclid(360.0);
+    if degrees % 30.0 != 0.0 {
+        return degrees.to_radians().sin();
+    }
+
+    match degrees as u32 {
+        0 | 180 => 0.0,
+        30 | 150 => 0.5,
+        90 => 1.0,
+        210 | 330 => -0.5,
+        270 => -1.0,
+        _ => degrees.to_radians().sin(),
+    }
+}
+
+fn cos_degrees(degrees: f64) -> f64 {
+    sin_degrees(degrees + 90.0)
+}
+
+/// Gets an argument as a number, with an error pointing at the argument if it isn't one.
+fn number_argument(interpreter: &Interpreter, arguments: &HashMap<String, Object>, name: &str, span: &InputSourceSpan) -> Result<f64, RuntimeError> {
+    arguments[name].as_number(interpreter.argument_span(name, span))
+}
+
+/// Gets the argument `x` as a number from -1 to 1, for `asin` and `acos`.
+fn unit_argument(interpreter: &Interpreter, arguments: &HashMap<String, Object>, function: &str, span: &InputSourceSpan) -> Result<f64, RuntimeError> {
+    let x = number_argument(interpreter, arguments, "x", span)?;
+    if !(-1.0..=1.0).contains(&x) {
+        return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+            format!("{function} needs a number from -1 to 1, but got {x}")
+        ), interpreter.argument_span("x", span)));
+    }
+    Ok(x)
+}
+
+/// The numbers which `min` and `max` choose between - either two numbers, or one vector of them.
+fn extremum_candidates(interpreter: &Interpreter, arguments: &HashMap<String, Object>, function: &str, span: &InputSourceSpan) -> Result<Vec<f64>, RuntimeError> {
+    match (&arguments["a"], &arguments["b"]) {
+        (Object::Number(a), Object::Number(b)) => Ok(vec![*a, *b]),
+        (Object::Number(_), b) => Err(RuntimeError::new(
+            RuntimeErrorKind::IncorrectType { expected: "number".to_owned(), actual: b.describe_type() },
+            interpreter.argument_span("b", span),
+        )),
+
+        (vector @ Object::Vector(items, _), Object::Null) => {
+            if items.is_empty() {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                    format!("{function} needs at least one number, but the vector is empty")
+                ), interpreter.argument_span("a", span)));
+            }
+            (0..items.len())
+                .map(|index| vector.vector_element_as_number(index, &interpreter.argument_span("a", span)))
+                .collect()
+        },
+        (Object::Vector(..), _) => Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+            format!("{function} takes either two numbers or one vector of numbers, but got a vector and another argument")
+        ), interpreter.argument_span("b", span))),
+
+        (a, _) => Err(RuntimeError::new(
+            RuntimeErrorKind::IncorrectType { expected: "number or vector".to_owned(), actual: a.describe_type() },
+            interpreter.argument_span("a", span),
+        )),
+    }
+}
+
+fn extremum_parameters() -> EvaluatedParameters {
+    EvaluatedParameters::new(vec!["a".to_owned()], vec![("b".to_owned(), Object::Null)])
+}
+
+/// The names of all built-in numeric functions. These are modules which return a number, like any
+/// other.
+pub const BUILTIN_MATH_NAMES: &[&str] = &[
+    "sin", "cos", "tan", "asin", "acos", "atan", "atan2", "sqrt", "abs", "floor", "ceil", "round",
+    "pow", "min", "max",
+];
+
+/// Get the implementation for a specific built-in numeric function. Angles are in degrees, like the
+/// rest of the language.
+///
+/// Returns [`None`] if no such function exists.
+pub fn get_builtin_math_module(name: &str) -> Option<ModuleDefinition> {
+    match name {
+        "sin" => Some(unary_definition!("sin", "The sine of an angle.", "Angle in degrees.", sin_degrees)),
+        "cos" => Some(unary_definition!("cos", "The cosine of an angle.", "Angle in degrees.", cos_degrees)),
+        "tan" => Some(unary_definition!("tan", "The tangent of an angle.", "Angle in degrees.", |x| sin_degrees(x) / cos_degrees(x))),
+        "atan" => Some(unary_definition!("atan", "The angle whose tangent is a number, in degrees.", "The tangent.", |x: f64| x.atan().to_degrees())),
+        "abs" => Some(unary_definition!("abs", "The absolute value of a number.", "The number.", f64::abs)),
+        "floor" => Some(unary_definition!("floor", "Rounds a number down to a whole number.", "The number.", f64::floor)),
+        "ceil" => Some(unary_definition!("ceil", "Rounds a number up to a whole number.", "The number.", f64::ceil)),
+        "round" => Some(unary_definition!("round", "Rounds a number to the nearest whole number, with halves rounded away from zero.", "The number.", f64::round)),
+
+        "asin" => Some(ModuleDefinition {
+            name: "asin",
+            summary: "The angle whose sine is a number, in degrees.",
+            parameter_docs: &[("x", "The sine, from -1 to 1.")],
+            parameters: EvaluatedParameters::required(vec!["x".to_owned()]),
+            action: &|interpreter, arguments, _, span| {
+                let x = unit_argument(interpreter, &arguments, "asin", &span)?;
+                Ok(Object::Number(x.asin().to_degrees()))
+            },
+        }),
+        "acos" => Some(ModuleDefinition {
+            name: "acos",
+            summary: "The angle whose cosine is a number, in degrees.",
+            parameter_docs: &[("x", "The cosine, from -1 to 1.")],
+            parameters: EvaluatedParameters::required(vec!["x".to_owned()]),
+            action: &|interpreter, arguments, _, span| {
+                let x = unit_argument(interpreter, &arguments, "acos", &span)?;
+                Ok(Object::Number(x.acos().to_degrees()))
+            },
+        }),
+        "atan2" => Some(ModuleDefinition {
+            name: "atan2",
+            summary: "The angle from the X axis to a point, in degrees, which unlike `atan` works in every quadrant.",
+            parameter_docs: &[("y", "Y coordinate of the point."), ("x", "X coordinate of the point.")],
+            parameters: EvaluatedParameters::required(vec!["y".to_owned(), "x".to_owned()]),
+            action: &|interpreter, arguments, _, span| {
+                let y = number_argument(interpreter, &arguments, "y", &span)?;
+                let x = number_argument(interpreter, &arguments, "x", &span)?;
+                Ok(Object::Number(y.atan2(x).to_degrees()))
+            },
+        }),
+        "sqrt" => Some(ModuleDefinition {
+            name: "sqrt",
+            summary: "The square root of a number.",
+            parameter_docs: &[("x", "The number, which mustn't be negative.")],
+            parameters: EvaluatedParameters::required(vec!["x".to_owned()]),
+            action: &|interpreter, arguments, _, span| {
+                let x = number_argument(interpreter, &arguments, "x", &span)?;
+                if x < 0.0 {
+                    return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                        format!("sqrt needs a number which isn't negative, but got {x}")
+                    ), interpreter.argument_span("x", &span)));
+                }
+                Ok(Object::Number(x.sqrt()))
+            },
+        }),
+        "pow" => Some(ModuleDefinition {
+            name: "pow",
+            summary: "Raises a number to a power.",
+            parameter_docs: &[("base", "The number to raise."), ("exponent", "The power to raise it to.")],
+            parameters: EvaluatedParameters::required(vec!["base".to_owned(), "exponent".to_owned()]),
+            action: &|interpreter, arguments, _, span| {
+                let base = number_argument(interpreter, &arguments, "base", &span)?;
+                let exponent = number_argument(interpreter, &arguments, "exponent", &span)?;
+                Ok(Object::Number(base.powf(exponent)))
+            },
+        }),
+
+        "min" => Some(ModuleDefinition {
+            name: "min",
+            summary: "The smallest of two numbers, or of the numbers in a vector.",
+            parameter_docs: &[("a", "A number, or a vector of numbers."), ("b", "The other number, if `a` is a number.")],
+            parameters: extremum_parameters(),
+            action: &|interpreter, arguments, _, span| {
+                let candidates = extremum_candidates(interpreter, &arguments, "min", &span)?;
+                Ok(Object::Number(candidates.into_iter().fold(f64::INFINITY, f64::min)))
+            },
+        }),
+        "max" => Some(ModuleDefinition {
+            name: "max",
+            summary: "The largest of two numbers, or of the numbers in a vector.",
+            parameter_docs: &[("a", "A number, or a vector of numbers."), ("b", "The other number, if `a` is a number.")],
+            parameters: extremum_parameters(),
+            action: &|interpreter, arguments, _, span| {
+                let candidates = extremum_candidates(interpreter, &arguments, "max", &span)?;
+                Ok(Object::Number(candidates.into_iter().fold(f64::NEG_INFINITY, f64::max)))
+            },
+        }),
+
+        _ => None,
+    }
+}
@@ -3,3 +3,9 @@ pub use operators::*;
 
 mod modules;
 pub use modules::*;
+
+mod math;
+pub use math::*;
+
+mod reference;
+pub use reference::*;
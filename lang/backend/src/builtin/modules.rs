@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path};
 
-use manifold_rs::{CrossSection, Manifold};
+use manifold_rs::{CrossSection, Manifold, MeshGL, Polygons, Vec3, ext::Stl};
 use yascad_frontend::InputSourceSpan;
 
-use crate::{EvaluatedParameters, Interpreter, RuntimeError, RuntimeErrorKind, geometry_table::{GeometryDisposition, GeometryTableIndex}, object::Object};
+use crate::{EvaluatedParameters, Interpreter, RuntimeError, RuntimeErrorKind, Unit, builtin::get_builtin_math_module, font::{Font, FontError, default_font_path}, geometry_table::{GeometryDisposition, GeometryTableIndex}, object::Object, surface::Heightmap};
 
 /// Defines the parameters and behaviour of a built-in module.
 /// 
@@ -11,16 +11,32 @@ use crate::{EvaluatedParameters, Interpreter, RuntimeError, RuntimeErrorKind, ge
 /// in `parameters` definitely exist.
 #[derive(Clone)]
 pub struct ModuleDefinition {
+    /// The name which the module is called by.
+    pub name: &'static str,
+
+    /// One or two sentences saying what the module does, for reference documentation.
+    pub summary: &'static str,
+
+    /// A description of each parameter, by name.
+    pub parameter_docs: &'static [(&'static str, &'static str)],
+
     pub parameters: EvaluatedParameters,
     pub action: &'static dyn Fn(&mut Interpreter, HashMap<String, Object>, Option<&[GeometryTableIndex]>, InputSourceSpan) -> Result<Object, RuntimeError>,
 }
 
 fn cube_definition() -> ModuleDefinition {
     ModuleDefinition {
-        parameters: EvaluatedParameters::required(vec!["size".to_owned()]),
+        name: "cube",
+        summary: "Creates a cuboid, from its corner at the origin.",
+        parameter_docs: &[("size", "Length of each side, or a vector of the X, Y and Z lengths."), ("center", "Whether to centre the cuboid on the origin instead.")],
+        parameters: EvaluatedParameters {
+            required: vec!["size".to_owned()],
+            optional: vec![],
+            optional_named_only: vec![center_parameter()],
+        },
         action: &|interpreter, arguments, _, span| {
             let (x, y, z) = match &arguments["size"] {
-                Object::Vector(_) => arguments["size"].as_3d_vector(span)?,
+                Object::Vector(..) => arguments["size"].as_3d_vector(span.clone())?,
                 Object::Number(n) => (*n, *n, *n),
                 o => {
                     return Err(RuntimeError::new(RuntimeErrorKind::IncorrectType {
@@ -29,34 +45,49 @@ fn cube_definition() -> ModuleDefinition {
                     }, span))
                 }
             };
-            Ok(Object::Manifold(interpreter.manifold_table.add_manifold(Manifold::cube(x, y, z, false), GeometryDisposition::Physical)))
+            let cube = Manifold::cube(x, y, z, arguments["center"].as_boolean(span.clone())?)
+                .map_err(|error| RuntimeError::new(RuntimeErrorKind::GeometryError(error), span.clone()))?;
+            Ok(Object::Manifold(interpreter.manifold_table.add_primitive(cube, span)))
         },
     }
 }
 
 fn cylinder_definition() -> ModuleDefinition {
     ModuleDefinition {
+        name: "cylinder",
+        summary: "Creates a cylinder standing upwards from the origin.",
+        parameter_docs: &[("h", "Height."), ("r", "Radius. Either this or `d` must be given."), ("d", "Diameter, instead of `r`."), ("center", "Whether to centre the cylinder vertically on the origin instead.")],
         parameters: EvaluatedParameters {
             required: vec!["h".to_owned()],
             optional: vec![("r".to_owned(), Object::Null)],
-            optional_named_only: vec![("d".to_owned(), Object::Null)],
+            optional_named_only: vec![("d".to_owned(), Object::Null), center_parameter()],
         },
         action: &|interpreter, arguments, _, span| {
             // TODO: needs to support cone forms
             let height = arguments["h"].as_number(span.clone())?;
-            let radius = radius_argument(&arguments, span)?;
+            let radius = radius_argument(&arguments, span.clone())?;
 
-            Ok(Object::Manifold(interpreter.manifold_table.add_manifold(Manifold::cylinder(radius, height, interpreter.circle_segments, false), GeometryDisposition::Physical)))
+            let center = arguments["center"].as_boolean(span.clone())?;
+            let cylinder = Manifold::cylinder(radius, height, interpreter.segments(), center)
+                .map_err(|error| RuntimeError::new(RuntimeErrorKind::GeometryError(error), span.clone()))?;
+            Ok(Object::Manifold(interpreter.manifold_table.add_primitive(cylinder, span)))
         },
     }
 }
 
 fn square_definition() -> ModuleDefinition {
     ModuleDefinition {
-        parameters: EvaluatedParameters::required(vec!["size".to_owned()]),
+        name: "square",
+        summary: "Creates a rectangle, from its corner at the origin.",
+        parameter_docs: &[("size", "Length of each side, or a vector of the X and Y lengths."), ("center", "Whether to centre the rectangle on the origin instead.")],
+        parameters: EvaluatedParameters {
+            required: vec!["size".to_owned()],
+            optional: vec![],
+            optional_named_only: vec![center_parameter()],
+        },
         action: &|interpreter, arguments: HashMap<String, Object>, _, span| {
             let (x, y) = match &arguments["size"] {
-                Object::Vector(_) => arguments["size"].as_2d_vector(span)?,
+                Object::Vector(..) => arguments["size"].as_2d_vector(span.clone())?,
                 Object::Number(n) => (*n, *n),
                 o => {
                     return Err(RuntimeError::new(RuntimeErrorKind::IncorrectType {
@@ -65,35 +96,240 @@ fn square_definition() -> ModuleDefinition {
                     }, span))
                 }
             };
-            Ok(Object::CrossSection(interpreter.manifold_table.add_cross_section(CrossSection::square(x, y, false), GeometryDisposition::Physical)))
+            let center = arguments["center"].as_boolean(span.clone())?;
+            Ok(Object::CrossSection(interpreter.manifold_table.add_cross_section(CrossSection::square(x, y, center), GeometryDisposition::Physical, span)))
         }
     }
 }
 
 fn circle_definition() -> ModuleDefinition {
     ModuleDefinition {
+        name: "circle",
+        summary: "Creates a circle centred on the origin.",
+        parameter_docs: &[("r", "Radius. Either this or `d` must be given."), ("d", "Diameter, instead of `r`.")],
         parameters: EvaluatedParameters {
             required: vec![],
             optional: vec![("r".to_owned(), Object::Null)],
             optional_named_only: vec![("d".to_owned(), Object::Null)],
         },
         action: &|interpreter, arguments: HashMap<String, Object>, _, span| {
-            let radius = radius_argument(&arguments, span)?;
-            Ok(Object::CrossSection(interpreter.manifold_table.add_cross_section(CrossSection::circle(radius, interpreter.circle_segments), GeometryDisposition::Physical)))
+            let radius = radius_argument(&arguments, span.clone())?;
+            Ok(Object::CrossSection(interpreter.manifold_table.add_cross_section(CrossSection::circle(radius, interpreter.segments()), GeometryDisposition::Physical, span)))
+        }
+    }
+}
+
+/// Creates a manifold from a heightmap, read from a CSV file with a row of comma-separated heights
+/// on each line.
+fn surface_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "surface",
+        summary: "Creates a surface from a heightmap, read from a CSV file with a row of comma-separated heights on each line.",
+        parameter_docs: &[("file", "Path to the CSV file, relative to the source file."), ("scale_z", "Multiplies every height.")],
+        parameters: EvaluatedParameters::new(
+            vec!["file".to_owned()],
+            vec![("scale_z".to_owned(), Object::Number(1.0))],
+        ),
+        action: &|interpreter, arguments, _, span| {
+            let path = arguments["file"].as_string(span.clone())?.to_owned();
+            let scale_z = arguments["scale_z"].as_number(span.clone())?;
+
+            let data = interpreter.read_file_to_string(&path, &span)?;
+            let heightmap = Heightmap::parse_csv(&data)
+                .map_err(|error| RuntimeError::new(RuntimeErrorKind::InvalidSurfaceData { row: error.row, reason: error.reason }, span.clone()))?;
+
+            let (vertices, triangles) = heightmap.mesh(scale_z);
+            let surface = Manifold::from_meshgl(&MeshGL::from_vertices_and_triangles(&vertices, &triangles))
+                .map_err(|error| RuntimeError::new(RuntimeErrorKind::GeometryError(error), span.clone()))?;
+            Ok(Object::Manifold(interpreter.manifold_table.add_primitive(surface, span)))
+        },
+    }
+}
+
+/// Creates a manifold from a raw mesh, for geometry which is easier to compute than to build out
+/// of primitives.
+fn polyhedron_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "polyhedron",
+        summary: "Creates a 3D geometry from a list of points, and the faces which join them up.",
+        parameter_docs: &[
+            ("points", "Each corner of the geometry, as a vector of X, Y and Z."),
+            ("faces", "Each face, as a vector of three or more indices into `points`, going anticlockwise when looked at from outside. Faces with more than three points must be flat and convex."),
+        ],
+        parameters: EvaluatedParameters::required(vec!["points".to_owned(), "faces".to_owned()]),
+        action: &|interpreter, arguments, _, span| {
+            let vertices = polyhedron_points(&arguments["points"], &interpreter.argument_span("points", &span))?;
+            let triangles = polyhedron_triangles(&arguments["faces"], vertices.len(), &interpreter.argument_span("faces", &span))?;
+
+            let polyhedron = Manifold::from_meshgl(&MeshGL::from_vertices_and_triangles(&vertices, &triangles))
+                .map_err(|error| {
+                    let reason = error.mesh_problem().map(str::to_owned).unwrap_or_else(|| error.to_string());
+                    RuntimeError::new(RuntimeErrorKind::InvalidMesh(reason), span.clone())
+                })?;
+            Ok(Object::Manifold(interpreter.manifold_table.add_primitive(polyhedron, span)))
+        },
+    }
+}
+
+/// The `points` of a `polyhedron`, which must each be a vector of three numbers.
+fn polyhedron_points(points: &Object, span: &InputSourceSpan) -> Result<Vec<Vec3<f32>>, RuntimeError> {
+    points.as_vector(span.clone())?.iter().enumerate()
+        .map(|(index, point)| {
+            let point_span = points.vector_element_span(index, span);
+            let length = point.as_vector(point_span.clone())?.len();
+            if length != 3 {
+                return Err(RuntimeError::new(RuntimeErrorKind::IncorrectVectorLength { expected: 3..=3, actual: length }, point_span));
+            }
+
+            let [x, y, z] = [0, 1, 2].map(|axis| point.vector_element_as_number(axis, &point_span));
+            Ok(Vec3::new(x? as f32, y? as f32, z? as f32))
+        })
+        .collect()
+}
+
+/// The `faces` of a `polyhedron`, split into triangles. Each face is split into a fan around its
+/// first point, which is only right for convex faces.
+fn polyhedron_triangles(faces: &Object, point_count: usize, span: &InputSourceSpan) -> Result<Vec<[u32; 3]>, RuntimeError> {
+    let mut triangles = vec![];
+    for (face_index, face) in faces.as_vector(span.clone())?.iter().enumerate() {
+        let face_span = faces.vector_element_span(face_index, span);
+        let length = face.as_vector(face_span.clone())?.len();
+        if length < 3 {
+            return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                format!("each face needs at least 3 points, but this one has {length}")
+            ), face_span));
         }
+
+        let indices = (0..length)
+            .map(|index| {
+                let point = face.vector_element_as_number(index, &face_span)?;
+                if point.fract() != 0.0 || point < 0.0 || point >= point_count as f64 {
+                    return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                        format!("faces must refer to points by their index, from 0 to {}, got {point}", point_count.saturating_sub(1))
+                    ), face.vector_element_span(index, &face_span)));
+                }
+                Ok(point as u32)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        triangles.extend((1..length - 1).map(|i| [indices[0], indices[i], indices[i + 1]]));
+    }
+    Ok(triangles)
+}
+
+/// Creates a cross-section of a line of text, like for labelling a part once it's extruded.
+fn text_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "text",
+        summary: "Creates a 2D outline of a line of text, in a TrueType font.",
+        parameter_docs: &[
+            ("string", "The text."),
+            ("size", "The size of the font's em square. Capital letters are usually about 70% of this tall."),
+            ("font", "Path to a TrueType (`.ttf`) font file. Without one, a font which comes with the system is used, if there is one."),
+        ],
+        parameters: EvaluatedParameters {
+            required: vec!["string".to_owned()],
+            optional: vec![("size".to_owned(), Object::Number(10.0))],
+            optional_named_only: vec![("font".to_owned(), Object::Null)],
+        },
+        action: &|interpreter, arguments, _, span| {
+            let string = arguments["string"].as_string(interpreter.argument_span("string", &span))?.to_owned();
+            let size = arguments["size"].as_number(interpreter.argument_span("size", &span))?;
+            if !(size.is_finite() && size > 0.0) {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                    format!("size must be positive, got {size}")
+                ), interpreter.argument_span("size", &span)));
+            }
+
+            let font_span = interpreter.argument_span("font", &span);
+            let data = match &arguments["font"] {
+                Object::Null => match default_font_path() {
+                    Some(path) => interpreter.read_builtin_file_to_bytes(Path::new(path), &span)?,
+                    None => return Err(
+                        RuntimeError::new(RuntimeErrorKind::AssertionError("no font was given, and the system doesn't have a default one".to_owned()), span)
+                            .with_help("give the path to a TrueType font file, like `text(\"Label\", font = \"fonts/DejaVuSans.ttf\")`")
+                    ),
+                },
+                font => {
+                    let font_path = font.as_string(font_span.clone())?.to_owned();
+                    interpreter.read_file_to_bytes(&font_path, &font_span)?
+                }
+            };
+            let font_error = |error: FontError| RuntimeError::new(RuntimeErrorKind::InvalidFont(error.reason), font_span.clone());
+            let font = Font::parse(data).map_err(font_error)?;
+
+            // Fonts usually draw a circle with around eight curves
+            let curve_segments = (interpreter.segments() as usize).div_ceil(8);
+            let text = interpreter.compute_with_timeout("text", &span, move || {
+                let outlines = font.outline_text(&string, size, curve_segments)?;
+                Ok(CrossSection::from_polygons(&Polygons::from_points(&outlines)))
+            })?.map_err(font_error)?;
+            Ok(Object::CrossSection(interpreter.manifold_table.add_cross_section(text, GeometryDisposition::Physical, span)))
+        },
+    }
+}
+
+fn import_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "import",
+        summary: "Loads a 3D geometry from an STL file, in either the textual or binary format.",
+        parameter_docs: &[("file", "Path to the STL file.")],
+        parameters: EvaluatedParameters::required(vec!["file".to_owned()]),
+        action: &|interpreter, arguments, _, span| {
+            let file = arguments["file"].as_string(interpreter.argument_span("file", &span))?.to_owned();
+            let data = interpreter.read_file_to_bytes(&file, &span)?;
+            let stl = Stl::read(&data)
+                .map_err(|error| RuntimeError::new(RuntimeErrorKind::InvalidStl(error.reason), span.clone()))?;
+
+            let imported = Manifold::from_meshgl(&stl.to_meshgl())
+                .map_err(|error| {
+                    let reason = error.mesh_problem().map(str::to_owned).unwrap_or_else(|| error.to_string());
+                    RuntimeError::new(RuntimeErrorKind::InvalidMesh(reason), span.clone())
+                        .with_help("the triangles of an STL file must meet exactly at their corners, and enclose a solid")
+                })?;
+            Ok(Object::Manifold(interpreter.manifold_table.add_primitive(imported, span)))
+        },
+    }
+}
+
+/// Creates a manifold with no geometry, to stand for "nothing" in places which need a 3D value.
+fn empty_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "empty",
+        summary: "Creates a 3D geometry with nothing in it.",
+        parameter_docs: &[],
+        parameters: EvaluatedParameters::empty(),
+        action: &|interpreter, _, _, span| {
+            Ok(Object::Manifold(interpreter.manifold_table.add_manifold(Manifold::new(), GeometryDisposition::Physical, span)))
+        },
+    }
+}
+
+/// Like [`empty_definition`], but creates an empty cross-section instead.
+fn empty_2d_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "empty_2d",
+        summary: "Creates a 2D geometry with nothing in it.",
+        parameter_docs: &[],
+        parameters: EvaluatedParameters::empty(),
+        action: &|interpreter, _, _, span| {
+            Ok(Object::CrossSection(interpreter.manifold_table.add_cross_section(CrossSection::new(), GeometryDisposition::Physical, span)))
+        },
     }
 }
 
 fn copy_definition() -> ModuleDefinition {
     ModuleDefinition {
+        name: "copy",
+        summary: "Copies a geometry, so that it can be used again without being consumed.",
+        parameter_docs: &[("source", "The geometry to copy.")],
         parameters: EvaluatedParameters::required(vec!["source".to_owned()]),
         action: &|interpreter, arguments, _, span| {
-            let manifold_index = arguments["source"].clone().into_manifold(span)?;
-            let manifold = interpreter.manifold_table.get(&manifold_index);
+            let manifold_index = arguments["source"].clone().into_manifold(span.clone())?;
+            let manifold = interpreter.manifold_table.get(&manifold_index, span.clone())?;
 
             // Even if it's being copied in a virtual disposition, we can make it physical here.
             // The `buffer` will "downgrade" it later.
-            let copied_manifold = interpreter.manifold_table.add(manifold.clone(), GeometryDisposition::Physical);
+            let copied_manifold = interpreter.manifold_table.add(manifold.clone(), GeometryDisposition::Physical, span);
             Ok(Object::Manifold(copied_manifold))
         },
     }
@@ -101,6 +337,9 @@ fn copy_definition() -> ModuleDefinition {
 
 fn children_definition() -> ModuleDefinition {
     ModuleDefinition {
+        name: "children",
+        summary: "Copies all of the children passed to the operator being run, as one geometry.",
+        parameter_docs: &[],
         parameters: EvaluatedParameters::empty(),
         action: &|interpreter, _, operator_children, span| {
             let Some(children) = operator_children
@@ -112,27 +351,351 @@ fn children_definition() -> ModuleDefinition {
             // Copy them as physical and then build a union of all of the copies.
             let copied_children = children.iter()
                 .map(|child| {
-                    let m = interpreter.manifold_table.get(child).clone();
-                    interpreter.manifold_table.add(m, GeometryDisposition::Physical)
+                    let m = interpreter.manifold_table.get(child, span.clone())?.clone();
+                    Ok(interpreter.manifold_table.add(m, GeometryDisposition::Physical, span.clone()))
                 })
-                .collect::<Vec<_>>();
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(copied_children, span.clone())?;
+            Ok(interpreter.manifold_table.add_into_object(geom, disp, span))
+        }
+    }
+}
+
+/// Copies one of the children passed to the operator being executed, by its index from 0, so that
+/// an operator body can treat its children differently.
+fn child_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "child",
+        summary: "Copies one of the children passed to the operator being run.",
+        parameter_docs: &[("i", "Index of the child, from 0.")],
+        parameters: EvaluatedParameters::required(vec!["i".to_owned()]),
+        action: &|interpreter, arguments, operator_children, span| {
+            let child = operator_child(&arguments["i"], operator_children, span.clone())?;
+
+            let geom = interpreter.manifold_table.get(child, span.clone())?.clone();
+            Ok(interpreter.manifold_table.add_into_object(geom, GeometryDisposition::Physical, span))
+        }
+    }
+}
+
+/// Gets the bounding box of one of the children passed to the operator being executed, as a vector
+/// of its lower and upper corners. This is cheaper than measuring a copy from `child`.
+fn child_bounds_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "child_bounds",
+        summary: "Gets the lower and upper corners of the bounding box of one of the children passed to the operator being run.",
+        parameter_docs: &[("i", "Index of the child, from 0.")],
+        parameters: EvaluatedParameters::required(vec!["i".to_owned()]),
+        action: &|interpreter, arguments, operator_children, span| {
+            let child = operator_child(&arguments["i"], operator_children, span.clone())?;
+
+            let object = if interpreter.manifold_table.get(child, span.clone())?.is_3d() {
+                Object::Manifold(child.clone())
+            } else {
+                Object::CrossSection(child.clone())
+            };
+            let corners = ["min_point", "max_point"].into_iter()
+                .map(|field| object.get_field(field, &interpreter.manifold_table, span.clone())
+                    .map(|corner| corner.expect("geometry has no bounds")))
+                .collect::<Result<_, _>>()?;
+            Ok(Object::vector(corners))
+        }
+    }
+}
+
+/// Look up an operator child by its index.
+fn operator_child<'a>(index: &Object, operator_children: Option<&'a [GeometryTableIndex]>, span: InputSourceSpan) -> Result<&'a GeometryTableIndex, RuntimeError> {
+    let Some(children) = operator_children
+    else {
+        return Err(RuntimeError::new(RuntimeErrorKind::ChildrenInvalid, span));
+    };
+
+    let index = index.as_number(span.clone())?;
+    if index < 0.0 || index.fract() != 0.0 {
+        return Err(RuntimeError::new(
+            RuntimeErrorKind::IncorrectType { expected: "whole number".to_owned(), actual: index.to_string() },
+            span,
+        ));
+    }
 
-            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(copied_children, span)?;
-            Ok(interpreter.manifold_table.add_into_object(geom, disp))
+    children.get(index as usize)
+        .ok_or_else(|| RuntimeError::new(RuntimeErrorKind::ChildIndexOutOfRange { index: index as usize, count: children.len() }, span))
+}
+
+/// Gets the number of children passed to the operator being executed, so that an operator body can
+/// avoid using `it` when there isn't exactly one child.
+fn children_count_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "children_count",
+        summary: "Gets the number of children passed to the operator being run.",
+        parameter_docs: &[],
+        parameters: EvaluatedParameters::empty(),
+        action: &|_, _, operator_children, span| {
+            let Some(children) = operator_children
+            else {
+                return Err(RuntimeError::new(RuntimeErrorKind::ChildrenInvalid, span));
+            };
+
+            Ok(Object::Number(children.len() as f64))
         }
     }
 }
 
+/// Defines a unit conversion function, which takes a length in `unit` and converts it into the
+/// model's base unit.
+fn unit_conversion_definition(unit: Unit) -> ModuleDefinition {
+    let (name, summary, action): (_, _, &'static dyn Fn(&mut Interpreter, HashMap<String, Object>, Option<&[GeometryTableIndex]>, InputSourceSpan) -> Result<Object, RuntimeError>) =
+        match unit {
+            Unit::Millimetre => ("mm", "Converts a length in millimetres into the model's units.",
+                &|interpreter, arguments, _, span| convert_unit_argument(interpreter, &arguments, Unit::Millimetre, span)),
+            Unit::Centimetre => ("cm", "Converts a length in centimetres into the model's units.",
+                &|interpreter, arguments, _, span| convert_unit_argument(interpreter, &arguments, Unit::Centimetre, span)),
+            Unit::Inch => ("inch", "Converts a length in inches into the model's units.",
+                &|interpreter, arguments, _, span| convert_unit_argument(interpreter, &arguments, Unit::Inch, span)),
+        };
+
+    ModuleDefinition {
+        name,
+        summary,
+        parameter_docs: &[("x", "The length to convert.")],
+        parameters: EvaluatedParameters::required(vec!["x".to_owned()]),
+        action,
+    }
+}
+
+fn convert_unit_argument(interpreter: &Interpreter, arguments: &HashMap<String, Object>, unit: Unit, span: InputSourceSpan) -> Result<Object, RuntimeError> {
+    let value = arguments["x"].as_number(span)?;
+    Ok(Object::Number(interpreter.units.convert_from(value, unit)))
+}
+
+/// Sets the clearance added to hole sizes by `hole_d` and `hole_r`, so that fits for a whole model
+/// can be tuned in one place. Negative clearances give an interference fit.
+fn print_clearance_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "print_clearance",
+        summary: "Sets the clearance which `hole_d` and `hole_r` add to hole sizes, for the whole model.",
+        parameter_docs: &[("c", "The clearance. Negative clearances give an interference fit.")],
+        parameters: EvaluatedParameters::required(vec!["c".to_owned()]),
+        action: &|interpreter, arguments, _, span| {
+            let clearance = arguments["c"].as_number(span.clone())?;
+            if !clearance.is_finite() {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                    format!("clearance must be a finite number, got {clearance}")
+                ), span));
+            }
+
+            // Holes which have already been sized must agree with the rest of the model
+            if interpreter.print_clearance.is_some() {
+                return Err(RuntimeError::new(RuntimeErrorKind::DuplicatePrintClearance, span));
+            }
+            if interpreter.print_clearance_uses > 0 {
+                return Err(RuntimeError::new(RuntimeErrorKind::PrintClearanceAfterUse, span));
+            }
+
+            interpreter.print_clearance = Some(clearance);
+            interpreter.print_clearance_uses += 1;
+            Ok(Object::Null)
+        },
+    }
+}
+
+/// Sets the number of segments used for a full circle by curved geometry created afterwards, so
+/// that a model can choose how smooth it should be.
+fn segments_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "segments",
+        summary: "Sets the number of segments used for a full circle by curved geometry created afterwards.",
+        parameter_docs: &[("n", "Number of segments, at least 3.")],
+        parameters: EvaluatedParameters::required(vec!["n".to_owned()]),
+        action: &|interpreter, arguments, _, span| {
+            let segments = arguments["n"].as_number(span.clone())?;
+            if !((3.0..=i32::MAX as f64).contains(&segments) && segments.fract() == 0.0) {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                    format!("segments must be a whole number, at least 3, got {segments}")
+                ), span));
+            }
+
+            interpreter.circle_segments = segments as i32;
+            Ok(Object::Null)
+        },
+    }
+}
+
+fn hole_d_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "hole_d",
+        summary: "Adds the print clearance to the diameter of a hole.",
+        parameter_docs: &[("d", "The diameter the hole should fit.")],
+        parameters: EvaluatedParameters::required(vec!["d".to_owned()]),
+        action: &|interpreter, arguments, _, span| {
+            let diameter = arguments["d"].as_number(span.clone())?;
+            clearance_hole(interpreter, diameter, 1.0, span)
+        },
+    }
+}
+
+fn hole_r_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "hole_r",
+        summary: "Adds the print clearance to the radius of a hole.",
+        parameter_docs: &[("r", "The radius the hole should fit.")],
+        parameters: EvaluatedParameters::required(vec!["r".to_owned()]),
+        action: &|interpreter, arguments, _, span| {
+            let radius = arguments["r"].as_number(span.clone())?;
+            clearance_hole(interpreter, radius, 0.5, span)
+        },
+    }
+}
+
+/// Add the print clearance to a hole size, multiplied by `factor` to convert it from a diameter.
+fn clearance_hole(interpreter: &mut Interpreter, size: f64, factor: f64, span: InputSourceSpan) -> Result<Object, RuntimeError> {
+    if !(size.is_finite() && size > 0.0) {
+        return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+            format!("hole size must be a positive number, got {size}")
+        ), span));
+    }
+
+    interpreter.print_clearance_uses += 1;
+    let result = size + interpreter.print_clearance.unwrap_or(0.0) * factor;
+    if result <= 0.0 {
+        return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+            format!("hole size {size} is too small for the print clearance, which would make it {result}")
+        ), span));
+    }
+
+    Ok(Object::Number(result))
+}
+
 fn __debug_definition() -> ModuleDefinition {
     ModuleDefinition {
+        name: "__debug",
+        summary: "Logs a description of an object, for debugging the interpreter.",
+        parameter_docs: &[("o", "The object to describe.")],
         parameters: EvaluatedParameters::required(vec!["o".to_owned()]),
-        action: &|_, arguments, _, _| {
-            println!("{:#?}", arguments["o"]);
+        action: &|interpreter, arguments, _, span| {
+            log::info!("{:#?}", arguments["o"].snapshot(&interpreter.manifold_table, span)?);
             Ok(Object::Null)
         },
     }
 }
 
+/// Gets the number of items in a vector, or characters in a string.
+fn len_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "len",
+        summary: "Gets the number of items in a vector, or characters in a string.",
+        parameter_docs: &[("value", "The vector or string.")],
+        parameters: EvaluatedParameters::required(vec!["value".to_owned()]),
+        action: &|_, arguments, _, span| {
+            match &arguments["value"] {
+                Object::Vector(items, _) => Ok(Object::Number(items.len() as f64)),
+                Object::String(string) => Ok(Object::Number(string.chars().count() as f64)),
+                other => Err(RuntimeError::new(
+                    RuntimeErrorKind::IncorrectType { expected: "vector or string".to_owned(), actual: other.describe_type() },
+                    span,
+                )),
+            }
+        },
+    }
+}
+
+/// Fails the build if a condition is false, with an optional message saying what went wrong. Used
+/// to validate the parameters of modules and operators.
+fn assert_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "assert",
+        summary: "Fails the build if a condition is false.",
+        parameter_docs: &[("condition", "The condition which must be true."), ("message", "A string saying what went wrong, shown in the error.")],
+        parameters: EvaluatedParameters::new(
+            vec!["condition".to_owned()],
+            vec![("message".to_owned(), Object::Null)],
+        ),
+        action: &|_, arguments, _, span| {
+            let condition = arguments["condition"].as_boolean(span.clone())?;
+            let message = match &arguments["message"] {
+                Object::Null => None,
+                message => Some(message.as_string(span.clone())?.to_owned()),
+            };
+
+            if !condition {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionFailed { message }, span));
+            }
+            Ok(Object::Null)
+        },
+    }
+}
+
+/// The tolerance used by `assert_same_shape` when none is given, relative to the larger volume.
+pub const DEFAULT_SHAPE_TOLERANCE: f64 = 1e-6;
+
+/// How different two manifolds are, as the volume of their symmetric difference relative to the
+/// larger of their volumes. Two empty manifolds don't differ at all.
+///
+/// The booleans are done directly with Manifold, so nothing is added to any geometry table.
+pub fn shape_difference(a: &Manifold, b: &Manifold) -> f64 {
+    let largest_volume = a.volume().max(b.volume());
+    if largest_volume <= 0.0 {
+        return 0.0;
+    }
+
+    let symmetric_difference = a.union(b).volume() - a.intersection(b).volume();
+    symmetric_difference.max(0.0) / largest_volume
+}
+
+/// Fails the build if two geometries aren't the same shape, for checking that a refactored model
+/// still builds the same thing. Both geometries are used up, so that if they were only built to be
+/// compared, they don't end up in the scene.
+fn assert_same_shape_definition() -> ModuleDefinition {
+    ModuleDefinition {
+        name: "assert_same_shape",
+        summary: "Fails the build if two geometries aren't the same shape, within a tolerance. Both geometries are used up.",
+        parameter_docs: &[
+            ("a", "The first geometry."),
+            ("b", "The second geometry."),
+            ("tolerance", "How much the geometries can differ by, as the volume of their difference relative to the larger volume."),
+        ],
+        parameters: EvaluatedParameters::new(
+            vec!["a".to_owned(), "b".to_owned()],
+            vec![("tolerance".to_owned(), Object::Number(DEFAULT_SHAPE_TOLERANCE))],
+        ),
+        action: &|interpreter, arguments, _, span| {
+            let a_index = arguments["a"].clone().into_manifold(interpreter.argument_span("a", &span))?;
+            let b_index = arguments["b"].clone().into_manifold(interpreter.argument_span("b", &span))?;
+            let tolerance = arguments["tolerance"].as_number(interpreter.argument_span("tolerance", &span))?;
+            if tolerance < 0.0 {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                    format!("tolerance must not be negative, got {tolerance}")
+                ), interpreter.argument_span("tolerance", &span)));
+            }
+
+            interpreter.manifold_table.get(&a_index, interpreter.argument_span("a", &span))?;
+            interpreter.manifold_table.get(&b_index, interpreter.argument_span("b", &span))?;
+            if a_index == b_index {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                    "the same geometry cannot be compared with itself".to_owned()
+                ), span));
+            }
+
+            let (a, _) = interpreter.manifold_table.remove(a_index);
+            let (b, _) = interpreter.manifold_table.remove(b_index);
+            let difference = shape_difference(a.unwrap_manifold(), b.unwrap_manifold());
+            if difference > tolerance {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionFailed {
+                    message: Some(format!("shapes differ by {difference} of the larger volume, which is more than the tolerance of {tolerance}")),
+                }, span));
+            }
+            Ok(Object::Null)
+        },
+    }
+}
+
+/// The `center` parameter of primitives, which centres them on the origin rather than putting
+/// their corner there.
+fn center_parameter() -> (String, Object) {
+    ("center".to_owned(), Object::Boolean(false))
+}
+
 /// Given an argument map which may contain a non-null `r` or `d`, gets the radius.
 pub fn radius_argument(arguments: &HashMap<String, Object>, span: InputSourceSpan) -> Result<f64, RuntimeError> {
     match (&arguments["r"], &arguments["d"]) {
@@ -151,6 +714,14 @@ pub fn radius_argument(arguments: &HashMap<String, Object>, span: InputSourceSpa
     }
 }
 
+/// The names of all built-in modules, for suggesting alternatives to misspelled names. Modules for
+/// debugging the interpreter aren't included, so that they're never suggested.
+pub const BUILTIN_MODULE_NAMES: &[&str] = &[
+    "cube", "cylinder", "square", "circle", "surface", "polyhedron", "text", "import", "empty",
+    "empty_2d", "copy", "children", "children_count", "child", "child_bounds", "mm", "cm", "inch",
+    "print_clearance", "hole_d", "hole_r", "segments", "assert", "assert_same_shape", "len",
+];
+
 /// Get the implementation for a specific built-in module.
 /// 
 /// Returns [`None`] if no such module exists.
@@ -160,10 +731,30 @@ pub fn get_builtin_module(name: &str) -> Option<ModuleDefinition> {
         "cylinder" => Some(cylinder_definition()),
         "square" => Some(square_definition()),
         "circle" => Some(circle_definition()),
+        "surface" => Some(surface_definition()),
+        "polyhedron" => Some(polyhedron_definition()),
+        "text" => Some(text_definition()),
+        "import" => Some(import_definition()),
+        "empty" => Some(empty_definition()),
+        "empty_2d" => Some(empty_2d_definition()),
         "copy" => Some(copy_definition()),
         "children" => Some(children_definition()),
+        "children_count" => Some(children_count_definition()),
+        "child" => Some(child_definition()),
+        "child_bounds" => Some(child_bounds_definition()),
+        "mm" => Some(unit_conversion_definition(Unit::Millimetre)),
+        "cm" => Some(unit_conversion_definition(Unit::Centimetre)),
+        "inch" => Some(unit_conversion_definition(Unit::Inch)),
+        "print_clearance" => Some(print_clearance_definition()),
+        "hole_d" => Some(hole_d_definition()),
+        "hole_r" => Some(hole_r_definition()),
+        "segments" => Some(segments_definition()),
+        "assert" => Some(assert_definition()),
+        "assert_same_shape" => Some(assert_same_shape_definition()),
+        "len" => Some(len_definition()),
+
         "__debug" => Some(__debug_definition()),
 
-        _ => None,
+        _ => get_builtin_math_module(name),
     }
 }
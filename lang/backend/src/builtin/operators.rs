@@ -1,182 +1,698 @@
 use std::collections::HashMap;
 
-use manifold_rs::Manifold;
+use manifold_rs::{CrossSection, JoinType, Manifold, Matrix3x4};
 use yascad_frontend::InputSourceSpan;
 
-use crate::{EvaluatedParameters, Interpreter, RuntimeError, RuntimeErrorKind, geometry_table::{GeometryDisposition, GeometryTableEntry, GeometryTableIndex}, object::Object};
+use crate::{EvaluatedParameters, Interpreter, RuntimeError, RuntimeErrorKind, RuntimeWarningKind, geometry_table::{GeometryDisposition, GeometryTableEntry, GeometryTableIndex}, instancing::Instances, object::Object};
 
 /// Defines the parameters and behaviour of a built-in operator.
 /// 
 /// The `action` can assume that all of its arguments have been validated - all of the keys defined
 /// in `parameters` definitely exist.
+/// 
+/// Alongside the children, the `action` is given the span of the child statement which produced
+/// each one, so that errors can point at a specific child.
 #[derive(Clone)]
 pub struct OperatorDefinition {
+    /// The name which the operator is applied by.
+    pub name: &'static str,
+
+    /// One or two sentences saying what the operator does, for reference documentation.
+    pub summary: &'static str,
+
+    /// A description of each parameter, by name.
+    pub parameter_docs: &'static [(&'static str, &'static str)],
+
     pub parameters: EvaluatedParameters,
-    pub action: &'static dyn Fn(&mut Interpreter, HashMap<String, Object>, Vec<GeometryTableIndex>, InputSourceSpan) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError>,
+    pub action: &'static dyn Fn(&mut Interpreter, HashMap<String, Object>, Vec<GeometryTableIndex>, &[InputSourceSpan], InputSourceSpan) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError>,
 }
 
 fn translate_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "translate",
+        summary: "Moves its children.",
+        parameter_docs: &[("v", "How far to move, as a vector of X, Y and Z - or X and Y for 2D children.")],
         parameters: EvaluatedParameters::required(vec!["v".to_owned()]),
-        action: &|interpreter, arguments, children, span| {
-            match interpreter.manifold_table.remove_many_into_union(children, span.clone())? {
-                (GeometryTableEntry::Manifold(manifold), d) => {
-                    let (x, y, z) = arguments["v"].as_3d_vector(span)?;
-                    Ok((GeometryTableEntry::Manifold(manifold.translate(x, y, z)), d))
+        action: &|interpreter, arguments, children, _, span| {
+            dimensional_transform(
+                interpreter, &arguments, children, span,
+                |manifold, v, v_span| {
+                    let (x, y, z) = v.as_3d_vector(v_span)?;
+                    Ok(manifold.translate(x, y, z))
                 },
-
-                (GeometryTableEntry::CrossSection(cross_section), d) => {
-                    let (x, y) = arguments["v"].as_2d_vector(span)?;
-                    Ok((GeometryTableEntry::CrossSection(cross_section.translate(x, y)), d))
+                |cross_section, v, v_span| {
+                    let (x, y) = v.as_2d_vector(v_span)?;
+                    Ok(cross_section.translate(x, y))
                 },
-            }
+                Some(|v, v_span| {
+                    let (x, y, z) = v.as_3d_vector(v_span)?;
+                    Ok(Matrix3x4::translation(x, y, z))
+                }),
+            )
         }
     }
 }
 
+/// Applies a transform to an operator's children, by combining them and then calling `for_3d` or
+/// `for_2d` depending on their dimension. Each is given the `v` argument and where it was written,
+/// to coerce it into whatever form suits that dimension, so that errors about it point at the
+/// argument whichever dimension the children are.
+///
+/// Instanced children are combined into one manifold for `for_3d`, unless the transform can also
+/// be written as a matrix by `instanced`, which is cheap to apply to each instance.
+fn dimensional_transform(
+    interpreter: &mut Interpreter,
+    arguments: &HashMap<String, Object>,
+    children: Vec<GeometryTableIndex>,
+    span: InputSourceSpan,
+    for_3d: impl FnOnce(Manifold, &Object, InputSourceSpan) -> Result<Manifold, RuntimeError>,
+    for_2d: impl FnOnce(CrossSection, &Object, InputSourceSpan) -> Result<CrossSection, RuntimeError>,
+    instanced: Option<fn(&Object, InputSourceSpan) -> Result<Matrix3x4, RuntimeError>>,
+) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError> {
+    let v = &arguments["v"];
+    let v_span = interpreter.argument_span("v", &span);
+    let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span)?;
+
+    let transformed = match geom {
+        GeometryTableEntry::Manifold(manifold) => GeometryTableEntry::Manifold(for_3d(manifold, v, v_span)?),
+        GeometryTableEntry::Instanced(instances) => match instanced {
+            Some(matrix) => GeometryTableEntry::Instanced(instances.transformed(&matrix(v, v_span)?)),
+            None => GeometryTableEntry::Manifold(for_3d(instances.into_manifold(), v, v_span)?),
+        },
+        GeometryTableEntry::CrossSection(cross_section) => GeometryTableEntry::CrossSection(for_2d(cross_section, v, v_span)?),
+    };
+    Ok((transformed, disp))
+}
+
 fn union_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "union",
+        summary: "Combines its children into one geometry.",
+        parameter_docs: &[],
         parameters: EvaluatedParameters::empty(),
-        action: &|interpreter, _, children, span| {
+        action: &|interpreter, _, children, child_spans, span| {
+            check_child_dimensions(interpreter, &children, child_spans)?;
             interpreter.manifold_table.remove_many_into_union(children, span)
         }
     }
 }
 
+/// Subtracts the union of its second and later children from its first.
+/// 
+/// Children produced by `keep` aren't subtracted - instead, they're added back afterwards, so that
+/// they can fill parts of what was taken away.
 fn difference_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "difference",
+        summary: "Subtracts its second and later children from its first. Children inside `keep` are added back afterwards instead.",
+        parameter_docs: &[],
         parameters: EvaluatedParameters::empty(),
-        action: &|interpreter, _, mut children, span| {
+        action: &|interpreter, _, children, child_spans, span| {
+            check_child_dimensions(interpreter, &children, child_spans)?;
+
+            let (kept, mut children): (Vec<_>, Vec<_>) = children.into_iter()
+                .partition(|child| interpreter.manifold_table.is_kept(child));
             if children.is_empty() {
                 return Err(RuntimeError::new(RuntimeErrorKind::ChildrenExpected, span))
             }
 
-            let (minuend, disp) = interpreter.manifold_table.remove(children.remove(0));
+            let minuend = children.remove(0);
+            let result = difference(interpreter, minuend, children, span.clone())?;
+            if kept.is_empty() {
+                return Ok(result)
+            }
+
+            let (geom, disp) = result;
+            let result = interpreter.manifold_table.add(geom, disp, span.clone());
+            interpreter.manifold_table.remove_many_into_union([vec![result], kept].concat(), span)
+        }
+    }
+}
+
+/// Subtract the union of `subtrahends` from `minuend`, removing all of them from the table.
+fn difference(interpreter: &mut Interpreter, minuend: GeometryTableIndex, subtrahends: Vec<GeometryTableIndex>, span: InputSourceSpan) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError> {
+    let (minuend, disp) = interpreter.manifold_table.remove(minuend);
+    let minuend = minuend.realise();
+    if subtrahends.is_empty() {
+        return Ok((minuend, disp))
+    }
+
+    let (subtrahend, _) = interpreter.manifold_table.remove_many_into_union(subtrahends, span.clone())?;
+    let subtrahend = subtrahend.realise();
+
+    // Nothing can be taken away from an empty minuend, and an empty subtrahend takes
+    // nothing away - whatever their dimensions
+    if minuend.is_empty() || subtrahend.is_empty() {
+        return Ok((minuend, disp))
+    }
+
+    match (minuend, subtrahend) {
+        (GeometryTableEntry::Manifold(minuend_manifold), GeometryTableEntry::Manifold(subtrahend_manifold)) => {
+            let (result, is_empty) = interpreter.compute_with_timeout("difference", &span, move || {
+                let result = minuend_manifold.difference(&subtrahend_manifold);
+                let is_empty = result.is_empty();
+                (result, is_empty)
+            })?;
+
+            // Code in a loop or a module would otherwise warn every time it runs
+            if is_empty && !interpreter.warnings().iter().any(|warning| warning.span == span) {
+                interpreter.warn(RuntimeWarningKind::DifferenceRemovedEverything, span.clone());
+            }
+            Ok((GeometryTableEntry::Manifold(result), disp))
+        },
+
+        (GeometryTableEntry::CrossSection(minuend_cross_section), GeometryTableEntry::CrossSection(subtrahend_cross_section)) => {
+            let result = interpreter.compute_with_timeout("difference", &span, move ||
+                minuend_cross_section.difference(&subtrahend_cross_section))?;
+            Ok((GeometryTableEntry::CrossSection(result), disp))
+        },
+
+        _ => {
+            Err(RuntimeError::new(RuntimeErrorKind::MixedGeometryDimensions, span))
+        }
+    }
+}
+
+fn intersection_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "intersection",
+        summary: "Keeps only the parts which all of its children have in common.",
+        parameter_docs: &[],
+        parameters: EvaluatedParameters::empty(),
+        action: &|interpreter, _, children, child_spans, span| {
             if children.is_empty() {
-                return Ok((minuend, disp))
+                return Err(RuntimeError::new(RuntimeErrorKind::ChildrenExpected, span))
             }
-            
-            let (subtrahend, _) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
-            match (minuend, subtrahend) {
-                (GeometryTableEntry::Manifold(minuend_manifold), GeometryTableEntry::Manifold(subtrahend_manifold)) => {
-                    Ok((GeometryTableEntry::Manifold(minuend_manifold.difference(&subtrahend_manifold)), disp))
-                },
+            check_child_dimensions(interpreter, &children, child_spans)?;
 
-                (GeometryTableEntry::CrossSection(minuend_cross_section), GeometryTableEntry::CrossSection(subtrahend_cross_section)) => {
-                    Ok((GeometryTableEntry::CrossSection(minuend_cross_section.difference(&subtrahend_cross_section)), disp))
-                },
+            let (all_entries, all_dispositions): (Vec<_>, Vec<_>) = children.into_iter()
+                .map(|child| interpreter.manifold_table.remove(child))
+                .unzip();
+            let disp = GeometryDisposition::flatten(&all_dispositions, span.clone())?;
+
+            let mut entries = all_entries.into_iter().map(GeometryTableEntry::realise);
+            let mut result = entries.next().unwrap();
+            for entry in entries {
+                result = match (result, entry) {
+                    (GeometryTableEntry::Manifold(result_manifold), GeometryTableEntry::Manifold(manifold)) =>
+                        GeometryTableEntry::Manifold(result_manifold.intersection(&manifold)),
 
-                _ => {
-                    Err(RuntimeError::new(RuntimeErrorKind::MixedGeometryDimensions, span))
-                }
+                    (GeometryTableEntry::CrossSection(result_cross_section), GeometryTableEntry::CrossSection(cross_section)) =>
+                        GeometryTableEntry::CrossSection(interpreter.compute_with_timeout("intersection", &span, move ||
+                            result_cross_section.intersection(&cross_section))?),
+
+                    _ => return Err(RuntimeError::new(RuntimeErrorKind::MixedGeometryDimensions, span)),
+                };
             }
+
+            Ok((result, disp))
         }
     }
 }
 
+/// Wraps all of its children in their convex hull, like stretching a sheet around them. Hulling two
+/// cylinders is a quick way to make a slot or a rounded bracket.
+fn hull_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "hull",
+        summary: "Creates the convex hull of its children - the smallest convex shape which contains all of them.",
+        parameter_docs: &[],
+        parameters: EvaluatedParameters::empty(),
+        action: &|interpreter, _, children, child_spans, span| {
+            if children.is_empty() {
+                return Err(RuntimeError::new(RuntimeErrorKind::ChildrenExpected, span))
+            }
+            check_child_dimensions(interpreter, &children, child_spans)?;
+
+            let (all_entries, all_dispositions): (Vec<_>, Vec<_>) = children.into_iter()
+                .map(|child| interpreter.manifold_table.remove(child))
+                .unzip();
+            let disp = GeometryDisposition::flatten(&all_dispositions, span.clone())?;
+
+            // Empty children add nothing to the hull, so they don't decide its dimensions either
+            let entries = all_entries.into_iter()
+                .map(GeometryTableEntry::realise)
+                .collect::<Vec<_>>();
+            let is_3d = entries.iter()
+                .find(|entry| !entry.is_empty())
+                .unwrap_or(&entries[0])
+                .is_3d();
+
+            let result = if is_3d {
+                let manifolds = entries.into_iter()
+                    .filter_map(|entry| match entry {
+                        GeometryTableEntry::Manifold(manifold) => Some(manifold),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                GeometryTableEntry::Manifold(interpreter.compute_with_timeout("hull", &span, move || Manifold::hull_many(&manifolds))?)
+            } else {
+                let cross_sections = entries.into_iter()
+                    .filter_map(|entry| match entry {
+                        GeometryTableEntry::CrossSection(cross_section) => Some(cross_section),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                GeometryTableEntry::CrossSection(interpreter.compute_with_timeout("hull", &span, move || CrossSection::hull_many(&cross_sections))?)
+            };
+
+            Ok((result, disp))
+        }
+    }
+}
+
+/// Grows or shrinks 2D children, like OpenSCAD's `offset`. With `r`, corners are rounded as if
+/// traced by a circle - which is how to round off a 2D outline before extruding it. With `delta`,
+/// corners stay sharp, or are cut off flat with `chamfer`.
+fn offset_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "offset",
+        summary: "Moves the edges of its 2D children outwards, or inwards by a negative amount.",
+        parameter_docs: &[
+            ("r", "How far to move the edges, rounding the corners. Either this or `delta` must be given."),
+            ("delta", "How far to move the edges, keeping the corners sharp, instead of `r`."),
+            ("chamfer", "Whether to cut off corners flat, when using `delta`."),
+        ],
+        parameters: EvaluatedParameters {
+            required: vec![],
+            optional: vec![("r".to_owned(), Object::Null)],
+            optional_named_only: vec![("delta".to_owned(), Object::Null), ("chamfer".to_owned(), Object::Boolean(false))],
+        },
+        action: &|interpreter, arguments, children, _, span| {
+            let (delta, join_type) = match (&arguments["r"], &arguments["delta"]) {
+                (Object::Null, Object::Null) =>
+                    return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                        "neither \"r\" nor \"delta\" argument is given, but one must be specified".to_owned()
+                    ), span)),
+
+                (radius, Object::Null) => (radius.as_number(span.clone())?, JoinType::Round),
+                (Object::Null, delta) => {
+                    let join_type = if arguments["chamfer"].as_boolean(span.clone())? { JoinType::Square } else { JoinType::Miter };
+                    (delta.as_number(span.clone())?, join_type)
+                },
+
+                (_, _) =>
+                    return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                        "both \"r\" and \"delta\" arguments are given, but only one must be specified".to_owned()
+                    ), span)),
+            };
+
+            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
+            let GeometryTableEntry::CrossSection(cross_section) = geom
+            else { return Err(RuntimeError::new(RuntimeErrorKind::Requires2DGeometry, span)) };
+
+            // Clipper's default, which is enough to keep right angles sharp
+            let miter_limit = 2.0;
+            let segments = interpreter.segments();
+            let result = interpreter.compute_with_timeout("offset", &span, move ||
+                cross_section.offset(delta, join_type, miter_limit, segments))?;
+            Ok((GeometryTableEntry::CrossSection(result), disp))
+        },
+    }
+}
+
 fn linear_extrude_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "linear_extrude",
+        summary: "Extrudes its 2D children upwards into 3D.",
+        parameter_docs: &[("h", "Height of the extrusion.")],
         parameters: EvaluatedParameters::required(vec!["h".to_owned()]),
-        action: &|interpreter, arguments, children, span| {
+        action: &|interpreter, arguments, children, _, span| {
             let height = arguments["h"].as_number(span.clone())?;
 
             let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
             let GeometryTableEntry::CrossSection(cross_section) = geom
             else { return Err(RuntimeError::new(RuntimeErrorKind::Requires2DGeometry, span.clone())) };
 
-            Ok((GeometryTableEntry::Manifold(Manifold::extrude(cross_section.polygons(), height)), disp))
+            let extrusion = interpreter.compute_with_timeout("linear_extrude", &span, move || Manifold::extrude(cross_section.polygons(), height))?
+                .map_err(|error| RuntimeError::new(RuntimeErrorKind::GeometryError(error), span))?;
+            Ok((GeometryTableEntry::Manifold(extrusion), disp))
         },
     }
 }
 
 fn rotate_extrude_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "rotate_extrude",
+        summary: "Revolves its 2D children around the Z axis into 3D, with their Y axis pointing upwards.",
+        parameter_docs: &[("angle", "How far to revolve, in degrees, up to a full 360.")],
         parameters: EvaluatedParameters::new(
             vec![],
             vec![("angle".to_owned(), Object::Number(360.0))],
         ),
-        action: &|interpreter, arguments, children, span| {
+        action: &|interpreter, arguments, children, _, span| {
             let angle = arguments["angle"].as_number(span.clone())?;
+            if !(angle > 0.0 && angle <= 360.0) {
+                return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                    format!("angle must be more than 0 and at most 360, got {angle}")
+                ), span));
+            }
 
             let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
             let GeometryTableEntry::CrossSection(cross_section) = geom
             else { return Err(RuntimeError::new(RuntimeErrorKind::Requires2DGeometry, span.clone())) };
 
-            Ok((GeometryTableEntry::Manifold(Manifold::revolve(cross_section.polygons(), interpreter.circle_segments, angle)), disp))
+            let segments = interpreter.segments();
+            let revolution = interpreter.compute_with_timeout("rotate_extrude", &span, move || Manifold::revolve(cross_section.polygons(), segments, angle))?
+                .map_err(|error| RuntimeError::new(RuntimeErrorKind::GeometryError(error), span))?;
+            Ok((GeometryTableEntry::Manifold(revolution), disp))
         },
     }
 }
 
 fn rotate_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "rotate",
+        summary: "Rotates its children.",
+        parameter_docs: &[("v", "Angles to rotate around the X, Y and Z axes, in degrees - or a single angle for 2D children.")],
         parameters: EvaluatedParameters::required(vec!["v".to_owned()]),
-        action: &|interpreter, arguments, children, span| {
-            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
-
-            Ok((match geom {
-                GeometryTableEntry::Manifold(manifold) => {
-                    let (x, y, z) = arguments["v"].as_3d_vector(span.clone())?;
-                    GeometryTableEntry::Manifold(manifold.rotate(x, y, z))
-                }
-                GeometryTableEntry::CrossSection(cross_section) => {
-                    let angle = arguments["v"].as_number(span.clone())?;
-                    GeometryTableEntry::CrossSection(cross_section.rotate(angle))
-                }
-            }, disp))
+        action: &|interpreter, arguments, children, _, span| {
+            dimensional_transform(
+                interpreter, &arguments, children, span,
+                |manifold, v, v_span| {
+                    let (x, y, z) = v.as_3d_vector(v_span)?;
+                    Ok(manifold.rotate(x, y, z))
+                },
+                |cross_section, v, v_span| Ok(cross_section.rotate(v.as_number(v_span)?)),
+                Some(|v, v_span| {
+                    let (x, y, z) = v.as_3d_vector(v_span)?;
+                    Ok(Matrix3x4::rotation(x, y, z))
+                }),
+            )
         },
     }
 }
 
 fn scale_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "scale",
+        summary: "Scales its children.",
+        parameter_docs: &[("v", "Factors to scale by along X, Y and Z - or X and Y for 2D children.")],
         parameters: EvaluatedParameters::required(vec!["v".to_owned()]),
-        action: &|interpreter, arguments, children, span| {
-            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
+        action: &|interpreter, arguments, children, _, span| {
+            // Only moving and rotating keep instances, since they're cheap to apply to a mesh
+            dimensional_transform(
+                interpreter, &arguments, children, span,
+                |manifold, v, v_span| {
+                    let (x, y, z) = v.as_3d_vector(v_span)?;
+                    Ok(manifold.scale(x, y, z))
+                },
+                |cross_section, v, v_span| {
+                    let (x, y) = v.as_2d_vector(v_span)?;
+                    Ok(cross_section.scale(x, y))
+                },
+                None,
+            )
+        },
+    }
+}
 
-            Ok((match geom {
-                GeometryTableEntry::Manifold(manifold) => {
-                    let (x, y, z) = arguments["v"].as_3d_vector(span.clone())?;
-                    GeometryTableEntry::Manifold(manifold.scale(x, y, z))
-                }
-                GeometryTableEntry::CrossSection(cross_section) => {
-                    let (x, y) = arguments["v"].as_2d_vector(span.clone())?;
-                    GeometryTableEntry::CrossSection(cross_section.scale(x, y))
-                }
-            }, disp))
+/// Scales its children to fit a size, like OpenSCAD's `resize`.
+fn resize_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "resize",
+        summary: "Scales its children so that their bounding box is a particular size.",
+        parameter_docs: &[
+            ("v", "The size along X, Y and Z - or X and Y for 2D children. Axes which are 0 are left as they are."),
+            ("auto", "Whether axes which are 0 are scaled as much as the first axis which isn't, rather than being left as they are."),
+        ],
+        parameters: EvaluatedParameters {
+            required: vec!["v".to_owned()],
+            optional: vec![],
+            optional_named_only: vec![("auto".to_owned(), Object::Boolean(false))],
+        },
+        action: &|interpreter, arguments, children, _, span| {
+            let auto = arguments["auto"].as_boolean(interpreter.argument_span("auto", &span))?;
+            dimensional_transform(
+                interpreter, &arguments, children, span,
+                |manifold, v, v_span| {
+                    let (x, y, z) = v.as_3d_vector(v_span.clone())?;
+                    let size = if manifold.is_empty() { [0.0; 3] } else {
+                        let size = manifold.bounding_box().size();
+                        [size.x, size.y, size.z]
+                    };
+                    let [x, y, z] = resize_factors(size, [x, y, z], auto, &v_span)?;
+                    Ok(manifold.scale(x, y, z))
+                },
+                |cross_section, v, v_span| {
+                    let (x, y) = v.as_2d_vector(v_span.clone())?;
+                    let size = if cross_section.is_empty() { [0.0; 2] } else {
+                        let size = cross_section.bounding_rectangle().size();
+                        [size.x, size.y]
+                    };
+                    let [x, y] = resize_factors(size, [x, y], auto, &v_span)?;
+                    Ok(cross_section.scale(x, y))
+                },
+                None,
+            )
         },
     }
 }
 
+/// The factors to scale by along each axis, to make geometry of `size` into `target` for `resize`.
+fn resize_factors<const N: usize>(size: [f64; N], target: [f64; N], auto: bool, span: &InputSourceSpan) -> Result<[f64; N], RuntimeError> {
+    const AXES: [&str; 3] = ["X", "Y", "Z"];
+
+    for axis in 0..N {
+        if !(target[axis].is_finite() && target[axis] >= 0.0) {
+            return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                format!("size along {} must be positive, or 0 to leave it as it is, got {}", AXES[axis], target[axis])
+            ), span.clone()));
+        }
+
+        // There's nothing to scale up
+        if target[axis] > 0.0 && size[axis] <= 0.0 {
+            return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                format!("cannot resize along {}, because the geometry has no size along it", AXES[axis])
+            ), span.clone()));
+        }
+    }
+
+    let unchanged_factor = (0..N)
+        .find(|axis| auto && target[*axis] > 0.0)
+        .map_or(1.0, |axis| target[axis] / size[axis]);
+    Ok(std::array::from_fn(|axis|
+        if target[axis] > 0.0 { target[axis] / size[axis] } else { unchanged_factor }
+    ))
+}
+
 fn mirror_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "mirror",
+        summary: "Mirrors its children across a plane through the origin.",
+        parameter_docs: &[("v", "The normal of the plane, as a vector of X, Y and Z - or X and Y for 2D children.")],
         parameters: EvaluatedParameters::required(vec!["v".to_owned()]),
-        action: &|interpreter, arguments, children, span| {
-            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
-
-            Ok((match geom {
-                GeometryTableEntry::Manifold(manifold) => {
-                    let (x, y, z) = arguments["v"].as_3d_vector(span.clone())?;
-                    GeometryTableEntry::Manifold(manifold.mirror(x, y, z))
-                }
-                GeometryTableEntry::CrossSection(cross_section) => {
-                    let (x, y) = arguments["v"].as_2d_vector(span.clone())?;
-                    GeometryTableEntry::CrossSection(cross_section.mirror(x, y))
-                }
-            }, disp))
+        action: &|interpreter, arguments, children, _, span| {
+            dimensional_transform(
+                interpreter, &arguments, children, span,
+                |manifold, v, v_span| {
+                    let (x, y, z) = v.as_3d_vector(v_span)?;
+                    Ok(manifold.mirror(x, y, z))
+                },
+                |cross_section, v, v_span| {
+                    let (x, y) = v.as_2d_vector(v_span)?;
+                    Ok(cross_section.mirror(x, y))
+                },
+                None,
+            )
         },
     }
 }
 
 fn buffer_definition() -> OperatorDefinition {
     OperatorDefinition {
+        name: "buffer",
+        summary: "Makes its children virtual, so that they can be measured or used by other operators without appearing in the model.",
+        parameter_docs: &[],
         parameters: EvaluatedParameters::empty(),
-        action: &|interpreter, _, children, span| {
+        action: &|interpreter, _, children, _, span| {
             let (geom, _) = interpreter.manifold_table.remove_many_into_union(children, span)?;
             Ok((geom, GeometryDisposition::Virtual))
         },
     }
 }
 
+/// Sets the tolerance of its children, so that booleans involving them can clean up features
+/// smaller than the tolerance, such as slivers left when faces are coincident.
+/// 
+/// Only one side of a boolean needs a tolerance, so a cutter which is flush with a surface can be
+/// given one - although making the cutter slightly oversized is usually more reliable.
+fn tolerance_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "tolerance",
+        summary: "Sets the tolerance of its 3D children, so that booleans involving them can clean up features smaller than it.",
+        parameter_docs: &[("t", "The tolerance.")],
+        parameters: EvaluatedParameters::required(vec!["t".to_owned()]),
+        action: &|interpreter, arguments, children, _, span| {
+            let tolerance = arguments["t"].as_number(span.clone())?;
+
+            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
+            let GeometryTableEntry::Manifold(manifold) = geom.realise()
+            else { return Err(RuntimeError::new(RuntimeErrorKind::Requires3DGeometry, span)) };
+
+            Ok((GeometryTableEntry::Manifold(manifold.set_tolerance(tolerance)), disp))
+        },
+    }
+}
+
+/// Repeats its children `count` times, each copy moved by `offset` from the last.
+/// 
+/// 3D copies are kept as instances of the same manifold, which is much cheaper than a union of
+/// separate manifolds.
+fn linear_pattern_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "linear_pattern",
+        summary: "Repeats its children, with each copy moved from the last.",
+        parameter_docs: &[("count", "Number of copies, including the original."), ("offset", "How far to move each copy from the last.")],
+        parameters: EvaluatedParameters::required(vec!["count".to_owned(), "offset".to_owned()]),
+        action: &|interpreter, arguments, children, _, span| {
+            let count = pattern_count(&arguments["count"], span.clone())?;
+
+            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
+            if let GeometryTableEntry::CrossSection(cross_section) = geom {
+                let (x, y) = arguments["offset"].as_2d_vector(span)?;
+                let pattern = (0..count)
+                    .map(|i| cross_section.translate(x * i as f64, y * i as f64))
+                    .fold(CrossSection::new(), |result, copy| result.union(&copy));
+                return Ok((GeometryTableEntry::CrossSection(pattern), disp))
+            }
+
+            let (x, y, z) = arguments["offset"].as_3d_vector(span)?;
+            let transforms = (0..count)
+                .map(|i| Matrix3x4::translation(x * i as f64, y * i as f64, z * i as f64))
+                .collect::<Vec<_>>();
+            Ok((instance_pattern(geom, &transforms), disp))
+        },
+    }
+}
+
+/// Repeats its children `count` times around the Z axis, with copies spread evenly around `angle`
+/// degrees - by default, a full circle.
+/// 
+/// Like `linear_pattern`, 3D copies are kept as instances.
+fn circular_pattern_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "circular_pattern",
+        summary: "Repeats its children around the Z axis, with the copies spread evenly.",
+        parameter_docs: &[("count", "Number of copies, including the original."), ("angle", "The angle to spread the copies around, in degrees.")],
+        parameters: EvaluatedParameters::new(
+            vec!["count".to_owned()],
+            vec![("angle".to_owned(), Object::Number(360.0))],
+        ),
+        action: &|interpreter, arguments, children, _, span| {
+            let count = pattern_count(&arguments["count"], span.clone())?;
+            let step = arguments["angle"].as_number(span.clone())? / count as f64;
+
+            let (geom, disp) = interpreter.manifold_table.remove_many_into_union(children, span.clone())?;
+            if let GeometryTableEntry::CrossSection(cross_section) = geom {
+                let pattern = (0..count)
+                    .map(|i| cross_section.rotate(step * i as f64))
+                    .fold(CrossSection::new(), |result, copy| result.union(&copy));
+                return Ok((GeometryTableEntry::CrossSection(pattern), disp))
+            }
+
+            let transforms = (0..count)
+                .map(|i| Matrix3x4::rotation(0.0, 0.0, step * i as f64))
+                .collect::<Vec<_>>();
+            Ok((instance_pattern(geom, &transforms), disp))
+        },
+    }
+}
+
+/// Repeat 3D geometry at each of the given transformations, as instances. Repeating instances
+/// repeats each of their copies, so the result is still one set of instances.
+fn instance_pattern(geom: GeometryTableEntry, transforms: &[Matrix3x4]) -> GeometryTableEntry {
+    let instances = match geom {
+        GeometryTableEntry::Instanced(instances) => instances,
+        GeometryTableEntry::Manifold(manifold) => Instances::new(manifold, vec![Matrix3x4::identity()]),
+        GeometryTableEntry::CrossSection(_) => panic!("`instance_pattern` called on 2D geometry"),
+    };
+    GeometryTableEntry::Instanced(instances.repeated(transforms))
+}
+
+/// Get the number of copies the pattern operators should make.
+fn pattern_count(count: &Object, span: InputSourceSpan) -> Result<usize, RuntimeError> {
+    let count = count.as_number(span.clone())?;
+    if count < 1.0 || count.fract() != 0.0 {
+        return Err(RuntimeError::new(
+            RuntimeErrorKind::IncorrectType { expected: "positive whole number".to_owned(), actual: count.to_string() },
+            span,
+        ));
+    }
+    Ok(count as usize)
+}
+
+/// Marks its children to be added back by the `difference` it's directly inside, after the
+/// subtraction, rather than being subtracted themselves. This can re-add features like bosses
+/// inside a pocket which was just cut.
+/// 
+/// The interpreter makes sure that this is only used directly inside `difference`.
+fn keep_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "keep",
+        summary: "Marks its children to be added back by the `difference` it's directly inside, rather than being subtracted.",
+        parameter_docs: &[],
+        parameters: EvaluatedParameters::empty(),
+        action: &|interpreter, _, children, _, span| {
+            if children.is_empty() {
+                return Err(RuntimeError::new(RuntimeErrorKind::ChildrenExpected, span))
+            }
+
+            let result = interpreter.manifold_table.remove_many_into_union(children, span)?;
+            interpreter.operator_result_kept = true;
+            Ok(result)
+        },
+    }
+}
+
+/// Names its children, so that they're kept separate from other geometry when exported.
+fn part_definition() -> OperatorDefinition {
+    OperatorDefinition {
+        name: "part",
+        summary: "Names its children, so that they're kept separate from other geometry when exported.",
+        parameter_docs: &[("name", "Name of the part.")],
+        parameters: EvaluatedParameters::required(vec!["name".to_owned()]),
+        action: &|interpreter, arguments, children, _, span| {
+            let name = arguments["name"].as_string(span.clone())?.to_owned();
+            let result = interpreter.manifold_table.remove_many_into_union(children, span)?;
+
+            interpreter.operator_result_name = Some(name);
+            Ok(result)
+        },
+    }
+}
+
+/// Check that all non-empty children are either 2D or 3D, pointing at the first one which isn't.
+fn check_child_dimensions(interpreter: &Interpreter, children: &[GeometryTableIndex], child_spans: &[InputSourceSpan]) -> Result<(), RuntimeError> {
+    let entries = children.iter()
+        .zip(child_spans)
+        .map(|(child, span)| Ok((interpreter.manifold_table.get(child, span.clone())?, span)))
+        .collect::<Result<Vec<_>, RuntimeError>>()?;
+    let mut non_empty_children = entries.into_iter()
+        .filter(|(entry, _)| !entry.is_empty());
+
+    let Some((first, _)) = non_empty_children.next()
+    else { return Ok(()) };
+    let expected_3d = first.is_3d();
+
+    let Some((_, mismatched_span)) = non_empty_children.find(|(entry, _)| entry.is_3d() != expected_3d)
+    else { return Ok(()) };
+
+    let help = if expected_3d {
+        "this child is 2D, but the first child is 3D - use `linear_extrude` to make it 3D"
+    } else {
+        "this child is 3D, but the first child is 2D - use `linear_extrude` on the 2D children to make them 3D too"
+    };
+    Err(RuntimeError::new(RuntimeErrorKind::MixedGeometryDimensions, mismatched_span.clone()).with_help(help))
+}
+
+/// The names of all built-in operators, for suggesting alternatives to misspelled names.
+pub const BUILTIN_OPERATOR_NAMES: &[&str] = &[
+    "translate", "union", "difference", "intersection", "linear_extrude", "rotate_extrude", "rotate",
+    "scale", "mirror", "buffer", "part", "tolerance", "linear_pattern", "circular_pattern", "keep",
+    "hull", "offset", "resize",
+];
+
 /// Get the implementation for a specific built-in operator.
 /// 
 /// Returns [`None`] if no such operator exists.
@@ -185,12 +701,21 @@ pub fn get_builtin_operator(name: &str) -> Option<OperatorDefinition> {
         "translate" => Some(translate_definition()),
         "union" => Some(union_definition()),
         "difference" => Some(difference_definition()),
+        "intersection" => Some(intersection_definition()),
         "linear_extrude" => Some(linear_extrude_definition()),
         "rotate_extrude" => Some(rotate_extrude_definition()),
         "rotate" => Some(rotate_definition()),
         "scale" => Some(scale_definition()),
+        "resize" => Some(resize_definition()),
         "mirror" => Some(mirror_definition()),
         "buffer" => Some(buffer_definition()),
+        "part" => Some(part_definition()),
+        "tolerance" => Some(tolerance_definition()),
+        "linear_pattern" => Some(linear_pattern_definition()),
+        "circular_pattern" => Some(circular_pattern_definition()),
+        "keep" => Some(keep_definition()),
+        "hull" => Some(hull_definition()),
+        "offset" => Some(offset_definition()),
 
         _ => None,
     }
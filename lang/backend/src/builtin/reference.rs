@@ -0,0 +1,121 @@
+use crate::{EvaluatedParameters, builtin::{BUILTIN_MATH_NAMES, BUILTIN_MODULE_NAMES, BUILTIN_OPERATOR_NAMES, get_builtin_module, get_builtin_operator}, object::Object};
+
+/// Whether a built-in is a module or an operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinKind {
+    Module,
+    Operator,
+}
+
+impl BuiltinKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            BuiltinKind::Module => "module",
+            BuiltinKind::Operator => "operator",
+        }
+    }
+}
+
+/// Reference documentation for a built-in module or operator, taken from its definition.
+#[derive(Debug, Clone)]
+pub struct BuiltinDoc {
+    pub name: &'static str,
+    pub kind: BuiltinKind,
+    pub summary: &'static str,
+
+    /// Every parameter, in the order they'd be expected to be specified - positional ones first.
+    pub parameters: Vec<ParameterDoc>,
+}
+
+/// Reference documentation for one parameter of a built-in.
+#[derive(Debug, Clone)]
+pub struct ParameterDoc {
+    pub name: String,
+    pub summary: &'static str,
+
+    /// The default value as it would be written in source, if the parameter is optional.
+    pub default: Option<String>,
+
+    /// Whether the argument can only be given by name, like `d` of `cylinder`.
+    pub named_only: bool,
+}
+
+impl BuiltinDoc {
+    fn new(name: &'static str, kind: BuiltinKind, summary: &'static str, parameters: &EvaluatedParameters, parameter_docs: &[(&str, &'static str)]) -> Self {
+        let summary_for = |name: &str| parameter_docs.iter()
+            .find(|(documented, _)| *documented == name)
+            .map(|(_, summary)| *summary)
+            .unwrap_or_default();
+
+        let required = parameters.required.iter()
+            .map(|name| ParameterDoc { name: name.clone(), summary: summary_for(name), default: None, named_only: false });
+        let optional = parameters.optional.iter()
+            .map(|(name, default)| (name, default, false))
+            .chain(parameters.optional_named_only.iter().map(|(name, default)| (name, default, true)))
+            .map(|(name, default, named_only)| ParameterDoc {
+                name: name.clone(),
+                summary: summary_for(name),
+                default: Some(describe_default(default)),
+                named_only,
+            });
+
+        Self { name, kind, summary, parameters: required.chain(optional).collect() }
+    }
+
+    /// How the built-in would be called, with the default of each optional parameter, like
+    /// `circle(r = null, d = null)`.
+    pub fn signature(&self) -> String {
+        let parameters = self.parameters.iter()
+            .map(|parameter| match &parameter.default {
+                Some(default) => format!("{} = {default}", parameter.name),
+                None => parameter.name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{}({parameters})", self.name)
+    }
+}
+
+/// Write a default value as it would appear in source. Defaults are only ever simple values.
+fn describe_default(default: &Object) -> String {
+    match default {
+        Object::Null => "null".to_owned(),
+        Object::Number(n) => n.to_string(),
+        Object::Boolean(b) => b.to_string(),
+        Object::String(s) => format!("{s:?}"),
+        other => panic!("built-in default cannot be written: {}", other.describe_type()),
+    }
+}
+
+/// Documentation for every built-in module and operator, in the order they're listed in
+/// [`BUILTIN_MODULE_NAMES`], [`BUILTIN_MATH_NAMES`] and [`BUILTIN_OPERATOR_NAMES`].
+pub fn all_builtins() -> impl Iterator<Item = BuiltinDoc> {
+    let modules = BUILTIN_MODULE_NAMES.iter().chain(BUILTIN_MATH_NAMES)
+        .map(|name| {
+            let module = get_builtin_module(name).expect("listed module does not exist");
+            BuiltinDoc::new(module.name, BuiltinKind::Module, module.summary, &module.parameters, module.parameter_docs)
+        });
+    let operators = BUILTIN_OPERATOR_NAMES.iter()
+        .map(|name| {
+            let operator = get_builtin_operator(name).expect("listed operator does not exist");
+            BuiltinDoc::new(operator.name, BuiltinKind::Operator, operator.summary, &operator.parameters, operator.parameter_docs)
+        });
+
+    modules.chain(operators)
+}
+
+/// Format documentation for built-ins as a plain-text reference, with each built-in's signature
+/// followed by a description of it and its parameters.
+pub fn format_builtin_reference(builtins: impl IntoIterator<Item = BuiltinDoc>) -> String {
+    builtins.into_iter()
+        .map(|builtin| {
+            let mut entry = format!("{} {}\n    {}\n", builtin.kind.describe(), builtin.signature(), builtin.summary);
+            for parameter in &builtin.parameters {
+                let named_only = if parameter.named_only { " (by name only)" } else { "" };
+                entry.push_str(&format!("    - {}{named_only}: {}\n", parameter.name, parameter.summary));
+            }
+            entry
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,20 +1,113 @@
-use std::{error::Error, fmt::Display, ops::RangeInclusive};
+use std::{error::Error, fmt::Display, ops::RangeInclusive, path::PathBuf, rc::Rc, time::Duration};
 
-use miette::Diagnostic;
-use yascad_frontend::InputSourceSpan;
+use manifold_rs::ManifoldError;
+use miette::{Diagnostic, LabeledSpan};
+use yascad_frontend::{IncludeKind, InputSourceSpan, SourceDiagnostic, truncate_for_display};
 
-#[derive(Debug, Clone, PartialEq, Eq, Diagnostic)]
+use crate::FileAccessPolicy;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RuntimeError {
     pub kind: RuntimeErrorKind,
-
-    #[source_code]
-    #[label]
     pub span: InputSourceSpan,
+
+    /// The user-defined modules, operators and functions which were executing when the error
+    /// occurred, innermost first.
+    ///
+    /// This, `help` and `context` are boxed to keep [`RuntimeError`] small, since it's returned
+    /// everywhere.
+    pub trace: Box<[Frame]>,
+
+    /// A suggestion of how to fix the error, if there's a likely one.
+    pub help: Option<Box<str>>,
+
+    /// Other parts of the source which help to explain the error.
+    pub context: Option<Box<ErrorContext>>,
+}
+
+/// Parts of the source related to a [`RuntimeError`], which are labelled alongside its own span.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The whole statement which was being evaluated when the error occurred, since the error's
+    /// own span might only cover part of it.
+    pub statement: Option<InputSourceSpan>,
+
+    /// The definition of the user-defined module or operator being called, if the error is about
+    /// how it was called.
+    pub definition: Option<InputSourceSpan>,
 }
 
 impl RuntimeError {
     pub fn new(kind: RuntimeErrorKind, span: InputSourceSpan) -> Self {
-        Self { kind, span }
+        Self { kind, span, trace: Box::new([]), help: None, context: None }
+    }
+
+    /// Attach a suggestion of how to fix the error.
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into().into_boxed_str());
+        self
+    }
+
+    /// Record the statement which was being evaluated when this error occurred, unless one has
+    /// already been recorded - the innermost statement is the most useful.
+    pub fn with_statement(mut self, statement: &InputSourceSpan) -> Self {
+        let context = self.context.get_or_insert_default();
+        if context.statement.is_none() {
+            context.statement = Some(statement.clone());
+        }
+        self
+    }
+
+    /// Point to the definition of the module or operator which was called incorrectly.
+    ///
+    /// Labels can only be drawn on the error's own source, so this does nothing if the definition
+    /// is in a different one.
+    pub fn with_definition(mut self, definition: &InputSourceSpan) -> Self {
+        if Rc::ptr_eq(&definition.source, &self.span.source) {
+            self.context.get_or_insert_default().definition = Some(definition.clone());
+        }
+        self
+    }
+
+    /// Record that this error propagated out of a call to a user-defined module or operator.
+    pub fn with_frame(mut self, frame: Frame) -> Self {
+        let mut trace = std::mem::take(&mut self.trace).into_vec();
+        trace.push(frame);
+        self.trace = trace.into_boxed_slice();
+        self
+    }
+}
+
+// Implemented by hand because the derive can't use a boxed span as an optional label
+impl Diagnostic for RuntimeError {
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(&self.span)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let primary = LabeledSpan::underline(self.span.clone());
+        let context = self.context.as_deref();
+        let statement = context.and_then(|context| context.statement.as_ref())
+            // Don't repeat the primary label if the error already covers the whole statement
+            .filter(|statement| **statement != self.span)
+            .map(|statement| LabeledSpan::new_with_span(
+                Some("while evaluating this statement".to_owned()),
+                statement.clone(),
+            ));
+
+        let definition = context.and_then(|context| context.definition.as_ref())
+            .filter(|definition| **definition != self.span)
+            .map(|definition| LabeledSpan::new_with_span(Some("defined here".to_owned()), definition.clone()));
+
+        Some(Box::new(std::iter::once(primary).chain(statement).chain(definition)))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.help.as_ref().map(|help| Box::new(help) as Box<dyn Display>)
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        Some(Box::new(self.trace.iter().map(|frame| frame as &dyn Diagnostic)))
     }
 }
 
@@ -26,6 +119,63 @@ impl Display for RuntimeError {
 }
 impl Error for RuntimeError {}
 
+impl SourceDiagnostic for RuntimeError {
+    fn span(&self) -> &InputSourceSpan {
+        &self.span
+    }
+}
+
+/// A call to a user-defined module, operator or function, or an included file, as part of a
+/// [`RuntimeError`]'s trace.
+#[derive(Debug, Clone, PartialEq, Eq, Diagnostic)]
+#[diagnostic(severity(Advice))]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub name: String,
+
+    /// Where the definition was called from, or the file was included from.
+    #[source_code]
+    #[label("called here")]
+    pub span: InputSourceSpan,
+}
+
+impl Frame {
+    pub fn new(kind: FrameKind, name: impl Into<String>, span: InputSourceSpan) -> Self {
+        Self { kind, name: name.into(), span }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            FrameKind::Include => write!(f, "in \"{}\", included from line {}", self.name, self.span.start_line()),
+            _ => write!(f, "in {} \"{}\", called from line {}", self.kind, self.name, self.span.start_line()),
+        }
+    }
+}
+impl Error for Frame {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    Module,
+    Operator,
+    Function,
+
+    /// A file loaded by `include` or `use`, named by the path it was given as.
+    Include,
+}
+
+impl Display for FrameKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameKind::Module => write!(f, "module"),
+            FrameKind::Operator => write!(f, "operator"),
+            FrameKind::Function => write!(f, "function"),
+            FrameKind::Include => write!(f, "include"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RuntimeErrorKind {
     IncorrectType { expected: String, actual: String },
@@ -38,15 +188,70 @@ pub enum RuntimeErrorKind {
     MissingNamedArguments(Vec<String>),
     NamedArgumentRepeatsPositionalArgument(String),
     IncorrectVectorLength { expected: RangeInclusive<usize>, actual: usize },
+    IncorrectVectorElementType { index: usize, expected: String, actual: String },
     MixedGeometryDisposition,
     MixedGeometryDimensions,
     DuplicateName(String),
+
+    /// A `set` of a binding from outside the module or operator body it's in.
+    ReassignmentOutsideDefinition(String),
     ItReferenceInvalid,
     ItReferenceUnsupportedNotOneChild,
     ChildrenExpected,
     ChildrenInvalid,
+    ChildIndexOutOfRange { index: usize, count: usize },
+    BranchProducedNoGeometry,
     FlippedRange,
+
+    /// A range's step is zero, or goes away from its end rather than towards it.
+    InvalidRangeStep { zero: bool },
+
+    /// A range's start, step or end is infinite or not a number, like after dividing by zero.
+    NonFiniteRange,
+
+    /// A range would have more items than the limit.
+    RangeTooLong { limit: usize },
     Requires2DGeometry,
+    Requires3DGeometry,
+    GeometryNoLongerExists,
+    GeometryError(ManifoldError),
+    UnknownUnit(String),
+    UnitsDeclarationNotTopLevel,
+    DuplicateUnitsDeclaration,
+    IncludeNotTopLevel(IncludeKind),
+
+    /// User-defined modules, operators and functions have called each other more deeply than the
+    /// limit, which is most likely recursion that never stops.
+    CallDepthExceeded { limit: usize },
+
+    /// A file would include itself, directly or through other files. Holds the path as written.
+    IncludeCycle(String),
+
+    /// An included file couldn't be tokenized or parsed. The error's span points into that file.
+    IncludedSyntaxError(String),
+    FileReadError { path: PathBuf, reason: String },
+    FileAccessDenied { path: PathBuf, policy: FileAccessPolicy },
+    InvalidSurfaceData { row: Option<usize>, reason: String },
+
+    /// The points and faces given to `polyhedron`, or the triangles read by `import`, don't make a
+    /// valid closed manifold.
+    InvalidMesh(String),
+
+    /// The file given to `import` couldn't be read as an STL file.
+    InvalidStl(String),
+
+    /// The font file given to `text` couldn't be read as a TrueType font.
+    InvalidFont(String),
+    OperationTimedOut { operator: String, timeout: Duration },
+
+    /// The geometry built so far is estimated to take up more memory, in bytes, than the budget.
+    MemoryBudgetExceeded { budget: usize, estimate: usize },
+    DuplicatePrintClearance,
+    PrintClearanceAfterUse,
+    KeepOutsideDifference,
+
+    /// A call to `assert` whose condition was false, with the message it was given.
+    AssertionFailed { message: Option<String> },
 
     /// A generic error which can be triggered by user code.
     AssertionError(String),
@@ -56,17 +261,17 @@ impl Display for RuntimeErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             RuntimeErrorKind::IncorrectType { expected, actual } => write!(f, "type error - expected {expected}, got {actual}"),
-            RuntimeErrorKind::UndefinedIdentifier(id) => write!(f, "undefined identifier \"{id}\""),
-            RuntimeErrorKind::InvalidIdentifier { id, kind } => write!(f, "identifier \"{id}\" is a {kind}, which cannot be used here"),
-            RuntimeErrorKind::UndefinedField { ty, field } => write!(f, "{ty} object has no field \"{field}\""),
+            RuntimeErrorKind::UndefinedIdentifier(id) => write!(f, "undefined identifier \"{}\"", truncate_for_display(id)),
+            RuntimeErrorKind::InvalidIdentifier { id, kind } => write!(f, "identifier \"{}\" is a {kind}, which cannot be used here", truncate_for_display(id)),
+            RuntimeErrorKind::UndefinedField { ty, field } => write!(f, "{ty} object has no field \"{}\"", truncate_for_display(field)),
             RuntimeErrorKind::IncorrectArity { expected, actual } => {
                 write!(f, "incorrect number of positional arguments - expected ")?;
                 fmt_length_range(f, expected)?;
                 write!(f, ", got {actual}")?;
                 Ok(())
             },
-            RuntimeErrorKind::DuplicateNamedArgument(name) => write!(f, "argument \"{name}\" cannot be passed by name more than once"),
-            RuntimeErrorKind::UndefinedNamedArgument(name) => write!(f, "no argument named \"{name}\""),
+            RuntimeErrorKind::DuplicateNamedArgument(name) => write!(f, "argument \"{}\" cannot be passed by name more than once", truncate_for_display(name)),
+            RuntimeErrorKind::UndefinedNamedArgument(name) => write!(f, "no argument named \"{}\"", truncate_for_display(name)),
             RuntimeErrorKind::MissingNamedArguments(names) => {
                 if names.len() > 1 {
                     write!(f, "missing multiple arguments: {}", names.join(", "))
@@ -74,28 +279,76 @@ impl Display for RuntimeErrorKind {
                     write!(f, "missing argument \"{}\"", names[0])
                 }
             }
-            RuntimeErrorKind::NamedArgumentRepeatsPositionalArgument(name) => write!(f, "argument \"{name}\" has already been passed as a positional argument, so cannot be passed again by name"),
+            RuntimeErrorKind::NamedArgumentRepeatsPositionalArgument(name) => write!(f, "argument \"{}\" has already been passed as a positional argument, so cannot be passed again by name", truncate_for_display(name)),
             RuntimeErrorKind::IncorrectVectorLength { expected, actual } => {
                 write!(f, "incorrect vector length - expected ")?;
                 fmt_length_range(f, expected)?;
                 write!(f, ", got {actual}")?;
                 Ok(())
             },
+            RuntimeErrorKind::IncorrectVectorElementType { index, expected, actual } => write!(f, "type error - expected element {index} of vector to be {expected}, got {actual}"),
             RuntimeErrorKind::MixedGeometryDisposition => write!(f, "this operation tried to mix geometries of different dispositions"),
             RuntimeErrorKind::MixedGeometryDimensions => write!(f, "this operation tried to mix 2D and 3D geometry"),
-            RuntimeErrorKind::DuplicateName(id) => write!(f, "name \"{id}\" is already defined"),
+            RuntimeErrorKind::DuplicateName(id) => write!(f, "name \"{}\" is already defined", truncate_for_display(id)),
+            RuntimeErrorKind::ReassignmentOutsideDefinition(id) => write!(f, "binding \"{}\" is defined outside of this module or operator, so it can't be changed from within it", truncate_for_display(id)),
             RuntimeErrorKind::ItReferenceInvalid => write!(f, "cannot use `it` outside of operator target arguments"),
             RuntimeErrorKind::ItReferenceUnsupportedNotOneChild => write!(f, "`it` is not currently supported without exactly one operator child - consider using `union()` first"),
             RuntimeErrorKind::ChildrenInvalid => write!(f, "cannot use `children` outside of operator body"),
+            RuntimeErrorKind::ChildIndexOutOfRange { index, count: 1 } => write!(f, "no child at index {index} - there is only 1 child, at index 0"),
+            RuntimeErrorKind::ChildIndexOutOfRange { index, count } => write!(f, "no child at index {index} - there are {count} children, numbered from 0"),
             RuntimeErrorKind::ChildrenExpected => write!(f, "this operation requires at least one child"),
+            RuntimeErrorKind::BranchProducedNoGeometry => write!(f, "the selected branch produced no geometry for this operator to apply to"),
             RuntimeErrorKind::FlippedRange => write!(f, "end of range is lower than start"),
+            RuntimeErrorKind::InvalidRangeStep { zero: true } => write!(f, "step of range cannot be zero"),
+            RuntimeErrorKind::InvalidRangeStep { zero: false } => write!(f, "step of range is negative, so it would never reach the end - count down from a higher start instead"),
+            RuntimeErrorKind::NonFiniteRange => write!(f, "start, end and step of range must be finite numbers"),
+            RuntimeErrorKind::RangeTooLong { limit } => write!(f, "range would have more than {limit} items"),
             RuntimeErrorKind::Requires2DGeometry => write!(f, "this operation requires 2D geometry, but 3D was provided"),
+            RuntimeErrorKind::Requires3DGeometry => write!(f, "this operation requires 3D geometry, but 2D was provided"),
+            RuntimeErrorKind::GeometryNoLongerExists => write!(f, "this geometry no longer exists - it has already been used up, like by being the child of an operator"),
+            RuntimeErrorKind::GeometryError(error) => write!(f, "{error}"),
+            RuntimeErrorKind::UnknownUnit(unit) => write!(f, "unknown unit \"{unit}\" - expected one of: mm, cm, inch"),
+            RuntimeErrorKind::UnitsDeclarationNotTopLevel => write!(f, "`units` can only be declared at the top level"),
+            RuntimeErrorKind::DuplicateUnitsDeclaration => write!(f, "`units` has already been declared"),
+            RuntimeErrorKind::IncludeNotTopLevel(kind) => write!(f, "`{}` can only be used at the top level", kind.keyword()),
+            RuntimeErrorKind::CallDepthExceeded { limit } => write!(f, "calls are nested more than {limit} deep"),
+            RuntimeErrorKind::IncludeCycle(path) => write!(f, "\"{path}\" is already being included, so including it again would never finish"),
+            RuntimeErrorKind::IncludedSyntaxError(message) => write!(f, "{message}"),
+            RuntimeErrorKind::DuplicatePrintClearance => write!(f, "`print_clearance` has already been set"),
+            RuntimeErrorKind::PrintClearanceAfterUse => write!(f, "`print_clearance` must be set before `hole_d` or `hole_r` are used"),
+            RuntimeErrorKind::KeepOutsideDifference => write!(f, "`keep` can only be used directly inside `difference`"),
+            RuntimeErrorKind::FileReadError { path, reason } => write!(f, "could not read \"{}\": {reason}", path.display()),
+            RuntimeErrorKind::FileAccessDenied { path, policy } => write!(f, "not allowed to read \"{}\", because {policy}", path.display()),
+            RuntimeErrorKind::InvalidSurfaceData { row: Some(row), reason } => write!(f, "invalid surface data on row {row}: {reason}"),
+            RuntimeErrorKind::InvalidSurfaceData { row: None, reason } => write!(f, "invalid surface data: {reason}"),
+            RuntimeErrorKind::InvalidMesh(reason) => write!(f, "invalid mesh: {reason}"),
+            RuntimeErrorKind::InvalidStl(reason) => write!(f, "invalid STL file: {reason}"),
+            RuntimeErrorKind::InvalidFont(reason) => write!(f, "invalid font: {reason}"),
+            RuntimeErrorKind::OperationTimedOut { operator, timeout } => write!(f, "operator \"{operator}\" was stopped after taking longer than {timeout:?}"),
+            RuntimeErrorKind::MemoryBudgetExceeded { budget, estimate } => {
+                write!(f, "geometry is estimated to need ")?;
+                fmt_memory(f, *estimate)?;
+                write!(f, " of memory, which is over the budget of ")?;
+                fmt_memory(f, *budget)
+            },
+
+            RuntimeErrorKind::AssertionFailed { message: Some(message) } => write!(f, "assertion failed: {message}"),
+            RuntimeErrorKind::AssertionFailed { message: None } => write!(f, "assertion failed"),
 
             RuntimeErrorKind::AssertionError(err) => write!(f, "{err}"),
         }
     }
 }
 
+/// Write a number of bytes in megabytes, or kilobytes if it's less than one megabyte.
+fn fmt_memory(f: &mut std::fmt::Formatter<'_>, bytes: usize) -> std::fmt::Result {
+    if bytes < 1_000_000 {
+        write!(f, "{:.1} KB", bytes as f64 / 1_000.0)
+    } else {
+        write!(f, "{:.1} MB", bytes as f64 / 1_000_000.0)
+    }
+}
+
 fn fmt_length_range(f: &mut std::fmt::Formatter<'_>, range: &RangeInclusive<usize>) -> std::fmt::Result {
     if range.start() == range.end() {
         write!(f, "{}", range.start())
@@ -0,0 +1,371 @@
+use std::path::Path;
+
+use manifold_rs::Vec2;
+use ttf_parser::{Face, GlyphId, OutlineBuilder};
+
+/// A TrueType font, read from a `.ttf` file for the `text` module.
+///
+/// Only what's needed to outline text is used: which glyph each character uses, the shape of each
+/// glyph, and how far along to move after it. There's no kerning or shaping.
+#[derive(Debug, Clone)]
+pub struct Font {
+    /// The whole font file. It's parsed again for each piece of text, which is cheap, since tables
+    /// are only read as they're needed.
+    data: Vec<u8>,
+}
+
+/// Why a font couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontError {
+    pub reason: String,
+}
+
+impl FontError {
+    fn new(reason: impl Into<String>) -> Self {
+        Self { reason: reason.into() }
+    }
+}
+
+/// How many contours one glyph can have, including those of its components. Composite glyphs can
+/// use each other many times over, so without this, a small font could produce a huge outline.
+const MAX_GLYPH_CONTOURS: usize = 512;
+
+/// Fonts to use when `text` isn't given one, in the order they're tried. Each comes with most
+/// installs of some platform.
+const DEFAULT_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// The path of a font which comes with the system, if one of [`DEFAULT_FONT_PATHS`] exists.
+pub fn default_font_path() -> Option<&'static str> {
+    DEFAULT_FONT_PATHS.iter().copied().find(|path| Path::new(path).is_file())
+}
+
+impl Font {
+    pub fn parse(data: Vec<u8>) -> Result<Self, FontError> {
+        match data.get(0..4) {
+            None => return Err(FontError::new("the font file ends too early")),
+            Some([0x00, 0x01, 0x00, 0x00] | b"true") => {}
+            Some(b"OTTO") => return Err(FontError::new("fonts with PostScript outlines aren't supported, only ones with TrueType outlines")),
+            Some(b"ttcf") => return Err(FontError::new("font collections aren't supported - use a file with only one font in it")),
+            Some(_) => return Err(FontError::new("not a TrueType font")),
+        }
+
+        let font = Self { data };
+        let face = font.face()?;
+        let tables = face.tables();
+        for (tag, present) in [("cmap", tables.cmap.is_some()), ("glyf", tables.glyf.is_some()), ("hmtx", tables.hmtx.is_some())] {
+            if !present {
+                return Err(FontError::new(format!("the font has no \"{tag}\" table")));
+            }
+        }
+        Ok(font)
+    }
+
+    fn face(&self) -> Result<Face<'_>, FontError> {
+        Face::parse(&self.data, 0).map_err(|error| FontError::new(format!("the font is malformed: {error}")))
+    }
+
+    /// The outline of a line of text, as polygons in the same units as `size`, which is the height
+    /// of the font's em square. The text starts at the origin, on the baseline.
+    ///
+    /// Outlines are counter-clockwise and holes are clockwise, ready to be made into a
+    /// cross-section. Each curve is split into `curve_segments` straight lines.
+    pub fn outline_text(&self, text: &str, size: f64, curve_segments: usize) -> Result<Vec<Vec<Vec2<f64>>>, FontError> {
+        let face = self.face()?;
+        let scale = size / face.units_per_em() as f64;
+        let mut polygons = vec![];
+        let mut pen_x = 0.0;
+
+        for char in text.chars() {
+            // Characters which the font doesn't have use its "missing character" glyph
+            let glyph = face.glyph_index(char).unwrap_or(GlyphId(0));
+
+            let mut outline = GlyphOutline { contours: vec![], curve_segments: curve_segments.max(1), too_many_contours: false };
+            // Glyphs with nothing to draw, like spaces, have no outline at all
+            if face.outline_glyph(glyph, &mut outline).is_some() {
+                if outline.too_many_contours {
+                    return Err(FontError::new(format!("a glyph has more than {MAX_GLYPH_CONTOURS} contours")));
+                }
+
+                for mut polygon in outline.contours {
+                    if polygon.len() < 3 {
+                        continue;
+                    }
+
+                    // TrueType outlines go clockwise, which is the opposite way to Manifold
+                    polygon.reverse();
+                    polygons.push(polygon.into_iter()
+                        .map(|point| Vec2::new((point.x + pen_x) * scale, point.y * scale))
+                        .collect());
+                }
+            }
+            pen_x += face.glyph_hor_advance(glyph).unwrap_or(0) as f64;
+        }
+
+        Ok(polygons)
+    }
+}
+
+/// Collects a glyph's contours as polygons in font units, splitting each curve into
+/// `curve_segments` straight lines.
+struct GlyphOutline {
+    contours: Vec<Vec<Vec2<f64>>>,
+    curve_segments: usize,
+
+    /// Whether the glyph has more than [`MAX_GLYPH_CONTOURS`] contours. Any after those are ignored.
+    too_many_contours: bool,
+}
+
+impl GlyphOutline {
+    fn push(&mut self, point: Vec2<f64>) {
+        if !self.too_many_contours && let Some(contour) = self.contours.last_mut() {
+            contour.push(point);
+        }
+    }
+
+    /// Add points along a curve from the last point, given where each step along it is.
+    fn curve(&mut self, point_at: impl Fn(Vec2<f64>, f64) -> Vec2<f64>) {
+        if self.too_many_contours {
+            return;
+        }
+        let Some(contour) = self.contours.last_mut() else { return };
+        let Some(&from) = contour.last() else { return };
+        contour.extend((1..=self.curve_segments).map(|step| point_at(from, step as f64 / self.curve_segments as f64)));
+    }
+}
+
+impl OutlineBuilder for GlyphOutline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        if self.contours.len() == MAX_GLYPH_CONTOURS {
+            self.too_many_contours = true;
+        }
+        if !self.too_many_contours {
+            self.contours.push(vec![Vec2::new(x as f64, y as f64)]);
+        }
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push(Vec2::new(x as f64, y as f64));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (control, to) = ((x1 as f64, y1 as f64), (x as f64, y as f64));
+        self.curve(|from, t| {
+            let (a, b, c) = ((1.0 - t) * (1.0 - t), 2.0 * (1.0 - t) * t, t * t);
+            Vec2::new(a * from.x + b * control.0 + c * to.0, a * from.y + b * control.1 + c * to.1)
+        });
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (first, second, to) = ((x1 as f64, y1 as f64), (x2 as f64, y2 as f64), (x as f64, y as f64));
+        self.curve(|from, t| {
+            let (a, b, c, d) = ((1.0 - t).powi(3), 3.0 * (1.0 - t).powi(2) * t, 3.0 * (1.0 - t) * t * t, t.powi(3));
+            Vec2::new(
+                a * from.x + b * first.0 + c * second.0 + d * to.0,
+                a * from.y + b * first.1 + c * second.1 + d * to.1,
+            )
+        });
+    }
+
+    fn close(&mut self) {
+        // Outlines come back to where they started, which only needs to be in the polygon once
+        if let Some(contour) = self.contours.last_mut() && contour.len() > 1 && contour.first() == contour.last() {
+            contour.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use manifold_rs::Vec2;
+
+    use crate::font::{Font, FontError, default_font_path};
+
+    /// Build a tiny TrueType font, with 1000 units to the em square:
+    ///
+    /// - "o" is a square with a square hole.
+    /// - "D" is a straight edge with a curve, which has one control point.
+    /// - "O" is "o" as a component, scaled by half and moved along.
+    /// - "A" is "O" 17 times over, and "B" is "A" 17 times over - more contours than are allowed.
+    pub(crate) fn test_font() -> Vec<u8> {
+        fn u16s(values: &[u16]) -> Vec<u8> {
+            values.iter().flat_map(|value| value.to_be_bytes()).collect()
+        }
+
+        // Every point of these is on the outline, apart from the top-right of "D", and each
+        // coordinate is a 16-bit delta from the last
+        let simple_glyph = |contours: &[&[(i16, i16, bool)]]| {
+            let mut glyph = u16s(&[contours.len() as u16, 0, 0, 1000, 1000]);
+            let mut end = 0;
+            for contour in contours {
+                end += contour.len() as u16;
+                glyph.extend(u16s(&[end - 1]));
+            }
+            glyph.extend(u16s(&[0]));
+
+            let points = contours.iter().flat_map(|contour| contour.iter()).collect::<Vec<_>>();
+            glyph.extend(points.iter().map(|(_, _, on_curve)| *on_curve as u8));
+            let (mut last_x, mut last_y) = (0, 0);
+            let mut ys = vec![];
+            for (x, y, _) in &points {
+                glyph.extend((x - last_x).to_be_bytes());
+                ys.extend((y - last_y).to_be_bytes());
+                (last_x, last_y) = (*x, *y);
+            }
+            glyph.extend(ys);
+            glyph
+        };
+
+        let o = simple_glyph(&[
+            &[(0, 0, true), (0, 1000, true), (1000, 1000, true), (1000, 0, true)],
+            &[(250, 250, true), (750, 250, true), (750, 750, true), (250, 750, true)],
+        ]);
+        let d = simple_glyph(&[&[(0, 0, true), (0, 1000, true), (1000, 1000, false), (1000, 0, true)]]);
+
+        // One component, with 16-bit X and Y offsets and a scale of 0.5
+        let big_o = [u16s(&[-1i16 as u16, 0, 0, 1000, 1000]), u16s(&[0x1 | 0x2 | 0x8, 1, 100, 0, 0x2000])].concat();
+
+        // Many copies of one component, with no offset
+        let repeated = |component: u16| {
+            let mut glyph = u16s(&[-1i16 as u16, 0, 0, 1000, 1000]);
+            for index in 0..17 {
+                let more_components = if index < 16 { 0x20 } else { 0 };
+                glyph.extend(u16s(&[0x1 | 0x2 | more_components, component, 0, 0]));
+            }
+            glyph
+        };
+
+        let mut glyf = vec![];
+        let mut loca = vec![];
+        for glyph in [vec![], o, d, big_o, repeated(3), repeated(4)] {
+            loca.extend((glyf.len() as u32).to_be_bytes());
+            glyf.extend(glyph);
+        }
+        loca.extend((glyf.len() as u32).to_be_bytes());
+
+        // Each character is its own segment, mapped with a delta
+        let characters = [('A', 4u16), ('B', 5), ('D', 2), ('O', 3), ('o', 1)];
+        let segment_count = characters.len() as u16 + 1;
+        let mut format_4 = u16s(&[4, 0, 0, segment_count * 2, 0, 0, 0]);
+        format_4.extend(u16s(&characters.map(|(char, _)| char as u16)));
+        format_4.extend(u16s(&[0xFFFF, 0]));
+        format_4.extend(u16s(&characters.map(|(char, _)| char as u16)));
+        format_4.extend(u16s(&[0xFFFF]));
+        format_4.extend(u16s(&characters.map(|(char, glyph)| glyph.wrapping_sub(char as u16))));
+        format_4.extend(u16s(&[1]));
+        format_4.extend(u16s(&vec![0; segment_count as usize]));
+        let cmap = [u16s(&[0, 1, 3, 1, 0, 12]), format_4].concat();
+
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        head[50..52].copy_from_slice(&1u16.to_be_bytes());
+        let mut hhea = vec![0; 36];
+        hhea[34..36].copy_from_slice(&4u16.to_be_bytes());
+        let maxp = u16s(&[0, 0x5000, 6]);
+        let hmtx = u16s(&[500, 0, 1200, 0, 1100, 0, 700, 0]);
+
+        let tables = [("cmap", cmap), ("glyf", glyf), ("head", head), ("hhea", hhea), ("hmtx", hmtx), ("loca", loca), ("maxp", maxp)];
+        let mut font = [0x00010000u32.to_be_bytes().to_vec(), u16s(&[tables.len() as u16, 0, 0, 0])].concat();
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, data) in &tables {
+            font.extend(tag.as_bytes());
+            font.extend([0; 4]);
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        for (_, data) in tables {
+            font.extend(data);
+        }
+        font
+    }
+
+    fn points(points: &[(f64, f64)]) -> Vec<Vec2<f64>> {
+        points.iter().map(|(x, y)| Vec2::new(*x, *y)).collect()
+    }
+
+    #[test]
+    fn test_outline_glyph_with_hole() {
+        let font = Font::parse(test_font()).unwrap();
+        assert_eq!(
+            font.outline_text("o", 10.0, 4).unwrap(),
+            vec![
+                points(&[(10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]),
+                points(&[(2.5, 7.5), (7.5, 7.5), (7.5, 2.5), (2.5, 2.5)]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_outline_curve() {
+        let font = Font::parse(test_font()).unwrap();
+        assert_eq!(
+            font.outline_text("D", 10.0, 2).unwrap(),
+            vec![points(&[(10.0, 0.0), (7.5, 7.5), (0.0, 10.0), (0.0, 0.0)])],
+        );
+    }
+
+    #[test]
+    fn test_outline_text() {
+        let font = Font::parse(test_font()).unwrap();
+
+        // Each glyph moves the next one along, and characters which the font doesn't have are
+        // left out, but still take up room
+        let outlines = font.outline_text("oxO", 10.0, 1).unwrap();
+        assert_eq!(outlines.len(), 4);
+        assert_eq!(outlines[0][0], Vec2::new(10.0, 0.0));
+
+        // The component is scaled down and moved along, after the 12 + 5 units of "o" and "x"
+        assert_eq!(outlines[2], points(&[(23.0, 0.0), (23.0, 5.0), (18.0, 5.0), (18.0, 0.0)]));
+        assert_eq!(outlines[3], points(&[(19.25, 3.75), (21.75, 3.75), (21.75, 1.25), (19.25, 1.25)]));
+
+        assert_eq!(font.outline_text(" ", 10.0, 1).unwrap(), Vec::<Vec<_>>::new());
+    }
+
+    #[test]
+    fn test_contour_count_limit() {
+        let font = Font::parse(test_font()).unwrap();
+        assert_eq!(font.outline_text("A", 10.0, 1).unwrap().len(), 17 * 2);
+        assert_eq!(font.outline_text("B", 10.0, 1).unwrap_err().reason, "a glyph has more than 512 contours");
+
+        // The limit is for each glyph, not the whole text
+        assert_eq!(font.outline_text("AA", 10.0, 1).unwrap().len(), 2 * 17 * 2);
+    }
+
+    #[test]
+    fn test_default_font() {
+        // Not every system has one, like a minimal container
+        let Some(path) = default_font_path() else { return };
+        let font = Font::parse(std::fs::read(path).unwrap()).unwrap();
+        assert!(font.outline_text("Hello", 10.0, 4).unwrap().len() >= 5);
+
+        // Accented letters are usually composite glyphs, made of the letter and the accent
+        assert!(font.outline_text("é", 10.0, 4).unwrap().len() > font.outline_text("e", 10.0, 4).unwrap().len());
+
+        // Characters which it doesn't have are all drawn as its "missing character" glyph
+        let missing = font.outline_text("\u{E000}", 10.0, 4).unwrap();
+        assert!(!missing.is_empty());
+        assert_eq!(font.outline_text("\u{10FFFF}", 10.0, 4).unwrap(), missing);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |data: Vec<u8>| Font::parse(data).unwrap_err();
+        assert_eq!(error(vec![0, 1]), FontError { reason: "the font file ends too early".to_owned() });
+        assert_eq!(error(b"OTTO".to_vec()).reason, "fonts with PostScript outlines aren't supported, only ones with TrueType outlines");
+        assert_eq!(error(b"abcd".to_vec()).reason, "not a TrueType font");
+
+        let mut font = test_font();
+        font.truncate(200);
+        assert_eq!(error(font).reason, "the font is malformed: the head table is missing or malformed");
+
+        let mut font = test_font();
+        font[12..16].copy_from_slice(b"xxxx");
+        assert_eq!(error(font).reason, "the font has no \"cmap\" table");
+    }
+}
@@ -1,12 +1,31 @@
-use std::collections::HashMap;
+use std::{collections::{BTreeMap, HashMap}, sync::atomic::{AtomicU64, Ordering}};
 
 use manifold_rs::{CrossSection, Manifold};
 use yascad_frontend::InputSourceSpan;
 
-use crate::{RuntimeError, RuntimeErrorKind, object::Object};
+use crate::{RuntimeError, RuntimeErrorKind, instancing::Instances, object::Object};
 
+/// Refers to geometry in a [`GeometryTable`].
+///
+/// Each table has its own generation, which its indices carry, so an index which outlives its
+/// table - like one kept from an earlier build - can't find unrelated geometry in a newer one.
+/// Indices aren't reused within a table either, so looking up geometry which has since been removed
+/// fails, rather than finding whatever replaced it.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct GeometryTableIndex(usize);
+pub struct GeometryTableIndex {
+    key: usize,
+    generation: u64,
+}
+
+/// The generation given to the next [`GeometryTable`] to be created.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Roughly how much memory Manifold uses for each triangle of a mesh, including its vertices,
+/// half-edges and normals.
+const ESTIMATED_BYTES_PER_TRIANGLE: usize = 200;
+
+/// Roughly how much memory Clipper uses for each vertex of a cross-section.
+const ESTIMATED_BYTES_PER_VERTEX: usize = 32;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GeometryDisposition {
@@ -36,90 +55,367 @@ impl GeometryDisposition {
 pub enum GeometryTableEntry {
     Manifold(Manifold),
     CrossSection(CrossSection),
+
+    /// Copies of a manifold, which behave like a manifold but are only combined when needed.
+    Instanced(Instances),
 }
 
 impl GeometryTableEntry {
+    /// Get the manifold of 3D geometry, realising it if it's instanced.
     pub fn unwrap_manifold(&self) -> &Manifold {
         match self {
             GeometryTableEntry::Manifold(manifold) => manifold,
+            GeometryTableEntry::Instanced(instances) => instances.manifold(),
             _ => panic!("expected manifold, got: {self:?}")
         }
     }
 
+    /// Turn instanced geometry into a plain manifold, for operations which can't work with
+    /// instances. Other geometry is returned as-is.
+    pub fn realise(self) -> Self {
+        match self {
+            GeometryTableEntry::Instanced(instances) => GeometryTableEntry::Manifold(instances.into_manifold()),
+            _ => self,
+        }
+    }
+
+    /// Whether this is 3D geometry.
+    pub fn is_3d(&self) -> bool {
+        matches!(self, GeometryTableEntry::Manifold(_) | GeometryTableEntry::Instanced(_))
+    }
+
     pub fn unwrap_cross_section(&self) -> &CrossSection {
         match self {
             GeometryTableEntry::CrossSection(cross_section) => cross_section,
             _ => panic!("expected cross-section, got: {self:?}")
         }
     }
+
+    /// Convert this entry into a manifold which can be displayed or exported.
+    /// 
+    /// 2D geometry is extruded by a very small height.
+    pub fn to_display_manifold(&self) -> Manifold {
+        // Height which 2D geometry is extruded to, for 3D display
+        const CROSS_SECTION_EXTRUDE_HEIGHT: f64 = 0.01;
+
+        match self {
+            GeometryTableEntry::Manifold(manifold) => manifold.clone(),
+            GeometryTableEntry::Instanced(instances) => instances.manifold().clone(),
+
+            // The cross-section is already known to be valid, so extruding it shouldn't fail
+            GeometryTableEntry::CrossSection(cross_section) =>
+                Manifold::extrude(cross_section.polygons(), CROSS_SECTION_EXTRUDE_HEIGHT)
+                    .expect("could not extrude cross-section for display"),
+        }
+    }
+
+    /// Compute any deferred operations which produced this entry - see [`Manifold::evaluate`].
+    /// 
+    /// Invalid geometry isn't reported here, since it's reported by whatever uses the entry.
+    pub fn evaluate(&self) {
+        match self {
+            GeometryTableEntry::Manifold(manifold) => { let _ = manifold.evaluate(); }
+            GeometryTableEntry::Instanced(instances) => { let _ = instances.source().evaluate(); }
+            GeometryTableEntry::CrossSection(_) => {}
+        }
+    }
+
+    /// Roughly how many bytes of memory this entry's geometry takes up.
+    ///
+    /// Counting a manifold's triangles computes any deferred operations which produced it, so this
+    /// is only used when a memory budget is set - see [`GeometryTable::set_memory_budget`].
+    /// Instances are counted as their source and transformations, since that's all they store
+    /// until they're realised.
+    pub fn estimated_memory(&self) -> usize {
+        match self {
+            GeometryTableEntry::Manifold(manifold) => manifold.count_triangles() * ESTIMATED_BYTES_PER_TRIANGLE,
+            GeometryTableEntry::CrossSection(cross_section) => cross_section.count_verts() * ESTIMATED_BYTES_PER_VERTEX,
+            GeometryTableEntry::Instanced(instances) =>
+                instances.source().count_triangles() * ESTIMATED_BYTES_PER_TRIANGLE
+                    + size_of_val(instances.transforms()),
+        }
+    }
+
+    /// Whether this entry contains no geometry, such as that created by `empty()`.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            GeometryTableEntry::Manifold(manifold) => manifold.is_empty(),
+            GeometryTableEntry::CrossSection(cross_section) => cross_section.is_empty(),
+            GeometryTableEntry::Instanced(instances) => instances.is_empty(),
+        }
+    }
 }
 
+/// A single item stored in the [`GeometryTable`].
+#[derive(Debug, Clone)]
+pub(crate) struct GeometryTableItem {
+    entry: GeometryTableEntry,
+    disposition: GeometryDisposition,
+
+    /// The source code which produced this geometry.
+    pub(crate) provenance: InputSourceSpan,
+
+    /// The name given to this geometry by `part`, if any.
+    name: Option<String>,
+
+    /// Whether this geometry was produced by `keep`, so should be added back by the `difference`
+    /// it's a child of.
+    kept: bool,
+
+    /// The table's operation count when this item was added, or last replaced.
+    changed_at: usize,
+
+    /// The [`GeometryTableEntry::estimated_memory`] of the entry, if the table has a memory budget,
+    /// or 0 otherwise.
+    estimated_memory: usize,
+}
+
+/// How the contents of a [`GeometryTable`] differ from an earlier point, found with
+/// [`GeometryTable::changes_since`].
+#[derive(Debug, Clone)]
+pub(crate) struct GeometryTableChanges {
+    /// Indices which existed before, but have since been removed or replaced.
+    pub(crate) removed: Vec<GeometryTableIndex>,
+
+    /// Items which have been added or replaced since, with their indices.
+    pub(crate) added: Vec<(GeometryTableIndex, GeometryTableItem)>,
+}
+
+/// Owns all geometry created by the interpreter.
+/// 
+/// Entries are kept ordered by index, so iteration follows the order in which geometry was added.
 #[derive(Debug)]
 pub struct GeometryTable {
-    table: HashMap<usize, (GeometryTableEntry, GeometryDisposition)>,
+    table: BTreeMap<usize, GeometryTableItem>,
     next_index: usize,
+
+    /// Distinguishes this table's indices from those of any other table.
+    generation: u64,
+
+    /// The number of additions, removals and replacements made so far.
+    operation_count: usize,
+
+    /// If enabled with [`Self::set_original_tracking`], the source of each primitive added with
+    /// [`Self::add_primitive`], by its [`Manifold::original_id`]. These are kept after the
+    /// geometry is removed, since its triangles can still be part of other geometry.
+    originals: Option<Originals>,
+
+    /// The most memory which geometry in the table should take up, set with
+    /// [`Self::set_memory_budget`].
+    memory_budget: Option<usize>,
+
+    /// The total estimated memory of every item in the table.
+    estimated_memory: usize,
+}
+
+/// The sources of tracked primitives - see [`GeometryTable::add_primitive`].
+#[derive(Debug, Default)]
+struct Originals {
+    /// Every tracked primitive, in the order they were added.
+    order: Vec<(u32, InputSourceSpan)>,
+
+    /// Where each [`Manifold::original_id`] is in `order`.
+    by_id: HashMap<u32, usize>,
+}
+
+impl Originals {
+    fn push(&mut self, id: u32, provenance: InputSourceSpan) {
+        self.by_id.entry(id).or_insert(self.order.len());
+        self.order.push((id, provenance));
+    }
 }
 
 impl GeometryTable {
     pub fn new() -> Self {
         Self {
-            table: HashMap::new(),
+            table: BTreeMap::new(),
             next_index: 1,
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+            operation_count: 0,
+            originals: None,
+            memory_budget: None,
+            estimated_memory: 0,
         }
     }
 
     /// Add new geometry to the table, and return its index.
-    pub fn add(&mut self, geometry: GeometryTableEntry, disposition: GeometryDisposition) -> GeometryTableIndex {
+    /// 
+    /// The `provenance` is the span of source code which is responsible for creating the geometry.
+    pub fn add(&mut self, geometry: GeometryTableEntry, disposition: GeometryDisposition, provenance: InputSourceSpan) -> GeometryTableIndex {
+        self.add_named(geometry, disposition, None, provenance)
+    }
+
+    /// Like [`Self::add`], but also gives the geometry a name.
+    pub fn add_named(&mut self, geometry: GeometryTableEntry, disposition: GeometryDisposition, name: Option<String>, provenance: InputSourceSpan) -> GeometryTableIndex {
+        self.insert_item(GeometryTableItem { entry: geometry, disposition, provenance, name, kept: false, changed_at: 0, estimated_memory: 0 })
+    }
+
+    /// Add an item taken from [`Self::changes_since`] (possibly of another table) under a new
+    /// index.
+    pub(crate) fn insert_item(&mut self, mut item: GeometryTableItem) -> GeometryTableIndex {
         let idx = self.take_next_index();
-        self.table.insert(idx.0, (geometry, disposition));
+        item.changed_at = self.operation_count;
+        self.operation_count += 1;
+        self.account_for(&mut item);
+        log::debug!("added geometry {} from line {}", idx.key, item.provenance.start_line());
+        self.table.insert(idx.key, item);
         idx
     }
 
     /// Like [`Self::add`] but wraps the index in an [`Object`] for easy use in the interpreter.
-    pub fn add_into_object(&mut self, geometry: GeometryTableEntry, disposition: GeometryDisposition) -> Object {
-        match geometry {
-            GeometryTableEntry::Manifold(manifold) =>
-                Object::Manifold(self.add_manifold(manifold, disposition)),
-            GeometryTableEntry::CrossSection(cross_section) => 
-                Object::CrossSection(self.add_cross_section(cross_section, disposition)),
+    pub fn add_into_object(&mut self, geometry: GeometryTableEntry, disposition: GeometryDisposition, provenance: InputSourceSpan) -> Object {
+        self.add_named_into_object(geometry, disposition, None, provenance)
+    }
+
+    /// Like [`Self::add_named`] but wraps the index in an [`Object`] for easy use in the interpreter.
+    pub fn add_named_into_object(&mut self, geometry: GeometryTableEntry, disposition: GeometryDisposition, name: Option<String>, provenance: InputSourceSpan) -> Object {
+        let is_3d = geometry.is_3d();
+        let index = self.add_named(geometry, disposition, name, provenance);
+        if is_3d {
+            Object::Manifold(index)
+        } else {
+            Object::CrossSection(index)
         }
     }
 
-    pub fn add_manifold(&mut self, manifold: Manifold, disposition: GeometryDisposition) -> GeometryTableIndex {
-        self.add(GeometryTableEntry::Manifold(manifold), disposition)
+    pub fn add_manifold(&mut self, manifold: Manifold, disposition: GeometryDisposition, provenance: InputSourceSpan) -> GeometryTableIndex {
+        self.add(GeometryTableEntry::Manifold(manifold), disposition, provenance)
     }
 
-    pub fn add_cross_section(&mut self, cross_section: CrossSection, disposition: GeometryDisposition) -> GeometryTableIndex {
-        self.add(GeometryTableEntry::CrossSection(cross_section), disposition)
+    /// Add a newly-created 3D primitive, like a cube, to the table.
+    /// 
+    /// This is the same as [`Self::add_manifold`], unless original tracking is enabled, in which
+    /// case the primitive becomes a new original so that its triangles can be traced back to the
+    /// `provenance` with [`Self::original_provenance`].
+    pub fn add_primitive(&mut self, manifold: Manifold, provenance: InputSourceSpan) -> GeometryTableIndex {
+        let manifold = match &mut self.originals {
+            Some(originals) => {
+                let manifold = manifold.as_original();
+                if let Some(id) = manifold.original_id() {
+                    originals.push(id, provenance.clone());
+                }
+                manifold
+            }
+            None => manifold,
+        };
+        self.add_manifold(manifold, GeometryDisposition::Physical, provenance)
+    }
+
+    pub fn add_cross_section(&mut self, cross_section: CrossSection, disposition: GeometryDisposition, provenance: InputSourceSpan) -> GeometryTableIndex {
+        self.add(GeometryTableEntry::CrossSection(cross_section), disposition, provenance)
     }
 
     pub fn remove(&mut self, index: GeometryTableIndex) -> (GeometryTableEntry, GeometryDisposition) {
-        self.table.remove(&index.0).expect("geometry not in table")
+        let item = self.take_item(&index);
+        self.operation_count += 1;
+        log::debug!("removed geometry {}", index.key);
+        (item.entry, item.disposition)
+    }
+
+    /// Whether the geometry at an index is still in the table.
+    pub fn contains(&self, index: &GeometryTableIndex) -> bool {
+        self.find_item(index).is_some()
+    }
+
+    /// Get the geometry at an index.
+    ///
+    /// Returns an error if the geometry isn't in the table any more - see [`GeometryTableIndex`].
+    pub fn get(&self, index: &GeometryTableIndex, span: InputSourceSpan) -> Result<&GeometryTableEntry, RuntimeError> {
+        self.get_existing_item(index, span).map(|item| &item.entry)
+    }
+
+    /// Like [`Self::get`], but gets the disposition of the geometry.
+    pub fn get_disposition(&self, index: &GeometryTableIndex, span: InputSourceSpan) -> Result<GeometryDisposition, RuntimeError> {
+        self.get_existing_item(index, span).map(|item| item.disposition)
+    }
+
+    pub fn get_provenance(&self, index: &GeometryTableIndex) -> &InputSourceSpan {
+        &self.get_item(index).provenance
+    }
+
+    /// Choose whether to track where primitives came from - see [`Self::add_primitive`].
+    pub fn set_original_tracking(&mut self, enabled: bool) {
+        self.originals = enabled.then(Originals::default);
+    }
+
+    /// The source of the primitive with the given [`Manifold::original_id`], if it was added
+    /// while original tracking was enabled.
+    pub fn original_provenance(&self, id: u32) -> Option<&InputSourceSpan> {
+        let originals = self.originals.as_ref()?;
+        originals.by_id.get(&id).map(|index| &originals.order[*index].1)
     }
 
-    pub fn get(&self, index: &GeometryTableIndex) -> &GeometryTableEntry {
-        &self.table.get(&index.0).expect("geometry not in table").0
+    /// The number of primitives which have been tracked so far.
+    pub(crate) fn original_count(&self) -> usize {
+        self.originals.as_ref().map_or(0, |originals| originals.order.len())
     }
 
-    pub fn get_disposition(&self, index: &GeometryTableIndex) -> GeometryDisposition {
-        self.table.get(&index.0).expect("geometry not in table").1
+    /// The primitives which have been tracked since there were `count` of them.
+    pub(crate) fn originals_since(&self, count: usize) -> Vec<(u32, InputSourceSpan)> {
+        self.originals.as_ref().map_or(vec![], |originals| originals.order[count..].to_vec())
+    }
+
+    /// Track a primitive which was added to an earlier table, such as by a reused statement.
+    pub(crate) fn record_original(&mut self, id: u32, provenance: InputSourceSpan) {
+        if let Some(originals) = &mut self.originals {
+            originals.push(id, provenance);
+        }
+    }
+
+    pub fn get_name(&self, index: &GeometryTableIndex) -> Option<&str> {
+        self.get_item(index).name.as_deref()
+    }
+
+    /// Mark the geometry at an index as produced by `keep`.
+    pub fn mark_kept(&mut self, index: &GeometryTableIndex) {
+        assert_eq!(index.generation, self.generation, "geometry is from another table");
+        self.table.get_mut(&index.key).expect("geometry not in table").kept = true;
+    }
+
+    /// Whether the geometry at an index was produced by `keep`.
+    pub fn is_kept(&self, index: &GeometryTableIndex) -> bool {
+        self.get_item(index).kept
+    }
+
+    /// If all of the given geometries have the same name, get it.
+    /// 
+    /// This is used to keep the name of geometry when it's used as the children of an operator.
+    pub fn common_name(&self, indices: &[GeometryTableIndex]) -> Option<String> {
+        let (first, rest) = indices.split_first()?;
+        let name = self.get_name(first)?;
+        if rest.iter().all(|index| self.get_name(index) == Some(name)) {
+            Some(name.to_owned())
+        } else {
+            None
+        }
     }
 
-    pub fn map(&mut self, index: GeometryTableIndex, func: impl FnOnce(GeometryTableEntry) -> GeometryTableEntry) -> GeometryTableIndex {
-        let (manifold, disposition) = self.remove(index);
-        self.add(func(manifold), disposition)
+    /// Replace the geometry at an index with a new version of it, computed by `func`.
+    /// 
+    /// This happens in place - the index stays the same, so any other references to it remain
+    /// valid, and so do the disposition, provenance and name.
+    pub fn map(&mut self, index: &GeometryTableIndex, func: impl FnOnce(GeometryTableEntry) -> GeometryTableEntry) {
+        // Moving the entry out requires taking the whole item, but it goes back under the same key
+        let mut item = self.take_item(index);
+        item.entry = func(item.entry);
+        item.changed_at = self.operation_count;
+        self.account_for(&mut item);
+        log::debug!("changed geometry {}", index.key);
+        self.table.insert(index.key, item);
+        self.operation_count += 1;
     }
 
-    pub fn map_manifold(&mut self, index: GeometryTableIndex, func: impl FnOnce(Manifold) -> Manifold) -> GeometryTableIndex {
+    pub fn map_manifold(&mut self, index: &GeometryTableIndex, func: impl FnOnce(Manifold) -> Manifold) {
         self.map(index, |entry|
             match entry {
                 GeometryTableEntry::Manifold(manifold) => GeometryTableEntry::Manifold(func(manifold)),
+                GeometryTableEntry::Instanced(instances) => GeometryTableEntry::Manifold(func(instances.into_manifold())),
                 _ => panic!("`map_manifold` called on non-manifold geometry")
             }
         )
     }
 
-    pub fn map_cross_section(&mut self, index: GeometryTableIndex, func: impl FnOnce(CrossSection) -> CrossSection) -> GeometryTableIndex {
+    pub fn map_cross_section(&mut self, index: &GeometryTableIndex, func: impl FnOnce(CrossSection) -> CrossSection) {
         self.map(index, |entry|
             match entry {
                 GeometryTableEntry::CrossSection(cross_section) => GeometryTableEntry::CrossSection(func(cross_section)),
@@ -136,6 +432,12 @@ impl GeometryTable {
     /// geometry operations which don't want to immediately put the geometry back in the table,
     /// rather use it for another operation.
     /// 
+    /// Empty geometries don't contribute anything to a union, so they are skipped, and are allowed
+    /// to have a different dimension to the rest.
+    /// 
+    /// A single geometry is returned as-is, so instanced geometry stays instanced. Otherwise,
+    /// instanced geometry is realised for the union.
+    /// 
     /// Returns an [`Err`] if the given geometries do not all have the same disposition or
     /// dimension.
     pub fn remove_many_into_union(&mut self, mut indices: Vec<GeometryTableIndex>, span: InputSourceSpan) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError> {
@@ -151,12 +453,22 @@ impl GeometryTable {
         }
 
         let (all_entries, all_dispositions): (Vec<_>, Vec<_>) = indices.into_iter()
-            .map(|child| self.remove(child))
+            .map(|child| {
+                let (entry, disposition) = self.remove(child);
+                (entry.realise(), disposition)
+            })
             .unzip();
 
         let disposition = GeometryDisposition::flatten(&all_dispositions, span.clone())?;
+
+        let mut non_empty_entries = all_entries.iter()
+            .filter(|entry| !entry.is_empty())
+            .collect::<Vec<_>>();
+        if non_empty_entries.is_empty() {
+            non_empty_entries.push(&all_entries[0]);
+        }
         
-        let (first, rest) = all_entries.split_first().unwrap();
+        let (first, rest) = non_empty_entries.split_first().unwrap();
     
         match first {
             GeometryTableEntry::Manifold(first_manifold) => {
@@ -181,15 +493,135 @@ impl GeometryTable {
                 
                 Ok((GeometryTableEntry::CrossSection(result), disposition))
             },
+            GeometryTableEntry::Instanced(_) => unreachable!("entries were realised"),
         }        
     }
 
-    pub fn iter_geometry(&self) -> impl Iterator<Item = &(GeometryTableEntry, GeometryDisposition)> {
+    /// Iterate over all geometry in the table, in the order it was added.
+    pub fn iter_geometry(&self) -> impl Iterator<Item = (&GeometryTableEntry, GeometryDisposition, &InputSourceSpan, Option<&str>)> {
         self.table.values()
+            .map(|item| (&item.entry, item.disposition, &item.provenance, item.name.as_deref()))
+    }
+
+    /// Limit how much memory geometry in the table should take up, in bytes, or remove the limit
+    /// with [`None`]. The table doesn't enforce this itself - the interpreter checks
+    /// [`Self::over_memory_budget`] after each statement.
+    ///
+    /// Memory is only estimated while there's a budget, because it can't be estimated without
+    /// computing geometry straight away, which stops Manifold from batching operations together.
+    ///
+    /// Must be set before adding anything to take effect.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    /// Roughly how many bytes of memory the geometry in the table takes up, if there's a memory
+    /// budget. This is always 0 without one.
+    pub fn estimated_memory(&self) -> usize {
+        self.estimated_memory
+    }
+
+    /// If the geometry in the table is estimated to take up more memory than the budget, the
+    /// budget.
+    pub fn over_memory_budget(&self) -> Option<usize> {
+        self.memory_budget.filter(|budget| self.estimated_memory > *budget)
+    }
+
+    /// The number of times geometry has been added, removed or replaced in this table, as a rough
+    /// measure of how much work the interpreter has done.
+    pub fn operation_count(&self) -> usize {
+        self.operation_count
+    }
+
+    /// A copy of the item at an index, which can be put back later with [`Self::restore_item`].
+    pub(crate) fn save_item(&self, index: &GeometryTableIndex) -> GeometryTableItem {
+        self.get_item(index).clone()
+    }
+
+    /// Put an item from [`Self::save_item`] back at its original index, replacing anything there.
+    pub(crate) fn restore_item(&mut self, index: &GeometryTableIndex, mut item: GeometryTableItem) {
+        assert_eq!(index.generation, self.generation, "geometry is from another table");
+        item.changed_at = self.operation_count;
+        self.operation_count += 1;
+        self.account_for(&mut item);
+        if let Some(replaced) = self.table.insert(index.key, item) {
+            self.estimated_memory -= replaced.estimated_memory;
+        }
+    }
+
+    /// The key which the next geometry added will have, to be passed to
+    /// [`Self::remove_added_since`] later.
+    pub(crate) fn next_key(&self) -> usize {
+        self.next_index
+    }
+
+    /// Remove all geometry which was added since [`Self::next_key`] returned `key`, and is still in
+    /// the table.
+    pub(crate) fn remove_added_since(&mut self, key: usize) {
+        let added = self.table.split_off(&key);
+        self.estimated_memory -= added.values().map(|item| item.estimated_memory).sum::<usize>();
+        if !added.is_empty() {
+            self.operation_count += 1;
+            log::debug!("removed {} geometries added from {key}", added.len());
+        }
+    }
+
+    /// All indices currently in the table, to be passed to [`Self::changes_since`] later.
+    pub(crate) fn indices(&self) -> Vec<GeometryTableIndex> {
+        self.table.keys().map(|key| self.index(*key)).collect()
+    }
+
+    /// Find what has changed since the table contained `indices_before`, and had performed
+    /// `operation_count` operations.
+    pub(crate) fn changes_since(&self, indices_before: &[GeometryTableIndex], operation_count: usize) -> GeometryTableChanges {
+        let removed = indices_before.iter()
+            .filter(|index| self.find_item(index).is_none_or(|item| item.changed_at >= operation_count))
+            .cloned()
+            .collect();
+        let added = self.table.iter()
+            .filter(|(_, item)| item.changed_at >= operation_count)
+            .map(|(key, item)| (self.index(*key), item.clone()))
+            .collect();
+
+        GeometryTableChanges { removed, added }
+    }
+
+    /// The item at an index, if it's still in this table.
+    fn find_item(&self, index: &GeometryTableIndex) -> Option<&GeometryTableItem> {
+        self.table.get(&index.key).filter(|_| index.generation == self.generation)
+    }
+
+    fn get_item(&self, index: &GeometryTableIndex) -> &GeometryTableItem {
+        self.find_item(index).expect("geometry not in table")
+    }
+
+    fn get_existing_item(&self, index: &GeometryTableIndex, span: InputSourceSpan) -> Result<&GeometryTableItem, RuntimeError> {
+        self.find_item(index).ok_or_else(|| RuntimeError::new(RuntimeErrorKind::GeometryNoLongerExists, span))
+    }
+
+    fn take_item(&mut self, index: &GeometryTableIndex) -> GeometryTableItem {
+        assert_eq!(index.generation, self.generation, "geometry is from another table");
+        let item = self.table.remove(&index.key).expect("geometry not in table");
+        self.estimated_memory -= item.estimated_memory;
+        item
+    }
+
+    /// Estimate the memory of an item which is about to be put into the table, and count it
+    /// towards the total.
+    fn account_for(&mut self, item: &mut GeometryTableItem) {
+        item.estimated_memory = match self.memory_budget {
+            Some(_) => item.entry.estimated_memory(),
+            None => 0,
+        };
+        self.estimated_memory += item.estimated_memory;
+    }
+
+    fn index(&self, key: usize) -> GeometryTableIndex {
+        GeometryTableIndex { key, generation: self.generation }
     }
 
     fn take_next_index(&mut self) -> GeometryTableIndex {
-        let idx = GeometryTableIndex(self.next_index);
+        let idx = self.index(self.next_index);
         self.next_index += 1;
         idx
     }
@@ -200,3 +632,206 @@ impl Default for GeometryTable {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use manifold_rs::{CrossSection, Manifold, Vec3};
+    use yascad_frontend::{InputSource, InputSourceSpan};
+
+    use crate::{RuntimeErrorKind, geometry_table::{GeometryDisposition, GeometryTable, GeometryTableEntry}, object::{Object, ObjectSnapshot}};
+
+    fn span() -> InputSourceSpan {
+        Rc::new(InputSource::new_string("cube(1);".to_owned())).span(0, 7)
+    }
+
+    fn cube(size: f64) -> Manifold {
+        Manifold::cube(size, size, size, false).unwrap()
+    }
+
+    #[test]
+    fn test_disposition_flatten() {
+        use GeometryDisposition::*;
+
+        assert_eq!(GeometryDisposition::flatten(&[Physical, Physical], span()), Ok(Physical));
+        assert_eq!(GeometryDisposition::flatten(&[Virtual], span()), Ok(Virtual));
+        assert_eq!(GeometryDisposition::flatten(&[Physical, Virtual], span()).unwrap_err().kind, RuntimeErrorKind::MixedGeometryDisposition);
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut table = GeometryTable::new();
+
+        let first = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        let second = table.add_named(GeometryTableEntry::Manifold(cube(2.0)), GeometryDisposition::Virtual, Some("lid".to_owned()), span());
+        assert_ne!(first, second);
+        assert_eq!(table.iter_geometry().count(), 2);
+
+        let (entry, disposition) = table.remove(second.clone());
+        assert_eq!(entry.unwrap_manifold().volume(), 8.0);
+        assert_eq!(disposition, GeometryDisposition::Virtual);
+        assert!(table.contains(&first));
+        assert!(!table.contains(&second));
+
+        // Indices aren't reused after removal
+        let third = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        assert_ne!(third, second);
+    }
+
+    #[test]
+    fn test_common_name() {
+        let mut table = GeometryTable::new();
+        let lid = table.add_named(GeometryTableEntry::Manifold(cube(1.0)), GeometryDisposition::Physical, Some("lid".to_owned()), span());
+        let also_lid = table.add_named(GeometryTableEntry::Manifold(cube(1.0)), GeometryDisposition::Physical, Some("lid".to_owned()), span());
+        let unnamed = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+
+        assert_eq!(table.common_name(&[lid.clone(), also_lid]), Some("lid".to_owned()));
+        assert_eq!(table.common_name(&[lid, unnamed]), None);
+        assert_eq!(table.common_name(&[]), None);
+    }
+
+    #[test]
+    fn test_union() {
+        let mut table = GeometryTable::new();
+        let a = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        let b = table.add_manifold(cube(1.0).translate(2.0, 0.0, 0.0), GeometryDisposition::Physical, span());
+
+        let (entry, disposition) = table.remove_many_into_union(vec![a.clone(), b.clone()], span()).unwrap();
+        assert_eq!(entry.unwrap_manifold().volume(), 2.0);
+        assert_eq!(disposition, GeometryDisposition::Physical);
+
+        // The children are used up
+        assert!(!table.contains(&a) && !table.contains(&b));
+        assert_eq!(table.iter_geometry().count(), 0);
+    }
+
+    #[test]
+    fn test_union_errors() {
+        let mut table = GeometryTable::new();
+
+        assert_eq!(table.remove_many_into_union(vec![], span()).unwrap_err().kind, RuntimeErrorKind::ChildrenExpected);
+
+        let physical = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        let virtual_ = table.add_manifold(cube(1.0), GeometryDisposition::Virtual, span());
+        assert_eq!(table.remove_many_into_union(vec![physical, virtual_], span()).unwrap_err().kind, RuntimeErrorKind::MixedGeometryDisposition);
+
+        let manifold = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        let cross_section = table.add_cross_section(CrossSection::square(1.0, 1.0, false), GeometryDisposition::Physical, span());
+        assert_eq!(table.remove_many_into_union(vec![manifold, cross_section], span()).unwrap_err().kind, RuntimeErrorKind::MixedGeometryDimensions);
+
+        // An empty geometry can be mixed with either dimension, since it contributes nothing
+        let manifold = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        let empty = table.add_cross_section(CrossSection::new(), GeometryDisposition::Physical, span());
+        let (entry, _) = table.remove_many_into_union(vec![manifold, empty], span()).unwrap();
+        assert_eq!(entry.unwrap_manifold().volume(), 1.0);
+    }
+
+    #[test]
+    fn test_map_keeps_index() {
+        let source = Rc::new(InputSource::new_string("cube(1);".to_owned()));
+        let mut table = GeometryTable::new();
+
+        let index = table.add_manifold(Manifold::cube(1.0, 1.0, 1.0, false).unwrap(), GeometryDisposition::Physical, source.span(0, 7));
+        let object = Object::Manifold(index.clone());
+        table.map_manifold(&index, |manifold| manifold.translate(5.0, 0.0, 0.0));
+
+        assert_eq!(object.get_field("origin", &table, span()), Ok(Some(Vec3::new(5.0, 0.0, 0.0).into())));
+        assert_eq!(table.iter_geometry().count(), 1);
+    }
+
+    #[test]
+    fn test_map_keeps_disposition() {
+        let source = Rc::new(InputSource::new_string("cube(1);".to_owned()));
+        let mut table = GeometryTable::new();
+
+        let physical = table.add_manifold(Manifold::cube(1.0, 1.0, 1.0, false).unwrap(), GeometryDisposition::Physical, source.span(0, 7));
+        let virtual_ = table.add_named(
+            GeometryTableEntry::Manifold(Manifold::cube(1.0, 1.0, 1.0, false).unwrap()),
+            GeometryDisposition::Virtual,
+            Some("lid".to_owned()),
+            source.span(0, 4),
+        );
+        table.map_manifold(&physical, |manifold| manifold.scale(2.0, 2.0, 2.0));
+        table.map_manifold(&virtual_, |manifold| manifold.scale(2.0, 2.0, 2.0));
+
+        assert_eq!(table.get_disposition(&physical, span()), Ok(GeometryDisposition::Physical));
+        assert_eq!(table.get_disposition(&virtual_, span()), Ok(GeometryDisposition::Virtual));
+        assert_eq!(table.get_name(&virtual_), Some("lid"));
+        assert_eq!(table.get_provenance(&virtual_), &source.span(0, 4));
+    }
+
+    #[test]
+    fn test_stale_index() {
+        let mut table = GeometryTable::new();
+        let index = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        let object = Object::Manifold(index.clone());
+        table.remove(index.clone());
+
+        assert_eq!(table.get(&index, span()).unwrap_err().kind, RuntimeErrorKind::GeometryNoLongerExists);
+        assert_eq!(table.get_disposition(&index, span()).unwrap_err().kind, RuntimeErrorKind::GeometryNoLongerExists);
+        assert_eq!(object.get_field("size", &table, span()).unwrap_err().kind, RuntimeErrorKind::GeometryNoLongerExists);
+        assert_eq!(object.snapshot(&table, span()).unwrap_err().kind, RuntimeErrorKind::GeometryNoLongerExists);
+    }
+
+    #[test]
+    fn test_index_from_another_table() {
+        let mut old_table = GeometryTable::new();
+        let old_index = old_table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+
+        // The new table has geometry in the same position, but it isn't the same geometry
+        let mut new_table = GeometryTable::new();
+        let new_index = new_table.add_manifold(cube(2.0), GeometryDisposition::Physical, span());
+        assert!(!new_table.contains(&old_index));
+        assert_eq!(new_table.get(&old_index, span()).unwrap_err().kind, RuntimeErrorKind::GeometryNoLongerExists);
+        assert_eq!(new_table.get(&new_index, span()).unwrap().unwrap_manifold().volume(), 8.0);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        // Nothing is estimated without a budget
+        let mut table = GeometryTable::new();
+        table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        assert_eq!(table.estimated_memory(), 0);
+        assert_eq!(table.over_memory_budget(), None);
+
+        let mut table = GeometryTable::new();
+        table.set_memory_budget(Some(5000));
+        let cube_memory = GeometryTableEntry::Manifold(cube(1.0)).estimated_memory();
+        assert!(cube_memory > 0);
+
+        let first = table.add_manifold(cube(1.0), GeometryDisposition::Physical, span());
+        let second = table.add_manifold(cube(1.0).translate(2.0, 0.0, 0.0), GeometryDisposition::Physical, span());
+        assert_eq!(table.estimated_memory(), cube_memory * 2);
+
+        // Replacing and removing geometry updates the total
+        table.map_manifold(&first, |manifold| manifold.union(&cube(1.0).translate(0.0, 2.0, 0.0)));
+        assert_eq!(table.estimated_memory(), cube_memory * 3);
+        table.remove(second);
+        assert_eq!(table.estimated_memory(), cube_memory * 2);
+        assert_eq!(table.over_memory_budget(), None);
+
+        let key = table.next_key();
+        for i in 0..20 {
+            table.add_manifold(cube(1.0).translate(0.0, 0.0, i as f64 * 2.0), GeometryDisposition::Physical, span());
+        }
+        assert_eq!(table.over_memory_budget(), Some(5000));
+        table.remove_added_since(key);
+        assert_eq!(table.estimated_memory(), cube_memory * 2);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut table = GeometryTable::new();
+        let index = table.add_manifold(cube(2.0), GeometryDisposition::Physical, span());
+        let object = Object::vector(vec![Object::Number(1.0), Object::Manifold(index.clone())]);
+
+        // The snapshot survives the geometry being used up
+        let snapshot = object.snapshot(&table, span()).unwrap();
+        table.remove(index);
+        assert_eq!(snapshot, ObjectSnapshot::Vector(vec![
+            ObjectSnapshot::Number(1.0),
+            ObjectSnapshot::Manifold { min_point: Vec3::new(0.0, 0.0, 0.0), max_point: Vec3::new(2.0, 2.0, 2.0), volume: 8.0 },
+        ]));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{fs, path::{Path, PathBuf}, rc::Rc};
+
+use yascad_frontend::{IncludeKind, InputSource, InputSourceOrigin, Node, NodeKind, SourceFile};
+
+use crate::{ExecutionContext, Frame, FrameKind, Interpreter, RuntimeError, RuntimeErrorKind, interpreter::{can_hoist, explain_unhoisted}};
+
+impl Interpreter {
+    /// Interpret an `include` or `use` statement, by loading the file it refers to and interpreting
+    /// its statements in the current scope.
+    ///
+    /// The file's statements behave like top-level ones, so its definitions can be used before
+    /// they're defined. With `definitions_only`, like within a `use`, only the definitions are
+    /// interpreted - and if the file has been loaded already, they're in scope so it's skipped.
+    pub(crate) fn interpret_include(&mut self, node: &Node, ctx: &ExecutionContext, definitions_only: bool) -> Result<(), RuntimeError> {
+        let NodeKind::Include { kind, path } = &node.kind
+        else { panic!("not an include") };
+
+        let resolved = self.resolve_readable_path(path, &node.span)?;
+        let identity = file_identity(&resolved);
+        if self.include_stack.contains(&identity) {
+            return Err(RuntimeError::new(RuntimeErrorKind::IncludeCycle(path.clone()), node.span.clone()));
+        }
+
+        let definitions_only = definitions_only || *kind == IncludeKind::Use;
+        if definitions_only && self.loaded_files.contains(&identity) {
+            return Ok(());
+        }
+
+        let file = match self.source_files.get(&identity) {
+            Some(file) => file.clone(),
+            None => {
+                let source = InputSource::new_file(&resolved)
+                    .map_err(|error| RuntimeError::new(
+                        RuntimeErrorKind::FileReadError { path: resolved.clone(), reason: error.to_string() },
+                        node.span.clone(),
+                    ))?;
+                self.record_file_read(&resolved, source.content().as_bytes());
+                let file = Rc::new(SourceFile::new(Rc::new(source)));
+                self.source_files.insert(identity.clone(), file.clone());
+                file
+            }
+        };
+
+        self.include_stack.push(identity.clone());
+        let result = self.interpret_included_source(&file, ctx, definitions_only);
+        self.include_stack.pop();
+
+        result.map_err(|error| error.with_frame(Frame::new(FrameKind::Include, path, node.span.clone())))?;
+        self.loaded_files.insert(identity);
+        Ok(())
+    }
+
+    fn interpret_included_source(&mut self, file: &SourceFile, ctx: &ExecutionContext, definitions_only: bool) -> Result<(), RuntimeError> {
+        // Only the first syntax error is reported, but it points into the included file
+        if let Some(error) = file.tokenize_errors().first() {
+            return Err(RuntimeError::new(RuntimeErrorKind::IncludedSyntaxError(error.to_string()), error.span.clone()));
+        }
+        let mut parser = file.parser();
+        let nodes = parser.parse_statements();
+        if let Some(error) = parser.errors.first() {
+            return Err(RuntimeError::new(RuntimeErrorKind::IncludedSyntaxError(error.to_string()), error.span.clone()));
+        }
+
+        let mut hoisted = vec![false; nodes.len()];
+        for (index, node) in nodes.iter().enumerate() {
+            if can_hoist(node) {
+                self.interpret(node, ctx)?;
+                hoisted[index] = true;
+            }
+        }
+
+        for (index, node) in nodes.iter().enumerate() {
+            match &node.kind {
+                _ if hoisted[index] => Ok(()),
+
+                NodeKind::Include { .. } => self.interpret_include(node, ctx, definitions_only),
+                NodeKind::UnitsDeclaration { .. } => return Err(
+                    RuntimeError::new(RuntimeErrorKind::UnitsDeclarationNotTopLevel, node.span.clone())
+                        .with_help("`units` applies to the whole model, so declare it in the main file instead")
+                ),
+
+                NodeKind::ModuleDefinition { .. } | NodeKind::OperatorDefinition { .. } | NodeKind::FunctionDefinition { .. } =>
+                    self.interpret(node, ctx).map(|_| ()),
+                _ if definitions_only => Ok(()),
+                _ => self.interpret(node, ctx).map(|_| ()),
+            }.map_err(|error| explain_unhoisted(error, &nodes[index + 1..]))?;
+        }
+
+        Ok(())
+    }
+
+    /// Start tracking which files are being included, from a model's top-level statements. The
+    /// model's own file counts, so that an included file can't include it back.
+    pub(crate) fn reset_include_stack(&mut self, nodes: &[Node]) {
+        self.loaded_files.clear();
+        self.source_files.clear();
+        self.include_stack = nodes.first()
+            .and_then(|node| match node.span.source.origin() {
+                InputSourceOrigin::File(path) => Some(file_identity(path)),
+                _ => None,
+            })
+            .into_iter()
+            .collect();
+    }
+}
+
+/// A path which is the same however a file was referred to, for detecting include cycles.
+fn file_identity(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
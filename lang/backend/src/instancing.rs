@@ -0,0 +1,186 @@
+use std::cell::OnceCell;
+
+use manifold_rs::{Manifold, Matrix3x4, Vec3};
+use yascad_frontend::InputSourceSpan;
+
+use crate::{Interpreter, geometry_table::GeometryTableEntry};
+
+/// One manifold repeated with many different transformations, like a pattern of identical screws.
+///
+/// Only the source manifold and the transformations are stored. The copies are only built into a
+/// single manifold when something needs it.
+#[derive(Debug, Clone)]
+pub struct Instances {
+    source: Manifold,
+    transforms: Vec<Matrix3x4>,
+    realised: OnceCell<Manifold>,
+}
+
+impl Instances {
+    pub fn new(source: Manifold, transforms: Vec<Matrix3x4>) -> Self {
+        Self { source, transforms, realised: OnceCell::new() }
+    }
+
+    /// The manifold which is repeated.
+    pub fn source(&self) -> &Manifold {
+        &self.source
+    }
+
+    /// The transformation applied to each copy of the source.
+    pub fn transforms(&self) -> &[Matrix3x4] {
+        &self.transforms
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty() || self.source.is_empty()
+    }
+
+    /// Apply another transformation to every copy. Unlike most operations, this keeps the copies
+    /// as instances.
+    pub fn transformed(&self, matrix: &Matrix3x4) -> Self {
+        let transforms = self.transforms.iter()
+            .map(|transform| transform.then(matrix))
+            .collect();
+        Self::new(self.source.clone(), transforms)
+    }
+
+    /// Repeat every copy at each of the given transformations, so the result has
+    /// `self.transforms().len() * transforms.len()` copies.
+    pub fn repeated(&self, transforms: &[Matrix3x4]) -> Self {
+        let transforms = transforms.iter()
+            .flat_map(|outer| self.transforms.iter().map(|inner| inner.then(outer)))
+            .collect();
+        Self::new(self.source.clone(), transforms)
+    }
+
+    /// All of the copies combined into one manifold, built the first time it's needed.
+    pub fn manifold(&self) -> &Manifold {
+        self.realised.get_or_init(|| realise(&self.source, &self.transforms))
+    }
+
+    /// Like [`Self::manifold`], but takes ownership.
+    pub fn into_manifold(self) -> Manifold {
+        match self.realised.into_inner() {
+            Some(manifold) => manifold,
+            None => realise(&self.source, &self.transforms),
+        }
+    }
+}
+
+/// Build the copies of `source` into one manifold.
+fn realise(source: &Manifold, transforms: &[Matrix3x4]) -> Manifold {
+    if transforms.is_empty() || source.is_empty() {
+        return Manifold::new();
+    }
+
+    let copies = transforms.iter()
+        .map(|transform| source.transform(transform))
+        .collect::<Vec<_>>();
+
+    // Composing is much faster than a union, but is only correct if the copies are disjoint
+    let bounds = transforms.iter()
+        .map(|transform| transformed_bounds(source, transform))
+        .collect::<Vec<_>>();
+    let any_overlap = bounds.iter().enumerate()
+        .any(|(i, a)| bounds[(i + 1)..].iter().any(|b| bounds_touch(a, b)));
+
+    if any_overlap {
+        let mut result = Manifold::new();
+        for copy in &copies {
+            result = result.union(copy);
+        }
+        result
+    } else {
+        Manifold::compose(&copies)
+    }
+}
+
+/// The axis-aligned bounds of `source` after it's transformed.
+fn transformed_bounds(source: &Manifold, transform: &Matrix3x4) -> (Vec3<f64>, Vec3<f64>) {
+    let bounding_box = source.bounding_box();
+    let (min, max) = (bounding_box.min_point(), bounding_box.max_point());
+
+    let corners = (0..8).map(|i| transform.apply(Vec3::new(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z },
+    )));
+    corners.fold(
+        (Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY), Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY)),
+        |(min, max), corner| (
+            Vec3::new(min.x.min(corner.x), min.y.min(corner.y), min.z.min(corner.z)),
+            Vec3::new(max.x.max(corner.x), max.y.max(corner.y), max.z.max(corner.z)),
+        ),
+    )
+}
+
+/// Whether two bounds overlap. Bounds which only share a face count too, since copies which touch
+/// need a union to merge them.
+fn bounds_touch((a_min, a_max): &(Vec3<f64>, Vec3<f64>), (b_min, b_max): &(Vec3<f64>, Vec3<f64>)) -> bool {
+    a_min.x <= b_max.x && b_min.x <= a_max.x
+        && a_min.y <= b_max.y && b_min.y <= a_max.y
+        && a_min.z <= b_max.z && b_min.z <= a_max.z
+}
+
+/// The physical geometry in the scene, arranged for an interactive preview.
+pub struct ScenePreview {
+    /// All geometry which isn't instanced, combined into one manifold.
+    pub manifold: Manifold,
+
+    /// Instanced geometry, which a renderer can draw by repeating one mesh.
+    pub instances: Vec<SceneInstances>,
+
+    /// Every part in the preview, in the same order as [`Interpreter::build_top_level_parts`].
+    pub parts: Vec<PreviewPart>,
+}
+
+/// A manifold which should be drawn once at each of the given transformations.
+pub struct SceneInstances {
+    pub source: Manifold,
+    pub transforms: Vec<Matrix3x4>,
+
+    /// The source code which produced this geometry.
+    pub provenance: InputSourceSpan,
+
+    /// The [`ScenePart::id`](crate::ScenePart::id) of the part which this geometry is.
+    pub id: u64,
+}
+
+/// Describes a part in a [`ScenePreview`], without building it.
+pub struct PreviewPart {
+    /// See [`ScenePart::id`](crate::ScenePart::id).
+    pub id: u64,
+
+    /// The name given to this part by `part`, if any.
+    pub name: Option<String>,
+
+    /// The source code which produced this part.
+    pub provenance: InputSourceSpan,
+}
+
+impl Interpreter {
+    /// Build the physical geometry in the scene for a preview. Unlike
+    /// [`Interpreter::build_top_level_manifold`], instanced geometry isn't combined into the
+    /// manifold, so that it doesn't have to be realised.
+    pub fn build_top_level_preview(&self) -> ScenePreview {
+        let mut manifold = Manifold::new();
+        let mut instances = vec![];
+
+        let mut parts = vec![];
+        for geometry in self.ordered_physical_geometry() {
+            match geometry.entry {
+                GeometryTableEntry::Instanced(entry_instances) => instances.push(SceneInstances {
+                    source: entry_instances.source().clone(),
+                    transforms: entry_instances.transforms().to_vec(),
+                    provenance: geometry.provenance.clone(),
+                    id: geometry.id,
+                }),
+                entry => manifold = manifold.union(&entry.to_display_manifold()),
+            }
+
+            parts.push(PreviewPart { id: geometry.id, name: geometry.name.map(str::to_owned), provenance: geometry.provenance.clone() });
+        }
+
+        ScenePreview { manifold, instances, parts }
+    }
+}
@@ -1,25 +1,47 @@
-use std::{cell::RefCell, collections::{HashMap, HashSet}, iter::zip, ops::RangeInclusive, rc::Rc};
+use std::{cell::RefCell, collections::{HashMap, HashSet}, iter::zip, mem, ops::RangeInclusive, path::PathBuf, rc::Rc, time::Duration};
 
 use manifold_rs::Manifold;
-use yascad_frontend::{Arguments, BinaryOperator, InputSourceSpan, Node, NodeKind, Parameters};
+use yascad_frontend::{Arguments, BinaryOperator, InputSource, InputSourceSpan, Node, NodeKind, Parameters, RebuildPlan, SourceFile, StatementNames};
 
-use crate::{RuntimeError, RuntimeErrorKind, builtin::{self, ModuleDefinition, OperatorDefinition}, geometry_table::{GeometryDisposition, GeometryTable, GeometryTableEntry, GeometryTableIndex}, lexical_scope::LexicalScope, object::Object};
+use crate::{FileAccessPolicy, Frame, FrameKind, Profile, ProfileEntryKind, RuntimeError, RuntimeErrorKind, RuntimeWarning, StatementResults, Step, StepCapture, Unit, rebuild::Reuse, suggestions::closest_name, builtin::{self, ModuleDefinition, OperatorDefinition}, geometry_table::{GeometryDisposition, GeometryTable, GeometryTableEntry, GeometryTableIndex}, lexical_scope::{LexicalScope, ScopeDefinition}, object::Object};
+
+/// How far short of a whole number of steps a range's end can be, in steps, while still being
+/// included in it.
+const RANGE_STEP_TOLERANCE: f64 = 1e-9;
+
+/// The most items which a range can have, so that a mistake like `[0:1000000000]` fails rather than
+/// running out of memory.
+const MAX_RANGE_LENGTH: usize = 10_000_000;
+
+/// How deeply calls to user-defined modules, operators and functions can be nested by default.
+/// Each call takes tens of kilobytes of stack in a debug build, so this is conservative.
+const DEFAULT_MAX_CALL_DEPTH: usize = 100;
 
 /// The context of whatever node is currently executing, to encapsulate surrounding state.
 #[derive(Clone, Debug)]
 pub struct ExecutionContext<'c> {
     /// The manifold (if any) which `it` currently refers to.
+    ///
+    /// This is only set while evaluating the arguments of an operator application, positional and
+    /// named alike. Its children, and the bodies of any modules called from its arguments, are
+    /// evaluated without it.
     it_manifold: ItManifold<'c>,
 
     /// If executing an operator body, its `children`.
     operator_children: Option<&'c [GeometryTableIndex]>,
 
     /// The current lexical scope.
+    ///
+    /// Interpreting can add names to any scope in the chain, so a borrow of one must never be held
+    /// while interpreting - clone out what's needed instead, like [`Interpreter::get_name`] does.
     lexical_scope: Rc<RefCell<LexicalScope>>,
 
     /// The current map of arguments available within a module/operator body.
     /// This is distinct from scope so we don't look up to parent frames.
     arguments: HashMap<String, Object>,
+
+    /// Whether statements here are direct children of `difference`, so can use `keep`.
+    keep_allowed: bool,
 }
 
 impl<'c> ExecutionContext<'c> {
@@ -29,6 +51,7 @@ impl<'c> ExecutionContext<'c> {
             operator_children: None,
             lexical_scope: Rc::new(RefCell::new(LexicalScope::new_root())),
             arguments: HashMap::new(),
+            keep_allowed: false,
         }
     }
 
@@ -50,13 +73,38 @@ impl<'c> ExecutionContext<'c> {
         }
     }
 
+    /// A new scope nested within this one.
+    /// 
+    /// Statements in the new scope, like the body of an `if`, aren't direct children of any
+    /// operator this scope is within.
     pub fn with_deeper_scope(&'_ self) -> ExecutionContext<'_> {
         ExecutionContext {
             lexical_scope: Rc::new(RefCell::new(LexicalScope::new(self.lexical_scope.clone()))),
+            keep_allowed: false,
+            ..self.clone()
+        }
+    }
+
+    /// A new scope for the body of a module or operator, which can't `set` any bindings outside
+    /// of it.
+    pub fn with_definition_scope(&'_ self) -> ExecutionContext<'_> {
+        let ctx = self.with_deeper_scope();
+        ctx.lexical_scope.borrow_mut().definition_body = true;
+        ctx
+    }
+
+    pub fn with_keep_allowed(&self, keep_allowed: bool) -> ExecutionContext<'c> {
+        ExecutionContext {
+            keep_allowed,
             ..self.clone()
         }
     }
 
+    /// Allow scopes nested within this one to replace its definitions with their own.
+    pub(crate) fn make_shadowable(&self) {
+        self.lexical_scope.borrow_mut().shadowable = true;
+    }
+
     pub fn with_arguments(&'_ self, arguments: HashMap<String, Object>) -> ExecutionContext<'_> {
         ExecutionContext {
             arguments,
@@ -65,6 +113,19 @@ impl<'c> ExecutionContext<'c> {
     }
 }
 
+#[cfg(test)]
+impl ExecutionContext<'_> {
+    /// A root context with some bindings already defined, so that tests can start from objects
+    /// which would be awkward to create in source.
+    pub(crate) fn with_bindings(bindings: Vec<(&str, Object)>) -> Self {
+        let ctx = Self::new();
+        for (name, object) in bindings {
+            ctx.lexical_scope.borrow_mut().add_binding(name.to_owned(), object);
+        }
+        ctx
+    }
+}
+
 impl Default for ExecutionContext<'_> {
     fn default() -> Self {
         Self::new()
@@ -73,7 +134,91 @@ impl Default for ExecutionContext<'_> {
 
 pub struct Interpreter {
     pub(crate) manifold_table: GeometryTable,
+
+    /// The number of segments to use for a full circle, before scaling. Can be changed by the
+    /// model with `segments`.
     pub(crate) circle_segments: i32,
+
+    /// Multiplies the number of segments used for curved geometry, to trade quality for speed.
+    pub(crate) segment_scale: f64,
+
+    /// The base unit of the model, set by a top-level `units` declaration.
+    pub(crate) units: Unit,
+
+    /// Added to hole diameters by `hole_d` and `hole_r`, if set by `print_clearance`.
+    pub(crate) print_clearance: Option<f64>,
+
+    /// How many times `print_clearance`, `hole_d` or `hole_r` have been called. Statements which
+    /// use the clearance can't be reused by a rebuild, because it might have changed elsewhere.
+    pub(crate) print_clearance_uses: usize,
+
+    /// Set by a built-in operator to name the geometry it returns, rather than the geometry
+    /// inheriting a name from the operator's children. Taken by the interpreter once the operator
+    /// has finished.
+    pub(crate) operator_result_name: Option<String>,
+
+    /// Set by `keep` to mark the geometry it returns as kept, like [`Self::operator_result_name`].
+    pub(crate) operator_result_kept: bool,
+
+    /// Where each argument to the built-in module or operator being called was written, so that it
+    /// can point errors at a specific argument. Set just before the action runs.
+    pub(crate) builtin_argument_spans: HashMap<String, InputSourceSpan>,
+
+    /// How much to record in `steps`.
+    pub(crate) step_capture: StepCapture,
+
+    /// The result of each operator application, if enabled by `step_capture`.
+    pub(crate) steps: Vec<Step>,
+
+    /// Timings of each statement and call, if profiling is enabled.
+    pub(crate) profile: Option<Profile>,
+
+    /// Everything suspicious noticed while interpreting.
+    pub(crate) warnings: Vec<RuntimeWarning>,
+
+    /// Whether to warn about numbers which look like they use a comma as a decimal separator.
+    pub(crate) locale_hints: bool,
+
+    /// Whether undefined identifier errors list the bindings which were in scope.
+    pub(crate) verbose_errors: bool,
+
+    /// What each top-level statement did, if recording is enabled.
+    pub(crate) statement_results: Option<StatementResults>,
+
+    /// How many paths to files outside of the source have been resolved. Statements which use
+    /// files can't be reused by a rebuild, because the files might have changed.
+    pub(crate) external_paths: usize,
+
+    /// The files which have been read, with the SHA-256 hash of their contents, in the order they
+    /// were read.
+    pub(crate) files_read: Vec<(PathBuf, [u8; 32])>,
+
+    /// Which files builtins are allowed to read.
+    pub(crate) file_access_policy: FileAccessPolicy,
+
+    /// The longest which each built-in operator's geometry operations can take, if limited.
+    pub(crate) operation_timeout: Option<Duration>,
+
+    /// Whether to load the prelude's definitions before interpreting the top level.
+    pub(crate) load_prelude: bool,
+
+    /// The files which are being interpreted because of `include` or `use`, starting with the
+    /// model's own file, used to detect cycles.
+    pub(crate) include_stack: Vec<PathBuf>,
+
+    /// The files which have already been loaded by `include` or `use`, so that using one again
+    /// doesn't interpret its definitions a second time.
+    pub(crate) loaded_files: HashSet<PathBuf>,
+
+    /// The files which have been read by `include` or `use`, so that including one again reuses
+    /// its tokens rather than reading and tokenizing it another time.
+    pub(crate) source_files: HashMap<PathBuf, Rc<SourceFile>>,
+
+    /// How many calls to user-defined modules, operators and functions are currently nested.
+    pub(crate) call_depth: usize,
+
+    /// How deeply calls can be nested before interpreting fails.
+    pub(crate) max_call_depth: usize,
 }
 
 impl Interpreter {
@@ -81,56 +226,360 @@ impl Interpreter {
         Self {
             manifold_table: GeometryTable::new(),
 
-            // TODO: add $fn setter support
             circle_segments: 20,
+            segment_scale: 1.0,
+
+            units: Unit::default(),
+            print_clearance: None,
+            print_clearance_uses: 0,
+
+            operator_result_name: None,
+            operator_result_kept: false,
+            builtin_argument_spans: HashMap::new(),
+
+            step_capture: StepCapture::Off,
+            steps: vec![],
+
+            profile: None,
+
+            warnings: vec![],
+            locale_hints: true,
+            verbose_errors: false,
+
+            statement_results: None,
+            external_paths: 0,
+            files_read: vec![],
+            file_access_policy: FileAccessPolicy::default(),
+
+            operation_timeout: None,
+            load_prelude: true,
+
+            include_stack: vec![],
+            loaded_files: HashSet::new(),
+            source_files: HashMap::new(),
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
         }
     }
 
-    pub fn build_top_level_manifold(&self) -> Manifold {
-        // Height which 2D geometry is extruded to, for 3D display
-        const CROSS_SECTION_EXTRUDE_HEIGHT: f64 = 0.01;
+    /// Scale the number of segments used for curved geometry by a multiplier. Lower values are
+    /// faster but less accurate, which can be useful for previews.
+    /// 
+    /// Must be set before interpreting anything to take effect.
+    pub fn set_segment_scale(&mut self, segment_scale: f64) {
+        self.segment_scale = segment_scale;
+    }
+
+    /// The number of segments to use for a full circle, with the segment scale applied.
+    /// 
+    /// This is never less than 3, whatever the scale, because that's the fewest segments which
+    /// still make a valid shape.
+    pub(crate) fn segments(&self) -> i32 {
+        // Casting NaN gives 0, so this also covers a NaN scale
+        ((self.circle_segments as f64 * self.segment_scale).round() as i32).max(3)
+    }
+
+    /// The base unit of the model, which all of its lengths are measured in.
+    pub fn units(&self) -> Unit {
+        self.units
+    }
+
+    /// Choose whether to track which primitive each triangle of the model came from, found
+    /// afterwards with [`Self::original_provenance`]. Tracking makes each primitive a separate
+    /// Manifold original, which costs a little time and memory, so it's disabled by default.
+    /// 
+    /// Must be set before interpreting anything to take effect.
+    pub fn set_original_tracking(&mut self, enabled: bool) {
+        self.manifold_table.set_original_tracking(enabled);
+    }
+
+    /// Limit roughly how much memory the model's geometry can take up, in bytes, or remove the
+    /// limit with [`None`]. Interpreting fails as soon as a statement leaves more geometry than
+    /// that, rather than carrying on until Manifold runs out of memory. Estimating memory computes
+    /// each operator's result straight away, so like a timeout, this makes building a little
+    /// slower.
+    ///
+    /// Must be set before interpreting anything to take effect.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.manifold_table.set_memory_budget(budget);
+    }
+
+    /// Limit how deeply calls to user-defined modules, operators and functions can be nested.
+    /// Interpreting fails when a call would go deeper, rather than overflowing the stack.
+    ///
+    /// The default suits the stack of a typical main thread. Raise it only when interpreting on a
+    /// thread with a larger stack.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
 
+    /// Choose whether an undefined identifier error's help lists every binding which was in scope,
+    /// as well as suggesting the closest name. Disabled by default, since the list can be long.
+    pub fn set_verbose_errors(&mut self, enabled: bool) {
+        self.verbose_errors = enabled;
+    }
+
+    /// Roughly how many bytes of memory the model's geometry takes up, if there's a memory budget.
+    /// This is always 0 without one - see [`Self::set_memory_budget`].
+    pub fn estimated_memory(&self) -> usize {
+        self.manifold_table.estimated_memory()
+    }
+
+    /// The source code of the primitive with the given [`Manifold::original_id`], if original
+    /// tracking is enabled. For a mesh of the model, the ID of each triangle comes from
+    /// [`manifold_rs::MeshGL::triangle_original_ids`].
+    pub fn original_provenance(&self, id: u32) -> Option<&InputSourceSpan> {
+        self.manifold_table.original_provenance(id)
+    }
+
+    pub fn build_top_level_manifold(&self) -> Manifold {
         let mut result = Manifold::new();
 
-        for (entry, disposition) in self.manifold_table.iter_geometry() {
-            if *disposition != GeometryDisposition::Physical {
-                continue;
-            }
+        for part in self.build_top_level_parts() {
+            result = result.union(&part.manifold);
+        }
 
-            match entry {
-                GeometryTableEntry::Manifold(manifold) => {
-                    result = result.union(manifold);
-                },
-                GeometryTableEntry::CrossSection(cross_section) => {
-                    result = result.union(&Manifold::extrude(cross_section.polygons(), CROSS_SECTION_EXTRUDE_HEIGHT));
-                }
+        result
+    }
+
+    /// Build each physical geometry in the scene into its own [`Manifold`], without combining
+    /// them.
+    /// 
+    /// Parts are ordered by [`Self::ordered_physical_geometry`].
+    pub fn build_top_level_parts(&self) -> Vec<ScenePart> {
+        self.ordered_physical_geometry()
+            .into_iter()
+            .map(|geometry| ScenePart {
+                manifold: geometry.entry.to_display_manifold(),
+                provenance: geometry.provenance.clone(),
+                name: geometry.name.map(str::to_owned),
+                id: geometry.id,
+            })
+            .collect()
+    }
+
+    /// The physical geometry in the scene, ordered by the file it came from and then by where it
+    /// appears in that file. Geometry from the same location is kept in the order it was created.
+    /// 
+    /// This is the same for every build of the same source, so is what anything shown to the user
+    /// should be ordered by.
+    pub(crate) fn ordered_physical_geometry(&self) -> Vec<OrderedGeometry<'_>> {
+        let mut geometry = self.manifold_table.iter_geometry()
+            // Empty geometry would only be unioned away, so don't bother extruding it
+            .filter(|(entry, disposition, _, _)| *disposition == GeometryDisposition::Physical && !entry.is_empty())
+            .map(|(entry, _, provenance, name)| OrderedGeometry { entry, provenance, name, id: 0 })
+            .collect::<Vec<_>>();
+
+        // Stable sort, so the table's insertion order breaks ties
+        geometry.sort_by_cached_key(|geometry| (geometry.provenance.source.origin().name(), geometry.provenance.start));
+
+        // Geometry from identical source is told apart by how many came before it
+        let mut hasher = PartSourceHasher::default();
+        let mut occurrences = HashMap::<u64, u64>::new();
+        for geometry in &mut geometry {
+            let source_id = hasher.part_source_id(geometry.provenance);
+            let occurrence = occurrences.entry(source_id).or_default();
+            geometry.id = fnv1a(&[source_id.to_le_bytes(), occurrence.to_le_bytes()].concat(), FNV_OFFSET_BASIS);
+            *occurrence += 1;
+        }
+
+        geometry
+    }
+
+    /// Build the physical geometry in the scene into one [`Manifold`] for each name given by
+    /// `part`, plus one for any unnamed geometry.
+    /// 
+    /// Groups are ordered by where their first part appears in the source.
+    pub fn build_top_level_groups(&self) -> Vec<SceneGroup> {
+        let mut groups: Vec<SceneGroup> = vec![];
+
+        for part in self.build_top_level_parts() {
+            if let Some(group) = groups.iter_mut().find(|group| group.name == part.name) {
+                group.manifold = group.manifold.union(&part.manifold);
+            } else {
+                groups.push(SceneGroup { name: part.name, manifold: part.manifold });
             }
         }
 
-        result
+        groups
     }
 
     pub fn interpret_top_level(&mut self, nodes: &[Node]) -> Result<(), RuntimeError> {
-        let ctx = ExecutionContext::new();
+        self.interpret_top_level_with(nodes, None)
+    }
+
+    /// Like [`Self::interpret_top_level`], but statements which `plan` allows to be reused take
+    /// their results from `previous` rather than being interpreted again.
+    ///
+    /// `previous` must have been recorded by an interpreter with the same settings as this one.
+    /// Reused statements don't contribute to steps or profiles. If a reused statement's results
+    /// don't fit with what's been built so far, it's interpreted after all.
+    pub fn interpret_top_level_reusing(&mut self, nodes: &[Node], plan: &RebuildPlan, previous: &StatementResults) -> Result<(), RuntimeError> {
+        self.interpret_top_level_with(nodes, Some(Reuse::new(nodes, plan, previous)))
+    }
+
+    fn interpret_top_level_with(&mut self, nodes: &[Node], mut reuse: Option<Reuse>) -> Result<(), RuntimeError> {
+        // The `units` declaration applies to the whole model, regardless of where it appears, so
+        // process it before anything else
+        let mut units_declared = false;
         for node in nodes {
-            self.interpret(node, &ctx)?;
+            if let NodeKind::UnitsDeclaration { unit } = &node.kind {
+                if units_declared {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::DuplicateUnitsDeclaration,
+                        node.span.clone(),
+                    ));
+                }
+
+                self.units = Unit::from_name(unit)
+                    .ok_or_else(|| RuntimeError::new(
+                        RuntimeErrorKind::UnknownUnit(unit.to_owned()),
+                        node.span.clone(),
+                    ))?;
+                units_declared = true;
+            }
+        }
+
+        self.reset_include_stack(nodes);
+
+        // The model can replace the prelude's definitions with its own
+        let prelude_ctx = ExecutionContext::new();
+        if self.load_prelude {
+            self.interpret_prelude(&prelude_ctx)?;
+        }
+
+        let ctx = prelude_ctx.with_deeper_scope();
+
+        // Like in OpenSCAD, modules and operators can be used before they're defined, so that the
+        // main geometry can come first. Bindings are still strictly in order, so definitions whose
+        // defaults refer to any name wait until they're reached, in case it isn't defined yet
+        let mut hoisted = vec![false; nodes.len()];
+        for (index, node) in nodes.iter().enumerate() {
+            if can_hoist(node) {
+                self.interpret(node, &ctx)?;
+                hoisted[index] = true;
+            }
+        }
+
+        for (index, node) in nodes.iter().enumerate() {
+            let recording_start = self.start_recording();
+            if matches!(node.kind, NodeKind::UnitsDeclaration { .. }) || hoisted[index] {
+                self.finish_recording(recording_start, node, &ctx);
+                continue;
+            }
+
+            if let Some(reuse) = reuse.as_mut() && self.restore_statement(reuse, index, &ctx) {
+                self.finish_recording(recording_start, node, &ctx);
+                continue;
+            }
+
+            let profile_start = self.start_profiling();
+            if matches!(node.kind, NodeKind::Include { .. }) {
+                self.interpret_include(node, &ctx, false)
+            } else {
+                self.interpret(node, &ctx).map(|_| ())
+            }.map_err(|error| explain_unhoisted(error, &nodes[index + 1..]))?;
+
+            let statement = node.statement_span.as_ref().unwrap_or(&node.span);
+            self.finish_profiling(profile_start, ProfileEntryKind::Statement, statement);
+            self.finish_recording(recording_start, node, &ctx);
         }
         Ok(())
     }
 
     pub fn interpret(&mut self, node: &Node, ctx: &ExecutionContext) -> Result<Object, RuntimeError> {
+        let result = self.interpret_node(node, ctx);
+
+        // Errors can come from anywhere deep within a statement, so note which one it was
+        match &node.statement_span {
+            Some(statement) => result
+                .and_then(|object| {
+                    self.check_memory_budget(statement)?;
+                    Ok(object)
+                })
+                .map_err(|error| error.with_statement(statement)),
+            None => result,
+        }
+    }
+
+    /// Apply the built-in operator `name` to the geometry on either side of a binary operator, as
+    /// if they were its two children.
+    fn apply_geometry_binop(&mut self, name: &'static str, left: GeometryTableIndex, right: GeometryTableIndex, operand_spans: Vec<InputSourceSpan>, span: &InputSourceSpan) -> Result<Object, RuntimeError> {
+        // Operators use up their children, so each side must be geometry which is still around,
+        // and can't be the same geometry as the other
+        self.manifold_table.get(&left, operand_spans[0].clone())?;
+        self.manifold_table.get(&right, operand_spans[1].clone())?;
+        if left == right {
+            return Err(RuntimeError::new(RuntimeErrorKind::AssertionError(
+                "the same geometry cannot be used on both sides of an operator".to_owned()
+            ), span.clone()));
+        }
+
+        let operator = builtin::get_builtin_operator(name).expect("geometry binop operator is not built in");
+        let children = vec![left, right];
+        let inherited_name = self.manifold_table.common_name(&children);
+
+        let save = self.save_for_timeout(&children);
+        self.builtin_argument_spans = HashMap::new();
+        let result = (operator.action)(self, HashMap::new(), children, &operand_spans, span.clone());
+        let (geom, disp) = self.evaluate_with_timeout(result, save, name, span)?;
+        self.record_step(name, &geom, span);
+        Ok(self.manifold_table.add_named_into_object(geom, disp, inherited_name, span.clone()))
+    }
+
+    /// Fail if the geometry built so far is over the memory budget. This is checked after every
+    /// statement, including those within loops and modules, so that the error points at the one
+    /// which went over.
+    fn check_memory_budget(&self, statement: &InputSourceSpan) -> Result<(), RuntimeError> {
+        match self.manifold_table.over_memory_budget() {
+            Some(budget) => Err(RuntimeError::new(
+                RuntimeErrorKind::MemoryBudgetExceeded { budget, estimate: self.manifold_table.estimated_memory() },
+                statement.clone(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn interpret_node(&mut self, node: &Node, ctx: &ExecutionContext) -> Result<Object, RuntimeError> {
         match &node.kind {
             NodeKind::Identifier(id) => {
                 match self.get_existing_name(id, ctx, node.span.clone())? {
                     NameDefinition::Argument(obj) | NameDefinition::Binding(obj) => Ok(obj),
                     
-                    def => Err(RuntimeError::new(
-                        RuntimeErrorKind::InvalidIdentifier {
-                            id: id.to_owned(),
-                            kind: def.describe_kind(),
-                        },
-                        node.span.clone(),
-                    )),
+                    def => {
+                        let error = RuntimeError::new(
+                            RuntimeErrorKind::InvalidIdentifier {
+                                id: id.to_owned(),
+                                kind: def.describe_kind(),
+                            },
+                            node.span.clone(),
+                        );
+
+                        // Modules and operators can't be called without parentheses, but it's an
+                        // easy mistake to make coming from OpenSCAD
+                        let module_help = |parameters: &EvaluatedParameters|
+                            if parameters.min_len() == 0 {
+                                format!("did you mean `{id}()`?")
+                            } else {
+                                format!("modules must be called with parentheses, like `{id}(...)`")
+                            };
+                        let help = match &def {
+                            NameDefinition::BuiltinModule(ModuleDefinition { parameters, .. }) => Some(module_help(parameters)),
+                            NameDefinition::UserDefinedModule(definition) => Some(module_help(&definition.parameters)),
+                            NameDefinition::BuiltinOperator(_) | NameDefinition::UserDefinedOperator(_) =>
+                                Some(format!("operators must be applied to children, like `{id}(...) {{ ... }}`")),
+                            NameDefinition::UserDefinedFunction(definition) if definition.parameters.min_len() == 0 =>
+                                Some(format!("did you mean `{id}()`?")),
+                            NameDefinition::UserDefinedFunction(_) =>
+                                Some(format!("functions must be called with parentheses, like `{id}(...)`")),
+                            NameDefinition::Argument(_) | NameDefinition::Binding(_) => None,
+                        };
+
+                        Err(match help {
+                            Some(help) => error.with_help(help),
+                            None => error,
+                        })
+                    },
                 }
             },
 
@@ -146,33 +595,62 @@ impl Interpreter {
                 Ok(Object::Boolean(*bool))
             }
 
+            NodeKind::StringLiteral(string) => {
+                Ok(Object::String(string.clone()))
+            }
+
             NodeKind::VectorLiteral(items) => {
+                self.warn_decimal_commas(items);
                 Ok(Object::Vector(
                     items.iter()
                         .map(|node| self.interpret(node, ctx))
-                        .collect::<Result<Vec<_>, _>>()?
+                        .collect::<Result<Vec<_>, _>>()?,
+                    Some(items.iter().map(|node| node.span.clone()).collect()),
                 ))
             },
 
-            NodeKind::VectorRangeLiteral { start, end } => {
+            NodeKind::VectorRangeLiteral { start, step, end } => {
                 let start = self.interpret(start, ctx)?.as_number(node.span.clone())?;
+                let step_value = match step {
+                    Some(step) => self.interpret(step, ctx)?.as_number(step.span.clone())?,
+                    None => 1.0,
+                };
                 let end = self.interpret(end, ctx)?.as_number(node.span.clone())?;
 
-                if end < start {
+                if !(start.is_finite() && step_value.is_finite() && end.is_finite()) {
+                    return Err(RuntimeError::new(RuntimeErrorKind::NonFiniteRange, node.span.clone()));
+                }
+
+                // A negative step counts down, so the end must be lower instead
+                if step_value == 0.0 || (step_value < 0.0 && end > start) {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::InvalidRangeStep { zero: step_value == 0.0 },
+                        step.as_ref().map(|step| step.span.clone()).unwrap_or(node.span.clone()),
+                    ));
+                }
+                if step_value > 0.0 && end < start {
                     return Err(RuntimeError::new(
                         RuntimeErrorKind::FlippedRange,
                         node.span.clone(),
                     ));
                 }
 
-                let mut current = start;
-                let mut items = vec![Object::Number(current)];
-                while current < end {
-                    current += 1.0;
-                    items.push(Object::Number(current));
+                // Each item is worked out from the start, rather than by adding up steps, so that
+                // rounding errors don't accumulate. The count is rounded generously so that the
+                // end is still included when a step like 0.1 doesn't divide it exactly
+                let count = ((end - start) / step_value + RANGE_STEP_TOLERANCE).floor();
+                if count >= MAX_RANGE_LENGTH as f64 {
+                    return Err(
+                        RuntimeError::new(RuntimeErrorKind::RangeTooLong { limit: MAX_RANGE_LENGTH }, node.span.clone())
+                            .with_help("use a larger step, or a smaller span between the start and the end")
+                    );
                 }
+                let count = count as usize;
+                let items = (0..=count)
+                    .map(|i| Object::Number(start + i as f64 * step_value))
+                    .collect();
 
-                Ok(Object::Vector(items))
+                Ok(Object::vector(items))
             }
 
             NodeKind::ItReference => {
@@ -195,89 +673,115 @@ impl Interpreter {
                 }
             },
 
-            NodeKind::OperatorApplication { name, arguments, children } => {
+            NodeKind::OperatorApplication { name, arguments: argument_nodes, children } => {
+                // `difference` needs to find its kept children straight away, before they're
+                // combined with anything else
+                if name == "keep" && !ctx.keep_allowed {
+                    return Err(
+                        RuntimeError::new(RuntimeErrorKind::KeepOutsideDifference, node.span.clone())
+                            .with_help("to move or transform kept geometry, put the operators inside `keep`, like `keep() translate(...) ...`")
+                    );
+                }
+
+                let child_ctx = ctx.with_it_manifold(ItManifold::None).with_keep_allowed(name == "difference");
                 let all_children = children.iter()
-                    .map(|child| self.interpret(child, &ctx.with_it_manifold(ItManifold::None)))
+                    .map(|child| self.interpret(child, &child_ctx))
                     .collect::<Result<Vec<_>, _>>()?;
 
-                // Not `physical_manifolds` because applying an operator to a virtual manifold is
-                // allowed
-                let manifold_children = self.filter_objects_to_geometry(all_children);
-
-                let it_manifold =
-                    if manifold_children.len() == 1 {
-                        ItManifold::Some(manifold_children.first().unwrap())
-                    } else {
-                        ItManifold::UnsupportedNotOneChild
-                    };
-
-                let arguments = self.evaluate_arguments(arguments, &ctx.with_it_manifold(it_manifold))?;
-
-                // We handle user-defined operators and built-in operators differently.
-                //
-                // User-defined operators can use `children` to access a new copy of the children.
-                // To implement this, we create virtual manifolds with all of the children
-                // rendered already. The user code never gets access to these manifolds - only
-                // copies of it - and these virtual manifolds are destroyed afterwards.
-                // (See the implementation for the `children` built-in function.)
-                //
-                // Built-in operators can do their own manifold table manipulation, so these are
-                // directly given the physical manifold indexes. They can do whatever they like with
-                // them.
-                match self.get_existing_name(name, ctx, node.span.clone())? {
-                    NameDefinition::UserDefinedOperator { parameters, body } => {
-                        let arguments = self.match_arguments_to_parameters(arguments, parameters, node.span.clone())?;
-
-                        let temporary_virtual_manifolds = manifold_children.into_iter()
-                            .map(|index| {
-                                let (m, _) = self.manifold_table.remove(index);
-                                self.manifold_table.add(m, GeometryDisposition::Virtual)
-                            })
-                            .collect::<Vec<_>>();
-
-                        let (geom, disp) = self.interpret_scoped_definition_body_into_geometry(
-                            &body, ctx, Some(&temporary_virtual_manifolds), arguments, node.span.clone()
-                        )?;
-
-                        for index in temporary_virtual_manifolds {
-                            self.manifold_table.remove(index);
+                let is_geometry = |(child, object): (&Node, &Object)|
+                    matches!(object, Object::Manifold(_) | Object::CrossSection(_)) || is_geometry_vector(child, object);
+
+                // A function's value would be left out like any other which isn't geometry, but
+                // calling one here is much more likely to be a mistake than a binding is
+                for (child, object) in zip(children, &all_children) {
+                    if let NodeKind::Call { name: child_name, .. } = &child.kind
+                        && !is_geometry((child, object))
+                        && let Some(def @ NameDefinition::UserDefinedFunction(_)) = self.get_callable_name(child_name, ctx)
+                    {
+                        return Err(
+                            RuntimeError::new(
+                                RuntimeErrorKind::InvalidIdentifier { id: child_name.to_owned(), kind: def.describe_kind() },
+                                child.span.clone(),
+                            )
+                            .with_help(format!("this returns a {} rather than geometry, so there's nothing for `{name}` to apply to", object.describe_type()))
+                        );
+                    }
+                }
+
+                // Without this, an if-statement which went down a branch with no geometry would
+                // leave the operator with no children, for reasons which aren't obvious
+                if !zip(children, &all_children).any(is_geometry)
+                    && let Some((branch, _)) = zip(children, &all_children)
+                        .find(|(child, object)| matches!(child.kind, NodeKind::IfConditional { .. }) && matches!(object, Object::Null))
+                {
+                    return Err(RuntimeError::new(RuntimeErrorKind::BranchProducedNoGeometry, branch.span.clone()));
+                }
+
+                // Keep track of which statement produced each child, so that errors can point at it.
+                // A vector of geometry, like the parts returned by a module, gives each of its
+                // elements as a separate child
+                let distribute = matches!((children.as_slice(), all_children.as_slice()), ([child], [object]) if is_geometry_vector(child, object));
+                let mut manifold_children = vec![];
+                let mut child_spans = vec![];
+                for (child, object) in zip(children, all_children) {
+                    match &object {
+                        // Not only physical geometry because applying an operator to a virtual
+                        // manifold is allowed
+                        Object::Manifold(index) | Object::CrossSection(index) => {
+                            manifold_children.push(index.clone());
+                            child_spans.push(child.span.clone());
                         }
 
-                        Ok(self.manifold_table.add_into_object(geom, disp))
-                    }
+                        Object::Vector(items, _) if is_geometry_vector(child, &object) => {
+                            for (i, item) in items.iter().enumerate() {
+                                if let Object::Manifold(index) | Object::CrossSection(index) = item {
+                                    manifold_children.push(index.clone());
+                                    child_spans.push(object.vector_element_span(i, &child.span));
+                                }
+                            }
+                        }
 
-                    NameDefinition::BuiltinOperator(op) => {
-                        let arguments = self.match_arguments_to_parameters(arguments, op.parameters, node.span.clone())?;
-                        let (geom, disp) = (op.action)(self, arguments, manifold_children, node.span.clone())?;
-                        Ok(self.manifold_table.add_into_object(geom, disp))
+                        _ => {}
                     }
+                }
 
-                    def => Err(RuntimeError::new(
-                        RuntimeErrorKind::InvalidIdentifier {
-                            id: name.to_owned(),
-                            kind: def.describe_kind(),
-                        },
-                        node.span.clone(),
-                    )),
+                // An operator applied to just a vector is applied to each element separately, so
+                // the parts stay separate
+                if distribute {
+                    let results = zip(manifold_children, child_spans.clone())
+                        .map(|(index, span)| self.apply_operator(node, name, argument_nodes, vec![index], vec![span], ctx))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    return Ok(Object::Vector(results, Some(child_spans.into())));
                 }
+
+                self.apply_operator(node, name, argument_nodes, manifold_children, child_spans, ctx)
             }
 
-            NodeKind::Call { name, arguments } => {
-                let arguments = self.evaluate_arguments(arguments, &ctx)?;
+            NodeKind::Call { name, arguments: argument_nodes } => {
+                let arguments = self.evaluate_arguments_for(name, argument_nodes, ctx)?;
 
-                match self.get_existing_name(name, ctx, node.span.clone())? {
+                match self.get_existing_callable_name(name, ctx, node.span.clone())? {
                     NameDefinition::BuiltinModule(module) => {
-                        let arguments = self.match_arguments_to_parameters(arguments, module.parameters, node.span.clone())?;
+                        let arguments = self.match_arguments_to_parameters(arguments, &module.parameters, node.span.clone(), None)?;
+                        self.builtin_argument_spans = module.parameters.argument_spans(argument_nodes);
                         (module.action)(self, arguments, ctx.operator_children, node.span.clone())
                     }
 
-                    NameDefinition::UserDefinedModule { parameters, body } => {
-                        let arguments = self.match_arguments_to_parameters(arguments, parameters, node.span.clone())?;
-                        let (geom, disp) = self.interpret_scoped_definition_body_into_geometry(
-                            &body, ctx, None, arguments, node.span.clone()
-                        )?;
+                    NameDefinition::UserDefinedModule(definition) => {
+                        let arguments = self.match_arguments_to_parameters(arguments, &definition.parameters, node.span.clone(), Some(&definition.span))?;
+                        match self.interpret_scoped_definition_body_into_geometry(
+                            &definition.body, ctx, None, arguments,
+                            Frame::new(FrameKind::Module, name, node.span.clone()),
+                        )? {
+                            BodyGeometry::Combined(geom, disp, geometry_name) =>
+                                Ok(self.manifold_table.add_named_into_object(geom, disp, geometry_name, node.span.clone())),
+                            BodyGeometry::Parts(parts) => Ok(parts),
+                        }
+                    }
 
-                        Ok(self.manifold_table.add_into_object(geom, disp))
+                    NameDefinition::UserDefinedFunction(definition) => {
+                        let arguments = self.match_arguments_to_parameters(arguments, &definition.parameters, node.span.clone(), Some(&definition.span))?;
+                        self.call_function(&definition, ctx, arguments, Frame::new(FrameKind::Function, name, node.span.clone()))
                     }
 
                     def => Err(RuntimeError::new(
@@ -297,10 +801,17 @@ impl Interpreter {
                 Ok(value)
             },
 
+            NodeKind::Reassignment { name, value } => {
+                let value = self.interpret(value, ctx)?;
+
+                self.reassign_name(name, value.clone(), ctx, node.span.clone())?;
+                Ok(value)
+            },
+
             NodeKind::FieldAccess { value, field } => {
                 let value = self.interpret(value, ctx)?;
 
-                if let Some(field_value) = value.get_field(field, &self.manifold_table) {
+                if let Some(field_value) = value.get_field(field, &self.manifold_table, node.span.clone())? {
                     Ok(field_value)
                 } else {
                     Err(RuntimeError::new(
@@ -310,10 +821,35 @@ impl Interpreter {
                 }
             },
 
+            // These short-circuit, so they can't evaluate both sides up-front like other operators
+            NodeKind::BinaryOperation { left, right, op: op @ (BinaryOperator::And | BinaryOperator::Or) } => {
+                let left = self.interpret(left, ctx)?.as_boolean(left.span.clone())?;
+                match (op, left) {
+                    (BinaryOperator::And, false) => Ok(Object::Boolean(false)),
+                    (BinaryOperator::Or, true) => Ok(Object::Boolean(true)),
+                    _ => Ok(Object::Boolean(self.interpret(right, ctx)?.as_boolean(right.span.clone())?)),
+                }
+            },
+
+            // Only the chosen value is evaluated, which lets a recursive function stop
+            NodeKind::TernaryConditional { condition, true_value, false_value } => {
+                let condition_span = condition.span.clone();
+                let value = if self.interpret(condition, ctx)?.as_boolean(condition_span)? { true_value } else { false_value };
+                self.interpret(value, ctx)
+            },
+
             NodeKind::BinaryOperation { left, right, op } => {
+                let operand_spans = vec![left.span.clone(), right.span.clone()];
                 let left = self.interpret(left, ctx)?;
                 let right = self.interpret(right, ctx)?;
 
+                // Common CSG shorthand - `a - b` is the same as `difference() { a; b; }`
+                if let (Object::Manifold(left_index) | Object::CrossSection(left_index), Object::Manifold(right_index) | Object::CrossSection(right_index)) = (&left, &right)
+                    && let Some(operator) = geometry_binop_operator(*op)
+                {
+                    return self.apply_geometry_binop(operator, left_index.clone(), right_index.clone(), operand_spans, &node.span);
+                }
+
                 let numeric_binop = |operation: &'static dyn Fn(f64, f64) -> f64| {
                     Ok::<Object, RuntimeError>(Object::Number(operation(
                         left.as_number(node.span.clone())?,
@@ -333,12 +869,18 @@ impl Interpreter {
                     BinaryOperator::Multiply => numeric_binop(&|l, r| l * r),
                     BinaryOperator::Divide => numeric_binop(&|l, r| l / r),
 
+                    // Euclidean rather than truncating, so that `i % n` counts up from 0 to n - 1
+                    // even for negative `i` - e.g. `-1 % 3` is 2, where C and OpenSCAD give -1
+                    BinaryOperator::Modulo => numeric_binop(&|l, r| l.rem_euclid(r)),
+
                     BinaryOperator::Equals => Ok(Object::Boolean(left == right)),
 
                     BinaryOperator::LessThan => numeric_comparison_binop(&|l, r| l < r),
                     BinaryOperator::LessThanOrEquals => numeric_comparison_binop(&|l, r| l <= r),
                     BinaryOperator::GreaterThan => numeric_comparison_binop(&|l, r| l > r),
                     BinaryOperator::GreaterThanOrEquals => numeric_comparison_binop(&|l, r| l >= r),
+
+                    BinaryOperator::And | BinaryOperator::Or => unreachable!(),
                 }
             },
 
@@ -347,21 +889,36 @@ impl Interpreter {
                 Ok(Object::Number(-value))
             },
 
-            NodeKind::OperatorDefinition { name, parameters, body } => {
+            NodeKind::UnaryNot(value) => {
+                let value = self.interpret(value, ctx)?.as_boolean(node.span.clone())?;
+                Ok(Object::Boolean(!value))
+            },
+
+            NodeKind::OperatorDefinition { name, parameters, body, .. } => {
+                let parameters = self.interpret_parameters(parameters, ctx)?;
+                self.add_name(
+                    name,
+                    NameDefinition::UserDefinedOperator(Rc::new(UserDefinition { parameters, body: body.clone(), span: node.span.clone() })),
+                    &ctx, node.span.clone()
+                )?;
+                Ok(Object::Null)
+            },
+
+            NodeKind::ModuleDefinition { name, parameters, body, .. } => {
                 let parameters = self.interpret_parameters(parameters, ctx)?;
                 self.add_name(
                     name,
-                    NameDefinition::UserDefinedOperator { parameters, body: body.clone() },
+                    NameDefinition::UserDefinedModule(Rc::new(UserDefinition { parameters, body: body.clone(), span: node.span.clone() })),
                     &ctx, node.span.clone()
                 )?;
                 Ok(Object::Null)
             },
 
-            NodeKind::ModuleDefinition { name, parameters, body } => {
+            NodeKind::FunctionDefinition { name, parameters, body, .. } => {
                 let parameters = self.interpret_parameters(parameters, ctx)?;
                 self.add_name(
                     name,
-                    NameDefinition::UserDefinedModule { parameters, body: body.clone() },
+                    NameDefinition::UserDefinedFunction(Rc::new(UserDefinition { parameters, body: vec![(**body).clone()], span: node.span.clone() })),
                     &ctx, node.span.clone()
                 )?;
                 Ok(Object::Null)
@@ -374,28 +931,55 @@ impl Interpreter {
                 for item in loop_source {
                     let ctx = ctx.with_deeper_scope();
                     self.add_name(&loop_variable, NameDefinition::Binding(item), &ctx, node.span.clone())?;
-                    let (geom, disp) = self.interpret_body_into_geometry(&body, &ctx, node.span.clone())?;
-                    
-                    result_indices.push(self.manifold_table.add(geom, disp));
+                    if let Some((geom, disp, geometry_name)) = self.interpret_body_into_geometry(&body, &ctx, node.span.clone())? {
+                        result_indices.push(self.manifold_table.add_named(geom, disp, geometry_name, node.span.clone()));
+                    }
                 }
 
+                // Looping over an empty vector, or over a body without geometry, gives empty
+                // geometry, which contributes nothing to the union it ends up in
+                if result_indices.is_empty() {
+                    return Ok(Object::Manifold(self.manifold_table.add_manifold(Manifold::new(), GeometryDisposition::Physical, node.span.clone())));
+                }
+
+                let geometry_name = self.manifold_table.common_name(&result_indices);
                 let (geom, disp) = self.manifold_table.remove_many_into_union(result_indices, node.span.clone())?;
-                Ok(self.manifold_table.add_into_object(geom, disp))
+                Ok(self.manifold_table.add_named_into_object(geom, disp, geometry_name, node.span.clone()))
             },
 
             NodeKind::IfConditional { condition, true_body, false_body } => {
                 let condition = self.interpret(condition, ctx)?.as_boolean(node.span.clone())?;
 
+                // The result is the geometry of whichever branch runs, or null if there was no geometry
+                // in it - or no branch to run. An `else if` is an if-statement nested in the `else`,
+                // so this is consistent along the whole chain
                 let ctx = ctx.with_deeper_scope();
-                if condition {
-                    let (geom, disp) = self.interpret_body_into_geometry(&true_body, &ctx, node.span.clone())?;
-                    Ok(self.manifold_table.add_into_object(geom, disp))
-                } else if let Some(false_body) = false_body {
-                    let (geom, disp) = self.interpret_body_into_geometry(&false_body, &ctx, node.span.clone())?;
-                    Ok(self.manifold_table.add_into_object(geom, disp))
-                } else {
-                    Ok(Object::Null)
+                let Some(body) = (if condition { Some(true_body) } else { false_body.as_ref() })
+                else { return Ok(Object::Null) };
+
+                let result_objects = self.interpret_body(body, &ctx)?;
+                let result_geometries = self.filter_objects_to_physical_geometries(result_objects, node.span.clone())?;
+                if result_geometries.is_empty() {
+                    return Ok(Object::Null);
                 }
+
+                let geometry_name = self.manifold_table.common_name(&result_geometries);
+                let (geom, disp) = self.manifold_table.remove_many_into_union(result_geometries, node.span.clone())?;
+                Ok(self.manifold_table.add_named_into_object(geom, disp, geometry_name, node.span.clone()))
+            },
+
+            // Handled by `interpret_top_level`, so any we encounter here aren't at the top level
+            NodeKind::UnitsDeclaration { .. } => {
+                Err(RuntimeError::new(
+                    RuntimeErrorKind::UnitsDeclarationNotTopLevel,
+                    node.span.clone(),
+                ))
+            },
+            NodeKind::Include { kind, .. } => {
+                Err(RuntimeError::new(
+                    RuntimeErrorKind::IncludeNotTopLevel(*kind),
+                    node.span.clone(),
+                ))
             },
         }
     }
@@ -425,42 +1009,274 @@ impl Interpreter {
     /// 
     /// It is the caller's responsibility to create a new deeper scope if necessary, because the
     /// caller may wish to inject variables into it (e.g. the `for` loop counter).
+    /// 
+    /// Also returns the name which the union should have, if all of the geometry in the body has
+    /// the same name. Returns [`None`] if the body produced no geometry, like a loop body which
+    /// only uses `set`.
     fn interpret_body_into_geometry(
         &mut self,
         nodes: &[Node],
         ctx: &ExecutionContext,
         span: InputSourceSpan,
-    ) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError> {
-        let result_objects = self.interpret_body(nodes, &ctx)?;
-        let result_manifolds = self.filter_objects_to_physical_geometries(result_objects);
-        self.manifold_table.remove_many_into_union(result_manifolds, span)
+    ) -> Result<Option<(GeometryTableEntry, GeometryDisposition, Option<String>)>, RuntimeError> {
+        let result_objects = self.interpret_body(nodes, ctx)?;
+        let result_manifolds = self.filter_objects_to_physical_geometries(result_objects, span.clone())?;
+        if result_manifolds.is_empty() {
+            return Ok(None);
+        }
+
+        let name = self.manifold_table.common_name(&result_manifolds);
+        let (geom, disp) = self.manifold_table.remove_many_into_union(result_manifolds, span)?;
+        Ok(Some((geom, disp, name)))
+    }
+
+    /// Union the geometry which the statements of a definition body produced.
+    fn union_body_geometry(&mut self, result_objects: Vec<Object>, span: InputSourceSpan) -> Result<(GeometryTableEntry, GeometryDisposition, Option<String>), RuntimeError> {
+        let result_manifolds = self.filter_objects_to_physical_geometries(result_objects, span.clone())?;
+        let name = self.manifold_table.common_name(&result_manifolds);
+        let (geom, disp) = self.manifold_table.remove_many_into_union(result_manifolds, span)?;
+        Ok((geom, disp, name))
+    }
+
+    /// Apply an operator to children which have already been evaluated, evaluating its arguments
+    /// with `it` referring to the children.
+    fn apply_operator(
+        &mut self,
+        node: &Node,
+        name: &str,
+        argument_nodes: &Arguments,
+        manifold_children: Vec<GeometryTableIndex>,
+        child_spans: Vec<InputSourceSpan>,
+        ctx: &ExecutionContext,
+    ) -> Result<Object, RuntimeError> {
+        let it_manifold =
+            if manifold_children.len() == 1 {
+                ItManifold::Some(manifold_children.first().unwrap())
+            } else {
+                ItManifold::UnsupportedNotOneChild
+            };
+
+        // Every argument can refer to `it`, however deeply it's nested
+        let arguments = self.evaluate_arguments_for(name, argument_nodes, &ctx.with_it_manifold(it_manifold))?;
+
+        // The result keeps the name of its children, if they agree on one
+        let inherited_name = self.manifold_table.common_name(&manifold_children);
+
+        // We handle user-defined operators and built-in operators differently.
+        //
+        // User-defined operators can use `children` to access a new copy of the children.
+        // To implement this, we create virtual manifolds with all of the children
+        // rendered already. The user code never gets access to these manifolds - only
+        // copies of it - and these virtual manifolds are destroyed afterwards.
+        // (See the implementation for the `children` built-in function.)
+        //
+        // Built-in operators can do their own manifold table manipulation, so these are
+        // directly given the physical manifold indexes. They can do whatever they like with
+        // them.
+        match self.get_existing_callable_name(name, ctx, node.span.clone())? {
+            NameDefinition::UserDefinedOperator(definition) => {
+                let arguments = self.match_arguments_to_parameters(arguments, &definition.parameters, node.span.clone(), Some(&definition.span))?;
+
+                let temporary_virtual_manifolds = manifold_children.into_iter()
+                    .map(|index| {
+                        let (m, _) = self.manifold_table.remove(index);
+                        self.manifold_table.add(m, GeometryDisposition::Virtual, node.span.clone())
+                    })
+                    .collect::<Vec<_>>();
+
+                let BodyGeometry::Combined(geom, disp, body_name) = self.interpret_scoped_definition_body_into_geometry(
+                    &definition.body, ctx, Some(&temporary_virtual_manifolds), arguments,
+                    Frame::new(FrameKind::Operator, name, node.span.clone()),
+                )?
+                else { unreachable!("operator bodies are always combined") };
+
+                for index in temporary_virtual_manifolds {
+                    self.manifold_table.remove(index);
+                }
+
+                self.record_step(name, &geom, &node.span);
+
+                let geometry_name = body_name.or(inherited_name);
+                Ok(self.manifold_table.add_named_into_object(geom, disp, geometry_name, node.span.clone()))
+            }
+
+            NameDefinition::BuiltinOperator(op) => {
+                let arguments = self.match_arguments_to_parameters(arguments, &op.parameters, node.span.clone(), None)?;
+                self.builtin_argument_spans = op.parameters.argument_spans(argument_nodes);
+                let save = self.save_for_timeout(&manifold_children);
+                let result = (op.action)(self, arguments, manifold_children, &child_spans, node.span.clone());
+                let (geom, disp) = self.evaluate_with_timeout(result, save, name, &node.span)?;
+                self.record_step(name, &geom, &node.span);
+                let geometry_name = self.operator_result_name.take().or(inherited_name);
+                let result = self.manifold_table.add_named_into_object(geom, disp, geometry_name, node.span.clone());
+                if mem::take(&mut self.operator_result_kept)
+                    && let Object::Manifold(index) | Object::CrossSection(index) = &result
+                {
+                    self.manifold_table.mark_kept(index);
+                }
+                Ok(result)
+            }
+
+            def @ NameDefinition::UserDefinedFunction(_) => Err(
+                RuntimeError::new(
+                    RuntimeErrorKind::InvalidIdentifier { id: name.to_owned(), kind: def.describe_kind() },
+                    node.span.clone(),
+                )
+                .with_help("functions return a value rather than geometry, so they can't be applied to children")
+            ),
+
+            def => Err(RuntimeError::new(
+                RuntimeErrorKind::InvalidIdentifier {
+                    id: name.to_owned(),
+                    kind: def.describe_kind(),
+                },
+                node.span.clone(),
+            )),
+        }
+    }
+
+    /// Evaluate the body of a user-defined function in a new scope, with a given set of arguments,
+    /// and return its value.
+    /// 
+    /// Like [`Self::interpret_scoped_definition_body_into_geometry`], any error which occurs within
+    /// the body will have the `frame` added to its trace.
+    fn call_function(
+        &mut self,
+        definition: &UserDefinition,
+        ctx: &ExecutionContext,
+        arguments: HashMap<String, Object>,
+        frame: Frame,
+    ) -> Result<Object, RuntimeError> {
+        self.enter_call(&frame.span)?;
+        let profile_start = self.start_profiling();
+        let result = self.interpret(
+            &definition.body[0],
+            &ctx
+                .with_it_manifold(ItManifold::None)
+                .with_operator_children(None)
+                .with_definition_scope()
+                .with_arguments(arguments),
+        );
+        self.call_depth -= 1;
+        let result = result.map_err(|error| error.with_frame(frame.clone()))?;
+
+        self.finish_profiling(profile_start, ProfileEntryKind::Call { kind: frame.kind, name: frame.name }, &frame.span);
+        Ok(result)
+    }
+
+    /// Note that a user-defined module, operator or function is being called, or return an error if
+    /// calls are already nested as deeply as the limit. The caller must decrement
+    /// [`Self::call_depth`] once the call has finished.
+    /// 
+    /// This turns recursion which never stops into an error, rather than a stack overflow.
+    fn enter_call(&mut self, span: &InputSourceSpan) -> Result<(), RuntimeError> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(
+                RuntimeError::new(RuntimeErrorKind::CallDepthExceeded { limit: self.max_call_depth }, span.clone())
+                    .with_help("check that recursive definitions have a case where they stop calling themselves")
+            );
+        }
+
+        self.call_depth += 1;
+        Ok(())
     }
 
     /// Execute a list of nodes in a new scope, with a given set of arguments and children, and
     /// collect any geometry that they generate into a single union'ed geometry. This is how modules
     /// and operators behave.
+    /// 
+    /// The exception is a module whose last statement is a vector of geometry, like
+    /// `[leaf_a, leaf_b]`, which gives back that vector so that the caller can place each part
+    /// separately - see [`Self::body_parts`].
+    /// 
+    /// The `frame` describes the call being executed. Any error which occurs within the body will
+    /// have it added to its trace.
     fn interpret_scoped_definition_body_into_geometry(
         &mut self,
         nodes: &[Node],
         ctx: &ExecutionContext,
         operator_children: Option<&[GeometryTableIndex]>,
         arguments: HashMap<String, Object>,
-        span: InputSourceSpan,
-    ) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError> {
-        self.interpret_body_into_geometry(
+        frame: Frame,
+    ) -> Result<BodyGeometry, RuntimeError> {
+        let span = frame.span.clone();
+        self.enter_call(&span)?;
+        let profile_start = self.start_profiling();
+        let result = self.interpret_definition_body(
             nodes,
             &ctx
                 .with_it_manifold(ItManifold::None)
                 .with_operator_children(operator_children)
-                .with_deeper_scope()
+                .with_definition_scope()
                 .with_arguments(arguments),
+            frame.kind,
             span,
-        )
+        );
+        self.call_depth -= 1;
+        let result = result.map_err(|error| error.with_frame(frame.clone()))?;
+
+        self.finish_profiling(profile_start, ProfileEntryKind::Call { kind: frame.kind, name: frame.name }, &frame.span);
+        Ok(result)
+    }
+
+    /// The body of [`Self::interpret_scoped_definition_body_into_geometry`], within its new scope.
+    fn interpret_definition_body(&mut self, nodes: &[Node], ctx: &ExecutionContext, kind: FrameKind, span: InputSourceSpan) -> Result<BodyGeometry, RuntimeError> {
+        let result_objects = self.interpret_body(nodes, ctx)?;
+        if kind == FrameKind::Module
+            && let Some(parts) = self.body_parts(&result_objects, &span)?
+        {
+            return Ok(BodyGeometry::Parts(parts));
+        }
+
+        let (geom, disp, name) = self.union_body_geometry(result_objects, span)?;
+        Ok(BodyGeometry::Combined(geom, disp, name))
+    }
+
+    /// If the last of a module body's results is a vector of geometry, that vector, which the module
+    /// returns instead of combining its geometry. The geometry in the vector is left as it is in
+    /// the table.
+    /// 
+    /// Any other geometry which the body produced would be left behind, so it's an error.
+    fn body_parts(&self, result_objects: &[Object], span: &InputSourceSpan) -> Result<Option<Object>, RuntimeError> {
+        let Some((parts @ Object::Vector(items, _), others)) = result_objects.split_last()
+        else { return Ok(None) };
+
+        let is_geometry = |object: &Object| matches!(object, Object::Manifold(_) | Object::CrossSection(_));
+        if items.is_empty() || !items.iter().all(is_geometry) {
+            return Ok(None)
+        }
+
+        let part_indices = self.filter_objects_to_geometry(items.clone());
+        for index in &part_indices {
+            self.manifold_table.get(index, span.clone())?;
+        }
+
+        let left_behind = self.filter_objects_to_physical_geometries(others.to_vec(), span.clone())?
+            .into_iter()
+            .find(|index| !part_indices.contains(index));
+        if let Some(index) = left_behind {
+            return Err(
+                RuntimeError::new(
+                    RuntimeErrorKind::AssertionError("this geometry isn't part of the vector which the module returns, so would be lost".to_owned()),
+                    self.manifold_table.get_provenance(&index).clone(),
+                )
+                .with_help("add it to the vector, or combine it with one of the parts")
+            );
+        }
+
+        Ok(Some(parts.clone()))
     }
 
     /// Given a list of objects, filter it down to only manifolds, and return them.
+    /// 
+    /// The geometry in a vector, like the result of an operator applied to a module's parts, is
+    /// included too.
     fn filter_objects_to_geometry(&self, objects: Vec<Object>) -> Vec<GeometryTableIndex> {
         objects.into_iter()
+            .flat_map(|child| match child {
+                Object::Vector(items, _) => items,
+                child => vec![child],
+            })
             .filter_map(|child|
                 if let Object::Manifold(index) | Object::CrossSection(index) = child {
                     Some(index)
@@ -472,17 +1288,22 @@ impl Interpreter {
     }
 
     /// Given a list of objects, filter it down to only the *physical* geometries, and return them.
-    fn filter_objects_to_physical_geometries(&self, objects: Vec<Object>) -> Vec<GeometryTableIndex> {
-        self.filter_objects_to_geometry(objects)
-            .into_iter()
-            .filter(|index| self.manifold_table.get_disposition(index) == GeometryDisposition::Physical)
-            .collect()
+    fn filter_objects_to_physical_geometries(&self, objects: Vec<Object>, span: InputSourceSpan) -> Result<Vec<GeometryTableIndex>, RuntimeError> {
+        let mut physical = vec![];
+        for index in self.filter_objects_to_geometry(objects) {
+            if self.manifold_table.get_disposition(&index, span.clone())? == GeometryDisposition::Physical {
+                physical.push(index);
+            }
+        }
+        Ok(physical)
     }
 
     /// Look up a name.
-    fn get_name(&self, name: &str, ctx: &ExecutionContext) -> Option<NameDefinition> {
-        if let Some(object) = ctx.lexical_scope.borrow().get_binding(name) {
-            return Some(NameDefinition::Binding(object))
+    pub(crate) fn get_name(&self, name: &str, ctx: &ExecutionContext) -> Option<NameDefinition> {
+        let scoped = ctx.lexical_scope.borrow().get_definition(name);
+
+        if let Some(ScopeDefinition::Binding(object)) = &scoped {
+            return Some(NameDefinition::Binding(object.clone()))
         }
 
         if let Some(object) = ctx.arguments.get(name) {
@@ -493,45 +1314,128 @@ impl Interpreter {
             return Some(NameDefinition::BuiltinModule(module))
         }
 
-        if let Some((parameters, body)) = ctx.lexical_scope.borrow().get_module(name) {
-            return Some(NameDefinition::UserDefinedModule { parameters, body })
+        if let Some(ScopeDefinition::Module(definition)) = &scoped {
+            return Some(NameDefinition::UserDefinedModule(definition.clone()))
+        }
+
+        if let Some(ScopeDefinition::Function(definition)) = &scoped {
+            return Some(NameDefinition::UserDefinedFunction(definition.clone()))
         }
 
         if let Some(operator) = builtin::get_builtin_operator(name) {
             return Some(NameDefinition::BuiltinOperator(operator))
         }
 
-        if let Some((parameters, body)) = ctx.lexical_scope.borrow().get_operator(name) {
-            return Some(NameDefinition::UserDefinedOperator { parameters, body })
+        if let Some(ScopeDefinition::Operator(definition)) = &scoped {
+            return Some(NameDefinition::UserDefinedOperator(definition.clone()))
         }
 
         None
     }
 
+    /// Like [`Self::get_name`], for a name which is being called or applied as an operator.
+    /// 
+    /// A binding or parameter can have the same name as a built-in module or operator, like
+    /// `offset = 2;`. It can't be called, so the built-in is found instead.
+    fn get_callable_name(&self, name: &str, ctx: &ExecutionContext) -> Option<NameDefinition> {
+        self.get_name(name, ctx).map(|def| prefer_builtin(name, def))
+    }
+
     /// Like [`Self::get_name`] but returns a [`RuntimeErrorKind::UndefinedIdentifier`] if the name
     /// is not defined.
+    /// 
+    /// If there's a similar name which is defined, it's suggested as help on the error.
     fn get_existing_name(&self, name: &str, ctx: &ExecutionContext, span: InputSourceSpan) -> Result<NameDefinition, RuntimeError> {
-        self.get_name(name, ctx).ok_or_else(||
-            RuntimeError::new(RuntimeErrorKind::UndefinedIdentifier(name.to_owned()), span))
+        self.get_name(name, ctx).ok_or_else(|| {
+            let error = RuntimeError::new(RuntimeErrorKind::UndefinedIdentifier(name.to_owned()), span);
+
+            let mut candidates = vec![];
+            ctx.lexical_scope.borrow().visible_names(&mut candidates);
+            candidates.extend(ctx.arguments.keys().cloned());
+            candidates.extend(builtin::BUILTIN_MODULE_NAMES.iter().chain(builtin::BUILTIN_MATH_NAMES).chain(builtin::BUILTIN_OPERATOR_NAMES).map(|name| name.to_string()));
+
+            let mut help = closest_name(name, &candidates).map(|suggestion| format!("did you mean \"{suggestion}\"?"));
+            if self.verbose_errors {
+                let mut bindings = vec![];
+                ctx.lexical_scope.borrow().visible_binding_names(&mut bindings);
+                bindings.extend(ctx.arguments.keys().cloned());
+                bindings.sort();
+                bindings.dedup();
+
+                let listing = if bindings.is_empty() {
+                    "no bindings are in scope".to_owned()
+                } else {
+                    format!("bindings in scope: {}", bindings.join(", "))
+                };
+                help = Some(match help {
+                    Some(help) => format!("{help}\n{listing}"),
+                    None => listing,
+                });
+            }
+
+            match help {
+                Some(help) => error.with_help(help),
+                None => error,
+            }
+        })
+    }
+
+    /// Like [`Self::get_existing_name`], for a name which is being called or applied as an operator
+    /// - see [`Self::get_callable_name`].
+    fn get_existing_callable_name(&self, name: &str, ctx: &ExecutionContext, span: InputSourceSpan) -> Result<NameDefinition, RuntimeError> {
+        self.get_existing_name(name, ctx, span).map(|def| prefer_builtin(name, def))
+    }
+
+    /// Change the value of an existing binding, for a `set` statement.
+    ///
+    /// Returns an error if there's no binding with this name which can be changed from here.
+    fn reassign_name(&self, name: &str, value: Object, ctx: &ExecutionContext, span: InputSourceSpan) -> Result<(), RuntimeError> {
+        if ctx.lexical_scope.borrow_mut().reassign_binding(name, value) {
+            return Ok(());
+        }
+
+        Err(match self.get_existing_name(name, ctx, span.clone())? {
+            NameDefinition::Binding(_) => RuntimeError::new(RuntimeErrorKind::ReassignmentOutsideDefinition(name.to_owned()), span)
+                .with_help("pass the value in as an argument instead"),
+            def => RuntimeError::new(RuntimeErrorKind::InvalidIdentifier { id: name.to_owned(), kind: def.describe_kind() }, span)
+                .with_help("only bindings can be changed with `set`"),
+        })
     }
 
     /// Define a new name.
     /// 
     /// Returns an error if the name is already defined.
-    fn add_name(&self, name: &str, def: NameDefinition, ctx: &ExecutionContext, span: InputSourceSpan) -> Result<(), RuntimeError> {
-        if self.get_name(name, ctx).is_some() {
-            return Err(RuntimeError::new(RuntimeErrorKind::DuplicateName(name.to_owned()), span))
+    pub(crate) fn add_name(&self, name: &str, def: NameDefinition, ctx: &ExecutionContext, span: InputSourceSpan) -> Result<(), RuntimeError> {
+        // Like `get_name`, but definitions in shadowable scopes don't count. Bindings can share a
+        // name with a built-in, since they're never called - see `get_callable_name`
+        let defined = ctx.lexical_scope.borrow().defines_unshadowable(name)
+            || ctx.arguments.contains_key(name)
+            || (!matches!(def, NameDefinition::Binding(_))
+                && (builtin::get_builtin_module(name).is_some() || builtin::get_builtin_operator(name).is_some()));
+        if defined {
+            let error = RuntimeError::new(RuntimeErrorKind::DuplicateName(name.to_owned()), span);
+            let existing = ctx.lexical_scope.borrow().get_definition(name);
+            return Err(match existing {
+                Some(ScopeDefinition::Module(definition) | ScopeDefinition::Operator(definition) | ScopeDefinition::Function(definition)) =>
+                    error.with_definition(&definition.span),
+                Some(ScopeDefinition::Binding(_)) if matches!(def, NameDefinition::Binding(_)) =>
+                    error.with_help(format!("to change its value, use `set {name} = ...` instead")),
+                _ => error,
+            })
         }
 
         match def {
             NameDefinition::Binding(object) => {
                 ctx.lexical_scope.borrow_mut().add_binding(name.to_owned(), object);
             }
-            NameDefinition::UserDefinedOperator { parameters, body } => {
-                ctx.lexical_scope.borrow_mut().add_operator(name.to_owned(), parameters, body);
+            NameDefinition::UserDefinedOperator(definition) => {
+                ctx.lexical_scope.borrow_mut().add_operator(name.to_owned(), definition);
             }
-            NameDefinition::UserDefinedModule  { parameters, body } => {
-                ctx.lexical_scope.borrow_mut().add_module(name.to_owned(), parameters, body);
+            NameDefinition::UserDefinedModule(definition) => {
+                ctx.lexical_scope.borrow_mut().add_module(name.to_owned(), definition);
+            }
+            NameDefinition::UserDefinedFunction(definition) => {
+                ctx.lexical_scope.borrow_mut().add_function(name.to_owned(), definition);
             }
 
             NameDefinition::Argument(_)
@@ -544,19 +1448,64 @@ impl Interpreter {
 
     /// Evaluate [`Arguments`]  into [`EvaluatedArguments`] using the interpreter.
     pub fn evaluate_arguments(&mut self, arguments: &Arguments, ctx: &ExecutionContext) -> Result<EvaluatedArguments, RuntimeError> {
+        self.evaluate_arguments_discarding(arguments, ctx, false)
+    }
+
+    /// Like [`Self::evaluate_arguments`], for a call to `name`.
+    /// 
+    /// Built-in modules and operators only take vectors of numbers, so any geometry created while
+    /// evaluating a vector literal argument to one of them can't be used, and is removed from the
+    /// table rather than being left in the scene. The exception is `__debug`, which can print
+    /// anything.
+    fn evaluate_arguments_for(&mut self, name: &str, arguments: &Arguments, ctx: &ExecutionContext) -> Result<EvaluatedArguments, RuntimeError> {
+        let builtin = name != "__debug"
+            && matches!(self.get_callable_name(name, ctx), Some(NameDefinition::BuiltinModule(_) | NameDefinition::BuiltinOperator(_)));
+        self.evaluate_arguments_discarding(arguments, ctx, builtin)
+    }
+
+    fn evaluate_arguments_discarding(&mut self, arguments: &Arguments, ctx: &ExecutionContext, discard_vector_geometry: bool) -> Result<EvaluatedArguments, RuntimeError> {
+        // If these were meant to be one number, the arguments are unlikely to match - but then the
+        // build fails, and the warning is never shown
+        self.warn_decimal_commas(&arguments.positional);
+
+        let mut evaluate = |arg: &Node| {
+            if discard_vector_geometry && matches!(arg.kind, NodeKind::VectorLiteral(_)) {
+                let key = self.manifold_table.next_key();
+                let result = self.interpret(arg, ctx);
+                self.manifold_table.remove_added_since(key);
+                result
+            } else {
+                self.interpret(arg, ctx)
+            }
+        };
+
         Ok(EvaluatedArguments {
             positional: arguments.positional.iter()
-                .map(|arg| self.interpret(arg, ctx))
+                .map(&mut evaluate)
                 .collect::<Result<Vec<_>, _>>()?,
             named: arguments.named.iter()
-                .map(|(name, arg)| self.interpret(arg, ctx).map(|obj| (name.clone(), obj)))
+                .map(|(name, arg)| evaluate(arg).map(|obj| (name.clone(), obj)))
                 .collect::<Result<Vec<_>, _>>()?,
         })
     }
 
+    /// Where the argument for the parameter `name` was written in the built-in module or operator
+    /// call being run, or the call itself if it wasn't given explicitly.
+    pub(crate) fn argument_span(&self, name: &str, call_span: &InputSourceSpan) -> InputSourceSpan {
+        self.builtin_argument_spans.get(name).unwrap_or(call_span).clone()
+    }
+
     /// Given a list of arguments and parameters, match the arguments to parameters, and return a
     /// set of parameter names matched to argument values (or defaults).
-    fn match_arguments_to_parameters(&mut self, arguments: EvaluatedArguments, parameters: EvaluatedParameters, span: InputSourceSpan) -> Result<HashMap<String, Object>, RuntimeError> {
+    ///
+    /// For user-defined modules and operators, `definition` is the span of the definition, which
+    /// errors point to.
+    pub(crate) fn match_arguments_to_parameters(&mut self, arguments: EvaluatedArguments, parameters: &EvaluatedParameters, span: InputSourceSpan, definition: Option<&InputSourceSpan>) -> Result<HashMap<String, Object>, RuntimeError> {
+        let with_definition = |error: RuntimeError| match definition {
+            Some(definition) => error.with_definition(definition),
+            None => error,
+        };
+
         // TODO: validate on definition that parameter names are unique
 
         // Check that named arguments are specified no more than once
@@ -572,13 +1521,14 @@ impl Interpreter {
 
         // Validate that there aren't more positional arguments than we can possibly ever accept
         if arguments.positional.len() > parameters.max_len() {
-            return Err(RuntimeError::new(
+            let error = RuntimeError::new(
                 RuntimeErrorKind::IncorrectArity {
                     expected: parameters.len_range(),
                     actual: arguments.positional.len(),
                 },
                 span,
-            ));
+            );
+            return Err(with_definition(error.with_help(parameters.describe_extra_arguments(arguments.positional.len()))));
         }
 
         // Map positional arguments to ascending parameters
@@ -593,7 +1543,7 @@ impl Interpreter {
         // and do actually exist
         for (name, arg) in &arguments.named {
             if !parameters.names().contains(name) {
-                return Err(RuntimeError::new(RuntimeErrorKind::UndefinedNamedArgument(name.to_owned()), span))
+                return Err(with_definition(RuntimeError::new(RuntimeErrorKind::UndefinedNamedArgument(name.to_owned()), span)))
             }
 
             if let Some((_, loc)) = map.get(name) {
@@ -612,7 +1562,7 @@ impl Interpreter {
             .cloned()
             .collect::<Vec<_>>();
         if !missing_required_params.is_empty() {
-            return Err(RuntimeError::new(RuntimeErrorKind::MissingNamedArguments(missing_required_params), span))
+            return Err(with_definition(RuntimeError::new(RuntimeErrorKind::MissingNamedArguments(missing_required_params), span)))
         }
 
         // For any optional parameters where values weren't given, instantiate the default
@@ -637,6 +1587,160 @@ impl Default for Interpreter {
     }
 }
 
+/// A single physical geometry from the final scene, built into a standalone manifold.
+#[derive(Debug, Clone)]
+pub struct ScenePart {
+    pub manifold: Manifold,
+
+    /// The source code which produced this part.
+    pub provenance: InputSourceSpan,
+
+    /// The name given to this part by `part`, if any.
+    pub name: Option<String>,
+
+    /// Identifies this part across builds. It stays the same as long as the source code which
+    /// produced the part doesn't change, even if the code around it does.
+    pub id: u64,
+}
+
+/// A physical geometry in the table, from [`Interpreter::ordered_physical_geometry`].
+pub(crate) struct OrderedGeometry<'a> {
+    pub entry: &'a GeometryTableEntry,
+    pub provenance: &'a InputSourceSpan,
+    pub name: Option<&'a str>,
+
+    /// See [`ScenePart::id`].
+    pub id: u64,
+}
+
+/// What the body of a module or operator produced, from
+/// [`Interpreter::interpret_scoped_definition_body_into_geometry`].
+enum BodyGeometry {
+    /// All of the body's geometry, combined into one, with the name it should have.
+    Combined(GeometryTableEntry, GeometryDisposition, Option<String>),
+
+    /// A vector of separate geometries, returned by a module.
+    Parts(Object),
+}
+
+/// The built-in operator which a binary operator stands for when both sides are geometry.
+fn geometry_binop_operator(op: BinaryOperator) -> Option<&'static str> {
+    match op {
+        BinaryOperator::Add => Some("union"),
+        BinaryOperator::Subtract => Some("difference"),
+        BinaryOperator::Multiply => Some("intersection"),
+        _ => None,
+    }
+}
+
+/// Whether an operator's child statement gave a vector of geometry, like the parts returned by a
+/// module, whose elements are each children of the operator.
+/// 
+/// Bindings give back their value too, but a vector in a binding is only being stored for later.
+fn is_geometry_vector(child: &Node, object: &Object) -> bool {
+    let Object::Vector(items, _) = object
+    else { return false };
+
+    !matches!(child.kind, NodeKind::Binding { .. } | NodeKind::Reassignment { .. })
+        && !items.is_empty()
+        && items.iter().all(|item| matches!(item, Object::Manifold(_) | Object::CrossSection(_)))
+}
+
+/// The built-in module or operator named `name` in place of `def`, if `def` is a binding or
+/// parameter which can't be called.
+fn prefer_builtin(name: &str, def: NameDefinition) -> NameDefinition {
+    if !matches!(def, NameDefinition::Binding(_) | NameDefinition::Argument(_)) {
+        return def;
+    }
+
+    if let Some(module) = builtin::get_builtin_module(name) {
+        NameDefinition::BuiltinModule(module)
+    } else if let Some(operator) = builtin::get_builtin_operator(name) {
+        NameDefinition::BuiltinOperator(operator)
+    } else {
+        def
+    }
+}
+
+/// Whether a top-level statement is a definition which can be interpreted before the statements
+/// preceding it. Its defaults are evaluated straight away, so they mustn't refer to any names.
+pub(crate) fn can_hoist(node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::ModuleDefinition { parameters, .. }
+        | NodeKind::OperatorDefinition { parameters, .. }
+        | NodeKind::FunctionDefinition { parameters, .. } =>
+            parameters.optional.iter()
+                .all(|(_, default)| StatementNames::of_statement(default).referenced.is_empty()),
+        _ => false,
+    }
+}
+
+/// If an error is about an undefined name which one of the `later` statements defines, explain
+/// that the definition couldn't be hoisted above the statement which used it.
+pub(crate) fn explain_unhoisted(error: RuntimeError, later: &[Node]) -> RuntimeError {
+    let RuntimeErrorKind::UndefinedIdentifier(id) = &error.kind else { return error };
+
+    let definition = later.iter().find(|node| match &node.kind {
+        NodeKind::ModuleDefinition { name, .. }
+        | NodeKind::OperatorDefinition { name, .. }
+        | NodeKind::FunctionDefinition { name, .. } => name == id && !can_hoist(node),
+        _ => false,
+    });
+    match definition {
+        Some(definition) => {
+            let help = format!("`{id}` is defined later in the file, but can't be used before its definition because its parameter defaults refer to other names");
+            error.with_help(help).with_definition(&definition.span)
+        }
+        None => error,
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+/// Hash some bytes with FNV-1a, which unlike [`std::hash::DefaultHasher`] is guaranteed to give the
+/// same result in every build of YASCAD.
+fn fnv1a(bytes: &[u8], basis: u64) -> u64 {
+    bytes.iter().fold(basis, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Hashes the file and source code which produced each part, but not where it is.
+/// 
+/// Spans count characters rather than bytes, so each file is indexed once, rather than being
+/// scanned again for every part in it.
+#[derive(Default)]
+pub(crate) struct PartSourceHasher {
+    /// The byte offset of each character in each source, plus the source's length, by the
+    /// source's address. The source is kept alive too, so that its address can't be reused.
+    char_offsets: HashMap<*const InputSource, (Rc<InputSource>, Vec<usize>)>,
+}
+
+impl PartSourceHasher {
+    pub(crate) fn part_source_id(&mut self, provenance: &InputSourceSpan) -> u64 {
+        let content = provenance.source.content();
+        let (_, offsets) = self.char_offsets.entry(Rc::as_ptr(&provenance.source))
+            .or_insert_with(|| (
+                provenance.source.clone(),
+                content.char_indices().map(|(offset, _)| offset).chain([content.len()]).collect(),
+            ));
+        let byte_offset = |char_offset: usize| offsets[char_offset.min(offsets.len() - 1)];
+        let text = &content[byte_offset(provenance.start)..byte_offset(provenance.start + provenance.length)];
+
+        let hash = fnv1a(provenance.source.origin().name().as_bytes(), FNV_OFFSET_BASIS);
+        let hash = fnv1a(&[0], hash);
+        fnv1a(text.as_bytes(), hash)
+    }
+}
+
+/// All of the physical geometry from the final scene with the same part name, built into one
+/// manifold.
+#[derive(Debug, Clone)]
+pub struct SceneGroup {
+    pub manifold: Manifold,
+
+    /// The name given by `part`, or [`None`] for geometry which wasn't named.
+    pub name: Option<String>,
+}
+
 /// Describes the manifold which will be referenced by `it`.
 #[derive(Clone, Copy, Debug)]
 pub enum ItManifold<'a> {
@@ -657,16 +1761,25 @@ pub enum NameDefinition {
     Argument(Object),
 
     BuiltinModule(ModuleDefinition),
-    UserDefinedModule {
-        parameters: EvaluatedParameters,
-        body: Vec<Node>,
-    },
+    UserDefinedModule(Rc<UserDefinition>),
 
     BuiltinOperator(OperatorDefinition),
-    UserDefinedOperator {
-        parameters: EvaluatedParameters,
-        body: Vec<Node>,
-    },
+    UserDefinedOperator(Rc<UserDefinition>),
+
+    UserDefinedFunction(Rc<UserDefinition>),
+}
+
+/// A module, operator or function defined in user code. A function's body is a single expression.
+///
+/// These are shared with [`Rc`] rather than being cloned each time they're looked up, since the
+/// body could be large and called many times.
+#[derive(Debug)]
+pub struct UserDefinition {
+    pub parameters: EvaluatedParameters,
+    pub body: Vec<Node>,
+
+    /// The whole definition, for pointing at in errors.
+    pub span: InputSourceSpan,
 }
 
 impl NameDefinition {
@@ -675,17 +1788,18 @@ impl NameDefinition {
             NameDefinition::Binding(_) => "binding",
             NameDefinition::Argument(_) => "parameter",
             NameDefinition::BuiltinModule(_) => "built-in module",
-            NameDefinition::UserDefinedModule { .. } => "user-defined module",
+            NameDefinition::UserDefinedModule(_) => "user-defined module",
             NameDefinition::BuiltinOperator(_) => "built-in operator",
-            NameDefinition::UserDefinedOperator { .. } => "user-defined operator",
+            NameDefinition::UserDefinedOperator(_) => "user-defined operator",
+            NameDefinition::UserDefinedFunction(_) => "user-defined function",
         }.to_string()
     }
 }
 
 /// A collection of evaluated arguments.
 pub struct EvaluatedArguments {
-    positional: Vec<Object>,
-    named: Vec<(String, Object)>,
+    pub(crate) positional: Vec<Object>,
+    pub(crate) named: Vec<(String, Object)>,
 }
 
 /// A collection of parameters with evaluated defaults.
@@ -741,6 +1855,34 @@ impl EvaluatedParameters {
             .chain(self.optional.iter().map(|(name, _)| name.clone()))
     }
 
+    /// Where each argument was written in a call with these parameters, by parameter name.
+    pub fn argument_spans(&self, arguments: &Arguments) -> HashMap<String, InputSourceSpan> {
+        zip(self.ordered_positional_names(), &arguments.positional)
+            .map(|(name, node)| (name, node.span.clone()))
+            .chain(arguments.named.iter().map(|(name, node)| (name.clone(), node.span.clone())))
+            .collect()
+    }
+
+    /// Explain which arguments are extra when `count` positional arguments were given, which is
+    /// more than [`Self::max_len`].
+    pub fn describe_extra_arguments(&self, count: usize) -> String {
+        let first_extra = self.max_len() + 1;
+        let extra = if count == first_extra {
+            format!("the {} argument is extra", ordinal(first_extra))
+        } else if count == first_extra + 1 {
+            format!("the {} and {} arguments are extra", ordinal(first_extra), ordinal(count))
+        } else {
+            format!("the {} to {} arguments are extra", ordinal(first_extra), ordinal(count))
+        };
+
+        if self.max_len() == 0 {
+            return format!("this takes no positional arguments, so {extra}");
+        }
+
+        let names = self.ordered_positional_names().collect::<Vec<_>>().join(", ");
+        format!("the parameters are ({names}), so {extra}")
+    }
+
     /// All optional parameters, both positional and named-only.
     pub fn all_optionals(&self) -> impl Iterator<Item = &(String, Object)> {
         self.optional.iter()
@@ -755,3 +1897,15 @@ impl EvaluatedParameters {
             .collect()
     }
 }
+
+/// Format a number like "1st", "2nd" or "11th".
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
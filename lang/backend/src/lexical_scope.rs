@@ -1,15 +1,32 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use yascad_frontend::Node;
+use crate::{UserDefinition, object::Object};
 
-use crate::{EvaluatedParameters, object::Object};
+/// Something defined in a [`LexicalScope`].
+#[derive(Debug)]
+pub enum ScopeDefinition {
+    Binding(Object),
+    Module(Rc<UserDefinition>),
+    Operator(Rc<UserDefinition>),
+    Function(Rc<UserDefinition>),
+}
 
 #[derive(Debug)]
 pub struct LexicalScope {
     bindings: HashMap<String, Object>,
-    operators: HashMap<String, (EvaluatedParameters, Vec<Node>)>,
-    modules: HashMap<String, (EvaluatedParameters, Vec<Node>)>,
+    operators: HashMap<String, Rc<UserDefinition>>,
+    modules: HashMap<String, Rc<UserDefinition>>,
+    functions: HashMap<String, Rc<UserDefinition>>,
     pub parent: Option<Rc<RefCell<LexicalScope>>>,
+
+    /// Whether nested scopes can define names which are already defined here, replacing them
+    /// within the nested scope. This is used for the prelude.
+    pub shadowable: bool,
+
+    /// Whether this is the outermost scope of a module or operator body. Bindings outside of it
+    /// can't be reassigned from within, so that calling a definition can't change its caller's
+    /// bindings.
+    pub definition_body: bool,
 }
 
 impl LexicalScope {
@@ -18,7 +35,10 @@ impl LexicalScope {
             bindings: HashMap::new(),
             operators: HashMap::new(),
             modules: HashMap::new(),
+            functions: HashMap::new(),
             parent: None,
+            shadowable: false,
+            definition_body: false,
         }
     }
 
@@ -27,79 +47,163 @@ impl LexicalScope {
             bindings: HashMap::new(),
             operators: HashMap::new(),
             modules: HashMap::new(),
+            functions: HashMap::new(),
             parent: Some(parent),
+            shadowable: false,
+            definition_body: false,
         }
     }
 
-    pub fn get_binding(&self, name: &str) -> Option<Object> {
-        if let Some(object) = self.bindings.get(name) {
-            return Some(object.clone());
+    /// Find the nearest definition of `name` in this scope or its parents.
+    /// 
+    /// Each scope is searched in full before its parent, so that a definition in a nested scope
+    /// hides one in a shadowable scope even if they're different kinds of definition.
+    pub fn get_definition(&self, name: &str) -> Option<ScopeDefinition> {
+        self.find_in_chain(|scope| {
+            if let Some(object) = scope.bindings.get(name) {
+                return Some(ScopeDefinition::Binding(object.clone()));
+            }
+            if let Some(item) = scope.modules.get(name) {
+                return Some(ScopeDefinition::Module(item.clone()));
+            }
+            if let Some(item) = scope.operators.get(name) {
+                return Some(ScopeDefinition::Operator(item.clone()));
+            }
+            if let Some(item) = scope.functions.get(name) {
+                return Some(ScopeDefinition::Function(item.clone()));
+            }
+            None
+        })
+    }
+
+    /// Whether anything named `name` is defined in this scope or its parents, excluding
+    /// shadowable scopes.
+    pub fn defines_unshadowable(&self, name: &str) -> bool {
+        self.find_in_chain(|scope| {
+            let defined_here = scope.bindings.contains_key(name)
+                || scope.operators.contains_key(name)
+                || scope.modules.contains_key(name)
+                || scope.functions.contains_key(name);
+            (defined_here && !scope.shadowable).then_some(())
+        }).is_some()
+    }
+
+    /// Call `f` on this scope, then on each of its parents in turn, until it returns [`Some`].
+    /// 
+    /// Only one parent is borrowed at a time, and never while `f` could be running on another
+    /// scope, so that walking the chain can't overlap with a borrow elsewhere in it.
+    fn find_in_chain<T>(&self, mut f: impl FnMut(&LexicalScope) -> Option<T>) -> Option<T> {
+        if let Some(found) = f(self) {
+            return Some(found);
         }
 
-        if let Some(parent) = self.parent.as_ref() {
-            parent.borrow().get_binding(name)
-        } else {
-            None
+        let mut next = self.parent.clone();
+        while let Some(scope) = next {
+            let scope = scope.borrow();
+            if let Some(found) = f(&scope) {
+                return Some(found);
+            }
+            next = scope.parent.clone();
         }
+        None
     }
 
     /// Add a new value binding to this scope.
     /// 
-    /// Panics if a binding with this name already exists. It's the caller's responsibility to check
-    /// for conflicts, as it may have names beyond the lexical scope which we don't know about.
+    /// Panics if a binding with this name already exists, outside of a shadowable scope. It's the
+    /// caller's responsibility to check for conflicts, as it may have names beyond the lexical
+    /// scope which we don't know about.
     pub fn add_binding(&mut self, name: String, value: Object) {
-        if self.get_binding(&name).is_some() {
+        if self.defines_unshadowable(&name) {
             panic!("binding {name} already exists");
         }
 
         self.bindings.insert(name, value);
     }
 
-    pub fn get_operator(&self, name: &str) -> Option<(EvaluatedParameters, Vec<Node>)> {
-        if let Some(item) = self.operators.get(name) {
-            return Some(item.clone());
+    /// Change the value of the nearest binding named `name`, in this scope or its parents.
+    ///
+    /// The search doesn't go beyond the outermost scope of a definition body, or into shadowable
+    /// scopes. Returns false, having changed nothing, if there's no binding to change.
+    pub fn reassign_binding(&mut self, name: &str, value: Object) -> bool {
+        if self.shadowable {
+            return false;
+        }
+        if let Some(binding) = self.bindings.get_mut(name) {
+            *binding = value;
+            return true;
         }
 
-        if let Some(parent) = self.parent.as_ref() {
-            parent.borrow().get_operator(name)
-        } else {
-            None
+        let mut next = if self.definition_body { None } else { self.parent.clone() };
+        while let Some(scope) = next {
+            let mut scope = scope.borrow_mut();
+            if scope.shadowable {
+                return false;
+            }
+            if let Some(binding) = scope.bindings.get_mut(name) {
+                *binding = value;
+                return true;
+            }
+            next = if scope.definition_body { None } else { scope.parent.clone() };
         }
+        false
     }
 
-    pub fn get_module(&self, name: &str) -> Option<(EvaluatedParameters, Vec<Node>)> {
-        if let Some(item) = self.modules.get(name) {
-            return Some(item.clone());
-        }
+    /// Add the names of everything defined in this scope and its parents to `names`.
+    pub fn visible_names(&self, names: &mut Vec<String>) {
+        self.find_in_chain::<()>(|scope| {
+            names.extend(scope.bindings.keys().cloned());
+            names.extend(scope.operators.keys().cloned());
+            names.extend(scope.modules.keys().cloned());
+            names.extend(scope.functions.keys().cloned());
+            None
+        });
+    }
 
-        if let Some(parent) = self.parent.as_ref() {
-            parent.borrow().get_module(name)
-        } else {
+    /// Like [`Self::visible_names`], but only the names of bindings.
+    pub fn visible_binding_names(&self, names: &mut Vec<String>) {
+        self.find_in_chain::<()>(|scope| {
+            names.extend(scope.bindings.keys().cloned());
             None
-        }
+        });
     }
 
     /// Add a new operator definition to this scope.
     /// 
-    /// Panics if an operator with this name already exists. It's the caller's responsibility to
-    /// check for conflicts, as it may have names beyond the lexical scope which we don't know about.
-    pub fn add_operator(&mut self, name: String, parameters: EvaluatedParameters, body: Vec<Node>) {
-        if self.get_operator(&name).is_some() {
+    /// Panics if an operator with this name already exists, outside of a shadowable scope. It's the
+    /// caller's responsibility to check for conflicts, as it may have names beyond the lexical
+    /// scope which we don't know about.
+    pub fn add_operator(&mut self, name: String, definition: Rc<UserDefinition>) {
+        if self.defines_unshadowable(&name) {
             panic!("operator {name} already exists");
         }
 
-        self.operators.insert(name, (parameters, body));
+        self.operators.insert(name, definition);
     }
 
-    /// Add a new operator definition to this scope.
+    /// Add a new module definition to this scope.
     /// 
-    /// Panics if an operator with this name already exists. It's the caller's responsibility to
-    /// check for conflicts, as it may have names beyond the lexical scope which we don't know about.
-    pub fn add_module(&mut self, name: String, parameters: EvaluatedParameters, body: Vec<Node>) {
-        if self.get_module(&name).is_some() {
+    /// Panics if a module with this name already exists, outside of a shadowable scope. It's the
+    /// caller's responsibility to check for conflicts, as it may have names beyond the lexical
+    /// scope which we don't know about.
+    pub fn add_module(&mut self, name: String, definition: Rc<UserDefinition>) {
+        if self.defines_unshadowable(&name) {
             panic!("module {name} already exists");
         }
 
-        self.modules.insert(name, (parameters, body));
+        self.modules.insert(name, definition);
+    }
+
+    /// Add a new function definition to this scope.
+    /// 
+    /// Panics if a function with this name already exists, outside of a shadowable scope. It's the
+    /// caller's responsibility to check for conflicts, as it may have names beyond the lexical
+    /// scope which we don't know about.
+    pub fn add_function(&mut self, name: String, definition: Rc<UserDefinition>) {
+        if self.defines_unshadowable(&name) {
+            panic!("function {name} already exists");
+        }
+
+        self.functions.insert(name, definition);
     }
 }
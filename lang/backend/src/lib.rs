@@ -1,13 +1,56 @@
 #![feature(type_alias_impl_trait)]
 
 mod object;
+pub use object::ObjectSnapshot;
 mod geometry_table;
+pub use geometry_table::GeometryDisposition;
 mod lexical_scope;
 
 mod error;
 pub use error::*;
 
+mod warning;
+pub use warning::*;
+
+mod paths;
+pub use paths::FileAccessPolicy;
+
+mod include;
+
+mod suggestions;
+
+mod surface;
+
+mod font;
+
 mod builtin;
+pub use builtin::{BuiltinDoc, BuiltinKind, DEFAULT_SHAPE_TOLERANCE, ParameterDoc, all_builtins, format_builtin_reference, shape_difference};
+
+mod units;
+pub use units::*;
+
+mod scene_dump;
+pub use scene_dump::*;
+
+mod steps;
+pub use steps::*;
+
+mod profile;
+pub use profile::{Profile, ProfileEntry, ProfileEntryKind, format_profile};
+
+mod timeout;
+
+mod rebuild;
+pub use rebuild::StatementResults;
+
+mod prelude;
+pub use prelude::PRELUDE_SOURCE;
+
+mod instancing;
+pub use instancing::{PreviewPart, ScenePreview, SceneInstances};
 
 mod interpreter;
 pub use interpreter::*;
+
+#[cfg(test)]
+mod tests;
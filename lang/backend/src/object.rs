@@ -1,4 +1,6 @@
-use manifold_rs::{Vec2, Vec3};
+use std::rc::Rc;
+
+use manifold_rs::{CrossSection, Manifold, Vec2, Vec3};
 use yascad_frontend::InputSourceSpan;
 
 use crate::{RuntimeError, RuntimeErrorKind, geometry_table::{GeometryTable, GeometryTableIndex}};
@@ -8,29 +10,42 @@ pub enum Object {
     Null,
     Number(f64),
     Boolean(bool),
+    String(String),
     Manifold(GeometryTableIndex),
     CrossSection(GeometryTableIndex),
-    Vector(Vec<Object>),
+
+    /// A vector of objects. If it was written as a literal, the spans of each element are kept too,
+    /// so that errors about one element can point to it.
+    Vector(Vec<Object>, Option<Rc<[InputSourceSpan]>>),
 }
 
 impl Object {
+    /// A vector of objects which didn't come from a literal, so has no element spans.
+    pub fn vector(items: Vec<Object>) -> Self {
+        Object::Vector(items, None)
+    }
+
     pub fn describe_type(&self) -> String {
         match self {
             Object::Null => "null",
             Object::Number(_) => "number",
             Object::Boolean(_) => "boolean",
+            Object::String(_) => "string",
             Object::Manifold(_) => "3D manifold",
             Object::CrossSection(_) => "2D cross-section",
-            Object::Vector(_) => "vector",
+            Object::Vector(..) => "vector",
         }.to_owned()
     }
 
+    /// Get a field of this object, like `size`, or [`None`] if it has no such field.
+    ///
+    /// Returns an error if this is geometry which no longer exists.
     #[allow(clippy::get_first)] // `get(1/2)` mixed with `first()` is confusing
-    pub fn get_field(&self, field: &str, manifold_table: &GeometryTable) -> Option<Object> {
-        match self {
-            Object::Null | Object::Number(_) | Object::Boolean(_) => None,
+    pub fn get_field(&self, field: &str, manifold_table: &GeometryTable, span: InputSourceSpan) -> Result<Option<Object>, RuntimeError> {
+        let value = match self {
+            Object::Null | Object::Number(_) | Object::Boolean(_) | Object::String(_) => None,
 
-            Object::Vector(objects) => {
+            Object::Vector(objects, _) => {
                 match field {
                     "x" => Some(objects.get(0).cloned().unwrap_or(Object::Null)),
                     "y" => Some(objects.get(1).cloned().unwrap_or(Object::Null)),
@@ -40,27 +55,62 @@ impl Object {
             },
 
             Object::Manifold(index) => {
-                let bounding_box = manifold_table.get(index).unwrap_manifold().bounding_box();
+                let manifold = manifold_table.get(index, span)?.unwrap_manifold();
+                let (min_point, max_point) = manifold_bounds(manifold);
 
                 match field {
-                    "origin" | "min_point" => Some(bounding_box.min_point().into()),
-                    "max_point" => Some(bounding_box.max_point().into()),
-                    "size" => Some(bounding_box.size().into()),
+                    "origin" | "min_point" => Some(min_point.into()),
+                    "max_point" => Some(max_point.into()),
+                    "size" => Some((max_point - min_point).into()),
+                    "is_empty" => Some(Object::Boolean(manifold.is_empty())),
+                    "volume" => Some(Object::Number(manifold.volume())),
                     _ => None,
                 }
             },
 
             Object::CrossSection(index) => {
-                let bounding_rect = manifold_table.get(index).unwrap_cross_section().bounding_rectangle();
+                let cross_section = manifold_table.get(index, span)?.unwrap_cross_section();
+                let (min_point, max_point) = cross_section_bounds(cross_section);
 
                 match field {
-                    "origin" | "min_point" => Some(bounding_rect.min_point().into()),
-                    "max_point" => Some(bounding_rect.max_point().into()),
-                    "size" => Some(bounding_rect.size().into()),
+                    "origin" | "min_point" => Some(min_point.into()),
+                    "max_point" => Some(max_point.into()),
+                    "size" => Some((max_point - min_point).into()),
+                    "is_empty" => Some(Object::Boolean(cross_section.is_empty())),
+                    "area" => Some(Object::Number(cross_section.area())),
                     _ => None,
                 }
             },
-        }
+        };
+        Ok(value)
+    }
+
+    /// Copy this object so that it can be kept without the geometry table - see [`ObjectSnapshot`].
+    ///
+    /// Returns an error if this is, or contains, geometry which no longer exists.
+    pub fn snapshot(&self, manifold_table: &GeometryTable, span: InputSourceSpan) -> Result<ObjectSnapshot, RuntimeError> {
+        Ok(match self {
+            Object::Null => ObjectSnapshot::Null,
+            Object::Number(n) => ObjectSnapshot::Number(*n),
+            Object::Boolean(b) => ObjectSnapshot::Boolean(*b),
+            Object::String(s) => ObjectSnapshot::String(s.clone()),
+            Object::Vector(items, _) => ObjectSnapshot::Vector(
+                items.iter()
+                    .map(|item| item.snapshot(manifold_table, span.clone()))
+                    .collect::<Result<_, _>>()?
+            ),
+
+            Object::Manifold(index) => {
+                let manifold = manifold_table.get(index, span)?.unwrap_manifold();
+                let (min_point, max_point) = manifold_bounds(manifold);
+                ObjectSnapshot::Manifold { min_point, max_point, volume: manifold.volume() }
+            },
+            Object::CrossSection(index) => {
+                let cross_section = manifold_table.get(index, span)?.unwrap_cross_section();
+                let (min_point, max_point) = cross_section_bounds(cross_section);
+                ObjectSnapshot::CrossSection { min_point, max_point, area: cross_section.area() }
+            },
+        })
     }
 
     pub fn as_number(&self, span: InputSourceSpan) -> Result<f64, RuntimeError> {
@@ -83,6 +133,16 @@ impl Object {
         }
     }
 
+    pub fn as_string(&self, span: InputSourceSpan) -> Result<&str, RuntimeError> {
+        match self {
+            Object::String(string) => Ok(string),
+            _ => Err(RuntimeError::new(
+                RuntimeErrorKind::IncorrectType { expected: "string".to_owned(), actual: self.describe_type() },
+                span.clone())
+            ),
+        }
+    }
+
     pub fn into_manifold(self, span: InputSourceSpan) -> Result<GeometryTableIndex, RuntimeError> {
         match self {
             Object::Manifold(manifold) => Ok(manifold),
@@ -105,7 +165,7 @@ impl Object {
 
     pub fn into_vector(self, span: InputSourceSpan) -> Result<Vec<Object>, RuntimeError> {
         match self {
-            Object::Vector(v) => Ok(v),
+            Object::Vector(v, _) => Ok(v),
             _ => Err(RuntimeError::new(
                 RuntimeErrorKind::IncorrectType { expected: "vector".to_owned(), actual: self.describe_type() },
                 span.clone())
@@ -115,7 +175,7 @@ impl Object {
 
     pub fn as_vector(&self, span: InputSourceSpan) -> Result<&[Object], RuntimeError> {
         match self {
-            Object::Vector(v) => Ok(v),
+            Object::Vector(v, _) => Ok(v),
             _ => Err(RuntimeError::new(
                 RuntimeErrorKind::IncorrectType { expected: "vector".to_owned(), actual: self.describe_type() },
                 span.clone())
@@ -135,8 +195,8 @@ impl Object {
             ));
         }
 
-        let x = vector[0].as_number(span.clone())?;
-        let y = vector[1].as_number(span.clone())?;
+        let x = self.vector_element_as_number(0, &span)?;
+        let y = self.vector_element_as_number(1, &span)?;
 
         Ok((x, y))
     }
@@ -154,29 +214,91 @@ impl Object {
             ));
         }
 
-        let x = vector[0].as_number(span.clone())?;
-        let y = vector[1].as_number(span.clone())?;
+        let x = self.vector_element_as_number(0, &span)?;
+        let y = self.vector_element_as_number(1, &span)?;
 
         let z =
             if vector.len() == 3 {
-                vector[2].as_number(span.clone())?
+                self.vector_element_as_number(2, &span)?
             } else {
                 0.0
             };
 
         Ok((x, y, z))
     }
+
+    /// Assume that the object is a vector with at least `index + 1` elements, then gets that
+    /// element as a number.
+    /// 
+    /// If the element isn't a number, the error points to the element if this vector was written
+    /// as a literal, or `span` if not.
+    pub(crate) fn vector_element_as_number(&self, index: usize, span: &InputSourceSpan) -> Result<f64, RuntimeError> {
+        let Object::Vector(items, _) = self else { panic!("not a vector") };
+        match &items[index] {
+            Object::Number(num) => Ok(*num),
+            item => Err(RuntimeError::new(
+                RuntimeErrorKind::IncorrectVectorElementType { index, expected: "number".to_owned(), actual: item.describe_type() },
+                self.vector_element_span(index, span),
+            )),
+        }
+    }
+
+    /// Where an element of this vector was written, if it was written as a literal, or `span` if
+    /// not.
+    pub(crate) fn vector_element_span(&self, index: usize, span: &InputSourceSpan) -> InputSourceSpan {
+        let Object::Vector(_, spans) = self else { panic!("not a vector") };
+        spans.as_ref().map(|spans| spans[index].clone()).unwrap_or_else(|| span.clone())
+    }
+}
+
+/// The corners of a manifold's bounding box.
+///
+/// The bounds of empty geometry are infinite, so it's reported as a point at the origin.
+fn manifold_bounds(manifold: &Manifold) -> (Vec3<f64>, Vec3<f64>) {
+    if manifold.is_empty() {
+        return (Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+    }
+
+    let bounding_box = manifold.bounding_box();
+    (bounding_box.min_point(), bounding_box.max_point())
+}
+
+/// Like [`manifold_bounds`], for a cross-section.
+fn cross_section_bounds(cross_section: &CrossSection) -> (Vec2<f64>, Vec2<f64>) {
+    if cross_section.is_empty() {
+        return (Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0));
+    }
+
+    let bounding_rect = cross_section.bounding_rectangle();
+    (bounding_rect.min_point(), bounding_rect.max_point())
+}
+
+/// A copy of an [`Object`] which doesn't refer to the interpreter's geometry table, for keeping
+/// values after the build which produced them, like to show them in an inspector.
+///
+/// Objects only refer to geometry by its index in the table, which stops meaning anything once the
+/// geometry has been used up, or the interpreter has been dropped. A snapshot copies values instead,
+/// and reduces geometry to a summary of it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectSnapshot {
+    Null,
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Manifold { min_point: Vec3<f64>, max_point: Vec3<f64>, volume: f64 },
+    CrossSection { min_point: Vec2<f64>, max_point: Vec2<f64>, area: f64 },
+    Vector(Vec<ObjectSnapshot>),
 }
 
 impl From<Vec3<f64>> for Object {
     fn from(value: Vec3<f64>) -> Self {
-        Self::Vector(vec![Self::Number(value.x), Self::Number(value.y), Self::Number(value.z)])
+        Self::vector(vec![Self::Number(value.x), Self::Number(value.y), Self::Number(value.z)])
     }
 }
 
 impl From<Vec2<f64>> for Object {
     fn from(value: Vec2<f64>) -> Self {
-        Self::Vector(vec![Self::Number(value.x), Self::Number(value.y)])
+        Self::vector(vec![Self::Number(value.x), Self::Number(value.y)])
     }
 }
 
@@ -186,7 +308,9 @@ impl PartialEq for Object {
         match (self, other) {
             (Self::Number(l), Self::Number(r)) => l == r,
             (Self::Boolean(l), Self::Boolean(r)) => l == r,
-            (Self::Vector(l), Self::Vector(r)) => l == r,
+            (Self::String(l), Self::String(r)) => l == r,
+            // Element spans are only for errors, so literals equal vectors built any other way
+            (Self::Vector(l, _), Self::Vector(r, _)) => l == r,
             (Self::Null, Self::Null) => true,
 
             // Because it's a footgun, geometry never compares
@@ -196,7 +320,8 @@ impl PartialEq for Object {
             // Not using `_` so we get exhaustiveness error for new variants
             (Self::Number(_), _)
             | (Self::Boolean(_), _)
-            | (Self::Vector(_), _)
+            | (Self::String(_), _)
+            | (Self::Vector(..), _)
             | (Self::Null, _)
             | (Self::Manifold(_), _)
             | (Self::CrossSection(_), _)
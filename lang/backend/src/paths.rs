@@ -0,0 +1,180 @@
+use std::{fmt::Display, fs, path::{self, Component, Path, PathBuf}};
+
+use sha2::{Digest, Sha256};
+use yascad_frontend::InputSourceSpan;
+
+use crate::{Interpreter, RuntimeError, RuntimeErrorKind, RuntimeWarningKind};
+
+/// Which files builtins like `surface` are allowed to read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FileAccessPolicy {
+    /// Any file can be read.
+    #[default]
+    Allowed,
+
+    /// No files can be read.
+    DenyAll,
+
+    /// Only files within one of these directories can be read.
+    AllowList(Vec<PathBuf>),
+}
+
+impl FileAccessPolicy {
+    /// Whether the file at `path` can be read.
+    /// 
+    /// Paths are compared after resolving `..` components and symbolic links, so neither can be
+    /// used to reach outside of an allowed directory.
+    pub fn permits(&self, path: &Path) -> bool {
+        match self {
+            FileAccessPolicy::Allowed => true,
+            FileAccessPolicy::DenyAll => false,
+            FileAccessPolicy::AllowList(roots) => {
+                let path = normalize(path);
+                roots.iter().any(|root| path.starts_with(normalize(root)))
+            }
+        }
+    }
+}
+
+impl Display for FileAccessPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileAccessPolicy::Allowed => write!(f, "all files are allowed"),
+            FileAccessPolicy::DenyAll => write!(f, "file access is disabled"),
+            FileAccessPolicy::AllowList(roots) if roots.is_empty() => write!(f, "no directories are allowed"),
+            FileAccessPolicy::AllowList(roots) => {
+                let roots = roots.iter()
+                    .map(|root| format!("\"{}\"", root.display()))
+                    .collect::<Vec<_>>();
+                write!(f, "only files within {} are allowed", roots.join(", "))
+            },
+        }
+    }
+}
+
+/// Make `path` absolute, with symbolic links and `.` and `..` components resolved.
+/// 
+/// If the path doesn't exist, it's resolved as far as possible: symbolic links are resolved in
+/// whichever of its parents does exist, and the rest is resolved without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+
+    let mut lexical = PathBuf::new();
+    for component in path::absolute(path).unwrap_or_else(|_| path.to_owned()).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => { lexical.pop(); },
+            component => lexical.push(component),
+        }
+    }
+
+    lexical.ancestors()
+        .find_map(|ancestor| {
+            let canonical = fs::canonicalize(ancestor).ok()?;
+            Some(canonical.join(lexical.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or(lexical)
+}
+
+impl Interpreter {
+    /// Choose which files builtins are allowed to read. Any file can be read by default.
+    pub fn set_file_access_policy(&mut self, policy: FileAccessPolicy) {
+        self.file_access_policy = policy;
+    }
+
+    /// The files which the model read, like for `include` or `surface`, with the SHA-256 hash of
+    /// their contents, in the order they were read.
+    pub fn files_read(&self) -> &[(PathBuf, [u8; 32])] {
+        &self.files_read
+    }
+
+    /// Record that a file was read, so that it's part of what the model was built from.
+    pub(crate) fn record_file_read(&mut self, path: &Path, contents: &[u8]) {
+        self.files_read.push((path.to_owned(), Sha256::digest(contents).into()));
+    }
+
+    /// Resolve a path written in source code into an absolute path.
+    /// 
+    /// Relative paths are resolved against the base directory of the source containing `span`. If
+    /// it doesn't have one, such as an unsaved file in an editor, the current directory is used
+    /// instead, with a warning.
+    pub(crate) fn resolve_path(&mut self, path: &str, span: &InputSourceSpan) -> PathBuf {
+        self.external_paths += 1;
+
+        let path = Path::new(path);
+        let joined = match span.source.base_dir() {
+            Some(base_dir) => base_dir.join(path),
+            None => {
+                if !path.is_absolute() {
+                    self.warn(
+                        RuntimeWarningKind::NoBaseDirectory {
+                            path: path.to_string_lossy().into_owned(),
+                            resolved: path::absolute(path).unwrap_or_else(|_| path.to_owned()),
+                        },
+                        span.clone(),
+                    );
+                }
+                path.to_owned()
+            }
+        };
+
+        // Joining onto an absolute path replaces it, so absolute paths are kept as-is. This only
+        // fails for an empty path, which can't be opened anyway
+        path::absolute(&joined).unwrap_or(joined)
+    }
+
+    /// Resolve a path written in source code with [`Self::resolve_path`], for a file which is about
+    /// to be read. Fails if the file access policy doesn't allow reading it.
+    /// 
+    /// Builtins which read files must get their paths from this.
+    pub(crate) fn resolve_readable_path(&mut self, path: &str, span: &InputSourceSpan) -> Result<PathBuf, RuntimeError> {
+        let resolved = self.resolve_path(path, span);
+        if !self.file_access_policy.permits(&resolved) {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::FileAccessDenied { path: resolved, policy: self.file_access_policy.clone() },
+                span.clone(),
+            ));
+        }
+        Ok(resolved)
+    }
+
+    /// Resolve a path written in source code with [`Self::resolve_readable_path`], and read the
+    /// file.
+    pub(crate) fn read_file_to_string(&mut self, path: &str, span: &InputSourceSpan) -> Result<String, RuntimeError> {
+        let resolved = self.resolve_readable_path(path, span)?;
+        let contents = fs::read_to_string(&resolved)
+            .map_err(|error| RuntimeError::new(
+                RuntimeErrorKind::FileReadError { path: resolved.clone(), reason: error.to_string() },
+                span.clone(),
+            ))?;
+        self.record_file_read(&resolved, contents.as_bytes());
+        Ok(contents)
+    }
+
+    /// Like [`Self::read_file_to_string`], for binary files.
+    pub(crate) fn read_file_to_bytes(&mut self, path: &str, span: &InputSourceSpan) -> Result<Vec<u8>, RuntimeError> {
+        let resolved = self.resolve_readable_path(path, span)?;
+        let contents = fs::read(&resolved)
+            .map_err(|error| RuntimeError::new(
+                RuntimeErrorKind::FileReadError { path: resolved.clone(), reason: error.to_string() },
+                span.clone(),
+            ))?;
+        self.record_file_read(&resolved, &contents);
+        Ok(contents)
+    }
+
+    /// Read a file which the model didn't choose, like the system font which `text` uses by
+    /// default. It's recorded like any other file which is read, but the file access policy doesn't
+    /// apply to it, since the policy is only about paths written in source code.
+    pub(crate) fn read_builtin_file_to_bytes(&mut self, path: &Path, span: &InputSourceSpan) -> Result<Vec<u8>, RuntimeError> {
+        let contents = fs::read(path)
+            .map_err(|error| RuntimeError::new(
+                RuntimeErrorKind::FileReadError { path: path.to_owned(), reason: error.to_string() },
+                span.clone(),
+            ))?;
+        self.record_file_read(path, &contents);
+        Ok(contents)
+    }
+}
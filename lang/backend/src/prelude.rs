@@ -0,0 +1,55 @@
+//! Definitions written in YASCAD itself, which are available to every model without being defined.
+//!
+//! These are loaded into a scope above the model's own top-level scope, so a model can define
+//! something with the same name to replace one.
+
+use std::rc::Rc;
+
+use yascad_frontend::{InputSource, SourceFile};
+
+use crate::{ExecutionContext, Interpreter, RuntimeError};
+
+/// The source of the prelude.
+pub const PRELUDE_SOURCE: &str = include_str!("prelude.yascad");
+
+impl Interpreter {
+    /// Choose whether to load the prelude before interpreting the top level. It's loaded by
+    /// default.
+    pub fn set_load_prelude(&mut self, enabled: bool) {
+        self.load_prelude = enabled;
+    }
+
+    /// Interpret the prelude's definitions into `ctx`, which should be a root context. Afterwards,
+    /// scopes nested within `ctx` can replace its definitions.
+    pub(crate) fn interpret_prelude(&mut self, ctx: &ExecutionContext) -> Result<(), RuntimeError> {
+        let file = SourceFile::new(Rc::new(InputSource::new_prelude(PRELUDE_SOURCE.to_owned())));
+
+        // The prelude never changes, and its tests make sure it's valid
+        assert!(file.tokenize_errors().is_empty(), "prelude has tokenize errors: {:?}", file.tokenize_errors());
+        let mut parser = file.parser();
+        let nodes = parser.parse_statements();
+        assert!(parser.errors.is_empty(), "prelude has parse errors: {:?}", parser.errors);
+
+        for node in &nodes {
+            self.interpret(node, ctx)?;
+        }
+        ctx.make_shadowable();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ExecutionContext, Interpreter, NameDefinition};
+
+    #[test]
+    fn test_prelude_definitions() {
+        let ctx = ExecutionContext::new();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret_prelude(&ctx).unwrap();
+
+        for name in ["ngon", "hexagon", "rounded_rect", "washer"] {
+            assert!(matches!(interpreter.get_name(name, &ctx), Some(NameDefinition::UserDefinedModule(_))), "prelude doesn't define {name}");
+        }
+    }
+}
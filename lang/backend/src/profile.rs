@@ -0,0 +1,132 @@
+use std::{cmp::Reverse, collections::HashMap, fmt::Display, time::{Duration, Instant}};
+
+use yascad_frontend::InputSourceSpan;
+
+use crate::{FrameKind, Interpreter};
+
+/// Timings of each part of a model, collected while interpreting if enabled with
+/// [`Interpreter::set_profiling`].
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    entries: Vec<ProfileEntry>,
+
+    /// Position of each entry in `entries`, so repeated calls from the same place accumulate.
+    positions: HashMap<(ProfileEntryKind, usize, usize), usize>,
+}
+
+/// Everything spent on one top-level statement, or on calls to a user-defined module or operator
+/// from one place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileEntry {
+    pub kind: ProfileEntryKind,
+
+    /// The statement, or the call site.
+    pub span: InputSourceSpan,
+
+    /// How many times this was executed - always 1 for a top-level statement.
+    pub count: usize,
+
+    /// Total wall-clock time spent executing this, including anything it called.
+    pub duration: Duration,
+
+    /// Total number of geometry table operations performed, as a rough measure of how much
+    /// geometry this created and combined.
+    pub geometry_operations: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProfileEntryKind {
+    Statement,
+    Call { kind: FrameKind, name: String },
+}
+
+impl Display for ProfileEntryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileEntryKind::Statement => write!(f, "statement"),
+            ProfileEntryKind::Call { kind, name } => write!(f, "{kind} \"{name}\""),
+        }
+    }
+}
+
+impl Profile {
+    /// All entries, in the order they were first executed.
+    pub fn entries(&self) -> &[ProfileEntry] {
+        &self.entries
+    }
+
+    /// All entries, slowest first.
+    pub fn entries_by_duration(&self) -> Vec<&ProfileEntry> {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| Reverse(entry.duration));
+        entries
+    }
+
+    fn record(&mut self, kind: ProfileEntryKind, span: &InputSourceSpan, duration: Duration, geometry_operations: usize) {
+        let key = (kind, span.start, span.length);
+        if let Some(&position) = self.positions.get(&key) {
+            let entry = &mut self.entries[position];
+            entry.count += 1;
+            entry.duration += duration;
+            entry.geometry_operations += geometry_operations;
+        } else {
+            self.positions.insert(key.clone(), self.entries.len());
+            self.entries.push(ProfileEntry { kind: key.0, span: span.clone(), count: 1, duration, geometry_operations });
+        }
+    }
+}
+
+/// Format a profile as a table, with the slowest entries first.
+pub fn format_profile(profile: &Profile) -> String {
+    let mut lines = vec![format!("{:>10}  {:>8}  {:>6}  {:>6}  {}", "time (ms)", "geometry", "calls", "line", "what")];
+    for entry in profile.entries_by_duration() {
+        lines.push(format!(
+            "{:>10.3}  {:>8}  {:>6}  {:>6}  {}",
+            entry.duration.as_secs_f64() * 1000.0,
+            entry.geometry_operations,
+            entry.count,
+            entry.span.start_line(),
+            entry.kind,
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Measurements taken when something starts executing, to be finished with
+/// [`Interpreter::finish_profiling`].
+pub(crate) struct ProfileStart {
+    instant: Instant,
+    geometry_operations: usize,
+}
+
+impl Interpreter {
+    /// Choose whether to time each top-level statement and each call to a user-defined module or
+    /// operator, retrieved afterwards with [`Interpreter::profile`].
+    ///
+    /// Must be set before interpreting anything to take effect.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(Profile::default);
+    }
+
+    /// The timings collected so far, if profiling is enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Start measuring something, if profiling is enabled.
+    pub(crate) fn start_profiling(&self) -> Option<ProfileStart> {
+        self.profile.as_ref().map(|_| ProfileStart {
+            instant: Instant::now(),
+            geometry_operations: self.manifold_table.operation_count(),
+        })
+    }
+
+    /// Record the measurements of something since [`Interpreter::start_profiling`].
+    pub(crate) fn finish_profiling(&mut self, start: Option<ProfileStart>, kind: ProfileEntryKind, span: &InputSourceSpan) {
+        let (Some(start), Some(profile)) = (start, self.profile.as_mut())
+        else { return };
+
+        let geometry_operations = self.manifold_table.operation_count() - start.geometry_operations;
+        profile.record(kind, span, start.instant.elapsed(), geometry_operations);
+    }
+}
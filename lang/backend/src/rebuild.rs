@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use yascad_frontend::{InputSourceSpan, Node, NodeKind, RebuildPlan};
+
+use crate::{ExecutionContext, Interpreter, NameDefinition, RuntimeWarning, geometry_table::{GeometryTableIndex, GeometryTableItem}, object::Object};
+
+/// What each top-level statement of a model did, recorded if enabled with
+/// [`Interpreter::set_statement_recording`] so that a later build can reuse it with
+/// [`Interpreter::interpret_top_level_reusing`].
+#[derive(Debug, Clone, Default)]
+pub struct StatementResults {
+    statements: Vec<RecordedStatement>,
+}
+
+impl StatementResults {
+    /// The number of statements recorded.
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RecordedStatement {
+    span: InputSourceSpan,
+
+    /// [`None`] if the statement can't be reused, and must always be interpreted.
+    result: Option<StatementResult>,
+}
+
+/// The effects of one statement.
+#[derive(Debug, Clone)]
+struct StatementResult {
+    /// Geometry from earlier statements which this statement consumed or changed.
+    removed: Vec<GeometryTableIndex>,
+
+    /// Geometry left behind by this statement.
+    added: Vec<(GeometryTableIndex, GeometryTableItem)>,
+
+    /// The top-level binding which this statement defined, if any.
+    binding: Option<(String, Object)>,
+
+    warnings: Vec<RuntimeWarning>,
+
+    /// The number of segments which curves were built with.
+    segments: i32,
+
+    /// Primitives created by this statement, if original tracking was enabled.
+    originals: Vec<(u32, InputSourceSpan)>,
+}
+
+/// Measurements taken before a statement executes, to be finished with
+/// [`Interpreter::finish_recording`].
+pub(crate) struct RecordingStart {
+    indices: Vec<GeometryTableIndex>,
+    operation_count: usize,
+    warning_count: usize,
+    external_paths: usize,
+    print_clearance_uses: usize,
+    segments: i32,
+    original_count: usize,
+}
+
+/// State carried between statements while reusing previous results.
+pub(crate) struct Reuse<'a> {
+    plan: &'a RebuildPlan,
+    previous: &'a StatementResults,
+
+    /// Where each reused statement has moved to in the new source, as the old span and the new
+    /// span.
+    moves: Vec<(InputSourceSpan, InputSourceSpan)>,
+
+    /// The new index of each piece of reused geometry, by its index in the previous build.
+    indices: HashMap<GeometryTableIndex, GeometryTableIndex>,
+}
+
+impl<'a> Reuse<'a> {
+    pub(crate) fn new(nodes: &[Node], plan: &'a RebuildPlan, previous: &'a StatementResults) -> Self {
+        let moves = nodes.iter()
+            .enumerate()
+            .filter_map(|(index, node)| {
+                let old = previous.statements.get(plan.reused_statement(index)?)?;
+                Some((old.span.clone(), statement_span(node).clone()))
+            })
+            .collect();
+
+        Self { plan, previous, moves, indices: HashMap::new() }
+    }
+
+    /// Move a span from the previous source to the same place in the new one. Spans outside of any
+    /// reused statement are left alone.
+    fn translate(&self, span: &InputSourceSpan) -> InputSourceSpan {
+        self.moves.iter()
+            .find(|(old, _)| span.start >= old.start && span.start + span.length <= old.start + old.length)
+            .map(|(old, new)| InputSourceSpan::new(new.source.clone(), new.start + (span.start - old.start), span.length))
+            .unwrap_or_else(|| span.clone())
+    }
+
+    /// The new index of some geometry from a previous statement, if it's been reused and is still
+    /// in the table.
+    fn existing_index(&self, interpreter: &Interpreter, index: &GeometryTableIndex) -> Option<GeometryTableIndex> {
+        self.indices.get(index)
+            .filter(|index| interpreter.manifold_table.contains(index))
+            .cloned()
+    }
+}
+
+impl Interpreter {
+    /// Choose whether to record what each top-level statement does, retrieved afterwards with
+    /// [`Interpreter::statement_results`].
+    ///
+    /// Must be set before interpreting anything to take effect.
+    pub fn set_statement_recording(&mut self, enabled: bool) {
+        self.statement_results = enabled.then(StatementResults::default);
+    }
+
+    /// What each top-level statement did, if recording is enabled.
+    pub fn statement_results(&self) -> Option<&StatementResults> {
+        self.statement_results.as_ref()
+    }
+
+    /// Start recording a top-level statement, if recording is enabled.
+    pub(crate) fn start_recording(&self) -> Option<RecordingStart> {
+        self.statement_results.as_ref().map(|_| RecordingStart {
+            indices: self.manifold_table.indices(),
+            operation_count: self.manifold_table.operation_count(),
+            warning_count: self.warnings.len(),
+            external_paths: self.external_paths,
+            print_clearance_uses: self.print_clearance_uses,
+            segments: self.segments(),
+            original_count: self.manifold_table.original_count(),
+        })
+    }
+
+    /// Record what a top-level statement did since [`Interpreter::start_recording`].
+    pub(crate) fn finish_recording(&mut self, start: Option<RecordingStart>, node: &Node, ctx: &ExecutionContext) {
+        let Some(start) = start
+        else { return };
+
+        // Definitions are cheap, and interpreting them again keeps their bodies pointing at the
+        // new source. Anything which used a file or the print clearance might give a different
+        // result next time, and anything which changed the number of segments needs to change it
+        // again
+        let reusable = !matches!(
+            node.kind,
+            NodeKind::ModuleDefinition { .. } | NodeKind::OperatorDefinition { .. } | NodeKind::FunctionDefinition { .. } | NodeKind::UnitsDeclaration { .. }
+        )
+            && self.external_paths == start.external_paths
+            && self.print_clearance_uses == start.print_clearance_uses
+            && self.segments() == start.segments;
+
+        let result = reusable.then(|| {
+            let changes = self.manifold_table.changes_since(&start.indices, start.operation_count);
+            let binding = match &node.kind {
+                NodeKind::Binding { name, .. } => match self.get_name(name, ctx) {
+                    Some(NameDefinition::Binding(object)) => Some((name.clone(), object)),
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            StatementResult {
+                removed: changes.removed,
+                added: changes.added,
+                binding,
+                warnings: self.warnings[start.warning_count..].to_vec(),
+                segments: start.segments,
+                originals: self.manifold_table.originals_since(start.original_count),
+            }
+        });
+
+        let statement = RecordedStatement { span: statement_span(node).clone(), result };
+        if let Some(results) = self.statement_results.as_mut() {
+            results.statements.push(statement);
+        }
+    }
+
+    /// Apply the previous results of the statement at `index`, if the plan allows it.
+    ///
+    /// Returns false, having changed nothing, if the statement must be interpreted instead.
+    pub(crate) fn restore_statement(&mut self, reuse: &mut Reuse, index: usize, ctx: &ExecutionContext) -> bool {
+        let Some(result) = reuse.plan.reused_statement(index)
+            .and_then(|previous_index| reuse.previous.statements.get(previous_index))
+            .and_then(|statement| statement.result.as_ref())
+        else { return false };
+
+        // Curves built with a different number of segments would look different now
+        if result.segments != self.segments() {
+            return false;
+        }
+
+        // Check everything before changing anything, so that we can still back out
+        let Some(removed) = result.removed.iter()
+            .map(|index| reuse.existing_index(self, index))
+            .collect::<Option<Vec<_>>>()
+        else { return false };
+
+        if let Some((name, object)) = &result.binding {
+            if self.get_name(name, ctx).is_some() {
+                return false;
+            }
+
+            let mut object_indices = vec![];
+            collect_indices(object, &mut object_indices);
+            let all_available = object_indices.iter()
+                .all(|index| result.added.iter().any(|(added, _)| added == index) || reuse.existing_index(self, index).is_some());
+            if !all_available {
+                return false;
+            }
+        }
+
+        for index in removed {
+            self.manifold_table.remove(index);
+        }
+        for (old_index, item) in &result.added {
+            let mut item = item.clone();
+            item.provenance = reuse.translate(&item.provenance);
+            let new_index = self.manifold_table.insert_item(item);
+            reuse.indices.insert(old_index.clone(), new_index);
+        }
+
+        for (id, provenance) in &result.originals {
+            self.manifold_table.record_original(*id, reuse.translate(provenance));
+        }
+
+        if let Some((name, object)) = &result.binding {
+            let object = remap_object(object, reuse);
+            let span = reuse.translate(&reuse.previous.statements[reuse.plan.reused_statement(index).unwrap()].span);
+            self.add_name(name, NameDefinition::Binding(object), ctx, span)
+                .expect("name was checked to be free");
+        }
+
+        for warning in &result.warnings {
+            let span = reuse.translate(&warning.span);
+            self.warnings.push(RuntimeWarning::new(warning.kind.clone(), span));
+        }
+
+        true
+    }
+}
+
+fn statement_span(node: &Node) -> &InputSourceSpan {
+    node.statement_span.as_ref().unwrap_or(&node.span)
+}
+
+/// Find all geometry which an object refers to.
+fn collect_indices(object: &Object, indices: &mut Vec<GeometryTableIndex>) {
+    match object {
+        Object::Manifold(index) | Object::CrossSection(index) => indices.push(index.clone()),
+        Object::Vector(items, _) => {
+            for item in items {
+                collect_indices(item, indices);
+            }
+        }
+        Object::Null | Object::Number(_) | Object::Boolean(_) | Object::String(_) => {}
+    }
+}
+
+/// Update the geometry which an object refers to, to the indices it has been reused at, and move
+/// the spans of any vector elements to where they are in the new source.
+fn remap_object(object: &Object, reuse: &Reuse) -> Object {
+    match object {
+        Object::Manifold(index) => Object::Manifold(reuse.indices.get(index).unwrap_or(index).clone()),
+        Object::CrossSection(index) => Object::CrossSection(reuse.indices.get(index).unwrap_or(index).clone()),
+        Object::Vector(items, spans) => Object::Vector(
+            items.iter().map(|item| remap_object(item, reuse)).collect(),
+            spans.as_ref().map(|spans| spans.iter().map(|span| reuse.translate(span)).collect()),
+        ),
+        Object::Null | Object::Number(_) | Object::Boolean(_) | Object::String(_) => object.clone(),
+    }
+}
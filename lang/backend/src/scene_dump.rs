@@ -0,0 +1,113 @@
+use std::fmt::Display;
+
+use manifold_rs::{Vec2, Vec3};
+use yascad_frontend::InputSourceSpan;
+
+use crate::{Interpreter, geometry_table::{GeometryDisposition, GeometryTableEntry}};
+
+/// A description of one entry in the geometry table, for debugging.
+#[derive(Debug, Clone)]
+pub struct SceneEntry {
+    pub geometry: SceneEntryGeometry,
+    pub disposition: GeometryDisposition,
+    pub provenance: InputSourceSpan,
+
+    /// The name given to the geometry by `part`, if any.
+    pub name: Option<String>,
+}
+
+/// Summary statistics about the geometry of a [`SceneEntry`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneEntryGeometry {
+    Manifold {
+        triangles: usize,
+        min: Vec3<f64>,
+        max: Vec3<f64>,
+    },
+    CrossSection {
+        points: usize,
+        min: Vec2<f64>,
+        max: Vec2<f64>,
+    },
+}
+
+impl Interpreter {
+    /// Describe every entry in the geometry table, in the order it was added.
+    pub fn dump_scene(&self) -> Vec<SceneEntry> {
+        self.manifold_table.iter_geometry()
+            .map(|(entry, disposition, provenance, name)| SceneEntry {
+                geometry: SceneEntryGeometry::of(entry),
+                disposition,
+                provenance: provenance.clone(),
+                name: name.map(str::to_owned),
+            })
+            .collect()
+    }
+}
+
+impl SceneEntryGeometry {
+    pub(crate) fn of(entry: &GeometryTableEntry) -> Self {
+        match entry {
+            GeometryTableEntry::Manifold(_) | GeometryTableEntry::Instanced(_) => {
+                let manifold = entry.unwrap_manifold();
+                let bounding_box = manifold.bounding_box();
+                SceneEntryGeometry::Manifold {
+                    triangles: manifold.count_triangles(),
+                    min: bounding_box.min_point(),
+                    max: bounding_box.max_point(),
+                }
+            },
+            GeometryTableEntry::CrossSection(cross_section) => {
+                let rectangle = cross_section.bounding_rectangle();
+                SceneEntryGeometry::CrossSection {
+                    points: cross_section.count_verts(),
+                    min: rectangle.min_point(),
+                    max: rectangle.max_point(),
+                }
+            },
+        }
+    }
+}
+
+/// Formats entries in a stable text format - one line for each entry's type and origin, then
+/// indented lines for its statistics.
+impl Display for SceneEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.geometry {
+            SceneEntryGeometry::Manifold { .. } => "manifold",
+            SceneEntryGeometry::CrossSection { .. } => "cross-section",
+        };
+        let disposition = match self.disposition {
+            GeometryDisposition::Physical => "physical",
+            GeometryDisposition::Virtual => "virtual",
+        };
+        write!(f, "{kind} ({disposition}) at {}:{}:{}",
+            self.provenance.source.origin().name(),
+            self.provenance.start_line(),
+            self.provenance.start_column(),
+        )?;
+        match &self.name {
+            Some(name) => writeln!(f, ", named {name:?}")?,
+            None => writeln!(f)?,
+        }
+
+        match &self.geometry {
+            SceneEntryGeometry::Manifold { triangles, min, max } => {
+                writeln!(f, "  triangles: {triangles}")?;
+                write!(f, "  bounds: [{}, {}, {}] to [{}, {}, {}]", min.x, min.y, min.z, max.x, max.y, max.z)
+            },
+            SceneEntryGeometry::CrossSection { points, min, max } => {
+                writeln!(f, "  points: {points}")?;
+                write!(f, "  bounds: [{}, {}] to [{}, {}]", min.x, min.y, max.x, max.y)
+            },
+        }
+    }
+}
+
+/// Format a whole scene dump, with a blank line between each entry.
+pub fn format_scene_dump(entries: &[SceneEntry]) -> String {
+    entries.iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
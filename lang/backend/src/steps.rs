@@ -0,0 +1,65 @@
+use manifold_rs::MeshGL;
+use yascad_frontend::InputSourceSpan;
+
+use crate::{Interpreter, SceneEntryGeometry, geometry_table::GeometryTableEntry};
+
+/// How much the interpreter records about the result of each operator, for step-through debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StepCapture {
+    /// Don't record anything.
+    #[default]
+    Off,
+
+    /// Record summary statistics for each step, which are cheap to keep.
+    Summary,
+
+    /// Record summary statistics and a mesh for each step. Only the first `max_meshes` steps get a
+    /// mesh, to limit memory usage - later steps only have statistics.
+    Mesh { max_meshes: usize },
+}
+
+/// The result of applying one operator, recorded when [`StepCapture`] is enabled.
+pub struct Step {
+    /// The name of the operator which was applied.
+    pub operator: String,
+
+    /// The operator application which produced this step.
+    pub span: InputSourceSpan,
+
+    pub geometry: SceneEntryGeometry,
+
+    /// The geometry at this step, if it was captured. 2D geometry is extruded by a small height.
+    pub mesh: Option<MeshGL>,
+}
+
+impl Interpreter {
+    /// Choose how much to record about each operator which is applied, retrieved afterwards with
+    /// [`Interpreter::steps`].
+    ///
+    /// Must be set before interpreting anything to take effect.
+    pub fn set_step_capture(&mut self, step_capture: StepCapture) {
+        self.step_capture = step_capture;
+    }
+
+    /// The result of every operator applied so far, in the order they finished - so an operator
+    /// comes after the operators applied to its children.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub(crate) fn record_step(&mut self, operator: &str, geometry: &GeometryTableEntry, span: &InputSourceSpan) {
+        let capture_mesh = match self.step_capture {
+            StepCapture::Off => return,
+            StepCapture::Summary => false,
+            // Meshes are only captured for the earliest steps
+            StepCapture::Mesh { max_meshes } => self.steps.len() < max_meshes,
+        };
+
+        self.steps.push(Step {
+            operator: operator.to_owned(),
+            span: span.clone(),
+            geometry: SceneEntryGeometry::of(geometry),
+            mesh: capture_mesh.then(|| geometry.to_display_manifold().meshgl()),
+        });
+    }
+}
@@ -0,0 +1,61 @@
+/// Names further than this many single-character edits away are too different to be suggested.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Find the candidate most similar to a name which couldn't be found, to suggest that it was meant
+/// instead.
+///
+/// Returns [`None`] if nothing is close enough to be a likely typo. Ties are broken alphabetically,
+/// so that the suggestion doesn't depend on the order of the candidates.
+pub(crate) fn closest_name<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    candidates.iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate.as_str()))
+        // Very short names are within a couple of edits of almost anything
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE && *distance < name.chars().count())
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The number of single-character insertions, deletions and substitutions needed to turn `a` into
+/// `b` (the Levenshtein distance).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+
+    // Distances from the part of `a` processed so far to each prefix of `b`
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::suggestions::{closest_name, edit_distance};
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("thickness", "thickness"), 0);
+        assert_eq!(edit_distance("thikness", "thickness"), 1);
+        assert_eq!(edit_distance("cbue", "cube"), 2);
+        assert_eq!(edit_distance("", "cube"), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_closest_name() {
+        let candidates = ["thickness", "width", "translate", "cube"].map(str::to_owned);
+
+        assert_eq!(closest_name("thikness", &candidates), Some("thickness"));
+        assert_eq!(closest_name("cub", &candidates), Some("cube"));
+
+        // Nothing within two edits
+        assert_eq!(closest_name("height", &candidates), None);
+        assert_eq!(closest_name("x", &["y".to_owned()]), None);
+    }
+}
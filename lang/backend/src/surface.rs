@@ -0,0 +1,169 @@
+use manifold_rs::Vec3;
+
+/// A grid of heights, read from a CSV file for the `surface` module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    /// The heights, row by row. All rows have the same length.
+    rows: Vec<Vec<f64>>,
+}
+
+/// Why some heightmap data couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeightmapError {
+    /// The line of the data which was invalid, starting from 1 - or [`None`] if the problem isn't
+    /// with one specific line.
+    pub row: Option<usize>,
+    pub reason: String,
+}
+
+impl Heightmap {
+    /// Read a heightmap from CSV data, with a row of comma-separated heights on each line. Blank
+    /// lines are ignored.
+    /// 
+    /// There must be at least two rows and two columns, so that the heightmap covers some area.
+    pub fn parse_csv(data: &str) -> Result<Self, HeightmapError> {
+        let mut rows: Vec<Vec<f64>> = vec![];
+        for (i, line) in data.lines().enumerate() {
+            let row_error = |reason: String| HeightmapError { row: Some(i + 1), reason };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let row = line.split(',')
+                .map(|cell| {
+                    let cell = cell.trim();
+                    cell.parse::<f64>().ok()
+                        .filter(|height| height.is_finite())
+                        .ok_or_else(|| row_error(format!("\"{cell}\" is not a number")))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if let Some(first) = rows.first() && first.len() != row.len() {
+                return Err(row_error(format!("expected {} heights like the first row, got {}", first.len(), row.len())));
+            }
+            rows.push(row);
+        }
+
+        if rows.len() < 2 || rows[0].len() < 2 {
+            return Err(HeightmapError { row: None, reason: "at least two rows and two columns of heights are needed".to_owned() });
+        }
+        Ok(Self { rows })
+    }
+
+    /// Build a closed mesh of the heightmap, as vertices and counter-clockwise triangles.
+    /// 
+    /// Each height is one unit apart in X and Y. The first row is at the back (highest Y), so the
+    /// model looks like the file when viewed from above. The heights are multiplied by `scale_z`,
+    /// and the mesh has a flat base one unit below the lowest point, like OpenSCAD.
+    pub fn mesh(&self, scale_z: f64) -> (Vec<Vec3<f32>>, Vec<[u32; 3]>) {
+        let row_count = self.rows.len();
+        let column_count = self.rows[0].len();
+
+        let heights = self.rows.iter()
+            .flatten()
+            .map(|height| height * scale_z)
+            .collect::<Vec<_>>();
+        let base = heights.iter().copied().fold(f64::INFINITY, f64::min) - 1.0;
+
+        // The top surface follows the heights, and the base is another grid of the same size below
+        // it, so that the sides can join them
+        let position = |row: usize, column: usize| (column as f32, (row_count - 1 - row) as f32);
+        let mut vertices = vec![];
+        for z in [None, Some(base)] {
+            for row in 0..row_count {
+                for column in 0..column_count {
+                    let (x, y) = position(row, column);
+                    let z = z.unwrap_or(heights[row * column_count + column]);
+                    vertices.push(Vec3::new(x, y, z as f32));
+                }
+            }
+        }
+
+        let top = |row: usize, column: usize| (row * column_count + column) as u32;
+        let bottom = |row: usize, column: usize| top(row, column) + (row_count * column_count) as u32;
+
+        let mut triangles = vec![];
+        for row in 0..(row_count - 1) {
+            for column in 0..(column_count - 1) {
+                // Corners of this cell, counter-clockwise from the front left when viewed from above
+                let corners = [(row + 1, column), (row + 1, column + 1), (row, column + 1), (row, column)];
+
+                let [a, b, c, d] = corners.map(|(r, c)| top(r, c));
+                triangles.push([a, b, c]);
+                triangles.push([a, c, d]);
+
+                // The base faces down, so is wound the other way
+                let [a, b, c, d] = corners.map(|(r, c)| bottom(r, c));
+                triangles.push([a, c, b]);
+                triangles.push([a, d, c]);
+            }
+        }
+
+        // Walk around the edge counter-clockwise, joining the top to the base
+        let perimeter = (0..column_count).map(|column| (row_count - 1, column))
+            .chain((0..row_count).rev().map(|row| (row, column_count - 1)).skip(1))
+            .chain((0..column_count).rev().map(|column| (0, column)).skip(1))
+            .chain((0..row_count).map(|row| (row, 0)).skip(1))
+            .collect::<Vec<_>>();
+        for pair in perimeter.windows(2) {
+            let [(r1, c1), (r2, c2)] = [pair[0], pair[1]];
+            triangles.push([bottom(r1, c1), bottom(r2, c2), top(r2, c2)]);
+            triangles.push([bottom(r1, c1), top(r2, c2), top(r1, c1)]);
+        }
+
+        (vertices, triangles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surface::{Heightmap, HeightmapError};
+
+    #[test]
+    fn test_parse_csv() {
+        let heightmap = Heightmap::parse_csv("1, 2, 3\n\n4,5,6.5\n").unwrap();
+        assert_eq!(heightmap.rows, vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.5]]);
+
+        assert_eq!(
+            Heightmap::parse_csv("1,2\n3,x\n"),
+            Err(HeightmapError { row: Some(2), reason: "\"x\" is not a number".to_owned() }),
+        );
+        assert_eq!(
+            Heightmap::parse_csv("1,2\n\n3,4,5\n"),
+            Err(HeightmapError { row: Some(3), reason: "expected 2 heights like the first row, got 3".to_owned() }),
+        );
+        assert_eq!(
+            Heightmap::parse_csv("1,2,3\n"),
+            Err(HeightmapError { row: None, reason: "at least two rows and two columns of heights are needed".to_owned() }),
+        );
+    }
+
+    #[test]
+    fn test_mesh_is_closed() {
+        let heightmap = Heightmap::parse_csv("1,2,3\n4,5,6\n7,8,9\n").unwrap();
+        let (vertices, triangles) = heightmap.mesh(2.0);
+        assert_eq!(vertices.len(), 9 * 2);
+
+        // Top and base have 2 triangles per cell each, and each edge of the perimeter has 2
+        assert_eq!(triangles.len(), 4 * 2 * 2 + 8 * 2);
+
+        // In a closed mesh with consistent winding, every edge is used once in each direction
+        let mut edges = triangles.iter()
+            .flat_map(|[a, b, c]| [(*a, *b), (*b, *c), (*c, *a)])
+            .collect::<Vec<_>>();
+        edges.sort();
+        for (a, b) in &edges {
+            assert!(edges.binary_search(&(*b, *a)).is_ok(), "edge {a}-{b} has no opposite");
+        }
+
+        // The base is one unit below the lowest height, after scaling
+        assert_eq!(vertices[0].z, 2.0);
+        assert!(vertices[9..].iter().all(|vertex| vertex.z == 1.0));
+
+        // The first row is at the back
+        assert_eq!((vertices[0].x, vertices[0].y), (0.0, 2.0));
+        assert_eq!((vertices[8].x, vertices[8].y), (2.0, 0.0));
+    }
+}
+
@@ -0,0 +1,1069 @@
+//! Tests which evaluate source directly with the interpreter, without building any output.
+//!
+//! Geometry builtins still run, but these are mostly useful for testing the object model - which
+//! is otherwise only tested end-to-end through the integration tests.
+
+use std::rc::Rc;
+
+use miette::Diagnostic;
+
+use yascad_frontend::{InputSource, InputSourceSpan, Parser, tokenize};
+
+use crate::{EvaluatedArguments, EvaluatedParameters, ExecutionContext, FileAccessPolicy, builtin, Interpreter, NameDefinition, RuntimeError, RuntimeErrorKind, RuntimeWarningKind, geometry_table::GeometryDisposition, object::Object};
+
+/// Evaluate every statement in `source`, and return the object produced by the last one.
+///
+/// Panics if the source doesn't parse.
+fn evaluate(source: &str) -> Result<Object, RuntimeError> {
+    evaluate_with(source, ExecutionContext::new())
+}
+
+/// Like [`evaluate`], but within the given context.
+fn evaluate_with(source: &str, ctx: ExecutionContext) -> Result<Object, RuntimeError> {
+    evaluate_in(&mut Interpreter::new(), source, ctx)
+}
+
+/// Like [`evaluate_with`], but using an existing interpreter, so that it can be configured first
+/// and inspected afterwards.
+fn evaluate_in(interpreter: &mut Interpreter, source: &str, ctx: ExecutionContext) -> Result<Object, RuntimeError> {
+    let source = Rc::new(InputSource::new_string(source.to_owned()));
+
+    let (tokens, errors) = tokenize(source.clone());
+    assert!(errors.is_empty(), "tokenize errors: {errors:?}");
+
+    let mut parser = Parser::new(source.clone(), tokens);
+    let nodes = parser.parse_statements();
+    assert!(parser.errors.is_empty(), "parse errors: {:?}", parser.errors);
+
+    let mut result = Object::Null;
+    for node in &nodes {
+        result = interpreter.interpret(node, &ctx)?;
+    }
+    Ok(result)
+}
+
+/// Like [`evaluate`], but expects an error and returns its message.
+fn evaluate_error(source: &str) -> String {
+    match evaluate(source) {
+        Ok(object) => panic!("expected an error, but got {object:?}"),
+        Err(error) => error.to_string(),
+    }
+}
+
+/// A span for errors raised directly by tests, rather than by evaluating source.
+fn test_span() -> InputSourceSpan {
+    Rc::new(InputSource::new_string("test".to_owned())).span(0, 4)
+}
+
+fn numbers(numbers: &[f64]) -> Object {
+    Object::vector(numbers.iter().map(|n| Object::Number(*n)).collect())
+}
+
+#[test]
+fn test_literals() {
+    assert_eq!(evaluate("1;").unwrap(), Object::Number(1.0));
+    assert_eq!(evaluate("\"hello\";").unwrap(), Object::String("hello".to_owned()));
+    assert_eq!(evaluate("[1, [true, null]];").unwrap(), Object::vector(vec![
+        Object::Number(1.0),
+        Object::vector(vec![Object::Boolean(true), Object::Null]),
+    ]));
+
+    // Bindings are visible to later statements
+    assert_eq!(evaluate("x = 2; y = x * 3; y - 1;").unwrap(), Object::Number(5.0));
+}
+
+#[test]
+fn test_vector_conversions() {
+    let span = test_span();
+    let error = |object: Object, three: bool| {
+        let result = if three {
+            object.as_3d_vector(span.clone()).map(|_| ())
+        } else {
+            object.as_2d_vector(span.clone()).map(|_| ())
+        };
+        result.unwrap_err().to_string()
+    };
+
+    assert_eq!(numbers(&[1.0, 2.0]).as_2d_vector(span.clone()).unwrap(), (1.0, 2.0));
+    assert_eq!(numbers(&[1.0, 2.0]).as_3d_vector(span.clone()).unwrap(), (1.0, 2.0, 0.0));
+    assert_eq!(numbers(&[1.0, 2.0, 3.0]).as_3d_vector(span.clone()).unwrap(), (1.0, 2.0, 3.0));
+
+    assert_eq!(error(numbers(&[1.0]), false), "incorrect vector length - expected 2, got 1");
+    assert_eq!(error(numbers(&[1.0, 2.0, 3.0]), false), "incorrect vector length - expected 2, got 3");
+    assert_eq!(error(numbers(&[1.0]), true), "incorrect vector length - expected 2-3, got 1");
+    assert_eq!(error(numbers(&[1.0, 2.0, 3.0, 4.0]), true), "incorrect vector length - expected 2-3, got 4");
+
+    assert_eq!(error(Object::Number(1.0), false), "type error - expected vector, got number");
+    assert_eq!(error(Object::Null, true), "type error - expected vector, got null");
+    assert_eq!(
+        error(Object::vector(vec![Object::Number(1.0), Object::String("2".to_owned())]), false),
+        "type error - expected element 1 of vector to be number, got string",
+    );
+    assert_eq!(
+        error(Object::vector(vec![Object::Number(1.0), Object::Number(2.0), Object::Boolean(true)]), true),
+        "type error - expected element 2 of vector to be number, got boolean",
+    );
+}
+
+#[test]
+fn test_vector_element_errors() {
+    let error_at = |source: &str| {
+        let error = evaluate(source).unwrap_err();
+        (error.to_string(), source[error.span.start..error.span.start + error.span.length].to_owned())
+    };
+
+    // Literals point to the element which is wrong
+    assert_eq!(
+        error_at("square([1, true]);"),
+        ("type error - expected element 1 of vector to be number, got boolean".to_owned(), "true".to_owned()),
+    );
+    assert_eq!(
+        error_at("cube([1, 2, \"three\"]);"),
+        ("type error - expected element 2 of vector to be number, got string".to_owned(), "\"three\"".to_owned()),
+    );
+
+    // Vectors keep the spans of their literal when they're passed around
+    assert_eq!(
+        error_at("size = [null, 2]; square(size);"),
+        ("type error - expected element 0 of vector to be number, got null".to_owned(), "null".to_owned()),
+    );
+}
+
+#[test]
+fn test_vector_arguments_leave_no_geometry() {
+    let source = Rc::new(InputSource::new_string("square([cube(1), 2]);".to_owned()));
+    let (tokens, _) = tokenize(source.clone());
+    let nodes = Parser::new(source.clone(), tokens).parse_statements();
+
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.interpret(&nodes[0], &ExecutionContext::new()).unwrap_err();
+    assert_eq!(error.to_string(), "type error - expected element 0 of vector to be number, got 3D manifold");
+    assert_eq!(interpreter.manifold_table.iter_geometry().count(), 0);
+}
+
+#[test]
+fn test_empty_vectors() {
+    assert_eq!(evaluate("[];").unwrap(), Object::vector(vec![]));
+    assert_eq!(evaluate("[[], [1]];").unwrap(), Object::vector(vec![Object::vector(vec![]), numbers(&[1.0])]));
+    assert_eq!(evaluate("v = []; v.x;").unwrap(), Object::Null);
+    assert_eq!(evaluate("__debug([]);").unwrap(), Object::Null);
+
+    assert_eq!(evaluate("len([]);").unwrap(), Object::Number(0.0));
+    assert_eq!(evaluate("len([1, [2, 3]]);").unwrap(), Object::Number(2.0));
+    assert_eq!(evaluate("len(\"héllo\");").unwrap(), Object::Number(5.0));
+    assert_eq!(evaluate_error("len(1);"), "type error - expected vector or string, got number");
+
+    assert_eq!(evaluate_error("square([]);"), "incorrect vector length - expected 2, got 0");
+}
+
+#[test]
+fn test_range_literals() {
+    assert_eq!(evaluate("[1:4];").unwrap(), numbers(&[1.0, 2.0, 3.0, 4.0]));
+    assert_eq!(evaluate("[-1:1];").unwrap(), numbers(&[-1.0, 0.0, 1.0]));
+
+    // A range with equal bounds has one item, not zero
+    assert_eq!(evaluate("[2:2];").unwrap(), numbers(&[2.0]));
+
+    // Bounds can be any expression
+    let ctx = ExecutionContext::with_bindings(vec![("n", Object::Number(3.0))]);
+    assert_eq!(evaluate_with("[n - 1:n];", ctx).unwrap(), numbers(&[2.0, 3.0]));
+
+    assert_eq!(evaluate_error("[3:1];"), "end of range is lower than start");
+    assert_eq!(evaluate_error("[\"a\":2];"), "type error - expected number, got string");
+    assert_eq!(evaluate_error("[1:null];"), "type error - expected number, got null");
+
+    // Items never go past the end
+    assert_eq!(evaluate("[1:3.5];").unwrap(), numbers(&[1.0, 2.0, 3.0]));
+}
+
+#[test]
+fn test_range_steps() {
+    assert_eq!(evaluate("[0:12.5:50];").unwrap(), numbers(&[0.0, 12.5, 25.0, 37.5, 50.0]));
+    assert_eq!(evaluate("[0:2:5];").unwrap(), numbers(&[0.0, 2.0, 4.0]));
+    assert_eq!(evaluate("[3:-1:1];").unwrap(), numbers(&[3.0, 2.0, 1.0]));
+    assert_eq!(evaluate("[2:0.5:2];").unwrap(), numbers(&[2.0]));
+
+    // Steps which can't be represented exactly still reach the end
+    let Object::Vector(items, _) = evaluate("[0:0.1:1];").unwrap() else { panic!("expected a vector") };
+    assert_eq!(items.len(), 11);
+    let Object::Number(last) = items[10] else { panic!("expected a number") };
+    assert!((last - 1.0).abs() < 1e-9);
+
+    assert_eq!(evaluate_error("[0:0:1];"), "step of range cannot be zero");
+    assert_eq!(evaluate_error("[0:-1:1];"), "step of range is negative, so it would never reach the end - count down from a higher start instead");
+    assert_eq!(evaluate_error("[3:1:1];"), "end of range is lower than start");
+    assert_eq!(evaluate_error("[0:\"a\":1];"), "type error - expected number, got string");
+
+    // Ranges which would never finish, or take up too much memory, are caught before building them
+    assert_eq!(evaluate_error("[0:1/0];"), "start, end and step of range must be finite numbers");
+    assert_eq!(evaluate_error("[0:0/0:1];"), "start, end and step of range must be finite numbers");
+    assert_eq!(evaluate_error("[0:1000000000000];"), "range would have more than 10000000 items");
+    assert_eq!(evaluate_error("[0:0.0000001:2];"), "range would have more than 10000000 items");
+}
+
+#[test]
+fn test_binary_operator_types() {
+    assert_eq!(evaluate("1 + 2 * 3;").unwrap(), Object::Number(7.0));
+    assert_eq!(evaluate("2 <= 2;").unwrap(), Object::Boolean(true));
+
+    // Modulo is Euclidean, so the result is never negative
+    assert_eq!(evaluate("1 + 5 % 3 * 2;").unwrap(), Object::Number(5.0));
+    assert_eq!(evaluate("7 % 3;").unwrap(), Object::Number(1.0));
+    assert_eq!(evaluate("-7 % 3;").unwrap(), Object::Number(2.0));
+    assert_eq!(evaluate("7 % -3;").unwrap(), Object::Number(1.0));
+    assert_eq!(evaluate("-7 % -3;").unwrap(), Object::Number(2.0));
+    assert_eq!(evaluate("5.5 % 2;").unwrap(), Object::Number(1.5));
+    assert_eq!(evaluate_error("5 % \"a\";"), "type error - expected number, got string");
+}
+
+#[test]
+fn test_logical_operators() {
+    assert_eq!(evaluate("true && false;").unwrap(), Object::Boolean(false));
+    assert_eq!(evaluate("false || true;").unwrap(), Object::Boolean(true));
+    assert_eq!(evaluate("!true;").unwrap(), Object::Boolean(false));
+    assert_eq!(evaluate("!!true;").unwrap(), Object::Boolean(true));
+
+    // Comparisons bind more tightly, and `&&` more tightly than `||`
+    assert_eq!(evaluate("x = 5; x > 1 && x < 10;").unwrap(), Object::Boolean(true));
+    assert_eq!(evaluate("x = 5; x < 1 || x == 5 && x < 10;").unwrap(), Object::Boolean(true));
+    assert_eq!(evaluate("true || false && false;").unwrap(), Object::Boolean(true));
+    assert_eq!(evaluate("(true || false) && false;").unwrap(), Object::Boolean(false));
+    assert_eq!(evaluate("!false == true;").unwrap(), Object::Boolean(true));
+
+    // The right side is only evaluated when it could change the result
+    assert_eq!(evaluate("false && missing;").unwrap(), Object::Boolean(false));
+    assert_eq!(evaluate("true || missing;").unwrap(), Object::Boolean(true));
+    assert_eq!(evaluate_error("true && missing;"), "undefined identifier \"missing\"");
+    assert_eq!(evaluate_error("false || missing;"), "undefined identifier \"missing\"");
+
+    // Operands must be booleans, rather than being truthy or falsy
+    assert_eq!(evaluate_error("1 && true;"), "type error - expected boolean, got number");
+    assert_eq!(evaluate_error("false || 1;"), "type error - expected boolean, got number");
+    assert_eq!(evaluate_error("true && null;"), "type error - expected boolean, got null");
+    assert_eq!(evaluate_error("!1;"), "type error - expected boolean, got number");
+
+    // Ternaries only evaluate the value they pick
+    assert_eq!(evaluate("x = 5; x > 1 ? x : 1;").unwrap(), Object::Number(5.0));
+    assert_eq!(evaluate("[true ? 1 : missing, false ? missing : 2];").unwrap(), numbers(&[1.0, 2.0]));
+    assert_eq!(evaluate("x = 0; x < 0 ? -1 : x == 0 ? 0 : 1;").unwrap(), Object::Number(0.0));
+    assert_eq!(evaluate("1 + (true ? 2 : 3);").unwrap(), Object::Number(3.0));
+    assert_eq!(evaluate_error("1 ? 2 : 3;"), "type error - expected boolean, got number");
+
+    assert_eq!(evaluate_error("1 + \"a\";"), "type error - expected number, got string");
+    assert_eq!(evaluate_error("true - 1;"), "type error - expected number, got boolean");
+    assert_eq!(evaluate_error("[1, 2] * 2;"), "type error - expected number, got vector");
+    assert_eq!(evaluate_error("null < 1;"), "type error - expected number, got null");
+    assert_eq!(evaluate_error("-true;"), "type error - expected number, got boolean");
+
+    // Equality works on anything, and different types are never equal
+    assert_eq!(evaluate("1 == \"1\";").unwrap(), Object::Boolean(false));
+    assert_eq!(evaluate("[1, \"a\"] == [1, \"a\"];").unwrap(), Object::Boolean(true));
+    assert_eq!(evaluate("null == null;").unwrap(), Object::Boolean(true));
+}
+
+#[test]
+fn test_duplicate_names() {
+    assert_eq!(evaluate_error("x = 1; x = 2;"), "name \"x\" is already defined");
+    assert_eq!(evaluate_error("module m() {} m = 1;"), "name \"m\" is already defined");
+    assert_eq!(evaluate_error("x = 1; operator x() {}"), "name \"x\" is already defined");
+
+    // Builtins can't be redefined either
+    assert_eq!(evaluate_error("module cube() {}"), "name \"cube\" is already defined");
+    assert_eq!(evaluate_error("operator offset() {}"), "name \"offset\" is already defined");
+
+    // But bindings can share their names, since they're never called
+    assert_eq!(evaluate("offset = 2; len = len([1, 2, 3]); [offset, len];").unwrap(), numbers(&[2.0, 3.0]));
+    assert_eq!(evaluate("function f(min) = max(min, 4); f(3);").unwrap(), Object::Number(4.0));
+
+    let ctx = ExecutionContext::with_bindings(vec![("y", Object::Null)]);
+    assert_eq!(evaluate_with("y = 1;", ctx).unwrap_err().to_string(), "name \"y\" is already defined");
+}
+
+#[test]
+fn test_error_labels() {
+    let labels = |error: &RuntimeError| error.labels().unwrap()
+        .map(|label| label.label().map(str::to_owned))
+        .collect::<Vec<_>>();
+
+    let error = evaluate("module m(a) {} m();").unwrap_err();
+    assert_eq!(labels(&error), vec![None, Some("while evaluating this statement".to_owned()), Some("defined here".to_owned())]);
+
+    // A definition which is the error itself isn't labelled twice
+    let span = test_span();
+    let error = RuntimeError::new(RuntimeErrorKind::DuplicateName("x".to_owned()), span.clone()).with_definition(&span);
+    assert_eq!(labels(&error), vec![None]);
+}
+
+#[test]
+fn test_reassignment() {
+    assert_eq!(evaluate("x = 1; set x = x + 1; x;").unwrap(), Object::Number(2.0));
+
+    // Nested scopes can change bindings from the scopes around them
+    assert_eq!(evaluate("width = 10; if (true) { if (true) { set width = 20; } } width;").unwrap(), Object::Number(20.0));
+    assert_eq!(evaluate("width = 10; if (false) { set width = 20; } width;").unwrap(), Object::Number(10.0));
+
+    // But bindings in a nested scope still go away with it
+    assert_eq!(evaluate_error("if (true) { inner = 1; set inner = 2; } inner;"), "undefined identifier \"inner\"");
+
+    // Only existing bindings can be changed
+    assert_eq!(evaluate_error("set x = 1;"), "undefined identifier \"x\"");
+    assert_eq!(evaluate_error("module m() {} set m = 1;"), "identifier \"m\" is a user-defined module, which cannot be used here");
+    assert_eq!(evaluate_error("set cube = 1;"), "identifier \"cube\" is a built-in module, which cannot be used here");
+
+    // A definition's body can't change anything outside of it, including its own parameters
+    assert_eq!(evaluate_error("x = 1; module m() { set x = 2; } m();"), "binding \"x\" is defined outside of this module or operator, so it can't be changed from within it");
+    assert_eq!(evaluate_error("x = 1; module m() { if (true) { set x = 2; } } m();"), "binding \"x\" is defined outside of this module or operator, so it can't be changed from within it");
+    assert_eq!(evaluate_error("module m(w = 1) { set w = 2; } m();"), "identifier \"w\" is a parameter, which cannot be used here");
+
+    // Defining the same name twice in one scope is still an error, which suggests `set`
+    let error = evaluate("x = 1; x = 2;").unwrap_err();
+    assert_eq!(error.to_string(), "name \"x\" is already defined");
+    assert_eq!(error.help.as_deref(), Some("to change its value, use `set x = ...` instead"));
+}
+
+#[test]
+fn test_reassignment_in_loops() {
+    // Accumulating over a loop
+    assert_eq!(evaluate("total = 0; for (i = [1:4]) { set total = total + i; } total;").unwrap(), Object::Number(10.0));
+
+    // Each iteration has its own scope, so a binding within the body doesn't clash with the one
+    // from the iteration before
+    assert_eq!(evaluate("total = 0; for (i = [1:3]) { step = i * 2; set total = total + step; } total;").unwrap(), Object::Number(12.0));
+
+    // A definition's own bindings can be changed from within it
+    assert!(evaluate("module m() { count = 0; for (i = [1:3]) { set count = count + 1; } cube(count); } m();").is_ok());
+}
+
+#[test]
+fn test_functions() {
+    assert_eq!(evaluate("function double(x) = x * 2; double(4);").unwrap(), Object::Number(8.0));
+    assert_eq!(evaluate("function add(a, b = 1) = a + b; [add(1), add(1, b = 5)];").unwrap(), numbers(&[2.0, 6.0]));
+    assert_eq!(evaluate("function origin() = [0, 0, 0]; origin();").unwrap(), numbers(&[0.0, 0.0, 0.0]));
+
+    // Functions can call each other, and look up names from where they're called like modules do
+    assert_eq!(evaluate("function sq(x) = x * x; function hyp(a, b) = sqrt(sq(a) + sq(b)); hyp(3, 4);").unwrap(), Object::Number(5.0));
+    assert_eq!(evaluate("factor = 3; function scaled(x) = x * factor; scaled(2);").unwrap(), Object::Number(6.0));
+
+    // The result can be used anywhere a value can, including as arguments to modules
+    assert!(matches!(evaluate("function side(n) = n * 10; cube(side(2));").unwrap(), Object::Manifold(_)));
+
+    // With a ternary to stop it, a function can call itself
+    assert_eq!(evaluate("function factorial(n) = n <= 1 ? 1 : n * factorial(n - 1); factorial(5);").unwrap(), Object::Number(120.0));
+    assert_eq!(evaluate("function factorial(n) = n <= 1 ? 1 : n * factorial(n - 1); factorial(0);").unwrap(), Object::Number(1.0));
+
+    // Arguments are checked against the parameters, like a module's
+    assert_eq!(evaluate_error("function double(x) = x * 2; double();"), "missing argument \"x\"");
+    assert_eq!(
+        evaluate("function f(x) = x + true; f(1);").unwrap_err().trace.iter().map(|frame| frame.to_string()).collect::<Vec<_>>(),
+        vec!["in function \"f\", called from line 1"],
+    );
+}
+
+#[test]
+fn test_function_misuse() {
+    // Functions don't produce geometry, so they can't be used like modules or operators
+    let error = evaluate("function double(x) = x * 2; double;").unwrap_err();
+    assert_eq!(error.to_string(), "identifier \"double\" is a user-defined function, which cannot be used here");
+    assert_eq!(error.help.as_deref(), Some("functions must be called with parentheses, like `double(...)`"));
+    assert_eq!(evaluate("function one() = 1; one;").unwrap_err().help.as_deref(), Some("did you mean `one()`?"));
+
+    let error = evaluate("function double(x) = x * 2; double(2) cube(1);").unwrap_err();
+    assert_eq!(error.to_string(), "identifier \"double\" is a user-defined function, which cannot be used here");
+    assert_eq!(error.help.as_deref(), Some("functions return a value rather than geometry, so they can't be applied to children"));
+
+    // Or given to operators as children, where the value would be thrown away
+    let error = evaluate("function double(x) = x * 2; translate([1, 0, 0]) double(2);").unwrap_err();
+    assert_eq!(error.to_string(), "identifier \"double\" is a user-defined function, which cannot be used here");
+    assert_eq!(error.help.as_deref(), Some("this returns a number rather than geometry, so there's nothing for `translate` to apply to"));
+    assert_eq!(error.span.start_column(), 50);
+    assert_eq!(
+        evaluate_error("function double(x) = x * 2; translate([1, 0, 0]) { x = double(2); double(x); }"),
+        "identifier \"double\" is a user-defined function, which cannot be used here",
+    );
+
+    // Functions share names with everything else
+    assert_eq!(evaluate_error("function f() = 1; module f() {}"), "name \"f\" is already defined");
+    assert_eq!(evaluate_error("x = 1; function x() = 2;"), "name \"x\" is already defined");
+    assert_eq!(evaluate_error("function cube() = 1;"), "name \"cube\" is already defined");
+}
+
+#[test]
+fn test_call_depth_limit() {
+    // Test threads have a small stack, so use a lower limit than usual
+    let evaluate_limited = |source: &str| {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_call_depth(10);
+        let result = evaluate_in(&mut interpreter, source, ExecutionContext::new());
+        (result, interpreter.call_depth)
+    };
+
+    // Recursion is allowed, up to the limit
+    assert_eq!(evaluate_limited("function f(n) = n; function g(n) = f(n) + f(n); g(2);").0.unwrap(), Object::Number(4.0));
+
+    // A ternary lets it stop before the limit, as long as it doesn't go too deep
+    let factorial = "function factorial(n) = n <= 1 ? 1 : n * factorial(n - 1);";
+    assert_eq!(evaluate_limited(&format!("{factorial} factorial(8);")).0.unwrap(), Object::Number(40320.0));
+    assert_eq!(evaluate_limited(&format!("{factorial} factorial(30);")).0.unwrap_err().to_string(), "calls are nested more than 10 deep");
+
+    // Recursion which never stops is an error, rather than a stack overflow
+    let (result, depth) = evaluate_limited("function forever(n) = forever(n + 1); forever(0);");
+    let error = result.unwrap_err();
+    assert_eq!(error.to_string(), "calls are nested more than 10 deep");
+    assert_eq!(error.trace.len(), 10);
+    assert_eq!(error.help.as_deref(), Some("check that recursive definitions have a case where they stop calling themselves"));
+
+    // Stopping partway through doesn't leave the depth raised for later calls
+    assert_eq!(depth, 0);
+
+    // The same goes for modules and operators, and calls between different kinds of definition
+    assert_eq!(evaluate_limited("module m() { m(); } m();").0.unwrap_err().to_string(), "calls are nested more than 10 deep");
+    assert_eq!(evaluate_limited("operator o() { o() children(); } o() empty();").0.unwrap_err().to_string(), "calls are nested more than 10 deep");
+    assert_eq!(evaluate_limited("function f() = g(); module g() { f(); } f();").0.unwrap_err().to_string(), "calls are nested more than 10 deep");
+}
+
+#[test]
+fn test_definitions_while_looking_up() {
+    // Parameter defaults are evaluated as the definition is added, looking names up through every
+    // scope above it, which mustn't conflict with adding names to any of them
+    let nested = |definitions: &str| format!("
+        b = 1;
+        if (true) {{
+            c = b + 1;
+            if (true) {{
+                {definitions}
+            }}
+        }}
+    ");
+    assert_eq!(evaluate(&nested("module m(a = b + c, d = [b, c]) {} operator o(e = c) {} x = c * 2;")).unwrap(), Object::Null);
+    assert_eq!(evaluate_error(&nested("module m(a = c) {} c = 3;")), "name \"c\" is already defined");
+    assert_eq!(evaluate_error(&nested("module m(a = b) {} m = 1;")), "name \"m\" is already defined");
+    assert_eq!(evaluate_error(&nested("module m(a = undefined) {}")), "undefined identifier \"undefined\"");
+
+    // Many scopes deep, each referring to the one above
+    let depth = 20;
+    let mut source = "v0 = 0;".to_owned();
+    for i in 1..=depth {
+        source.push_str(&format!(" if (true) {{ v{i} = v{} + 1;", i - 1));
+    }
+    source.push_str(&format!(" module m(a = v{depth} + v0) {{}} m = 1;"));
+    source.push_str(&" }".repeat(depth));
+    assert_eq!(evaluate_error(&source), "name \"m\" is already defined");
+}
+
+#[test]
+fn test_prelude_shadowing() {
+    let evaluate_after_prelude = |source: &str| {
+        let prelude_ctx = ExecutionContext::new();
+        Interpreter::new().interpret_prelude(&prelude_ctx).unwrap();
+        evaluate_with(source, prelude_ctx.with_deeper_scope())
+    };
+
+    assert_eq!(evaluate_after_prelude("hexagon = 1; hexagon;").unwrap(), Object::Number(1.0));
+
+    // Only the prelude's definitions can be replaced, not the model's own
+    assert_eq!(evaluate_after_prelude("hexagon = 1; hexagon = 2;").unwrap_err().to_string(), "name \"hexagon\" is already defined");
+    assert_eq!(evaluate_after_prelude("module washer() {} module washer() {}").unwrap_err().to_string(), "name \"washer\" is already defined");
+}
+
+#[test]
+fn test_undefined_name_suggestions() {
+    let help = |source: &str| evaluate(source).unwrap_err().help.map(String::from);
+
+    assert_eq!(help("thickness = 2; thikness;"), Some("did you mean \"thickness\"?".to_owned()));
+    assert_eq!(help("cub(1);"), Some("did you mean \"cube\"?".to_owned()));
+    assert_eq!(help("module m(size) { cube(sise); } m(1);"), Some("did you mean \"size\"?".to_owned()));
+    assert_eq!(help("for (i = [1, 2]) { j; }"), None);
+
+    // Nothing is within two edits
+    assert_eq!(help("thickness = 2; height;"), None);
+
+    // Verbose errors list the bindings in scope too, including arguments
+    let verbose_help = |source: &str| {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_verbose_errors(true);
+        evaluate_in(&mut interpreter, source, ExecutionContext::new()).unwrap_err().help.map(String::from)
+    };
+    assert_eq!(
+        verbose_help("thickness = 2; width = 3; thikness;"),
+        Some("did you mean \"thickness\"?\nbindings in scope: thickness, width".to_owned()),
+    );
+    assert_eq!(
+        verbose_help("wall = 1; module m(size) { cube(height); } m(1);"),
+        Some("bindings in scope: size, wall".to_owned()),
+    );
+    assert_eq!(verbose_help("height;"), Some("no bindings are in scope".to_owned()));
+}
+
+#[test]
+fn test_math() {
+    assert_eq!(evaluate("sin(30);").unwrap(), Object::Number(0.5));
+    assert_eq!(evaluate("cos(90);").unwrap(), Object::Number(0.0));
+    assert_eq!(evaluate("cos(-180);").unwrap(), Object::Number(-1.0));
+    assert_eq!(evaluate("abs(tan(45) - 1) < 0.000001;").unwrap(), Object::Boolean(true));
+    assert_eq!(evaluate("atan2(1, 0);").unwrap(), Object::Number(90.0));
+    assert_eq!(evaluate("asin(1);").unwrap(), Object::Number(90.0));
+    assert_eq!(evaluate("sqrt(16);").unwrap(), Object::Number(4.0));
+    assert_eq!(evaluate("pow(2, 10);").unwrap(), Object::Number(1024.0));
+    assert_eq!(evaluate("[abs(-2), floor(2.5), ceil(2.5), round(2.5), round(-2.5)];").unwrap(), numbers(&[2.0, 2.0, 3.0, 3.0, -3.0]));
+
+    // min and max take either two numbers or a vector
+    assert_eq!(evaluate("min(3, 2);").unwrap(), Object::Number(2.0));
+    assert_eq!(evaluate("max([4, 8, 1]);").unwrap(), Object::Number(8.0));
+    assert_eq!(evaluate_error("max([]);"), "max needs at least one number, but the vector is empty");
+    assert_eq!(evaluate_error("min(1);"), "type error - expected number, got null");
+
+    // Errors point to the argument which is wrong
+    let error_at = |source: &str| {
+        let error = evaluate(source).unwrap_err();
+        (error.to_string(), source[error.span.start..error.span.start + error.span.length].to_owned())
+    };
+    assert_eq!(
+        error_at("sqrt(-1);"),
+        ("sqrt needs a number which isn't negative, but got -1".to_owned(), "-1".to_owned()),
+    );
+    assert_eq!(
+        error_at("pow(2, exponent = true);"),
+        ("type error - expected number, got boolean".to_owned(), "true".to_owned()),
+    );
+    assert_eq!(
+        error_at("max(\"a\", 1);"),
+        ("type error - expected number or vector, got string".to_owned(), "\"a\"".to_owned()),
+    );
+    assert_eq!(
+        error_at("min([1, false]);"),
+        ("type error - expected element 1 of vector to be number, got boolean".to_owned(), "false".to_owned()),
+    );
+}
+
+#[test]
+fn test_builtin_names() {
+    for name in builtin::BUILTIN_MODULE_NAMES {
+        assert!(builtin::get_builtin_module(name).is_some(), "no module named {name}");
+    }
+    for name in builtin::BUILTIN_MATH_NAMES {
+        assert!(builtin::get_builtin_module(name).is_some(), "no function named {name}");
+    }
+    for name in builtin::BUILTIN_OPERATOR_NAMES {
+        assert!(builtin::get_builtin_operator(name).is_some(), "no operator named {name}");
+    }
+}
+
+#[test]
+fn test_builtin_docs() {
+    let builtins = builtin::all_builtins().collect::<Vec<_>>();
+    assert_eq!(
+        builtins.len(),
+        builtin::BUILTIN_MODULE_NAMES.len() + builtin::BUILTIN_MATH_NAMES.len() + builtin::BUILTIN_OPERATOR_NAMES.len(),
+    );
+
+    for doc in &builtins {
+        let name = doc.name;
+        assert!(!doc.summary.is_empty(), "{name} has no summary");
+
+        // The registry names each built-in the same as it's looked up by
+        let (resolved_name, parameter_docs) = match doc.kind {
+            builtin::BuiltinKind::Module => {
+                let module = builtin::get_builtin_module(name).unwrap_or_else(|| panic!("no module named {name}"));
+                (module.name, module.parameter_docs)
+            }
+            builtin::BuiltinKind::Operator => {
+                let operator = builtin::get_builtin_operator(name).unwrap_or_else(|| panic!("no operator named {name}"));
+                (operator.name, operator.parameter_docs)
+            }
+        };
+        assert_eq!(resolved_name, name);
+
+        for parameter in &doc.parameters {
+            assert!(!parameter.summary.is_empty(), "parameter {} of {name} has no summary", parameter.name);
+        }
+        for (documented, _) in parameter_docs {
+            assert!(doc.parameters.iter().any(|parameter| parameter.name == *documented), "{name} documents an unknown parameter {documented}");
+        }
+    }
+
+    let reference = builtin::format_builtin_reference(builtins.into_iter().filter(|doc| doc.name == "cylinder"));
+    assert_eq!(reference, "\
+module cylinder(h, r = null, d = null, center = false)
+    Creates a cylinder standing upwards from the origin.
+    - h: Height.
+    - r: Radius. Either this or `d` must be given.
+    - d (by name only): Diameter, instead of `r`.
+    - center (by name only): Whether to centre the cylinder vertically on the origin instead.
+");
+}
+
+#[test]
+fn test_arity() {
+    assert_eq!(evaluate_error("module m(a) {} m(1, 2);"), "incorrect number of positional arguments - expected 1, got 2");
+    assert_eq!(evaluate_error("module m(a, b = 2) {} m(1, 2, 3);"), "incorrect number of positional arguments - expected 1-2, got 3");
+    assert_eq!(evaluate_error("module m(a, b) {} m();"), "missing multiple arguments: a, b");
+    assert_eq!(evaluate_error("module m(a) {} m(c = 1);"), "no argument named \"c\"");
+    assert_eq!(evaluate_error("module m(a) {} m(1, a = 1);"), "argument \"a\" has already been passed as a positional argument, so cannot be passed again by name");
+    assert_eq!(evaluate_error("module m(a) {} m(a = 1, a = 2);"), "argument \"a\" cannot be passed by name more than once");
+}
+
+#[test]
+fn test_named_arguments() {
+    let parameters = EvaluatedParameters {
+        required: vec!["h".to_owned()],
+        optional: vec![("r".to_owned(), Object::Null), ("center".to_owned(), Object::Boolean(false))],
+        optional_named_only: vec![("d".to_owned(), Object::Null)],
+    };
+    let matched = Interpreter::new().match_arguments_to_parameters(
+        EvaluatedArguments {
+            positional: vec![Object::Number(10.0)],
+            named: vec![("d".to_owned(), Object::Number(4.0)), ("center".to_owned(), Object::Boolean(true))],
+        },
+        &parameters,
+        test_span(),
+        None,
+    ).unwrap();
+
+    assert_eq!(matched.len(), 4);
+    assert_eq!(matched["h"], Object::Number(10.0));
+    assert_eq!(matched["d"], Object::Number(4.0));
+    assert_eq!(matched["center"], Object::Boolean(true));
+
+    // Parameters which weren't given get their defaults
+    assert_eq!(matched["r"], Object::Null);
+}
+
+#[test]
+fn test_named_arguments_builtins() {
+    // These are rejected before any geometry is built
+    assert_eq!(evaluate_error("cylinder(r = 1);"), "missing argument \"h\"");
+    assert_eq!(evaluate_error("cylinder(10, diameter = 2);"), "no argument named \"diameter\"");
+    assert_eq!(evaluate_error("cylinder(10, h = 10);"), "argument \"h\" has already been passed as a positional argument, so cannot be passed again by name");
+    assert_eq!(evaluate_error("cylinder(10, d = 2, d = 3);"), "argument \"d\" cannot be passed by name more than once");
+
+    assert_eq!(evaluate_error("translate(w = [1, 2, 3]) {}"), "no argument named \"w\"");
+    assert_eq!(evaluate_error("translate([1, 2, 3], v = [1, 2, 3]) {}"), "argument \"v\" has already been passed as a positional argument, so cannot be passed again by name");
+    assert_eq!(evaluate_error("operator o(a) { children; } o(a = 1, a = 2) {}"), "argument \"a\" cannot be passed by name more than once");
+}
+
+#[test]
+fn test_parameter_defaults() {
+    let source = Rc::new(InputSource::new_string("size = 2;\nmodule m(a, v = [1, 2, 3], s = size * 2) { cube(a); }".to_owned()));
+    let (tokens, _) = tokenize(source.clone());
+    let nodes = Parser::new(source.clone(), tokens).parse_statements();
+
+    let ctx = ExecutionContext::new();
+    let mut interpreter = Interpreter::new();
+    for node in &nodes {
+        interpreter.interpret(node, &ctx).unwrap();
+    }
+
+    // Defaults are evaluated once, where the module is defined
+    let Some(NameDefinition::UserDefinedModule(definition)) = interpreter.get_name("m", &ctx)
+    else { panic!("expected m to be user-defined") };
+    assert_eq!(definition.parameters.optional, vec![
+        ("v".to_owned(), numbers(&[1.0, 2.0, 3.0])),
+        ("s".to_owned(), Object::Number(4.0)),
+    ]);
+
+    // Arguments which are given replace the default
+    let matched = interpreter.match_arguments_to_parameters(
+        EvaluatedArguments { positional: vec![Object::Number(1.0)], named: vec![("s".to_owned(), Object::Number(10.0))] },
+        &definition.parameters,
+        test_span(),
+        Some(&definition.span),
+    ).unwrap();
+    assert_eq!(matched["v"], numbers(&[1.0, 2.0, 3.0]));
+    assert_eq!(matched["s"], Object::Number(10.0));
+}
+
+#[test]
+fn test_parameter_default_errors() {
+    // Other parameters don't exist yet when defaults are evaluated
+    assert_eq!(evaluate_error("module m(a, b = a) {}"), "undefined identifier \"a\"");
+    assert_eq!(evaluate_error("operator o(a, b = a * 2) { children(); }"), "undefined identifier \"a\"");
+
+    // Neither do bindings made after the definition
+    assert_eq!(evaluate_error("module m(a = later) {} later = 1;"), "undefined identifier \"later\"");
+
+    assert_eq!(evaluate_error("module m(a = 1 + true) {}"), "type error - expected number, got boolean");
+    assert_eq!(evaluate_error("module m(a, b = 2) {} m();"), "missing argument \"a\"");
+}
+
+#[test]
+fn test_arity_help() {
+    let help = |source: &str| evaluate(source).unwrap_err().help.map(String::from);
+
+    assert_eq!(help("module m(a, b = 2) {} m(1, 2, 3);"), Some("the parameters are (a, b), so the 3rd argument is extra".to_owned()));
+    assert_eq!(help("module m() {} m(1, 2, 3);"), Some("this takes no positional arguments, so the 1st to 3rd arguments are extra".to_owned()));
+    assert_eq!(help("cube(1, false, 2);"), Some("the parameters are (size), so the 2nd and 3rd arguments are extra".to_owned()));
+}
+
+#[test]
+fn test_arity_definition_label() {
+    let definition = |source: &str| evaluate(source).unwrap_err().context
+        .and_then(|context| context.definition)
+        .map(|span| span.start_line());
+
+    assert_eq!(definition("x = 1;\nmodule m(a) {}\nm(1, 2);"), Some(2));
+    assert_eq!(definition("module m(a) {}\nm(b = 1);"), Some(1));
+
+    // Built-ins have no definition in the source
+    assert_eq!(definition("cube(1, 2, 3);"), None);
+}
+
+#[test]
+fn test_print_clearance() {
+    assert_eq!(evaluate("hole_d(5);").unwrap(), Object::Number(5.0));
+    assert_eq!(evaluate("print_clearance(0.4); hole_d(5);").unwrap(), Object::Number(5.4));
+    assert_eq!(evaluate("print_clearance(0.4); hole_r(2.5);").unwrap(), Object::Number(2.7));
+    assert_eq!(evaluate("print_clearance(-0.1); hole_d(3);").unwrap(), Object::Number(2.9));
+}
+
+#[test]
+fn test_segments_validation() {
+    assert_eq!(evaluate("segments(64);").unwrap(), Object::Null);
+    assert_eq!(evaluate_error("segments(\"fine\");"), "type error - expected number, got string");
+    assert_eq!(evaluate_error("segments(2);"), "segments must be a whole number, at least 3, got 2");
+    assert_eq!(evaluate_error("segments(12.5);"), "segments must be a whole number, at least 3, got 12.5");
+    assert_eq!(evaluate_error("segments(1 / 0);"), "segments must be a whole number, at least 3, got inf");
+}
+
+#[test]
+fn test_center_validation() {
+    assert_eq!(evaluate_error("cube(1, center = 1);"), "type error - expected boolean, got number");
+    assert_eq!(evaluate_error("square(1, center = \"yes\");"), "type error - expected boolean, got string");
+    assert_eq!(evaluate_error("cylinder(1, 1, center = null);"), "type error - expected boolean, got null");
+}
+
+#[test]
+fn test_assert() {
+    assert_eq!(evaluate("assert(1 < 2);").unwrap(), Object::Null);
+    assert_eq!(evaluate("assert(true, \"unused\");").unwrap(), Object::Null);
+    assert_eq!(evaluate_error("assert(1 > 2);"), "assertion failed");
+    assert_eq!(evaluate_error("assert(false, \"need at least two holes\");"), "assertion failed: need at least two holes");
+    assert_eq!(evaluate_error("assert(1);"), "type error - expected boolean, got number");
+    assert_eq!(evaluate_error("assert(false, 3);"), "type error - expected string, got number");
+
+    // The error points at the call
+    let error = evaluate("x = 1;\nassert(x == 2, \"x must be 2\");").unwrap_err();
+    assert_eq!(error.kind, RuntimeErrorKind::AssertionFailed { message: Some("x must be 2".to_owned()) });
+    assert_eq!((error.span.start, error.span.length), (7, 29));
+}
+
+#[test]
+fn test_keep_outside_difference() {
+    let message = "`keep` can only be used directly inside `difference`";
+    assert_eq!(evaluate_error("keep() cube(1);"), message);
+    assert_eq!(evaluate_error("union() keep() cube(1);"), message);
+    assert_eq!(evaluate_error("difference() translate([1, 0, 0]) keep() cube(1);"), message);
+
+    // It must be a direct child, rather than within a body
+    assert_eq!(evaluate_error("difference() { if (true) { keep() cube(1); } }"), message);
+    assert_eq!(evaluate_error("difference() { for (i = [1, 2]) { keep() cube(i); } }"), message);
+    assert_eq!(evaluate_error("module boss() { keep() cube(1); } difference() { boss(); }"), message);
+}
+
+#[test]
+fn test_print_clearance_validation() {
+    assert_eq!(evaluate_error("print_clearance(0.2); print_clearance(0.3);"), "`print_clearance` has already been set");
+    assert_eq!(evaluate_error("x = hole_d(5); print_clearance(0.2);"), "`print_clearance` must be set before `hole_d` or `hole_r` are used");
+    assert_eq!(evaluate_error("print_clearance(\"loose\");"), "type error - expected number, got string");
+    assert_eq!(evaluate_error("print_clearance(1 / 0);"), "clearance must be a finite number, got inf");
+    assert_eq!(evaluate_error("hole_d(0);"), "hole size must be a positive number, got 0");
+    assert_eq!(evaluate_error("print_clearance(-2); hole_d(1);"), "hole size 1 is too small for the print clearance, which would make it -1");
+}
+
+#[test]
+fn test_it_and_children_misuse() {
+    assert_eq!(evaluate_error("it;"), "cannot use `it` outside of operator target arguments");
+    assert_eq!(evaluate_error("x = it.size;"), "cannot use `it` outside of operator target arguments");
+    assert_eq!(evaluate_error("cube(it.size.x);"), "cannot use `it` outside of operator target arguments");
+    assert_eq!(evaluate_error("cube(size = [it.size.x, 1, 1]);"), "cannot use `it` outside of operator target arguments");
+    assert_eq!(evaluate_error("translate([1, 0, 0]) cube(it.size.x);"), "cannot use `it` outside of operator target arguments");
+    assert_eq!(evaluate_error("children();"), "cannot use `children` outside of operator body");
+    assert_eq!(evaluate_error("module m() { children(); } m();"), "cannot use `children` outside of operator body");
+    assert_eq!(evaluate_error("child(0);"), "cannot use `children` outside of operator body");
+    assert_eq!(evaluate_error("x = child_bounds(0);"), "cannot use `children` outside of operator body");
+}
+
+#[test]
+fn test_if_chain_without_geometry() {
+    // Whichever branch runs, having no geometry gives null rather than an error
+    assert_eq!(evaluate("x = 2; if (x == 1) { y = 1; } else if (x == 2) { y = 2; } else { y = 3; }").unwrap(), Object::Null);
+    assert_eq!(evaluate("if (false) { y = 1; }").unwrap(), Object::Null);
+
+    assert_eq!(
+        evaluate_error("x = 2; translate([1, 0, 0]) { if (x == 1) { y = 1; } else if (x == 2) { y = 2; } else { y = 3; } }"),
+        "the selected branch produced no geometry for this operator to apply to",
+    );
+    assert_eq!(
+        evaluate_error("translate([1, 0, 0]) { if (false) { y = 1; } }"),
+        "the selected branch produced no geometry for this operator to apply to",
+    );
+}
+
+#[test]
+fn test_flatten_dispositions() {
+    use GeometryDisposition::{Physical, Virtual};
+
+    assert_eq!(GeometryDisposition::flatten(&[Physical, Physical], test_span()).unwrap(), Physical);
+    assert_eq!(GeometryDisposition::flatten(&[Virtual], test_span()).unwrap(), Virtual);
+
+    // Nothing at all counts as physical
+    assert_eq!(GeometryDisposition::flatten(&[], test_span()).unwrap(), Physical);
+
+    assert_eq!(
+        GeometryDisposition::flatten(&[Physical, Virtual], test_span()).unwrap_err().to_string(),
+        "this operation tried to mix geometries of different dispositions",
+    );
+}
+
+#[test]
+fn test_resolve_paths() {
+    let dir = std::env::temp_dir().join(format!("yascad-test-resolve-paths-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("parts")).unwrap();
+    std::fs::write(dir.join("parts/data.txt"), "hello").unwrap();
+
+    // Relative paths in a string source are resolved against its base
+    let source = Rc::new(InputSource::new_string_with_base("import".to_owned(), &dir));
+    let span = source.span(0, 6);
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.read_file_to_string("parts/data.txt", &span).unwrap(), "hello");
+    assert!(interpreter.warnings().is_empty());
+
+    // Errors give the whole path which was tried
+    let error = interpreter.read_file_to_string("parts/missing.txt", &span).unwrap_err();
+    let RuntimeErrorKind::FileReadError { path, .. } = &error.kind else { panic!("expected file read error, got {error:?}") };
+    assert_eq!(path, &dir.join("parts/missing.txt"));
+    assert!(path.is_absolute());
+    assert!(error.to_string().starts_with(&format!("could not read \"{}\"", dir.join("parts/missing.txt").display())));
+
+    // Without a base, the current directory is used, but that's probably a mistake
+    let source = Rc::new(InputSource::new_string("import".to_owned()));
+    let span = source.span(0, 6);
+    let resolved = interpreter.resolve_path("parts/data.txt", &span);
+    assert_eq!(resolved, std::env::current_dir().unwrap().join("parts/data.txt"));
+    let [warning] = interpreter.warnings() else { panic!("expected one warning") };
+    assert!(matches!(&warning.kind, RuntimeWarningKind::NoBaseDirectory { resolved: warned, .. } if *warned == resolved));
+
+    // Absolute paths don't need a base
+    let absolute = dir.join("parts/data.txt");
+    assert_eq!(interpreter.read_file_to_string(absolute.to_str().unwrap(), &span).unwrap(), "hello");
+    assert_eq!(interpreter.warnings().len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_file_access_policy() {
+    let dir = std::env::temp_dir().join(format!("yascad-test-file-access-policy-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("model/parts")).unwrap();
+    std::fs::write(dir.join("model/parts/data.txt"), "hello").unwrap();
+    std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+    let source = Rc::new(InputSource::new_string_with_base("import".to_owned(), dir.join("model")));
+    let span = source.span(0, 6);
+    let read = |policy: FileAccessPolicy, path: &str| {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_file_access_policy(policy);
+        interpreter.read_file_to_string(path, &span).map_err(|error| error.kind)
+    };
+
+    // Anything can be read by default
+    assert_eq!(read(FileAccessPolicy::default(), "parts/data.txt").unwrap(), "hello");
+    assert_eq!(read(FileAccessPolicy::Allowed, "../secret.txt").unwrap(), "secret");
+
+    // Denied files give the path and the policy, whether or not they exist
+    let error = read(FileAccessPolicy::DenyAll, "parts/data.txt").unwrap_err();
+    assert_eq!(error, RuntimeErrorKind::FileAccessDenied { path: dir.join("model/parts/data.txt"), policy: FileAccessPolicy::DenyAll });
+    assert_eq!(error.to_string(), format!("not allowed to read \"{}\", because file access is disabled", dir.join("model/parts/data.txt").display()));
+    assert!(matches!(read(FileAccessPolicy::DenyAll, "parts/missing.txt"), Err(RuntimeErrorKind::FileAccessDenied { .. })));
+
+    // Only files within the allowed directories can be read, however their paths are written
+    let allow_model = FileAccessPolicy::AllowList(vec![dir.join("model")]);
+    assert_eq!(read(allow_model.clone(), "parts/data.txt").unwrap(), "hello");
+    assert_eq!(read(allow_model.clone(), "./parts/../parts/data.txt").unwrap(), "hello");
+    assert!(matches!(read(allow_model.clone(), "parts/missing.txt"), Err(RuntimeErrorKind::FileReadError { .. })));
+    for escape in ["../secret.txt", "parts/../../secret.txt", "../../../../../../../../etc/passwd", "/etc/passwd"] {
+        let error = read(allow_model.clone(), escape).unwrap_err();
+        assert!(matches!(&error, RuntimeErrorKind::FileAccessDenied { policy, .. } if *policy == allow_model), "{escape} wasn't denied: {error:?}");
+        assert!(error.to_string().ends_with(&format!("because only files within \"{}\" are allowed", dir.join("model").display())));
+    }
+    assert!(read(FileAccessPolicy::AllowList(vec![]), "parts/data.txt").unwrap_err().to_string().ends_with("because no directories are allowed"));
+
+    // Symbolic links can't be used to escape either
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&dir, dir.join("model/parts/escape")).unwrap();
+        assert!(matches!(read(allow_model.clone(), "parts/escape/secret.txt"), Err(RuntimeErrorKind::FileAccessDenied { .. })));
+        assert_eq!(read(FileAccessPolicy::AllowList(vec![dir.clone()]), "parts/escape/secret.txt").unwrap(), "secret");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_decimal_comma_warnings() {
+    let warnings = |code: &str, locale_hints: bool| {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let (tokens, _) = tokenize(source.clone());
+        let nodes = Parser::new(source.clone(), tokens).parse_statements();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_locale_hints(locale_hints);
+        let ctx = ExecutionContext::new();
+        for node in &nodes {
+            interpreter.interpret(node, &ctx).unwrap();
+        }
+        interpreter.warnings().iter().map(|warning| warning.to_string()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        warnings("x = [10,5, 2];", true),
+        vec!["\"10,5\" is two numbers, 10 and 5 - if you meant one number, write \"10.5\" instead, or put a space after the comma to keep them separate"],
+    );
+
+    assert!(warnings("x = [10, 5, 2];", true).is_empty());
+    assert!(warnings("x = [10.0,5, 2];", true).is_empty());
+    assert!(warnings("x = [10,5, 2];", false).is_empty());
+}
+
+#[test]
+fn test_definitions_shared_between_lookups() {
+    let source = Rc::new(InputSource::new_string("module m(a) { x = a; }\noperator o() { children(); }".to_owned()));
+    let (tokens, _) = tokenize(source.clone());
+    let nodes = Parser::new(source.clone(), tokens).parse_statements();
+
+    let ctx = ExecutionContext::new();
+    let mut interpreter = Interpreter::new();
+    for node in &nodes {
+        interpreter.interpret(node, &ctx).unwrap();
+    }
+
+    // Looking up a definition, as each call does, shouldn't copy its body
+    for name in ["m", "o"] {
+        let (Some(NameDefinition::UserDefinedModule(first) | NameDefinition::UserDefinedOperator(first)), Some(NameDefinition::UserDefinedModule(second) | NameDefinition::UserDefinedOperator(second)))
+            = (interpreter.get_name(name, &ctx), interpreter.get_name(name, &ctx))
+        else { panic!("expected {name} to be user-defined") };
+        assert!(Rc::ptr_eq(&first, &second));
+    }
+}
+
+#[test]
+fn test_many_calls() {
+    // A large body, called many times
+    let body = (0..100).map(|i| format!("x{i} = [a, {i}, a * {i}];")).collect::<String>();
+    let source = format!("module m(a) {{ {body} empty(); }}\nfor (i = [0:4999]) {{ m(i); }}");
+    assert!(matches!(evaluate(&source).unwrap(), Object::Manifold(_)));
+}
+
+#[test]
+fn test_module_returning_parts() {
+    let source = Rc::new(InputSource::new_string("
+        module pair() {
+            a = cube(1);
+            b = square(2);
+            [a, b];
+        }
+        pair();
+    ".to_owned()));
+    let (tokens, _) = tokenize(source.clone());
+    let nodes = Parser::new(source.clone(), tokens).parse_statements();
+
+    // The parts stay separate, rather than being combined into one geometry
+    let mut interpreter = Interpreter::new();
+    let ctx = ExecutionContext::new();
+    interpreter.interpret(&nodes[0], &ctx).unwrap();
+    let result = interpreter.interpret(&nodes[1], &ctx).unwrap();
+    assert!(matches!(&result, Object::Vector(items, _) if matches!(items.as_slice(), [Object::Manifold(_), Object::CrossSection(_)])));
+    assert_eq!(interpreter.manifold_table.iter_geometry().count(), 2);
+}
+
+#[test]
+fn test_memory_budget() {
+    let source = "
+        cube(1);
+        for (i = [0:100]) {
+            cube([1, 1, i + 1]);
+        }
+    ";
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_memory_budget(Some(10_000));
+    let error = evaluate_in(&mut interpreter, source, ExecutionContext::new()).unwrap_err();
+
+    // The statement inside the loop which went over is blamed, not the whole loop
+    let RuntimeErrorKind::MemoryBudgetExceeded { budget, estimate } = error.kind
+    else { panic!("expected a memory budget error, got {error:?}") };
+    assert_eq!(budget, 10_000);
+    assert!(estimate > budget);
+    assert_eq!(&source[error.span.start..error.span.start + error.span.length], "cube([1, 1, i + 1]);");
+    assert!(error.to_string().ends_with("of memory, which is over the budget of 10.0 KB"));
+
+    // The table is left with the geometry which was built, and an estimate to match
+    let table = &interpreter.manifold_table;
+    assert_eq!(table.estimated_memory(), estimate);
+    assert_eq!(table.estimated_memory(), table.iter_geometry().map(|(entry, ..)| entry.estimated_memory()).sum::<usize>());
+
+    // A big enough budget changes nothing
+    let mut interpreter = Interpreter::new();
+    interpreter.set_memory_budget(Some(1_000_000_000));
+    assert!(evaluate_in(&mut interpreter, source, ExecutionContext::new()).is_ok());
+    assert_eq!(interpreter.estimated_memory(), interpreter.manifold_table.iter_geometry().map(|(entry, ..)| entry.estimated_memory()).sum::<usize>());
+}
+
+#[test]
+fn test_part_source_id() {
+    let mut hasher = crate::PartSourceHasher::default();
+    let mut id = |code: &str, start, length| {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        hasher.part_source_id(&source.span(start, length))
+    };
+
+    // Moving the code doesn't change its ID, but changing it does
+    assert_eq!(id("cube(1);", 0, 7), id("sphere(2);\ncube(1);", 11, 7));
+    assert_ne!(id("cube(1);", 0, 7), id("cube(2);", 0, 7));
+
+    // Spans count characters, not bytes
+    assert_eq!(id("cube(1);", 0, 7), id("// é\ncube(1);", 5, 7));
+
+    // But the same code in a different file has a different ID
+    let same_code = id("cube(1);", 0, 7);
+    let file = Rc::new(InputSource::new_prelude("cube(1);".to_owned()));
+    assert_ne!(same_code, hasher.part_source_id(&file.span(0, 7)));
+}
+
+#[test]
+fn test_text() {
+    let dir = std::env::temp_dir().join(format!("yascad-test-text-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("font.ttf"), crate::font::test::test_font()).unwrap();
+    std::fs::write(dir.join("not-a-font.ttf"), "hello").unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().replace('\\', "/");
+
+    // Without a font, the system's is used if it has one. The model didn't choose that file, so
+    // it's read even when the model can't read any files
+    let mut interpreter = Interpreter::new();
+    interpreter.set_file_access_policy(FileAccessPolicy::DenyAll);
+    match (crate::font::default_font_path(), evaluate_in(&mut interpreter, "text(\"o\");", ExecutionContext::new())) {
+        (Some(default_font), Ok(_)) => assert_eq!(
+            interpreter.files_read().iter().map(|(path, _)| path.as_path()).collect::<Vec<_>>(),
+            vec![std::path::Path::new(default_font)],
+        ),
+        (None, Err(error)) => assert_eq!(error.kind.to_string(), "no font was given, and the system doesn't have a default one"),
+        (default_font, result) => panic!("unexpected result with default font {default_font:?}: {result:?}"),
+    }
+    assert_eq!(
+        evaluate_error(&format!("text(\"o\", font = \"{}\");", path("not-a-font.ttf"))),
+        "invalid font: not a TrueType font",
+    );
+    assert_eq!(evaluate_error(&format!("text(\"o\", 0, font = \"{}\");", path("font.ttf"))), "size must be positive, got 0");
+
+    // The hole in "o" is left out, and each glyph is placed after the last
+    let mut interpreter = Interpreter::new();
+    let source = format!("text(\"oo\", 10, font = \"{}\");", path("font.ttf"));
+    let Object::CrossSection(index) = evaluate_in(&mut interpreter, &source, ExecutionContext::new()).unwrap()
+    else { panic!("expected a cross-section") };
+    let entry = interpreter.manifold_table.get(&index, test_span()).unwrap();
+    let crate::geometry_table::GeometryTableEntry::CrossSection(text) = entry else { panic!("expected a cross-section") };
+    assert!((text.area() - 2.0 * (100.0 - 25.0)).abs() < 1e-6);
+    assert_eq!(text.polygons().count_polygons(), 4);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
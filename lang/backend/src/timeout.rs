@@ -0,0 +1,104 @@
+use std::{sync::mpsc::{self, RecvTimeoutError}, thread, time::Duration};
+
+use yascad_frontend::InputSourceSpan;
+
+use crate::{Interpreter, RuntimeError, RuntimeErrorKind, geometry_table::{GeometryDisposition, GeometryTableEntry, GeometryTableIndex, GeometryTableItem}};
+
+/// Copies of the geometry which an operator is about to consume, so that it can be put back if the
+/// operator times out.
+pub(crate) struct TimeoutSave {
+    items: Vec<(GeometryTableIndex, GeometryTableItem)>,
+}
+
+impl Interpreter {
+    /// Limit how long the geometry operations of each built-in operator can take, or remove the
+    /// limit with [`None`].
+    /// 
+    /// With a limit, each operator's result is computed straight away on a separate thread. This is
+    /// slower, since Manifold can't batch operations together, so is best used for previews.
+    /// 
+    /// Must be set before interpreting anything to take effect.
+    pub fn set_operation_timeout(&mut self, timeout: Option<Duration>) {
+        self.operation_timeout = timeout;
+    }
+
+    /// Save the children of an operator before it runs, if there's a timeout.
+    pub(crate) fn save_for_timeout(&self, children: &[GeometryTableIndex]) -> Option<TimeoutSave> {
+        self.operation_timeout?;
+
+        let items = children.iter()
+            .map(|index| (index.clone(), self.manifold_table.save_item(index)))
+            .collect();
+        Some(TimeoutSave { items })
+    }
+
+    /// Compute the result of an operator, if there's a timeout, giving up with an error if it takes
+    /// too long.
+    /// 
+    /// The computation can't be interrupted, so it carries on in the background, but its result is
+    /// discarded. If the operator's action gave up already, in [`Self::compute_with_timeout`], or
+    /// this does, the operator's children are restored from `save`, so the geometry table is left
+    /// as it was before the operator.
+    pub(crate) fn evaluate_with_timeout(
+        &mut self,
+        result: Result<(GeometryTableEntry, GeometryDisposition), RuntimeError>,
+        save: Option<TimeoutSave>,
+        operator: &str,
+        span: &InputSourceSpan,
+    ) -> Result<(GeometryTableEntry, GeometryDisposition), RuntimeError> {
+        let Some(save) = save
+        else { return result };
+
+        let result = result.and_then(|(entry, disp)| {
+            // 2D geometry has already been computed, so there's nothing to wait for
+            if !entry.is_3d() {
+                return Ok((entry, disp));
+            }
+
+            let entry = self.compute_with_timeout(operator, span, move || {
+                entry.evaluate();
+                entry
+            })?;
+            Ok((entry, disp))
+        });
+
+        if let Err(RuntimeError { kind: RuntimeErrorKind::OperationTimedOut { .. }, .. }) = &result {
+            for (index, item) in save.items {
+                self.manifold_table.restore_item(&index, item);
+            }
+        }
+        result
+    }
+
+    /// Run some of an operator's geometry operations on a separate thread, if there's a timeout,
+    /// giving up with an error if they take too long.
+    /// 
+    /// 3D operations are deferred until [`Self::evaluate_with_timeout`], but others are computed as
+    /// soon as they're called, like 2D booleans, `offset`, `hull` and extrusions. Operators use this
+    /// for those, so that they can't run for longer than the timeout either.
+    pub(crate) fn compute_with_timeout<T: Send + 'static>(&self, operator: &str, span: &InputSourceSpan, work: impl FnOnce() -> T + Send + 'static) -> Result<T, RuntimeError> {
+        let Some(timeout) = self.operation_timeout
+        else { return Ok(work()) };
+
+        let (sender, receiver) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            // If this fails, the operator has already timed out, so nobody wants the result
+            let _ = sender.send(work());
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => Ok(result),
+
+            Err(RecvTimeoutError::Timeout) => Err(RuntimeError::new(
+                RuntimeErrorKind::OperationTimedOut { operator: operator.to_owned(), timeout },
+                span.clone(),
+            )),
+
+            // The worker panicked without sending anything, so pass the panic on
+            Err(RecvTimeoutError::Disconnected) => match worker.join() {
+                Err(panic) => std::panic::resume_unwind(panic),
+                Ok(()) => unreachable!("worker finished without sending a result"),
+            },
+        }
+    }
+}
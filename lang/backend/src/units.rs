@@ -0,0 +1,47 @@
+/// A unit of length which a model can be measured in.
+/// 
+/// The model's base unit is set with a top-level `units` declaration, and defaults to millimetres.
+/// All lengths in the model are in terms of this base unit, and the conversion functions (`mm`,
+/// `cm`, `inch`) convert into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unit {
+    #[default]
+    Millimetre,
+    Centimetre,
+    Inch,
+}
+
+impl Unit {
+    /// Look up a unit by the name used in `units` declarations.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mm" => Some(Unit::Millimetre),
+            "cm" => Some(Unit::Centimetre),
+            "inch" => Some(Unit::Inch),
+            _ => None,
+        }
+    }
+
+    /// The name used for this unit in `units` declarations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unit::Millimetre => "mm",
+            Unit::Centimetre => "cm",
+            Unit::Inch => "inch",
+        }
+    }
+
+    /// The length of one of this unit, in millimetres.
+    pub fn in_millimetres(&self) -> f64 {
+        match self {
+            Unit::Millimetre => 1.0,
+            Unit::Centimetre => 10.0,
+            Unit::Inch => 25.4,
+        }
+    }
+
+    /// Convert a length measured in `from` into this unit.
+    pub fn convert_from(&self, value: f64, from: Unit) -> f64 {
+        value * from.in_millimetres() / self.in_millimetres()
+    }
+}
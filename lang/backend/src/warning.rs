@@ -0,0 +1,101 @@
+use std::{error::Error, fmt::Display, path::PathBuf};
+
+use miette::Diagnostic;
+use yascad_frontend::{InputSourceSpan, Node, Severity, SourceDiagnostic, decimal_comma_hints};
+
+use crate::Interpreter;
+
+/// Something suspicious noticed while interpreting, which doesn't stop the model from building.
+#[derive(Debug, Clone, PartialEq, Eq, Diagnostic)]
+#[diagnostic(severity(Warning))]
+pub struct RuntimeWarning {
+    pub kind: RuntimeWarningKind,
+
+    #[source_code]
+    #[label]
+    pub span: InputSourceSpan,
+}
+
+impl RuntimeWarning {
+    pub fn new(kind: RuntimeWarningKind, span: InputSourceSpan) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Display for RuntimeWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+impl Error for RuntimeWarning {}
+
+impl SourceDiagnostic for RuntimeWarning {
+    fn span(&self) -> &InputSourceSpan {
+        &self.span
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeWarningKind {
+    /// A relative path was used in a source which has no base directory, so it was resolved
+    /// against the current directory instead - which is unlikely to be what was meant.
+    NoBaseDirectory { path: String, resolved: PathBuf },
+
+    /// Two numbers were separated by only a comma, like `10,5`, which might have been meant as one
+    /// number with a comma as its decimal separator.
+    PossibleDecimalComma { whole: String, fraction: String },
+
+    /// A `difference` took away everything from a first child which wasn't empty, which is more
+    /// likely a mistake with the cutters than something meant on purpose.
+    DifferenceRemovedEverything,
+}
+
+impl Display for RuntimeWarningKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeWarningKind::NoBaseDirectory { path, resolved } =>
+                write!(f, "this source has no directory to resolve \"{path}\" against, so it was resolved against the current directory, as \"{}\" - save the file first", resolved.display()),
+            RuntimeWarningKind::PossibleDecimalComma { whole, fraction } =>
+                write!(f, "\"{whole},{fraction}\" is two numbers, {whole} and {fraction} - if you meant one number, write \"{whole}.{fraction}\" instead, or put a space after the comma to keep them separate"),
+            RuntimeWarningKind::DifferenceRemovedEverything =>
+                write!(f, "this difference removed everything from its first child - to cut flush with a surface, make the cutter slightly larger than the surface so that their faces don't coincide, or give it a `tolerance`"),
+        }
+    }
+}
+
+impl Interpreter {
+    /// Everything suspicious noticed so far, in the order it was noticed.
+    pub fn warnings(&self) -> &[RuntimeWarning] {
+        &self.warnings
+    }
+
+    pub(crate) fn warn(&mut self, kind: RuntimeWarningKind, span: InputSourceSpan) {
+        self.warnings.push(RuntimeWarning::new(kind, span));
+    }
+
+    /// Choose whether to warn about numbers which look like they use a comma as a decimal
+    /// separator, like `10,5`. Enabled by default.
+    pub fn set_locale_hints(&mut self, enabled: bool) {
+        self.locale_hints = enabled;
+    }
+
+    /// Warn about numbers in a list of arguments or vector items which look like they use a comma
+    /// as a decimal separator, if enabled.
+    pub(crate) fn warn_decimal_commas(&mut self, items: &[Node]) {
+        if !self.locale_hints {
+            return;
+        }
+
+        for hint in decimal_comma_hints(items) {
+            // Code in a loop or a module would otherwise warn every time it runs
+            if self.warnings.iter().any(|warning| warning.span == hint.span) {
+                continue;
+            }
+            self.warn(RuntimeWarningKind::PossibleDecimalComma { whole: hint.whole, fraction: hint.fraction }, hint.span);
+        }
+    }
+}
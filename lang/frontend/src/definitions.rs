@@ -0,0 +1,117 @@
+use crate::{InputSourceSpan, Node, NodeKind};
+
+/// A module, operator or function defined by a model, which a host application could describe to the user
+/// (for example, when hovering over a call).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelDefinition {
+    pub name: String,
+    pub kind: DefinitionKind,
+
+    /// The names of all parameters, required ones first.
+    pub parameters: Vec<String>,
+
+    /// The doc comments directly before the definition, one line each.
+    pub doc: Option<String>,
+
+    pub span: InputSourceSpan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Module,
+    Operator,
+    Function,
+}
+
+/// Find the modules, operators and functions defined at the top level of a model.
+pub fn extract_definitions(nodes: &[Node]) -> Vec<ModelDefinition> {
+    nodes.iter()
+        .filter_map(|node| {
+            let (kind, name, parameters, doc) = match &node.kind {
+                NodeKind::ModuleDefinition { name, parameters, doc, .. } => (DefinitionKind::Module, name, parameters, doc),
+                NodeKind::OperatorDefinition { name, parameters, doc, .. } => (DefinitionKind::Operator, name, parameters, doc),
+                NodeKind::FunctionDefinition { name, parameters, doc, .. } => (DefinitionKind::Function, name, parameters, doc),
+                _ => return None,
+            };
+
+            Some(ModelDefinition {
+                name: name.clone(),
+                kind,
+                parameters: parameters.required.iter()
+                    .chain(parameters.optional.iter().map(|(name, _)| name))
+                    .cloned()
+                    .collect(),
+                doc: doc.clone(),
+                span: node.span.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{DefinitionKind, InputSource, Parser, extract_definitions, format_statements, tokenize};
+
+    fn definitions(code: &str) -> Vec<(String, DefinitionKind, Vec<String>, Option<String>)> {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+        let mut parser = Parser::new(source.clone(), tokens);
+        let stmts = parser.parse_statements();
+        assert_eq!(parser.errors, vec![]);
+
+        extract_definitions(&stmts).into_iter()
+            .map(|definition| (definition.name, definition.kind, definition.parameters, definition.doc))
+            .collect()
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let definitions = definitions("
+            /// A peg to hold the lid on.
+            ///
+            /// Taller pegs hold better.
+            module peg(radius, height = 5) { cylinder(radius, height); }
+
+            //! Moves things up.
+            operator raise() { translate([0, 0, 1]) children(); }
+
+            /// Twice as much.
+            function double(x) = x * 2;
+        ");
+        assert_eq!(definitions, vec![
+            (
+                "peg".to_owned(), DefinitionKind::Module, vec!["radius".to_owned(), "height".to_owned()],
+                Some("A peg to hold the lid on.\n\nTaller pegs hold better.".to_owned()),
+            ),
+            ("raise".to_owned(), DefinitionKind::Operator, vec![], Some("Moves things up.".to_owned())),
+            ("double".to_owned(), DefinitionKind::Function, vec!["x".to_owned()], Some("Twice as much.".to_owned())),
+        ]);
+    }
+
+    #[test]
+    fn test_ordinary_comments_not_captured() {
+        let definitions = definitions("
+            // Just a comment
+            module a() {}
+
+            /// Not for this definition
+            x = 1;
+            module b() {}
+
+            //// Divider
+            module c() {}
+        ");
+        assert!(definitions.iter().all(|(_, _, _, doc)| doc.is_none()), "{definitions:?}");
+    }
+
+    #[test]
+    fn test_format_doc_comments() {
+        let source = Rc::new(InputSource::new_string("///  Indented\n///\n/// Text\nmodule m() {}".to_owned()));
+        let (tokens, _) = tokenize(source.clone());
+        let stmts = Parser::new(source.clone(), tokens).parse_statements();
+        assert_eq!(format_statements(&stmts), "///  Indented\n///\n/// Text\nmodule m() {}\n");
+    }
+}
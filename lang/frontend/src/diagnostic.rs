@@ -0,0 +1,175 @@
+use std::{fmt::Display, ops::Range};
+
+use crate::{InputSourceSpan, ParseError, TokenizeError, openscad::OpenScadError};
+
+/// An error or warning about a particular part of the source, which can be rendered without miette
+/// using [`render_plain`].
+pub trait SourceDiagnostic: Display {
+    fn span(&self) -> &InputSourceSpan;
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A diagnostic broken down into the pieces needed to display it, for hosts which render errors
+/// themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainDiagnostic {
+    pub message: String,
+    pub severity: Severity,
+
+    /// The name of the source the diagnostic is in, like its path.
+    pub file: String,
+
+    /// The line which the diagnostic starts on, counting from 1.
+    pub line: usize,
+
+    /// The column which the diagnostic starts on, counting from 1.
+    pub column: usize,
+
+    /// The full text of [`Self::line`], without its line ending.
+    pub line_text: String,
+
+    /// The characters of [`Self::line_text`] to highlight, counting from 0.
+    ///
+    /// A diagnostic covering several lines is cut off at the end of the first. This always covers
+    /// at least one character, so that zero-length diagnostics (like the end of the file) still
+    /// show where they are, even if that's just past the end of the line.
+    pub underline_range: Range<usize>,
+}
+
+impl PlainDiagnostic {
+    /// A one-line description, like `model.yascad:3:5: undefined identifier "x"`, in the format
+    /// used by compilers so that it can be read by other tools.
+    pub fn short(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => "",
+            Severity::Warning => "warning: ",
+        };
+        format!("{}:{}:{}: {severity}{}", self.file, self.line, self.column, self.message)
+    }
+}
+
+/// Work out where a diagnostic is, for displaying it.
+pub fn render_plain(diagnostic: &dyn SourceDiagnostic) -> PlainDiagnostic {
+    let span = diagnostic.span();
+    let chars = span.source.content().chars().collect::<Vec<_>>();
+    let start = span.start.min(chars.len());
+
+    let line_start = chars[..start].iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1);
+    let line_end = chars[start..].iter().position(|c| *c == '\n').map_or(chars.len(), |i| start + i);
+    let line_text = chars[line_start..line_end].iter()
+        .collect::<String>()
+        .trim_end_matches('\r')
+        .to_owned();
+
+    let underline_start = start - line_start;
+    let underline_end = (span.start + span.length).min(line_end) - line_start;
+
+    PlainDiagnostic {
+        message: diagnostic.to_string(),
+        severity: diagnostic.severity(),
+        file: span.source.origin().name(),
+        line: chars[..line_start].iter().filter(|c| **c == '\n').count() + 1,
+        column: underline_start + 1,
+        line_text,
+        underline_range: underline_start..underline_end.max(underline_start + 1),
+    }
+}
+
+impl SourceDiagnostic for TokenizeError {
+    fn span(&self) -> &InputSourceSpan {
+        &self.span
+    }
+}
+
+impl SourceDiagnostic for ParseError {
+    fn span(&self) -> &InputSourceSpan {
+        &self.span
+    }
+}
+
+impl SourceDiagnostic for OpenScadError {
+    fn span(&self) -> &InputSourceSpan {
+        &self.span
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, InputSourceSpan, PlainDiagnostic, Severity, SourceDiagnostic, render_plain};
+
+    struct TestDiagnostic(InputSourceSpan);
+
+    impl std::fmt::Display for TestDiagnostic {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "something's wrong")
+        }
+    }
+
+    impl SourceDiagnostic for TestDiagnostic {
+        fn span(&self) -> &InputSourceSpan {
+            &self.0
+        }
+    }
+
+    fn render(code: &str, start: usize, length: usize) -> PlainDiagnostic {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        render_plain(&TestDiagnostic(source.span(start, length)))
+    }
+
+    #[test]
+    fn test_render_plain() {
+        let code = "x = 1;\ny = zz + 2;\n";
+        let diagnostic = render(code, code.find("zz").unwrap(), 2);
+        assert_eq!(diagnostic, PlainDiagnostic {
+            message: "something's wrong".to_owned(),
+            severity: Severity::Error,
+            file: "<input>".to_owned(),
+            line: 2,
+            column: 5,
+            line_text: "y = zz + 2;".to_owned(),
+            underline_range: 4..6,
+        });
+        assert_eq!(diagnostic.short(), "<input>:2:5: something's wrong");
+    }
+
+    #[test]
+    fn test_render_plain_multi_line() {
+        // Only the first line is underlined
+        let code = "a = 1;\ntranslate([1, 2, 3]) {\n    cube(1);\n}\n";
+        let diagnostic = render(code, code.find("translate").unwrap(), 34);
+        assert_eq!((diagnostic.line, diagnostic.column), (2, 1));
+        assert_eq!(diagnostic.line_text, "translate([1, 2, 3]) {");
+        assert_eq!(diagnostic.underline_range, 0..22);
+
+        // Windows line endings aren't part of the line
+        let diagnostic = render("a = 1;\r\nb = 2;\r\n", 4, 10);
+        assert_eq!(diagnostic.line_text, "a = 1;");
+        assert_eq!(diagnostic.underline_range, 4..7);
+    }
+
+    #[test]
+    fn test_render_plain_end_of_file() {
+        let code = "cube(10)";
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let diagnostic = render_plain(&TestDiagnostic(source.eof_span()));
+        assert_eq!((diagnostic.line, diagnostic.column), (1, 9));
+        assert_eq!(diagnostic.line_text, "cube(10)");
+        assert_eq!(diagnostic.underline_range, 8..9);
+
+        // Counting is by character, like spans are
+        let diagnostic = render("ü = 1; ü2", 7, 2);
+        assert_eq!(diagnostic.column, 8);
+        assert_eq!(diagnostic.underline_range, 7..9);
+    }
+}
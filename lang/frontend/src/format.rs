@@ -0,0 +1,352 @@
+use std::rc::Rc;
+
+use crate::{Arguments, BinaryOperator, InputSource, Node, NodeKind, Parameters, Parser, Trivia, tokenize_with_trivia};
+
+const INDENT: &str = "    ";
+
+/// Format a list of statements back into yascad source code.
+///
+/// The output is not necessarily formatted the same way as the source the nodes were parsed from,
+/// since the nodes don't retain any formatting information. It will parse back into equivalent
+/// nodes (although their spans will differ).
+/// 
+/// Comments attached to statements are kept, each on its own line. Comments within an expression
+/// or an empty block aren't attached to anything, so they're lost.
+pub fn format_statements(nodes: &[Node]) -> String {
+    let mut result = String::new();
+
+    for (i, node) in nodes.iter().enumerate() {
+        // Separate definitions from their surroundings with a blank line
+        if i > 0 && (is_definition(node) || is_definition(&nodes[i - 1])) {
+            result.push('\n');
+        }
+
+        result.push_str(&format_commented_statement(node, 0));
+        result.push('\n');
+    }
+
+    result
+}
+
+/// Parse and format source code with [`format_statements`], keeping its comments.
+///
+/// Returns [`None`] if the source doesn't tokenize or parse.
+pub fn format_source(source: Rc<InputSource>) -> Option<String> {
+    let (tokens, trailing_trivia, errors) = tokenize_with_trivia(source.clone());
+    if !errors.is_empty() {
+        return None;
+    }
+
+    let mut parser = Parser::new(source, tokens);
+    let mut nodes = parser.parse_statements();
+    if !parser.errors.is_empty() {
+        return None;
+    }
+
+    // Comments after the last statement have no token to be attached to
+    let trailing_comments = trailing_trivia.iter().filter_map(Trivia::comment_text);
+    match nodes.last_mut() {
+        Some(last) => {
+            last.trailing_comments.extend(trailing_comments);
+            Some(format_statements(&nodes))
+        }
+        None => Some(trailing_comments.map(|comment| comment + "\n").collect()),
+    }
+}
+
+fn is_definition(node: &Node) -> bool {
+    matches!(node.kind, NodeKind::OperatorDefinition { .. } | NodeKind::ModuleDefinition { .. } | NodeKind::FunctionDefinition { .. })
+}
+
+/// Format a single statement, including its terminator, without any leading indentation.
+///
+/// If the statement spans multiple lines, the subsequent lines are indented to `indent`.
+fn format_statement(node: &Node, indent: usize) -> String {
+    match &node.kind {
+        NodeKind::OperatorDefinition { name, parameters, body, doc } =>
+            format!("{}operator {name}({}) {}", format_doc(doc.as_deref(), indent), format_parameters(parameters, indent), format_block(body, indent)),
+        NodeKind::ModuleDefinition { name, parameters, body, doc } =>
+            format!("{}module {name}({}) {}", format_doc(doc.as_deref(), indent), format_parameters(parameters, indent), format_block(body, indent)),
+        NodeKind::FunctionDefinition { name, parameters, body, doc } =>
+            format!("{}function {name}({}) = {};", format_doc(doc.as_deref(), indent), format_parameters(parameters, indent), format_expression(body, indent)),
+
+        NodeKind::ForLoop { loop_variable, loop_source, body } =>
+            format!("for ({loop_variable} = {}) {}", format_expression(loop_source, indent), format_block(body, indent)),
+
+        NodeKind::IfConditional { condition, true_body, false_body } => {
+            let mut result = format!("if ({}) {}", format_expression(condition, indent), format_block(true_body, indent));
+            match false_body.as_deref() {
+                Some([else_if @ Node { kind: NodeKind::IfConditional { .. }, .. }]) => {
+                    result.push_str(" else ");
+                    result.push_str(&format_statement(else_if, indent));
+                },
+                Some(false_body) => {
+                    result.push_str(" else ");
+                    result.push_str(&format_block(false_body, indent));
+                },
+                None => {},
+            }
+            result
+        },
+
+        NodeKind::UnitsDeclaration { unit } => format!("units {unit};"),
+        NodeKind::Include { kind, path } => format!("{} {};", kind.keyword(), format_string(path)),
+
+        NodeKind::Binding { name, value } =>
+            terminate(format!("{name} = {}", format_expression(value, indent))),
+        NodeKind::Reassignment { name, value } =>
+            terminate(format!("set {name} = {}", format_expression(value, indent))),
+
+        _ => terminate(format_expression(node, indent)),
+    }
+}
+
+/// Like [`format_statement`], with the statement's comments on the lines around it.
+fn format_commented_statement(node: &Node, indent: usize) -> String {
+    let mut result = String::new();
+    for comment in &node.comments {
+        result.push_str(comment);
+        result.push('\n');
+        result.push_str(&INDENT.repeat(indent));
+    }
+    result.push_str(&format_statement(node, indent));
+    for comment in &node.trailing_comments {
+        result.push('\n');
+        result.push_str(&INDENT.repeat(indent));
+        result.push_str(comment);
+    }
+    result
+}
+
+/// Add a semicolon to an expression statement, unless it ends with a brace.
+fn terminate(mut statement: String) -> String {
+    if !statement.ends_with('}') {
+        statement.push(';');
+    }
+    statement
+}
+
+/// Format doc comments to go before a definition, each followed by the indentation for the next
+/// line.
+fn format_doc(doc: Option<&str>, indent: usize) -> String {
+    let Some(doc) = doc
+    else { return String::new() };
+
+    doc.split('\n')
+        .map(|line| if line.is_empty() { "///".to_owned() } else { format!("/// {line}") })
+        .map(|line| format!("{line}\n{}", INDENT.repeat(indent)))
+        .collect()
+}
+
+fn format_block(body: &[Node], indent: usize) -> String {
+    if body.is_empty() {
+        return "{}".to_owned();
+    }
+
+    let mut result = "{\n".to_owned();
+    for node in body {
+        result.push_str(&INDENT.repeat(indent + 1));
+        result.push_str(&format_commented_statement(node, indent + 1));
+        result.push('\n');
+    }
+    result.push_str(&INDENT.repeat(indent));
+    result.push('}');
+    result
+}
+
+fn format_expression(node: &Node, indent: usize) -> String {
+    match &node.kind {
+        NodeKind::Identifier(id) => id.to_owned(),
+        NodeKind::NullLiteral => "null".to_owned(),
+        NodeKind::NumberLiteral(num) => num.to_string(),
+        NodeKind::BooleanLiteral(bool) => bool.to_string(),
+        NodeKind::StringLiteral(string) => format_string(string),
+        NodeKind::VectorLiteral(items) => {
+            let items = items.iter()
+                .map(|item| format_expression(item, indent))
+                .collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        },
+        NodeKind::VectorRangeLiteral { start, step: None, end } =>
+            format!("[{}:{}]", format_expression(start, indent), format_expression(end, indent)),
+        NodeKind::VectorRangeLiteral { start, step: Some(step), end } =>
+            format!("[{}:{}:{}]", format_expression(start, indent), format_expression(step, indent), format_expression(end, indent)),
+        NodeKind::ItReference => "it".to_owned(),
+
+        NodeKind::OperatorApplication { name, arguments, children } => {
+            let application = format!("{name}({})", format_arguments(arguments, indent));
+
+            // A single expression child can follow the operator directly, otherwise use braces
+            match &children[..] {
+                [child @ Node { kind: NodeKind::Call { .. } | NodeKind::OperatorApplication { .. }, .. }] =>
+                    format!("{application} {}", format_expression(child, indent)),
+                _ =>
+                    format!("{application} {}", format_block(children, indent)),
+            }
+        },
+        NodeKind::Call { name, arguments } =>
+            format!("{name}({})", format_arguments(arguments, indent)),
+
+        NodeKind::FieldAccess { value, field } =>
+            format!("{}.{field}", format_expression(value, indent)),
+
+        NodeKind::BinaryOperation { left, right, op } => {
+            // Operators are left-associative, so the right operand needs parentheses if it binds
+            // equally tightly
+            let precedence = operator_precedence(*op);
+            let left = format_operand(left, indent, |p| p < precedence);
+            let right = format_operand(right, indent, |p| p <= precedence);
+
+            format!("{left} {} {right}", operator_symbol(*op))
+        },
+        NodeKind::UnaryNegate(value) =>
+            format!("-{}", format_operand(value, indent, |_| true)),
+        NodeKind::UnaryNot(value) =>
+            format!("!{}", format_operand(value, indent, |_| true)),
+
+        // The values don't need parentheses, since `?` and `:` both finish the expression before
+        NodeKind::TernaryConditional { condition, true_value, false_value } =>
+            format!(
+                "{} ? {} : {}",
+                format_operand(condition, indent, |_| false),
+                format_expression(true_value, indent),
+                format_expression(false_value, indent),
+            ),
+
+        // Statements can't appear inside expressions when parsed, but format them somehow anyway
+        _ => format_statement(node, indent),
+    }
+}
+
+/// Format an operand of an operator, wrapping it in parentheses if it's a binary operation whose
+/// precedence satisfies `needs_parentheses`, or a ternary, which binds less tightly than any
+/// operator.
+fn format_operand(node: &Node, indent: usize, needs_parentheses: impl Fn(u8) -> bool) -> String {
+    let formatted = format_expression(node, indent);
+    match &node.kind {
+        NodeKind::BinaryOperation { op, .. } if needs_parentheses(operator_precedence(*op)) =>
+            format!("({formatted})"),
+        NodeKind::TernaryConditional { .. } => format!("({formatted})"),
+        _ => formatted,
+    }
+}
+
+/// How tightly an operator binds - higher numbers bind more tightly.
+fn operator_precedence(op: BinaryOperator) -> u8 {
+    match op {
+        BinaryOperator::Or => 0,
+        BinaryOperator::And => 1,
+
+        BinaryOperator::Equals
+        | BinaryOperator::LessThan
+        | BinaryOperator::LessThanOrEquals
+        | BinaryOperator::GreaterThan
+        | BinaryOperator::GreaterThanOrEquals => 2,
+
+        BinaryOperator::Add | BinaryOperator::Subtract => 3,
+        BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 4,
+    }
+}
+
+fn operator_symbol(op: BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::Modulo => "%",
+        BinaryOperator::Equals => "==",
+        BinaryOperator::LessThan => "<",
+        BinaryOperator::LessThanOrEquals => "<=",
+        BinaryOperator::GreaterThan => ">",
+        BinaryOperator::GreaterThanOrEquals => ">=",
+        BinaryOperator::And => "&&",
+        BinaryOperator::Or => "||",
+    }
+}
+
+/// Quote a string, escaping it so that it tokenizes back to the same value.
+fn format_string(string: &str) -> String {
+    let mut result = "\"".to_owned();
+    for char in string.chars() {
+        match char {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(char),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn format_arguments(arguments: &Arguments, indent: usize) -> String {
+    arguments.positional.iter()
+        .map(|value| format_expression(value, indent))
+        .chain(arguments.named.iter()
+            .map(|(name, value)| format!("{name} = {}", format_expression(value, indent))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_parameters(parameters: &Parameters, indent: usize) -> String {
+    parameters.required.iter()
+        .cloned()
+        .chain(parameters.optional.iter()
+            .map(|(name, default)| format!("{name} = {}", format_expression(default, indent))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, format_source};
+
+    fn format(code: &str) -> String {
+        format_source(Rc::new(InputSource::new_string(code.to_owned()))).unwrap()
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let code = "\
+            // Settings\n\
+            size = 10; /* wide */ height = 2;\n\
+            \n\
+            /* A simple\n   plate */\n\
+            /// The plate\n\
+            module plate() {\n\
+            // Base\n\
+            cube([size, size, height]);\n\
+                // TODO: holes\n\
+            }\n\
+            plate(); // Done\n\
+            // The end\n";
+        let formatted = "\
+            // Settings\n\
+            size = 10;\n\
+            /* wide */\n\
+            height = 2;\n\
+            \n\
+            /* A simple\n   plate */\n\
+            /// The plate\n\
+            module plate() {\n\
+            \x20   // Base\n\
+            \x20   cube([size, size, height]);\n\
+            \x20   // TODO: holes\n\
+            }\n\
+            \n\
+            plate();\n\
+            // Done\n\
+            // The end\n";
+        assert_eq!(format(code), formatted);
+
+        // Formatting is stable once the comments are on their own lines
+        assert_eq!(format(formatted), formatted);
+
+        assert_eq!(format("// Nothing yet"), "// Nothing yet\n");
+        assert_eq!(format("if (true) {\n} // Empty\nx = 1;"), "if (true) {}\n// Empty\nx = 1;\n");
+    }
+}
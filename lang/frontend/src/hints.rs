@@ -0,0 +1,84 @@
+use crate::{InputSourceSpan, Node, NodeKind};
+
+/// A pair of adjacent numbers in a list which look like they were meant to be one number, written
+/// with a comma as the decimal separator - like `10,5` for 10.5.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecimalCommaHint {
+    /// The number before the comma, as written.
+    pub whole: String,
+
+    /// The number after the comma, as written.
+    pub fraction: String,
+
+    /// Covers both numbers and the comma between them.
+    pub span: InputSourceSpan,
+}
+
+/// Find numbers in a list of arguments or vector items which are separated by only a comma, with
+/// no space either side, like `10,5`.
+///
+/// Numbers which already have a decimal point aren't suspicious, since they can't also be using a
+/// comma for one.
+pub fn decimal_comma_hints(items: &[Node]) -> Vec<DecimalCommaHint> {
+    items.windows(2)
+        .filter_map(|pair| {
+            let [first, second] = pair
+            else { unreachable!() };
+            if !matches!(first.kind, NodeKind::NumberLiteral(_)) || !matches!(second.kind, NodeKind::NumberLiteral(_)) {
+                return None;
+            }
+
+            // Only a single comma between them
+            if second.span.start != first.span.start + first.span.length + 1 {
+                return None;
+            }
+
+            let whole = span_text(&first.span);
+            let fraction = span_text(&second.span);
+            if whole.contains('.') || fraction.contains('.') {
+                return None;
+            }
+
+            Some(DecimalCommaHint { whole, fraction, span: first.span.union_with(std::slice::from_ref(&second.span)) })
+        })
+        .collect()
+}
+
+fn span_text(span: &InputSourceSpan) -> String {
+    span.source.content().chars().skip(span.start).take(span.length).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, NodeKind, Parser, decimal_comma_hints, tokenize};
+
+    fn hints_in_call(code: &str) -> Vec<(String, String)> {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+        let mut parser = Parser::new(source.clone(), tokens);
+        let stmts = parser.parse_statements();
+        assert_eq!(parser.errors, vec![]);
+
+        let NodeKind::Call { arguments, .. } = &stmts[0].kind
+        else { panic!("expected call") };
+        decimal_comma_hints(&arguments.positional).into_iter()
+            .map(|hint| (hint.whole, hint.fraction))
+            .collect()
+    }
+
+    #[test]
+    fn test_decimal_comma_hints() {
+        assert_eq!(hints_in_call("cylinder(10,5);"), vec![("10".to_owned(), "5".to_owned())]);
+        assert_eq!(hints_in_call("sphere(2, 10,5);"), vec![("10".to_owned(), "5".to_owned())]);
+
+        // Spaced out, or already decimal, is clearly intentional
+        assert_eq!(hints_in_call("cylinder(10, 5);"), vec![]);
+        assert_eq!(hints_in_call("cylinder(10 ,5);"), vec![]);
+        assert_eq!(hints_in_call("cylinder(10.5,2);"), vec![]);
+        assert_eq!(hints_in_call("cylinder(10,2.5);"), vec![]);
+        assert_eq!(hints_in_call("cylinder(x,5);"), vec![]);
+    }
+}
@@ -6,10 +6,39 @@ pub use tokenize::*;
 mod parse;
 pub use parse::*;
 
+mod format;
+pub use format::*;
+
+mod parameters;
+pub use parameters::*;
+
+mod definitions;
+pub use definitions::*;
+
+mod rebuild;
+pub use rebuild::*;
+
+mod hints;
+pub use hints::*;
+
+mod lookup;
+pub use lookup::*;
+
+mod source_file;
+pub use source_file::*;
+
+mod diagnostic;
+pub use diagnostic::*;
+
+pub mod openscad;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct InputSource {
     content: String,
     origin: InputSourceOrigin,
+
+    /// The directory which relative paths in this source are resolved against, if there is one.
+    base_dir: Option<PathBuf>,
 }
 
 impl InputSource {
@@ -17,18 +46,61 @@ impl InputSource {
         Self {
             content,
             origin: InputSourceOrigin::new_string(),
+            base_dir: None,
+        }
+    }
+
+    /// Like [`Self::new_string`], but relative paths are resolved against `base_dir`, like they
+    /// would be for a file in that directory. Useful for editors, which build from an in-memory
+    /// copy of a file.
+    pub fn new_string_with_base(content: String, base_dir: impl AsRef<Path>) -> Self {
+        Self {
+            base_dir: Some(base_dir.as_ref().to_owned()),
+            ..Self::new_string(content)
         }
     }
 
+    /// The source of definitions which are loaded before every model, so that errors in it are
+    /// reported as being in the prelude, rather than in the model.
+    pub fn new_prelude(content: String) -> Self {
+        Self {
+            content,
+            origin: InputSourceOrigin::Prelude,
+            base_dir: None,
+        }
+    }
+
+    /// Read a source file. Relative paths within it are resolved against the directory containing
+    /// it.
     pub fn new_file(path: impl AsRef<Path>) -> Result<Self, io::Error> {
         let path_buf = path.as_ref().to_owned();
         let content = std::fs::read_to_string(path)?;
+
+        // A bare file name has an empty parent, meaning the current directory
+        let base_dir = path_buf.parent()
+            .map(|parent| if parent.as_os_str().is_empty() { Path::new(".") } else { parent })
+            .map(Path::to_owned);
+
         Ok(Self {
             content,
             origin: InputSourceOrigin::File(path_buf),
+            base_dir,
         })
     } 
 
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn origin(&self) -> &InputSourceOrigin {
+        &self.origin
+    }
+
+    /// The directory which relative paths in this source are resolved against, if known.
+    pub fn base_dir(&self) -> Option<&Path> {
+        self.base_dir.as_deref()
+    }
+
     pub fn span(self: &Rc<Self>, start: usize, length: usize) -> InputSourceSpan {
         InputSourceSpan::new(self.clone(), start, length)
     }
@@ -42,6 +114,7 @@ impl InputSource {
 pub enum InputSourceOrigin {
     String,
     File(PathBuf),
+    Prelude,
 }
 
 impl InputSourceOrigin {
@@ -56,6 +129,7 @@ impl InputSourceOrigin {
     pub fn name(&self) -> String {
         match self {
             InputSourceOrigin::String => "<input>".to_owned(),
+            InputSourceOrigin::Prelude => "<prelude>".to_owned(),
             InputSourceOrigin::File(path) => path.to_string_lossy().to_string(),
         }
     }
@@ -83,6 +157,30 @@ impl InputSourceSpan {
         self.start + self.length - 1
     }
 
+    /// The line number (starting from 1) on which this span begins.
+    pub fn start_line(&self) -> usize {
+        self.source.content.chars()
+            .take(self.start)
+            .filter(|c| *c == '\n')
+            .count() + 1
+    }
+
+    /// The column number (starting from 1) on which this span begins.
+    pub fn start_column(&self) -> usize {
+        self.source.content.chars()
+            .take(self.start)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .take_while(|c| *c != '\n')
+            .count() + 1
+    }
+
+    /// The source text which the span covers.
+    pub fn text(&self) -> String {
+        self.source.content.chars().skip(self.start).take(self.length).collect()
+    }
+
     /// Create a new span which covers all of the given spans.
     /// 
     /// Panics if some spans have different sources.
@@ -0,0 +1,188 @@
+use crate::{Node, NodeKind};
+
+/// A node found by [`find_node_at`], along with the nodes it's nested within.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodePath<'a> {
+    /// Every node from a top-level statement down to the one which was found, outermost first.
+    pub nodes: Vec<&'a Node>,
+}
+
+impl<'a> NodePath<'a> {
+    /// The node which was found.
+    pub fn node(&self) -> &'a Node {
+        self.nodes.last().expect("node path is empty")
+    }
+
+    /// The nodes which contain [`Self::node`], outermost first.
+    pub fn ancestors(&self) -> &[&'a Node] {
+        &self.nodes[..self.nodes.len() - 1]
+    }
+}
+
+impl Node {
+    /// The nodes directly nested within this one, in source order.
+    pub fn child_nodes(&self) -> Vec<&Node> {
+        match &self.kind {
+            NodeKind::Identifier(_)
+            | NodeKind::NullLiteral
+            | NodeKind::NumberLiteral(_)
+            | NodeKind::BooleanLiteral(_)
+            | NodeKind::StringLiteral(_)
+            | NodeKind::ItReference
+            | NodeKind::UnitsDeclaration { .. }
+            | NodeKind::Include { .. } => vec![],
+
+            NodeKind::VectorLiteral(items) => items.iter().collect(),
+            NodeKind::VectorRangeLiteral { start, step, end } => [Some(start), step.as_ref(), Some(end)].into_iter().flatten().map(|node| &**node).collect(),
+
+            NodeKind::OperatorApplication { arguments, children, .. } =>
+                arguments.positional.iter()
+                    .chain(arguments.named.iter().map(|(_, arg)| arg))
+                    .chain(children)
+                    .collect(),
+            NodeKind::Call { arguments, .. } =>
+                arguments.positional.iter()
+                    .chain(arguments.named.iter().map(|(_, arg)| arg))
+                    .collect(),
+
+            NodeKind::Binding { value, .. } | NodeKind::Reassignment { value, .. } => vec![value],
+            NodeKind::FieldAccess { value, .. } => vec![value],
+            NodeKind::BinaryOperation { left, right, .. } => vec![left, right],
+            NodeKind::UnaryNegate(value) | NodeKind::UnaryNot(value) => vec![value],
+            NodeKind::TernaryConditional { condition, true_value, false_value } => vec![condition, true_value, false_value],
+
+            NodeKind::OperatorDefinition { parameters, body, .. }
+            | NodeKind::ModuleDefinition { parameters, body, .. } =>
+                parameters.optional.iter()
+                    .map(|(_, default)| default)
+                    .chain(body)
+                    .collect(),
+            NodeKind::FunctionDefinition { parameters, body, .. } =>
+                parameters.optional.iter()
+                    .map(|(_, default)| default)
+                    .chain(std::iter::once(body.as_ref()))
+                    .collect(),
+
+            NodeKind::ForLoop { loop_source, body, .. } =>
+                std::iter::once(loop_source.as_ref()).chain(body).collect(),
+            NodeKind::IfConditional { condition, true_body, false_body } =>
+                std::iter::once(condition.as_ref())
+                    .chain(true_body)
+                    .chain(false_body.iter().flatten())
+                    .collect(),
+        }
+    }
+}
+
+/// Find the innermost node whose span contains `offset`, for tooling like hover information.
+///
+/// The offset is counted in characters from the start of the source, like spans are.
+///
+/// Some nodes' spans don't cover their children - an operator application's span is only the
+/// operator call, and an `if` statement's ends after its condition - so children are always searched,
+/// even if their parent doesn't contain the offset.
+pub fn find_node_at(stmts: &[Node], offset: usize) -> Option<NodePath<'_>> {
+    let mut path = vec![];
+    let mut innermost = None;
+    search(stmts.iter(), offset, &mut path, &mut innermost);
+    innermost.map(|nodes| NodePath { nodes })
+}
+
+fn search<'a>(nodes: impl IntoIterator<Item = &'a Node>, offset: usize, path: &mut Vec<&'a Node>, innermost: &mut Option<Vec<&'a Node>>) {
+    for node in nodes {
+        path.push(node);
+
+        let contains = node.span.start <= offset && offset < node.span.start + node.span.length;
+        if contains && innermost.as_ref().is_none_or(|innermost| path.len() > innermost.len()) {
+            *innermost = Some(path.clone());
+        }
+        search(node.child_nodes(), offset, path, innermost);
+
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, Node, NodeKind, Parser, find_node_at, tokenize};
+
+    const SAMPLE: &str = "\
+module peg(radius, height = 5) {
+    cylinder(radius, height);
+}
+
+translate([0, 0, 2]) {
+    if (size > 3) {
+        peg(size / 2);
+    }
+}
+";
+
+    fn parse(code: &str) -> Vec<Node> {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+        let mut parser = Parser::new(source.clone(), tokens);
+        let stmts = parser.parse_statements();
+        assert_eq!(parser.errors, vec![]);
+        stmts
+    }
+
+    /// Describe each node in the path to whatever is at the first occurrence of `needle`.
+    fn path_at(stmts: &[Node], needle: &str) -> Vec<String> {
+        let offset = SAMPLE.find(needle).unwrap();
+        let Some(path) = find_node_at(stmts, offset)
+        else { return vec![] };
+
+        path.nodes.iter()
+            .map(|node| match &node.kind {
+                NodeKind::ModuleDefinition { name, .. } => format!("module {name}"),
+                NodeKind::OperatorApplication { name, .. } => format!("operator {name}"),
+                NodeKind::Call { name, .. } => format!("call {name}"),
+                NodeKind::IfConditional { .. } => "if".to_owned(),
+                NodeKind::BinaryOperation { op, .. } => format!("{op:?}"),
+                NodeKind::Identifier(name) => format!("identifier {name}"),
+                NodeKind::NumberLiteral(n) => format!("number {n}"),
+                NodeKind::VectorLiteral(_) => "vector".to_owned(),
+                kind => panic!("unexpected node {kind:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_node_at() {
+        let stmts = parse(SAMPLE);
+
+        assert_eq!(path_at(&stmts, "height)"), vec!["module peg", "call cylinder", "identifier height"]);
+        assert_eq!(path_at(&stmts, "5)"), vec!["module peg", "number 5"]);
+        assert_eq!(path_at(&stmts, "translate"), vec!["operator translate"]);
+        assert_eq!(path_at(&stmts, "2]"), vec!["operator translate", "vector", "number 2"]);
+
+        // The operator's span doesn't cover its children, and the `if`'s doesn't cover its body,
+        // but they're still found
+        assert_eq!(path_at(&stmts, "size >"), vec!["operator translate", "if", "GreaterThan", "identifier size"]);
+        assert_eq!(path_at(&stmts, "/ 2"), vec!["operator translate", "if", "call peg", "Divide"]);
+        assert_eq!(path_at(&stmts, "peg(size"), vec!["operator translate", "if", "call peg"]);
+    }
+
+    #[test]
+    fn test_find_node_at_nothing() {
+        let stmts = parse(SAMPLE);
+
+        // Whitespace between statements, and past the end
+        assert_eq!(find_node_at(&stmts, SAMPLE.find("\n\ntranslate").unwrap() + 1), None);
+        assert_eq!(find_node_at(&stmts, SAMPLE.len() + 10), None);
+    }
+
+    #[test]
+    fn test_node_path() {
+        let stmts = parse(SAMPLE);
+        let path = find_node_at(&stmts, SAMPLE.find("radius, height);").unwrap()).unwrap();
+
+        assert!(matches!(path.node().kind, NodeKind::Identifier(_)));
+        assert_eq!(path.ancestors().len(), 2);
+        assert!(std::ptr::eq(path.ancestors()[0], &stmts[0]));
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use crate::{Arguments, InputSourceSpan, Node, NodeKind, openscad::{OpenScadError, OpenScadErrorKind, parse::Argument}};
+
+/// Modules which only affect how geometry is displayed, so are converted into a plain union of
+/// their children.
+pub const DISCARDED_MODULES: &[&str] = &["color", "render"];
+
+/// OpenSCAD built-in modules which have no yascad equivalent.
+const UNSUPPORTED_MODULES: &[&str] = &[
+    "sphere", "polygon", "polyhedron", "text", "import", "surface", "projection",
+    "minkowski", "multmatrix", "children", "echo", "assert", "intersection_for",
+    "parent_module",
+];
+
+/// OpenSCAD built-in functions which behave the same as the yascad built-in module with the same
+/// name, so calls to them are kept as they are.
+pub const EQUIVALENT_FUNCTIONS: &[&str] = &[
+    "abs", "sin", "cos", "tan", "acos", "asin", "atan", "atan2", "floor", "round", "ceil", "pow",
+    "sqrt", "min", "max", "len",
+];
+
+/// OpenSCAD built-in functions which have no yascad equivalent.
+pub const UNSUPPORTED_FUNCTIONS: &[&str] = &[
+    "sign", "ln", "log", "exp", "norm", "cross", "concat", "lookup", "rands", "search", "str",
+    "chr", "ord", "version", "version_num", "is_undef", "is_bool", "is_num", "is_string",
+    "is_list", "is_function", "echo", "assert", "let",
+];
+
+/// Convert the instantiation of an OpenSCAD module into the equivalent yascad node.
+///
+/// `children` is [`None`] if the instantiation was terminated with a semicolon, rather than
+/// followed by child statements.
+///
+/// Returns [`None`] if the instantiation has no effect, and so doesn't need to be converted.
+pub fn convert_instantiation(
+    name: String,
+    name_span: InputSourceSpan,
+    arguments: Vec<Argument>,
+    children: Option<Vec<Node>>,
+    span: InputSourceSpan,
+) -> Result<Option<Node>, OpenScadError> {
+    if UNSUPPORTED_MODULES.contains(&name.as_str()) {
+        return Err(OpenScadError::unsupported(
+            format!("the built-in module \"{name}\", which has no yascad equivalent"),
+            name_span,
+        ))
+    }
+
+    if DISCARDED_MODULES.contains(&name.as_str()) {
+        return Ok(children.map(|children| operator("union", vec![], vec![], children, span)))
+    }
+
+    match name.as_str() {
+        "cube" | "square" => {
+            let mut arguments = BoundArguments::bind(&name, &["size", "center"], arguments, &span)?;
+            reject_children(&name, children)?;
+            let center = arguments.take_centering();
+
+            let size = arguments.take_or("size", 1.0, &span);
+            Ok(Some(call(&name, vec![size], center.into_iter().collect(), span)))
+        },
+
+        "circle" => {
+            let mut arguments = BoundArguments::bind(&name, &["r", "d"], arguments, &span)?;
+            reject_children(&name, children)?;
+
+            Ok(Some(match (arguments.take("r"), arguments.take("d")) {
+                (_, Some(d)) => call(&name, vec![], vec![("d", d)], span),
+                (r, None) => {
+                    let r = r.unwrap_or_else(|| number(1.0, &span));
+                    call(&name, vec![r], vec![], span)
+                },
+            }))
+        },
+
+        "cylinder" => {
+            let mut arguments = BoundArguments::bind(&name, &["h", "r1", "r2", "center", "r", "d", "d1", "d2"], arguments, &span)?;
+            reject_children(&name, children)?;
+            let center = arguments.take_centering();
+            for cone_argument in ["r1", "r2", "d1", "d2"] {
+                if let Some(value) = arguments.take(cone_argument) {
+                    return Err(OpenScadError::unsupported("cones (`r1`, `r2`, `d1` and `d2` arguments)", value.span))
+                }
+            }
+
+            let h = arguments.take_or("h", 1.0, &span);
+            Ok(Some(match (arguments.take("r"), arguments.take("d")) {
+                (_, Some(d)) => call(&name, vec![h], [("d", d)].into_iter().chain(center).collect(), span),
+                (r, None) => {
+                    let r = r.unwrap_or_else(|| number(1.0, &span));
+                    call(&name, vec![h, r], center.into_iter().collect(), span)
+                },
+            }))
+        },
+
+        "translate" | "mirror" => {
+            let mut arguments = BoundArguments::bind(&name, &["v"], arguments, &span)?;
+            let v = arguments.take_required("v")?;
+            Ok(children.map(|children| operator(&name, vec![v], vec![], children, span)))
+        },
+
+        "rotate" => {
+            let mut arguments = BoundArguments::bind(&name, &["a", "v"], arguments, &span)?;
+            if let Some(v) = arguments.take("v") {
+                return Err(OpenScadError::unsupported("rotation around an arbitrary axis", v.span))
+            }
+
+            let a = arguments.take_required("a")?;
+            Ok(children.map(|children| operator(&name, vec![a], vec![], children, span)))
+        },
+
+        "scale" => {
+            let mut arguments = BoundArguments::bind(&name, &["v"], arguments, &span)?;
+            let v = arguments.take_required("v")?;
+
+            // The dimensions of the children aren't known, so a uniform scale can't be converted
+            // into a vector of the right length
+            if let NodeKind::NumberLiteral(_) = v.kind {
+                return Err(OpenScadError::unsupported("uniform scale factors - use a vector instead", v.span))
+            }
+
+            Ok(children.map(|children| operator(&name, vec![v], vec![], children, span)))
+        },
+
+        "resize" => {
+            let mut arguments = BoundArguments::bind(&name, &["newsize", "auto", "convexity"], arguments, &span)?;
+            arguments.take("convexity");
+
+            let newsize = arguments.take_required("newsize")?;
+            let auto = arguments.take("auto");
+            if let Some(auto) = &auto && let NodeKind::VectorLiteral(_) = auto.kind {
+                return Err(OpenScadError::unsupported("a vector `auto` argument to `resize`", auto.span.clone()))
+            }
+
+            let named = auto.into_iter().map(|auto| ("auto", auto)).collect();
+            Ok(children.map(|children| operator(&name, vec![newsize], named, children, span)))
+        },
+
+        "union" | "difference" | "intersection" | "hull" => {
+            BoundArguments::bind(&name, &[], arguments, &span)?;
+            Ok(children.map(|children| operator(&name, vec![], vec![], children, span)))
+        },
+
+        "offset" => {
+            let mut arguments = BoundArguments::bind(&name, &["r", "delta", "chamfer"], arguments, &span)?;
+            let positional = arguments.take("r").into_iter().collect();
+            let named = [("delta", arguments.take("delta")), ("chamfer", arguments.take("chamfer"))]
+                .into_iter()
+                .filter_map(|(name, value)| value.map(|value| (name, value)))
+                .collect();
+            Ok(children.map(|children| operator(&name, positional, named, children, span)))
+        },
+
+        "linear_extrude" => {
+            let mut arguments = BoundArguments::bind(&name, &["height", "center", "convexity", "twist", "slices", "scale"], arguments, &span)?;
+            arguments.reject_centering()?;
+            for unsupported_argument in ["twist", "slices", "scale"] {
+                if let Some(value) = arguments.take(unsupported_argument) {
+                    return Err(OpenScadError::unsupported(format!("the `{unsupported_argument}` argument to `linear_extrude`"), value.span))
+                }
+            }
+
+            // Only a rendering hint
+            arguments.take("convexity");
+
+            let height = arguments.take_or("height", 100.0, &span);
+            Ok(children.map(|children| operator(&name, vec![height], vec![], children, span)))
+        },
+
+        "rotate_extrude" => {
+            let mut arguments = BoundArguments::bind(&name, &["angle", "convexity"], arguments, &span)?;
+            arguments.take("convexity");
+
+            let positional = arguments.take("angle").into_iter().collect();
+            Ok(children.map(|children| operator(&name, positional, vec![], children, span)))
+        },
+
+        // Assume anything else is a user-defined module
+        _ => {
+            // Using children needs `children()`, which isn't supported
+            if children.as_ref().is_some_and(|children| !children.is_empty()) {
+                return Err(OpenScadError::unsupported("passing children to user-defined modules", span))
+            }
+
+            Ok(Some(Node::new(NodeKind::Call { name, arguments: to_yascad_arguments(arguments) }, span)))
+        }
+    }
+}
+
+/// Convert arguments as-is, keeping their names.
+pub fn to_yascad_arguments(arguments: Vec<Argument>) -> Arguments {
+    let mut result = Arguments { positional: vec![], named: vec![] };
+    for Argument { name, value } in arguments {
+        match name {
+            Some((name, _)) => result.named.push((name, value)),
+            None => result.positional.push(value),
+        }
+    }
+    result
+}
+
+/// Arguments passed to a built-in module, keyed by the name of the OpenSCAD parameter they were
+/// passed for.
+struct BoundArguments {
+    module: String,
+    values: HashMap<String, Node>,
+
+    /// The span of the whole instantiation, for errors about missing arguments.
+    span: InputSourceSpan,
+}
+
+impl BoundArguments {
+    fn bind(module: &str, parameters: &[&str], arguments: Vec<Argument>, span: &InputSourceSpan) -> Result<Self, OpenScadError> {
+        let mut values = HashMap::new();
+        let mut next_positional = 0;
+
+        for Argument { name, value } in arguments {
+            let name = match name {
+                Some((name, name_span)) => {
+                    if !parameters.contains(&name.as_str()) {
+                        return Err(OpenScadError::new(
+                            OpenScadErrorKind::UnknownArgument { module: module.to_owned(), name },
+                            name_span,
+                        ))
+                    }
+                    name
+                },
+                None => {
+                    let Some(name) = parameters.get(next_positional)
+                    else {
+                        return Err(OpenScadError::new(
+                            OpenScadErrorKind::TooManyArguments { module: module.to_owned(), max: parameters.len() },
+                            value.span,
+                        ))
+                    };
+                    next_positional += 1;
+                    name.to_string()
+                },
+            };
+
+            if values.contains_key(&name) {
+                return Err(OpenScadError::new(OpenScadErrorKind::DuplicateArgument(name), span.clone()))
+            }
+            values.insert(name, value);
+        }
+
+        Ok(Self { module: module.to_owned(), values, span: span.clone() })
+    }
+
+    fn take(&mut self, name: &str) -> Option<Node> {
+        self.values.remove(name)
+    }
+
+    fn take_or(&mut self, name: &str, default: f64, span: &InputSourceSpan) -> Node {
+        self.take(name).unwrap_or_else(|| number(default, span))
+    }
+
+    fn take_required(&mut self, name: &str) -> Result<Node, OpenScadError> {
+        self.take(name).ok_or_else(|| OpenScadError::new(
+            OpenScadErrorKind::MissingArgument { module: self.module.clone(), name: name.to_owned() },
+            self.span.clone(),
+        ))
+    }
+
+    /// yascad primitives are never centred, so `center` is only accepted if it's false.
+    /// Take a `center` argument, as a named argument to pass on. It's dropped if it's `false`,
+    /// since that's the default.
+    fn take_centering(&mut self) -> Option<(&'static str, Node)> {
+        self.take("center")
+            .filter(|value| !matches!(value.kind, NodeKind::BooleanLiteral(false)))
+            .map(|value| ("center", value))
+    }
+
+    fn reject_centering(&mut self) -> Result<(), OpenScadError> {
+        match self.take("center") {
+            None | Some(Node { kind: NodeKind::BooleanLiteral(false), .. }) => Ok(()),
+            Some(value) => Err(OpenScadError::unsupported("`center` arguments", value.span)),
+        }
+    }
+}
+
+fn reject_children(module: &str, children: Option<Vec<Node>>) -> Result<(), OpenScadError> {
+    match children.as_deref() {
+        None | Some([]) => Ok(()),
+        Some([first, ..]) => Err(OpenScadError::new(OpenScadErrorKind::UnexpectedChildren(module.to_owned()), first.span.clone())),
+    }
+}
+
+fn number(value: f64, span: &InputSourceSpan) -> Node {
+    Node::new(NodeKind::NumberLiteral(value), span.clone())
+}
+
+fn call(name: &str, positional: Vec<Node>, named: Vec<(&str, Node)>, span: InputSourceSpan) -> Node {
+    Node::new(
+        NodeKind::Call {
+            name: name.to_owned(),
+            arguments: Arguments {
+                positional,
+                named: named.into_iter().map(|(name, value)| (name.to_owned(), value)).collect(),
+            },
+        },
+        span,
+    )
+}
+
+fn operator(name: &str, positional: Vec<Node>, named: Vec<(&str, Node)>, children: Vec<Node>, span: InputSourceSpan) -> Node {
+    Node::new(
+        NodeKind::OperatorApplication {
+            name: name.to_owned(),
+            arguments: Arguments {
+                positional,
+                named: named.into_iter().map(|(name, value)| (name.to_owned(), value)).collect(),
+            },
+            children,
+        },
+        span,
+    )
+}
@@ -0,0 +1,320 @@
+//! A best-effort translation layer from OpenSCAD source into yascad.
+//!
+//! Only a common subset of OpenSCAD is handled: module definitions, single-expression functions,
+//! `for` and `if`, variables, the primitive modules which yascad has an equivalent for, and the
+//! transformation and boolean operations. Anything outside of this subset is reported as an
+//! [`OpenScadErrorKind::Unsupported`] error, located at the offending construct, rather than being
+//! silently dropped.
+//!
+//! OpenSCAD functions have no direct equivalent, so calls to them are inlined at each call site.
+
+use std::{error::Error, fmt::Display, rc::Rc};
+
+use miette::Diagnostic;
+
+use crate::{InputSource, InputSourceSpan, Node, format_statements};
+
+mod tokenize;
+mod parse;
+mod builtins;
+
+#[derive(Debug, Clone, PartialEq, Diagnostic)]
+pub struct OpenScadError {
+    pub kind: OpenScadErrorKind,
+
+    #[source_code]
+    #[label]
+    pub span: InputSourceSpan,
+}
+
+impl OpenScadError {
+    pub fn new(kind: OpenScadErrorKind, span: InputSourceSpan) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn unsupported(construct: impl Into<String>, span: InputSourceSpan) -> Self {
+        Self::new(OpenScadErrorKind::Unsupported(construct.into()), span)
+    }
+}
+
+impl Display for OpenScadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+impl Error for OpenScadError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpenScadErrorKind {
+    UnexpectedChar(char),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    InvalidNumber,
+
+    /// The construct is valid OpenSCAD, but can't be converted.
+    Unsupported(String),
+
+    UnknownArgument { module: String, name: String },
+    DuplicateArgument(String),
+    TooManyArguments { module: String, max: usize },
+    MissingArgument { module: String, name: String },
+    UnexpectedChildren(String),
+    RecursiveFunction(String),
+}
+
+impl Display for OpenScadErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenScadErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {c}"),
+            OpenScadErrorKind::UnexpectedToken(token) => write!(f, "unexpected {token}"),
+            OpenScadErrorKind::UnexpectedEnd => write!(f, "unexpected end-of-file"),
+            OpenScadErrorKind::InvalidNumber => write!(f, "number could not be parsed, possibly out-of-range?"),
+            OpenScadErrorKind::Unsupported(construct) => write!(f, "unsupported OpenSCAD construct: {construct}"),
+            OpenScadErrorKind::UnknownArgument { module, name } => write!(f, "\"{module}\" has no argument named \"{name}\""),
+            OpenScadErrorKind::DuplicateArgument(name) => write!(f, "argument \"{name}\" is passed more than once"),
+            OpenScadErrorKind::TooManyArguments { module, max } => write!(f, "too many arguments passed to \"{module}\" - expected at most {max}"),
+            OpenScadErrorKind::MissingArgument { module, name } => write!(f, "\"{module}\" is missing argument \"{name}\""),
+            OpenScadErrorKind::UnexpectedChildren(module) => write!(f, "\"{module}\" does not take children"),
+            OpenScadErrorKind::RecursiveFunction(name) => write!(f, "function \"{name}\" is recursive, so cannot be inlined"),
+        }
+    }
+}
+
+/// Convert OpenSCAD source into yascad nodes.
+///
+/// The spans of the returned nodes refer to the original OpenSCAD source.
+pub fn convert(source: Rc<InputSource>) -> Result<Vec<Node>, Vec<OpenScadError>> {
+    let (tokens, errors) = tokenize::tokenize(source.clone());
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+
+    let mut converter = parse::Converter::new(source, tokens);
+    let nodes = converter.convert_statements();
+    let nodes = converter.inline_functions(nodes);
+
+    if converter.errors.is_empty() {
+        Ok(nodes)
+    } else {
+        Err(converter.errors)
+    }
+}
+
+/// Convert OpenSCAD source into equivalent yascad source code.
+pub fn convert_to_source(source: Rc<InputSource>) -> Result<String, Vec<OpenScadError>> {
+    convert(source).map(|nodes| format_statements(&nodes))
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, openscad::{OpenScadErrorKind, convert_to_source}};
+
+    fn convert(source: &str) -> String {
+        convert_to_source(Rc::new(InputSource::new_string(source.to_owned()))).unwrap()
+    }
+
+    fn convert_errors(source: &str) -> Vec<(OpenScadErrorKind, String)> {
+        let source = Rc::new(InputSource::new_string(source.to_owned()));
+        convert_to_source(source.clone())
+            .unwrap_err()
+            .into_iter()
+            .map(|error| {
+                let text = source.content().chars().skip(error.span.start).take(error.span.length).collect();
+                (error.kind, text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_convert_primitives_and_operators() {
+        assert_eq!(
+            convert("
+                cube([10, 20, 30]);
+                translate([5, 0, 0]) cylinder(h = 10, r = 2);
+                difference() {
+                    cube(10, center = false);
+                    translate([1, 1, -1]) cylinder(12, d = 4);
+                }
+                square([4, 2], true);
+                cylinder(5, r = 1, center = true);
+                cylinder(5, d = 2, center = centered);
+            "),
+            "cube([10, 20, 30]);
+translate([5, 0, 0]) cylinder(10, 2);
+difference() {
+    cube(10);
+    translate([1, 1, -1]) cylinder(12, d = 4);
+}
+square([4, 2], center = true);
+cylinder(5, 1, center = true);
+cylinder(5, d = 2, center = centered);
+",
+        );
+    }
+
+    #[test]
+    fn test_convert_extrusion() {
+        assert_eq!(
+            convert("linear_extrude(height = 5, convexity = 4) { square([2, 3]); circle(d = 1); }"),
+            "linear_extrude(5) {
+    square([2, 3]);
+    circle(d = 1);
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_convert_offset() {
+        assert_eq!(
+            convert("linear_extrude(2) offset(r = 1) square(5); offset(delta = -1, chamfer = true) square(5);"),
+            "linear_extrude(2) offset(1) square(5);
+offset(delta = -1, chamfer = true) square(5);
+",
+        );
+    }
+
+    #[test]
+    fn test_convert_resize() {
+        assert_eq!(
+            convert("resize([10, 0, 0], auto = true) cube(2); resize(newsize = [5, 5]) circle(1);"),
+            "resize([10, 0, 0], auto = true) cube(2);
+resize([5, 5]) circle(1);
+",
+        );
+        assert_eq!(
+            convert_errors("resize([10, 0, 0], auto = [true, true, false]) cube(2);"),
+            vec![(OpenScadErrorKind::Unsupported("a vector `auto` argument to `resize`".to_owned()), "[true, true, false]".to_owned())],
+        );
+    }
+
+    #[test]
+    fn test_convert_control_flow_and_modules() {
+        assert_eq!(
+            convert("
+                module peg(height, radius = 1) {
+                    cylinder(h = height, r = radius);
+                }
+
+                count = 3;
+                for (i = [0 : count - 1], j = [0, 1]) translate([i * 5, j * 5]) peg(2);
+
+                if (count > 2) cube(1); else { sphere_free(); }
+            "),
+            "module peg(height, radius = 1) {
+    cylinder(height, radius);
+}
+
+count = 3;
+for (i = [0:count - 1]) {
+    for (j = [0, 1]) {
+        translate([i * 5, j * 5]) peg(2);
+    }
+}
+if (count > 2) {
+    cube(1);
+} else {
+    sphere_free();
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_convert_range_step() {
+        assert_eq!(
+            convert("for (x = [0 : 12.5 : 100]) translate([x, 0, 0]) cube(1);"),
+            "for (x = [0:12.5:100]) {
+    translate([x, 0, 0]) cube(1);
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_convert_inlines_functions() {
+        assert_eq!(
+            convert("
+                function double(x) = x * 2;
+                function area(w, h = 2) = w * h + double(w);
+                size = [3, 4];
+                cube(area(size[0]) - (1 + 2));
+            "),
+            "size = [3, 4];
+cube(size.x * 2 + size.x * 2 - (1 + 2));
+",
+        );
+    }
+
+    #[test]
+    fn test_convert_math_functions() {
+        // Built-in functions with a yascad equivalent are kept as calls
+        assert_eq!(
+            convert("
+                function double(x) = x * 2;
+                translate([10 * cos(30), sqrt(double(8)), 0]) cube(max(1, abs(-2)));
+            "),
+            "translate([10 * cos(30), sqrt(8 * 2), 0]) cube(max(1, abs(-2)));
+",
+        );
+    }
+
+    #[test]
+    fn test_convert_logical_operators() {
+        assert_eq!(
+            convert("if (a > 1 && a < 10 || !small && a != 0) cube(a);"),
+            "if (a > 1 && a < 10 || !small && !(a == 0)) {
+    cube(a);
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_unsupported_constructs() {
+        assert_eq!(
+            convert_errors("
+                include <lib.scad>
+                $fn = 20;
+                sphere(5);
+                linear_extrude(5, center = true) square(5);
+                cylinder(10, 5);
+                echo(\"hi\");
+                module m() { children(); }
+                x = is_odd ? 1 : 2;
+            "),
+            vec![
+                (OpenScadErrorKind::Unsupported("`include` and `use`".to_owned()), "include".to_owned()),
+                (OpenScadErrorKind::Unsupported("special variables such as `$fn`".to_owned()), "$fn".to_owned()),
+                (OpenScadErrorKind::Unsupported("the built-in module \"sphere\", which has no yascad equivalent".to_owned()), "sphere".to_owned()),
+                (OpenScadErrorKind::Unsupported("`center` arguments".to_owned()), "true".to_owned()),
+                (OpenScadErrorKind::Unsupported("cones (`r1`, `r2`, `d1` and `d2` arguments)".to_owned()), "5".to_owned()),
+                (OpenScadErrorKind::Unsupported("strings".to_owned()), "\"hi\"".to_owned()),
+                (OpenScadErrorKind::Unsupported("the built-in module \"echo\", which has no yascad equivalent".to_owned()), "echo".to_owned()),
+                (OpenScadErrorKind::Unsupported("the built-in module \"children\", which has no yascad equivalent".to_owned()), "children".to_owned()),
+                (OpenScadErrorKind::Unsupported("the ternary operator".to_owned()), "?".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        assert_eq!(
+            convert_errors("set = 1; units = 2;"),
+            vec![
+                (OpenScadErrorKind::Unsupported("the name \"set\", which is reserved in yascad".to_owned()), "set".to_owned()),
+                (OpenScadErrorKind::Unsupported("the name \"units\", which is reserved in yascad".to_owned()), "units".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_recursive_function() {
+        assert_eq!(
+            convert_errors("function f(n) = f(n - 1); cube(f(3));"),
+            vec![(OpenScadErrorKind::RecursiveFunction("f".to_owned()), "f(n - 1)".to_owned())],
+        );
+    }
+}
@@ -0,0 +1,1050 @@
+use std::{collections::HashMap, iter::Peekable, rc::Rc, slice};
+
+use crate::{Arguments, BinaryOperator, InputSource, InputSourceSpan, Node, NodeKind, Parameters, openscad::{OpenScadError, OpenScadErrorKind, builtins, tokenize::{Token, TokenKind}}};
+
+/// A single argument passed to a module or function.
+pub struct Argument {
+    pub name: Option<(String, InputSourceSpan)>,
+    pub value: Node,
+}
+
+/// An OpenSCAD function, which calls are inlined into.
+struct FunctionDefinition {
+    parameters: Parameters,
+    body: Node,
+}
+
+/// Names which are valid identifiers in OpenSCAD, but are keywords in yascad.
+const RESERVED_NAMES: &[&str] = &["it", "operator", "null", "units", "set"];
+
+/// Parses OpenSCAD source, converting it into yascad nodes as it goes.
+///
+/// Like the yascad [`Parser`](crate::Parser), this tries to recover from errors so that as many as
+/// possible can be reported at once. Parsing methods return [`None`] only when a syntax error has
+/// been encountered and recorded. Unsupported-but-valid constructs are recorded as errors, but
+/// parsing continues with a placeholder.
+pub struct Converter<I: Iterator<Item = Token>> {
+    source: Rc<InputSource>,
+    tokens: Peekable<I>,
+    functions: HashMap<String, FunctionDefinition>,
+    pub errors: Vec<OpenScadError>,
+}
+
+impl<I: Iterator<Item = Token>> Converter<I> {
+    pub fn new(source: Rc<InputSource>, tokens: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            source,
+            tokens: tokens.into_iter().peekable(),
+            functions: HashMap::new(),
+            errors: vec![],
+        }
+    }
+
+    pub fn convert_statements(&mut self) -> Vec<Node> {
+        let mut stmts = vec![];
+
+        while self.tokens.peek().is_some() {
+            match self.convert_statement() {
+                Some(nodes) => stmts.extend(nodes),
+                None => self.recover(),
+            }
+        }
+
+        stmts
+    }
+
+    /// Skip tokens until the end of the current statement, after a syntax error.
+    fn recover(&mut self) {
+        let mut depth = 0usize;
+
+        while let Some(token) = self.tokens.peek() {
+            match token.kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth == 0 => return,
+                TokenKind::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.tokens.next();
+                        return
+                    }
+                },
+                TokenKind::Semicolon if depth == 0 => {
+                    self.tokens.next();
+                    return
+                },
+                _ => {},
+            }
+
+            self.tokens.next();
+        }
+    }
+
+    /// Convert a single OpenSCAD statement. This may convert into no nodes (e.g. for function
+    /// definitions), or multiple.
+    fn convert_statement(&mut self) -> Option<Vec<Node>> {
+        let Some(Token { kind, span }) = self.tokens.peek().cloned()
+        else {
+            self.errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedEnd, self.source.eof_span()));
+            return None
+        };
+
+        match kind {
+            TokenKind::Semicolon => {
+                self.tokens.next();
+                Some(vec![])
+            },
+
+            // A bare block is an implicit union
+            TokenKind::LBrace => {
+                let (children, block_span) = self.convert_block()?;
+                Some(vec![Node::new(
+                    NodeKind::OperatorApplication {
+                        name: "union".to_owned(),
+                        arguments: Arguments { positional: vec![], named: vec![] },
+                        children,
+                    },
+                    block_span,
+                )])
+            },
+
+            TokenKind::KwModule => self.convert_module_definition().map(|node| vec![node]),
+            TokenKind::KwFunction => {
+                self.convert_function_definition()?;
+                Some(vec![])
+            },
+            TokenKind::KwFor => self.convert_for_loop().map(|node| vec![node]),
+            TokenKind::KwIf => self.convert_if_statement().map(|node| vec![node]),
+
+            TokenKind::KwInclude | TokenKind::KwUse => {
+                self.tokens.next();
+                self.errors.push(OpenScadError::unsupported("`include` and `use`", span));
+
+                if self.tokens.peek().is_some_and(|token| matches!(token.kind, TokenKind::Path(_))) {
+                    self.tokens.next();
+                }
+                Some(vec![])
+            },
+
+            TokenKind::Hash | TokenKind::Percent | TokenKind::Exclamation | TokenKind::Star => {
+                self.tokens.next();
+                self.errors.push(OpenScadError::unsupported("modifier characters (`#`, `%`, `!` and `*`)", span));
+
+                self.convert_statement()?;
+                Some(vec![])
+            },
+
+            TokenKind::SpecialIdentifier(_) => {
+                self.tokens.next();
+                self.errors.push(OpenScadError::unsupported("special variables such as `$fn`", span));
+
+                self.expect(TokenKind::Equals)?;
+                self.convert_expression()?;
+                self.expect(TokenKind::Semicolon)?;
+                Some(vec![])
+            },
+
+            TokenKind::Identifier(name) => {
+                self.tokens.next();
+
+                match self.tokens.peek().map(|token| &token.kind) {
+                    Some(TokenKind::Equals) => {
+                        self.tokens.next();
+                        self.check_name(&name, &span);
+
+                        let value = self.convert_expression()?;
+                        self.expect(TokenKind::Semicolon)?;
+
+                        let binding_span = span.union_with(slice::from_ref(&value.span));
+                        Some(vec![Node::new(
+                            NodeKind::Binding { name, value: Box::new(value) },
+                            binding_span,
+                        )])
+                    },
+
+                    Some(TokenKind::LParen) => self.convert_instantiation(name, span),
+
+                    _ => {
+                        self.unexpected_next_token();
+                        None
+                    },
+                }
+            },
+
+            _ => {
+                self.unexpected_next_token();
+                None
+            }
+        }
+    }
+
+    /// Convert the instantiation of a module, once the name has been consumed.
+    fn convert_instantiation(&mut self, name: String, name_span: InputSourceSpan) -> Option<Vec<Node>> {
+        // The arguments of these are discarded, so don't report problems with them
+        let errors_before_arguments = self.errors.len();
+        let (arguments, arguments_span) = self.convert_argument_list()?;
+        if builtins::DISCARDED_MODULES.contains(&name.as_str()) {
+            self.errors.truncate(errors_before_arguments);
+        }
+
+        let span = name_span.union_with(&[arguments_span]);
+        let children = match self.tokens.peek().map(|token| &token.kind) {
+            Some(TokenKind::Semicolon) => {
+                self.tokens.next();
+                None
+            },
+            Some(TokenKind::LBrace) => Some(self.convert_block()?.0),
+            _ => Some(self.convert_statement()?),
+        };
+
+        match builtins::convert_instantiation(name, name_span, arguments, children, span) {
+            Ok(node) => Some(node.into_iter().collect()),
+            Err(error) => {
+                self.errors.push(error);
+                Some(vec![])
+            }
+        }
+    }
+
+    /// Convert the body of a construct which takes children - either a braced block, or a single
+    /// statement.
+    fn convert_body(&mut self) -> Option<Vec<Node>> {
+        if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::LBrace) {
+            self.convert_block().map(|(body, _)| body)
+        } else {
+            self.convert_statement()
+        }
+    }
+
+    fn convert_block(&mut self) -> Option<(Vec<Node>, InputSourceSpan)> {
+        let Token { span: start_span, .. } = self.expect(TokenKind::LBrace)?;
+
+        let mut stmts = vec![];
+        loop {
+            if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::RBrace) {
+                let Token { span: end_span, .. } = self.tokens.next().unwrap();
+                return Some((stmts, start_span.union_with(&[end_span])))
+            } else if self.tokens.peek().is_none() {
+                self.errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedEnd, self.source.eof_span()));
+                return None
+            } else {
+                match self.convert_statement() {
+                    Some(nodes) => stmts.extend(nodes),
+                    None => self.recover(),
+                }
+            }
+        }
+    }
+
+    fn convert_module_definition(&mut self) -> Option<Node> {
+        let Token { span: start_span, .. } = self.tokens.next().unwrap();
+        let (name, name_span) = self.expect_identifier()?;
+        self.check_name(&name, &name_span);
+
+        let parameters = self.convert_parameter_list(true)?;
+        let body = self.convert_body()?;
+
+        let body_spans = body.iter().map(|node| node.span.clone()).collect::<Vec<_>>();
+        Some(Node::new(
+            NodeKind::ModuleDefinition { name, parameters, body, doc: None },
+            start_span.union_with(&[name_span]).union_with(&body_spans),
+        ))
+    }
+
+    fn convert_function_definition(&mut self) -> Option<()> {
+        self.tokens.next().unwrap();
+        let (name, name_span) = self.expect_identifier()?;
+        self.check_name(&name, &name_span);
+
+        let parameters = self.convert_parameter_list(false)?;
+        self.expect(TokenKind::Equals)?;
+        let body = self.convert_expression()?;
+        self.expect(TokenKind::Semicolon)?;
+
+        self.functions.insert(name, FunctionDefinition { parameters, body });
+        Some(())
+    }
+
+    /// Convert a parenthesised list of parameters, for either a module or function.
+    ///
+    /// yascad requires that required parameters come before optional ones, so `check_order`
+    /// validates this. (Function parameters are never seen by yascad, so don't need it.)
+    fn convert_parameter_list(&mut self, check_order: bool) -> Option<Parameters> {
+        self.expect(TokenKind::LParen)?;
+
+        let mut parameters = Parameters { required: vec![], optional: vec![] };
+        self.convert_comma_separated_list(TokenKind::RParen, |converter| {
+            let Token { kind, span } = converter.tokens.next()?;
+            let name = match kind {
+                TokenKind::Identifier(name) => name,
+                TokenKind::SpecialIdentifier(name) => {
+                    converter.errors.push(OpenScadError::unsupported("special variables such as `$fn`", span.clone()));
+                    name
+                },
+                kind => {
+                    converter.errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedToken(kind.to_string()), span));
+                    return None
+                }
+            };
+            converter.check_name(&name, &span);
+
+            if converter.tokens.peek().is_some_and(|token| token.kind == TokenKind::Equals) {
+                converter.tokens.next();
+                let default = converter.convert_expression()?;
+                parameters.optional.push((name, default));
+            } else {
+                if check_order && !parameters.optional.is_empty() {
+                    converter.errors.push(OpenScadError::unsupported("required parameters after optional parameters", span));
+                }
+                parameters.required.push(name);
+            }
+
+            Some(())
+        })?;
+
+        Some(parameters)
+    }
+
+    fn convert_argument_list(&mut self) -> Option<(Vec<Argument>, InputSourceSpan)> {
+        let Token { span: start_span, .. } = self.expect(TokenKind::LParen)?;
+
+        let mut arguments = vec![];
+        let end_span = self.convert_comma_separated_list(TokenKind::RParen, |converter| {
+            // Special variables are only supported as named arguments - report them, but parse
+            // through them
+            if let Some(Token { kind: TokenKind::SpecialIdentifier(_), span }) = converter.tokens.peek().cloned() {
+                converter.tokens.next();
+                converter.errors.push(OpenScadError::unsupported("special variables such as `$fn`", span));
+
+                converter.expect(TokenKind::Equals)?;
+                converter.convert_expression()?;
+                return Some(())
+            }
+
+            let value = converter.convert_expression()?;
+            if let NodeKind::Identifier(name) = &value.kind
+                && converter.tokens.peek().is_some_and(|token| token.kind == TokenKind::Equals)
+            {
+                converter.tokens.next();
+                let name = (name.to_owned(), value.span.clone());
+                let value = converter.convert_expression()?;
+                arguments.push(Argument { name: Some(name), value });
+            } else {
+                arguments.push(Argument { name: None, value });
+            }
+
+            Some(())
+        })?;
+
+        Some((arguments, start_span.union_with(&[end_span])))
+    }
+
+    /// Convert items separated by commas, until the `end` token. The start of the list must already
+    /// have been consumed. Trailing commas are permitted.
+    ///
+    /// Returns the span of the `end` token.
+    fn convert_comma_separated_list(&mut self, end: TokenKind, mut convert_fn: impl FnMut(&mut Self) -> Option<()>) -> Option<InputSourceSpan> {
+        loop {
+            if let Some(Token { kind, span }) = self.tokens.peek()
+                && *kind == end
+            {
+                let span = span.clone();
+                self.tokens.next();
+                return Some(span)
+            }
+
+            convert_fn(self)?;
+
+            match self.tokens.peek() {
+                Some(Token { kind: TokenKind::Comma, .. }) => {
+                    self.tokens.next();
+                },
+                Some(Token { kind, .. }) if *kind == end => {},
+                _ => {
+                    self.unexpected_next_token();
+                    return None
+                }
+            }
+        }
+    }
+
+    fn convert_for_loop(&mut self) -> Option<Node> {
+        let Token { span: start_span, .. } = self.tokens.next().unwrap();
+        self.expect(TokenKind::LParen)?;
+
+        // Multiple loop variables iterate over every combination, like nested loops
+        let mut loops = vec![];
+        self.convert_comma_separated_list(TokenKind::RParen, |converter| {
+            let (name, name_span) = converter.expect_identifier()?;
+            converter.check_name(&name, &name_span);
+
+            converter.expect(TokenKind::Equals)?;
+            let source = converter.convert_expression()?;
+            loops.push((name, source));
+            Some(())
+        })?;
+
+        let body = self.convert_body()?;
+        let body_spans = body.iter().map(|node| node.span.clone()).collect::<Vec<_>>();
+        let span = start_span.union_with(&body_spans);
+
+        let Some(innermost) = loops.pop()
+        else {
+            self.errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedToken(TokenKind::RParen.to_string()), start_span));
+            return None
+        };
+        let node = loops.into_iter().rev().fold(
+            Node::new(
+                NodeKind::ForLoop { loop_variable: innermost.0, loop_source: Box::new(innermost.1), body },
+                span.clone(),
+            ),
+            |body, (loop_variable, loop_source)| Node::new(
+                NodeKind::ForLoop { loop_variable, loop_source: Box::new(loop_source), body: vec![body] },
+                span.clone(),
+            ),
+        );
+        Some(node)
+    }
+
+    fn convert_if_statement(&mut self) -> Option<Node> {
+        let Token { span: start_span, .. } = self.tokens.next().unwrap();
+        self.expect(TokenKind::LParen)?;
+        let condition = self.convert_expression()?;
+        let Token { span: end_span, .. } = self.expect(TokenKind::RParen)?;
+
+        let true_body = self.convert_body()?;
+        let false_body =
+            if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::KwElse) {
+                self.tokens.next();
+                Some(self.convert_body()?)
+            } else {
+                None
+            };
+
+        Some(Node::new(
+            NodeKind::IfConditional { condition: Box::new(condition), true_body, false_body },
+            start_span.union_with(&[end_span]),
+        ))
+    }
+
+    fn convert_expression(&mut self) -> Option<Node> {
+        let condition = self.convert_logical_expression()?;
+
+        if let Some(Token { kind: TokenKind::Question, span }) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            self.errors.push(OpenScadError::unsupported("the ternary operator", span));
+
+            self.convert_expression()?;
+            self.expect(TokenKind::Colon)?;
+            self.convert_expression()?;
+        }
+
+        Some(condition)
+    }
+
+    fn convert_logical_expression(&mut self) -> Option<Node> {
+        let mut left = self.convert_and_expression()?;
+
+        while let Some(Token { kind: TokenKind::DoublePipe, .. }) = self.tokens.peek() {
+            self.tokens.next();
+            let right = self.convert_and_expression()?;
+            left = binary_operation(left, right, BinaryOperator::Or);
+        }
+
+        Some(left)
+    }
+
+    fn convert_and_expression(&mut self) -> Option<Node> {
+        let mut left = self.convert_equality_expression()?;
+
+        while let Some(Token { kind: TokenKind::DoubleAmpersand, .. }) = self.tokens.peek() {
+            self.tokens.next();
+            let right = self.convert_equality_expression()?;
+            left = binary_operation(left, right, BinaryOperator::And);
+        }
+
+        Some(left)
+    }
+
+    fn convert_equality_expression(&mut self) -> Option<Node> {
+        let mut left = self.convert_relational_expression()?;
+
+        loop {
+            match self.tokens.peek().cloned() {
+                Some(Token { kind: TokenKind::DoubleEquals, .. }) => {
+                    self.tokens.next();
+                    let right = self.convert_relational_expression()?;
+                    left = binary_operation(left, right, BinaryOperator::Equals);
+                },
+                Some(Token { kind: TokenKind::ExclamationEquals, .. }) => {
+                    self.tokens.next();
+                    let right = self.convert_relational_expression()?;
+                    let equals = binary_operation(left, right, BinaryOperator::Equals);
+                    let span = equals.span.clone();
+                    left = Node::new(NodeKind::UnaryNot(Box::new(equals)), span);
+                },
+                _ => break,
+            }
+        }
+
+        Some(left)
+    }
+
+    fn convert_relational_expression(&mut self) -> Option<Node> {
+        let mut left = self.convert_additive_expression()?;
+
+        while let Some(Token { kind, .. }) = self.tokens.peek() {
+            let op = match kind {
+                TokenKind::LAngle => BinaryOperator::LessThan,
+                TokenKind::LAngleEquals => BinaryOperator::LessThanOrEquals,
+                TokenKind::RAngle => BinaryOperator::GreaterThan,
+                TokenKind::RAngleEquals => BinaryOperator::GreaterThanOrEquals,
+                _ => break,
+            };
+
+            self.tokens.next();
+            let right = self.convert_additive_expression()?;
+            left = binary_operation(left, right, op);
+        }
+
+        Some(left)
+    }
+
+    fn convert_additive_expression(&mut self) -> Option<Node> {
+        let mut left = self.convert_multiplicative_expression()?;
+
+        while let Some(Token { kind, .. }) = self.tokens.peek() {
+            let op = match kind {
+                TokenKind::Plus => BinaryOperator::Add,
+                TokenKind::Minus => BinaryOperator::Subtract,
+                _ => break,
+            };
+
+            self.tokens.next();
+            let right = self.convert_multiplicative_expression()?;
+            left = binary_operation(left, right, op);
+        }
+
+        Some(left)
+    }
+
+    fn convert_multiplicative_expression(&mut self) -> Option<Node> {
+        let mut left = self.convert_unary_expression()?;
+
+        while let Some(Token { kind, span }) = self.tokens.peek().cloned() {
+            let op = match kind {
+                TokenKind::Star => BinaryOperator::Multiply,
+                TokenKind::ForwardSlash => BinaryOperator::Divide,
+                TokenKind::Percent => {
+                    self.tokens.next();
+                    self.errors.push(OpenScadError::unsupported("the `%` operator", span));
+                    self.convert_unary_expression()?;
+                    continue;
+                },
+                _ => break,
+            };
+
+            self.tokens.next();
+            let right = self.convert_unary_expression()?;
+            left = binary_operation(left, right, op);
+        }
+
+        Some(left)
+    }
+
+    fn convert_unary_expression(&mut self) -> Option<Node> {
+        match self.tokens.peek().cloned() {
+            Some(Token { kind: TokenKind::Minus, span }) => {
+                self.tokens.next();
+                let value = self.convert_unary_expression()?;
+                let span = span.union_with(slice::from_ref(&value.span));
+                Some(Node::new(NodeKind::UnaryNegate(Box::new(value)), span))
+            },
+            Some(Token { kind: TokenKind::Plus, .. }) => {
+                self.tokens.next();
+                self.convert_unary_expression()
+            },
+            Some(Token { kind: TokenKind::Exclamation, span }) => {
+                self.tokens.next();
+                let value = self.convert_unary_expression()?;
+                let span = span.union_with(slice::from_ref(&value.span));
+                Some(Node::new(NodeKind::UnaryNot(Box::new(value)), span))
+            },
+            _ => self.convert_exponent_expression(),
+        }
+    }
+
+    fn convert_exponent_expression(&mut self) -> Option<Node> {
+        let base = self.convert_postfix_expression()?;
+
+        if let Some(Token { kind: TokenKind::Caret, span }) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            self.errors.push(OpenScadError::unsupported("the `^` operator", span));
+            self.convert_unary_expression()?;
+        }
+
+        Some(base)
+    }
+
+    fn convert_postfix_expression(&mut self) -> Option<Node> {
+        let mut value = self.convert_primary_expression()?;
+
+        loop {
+            match self.tokens.peek().cloned() {
+                Some(Token { kind: TokenKind::Dot, .. }) => {
+                    self.tokens.next();
+                    let (field, field_span) = self.expect_identifier()?;
+                    let span = value.span.union_with(&[field_span]);
+                    value = Node::new(NodeKind::FieldAccess { value: Box::new(value), field }, span);
+                },
+
+                // yascad doesn't have general indexing, but the first three elements can be
+                // accessed as fields
+                Some(Token { kind: TokenKind::LBracket, .. }) => {
+                    self.tokens.next();
+                    let index = self.convert_expression()?;
+                    let Token { span: end_span, .. } = self.expect(TokenKind::RBracket)?;
+
+                    let field = match index.kind {
+                        NodeKind::NumberLiteral(0.0) => "x",
+                        NodeKind::NumberLiteral(1.0) => "y",
+                        NodeKind::NumberLiteral(2.0) => "z",
+                        _ => {
+                            self.errors.push(OpenScadError::unsupported("indexing, except with a constant 0, 1 or 2", index.span));
+                            "x"
+                        }
+                    };
+
+                    let span = value.span.union_with(&[end_span]);
+                    value = Node::new(NodeKind::FieldAccess { value: Box::new(value), field: field.to_owned() }, span);
+                },
+
+                _ => break,
+            }
+        }
+
+        Some(value)
+    }
+
+    fn convert_primary_expression(&mut self) -> Option<Node> {
+        let Some(Token { kind, span }) = self.tokens.next()
+        else {
+            self.errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedEnd, self.source.eof_span()));
+            return None
+        };
+
+        match kind {
+            TokenKind::Number(num) => {
+                let value = num.parse().unwrap_or_else(|_| {
+                    self.errors.push(OpenScadError::new(OpenScadErrorKind::InvalidNumber, span.clone()));
+                    0.0
+                });
+                Some(Node::new(NodeKind::NumberLiteral(value), span))
+            },
+
+            TokenKind::KwTrue => Some(Node::new(NodeKind::BooleanLiteral(true), span)),
+            TokenKind::KwFalse => Some(Node::new(NodeKind::BooleanLiteral(false), span)),
+            TokenKind::KwUndef => Some(Node::new(NodeKind::NullLiteral, span)),
+
+            TokenKind::Identifier(name) => {
+                if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::LParen) {
+                    // Function call - these are inlined later, once all functions are known
+                    let (arguments, arguments_span) = self.convert_argument_list()?;
+                    let arguments = builtins::to_yascad_arguments(arguments);
+                    Some(Node::new(NodeKind::Call { name, arguments }, span.union_with(&[arguments_span])))
+                } else {
+                    self.check_name(&name, &span);
+                    Some(Node::new(NodeKind::Identifier(name), span))
+                }
+            },
+
+            TokenKind::LParen => {
+                let value = self.convert_expression()?;
+                self.expect(TokenKind::RParen)?;
+                Some(value)
+            },
+
+            TokenKind::LBracket => self.convert_vector(span),
+
+            TokenKind::String(_) => {
+                self.errors.push(OpenScadError::unsupported("strings", span.clone()));
+                Some(Node::new(NodeKind::NullLiteral, span))
+            },
+            TokenKind::SpecialIdentifier(_) => {
+                self.errors.push(OpenScadError::unsupported("special variables such as `$fn`", span.clone()));
+                Some(Node::new(NodeKind::NullLiteral, span))
+            },
+            TokenKind::KwLet => {
+                self.errors.push(OpenScadError::unsupported("`let`", span.clone()));
+                self.convert_argument_list()?;
+                self.convert_expression()
+            },
+            TokenKind::KwFunction => {
+                self.errors.push(OpenScadError::unsupported("function literals", span.clone()));
+                self.convert_parameter_list(false)?;
+                self.convert_expression()
+            },
+
+            _ => {
+                self.errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedToken(kind.to_string()), span));
+                None
+            },
+        }
+    }
+
+    /// Convert a vector or range, once the opening bracket has been consumed.
+    fn convert_vector(&mut self, start_span: InputSourceSpan) -> Option<Node> {
+        if let Some(Token { kind: TokenKind::KwFor | TokenKind::KwEach | TokenKind::KwLet, span }) = self.tokens.peek().cloned() {
+            self.errors.push(OpenScadError::unsupported("list comprehensions", span));
+
+            // Too complex to parse through, so skip to the end
+            let mut depth = 0usize;
+            for token in self.tokens.by_ref() {
+                match token.kind {
+                    TokenKind::LBracket => depth += 1,
+                    TokenKind::RBracket if depth == 0 => break,
+                    TokenKind::RBracket => depth -= 1,
+                    _ => {},
+                }
+            }
+            return Some(Node::new(NodeKind::NullLiteral, start_span))
+        }
+
+        if let Some(Token { kind: TokenKind::RBracket, span }) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            let span = start_span.union_with(&[span]);
+            self.errors.push(OpenScadError::unsupported("empty vectors", span.clone()));
+            return Some(Node::new(NodeKind::NullLiteral, span))
+        }
+
+        let first = self.convert_expression()?;
+
+        // Range
+        if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Colon) {
+            self.tokens.next();
+            let mut end = self.convert_expression()?;
+
+            // OpenSCAD puts the step in the middle too, like `[0:2:10]`
+            let mut step = None;
+            if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Colon) {
+                self.tokens.next();
+                step = Some(Box::new(end));
+                end = self.convert_expression()?;
+            }
+
+            let Token { span: end_span, .. } = self.expect(TokenKind::RBracket)?;
+            return Some(Node::new(
+                NodeKind::VectorRangeLiteral { start: Box::new(first), step, end: Box::new(end) },
+                start_span.union_with(&[end_span]),
+            ))
+        }
+
+        let mut items = vec![first];
+        if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Comma) {
+            self.tokens.next();
+        }
+        let end_span = self.convert_comma_separated_list(TokenKind::RBracket, |converter| {
+            items.push(converter.convert_expression()?);
+            Some(())
+        })?;
+
+        Some(Node::new(NodeKind::VectorLiteral(items), start_span.union_with(&[end_span])))
+    }
+
+    /// Record an error if the given name can't be used in yascad.
+    fn check_name(&mut self, name: &str, span: &InputSourceSpan) {
+        if RESERVED_NAMES.contains(&name) {
+            self.errors.push(OpenScadError::unsupported(
+                format!("the name \"{name}\", which is reserved in yascad"),
+                span.clone(),
+            ));
+        }
+    }
+
+    /// Consume the next token, generating an error if it's not of the expected kind.
+    fn expect(&mut self, kind: TokenKind) -> Option<Token> {
+        match self.tokens.peek() {
+            Some(token) if token.kind == kind => self.tokens.next(),
+            _ => {
+                self.unexpected_next_token();
+                None
+            }
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Option<(String, InputSourceSpan)> {
+        match self.tokens.peek() {
+            Some(Token { kind: TokenKind::Identifier(_), .. }) => {
+                let Some(Token { kind: TokenKind::Identifier(id), span }) = self.tokens.next()
+                else { unreachable!() };
+                Some((id, span))
+            },
+            _ => {
+                self.unexpected_next_token();
+                None
+            }
+        }
+    }
+
+    /// Record an error about the next token, without consuming it.
+    fn unexpected_next_token(&mut self) {
+        let error = match self.tokens.peek() {
+            Some(Token { kind, span }) => OpenScadError::new(OpenScadErrorKind::UnexpectedToken(kind.to_string()), span.clone()),
+            None => OpenScadError::new(OpenScadErrorKind::UnexpectedEnd, self.source.eof_span()),
+        };
+        self.errors.push(error);
+    }
+
+    /// Replace all calls to OpenSCAD functions with the body of that function.
+    pub fn inline_functions(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        let mut inliner = FunctionInliner {
+            functions: &self.functions,
+            call_stack: vec![],
+            errors: &mut self.errors,
+        };
+
+        nodes.into_iter()
+            .map(|node| inliner.inline_statement(node))
+            .collect()
+    }
+}
+
+fn binary_operation(left: Node, right: Node, op: BinaryOperator) -> Node {
+    let span = left.span.union_with(slice::from_ref(&right.span));
+    Node::new(
+        NodeKind::BinaryOperation { left: Box::new(left), right: Box::new(right), op },
+        span,
+    )
+}
+
+struct FunctionInliner<'a> {
+    functions: &'a HashMap<String, FunctionDefinition>,
+
+    /// The functions currently being inlined, to detect recursion.
+    call_stack: Vec<String>,
+
+    errors: &'a mut Vec<OpenScadError>,
+}
+
+impl FunctionInliner<'_> {
+    fn inline_statements(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        nodes.into_iter()
+            .map(|node| self.inline_statement(node))
+            .collect()
+    }
+
+    /// Inline function calls within a statement. A [`NodeKind::Call`] at this level is the
+    /// instantiation of a module, rather than a function call.
+    fn inline_statement(&mut self, node: Node) -> Node {
+        let kind = match node.kind {
+            NodeKind::Call { name, arguments } =>
+                NodeKind::Call { name, arguments: self.inline_arguments(arguments) },
+            NodeKind::OperatorApplication { name, arguments, children } =>
+                NodeKind::OperatorApplication {
+                    name,
+                    arguments: self.inline_arguments(arguments),
+                    children: self.inline_statements(children),
+                },
+            NodeKind::Binding { name, value } =>
+                NodeKind::Binding { name, value: Box::new(self.inline_expression(*value)) },
+            NodeKind::ModuleDefinition { name, parameters, body, doc } =>
+                NodeKind::ModuleDefinition {
+                    name,
+                    doc,
+                    parameters: Parameters {
+                        required: parameters.required,
+                        optional: parameters.optional.into_iter()
+                            .map(|(name, default)| (name, self.inline_expression(default)))
+                            .collect(),
+                    },
+                    body: self.inline_statements(body),
+                },
+            NodeKind::ForLoop { loop_variable, loop_source, body } =>
+                NodeKind::ForLoop {
+                    loop_variable,
+                    loop_source: Box::new(self.inline_expression(*loop_source)),
+                    body: self.inline_statements(body),
+                },
+            NodeKind::IfConditional { condition, true_body, false_body } =>
+                NodeKind::IfConditional {
+                    condition: Box::new(self.inline_expression(*condition)),
+                    true_body: self.inline_statements(true_body),
+                    false_body: false_body.map(|body| self.inline_statements(body)),
+                },
+
+            kind => return self.inline_expression(Node::new(kind, node.span)),
+        };
+
+        Node::new(kind, node.span)
+    }
+
+    fn inline_expression(&mut self, node: Node) -> Node {
+        let kind = match node.kind {
+            NodeKind::Call { name, arguments } => {
+                let arguments = self.inline_arguments(arguments);
+                return self.inline_call(name, arguments, node.span)
+            },
+
+            NodeKind::VectorLiteral(items) =>
+                NodeKind::VectorLiteral(items.into_iter().map(|item| self.inline_expression(item)).collect()),
+            NodeKind::VectorRangeLiteral { start, step, end } =>
+                NodeKind::VectorRangeLiteral {
+                    start: Box::new(self.inline_expression(*start)),
+                    step: step.map(|step| Box::new(self.inline_expression(*step))),
+                    end: Box::new(self.inline_expression(*end)),
+                },
+            NodeKind::FieldAccess { value, field } =>
+                NodeKind::FieldAccess { value: Box::new(self.inline_expression(*value)), field },
+            NodeKind::BinaryOperation { left, right, op } =>
+                NodeKind::BinaryOperation {
+                    left: Box::new(self.inline_expression(*left)),
+                    right: Box::new(self.inline_expression(*right)),
+                    op,
+                },
+            NodeKind::UnaryNegate(value) =>
+                NodeKind::UnaryNegate(Box::new(self.inline_expression(*value))),
+            NodeKind::UnaryNot(value) =>
+                NodeKind::UnaryNot(Box::new(self.inline_expression(*value))),
+
+            kind => kind,
+        };
+
+        Node::new(kind, node.span)
+    }
+
+    fn inline_arguments(&mut self, arguments: Arguments) -> Arguments {
+        Arguments {
+            positional: arguments.positional.into_iter()
+                .map(|value| self.inline_expression(value))
+                .collect(),
+            named: arguments.named.into_iter()
+                .map(|(name, value)| (name, self.inline_expression(value)))
+                .collect(),
+        }
+    }
+
+    /// Replace a call with the body of the function being called. The arguments must already have
+    /// been inlined.
+    fn inline_call(&mut self, name: String, arguments: Arguments, span: InputSourceSpan) -> Node {
+        let placeholder = Node::new(NodeKind::NullLiteral, span.clone());
+
+        let Some(function) = self.functions.get(&name)
+        else {
+            if builtins::EQUIVALENT_FUNCTIONS.contains(&name.as_str()) {
+                return Node::new(NodeKind::Call { name, arguments }, span)
+            }
+
+            let construct =
+                if builtins::UNSUPPORTED_FUNCTIONS.contains(&name.as_str()) {
+                    format!("the built-in function \"{name}\", which has no yascad equivalent")
+                } else {
+                    format!("call to function \"{name}\", which is not defined in this file")
+                };
+            self.errors.push(OpenScadError::unsupported(construct, span));
+            return placeholder
+        };
+
+        if self.call_stack.contains(&name) {
+            self.errors.push(OpenScadError::new(OpenScadErrorKind::RecursiveFunction(name), span));
+            return placeholder
+        }
+
+        // Work out what each parameter should be replaced with
+        let parameter_count = function.parameters.required.len() + function.parameters.optional.len();
+        if arguments.positional.len() > parameter_count {
+            self.errors.push(OpenScadError::new(
+                OpenScadErrorKind::TooManyArguments { module: name, max: parameter_count },
+                span,
+            ));
+            return placeholder
+        }
+
+        let parameter_names = function.parameters.required.iter()
+            .chain(function.parameters.optional.iter().map(|(name, _)| name))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut replacements = parameter_names.iter()
+            .cloned()
+            .zip(arguments.positional)
+            .collect::<HashMap<_, _>>();
+        for (argument_name, value) in arguments.named {
+            if !parameter_names.contains(&argument_name) {
+                self.errors.push(OpenScadError::new(
+                    OpenScadErrorKind::UnknownArgument { module: name.clone(), name: argument_name },
+                    value.span,
+                ));
+                return placeholder
+            }
+            if replacements.insert(argument_name.clone(), value).is_some() {
+                self.errors.push(OpenScadError::new(OpenScadErrorKind::DuplicateArgument(argument_name), span));
+                return placeholder
+            }
+        }
+        for required in &function.parameters.required {
+            if !replacements.contains_key(required) {
+                self.errors.push(OpenScadError::new(
+                    OpenScadErrorKind::MissingArgument { module: name.clone(), name: required.clone() },
+                    span,
+                ));
+                return placeholder
+            }
+        }
+        for (optional, default) in &function.parameters.optional {
+            if !replacements.contains_key(optional) {
+                replacements.insert(optional.clone(), default.clone());
+            }
+        }
+
+        let body = substitute(&function.body, &replacements);
+
+        self.call_stack.push(name);
+        let result = self.inline_expression(body);
+        self.call_stack.pop();
+
+        result
+    }
+}
+
+/// Replace identifiers in an expression according to `replacements`.
+fn substitute(node: &Node, replacements: &HashMap<String, Node>) -> Node {
+    let kind = match &node.kind {
+        NodeKind::Identifier(id) => {
+            if let Some(replacement) = replacements.get(id) {
+                return replacement.clone()
+            }
+            NodeKind::Identifier(id.clone())
+        },
+
+        NodeKind::Call { name, arguments } =>
+            NodeKind::Call {
+                name: name.clone(),
+                arguments: Arguments {
+                    positional: arguments.positional.iter().map(|value| substitute(value, replacements)).collect(),
+                    named: arguments.named.iter().map(|(name, value)| (name.clone(), substitute(value, replacements))).collect(),
+                },
+            },
+        NodeKind::VectorLiteral(items) =>
+            NodeKind::VectorLiteral(items.iter().map(|item| substitute(item, replacements)).collect()),
+        NodeKind::VectorRangeLiteral { start, step, end } =>
+            NodeKind::VectorRangeLiteral {
+                start: Box::new(substitute(start, replacements)),
+                step: step.as_ref().map(|step| Box::new(substitute(step, replacements))),
+                end: Box::new(substitute(end, replacements)),
+            },
+        NodeKind::FieldAccess { value, field } =>
+            NodeKind::FieldAccess { value: Box::new(substitute(value, replacements)), field: field.clone() },
+        NodeKind::BinaryOperation { left, right, op } =>
+            NodeKind::BinaryOperation {
+                left: Box::new(substitute(left, replacements)),
+                right: Box::new(substitute(right, replacements)),
+                op: *op,
+            },
+        NodeKind::UnaryNegate(value) =>
+            NodeKind::UnaryNegate(Box::new(substitute(value, replacements))),
+        NodeKind::UnaryNot(value) =>
+            NodeKind::UnaryNot(Box::new(substitute(value, replacements))),
+
+        kind => kind.clone(),
+    };
+
+    Node::new(kind, node.span.clone())
+}
@@ -0,0 +1,365 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::{InputSource, InputSourceSpan, openscad::{OpenScadError, OpenScadErrorKind}};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: InputSourceSpan,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, span: InputSourceSpan) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Identifier(String),
+
+    /// An identifier beginning with `$`, such as `$fn`.
+    SpecialIdentifier(String),
+
+    Number(String),
+    String(String),
+
+    /// The `<...>` path following `include` or `use`.
+    Path(String),
+
+    KwModule,
+    KwFunction,
+    KwFor,
+    KwIf,
+    KwElse,
+    KwLet,
+    KwEach,
+    KwTrue,
+    KwFalse,
+    KwUndef,
+    KwInclude,
+    KwUse,
+
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    LAngle,
+    RAngle,
+
+    Comma,
+    Semicolon,
+    Dot,
+    Colon,
+    Question,
+    Hash,
+    Caret,
+
+    Plus,
+    Minus,
+    ForwardSlash,
+    Star,
+    Percent,
+    Exclamation,
+
+    Equals,
+    DoubleEquals,
+    ExclamationEquals,
+    LAngleEquals,
+    RAngleEquals,
+    DoubleAmpersand,
+    DoublePipe,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Identifier(id) => write!(f, "identifier \"{id}\""),
+            TokenKind::SpecialIdentifier(id) => write!(f, "special variable \"{id}\""),
+            TokenKind::Number(num) => write!(f, "number \"{num}\""),
+            TokenKind::String(_) => write!(f, "string"),
+            TokenKind::Path(path) => write!(f, "path <{path}>"),
+
+            TokenKind::KwModule => write!(f, "keyword \"module\""),
+            TokenKind::KwFunction => write!(f, "keyword \"function\""),
+            TokenKind::KwFor => write!(f, "keyword \"for\""),
+            TokenKind::KwIf => write!(f, "keyword \"if\""),
+            TokenKind::KwElse => write!(f, "keyword \"else\""),
+            TokenKind::KwLet => write!(f, "keyword \"let\""),
+            TokenKind::KwEach => write!(f, "keyword \"each\""),
+            TokenKind::KwTrue => write!(f, "keyword \"true\""),
+            TokenKind::KwFalse => write!(f, "keyword \"false\""),
+            TokenKind::KwUndef => write!(f, "keyword \"undef\""),
+            TokenKind::KwInclude => write!(f, "keyword \"include\""),
+            TokenKind::KwUse => write!(f, "keyword \"use\""),
+
+            TokenKind::LParen => write!(f, "left parenthesis"),
+            TokenKind::RParen => write!(f, "right parenthesis"),
+            TokenKind::LBrace => write!(f, "left brace"),
+            TokenKind::RBrace => write!(f, "right brace"),
+            TokenKind::LBracket => write!(f, "left bracket"),
+            TokenKind::RBracket => write!(f, "right bracket"),
+            TokenKind::LAngle => write!(f, "less-than"),
+            TokenKind::RAngle => write!(f, "greater-than"),
+
+            TokenKind::Comma => write!(f, "comma"),
+            TokenKind::Semicolon => write!(f, "semicolon"),
+            TokenKind::Dot => write!(f, "dot"),
+            TokenKind::Colon => write!(f, "colon"),
+            TokenKind::Question => write!(f, "question mark"),
+            TokenKind::Hash => write!(f, "hash"),
+            TokenKind::Caret => write!(f, "caret"),
+
+            TokenKind::Plus => write!(f, "plus"),
+            TokenKind::Minus => write!(f, "minus"),
+            TokenKind::ForwardSlash => write!(f, "forward slash"),
+            TokenKind::Star => write!(f, "star"),
+            TokenKind::Percent => write!(f, "percent"),
+            TokenKind::Exclamation => write!(f, "exclamation mark"),
+
+            TokenKind::Equals => write!(f, "equals"),
+            TokenKind::DoubleEquals => write!(f, "double-equals"),
+            TokenKind::ExclamationEquals => write!(f, "not-equals"),
+            TokenKind::LAngleEquals => write!(f, "less-than-or-equals"),
+            TokenKind::RAngleEquals => write!(f, "greater-than-or-equals"),
+            TokenKind::DoubleAmpersand => write!(f, "double-ampersand"),
+            TokenKind::DoublePipe => write!(f, "double-pipe"),
+        }
+    }
+}
+
+/// Tokenize OpenSCAD source.
+/// 
+/// This is deliberately more permissive than the subset of OpenSCAD which can be converted, so
+/// that unsupported constructs can be reported by the parser with a meaningful message, rather
+/// than as an unexpected character.
+pub fn tokenize(source: Rc<InputSource>) -> (Vec<Token>, Vec<OpenScadError>) {
+    let mut tokens: Vec<Token> = vec![];
+    let mut errors = vec![];
+
+    let source_for_chars = source.clone();
+    let mut chars = source_for_chars.content().chars().enumerate().peekable();
+
+    while let Some((start_index, char)) = chars.next() {
+        // The path after `include` or `use` isn't a normal expression, so is tokenized specially
+        if char == '<' && tokens.last().is_some_and(|token| matches!(token.kind, TokenKind::KwInclude | TokenKind::KwUse)) {
+            let mut buffer = String::new();
+            let mut terminated = false;
+
+            for (_, char) in chars.by_ref() {
+                if char == '>' {
+                    terminated = true;
+                    break;
+                }
+                buffer.push(char);
+            }
+
+            if !terminated {
+                errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedEnd, source.eof_span()));
+            }
+            let length = buffer.chars().count() + 2;
+            tokens.push(Token::new(TokenKind::Path(buffer), source.span(start_index, length)));
+            continue;
+        }
+
+        // Single- and double-character punctuation which maps directly onto a token
+        let simple_token = match char {
+            '(' => Some(TokenKind::LParen),
+            ')' => Some(TokenKind::RParen),
+            '{' => Some(TokenKind::LBrace),
+            '}' => Some(TokenKind::RBrace),
+            '[' => Some(TokenKind::LBracket),
+            ']' => Some(TokenKind::RBracket),
+            ',' => Some(TokenKind::Comma),
+            ';' => Some(TokenKind::Semicolon),
+            ':' => Some(TokenKind::Colon),
+            '?' => Some(TokenKind::Question),
+            '#' => Some(TokenKind::Hash),
+            '^' => Some(TokenKind::Caret),
+            '+' => Some(TokenKind::Plus),
+            '-' => Some(TokenKind::Minus),
+            '*' => Some(TokenKind::Star),
+            '%' => Some(TokenKind::Percent),
+            _ => None,
+        };
+        if let Some(kind) = simple_token {
+            tokens.push(Token::new(kind, source.span(start_index, 1)));
+            continue;
+        }
+
+        // Punctuation which might be followed by an `=` to form a different token
+        let maybe_with_equals = match char {
+            '=' => Some((TokenKind::Equals, TokenKind::DoubleEquals)),
+            '!' => Some((TokenKind::Exclamation, TokenKind::ExclamationEquals)),
+            '<' => Some((TokenKind::LAngle, TokenKind::LAngleEquals)),
+            '>' => Some((TokenKind::RAngle, TokenKind::RAngleEquals)),
+            _ => None,
+        };
+        if let Some((normal, with_equals)) = maybe_with_equals {
+            if chars.peek().is_some_and(|(_, c)| *c == '=') {
+                chars.next().unwrap();
+                tokens.push(Token::new(with_equals, source.span(start_index, 2)));
+            } else {
+                tokens.push(Token::new(normal, source.span(start_index, 1)));
+            }
+            continue;
+        }
+
+        match char {
+            // Numbers, including forms like `.5` and `1e-3`
+            _ if char.is_ascii_digit() || (char == '.' && chars.peek().is_some_and(|(_, c)| c.is_ascii_digit())) => {
+                let mut buffer = char.to_string();
+                let mut had_decimal_point = char == '.';
+                let mut had_exponent = false;
+
+                while let Some((_, char)) = chars.peek() {
+                    if char.is_ascii_digit() {
+                        let (_, char) = chars.next().unwrap();
+                        buffer.push(char)
+                    } else if !had_decimal_point && !had_exponent && *char == '.' {
+                        chars.next().unwrap();
+                        had_decimal_point = true;
+                        buffer.push('.');
+                    } else if !had_exponent && (*char == 'e' || *char == 'E') {
+                        chars.next().unwrap();
+                        had_exponent = true;
+                        buffer.push('e');
+
+                        if let Some((_, sign @ ('+' | '-'))) = chars.peek() {
+                            buffer.push(*sign);
+                            chars.next().unwrap();
+                        }
+                    } else {
+                        break;
+                    }
+                }
+
+                let length = buffer.chars().count();
+                tokens.push(Token::new(TokenKind::Number(buffer), source.span(start_index, length)));
+            }
+
+            _ if char.is_alphabetic() || char == '_' || char == '$' => {
+                let mut buffer = char.to_string();
+
+                while let Some((_, char)) = chars.peek() {
+                    if char.is_alphanumeric() || *char == '_' {
+                        let (_, char) = chars.next().unwrap();
+                        buffer.push(char)
+                    } else {
+                        break;
+                    }
+                }
+
+                let span = source.span(start_index, buffer.chars().count());
+                let token_kind =
+                    if buffer.starts_with('$') {
+                        TokenKind::SpecialIdentifier(buffer)
+                    } else {
+                        match lookup_keyword(&buffer) {
+                            Some(kw) => kw,
+                            None => TokenKind::Identifier(buffer),
+                        }
+                    };
+                tokens.push(Token::new(token_kind, span));
+            }
+
+            '"' => {
+                let mut buffer = String::new();
+                let mut length = 1;
+                let mut terminated = false;
+
+                while let Some((_, char)) = chars.next() {
+                    length += 1;
+                    match char {
+                        '"' => {
+                            terminated = true;
+                            break;
+                        }
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                length += 1;
+                                buffer.push(escaped);
+                            }
+                        }
+                        _ => buffer.push(char),
+                    }
+                }
+
+                if !terminated {
+                    errors.push(OpenScadError::new(OpenScadErrorKind::UnexpectedEnd, source.eof_span()));
+                }
+                tokens.push(Token::new(TokenKind::String(buffer), source.span(start_index, length)));
+            }
+
+            // Line comment
+            '/' if chars.peek().is_some_and(|(_, char)| *char == '/') => {
+                for (_, char) in chars.by_ref() {
+                    if char == '\n' {
+                        break
+                    }
+                }
+            }
+
+            // Block comment
+            '/' if chars.peek().is_some_and(|(_, char)| *char == '*') => {
+                chars.next();
+
+                while let Some((_, this_char)) = chars.next() {
+                    if this_char == '*' && chars.peek().is_some_and(|(_, char)| *char == '/') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+
+            '/' => {
+                tokens.push(Token::new(TokenKind::ForwardSlash, source.span(start_index, 1)))
+            }
+            '.' => {
+                tokens.push(Token::new(TokenKind::Dot, source.span(start_index, 1)))
+            }
+
+            '&' if chars.peek().is_some_and(|(_, c)| *c == '&') => {
+                chars.next().unwrap();
+                tokens.push(Token::new(TokenKind::DoubleAmpersand, source.span(start_index, 2)))
+            }
+            '|' if chars.peek().is_some_and(|(_, c)| *c == '|') => {
+                chars.next().unwrap();
+                tokens.push(Token::new(TokenKind::DoublePipe, source.span(start_index, 2)))
+            }
+
+            _ if char.is_whitespace() => {
+                // Skip
+            }
+
+            _ => {
+                errors.push(OpenScadError::new(
+                    OpenScadErrorKind::UnexpectedChar(char),
+                    source.span(start_index, 1),
+                ))
+            }
+        }
+    }
+
+    (tokens, errors)
+}
+
+fn lookup_keyword(name: &str) -> Option<TokenKind> {
+    match name {
+        "module" => Some(TokenKind::KwModule),
+        "function" => Some(TokenKind::KwFunction),
+        "for" => Some(TokenKind::KwFor),
+        "if" => Some(TokenKind::KwIf),
+        "else" => Some(TokenKind::KwElse),
+        "let" => Some(TokenKind::KwLet),
+        "each" => Some(TokenKind::KwEach),
+        "true" => Some(TokenKind::KwTrue),
+        "false" => Some(TokenKind::KwFalse),
+        "undef" => Some(TokenKind::KwUndef),
+        "include" => Some(TokenKind::KwInclude),
+        "use" => Some(TokenKind::KwUse),
+        _ => None,
+    }
+}
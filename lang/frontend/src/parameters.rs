@@ -0,0 +1,108 @@
+use crate::{InputSourceSpan, Node, NodeKind};
+
+/// A top-level binding to a constant value, which a host application could offer as a setting for
+/// the model (like OpenSCAD's customizer).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelParameter {
+    pub name: String,
+    pub value: ParameterValue,
+
+    /// The span of the value, so that a host can replace it in the source.
+    pub span: InputSourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterValue {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Vector(Vec<ParameterValue>),
+}
+
+impl ParameterValue {
+    /// Get the constant value of a literal node, or [`None`] if the node isn't made up purely of
+    /// literals.
+    pub fn from_node(node: &Node) -> Option<Self> {
+        match &node.kind {
+            NodeKind::NumberLiteral(num) => Some(ParameterValue::Number(*num)),
+            NodeKind::BooleanLiteral(bool) => Some(ParameterValue::Boolean(*bool)),
+            NodeKind::StringLiteral(string) => Some(ParameterValue::String(string.clone())),
+            NodeKind::VectorLiteral(items) => items.iter()
+                .map(ParameterValue::from_node)
+                .collect::<Option<Vec<_>>>()
+                .map(ParameterValue::Vector),
+
+            // Negative numbers are parsed as a negation
+            NodeKind::UnaryNegate(value) => match value.kind {
+                NodeKind::NumberLiteral(num) => Some(ParameterValue::Number(-num)),
+                _ => None,
+            },
+
+            _ => None,
+        }
+    }
+}
+
+/// Find the parameters of a model - the top-level bindings whose values are constants.
+///
+/// Bindings inside definitions or control flow, and bindings to computed values, aren't included.
+pub fn extract_parameters(nodes: &[Node]) -> Vec<ModelParameter> {
+    nodes.iter()
+        .filter_map(|node| {
+            let NodeKind::Binding { name, value } = &node.kind
+            else { return None };
+
+            Some(ModelParameter {
+                name: name.clone(),
+                value: ParameterValue::from_node(value)?,
+                span: value.span.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, ParameterValue, Parser, extract_parameters, tokenize};
+
+    #[test]
+    fn test_extract_parameters() {
+        let source = Rc::new(InputSource::new_string("
+            width = 10;
+            offset = -2.5;
+            size = [width, 2];
+            hole = [1, [2, true]];
+            label = \"lid\";
+            if (true) { inner = 3; }
+            cube(width);
+        ".to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+        let mut parser = Parser::new(source.clone(), tokens);
+        let stmts = parser.parse_statements();
+        assert_eq!(parser.errors, vec![]);
+
+        let parameters = extract_parameters(&stmts)
+            .into_iter()
+            .map(|parameter| {
+                let text = source.content().chars().skip(parameter.span.start).take(parameter.span.length).collect::<String>();
+                (parameter.name, parameter.value, text)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(parameters, vec![
+            ("width".to_owned(), ParameterValue::Number(10.0), "10".to_owned()),
+            ("offset".to_owned(), ParameterValue::Number(-2.5), "-2.5".to_owned()),
+            (
+                "hole".to_owned(),
+                ParameterValue::Vector(vec![
+                    ParameterValue::Number(1.0),
+                    ParameterValue::Vector(vec![ParameterValue::Number(2.0), ParameterValue::Boolean(true)]),
+                ]),
+                "[1, [2, true]]".to_owned(),
+            ),
+            ("label".to_owned(), ParameterValue::String("lid".to_owned()), "\"lid\"".to_owned()),
+        ]);
+    }
+}
@@ -1,18 +1,34 @@
-use std::{error::Error, fmt::Display, iter::Peekable, ops::RangeInclusive, rc::Rc, slice};
+use std::{error::Error, fmt::Display, iter::Peekable, mem, ops::RangeInclusive, rc::Rc, slice};
 
 use miette::Diagnostic;
 
-use crate::{InputSource, InputSourceSpan, Token, TokenKind};
+use crate::{InputSource, InputSourceSpan, Token, TokenKind, Trivia, truncate_for_display};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Node {
     pub kind: NodeKind,
     pub span: InputSourceSpan,
+
+    /// If this node was parsed as a statement, the span of the whole statement, from its first
+    /// token to its terminator.
+    /// 
+    /// This can be longer than `span`, which doesn't always cover the whole construct.
+    pub statement_span: Option<InputSourceSpan>,
+
+    /// If this node was parsed as a statement, the `//` and `/* ... */` comments on the lines
+    /// before it, including their delimiters.
+    /// 
+    /// Comments are only kept if the tokens came from [`tokenize_with_trivia`](crate::tokenize_with_trivia).
+    pub comments: Vec<String>,
+
+    /// Like `comments`, but for the comments after this statement, if it's the last one in a
+    /// block.
+    pub trailing_comments: Vec<String>,
 }
 
 impl Node {
     pub fn new(kind: NodeKind, span: InputSourceSpan) -> Self {
-        Self { kind, span }
+        Self { kind, span, statement_span: None, comments: vec![], trailing_comments: vec![] }
     }
 }
 
@@ -22,11 +38,16 @@ pub enum NodeKind {
     NullLiteral,
     NumberLiteral(f64),
     BooleanLiteral(bool),
+    StringLiteral(String),
     VectorLiteral(Vec<Node>),
     VectorRangeLiteral {
         start: Box<Node>,
+
+        /// How much each item is after the previous one, if given, like `[0:5:20]`. Otherwise,
+        /// it's 1.
+        step: Option<Box<Node>>,
+
         end: Box<Node>,
-        // TODO: step
     },
     ItReference,
 
@@ -47,6 +68,12 @@ pub enum NodeKind {
         name: String,
         value: Box<Node>,
     },
+
+    /// Changes the value of an existing binding, like `set total = total + x;`.
+    Reassignment {
+        name: String,
+        value: Box<Node>,
+    },
     FieldAccess {
         value: Box<Node>,
         field: String,
@@ -58,16 +85,40 @@ pub enum NodeKind {
         op: BinaryOperator,
     },
     UnaryNegate(Box<Node>),
+    UnaryNot(Box<Node>),
+
+    /// A value chosen by a condition, like `n > 0 ? n : 1`. Only the chosen value is evaluated.
+    TernaryConditional {
+        condition: Box<Node>,
+        true_value: Box<Node>,
+        false_value: Box<Node>,
+    },
 
     OperatorDefinition {
         name: String,
         parameters: Parameters,
         body: Vec<Node>,
+
+        /// The doc comments directly before the definition, one line each.
+        doc: Option<String>,
     },
     ModuleDefinition {
         name: String,
         parameters: Parameters,
         body: Vec<Node>,
+
+        /// The doc comments directly before the definition, one line each.
+        doc: Option<String>,
+    },
+
+    /// A function, like `function area(w, h) = w * h;`, whose body is a single expression.
+    FunctionDefinition {
+        name: String,
+        parameters: Parameters,
+        body: Box<Node>,
+
+        /// The doc comments directly before the definition, one line each.
+        doc: Option<String>,
     },
 
     ForLoop {
@@ -79,6 +130,38 @@ pub enum NodeKind {
         condition: Box<Node>,
         true_body: Vec<Node>,
         false_body: Option<Vec<Node>>,
+    },
+
+    UnitsDeclaration {
+        unit: String,
+    },
+
+    /// Loads another source file, like `include "lib/gears.yascad";`.
+    Include {
+        kind: IncludeKind,
+
+        /// The path as written, relative to the including file.
+        path: String,
+    },
+}
+
+/// How an [`Include`](NodeKind::Include) statement uses the file it loads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IncludeKind {
+    /// `include`, which runs every statement in the file, as if it were written in place.
+    Include,
+
+    /// `use`, which only takes the file's module and operator definitions.
+    Use,
+}
+
+impl IncludeKind {
+    /// The keyword which introduces this kind of statement.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            IncludeKind::Include => "include",
+            IncludeKind::Use => "use",
+        }
     }
 }
 
@@ -101,11 +184,20 @@ pub enum BinaryOperator {
     Multiply,
     Divide,
 
+    /// The Euclidean remainder after dividing, which is never negative.
+    Modulo,
+
     Equals,
     LessThan,
     LessThanOrEquals,
     GreaterThan,
     GreaterThanOrEquals,
+
+    /// Both sides are true. The right side isn't evaluated if the left is false.
+    And,
+
+    /// Either side is true. The right side isn't evaluated if the left is true.
+    Or,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -142,37 +234,109 @@ impl Error for ParseError {}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParseErrorKind {
-    UnexpectedToken(TokenKind),
+    /// A token which can't appear here, along with descriptions of what could have.
+    UnexpectedToken { token: TokenKind, expected: Vec<String> },
     UnexpectedEnd,
     InvalidNumber,
     RequiredParameterAfterOptionalParameter(String),
     PositionalArgumentAfterNamedArgument,
+
+    /// A range written with two dots rather than a colon, like `[1..5]`.
+    DotsInRange { start: f64, end: f64 },
 }
 
 impl Display for ParseErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseErrorKind::UnexpectedToken(token_kind) => write!(f, "unexpected {token_kind}"),
+            ParseErrorKind::UnexpectedToken { token, expected } => {
+                write!(f, "unexpected {token}")?;
+                if let Some((last, rest)) = expected.split_last() {
+                    write!(f, ", expected ")?;
+                    if !rest.is_empty() {
+                        write!(f, "{} or ", rest.join(", "))?;
+                    }
+                    write!(f, "{last}")?;
+                }
+                Ok(())
+            },
             ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end-of-file"),
             ParseErrorKind::InvalidNumber => write!(f, "number could not be parsed, possibly out-of-range?"),
-            ParseErrorKind::RequiredParameterAfterOptionalParameter(name) => write!(f, "required parameter \"{name}\" appears after optional parameters - required parameters must come first"),
+            ParseErrorKind::RequiredParameterAfterOptionalParameter(name) => write!(f, "required parameter \"{}\" appears after optional parameters - required parameters must come first", truncate_for_display(name)),
             ParseErrorKind::PositionalArgumentAfterNamedArgument => write!(f, "positional argument appears after named arguments - positional arguments must come first"),
+            ParseErrorKind::DotsInRange { start, end } => write!(f, "ranges are written with a colon - did you mean `[{start}:{end}]`?"),
+        }
+    }
+}
+
+/// A peekable stream of tokens, which remembers the span of the last token taken from it.
+/// 
+/// Doc comments aren't part of the grammar, so they never come out of the stream. Instead, they're
+/// set aside until the next token is taken, so that a definition can claim the ones before it.
+struct TokenStream<I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+    last_span: Option<InputSourceSpan>,
+    docs: Vec<String>,
+
+    /// The ordinary comments before the doc comments which have been set aside.
+    doc_comments: Vec<String>,
+}
+
+impl<I: Iterator<Item = Token>> TokenStream<I> {
+    fn peek(&mut self) -> Option<&Token> {
+        self.skip_docs();
+        self.tokens.peek()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.skip_docs();
+        self.docs.clear();
+        self.doc_comments.clear();
+
+        let token = self.tokens.next()?;
+        self.last_span = Some(token.span.clone());
+        Some(token)
+    }
+
+    /// Take the doc comments directly before the next token, joined into lines.
+    fn take_docs(&mut self) -> Option<String> {
+        self.skip_docs();
+        (!self.docs.is_empty()).then(|| mem::take(&mut self.docs).join("\n"))
+    }
+
+    fn skip_docs(&mut self) {
+        while let Some(Token { kind: TokenKind::DocComment(text), leading_trivia, .. }) = self.tokens.next_if(|token| matches!(token.kind, TokenKind::DocComment(_))) {
+            self.docs.push(text);
+            self.doc_comments.extend(leading_trivia.iter().filter_map(Trivia::comment_text));
         }
     }
+
+    /// Take the ordinary comments before the next token, including any before its doc comments.
+    fn take_comments(&mut self) -> Vec<String> {
+        self.skip_docs();
+        let mut comments = mem::take(&mut self.doc_comments);
+        if let Some(token) = self.tokens.peek() {
+            comments.extend(token.leading_trivia.iter().filter_map(Trivia::comment_text));
+        }
+        comments
+    }
 }
 
 pub struct Parser<I: Iterator<Item = Token>> {
     source: Rc<InputSource>,
-    tokens: Peekable<I>,
+    tokens: TokenStream<I>,
     pub errors: Vec<ParseError>,
+
+    /// How many braced statement lists are currently being parsed.
+    block_depth: usize,
 }
 
 impl<I: Iterator<Item = Token>> Parser<I> {
     pub fn new(source: Rc<InputSource>, tokens: impl IntoIterator<IntoIter = I>) -> Self {
         Self {
             source,
-            tokens: tokens.into_iter().peekable(),
+            tokens: TokenStream { tokens: tokens.into_iter().peekable(), last_span: None, docs: vec![], doc_comments: vec![] },
             errors: vec![],
+            block_depth: 0,
         }
     }
 
@@ -182,7 +346,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         // Assumes `parse_statement` always makes forward progress through the token iterator,
         // even in the worst error case
         while self.tokens.peek().is_some() {
-            if let Some(stmt) = self.parse_statement() {
+            if let Some(stmt) = self.parse_statement_with_span() {
                 stmts.push(stmt);
             }
         }
@@ -190,6 +354,20 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         stmts
     }
 
+    /// Parse a statement with [`Self::parse_statement`], and record its
+    /// [`statement_span`](Node::statement_span) and [`comments`](Node::comments).
+    fn parse_statement_with_span(&mut self) -> Option<Node> {
+        let start_span = self.tokens.peek()?.span.clone();
+        let comments = self.tokens.take_comments();
+        let mut stmt = self.parse_statement()?;
+        stmt.comments = comments;
+
+        // The statement must have taken at least one token to succeed
+        let end_span = self.tokens.last_span.clone().unwrap();
+        stmt.statement_span = Some(start_span.union_with(&[end_span]));
+        Some(stmt)
+    }
+
     // The parser methods here all return an `Option` because they try to do error recovery.
     //
     // If they return `Some`, either all parsing was valid and the returned node is correct, or
@@ -199,12 +377,14 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     pub fn parse_statement(&mut self) -> Option<Node> {
         // Try parse operator definition
         if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::KwOperator) {
+            let doc = self.tokens.take_docs();
             let (name, parameters, body, span) = self.parse_definition()?;
             return Some(Node::new(
                 NodeKind::OperatorDefinition {
                     name: name.to_owned(),
                     parameters,
                     body,
+                    doc,
                 },
                 span,
             ))
@@ -212,34 +392,40 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
         // Try parse module definition
         if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::KwModule) {
+            let doc = self.tokens.take_docs();
             let (name, parameters, body, span) = self.parse_definition()?;
             return Some(Node::new(
                 NodeKind::ModuleDefinition {
                     name: name.to_owned(),
                     parameters,
                     body,
+                    doc,
                 },
                 span,
             ))
         }
 
+        // Try parse function definition
+        if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::KwFunction) {
+            let doc = self.tokens.take_docs();
+            let (name, parameters, start_span) = self.parse_definition_header()?;
+            self.expect(TokenKind::Equals)?;
+            let (body, _) = self.parse_expression()?;
+            self.expect(TokenKind::Semicolon)?;
+
+            let span = start_span.union_with(slice::from_ref(&body.span));
+            return Some(Node::new(
+                NodeKind::FunctionDefinition { name, parameters, body: Box::new(body), doc },
+                span,
+            ))
+        }
+
         // Try parse `for` loop
         if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::KwFor) {
             let Token { span: start_span, .. } = self.tokens.next().unwrap();
 
             self.expect(TokenKind::LParen)?;
-            
-            // TODO: REALLY need to break this sequence out into a function
-            let Some(Token { kind, span: name_span }) = self.tokens.next()
-            else {
-                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedEnd, self.source.eof_span()));
-                return None
-            };
-            let TokenKind::Identifier(loop_variable) = &kind
-            else {
-                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken(kind), name_span));
-                return None
-            };
+            let (loop_variable, _) = self.expect_identifier("loop variable name")?;
 
             self.expect(TokenKind::Equals)?;
             let (loop_source, _) = self.parse_expression()?;
@@ -254,7 +440,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             let span = start_span.union_with(&body_spans);
             return Some(Node::new(
                 NodeKind::ForLoop {
-                    loop_variable: loop_variable.to_owned(),
+                    loop_variable,
                     loop_source: Box::new(loop_source),
                     body,
                 },
@@ -267,10 +453,39 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             return self.parse_if_statement()
         }
 
-        let (mut expr, mut terminator) = self.parse_expression()?;
+        // Try parse `units` declaration
+        if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::KwUnits) {
+            let Token { span: start_span, .. } = self.tokens.next().unwrap();
+            let (unit, unit_span) = self.expect_identifier("unit name")?;
+            self.expect(TokenKind::Semicolon)?;
+
+            return Some(Node::new(
+                NodeKind::UnitsDeclaration { unit },
+                start_span.union_with(&[unit_span]),
+            ))
+        }
+
+        // Try parse `include` or `use` statement
+        if self.tokens.peek().is_some_and(|token| matches!(token.kind, TokenKind::KwInclude | TokenKind::KwUse)) {
+            let Token { kind, span: start_span, .. } = self.tokens.next().unwrap();
+            let kind = if kind == TokenKind::KwInclude { IncludeKind::Include } else { IncludeKind::Use };
+            let (path, path_span) = self.expect_string("file path")?;
+            self.expect(TokenKind::Semicolon)?;
+
+            return Some(Node::new(
+                NodeKind::Include { kind, path },
+                start_span.union_with(&[path_span]),
+            ))
+        }
+
+        let (mut expr, mut terminator) = if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::KwSet) {
+            self.parse_reassignment()?
+        } else {
+            self.parse_expression()?
+        };
 
         // Parse assignment
-        if let Node { span, kind: NodeKind::Identifier(id) } = &expr
+        if let Node { span, kind: NodeKind::Identifier(id), .. } = &expr
             && self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Equals)
         {
             self.tokens.next().unwrap(); // discard equals
@@ -286,7 +501,14 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
         match terminator {
             StatementTerminator::NeedsSemicolon => {
-                self.expect(TokenKind::Semicolon)?;
+                // The last statement of a block or file can leave off its semicolon
+                let at_end = match self.tokens.peek() {
+                    None => true,
+                    Some(token) => token.kind == TokenKind::RBrace && self.block_depth > 0,
+                };
+                if !at_end {
+                    self.expect_with_alternatives(TokenKind::Semicolon, &["an operator"])?;
+                }
             },
             StatementTerminator::Braced => {
                 if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Semicolon) {
@@ -298,8 +520,89 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Some(expr)
     }
 
+    /// Parse a `set` reassignment, up to the end of its value.
+    fn parse_reassignment(&mut self) -> Option<(Node, StatementTerminator)> {
+        let Token { span: start_span, .. } = self.tokens.next().unwrap();
+        let (name, _) = self.expect_identifier("binding name")?;
+        self.expect(TokenKind::Equals)?;
+
+        let (value, terminator) = self.parse_expression()?;
+        let span = start_span.union_with(slice::from_ref(&value.span));
+        Some((Node::new(NodeKind::Reassignment { name, value: Box::new(value) }, span), terminator))
+    }
+
     fn parse_expression(&mut self) -> Option<(Node, StatementTerminator)> {
-        self.parse_comparison_expression()
+        self.parse_ternary_expression()
+    }
+
+    fn parse_ternary_expression(&mut self) -> Option<(Node, StatementTerminator)> {
+        let (condition, terminator) = self.parse_or_expression()?;
+        if !self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Question) {
+            return Some((condition, terminator));
+        }
+        self.tokens.next().unwrap();
+
+        // Both values can be ternaries themselves, so `a ? b : c ? d : e` chains like `else if`
+        let (true_value, _) = self.parse_expression()?;
+        self.expect(TokenKind::Colon)?;
+        let (false_value, terminator) = self.parse_expression()?;
+
+        let span = condition.span.union_with(slice::from_ref(&false_value.span));
+        Some((
+            Node::new(
+                NodeKind::TernaryConditional {
+                    condition: Box::new(condition),
+                    true_value: Box::new(true_value),
+                    false_value: Box::new(false_value),
+                },
+                span,
+            ),
+            terminator,
+        ))
+    }
+
+    fn parse_or_expression(&mut self) -> Option<(Node, StatementTerminator)> {
+        let (mut left, mut terminator) = self.parse_and_expression()?;
+
+        while self.tokens.peek().is_some_and(|token| token.kind == TokenKind::DoublePipe) {
+            self.tokens.next().unwrap();
+
+            let (right, right_terminator) = self.parse_and_expression()?;
+            let span = left.span.union_with(slice::from_ref(&right.span));
+            left = Node::new(
+                NodeKind::BinaryOperation {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    op: BinaryOperator::Or,
+                },
+                span,
+            );
+            terminator = right_terminator;
+        }
+
+        Some((left, terminator))
+    }
+
+    fn parse_and_expression(&mut self) -> Option<(Node, StatementTerminator)> {
+        let (mut left, mut terminator) = self.parse_comparison_expression()?;
+
+        while self.tokens.peek().is_some_and(|token| token.kind == TokenKind::DoubleAmpersand) {
+            self.tokens.next().unwrap();
+
+            let (right, right_terminator) = self.parse_comparison_expression()?;
+            let span = left.span.union_with(slice::from_ref(&right.span));
+            left = Node::new(
+                NodeKind::BinaryOperation {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    op: BinaryOperator::And,
+                },
+                span,
+            );
+            terminator = right_terminator;
+        }
+
+        Some((left, terminator))
     }
 
     fn parse_comparison_expression(&mut self) -> Option<(Node, StatementTerminator)> {
@@ -363,11 +666,12 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     fn parse_mul_div_expression(&mut self) -> Option<(Node, StatementTerminator)> {
         let (mut left, mut terminator) = self.parse_bottom_expression()?;
 
-        while self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Star || token.kind == TokenKind::ForwardSlash) {
+        while self.tokens.peek().is_some_and(|token| matches!(token.kind, TokenKind::Star | TokenKind::ForwardSlash | TokenKind::Percent)) {
             let Token { kind, .. } = self.tokens.next().unwrap();
             let op = match kind {
                 TokenKind::Star => BinaryOperator::Multiply,
                 TokenKind::ForwardSlash => BinaryOperator::Divide,
+                TokenKind::Percent => BinaryOperator::Modulo,
                 _ => unreachable!(),
             };
 
@@ -388,7 +692,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn parse_bottom_expression(&mut self) -> Option<(Node, StatementTerminator)> {
-        let Token { kind, span } = self.tokens.next()?;
+        let Token { kind, span, .. } = self.tokens.next()?;
         match kind {
             TokenKind::Identifier(id) => {
                 if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::LParen) {
@@ -453,7 +757,12 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             }
             
             TokenKind::LBracket => {
-                // TODO: support parsing empty vector
+                // Empty vector
+                if let Some(Token { kind: TokenKind::RBracket, .. }) = self.tokens.peek() {
+                    let end = self.tokens.next().unwrap();
+                    let vector_span = span.union_with(slice::from_ref(&end.span));
+                    return Some((Node::new(NodeKind::VectorLiteral(vec![]), vector_span), StatementTerminator::NeedsSemicolon));
+                }
 
                 // Parse the first item ourselves, because we need to check whether this is an
                 // item-based vector or a range vector.
@@ -461,8 +770,9 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
                 match self.tokens.peek() {
                     // Single-item vector
-                    Some(Token { kind: TokenKind::RBracket, span: end_span }) => {
-                        let vector_span = span.union_with(slice::from_ref(end_span));
+                    Some(Token { kind: TokenKind::RBracket, .. }) => {
+                        let end = self.tokens.next().unwrap();
+                        let vector_span = span.union_with(slice::from_ref(&end.span));
                         Some((Node::new(NodeKind::VectorLiteral(vec![first_item]), vector_span), StatementTerminator::NeedsSemicolon))
                     },
 
@@ -477,25 +787,36 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                         Some((Node::new(NodeKind::VectorLiteral(items), vector_span), StatementTerminator::NeedsSemicolon))
                     },
 
-                    // Range vector
+                    // Range vector, with an optional step between the start and end
                     Some(Token { kind: TokenKind::Colon, .. }) => {
                         self.tokens.next().unwrap();
 
-                        let (end_item, _) = self.parse_expression()?;
+                        let (mut end_item, _) = self.parse_expression()?;
+                        let mut step = None;
+                        if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Colon) {
+                            self.tokens.next().unwrap();
+                            step = Some(Box::new(end_item));
+                            (end_item, _) = self.parse_expression()?;
+                        }
                         self.expect(TokenKind::RBracket)?;
 
                         let vector_span = span.union_with(slice::from_ref(&end_item.span));
                         Some((
                             Node::new(NodeKind::VectorRangeLiteral {
                                 start: Box::new(first_item),
+                                step,
                                 end: Box::new(end_item),
                             }, vector_span),
                             StatementTerminator::NeedsSemicolon
                         ))
                     }
 
-                    Some(Token { span, .. }) => {
-                        self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken(kind), span.clone()));
+                    Some(Token { kind: TokenKind::Dot, .. }) if matches!(first_item.kind, NodeKind::NumberLiteral(_)) =>
+                        self.parse_dotted_range(span, first_item),
+
+                    Some(Token { kind, span, .. }) => {
+                        let expected = [TokenKind::Comma, TokenKind::Colon, TokenKind::RBracket].map(|kind| kind.to_string());
+                        self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken { token: kind.clone(), expected: expected.into() }, span.clone()));
                         None
                     },
                     None => {
@@ -523,6 +844,15 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 ))
             }
 
+            TokenKind::Exclamation => {
+                let (value, terminator) = self.parse_bottom_expression()?;
+                let span = span.union_with(slice::from_ref(&value.span));
+                Some((
+                    Node::new(NodeKind::UnaryNot(Box::new(value)), span),
+                    terminator,
+                ))
+            }
+
             TokenKind::LParen => {
                 let (node, _) = self.parse_expression()?;
                 self.expect(TokenKind::RParen)?;
@@ -530,6 +860,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 Some((node, StatementTerminator::NeedsSemicolon))
             }
 
+            TokenKind::String(string) => {
+                Some((Node::new(NodeKind::StringLiteral(string), span), StatementTerminator::NeedsSemicolon))
+            }
+
             TokenKind::KwTrue => {
                 Some((Node::new(NodeKind::BooleanLiteral(true), span), StatementTerminator::NeedsSemicolon))
             }
@@ -547,7 +881,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
             _ => {
                 self.errors.push(ParseError::new(
-                    ParseErrorKind::UnexpectedToken(kind),
+                    ParseErrorKind::UnexpectedToken { token: kind, expected: vec!["an expression".to_owned()] },
                     span,
                 ));
                 None
@@ -567,14 +901,36 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     ///   - Body
     ///   - Span of entire definition
     fn parse_definition(&mut self) -> Option<(String, Parameters, Vec<Node>, InputSourceSpan)> {
-        let Token { span: start_span, .. } = self.tokens.next().unwrap();
+        let (name, parameters, start_span) = self.parse_definition_header()?;
+
+        // Parse body
+        let body = self.parse_braced_statement_list()?;
+        let body_spans = body
+            .iter()
+            .map(|item| item.span.clone())
+            .collect::<Vec<_>>();
 
-        let (name, _) = self.expect_identifier()?;
+        let span = start_span.union_with(&body_spans);
+
+        Some((name.to_owned(), parameters, body, span))
+    }
+
+    /// Parse the keyword, name and parameters which begin a module, operator or function
+    /// definition. Returns the name, the parameters, and the span of the keyword.
+    fn parse_definition_header(&mut self) -> Option<(String, Parameters, InputSourceSpan)> {
+        let Token { kind, span: start_span, .. } = self.tokens.next().unwrap();
+
+        let what = match kind {
+            TokenKind::KwModule => "module name",
+            TokenKind::KwFunction => "function name",
+            _ => "operator name",
+        };
+        let (name, _) = self.expect_identifier(what)?;
 
         // Parse parameters
         self.expect(TokenKind::LParen)?;
         let (parsed_parameters, _) = self.parse_bracketed_comma_separated_list(TokenKind::RParen, |parser| {
-            let (name, _) = parser.expect_identifier()?;
+            let (name, _) = parser.expect_identifier("parameter name")?;
 
             if parser.tokens.peek().is_some_and(|token| token.kind == TokenKind::Equals) {
                 parser.tokens.next().unwrap();
@@ -602,16 +958,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             }
         }
 
-        // Parse body
-        let body = self.parse_braced_statement_list()?;
-        let body_spans = body
-            .iter()
-            .map(|item| item.span.clone())
-            .collect::<Vec<_>>();
-
-        let span = start_span.union_with(&body_spans);
-
-        Some((name.to_owned(), parameters, body, span))
+        Some((name, parameters, start_span))
     }
 
     fn parse_argument_list(&mut self) -> Option<(Arguments, InputSourceSpan)> {
@@ -656,7 +1003,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         while self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Dot) {
             let Token { span: dot_span, .. } = self.tokens.next().unwrap();
 
-            let Some((field, name_span)) = self.expect_identifier()
+            let Some((field, name_span)) = self.expect_identifier("field name")
             else { break };
 
             value = Node::new(
@@ -707,7 +1054,8 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 end_span = Some(separator.span);
                 break;
             } else {
-                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken(separator.kind), separator.span));
+                let expected = vec![TokenKind::Comma.to_string(), end.to_string()];
+                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken { token: separator.kind, expected }, separator.span));
             }
         }
 
@@ -718,6 +1066,36 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Some((items, span))
     }
 
+    /// Having parsed the start of a vector up to a number followed by a dot, check whether it's a
+    /// range mistakenly written like `[1..5]`. If it is, this reports it with a suggestion and
+    /// recovers by parsing it as the range `[1:5]`, rather than giving a chain of confusing errors.
+    fn parse_dotted_range(&mut self, bracket_span: InputSourceSpan, start: Node) -> Option<(Node, StatementTerminator)> {
+        let NodeKind::NumberLiteral(start_value) = start.kind else { unreachable!("range start was checked to be a number") };
+        let dot = self.tokens.next().unwrap();
+
+        let is_range = self.tokens.peek().is_some_and(|token| token.kind == TokenKind::Dot) && {
+            self.tokens.next().unwrap();
+            self.tokens.peek().is_some_and(|token| matches!(token.kind, TokenKind::Number(_)))
+        };
+        if !is_range {
+            let expected = [TokenKind::Comma, TokenKind::Colon, TokenKind::RBracket].map(|kind| kind.to_string());
+            self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken { token: dot.kind, expected: expected.into() }, dot.span));
+            return None;
+        }
+
+        let (end, _) = self.parse_bottom_expression()?;
+        let NodeKind::NumberLiteral(end_value) = end.kind else { unreachable!("range end was checked to be a number") };
+        let error_span = start.span.union_with(slice::from_ref(&end.span));
+        self.errors.push(ParseError::new(ParseErrorKind::DotsInRange { start: start_value, end: end_value }, error_span));
+        self.expect(TokenKind::RBracket)?;
+
+        let vector_span = bracket_span.union_with(slice::from_ref(&end.span));
+        Some((
+            Node::new(NodeKind::VectorRangeLiteral { start: Box::new(start), step: None, end: Box::new(end) }, vector_span),
+            StatementTerminator::NeedsSemicolon,
+        ))
+    }
+
     // Assumes you have already consumed the start of the list (e.g. left paren)
     fn parse_bracketed_comma_separated_expression_list(&mut self, end: TokenKind) -> Option<(Vec<Node>, InputSourceSpan)> {
         self.parse_bracketed_comma_separated_list(end, |p|
@@ -727,18 +1105,23 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     fn parse_braced_statement_list(&mut self) -> Option<Vec<Node>> {
         self.expect(TokenKind::LBrace)?;
 
-        let mut stmts = vec![];
+        self.block_depth += 1;
+        let mut stmts: Vec<Node> = vec![];
         loop {
             if self.tokens.peek().is_some_and(|token| token.kind == TokenKind::RBrace) {
-                self.tokens.next().unwrap();
+                let closing = self.tokens.next().unwrap();
+                if let Some(last) = stmts.last_mut() {
+                    last.trailing_comments = closing.leading_trivia.iter().filter_map(Trivia::comment_text).collect();
+                }
                 break
             } else if self.tokens.peek().is_none() {
                 self.errors.push(ParseError::new(ParseErrorKind::UnexpectedEnd, self.source.eof_span()));
                 break
-            } else if let Some(stmt) = self.parse_statement() {
+            } else if let Some(stmt) = self.parse_statement_with_span() {
                 stmts.push(stmt);
             }
         }
+        self.block_depth -= 1;
 
         Some(stmts)
     }
@@ -759,8 +1142,9 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                     Some(Token { kind: TokenKind::KwIf, .. }) => Some(vec![self.parse_if_statement()?]),
                     Some(Token { kind: TokenKind::LBrace, .. }) => Some(self.parse_braced_statement_list()?),
                     
-                    Some(Token { kind, span }) => {
-                        self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken(kind.clone()), span.clone()));
+                    Some(Token { kind, span, .. }) => {
+                        let expected = [TokenKind::KwIf, TokenKind::LBrace].map(|kind| kind.to_string());
+                        self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken { token: kind.clone(), expected: expected.into() }, span.clone()));
                         None
                     }
                     None => {
@@ -790,11 +1174,21 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     ///   - `Some(None)` if a token was consumed, but not the expected kind of token
     ///   - `None` if there was no token (EOF)
     fn expect(&mut self, kind: TokenKind) -> Option<Option<Token>> {
+        self.expect_with_alternatives(kind, &[])
+    }
+
+    /// Like [`expect`], but if the token doesn't match, the error also mentions other things which
+    /// could have validly appeared instead - like an operator, where a semicolon is expected after
+    /// an expression.
+    fn expect_with_alternatives(&mut self, kind: TokenKind, alternatives: &[&str]) -> Option<Option<Token>> {
         let token = self.tokens.next();
         if token.as_ref().is_some_and(|token| token.kind == kind) {
             Some(Some(token.unwrap()))
-        } else if let Some(Token { kind, span }) = token {
-            self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken(kind), span));
+        } else if let Some(Token { kind: found, span, .. }) = token {
+            let expected = std::iter::once(kind.to_string())
+                .chain(alternatives.iter().map(|alternative| alternative.to_string()))
+                .collect();
+            self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken { token: found, expected }, span));
             Some(None)
         } else {
             self.errors.push(ParseError::new(ParseErrorKind::UnexpectedEnd, self.source.eof_span()));
@@ -802,12 +1196,31 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         }
     }
 
+    /// Like [`expect`] but specifically expects a string literal, and returns its value.
+    /// 
+    /// `what` describes the string for errors, like "file path".
+    fn expect_string(&mut self, what: &str) -> Option<(String, InputSourceSpan)> {
+        match self.tokens.next() {
+            Some(Token { kind: TokenKind::String(string), span, .. }) => Some((string, span)),
+            Some(Token { kind, span, .. }) => {
+                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken { token: kind, expected: vec![what.to_owned()] }, span));
+                None
+            }
+            None => {
+                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedEnd, self.source.eof_span()));
+                None
+            }
+        }
+    }
+
     /// Like [`expect`] but specifically expects an identifier, and returns its string value.
-    fn expect_identifier(&mut self) -> Option<(String, InputSourceSpan)> {
+    /// 
+    /// `what` describes the identifier for errors, like "parameter name".
+    fn expect_identifier(&mut self, what: &str) -> Option<(String, InputSourceSpan)> {
         match self.tokens.next() {
-            Some(Token { kind: TokenKind::Identifier(id), span }) => Some((id, span)),
-            Some(Token { kind, span }) => {
-                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken(kind), span));
+            Some(Token { kind: TokenKind::Identifier(id), span, .. }) => Some((id, span)),
+            Some(Token { kind, span, .. }) => {
+                self.errors.push(ParseError::new(ParseErrorKind::UnexpectedToken { token: kind, expected: vec![what.to_owned()] }, span));
                 None
             }
             None => {
@@ -822,7 +1235,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 mod test {
     use std::rc::Rc;
 
-    use crate::{Arguments, InputSource, Node, NodeKind, Parser, tokenize};
+    use crate::{Arguments, BinaryOperator, IncludeKind, InputSource, Node, NodeKind, ParseError, ParseErrorKind, Parser, format_statements, tokenize};
 
     #[test]
     fn test_basic_parse() {
@@ -840,20 +1253,277 @@ mod test {
 
         assert_eq!(
             stmt,
-            &Node::new(
-                NodeKind::Call {
-                    name: "cube".to_owned(),
-                    arguments: Arguments {
-                        positional: vec![
-                            Node::new(NodeKind::NumberLiteral(10.0), source.span(5, 2)),
-                            Node::new(NodeKind::NumberLiteral(20.5), source.span(9, 4)),
-                            Node::new(NodeKind::NumberLiteral(30.0), source.span(15, 2)),
-                        ],
-                        named: vec![],
-                    }
-                },
-                source.span(0, 18)
-            )
+            &Node {
+                // The statement span includes the semicolon
+                statement_span: Some(source.span(0, 19)),
+                ..Node::new(
+                    NodeKind::Call {
+                        name: "cube".to_owned(),
+                        arguments: Arguments {
+                            positional: vec![
+                                Node::new(NodeKind::NumberLiteral(10.0), source.span(5, 2)),
+                                Node::new(NodeKind::NumberLiteral(20.5), source.span(9, 4)),
+                                Node::new(NodeKind::NumberLiteral(30.0), source.span(15, 2)),
+                            ],
+                            named: vec![],
+                        }
+                    },
+                    source.span(0, 18)
+                )
+            }
         )
     }
+
+    #[test]
+    fn test_units_declaration() {
+        let source = Rc::new(InputSource::new_string(
+            "units inch;".to_owned()
+        ));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+
+        let mut parser = Parser::new(source.clone(), tokens);
+        let stmts = parser.parse_statements();
+        assert_eq!(parser.errors, vec![]);
+        assert_eq!(
+            stmts,
+            vec![
+                Node {
+                    statement_span: Some(source.span(0, 11)),
+                    ..Node::new(
+                        NodeKind::UnitsDeclaration { unit: "inch".to_owned() },
+                        source.span(0, 10)
+                    )
+                }
+            ]
+        )
+    }
+
+    fn parse(code: &str) -> (Rc<InputSource>, Vec<Node>, Vec<ParseError>) {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+
+        let mut parser = Parser::new(source.clone(), tokens);
+        let stmts = parser.parse_statements();
+        (source, stmts, parser.errors)
+    }
+
+    #[test]
+    fn test_optional_final_semicolon() {
+        let cube = |source: &Rc<InputSource>, start| Node::new(
+            NodeKind::Call {
+                name: "cube".to_owned(),
+                arguments: Arguments { positional: vec![Node::new(NodeKind::NumberLiteral(5.0), source.span(start + 5, 1))], named: vec![] },
+            },
+            source.span(start, 7),
+        );
+
+        // At the end of the file
+        let (source, stmts, errors) = parse("cube(1); cube(5)");
+        assert_eq!(errors, vec![]);
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(stmts[1], Node { statement_span: Some(source.span(9, 7)), ..cube(&source, 9) });
+
+        // At the end of a block
+        let (source, stmts, errors) = parse("translate([1, 0, 0]) { cube(5) }");
+        assert_eq!(errors, vec![]);
+        let [Node { kind: NodeKind::OperatorApplication { children, .. }, .. }] = &stmts[..] else { panic!("expected one operator application") };
+        assert_eq!(children, &vec![Node { statement_span: Some(source.span(23, 7)), ..cube(&source, 23) }]);
+
+        let (_, _, errors) = parse("module m() { x = 1; cube(x) } if (true) { m() } else { cube(1) }");
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn test_missing_semicolon() {
+        // Semicolons are still needed between statements
+        let (source, _, errors) = parse("module m() {\n  x = 5\n  cube(x);\n}");
+        let [error, ..] = &errors[..] else { panic!("expected an error") };
+        assert_eq!(error.to_string(), "unexpected identifier \"cube\", expected semicolon or an operator");
+        assert_eq!(error.span, source.span(23, 4));
+
+        // A stray brace at the top level isn't the end of a block
+        let (_, _, errors) = parse("cube(5) }");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected right brace, expected semicolon or an operator"));
+    }
+
+    #[test]
+    fn test_empty_vector() {
+        let (source, stmts, errors) = parse("x = [ ];");
+        assert_eq!(errors, vec![]);
+
+        let NodeKind::Binding { value, .. } = &stmts[0].kind else { panic!("expected a binding") };
+        assert_eq!(value.kind, NodeKind::VectorLiteral(vec![]));
+        assert_eq!(value.span, source.span(4, 3));
+
+        // Empty and single-item vectors can be nested
+        let (_, stmts, errors) = parse("[[], [1]];");
+        assert_eq!(errors, vec![]);
+        let NodeKind::VectorLiteral(items) = &stmts[0].kind else { panic!("expected a vector") };
+        assert_eq!(items[0].kind, NodeKind::VectorLiteral(vec![]));
+        let NodeKind::VectorLiteral(single) = &items[1].kind else { panic!("expected a vector") };
+        assert_eq!(single.len(), 1);
+    }
+
+    #[test]
+    fn test_range_step() {
+        let range_parts = |code: &str| {
+            let (_, stmts, errors) = parse(code);
+            assert_eq!(errors, vec![]);
+            let NodeKind::VectorRangeLiteral { start, step, end } = &stmts[0].kind else { panic!("expected a range") };
+            (start.kind.clone(), step.as_ref().map(|step| step.kind.clone()), end.kind.clone())
+        };
+
+        assert_eq!(range_parts("[0:10];"), (NodeKind::NumberLiteral(0.0), None, NodeKind::NumberLiteral(10.0)));
+        assert_eq!(range_parts("[0:2.5:10];"), (NodeKind::NumberLiteral(0.0), Some(NodeKind::NumberLiteral(2.5)), NodeKind::NumberLiteral(10.0)));
+
+        let (_, _, errors) = parse("[0:1:2:3];");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected colon, expected right bracket"));
+    }
+
+    #[test]
+    fn test_dotted_range() {
+        // One targeted error, and then it's parsed as the range it was probably meant to be
+        let (source, stmts, errors) = parse("for (i = [1..5]) { cube(i); }");
+        assert_eq!(errors, vec![ParseError::new(ParseErrorKind::DotsInRange { start: 1.0, end: 5.0 }, source.span(10, 4))]);
+        assert_eq!(errors[0].to_string(), "ranges are written with a colon - did you mean `[1:5]`?");
+        let NodeKind::ForLoop { loop_source: range, .. } = &stmts[0].kind else { panic!("expected a for loop") };
+        assert!(matches!(range.kind, NodeKind::VectorRangeLiteral { step: None, .. }));
+
+        // Decimals and other stray dots are left alone
+        let (_, _, errors) = parse("[1.5:2.5];");
+        assert_eq!(errors, vec![]);
+        let (_, _, errors) = parse("[1.x];");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected dot, expected comma, colon or right bracket"));
+        let (_, _, errors) = parse("x = 5.;");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected dot, expected semicolon or an operator"));
+    }
+
+    #[test]
+    fn test_include() {
+        let (source, stmts, errors) = parse("include \"lib/gears.yascad\";\nuse \"lib/bolts.yascad\";");
+        assert_eq!(errors, vec![]);
+        assert_eq!(stmts[0].kind, NodeKind::Include { kind: IncludeKind::Include, path: "lib/gears.yascad".to_owned() });
+        assert_eq!(stmts[0].span, source.span(0, 26));
+        assert_eq!(stmts[1].kind, NodeKind::Include { kind: IncludeKind::Use, path: "lib/bolts.yascad".to_owned() });
+        assert_eq!(format_statements(&stmts), "include \"lib/gears.yascad\";\nuse \"lib/bolts.yascad\";\n");
+
+        let (_, _, errors) = parse("include gears;");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected identifier \"gears\", expected file path"));
+    }
+
+    #[test]
+    fn test_reassignment() {
+        let (source, stmts, errors) = parse("total = 0;\nset total = total + 1;\nif (wide) { set width = 20 }");
+        assert_eq!(errors, vec![]);
+        let NodeKind::Reassignment { name, value } = &stmts[1].kind else { panic!("expected a reassignment") };
+        assert_eq!(name, "total");
+        assert!(matches!(value.kind, NodeKind::BinaryOperation { op: BinaryOperator::Add, .. }));
+        assert_eq!(stmts[1].span, source.span(11, 21));
+        assert_eq!(format_statements(&stmts), "total = 0;\nset total = total + 1;\nif (wide) {\n    set width = 20;\n}\n");
+
+        let (_, _, errors) = parse("set 5 = 1;");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected number \"5\", expected binding name"));
+        let (_, _, errors) = parse("set total;");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected semicolon, expected equals"));
+    }
+
+    #[test]
+    fn test_function_definition() {
+        let (source, stmts, errors) = parse("/// The distance between teeth.\nfunction pitch(teeth, size = 2) = teeth * size;\ncube(pitch(10));");
+        assert_eq!(errors, vec![]);
+        let NodeKind::FunctionDefinition { name, parameters, body, doc } = &stmts[0].kind else { panic!("expected a function definition") };
+        assert_eq!(name, "pitch");
+        assert_eq!(parameters.required, vec!["teeth".to_owned()]);
+        assert_eq!(parameters.optional[0].0, "size");
+        assert!(matches!(body.kind, NodeKind::BinaryOperation { op: BinaryOperator::Multiply, .. }));
+        assert_eq!(doc.as_deref(), Some("The distance between teeth."));
+        assert_eq!(stmts[0].span, source.span(32, 46));
+        assert_eq!(format_statements(&stmts), "/// The distance between teeth.\nfunction pitch(teeth, size = 2) = teeth * size;\n\ncube(pitch(10));\n");
+
+        let (_, _, errors) = parse("function f(x) { x; }");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected left brace, expected equals"));
+        let (_, _, errors) = parse("function (x) = x;");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected left paren, expected function name"));
+    }
+
+    #[test]
+    fn test_modulo_precedence() {
+        // `%` binds as tightly as `*`, and both are left-associative
+        let (_, stmts, errors) = parse("1 + 5 % 3 * 2;");
+        assert_eq!(errors, vec![]);
+
+        let NodeKind::BinaryOperation { left, right, op: BinaryOperator::Add } = &stmts[0].kind else { panic!("expected an addition") };
+        assert_eq!(left.kind, NodeKind::NumberLiteral(1.0));
+        let NodeKind::BinaryOperation { left, right, op: BinaryOperator::Multiply } = &right.kind else { panic!("expected a multiplication") };
+        assert_eq!(right.kind, NodeKind::NumberLiteral(2.0));
+        let NodeKind::BinaryOperation { left, right, op: BinaryOperator::Modulo } = &left.kind else { panic!("expected a modulo") };
+        assert_eq!((&left.kind, &right.kind), (&NodeKind::NumberLiteral(5.0), &NodeKind::NumberLiteral(3.0)));
+    }
+
+    #[test]
+    fn test_logical_precedence() {
+        // `||` binds least tightly, then `&&`, then comparisons
+        let (_, stmts, errors) = parse("a || b < 1 && !c;");
+        assert_eq!(errors, vec![]);
+
+        let NodeKind::BinaryOperation { left, right, op: BinaryOperator::Or } = &stmts[0].kind else { panic!("expected an or") };
+        assert_eq!(left.kind, NodeKind::Identifier("a".to_owned()));
+        let NodeKind::BinaryOperation { left, right, op: BinaryOperator::And } = &right.kind else { panic!("expected an and") };
+        assert!(matches!(left.kind, NodeKind::BinaryOperation { op: BinaryOperator::LessThan, .. }));
+        let NodeKind::UnaryNot(value) = &right.kind else { panic!("expected a not") };
+        assert_eq!(value.kind, NodeKind::Identifier("c".to_owned()));
+    }
+
+    #[test]
+    fn test_ternary_conditional() {
+        // `?` binds less tightly than `||`, and chains to the right
+        let (_, stmts, errors) = parse("x = a || b ? 1 : c ? 2 : 3;");
+        assert_eq!(errors, vec![]);
+        let NodeKind::Binding { value, .. } = &stmts[0].kind else { panic!("expected a binding") };
+        let NodeKind::TernaryConditional { condition, true_value, false_value } = &value.kind else { panic!("expected a ternary") };
+        assert!(matches!(condition.kind, NodeKind::BinaryOperation { op: BinaryOperator::Or, .. }));
+        assert_eq!(true_value.kind, NodeKind::NumberLiteral(1.0));
+        assert!(matches!(false_value.kind, NodeKind::TernaryConditional { .. }));
+        assert_eq!(format_statements(&stmts), "x = a || b ? 1 : c ? 2 : 3;\n");
+
+        // Inside a range, the ternary is part of the start or end
+        let (_, stmts, errors) = parse("[0 : a ? 1 : 2];");
+        assert_eq!(errors, vec![]);
+        let NodeKind::VectorRangeLiteral { end, step: None, .. } = &stmts[0].kind else { panic!("expected a range") };
+        assert!(matches!(end.kind, NodeKind::TernaryConditional { .. }));
+
+        // A ternary within another operator needs parentheses to keep its meaning
+        let (_, stmts, _) = parse("x = (a ? 1 : 2) + 3; y = (a ? b : c) ? 1 : 2;");
+        assert_eq!(format_statements(&stmts), "x = (a ? 1 : 2) + 3;\ny = (a ? b : c) ? 1 : 2;\n");
+
+        let (_, _, errors) = parse("x = a ? 1;");
+        assert_eq!(errors.first().map(ToString::to_string).as_deref(), Some("unexpected semicolon, expected colon"));
+    }
+
+    /// The message of the first error from parsing `code`. Later errors are often just knock-on
+    /// effects of the first.
+    fn parse_error(code: &str) -> String {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+
+        let mut parser = Parser::new(source.clone(), tokens);
+        parser.parse_statements();
+        parser.errors.first().expect("no errors").to_string()
+    }
+
+    #[test]
+    fn test_expected_tokens() {
+        assert_eq!(parse_error("cube(10) }"), "unexpected right brace, expected semicolon or an operator");
+        assert_eq!(parse_error("x = ;"), "unexpected semicolon, expected an expression");
+        assert_eq!(parse_error("module (a) {}"), "unexpected left paren, expected module name");
+        assert_eq!(parse_error("operator o(1) {}"), "unexpected number \"1\", expected parameter name");
+        assert_eq!(parse_error("cube(1 x);"), "unexpected identifier \"x\", expected comma or right paren");
+        assert_eq!(parse_error("x = [1; 2];"), "unexpected semicolon, expected comma, colon or right bracket");
+        assert_eq!(parse_error("if (true) {} else cube(1);"), "unexpected identifier \"cube\", expected keyword \"if\" or left brace");
+        assert_eq!(parse_error("for (1 = [0:2]) {}"), "unexpected number \"1\", expected loop variable name");
+        assert_eq!(parse_error("x = a.[1];"), "unexpected left bracket, expected field name");
+    }
 }
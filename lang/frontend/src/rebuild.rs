@@ -0,0 +1,384 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{Arguments, Node, NodeKind, Parameters};
+
+/// The names which a top-level statement defines, and the names it refers to.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StatementNames {
+    /// Names which the statement adds to the top-level scope, or might change with `set`.
+    ///
+    /// Like [`Self::referenced`], this includes every name which is `set` anywhere within the
+    /// statement, even if it's really local.
+    pub defined: HashSet<String>,
+
+    /// Every name used anywhere within the statement, including within definition bodies.
+    ///
+    /// This errs on the side of including too much - names which are only local to the statement,
+    /// like loop variables and parameters, are included too.
+    pub referenced: HashSet<String>,
+}
+
+impl StatementNames {
+    /// Collect the names defined and referenced by a top-level statement.
+    pub fn of_statement(node: &Node) -> Self {
+        let mut names = Self::default();
+        match &node.kind {
+            NodeKind::Binding { name, .. }
+            | NodeKind::ModuleDefinition { name, .. }
+            | NodeKind::OperatorDefinition { name, .. }
+            | NodeKind::FunctionDefinition { name, .. } => {
+                names.defined.insert(name.clone());
+            }
+            _ => {}
+        }
+
+        names.collect_references(node);
+        names
+    }
+
+    fn collect_references(&mut self, node: &Node) {
+        match &node.kind {
+            NodeKind::NullLiteral
+            | NodeKind::NumberLiteral(_)
+            | NodeKind::BooleanLiteral(_)
+            | NodeKind::StringLiteral(_)
+            | NodeKind::ItReference
+            | NodeKind::UnitsDeclaration { .. }
+            | NodeKind::Include { .. } => {}
+
+            NodeKind::Identifier(name) => {
+                self.referenced.insert(name.clone());
+            }
+
+            NodeKind::VectorLiteral(items) => self.collect_body(items),
+            NodeKind::VectorRangeLiteral { start, step, end } => {
+                self.collect_references(start);
+                if let Some(step) = step {
+                    self.collect_references(step);
+                }
+                self.collect_references(end);
+            }
+
+            NodeKind::OperatorApplication { name, arguments, children } => {
+                self.referenced.insert(name.clone());
+                self.collect_arguments(arguments);
+                self.collect_body(children);
+            }
+            NodeKind::Call { name, arguments } => {
+                self.referenced.insert(name.clone());
+                self.collect_arguments(arguments);
+            }
+
+            NodeKind::Binding { value, .. } => self.collect_references(value),
+            NodeKind::Reassignment { name, value } => {
+                // Changing a binding is like defining it again, so that nothing which uses it can
+                // be reused
+                self.defined.insert(name.clone());
+                self.referenced.insert(name.clone());
+                self.collect_references(value);
+            }
+            NodeKind::FieldAccess { value, .. } => self.collect_references(value),
+
+            NodeKind::BinaryOperation { left, right, .. } => {
+                self.collect_references(left);
+                self.collect_references(right);
+            }
+            NodeKind::UnaryNegate(value) | NodeKind::UnaryNot(value) => self.collect_references(value),
+            NodeKind::TernaryConditional { condition, true_value, false_value } => {
+                self.collect_references(condition);
+                self.collect_references(true_value);
+                self.collect_references(false_value);
+            }
+
+            NodeKind::OperatorDefinition { parameters, body, .. }
+            | NodeKind::ModuleDefinition { parameters, body, .. } => {
+                self.collect_parameters(parameters);
+                self.collect_body(body);
+            }
+            NodeKind::FunctionDefinition { parameters, body, .. } => {
+                self.collect_parameters(parameters);
+                self.collect_references(body);
+            }
+
+            NodeKind::ForLoop { loop_source, body, .. } => {
+                self.collect_references(loop_source);
+                self.collect_body(body);
+            }
+            NodeKind::IfConditional { condition, true_body, false_body } => {
+                self.collect_references(condition);
+                self.collect_body(true_body);
+                if let Some(false_body) = false_body {
+                    self.collect_body(false_body);
+                }
+            }
+        }
+    }
+
+    fn collect_body(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.collect_references(node);
+        }
+    }
+
+    fn collect_arguments(&mut self, arguments: &Arguments) {
+        self.collect_body(&arguments.positional);
+        for (_, value) in &arguments.named {
+            self.collect_references(value);
+        }
+    }
+
+    fn collect_parameters(&mut self, parameters: &Parameters) {
+        for (_, default) in &parameters.optional {
+            self.collect_references(default);
+        }
+    }
+}
+
+/// Which top-level statements of a model need to be interpreted again after an edit, created by
+/// [`plan_rebuild`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuildPlan {
+    /// For each new statement, the index of the old statement whose results can be used instead of
+    /// interpreting it again.
+    reused: Vec<Option<usize>>,
+}
+
+impl RebuildPlan {
+    /// A plan which interprets every one of `statement_count` statements again.
+    pub fn rebuild_all(statement_count: usize) -> Self {
+        Self { reused: vec![None; statement_count] }
+    }
+
+    /// The index of the old statement whose results can be used in place of the new statement at
+    /// `index`, or [`None`] if it must be interpreted.
+    pub fn reused_statement(&self, index: usize) -> Option<usize> {
+        self.reused.get(index).copied().flatten()
+    }
+
+    /// The indices of the new statements which must be interpreted.
+    pub fn rebuilt_statements(&self) -> Vec<usize> {
+        self.reused.iter()
+            .enumerate()
+            .filter(|(_, reused)| reused.is_none())
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Work out which top-level statements in `new` can reuse the results of a statement in `old`.
+///
+/// A statement is reused only if an old statement had exactly the same source text, and every
+/// top-level name it refers to is defined by a statement which is itself reused from the matching
+/// old statement. Anything less certain is rebuilt. Changing the `units` declaration rebuilds
+/// everything, and so does any `include` or `use`, since the file it loads can change without the
+/// source changing.
+pub fn plan_rebuild(old: &[Node], new: &[Node]) -> RebuildPlan {
+    if old.iter().chain(new).any(|node| matches!(node.kind, NodeKind::Include { .. })) {
+        return RebuildPlan::rebuild_all(new.len());
+    }
+
+    let units_declarations = |nodes: &[Node]| nodes.iter()
+        .filter_map(|node| match &node.kind {
+            NodeKind::UnitsDeclaration { unit } => Some(unit.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if units_declarations(old) != units_declarations(new) {
+        return RebuildPlan::rebuild_all(new.len());
+    }
+
+    let old_names = old.iter().map(StatementNames::of_statement).collect::<Vec<_>>();
+    let new_names = new.iter().map(StatementNames::of_statement).collect::<Vec<_>>();
+    let old_definitions = definitions(&old_names);
+    let new_definitions = definitions(&new_names);
+
+    // Identical statements are matched up in order
+    let mut unmatched_old = HashMap::<String, VecDeque<usize>>::new();
+    for (index, node) in old.iter().enumerate() {
+        unmatched_old.entry(statement_text(node)).or_default().push_back(index);
+    }
+
+    let mut reused = Vec::with_capacity(new.len());
+    for (index, node) in new.iter().enumerate() {
+        let Some(old_index) = unmatched_old.get_mut(&statement_text(node)).and_then(VecDeque::pop_front)
+        else {
+            reused.push(None);
+            continue;
+        };
+
+        let dependencies_unchanged = new_names[index].referenced.iter()
+            .all(|name| match (old_definitions.get(name.as_str()).map(Vec::as_slice), new_definitions.get(name.as_str()).map(Vec::as_slice)) {
+                // Not a top-level name, so it's a built-in or local to the statement
+                (None, None) => true,
+
+                // Defined once in both, by an earlier statement which is being reused from the
+                // same place
+                (Some(&[old_definition]), Some(&[new_definition])) =>
+                    new_definition < index && reused[new_definition] == Some(old_definition),
+
+                _ => false,
+            });
+
+        reused.push(dependencies_unchanged.then_some(old_index));
+    }
+
+    RebuildPlan { reused }
+}
+
+/// Map each defined name to the statements which define it.
+fn definitions(names: &[StatementNames]) -> HashMap<&str, Vec<usize>> {
+    let mut definitions = HashMap::<&str, Vec<usize>>::new();
+    for (index, names) in names.iter().enumerate() {
+        for name in &names.defined {
+            definitions.entry(name).or_default().push(index);
+        }
+    }
+    definitions
+}
+
+/// The source code of a whole statement.
+fn statement_text(node: &Node) -> String {
+    node.statement_span.as_ref().unwrap_or(&node.span).text()
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, Node, Parser, StatementNames, plan_rebuild, tokenize};
+
+    fn parse(code: &str) -> Vec<Node> {
+        let source = Rc::new(InputSource::new_string(code.to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+        let mut parser = Parser::new(source.clone(), tokens);
+        let stmts = parser.parse_statements();
+        assert_eq!(parser.errors, vec![]);
+        stmts
+    }
+
+    #[test]
+    fn test_statement_names() {
+        let stmts = parse("
+            module peg(height = default_height) { cylinder(radius, height); }
+            for (x = [0:count]) { translate([x * spacing, 0, 0]) peg(); }
+        ");
+
+        let names = StatementNames::of_statement(&stmts[0]);
+        assert_eq!(names.defined, ["peg".to_owned()].into());
+        assert_eq!(names.referenced, ["default_height", "cylinder", "radius", "height"].map(str::to_owned).into());
+
+        let names = StatementNames::of_statement(&stmts[1]);
+        assert!(names.defined.is_empty());
+        assert_eq!(names.referenced, ["count", "translate", "x", "spacing", "peg"].map(str::to_owned).into());
+    }
+
+    #[test]
+    fn test_plan_rebuild_changed_statement() {
+        let old = parse("
+            cube(1);
+            translate([5, 0, 0]) cube(2);
+        ");
+        let new = parse("
+            cube(1);
+            translate([5, 0, 0]) cube(3);
+        ");
+
+        let plan = plan_rebuild(&old, &new);
+        assert_eq!(plan.reused_statement(0), Some(0));
+        assert_eq!(plan.rebuilt_statements(), vec![1]);
+    }
+
+    #[test]
+    fn test_plan_rebuild_changed_binding() {
+        let old = parse("
+            size = 2;
+            other = 4;
+            cube(size);
+            sphere(other);
+            translate([size, 0, 0]) cylinder(1, 1);
+        ");
+        let new = parse("
+            size = 3;
+            other = 4;
+            cube(size);
+            sphere(other);
+            translate([size, 0, 0]) cylinder(1, 1);
+        ");
+
+        let plan = plan_rebuild(&old, &new);
+        assert_eq!(plan.rebuilt_statements(), vec![0, 2, 4]);
+        assert_eq!(plan.reused_statement(3), Some(3));
+    }
+
+    #[test]
+    fn test_plan_rebuild_changed_definition() {
+        let old = parse("
+            width = 2;
+            module peg() { cube(width); }
+            peg();
+            cube(1);
+        ");
+        let new = parse("
+            width = 5;
+            module peg() { cube(width); }
+            peg();
+            cube(1);
+        ");
+
+        // The module's body depends on the binding, so its callers do too
+        let plan = plan_rebuild(&old, &new);
+        assert_eq!(plan.rebuilt_statements(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_plan_rebuild_moved_statements() {
+        let old = parse("
+            a = cube(1);
+            cube(2);
+        ");
+        let new = parse("
+            cube(4);
+            cube(2);
+            a = cube(1);
+        ");
+
+        let plan = plan_rebuild(&old, &new);
+        assert_eq!(plan.rebuilt_statements(), vec![0]);
+        assert_eq!(plan.reused_statement(1), Some(1));
+        assert_eq!(plan.reused_statement(2), Some(0));
+    }
+
+    #[test]
+    fn test_plan_rebuild_units() {
+        let old = parse("units mm; cube(1);");
+        let new = parse("units cm; cube(1);");
+
+        assert_eq!(plan_rebuild(&old, &new).rebuilt_statements(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_plan_rebuild_reassignment() {
+        let old = parse("
+            total = 0;
+            for (i = [1:3]) { set total = total + i; }
+            cube(1);
+            cube(total);
+        ");
+        let names = StatementNames::of_statement(&old[1]);
+        assert_eq!(names.defined, ["total".to_owned()].into());
+
+        // `total` has more than one definition, so everything using it is rebuilt even though
+        // nothing changed
+        let plan = plan_rebuild(&old, &old);
+        assert_eq!(plan.rebuilt_statements(), vec![1, 3]);
+        assert_eq!(plan.reused_statement(0), Some(0));
+        assert_eq!(plan.reused_statement(2), Some(2));
+    }
+
+    #[test]
+    fn test_plan_rebuild_include() {
+        let old = parse("include \"parts.yascad\"; cube(1);");
+        assert_eq!(plan_rebuild(&old, &old).rebuilt_statements(), vec![0, 1]);
+    }
+}
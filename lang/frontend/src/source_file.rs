@@ -0,0 +1,74 @@
+use std::{cell::OnceCell, iter::Cloned, rc::Rc, slice};
+
+use crate::{InputSource, Parser, Token, TokenizeError, tokenize};
+
+/// A source, along with its tokens. These are only worked out the first time they're needed, and
+/// then shared by everything which parses the source through this.
+///
+/// Sources can't change once they're created, so the tokens never need to be worked out again.
+#[derive(Debug)]
+pub struct SourceFile {
+    source: Rc<InputSource>,
+    tokens: OnceCell<(Vec<Token>, Vec<TokenizeError>)>,
+}
+
+impl SourceFile {
+    pub fn new(source: Rc<InputSource>) -> Self {
+        Self { source, tokens: OnceCell::new() }
+    }
+
+    pub fn source(&self) -> &Rc<InputSource> {
+        &self.source
+    }
+
+    /// The tokens in the source. If there were tokenize errors, these are the tokens which could
+    /// be read around them.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokenized().0
+    }
+
+    pub fn tokenize_errors(&self) -> &[TokenizeError] {
+        &self.tokenized().1
+    }
+
+    /// A new parser over the source's tokens.
+    pub fn parser(&self) -> Parser<Cloned<slice::Iter<'_, Token>>> {
+        Parser::new(self.source.clone(), self.tokens().iter().cloned())
+    }
+
+    fn tokenized(&self) -> &(Vec<Token>, Vec<TokenizeError>) {
+        self.tokens.get_or_init(|| tokenize(self.source.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{InputSource, SourceFile, TOKENIZE_COUNT, extract_definitions, format_statements};
+
+    #[test]
+    fn test_tokenizes_once() {
+        let file = SourceFile::new(Rc::new(InputSource::new_string("module peg(r) { cylinder(5, r); }\npeg(2);".to_owned())));
+        let before = TOKENIZE_COUNT.with(|count| count.get());
+
+        let mut parser = file.parser();
+        let definitions = extract_definitions(&parser.parse_statements());
+        assert_eq!(parser.errors, vec![]);
+        assert_eq!(definitions.len(), 1);
+
+        let mut parser = file.parser();
+        let formatted = format_statements(&parser.parse_statements());
+        assert_eq!(formatted, "module peg(r) {\n    cylinder(5, r);\n}\n\npeg(2);\n");
+        assert!(file.tokenize_errors().is_empty());
+
+        assert_eq!(TOKENIZE_COUNT.with(|count| count.get()) - before, 1);
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        let file = SourceFile::new(Rc::new(InputSource::new_string("x = 1_000;".to_owned())));
+        assert_eq!(file.tokenize_errors().len(), 1);
+        assert!(!file.tokens().is_empty());
+    }
+}
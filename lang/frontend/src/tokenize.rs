@@ -1,4 +1,4 @@
-use std::{error::Error, fmt::Display, rc::Rc};
+use std::{borrow::Cow, error::Error, fmt::Display, mem, rc::Rc};
 
 use miette::Diagnostic;
 
@@ -8,19 +8,60 @@ use crate::{InputSource, InputSourceSpan};
 pub struct Token {
     pub kind: TokenKind,
     pub span: InputSourceSpan,
+
+    /// The whitespace and comments between the previous token and this one, in order.
+    /// 
+    /// Only kept by [`tokenize_with_trivia`] - otherwise, this is always empty.
+    pub leading_trivia: Vec<Trivia>,
 }
 
 impl Token {
     pub fn new(kind: TokenKind, span: InputSourceSpan) -> Self {
-        Self { kind, span }
+        Self { kind, span, leading_trivia: vec![] }
     }
 }
 
+/// Source text between tokens, which has no effect on how the source is parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: InputSourceSpan,
+}
+
+impl Trivia {
+    /// The text of the trivia if it's a comment, including its delimiters.
+    pub fn comment_text(&self) -> Option<String> {
+        matches!(self.kind, TriviaKind::LineComment | TriviaKind::BlockComment).then(|| self.span.text())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A run of whitespace, including any line breaks.
+    Whitespace,
+
+    /// A `//` comment, up to but not including the end of its line. Doc comments are tokens
+    /// instead.
+    LineComment,
+
+    /// A `/* ... */` comment.
+    BlockComment,
+
+    /// Text which couldn't be tokenized, like an unexpected character. There's an error for it too.
+    Skipped,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenKind {
     Identifier(String),
     Number(String),
 
+    /// The text of a `///` or `//!` comment, which documents the definition after it.
+    DocComment(String),
+
+    /// A string literal. Escape sequences have already been processed.
+    String(String),
+
     KwIt,
     KwOperator,
     KwModule,
@@ -31,6 +72,11 @@ pub enum TokenKind {
     KwFalse,
     KwNull,
     KwUndef,
+    KwUnits,
+    KwInclude,
+    KwUse,
+    KwSet,
+    KwFunction,
 
     LParen,
     RParen,
@@ -45,11 +91,17 @@ pub enum TokenKind {
     Semicolon,
     Dot,
     Colon,
+    Question,
 
     Plus,
     Minus,
     ForwardSlash,
     Star,
+    Percent,
+
+    DoubleAmpersand,
+    DoublePipe,
+    Exclamation,
 
     Equals,
     DoubleEquals,
@@ -60,8 +112,10 @@ pub enum TokenKind {
 impl Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TokenKind::Identifier(id) => write!(f, "identifier \"{id}\""),
-            TokenKind::Number(number) => write!(f, "number \"{number}\""),
+            TokenKind::Identifier(id) => write!(f, "identifier \"{}\"", truncate_for_display(id)),
+            TokenKind::Number(number) => write!(f, "number \"{}\"", truncate_for_display(number)),
+            TokenKind::DocComment(_) => write!(f, "doc comment"),
+            TokenKind::String(string) => write!(f, "string {:?}", truncate_for_display(string)),
             TokenKind::LParen => write!(f, "left paren"),
             TokenKind::RParen => write!(f, "right paren"),
             TokenKind::LBrace => write!(f, "left brace"),
@@ -81,16 +135,27 @@ impl Display for TokenKind {
             TokenKind::KwFalse => write!(f, "keyword \"false\""),
             TokenKind::KwNull => write!(f, "keyword \"null\""),
             TokenKind::KwUndef => write!(f, "keyword \"undef\""),
+            TokenKind::KwUnits => write!(f, "keyword \"units\""),
+            TokenKind::KwInclude => write!(f, "keyword \"include\""),
+            TokenKind::KwUse => write!(f, "keyword \"use\""),
+            TokenKind::KwSet => write!(f, "keyword \"set\""),
+            TokenKind::KwFunction => write!(f, "keyword \"function\""),
 
             TokenKind::Comma => write!(f, "comma"),
             TokenKind::Semicolon => write!(f, "semicolon"),
             TokenKind::Dot => write!(f, "dot"),
             TokenKind::Colon => write!(f, "colon"),
+            TokenKind::Question => write!(f, "question mark"),
 
             TokenKind::Plus => write!(f, "plus"),
             TokenKind::Minus => write!(f, "minus"),
             TokenKind::ForwardSlash => write!(f, "forward slash"),
             TokenKind::Star => write!(f, "star"),
+            TokenKind::Percent => write!(f, "percent"),
+
+            TokenKind::DoubleAmpersand => write!(f, "double-ampersand"),
+            TokenKind::DoublePipe => write!(f, "double-pipe"),
+            TokenKind::Exclamation => write!(f, "exclamation mark"),
 
             TokenKind::Equals => write!(f, "equals"),
             TokenKind::DoubleEquals => write!(f, "double-equals"),
@@ -127,74 +192,282 @@ impl Error for TokenizeError {}
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenizeErrorKind {
     UnexpectedChar(char),
+    UnterminatedString,
+    UnknownEscape(char),
+
+    /// A number was split up with a separator, like `1_000` or `1 000`, which isn't supported.
+    SeparatedNumber(char),
+
+    /// A token was longer than the tokenizer's maximum, so its text wasn't kept.
+    TokenTooLong { token: &'static str, length: usize, max: usize },
 }
 
 impl Display for TokenizeErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TokenizeErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {c}"),
+            TokenizeErrorKind::UnterminatedString => write!(f, "string is missing a closing quote"),
+            TokenizeErrorKind::UnknownEscape(c) => write!(f, "unknown escape sequence \\{c}"),
+            TokenizeErrorKind::SeparatedNumber('_') => write!(f, "numbers can't be split up with underscores - write the digits together"),
+            TokenizeErrorKind::SeparatedNumber(' ') => write!(f, "numbers can't be split up with spaces - if these are separate numbers, put a comma between them"),
+            TokenizeErrorKind::SeparatedNumber(c) => write!(f, "numbers can't be split up with {c:?}"),
+            TokenizeErrorKind::TokenTooLong { token, length, max } => write!(f, "{token} is {length} characters long, but the most allowed is {max}"),
         }
     }
 }
 
+/// How many characters of a token's text are shown in diagnostics, before it's cut short.
+pub const MAX_DISPLAYED_TOKEN_LENGTH: usize = 64;
+
+/// The longest identifier, number, string or doc comment which [`tokenize`] will accept, in
+/// characters.
+pub const DEFAULT_MAX_TOKEN_LENGTH: usize = 4096;
+
+/// Shortens `text` to [`MAX_DISPLAYED_TOKEN_LENGTH`] characters with an ellipsis, so that echoing a
+/// huge identifier in a diagnostic doesn't make it unreadable.
+pub fn truncate_for_display(text: &str) -> Cow<'_, str> {
+    match text.char_indices().nth(MAX_DISPLAYED_TOKEN_LENGTH) {
+        Some((cut, _)) => Cow::Owned(format!("{}…", &text[..cut])),
+        None => Cow::Borrowed(text),
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// How many times [`tokenize`] has run on this thread, so that tests can check it isn't
+    /// repeated unnecessarily.
+    pub(crate) static TOKENIZE_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 pub fn tokenize(source: Rc<InputSource>) -> (Vec<Token>, Vec<TokenizeError>) {
+    tokenize_with_max_length(source, DEFAULT_MAX_TOKEN_LENGTH)
+}
+
+/// Like [`tokenize`], but with a different limit on how many characters long an identifier, number,
+/// string or doc comment can be.
+///
+/// Tokens over the limit are skipped over with a [`TokenizeErrorKind::TokenTooLong`] error, rather
+/// than being kept in memory.
+pub fn tokenize_with_max_length(source: Rc<InputSource>, max_length: usize) -> (Vec<Token>, Vec<TokenizeError>) {
+    let (tokens, _, errors) = tokenize_keeping_trivia(source, max_length, false);
+    (tokens, errors)
+}
+
+/// Like [`tokenize`], but also keep the whitespace and comments between tokens, as each token's
+/// [`leading_trivia`](Token::leading_trivia). This is for tools like editors, which need to know
+/// exactly how the source was written.
+/// 
+/// The trivia after the last token is returned separately. Together with the tokens' own text,
+/// the trivia covers every character of the source, in order.
+pub fn tokenize_with_trivia(source: Rc<InputSource>) -> (Vec<Token>, Vec<Trivia>, Vec<TokenizeError>) {
+    tokenize_keeping_trivia(source, DEFAULT_MAX_TOKEN_LENGTH, true)
+}
+
+fn tokenize_keeping_trivia(source: Rc<InputSource>, max_length: usize, keep_trivia: bool) -> (Vec<Token>, Vec<Trivia>, Vec<TokenizeError>) {
+    #[cfg(test)]
+    TOKENIZE_COUNT.with(|count| count.set(count.get() + 1));
+
     let mut tokens = vec![];
     let mut errors = vec![];
+    let mut trivia: Vec<Trivia> = vec![];
 
     let source_for_chars = source.clone();
     let mut chars = source_for_chars.content.chars().enumerate().peekable();
+    let char_count = if keep_trivia { source.content.chars().count() } else { 0 };
 
     while let Some((start_index, char)) = chars.next() {
+        let tokens_before = tokens.len();
+        let next_char = chars.peek().map(|(_, char)| *char);
+
         match char {
             _ if char.is_ascii_digit() => {
                 let mut buffer = char.to_string();
+                let mut length = 1;
                 let mut had_decimal_point = false;
 
-                while let Some((_, char)) = chars.peek() {
-                    if char.is_ascii_digit() {
+                while let Some(&(_, char)) = chars.peek() {
+                    // A dot is only a decimal point if there's a digit after it, so that `5.` is a
+                    // number and a dot, and `1..5` doesn't become `1.` and `.5`
+                    let is_decimal_point = !had_decimal_point && char == '.' && {
+                        let mut lookahead = chars.clone();
+                        lookahead.next();
+                        lookahead.peek().is_some_and(|(_, char)| char.is_ascii_digit())
+                    };
+
+                    if char.is_ascii_digit() || is_decimal_point {
                         let (_, char) = chars.next().unwrap();
-                        buffer.push(char)
-                    } else if !had_decimal_point && *char == '.' {
-                        chars.next().unwrap();
-                        had_decimal_point = true;
-                        buffer.push('.');
+                        had_decimal_point |= char == '.';
+                        length += 1;
+                        if length <= max_length {
+                            buffer.push(char);
+                        }
                     } else {
                         break;
                     }
                 }
 
-                let length = buffer.len();
-                tokens.push(Token::new(TokenKind::Number(buffer), source.span(start_index, length)));
+                // Digit separators would otherwise be read as the start of another token, giving a
+                // confusing error later on
+                let mut separator = None;
+                loop {
+                    let mut lookahead = chars.clone();
+                    let mut separator_chars = vec![];
+                    while let Some((_, char)) = lookahead.peek() && (*char == '_' || *char == ' ') {
+                        separator_chars.push(*char);
+                        lookahead.next();
+                    }
+                    if separator_chars.is_empty() || !lookahead.peek().is_some_and(|(_, char)| char.is_ascii_digit()) {
+                        break;
+                    }
+
+                    separator.get_or_insert(separator_chars[0]);
+                    chars = lookahead;
+                    length += separator_chars.len();
+                    while chars.peek().is_some_and(|(_, char)| char.is_ascii_digit() || *char == '.') {
+                        chars.next();
+                        length += 1;
+                    }
+                }
+
+                if let Some(separator) = separator {
+                    errors.push(TokenizeError::new(TokenizeErrorKind::SeparatedNumber(separator), source.span(start_index, length)));
+                } else if length > max_length {
+                    errors.push(TokenizeError::new(
+                        TokenizeErrorKind::TokenTooLong { token: "number", length, max: max_length },
+                        source.span(start_index, length),
+                    ));
+                } else {
+                    tokens.push(Token::new(TokenKind::Number(buffer), source.span(start_index, length)));
+                }
             }
 
             _ if char.is_alphabetic() || char == '_' => {
                 let mut buffer = char.to_string();
+                let mut length = 1;
 
                 while let Some((_, char)) = chars.peek() {
                     if char.is_alphanumeric() || *char == '_' {
                         let (_, char) = chars.next().unwrap();
-                        buffer.push(char)
+                        length += 1;
+                        if length <= max_length {
+                            buffer.push(char);
+                        }
                     } else {
                         break;
                     }
                 }
 
-                let span = source.span(start_index, buffer.len());
-                let token_kind = match lookup_keyword(&buffer) {
-                    Some(kw) => kw,
-                    None => TokenKind::Identifier(buffer),
-                };
-                tokens.push(Token::new(token_kind, span));
+                let span = source.span(start_index, length);
+                if length > max_length {
+                    errors.push(TokenizeError::new(
+                        TokenizeErrorKind::TokenTooLong { token: "identifier", length, max: max_length },
+                        span,
+                    ));
+                } else {
+                    let token_kind = match lookup_keyword(&buffer) {
+                        Some(kw) => kw,
+                        None => TokenKind::Identifier(buffer),
+                    };
+                    tokens.push(Token::new(token_kind, span));
+                }
+            }
+
+            '"' => {
+                let mut buffer = String::new();
+                let mut length = 0;
+                let mut end_index = None;
+
+                while let Some((index, char)) = chars.next() {
+                    match char {
+                        '"' => {
+                            end_index = Some(index);
+                            break;
+                        },
+
+                        '\\' => {
+                            let escaped = match chars.next() {
+                                Some((_, '"')) => '"',
+                                Some((_, '\\')) => '\\',
+                                Some((_, 'n')) => '\n',
+                                Some((_, 't')) => '\t',
+                                Some((escape_index, other)) => {
+                                    errors.push(TokenizeError::new(
+                                        TokenizeErrorKind::UnknownEscape(other),
+                                        source.span(escape_index - 1, 2),
+                                    ));
+                                    continue;
+                                },
+                                None => break,
+                            };
+                            length += 1;
+                            if length <= max_length {
+                                buffer.push(escaped);
+                            }
+                        },
+
+                        _ => {
+                            length += 1;
+                            if length <= max_length {
+                                buffer.push(char);
+                            }
+                        },
+                    }
+                }
+
+                match end_index {
+                    Some(end_index) if length > max_length => errors.push(TokenizeError::new(
+                        TokenizeErrorKind::TokenTooLong { token: "string", length, max: max_length },
+                        source.span(start_index, end_index - start_index + 1),
+                    )),
+                    Some(end_index) => {
+                        let span = source.span(start_index, end_index - start_index + 1);
+                        tokens.push(Token::new(TokenKind::String(buffer), span));
+                    },
+                    None => errors.push(TokenizeError::new(
+                        TokenizeErrorKind::UnterminatedString,
+                        source.span(start_index, 1),
+                    )),
+                }
             }
 
             // Line comment
             '/' if chars.peek().is_some_and(|(_, char)| *char == '/') => {
-                loop {
-                    let Some((_, char)) = chars.next()
-                    else { break };
+                chars.next();
+
+                // `///` and `//!` are doc comments, but `////` (like a divider) is ordinary
+                let mut lookahead = chars.clone();
+                let is_doc = match lookahead.next() {
+                    Some((_, '/')) => !lookahead.peek().is_some_and(|(_, char)| *char == '/'),
+                    Some((_, '!')) => true,
+                    _ => false,
+                };
+                if is_doc {
+                    chars.next();
+                }
+
+                // Only doc comments have their text kept
+                let mut text = String::new();
+                let mut length = 0;
 
-                    if char == '\n' {
-                        break
+                // The line break is left to be read as whitespace
+                while let Some((_, char)) = chars.next_if(|(_, char)| *char != '\n') {
+                    length += 1;
+                    if is_doc && length <= max_length {
+                        text.push(char);
+                    }
+                }
+
+                if is_doc {
+                    let span = source.span(start_index, length + 3);
+                    if length > max_length {
+                        errors.push(TokenizeError::new(
+                            TokenizeErrorKind::TokenTooLong { token: "doc comment", length, max: max_length },
+                            span,
+                        ));
+                    } else {
+                        // Conventionally there's a space after the marker, which isn't part of the text
+                        let text = text.strip_prefix(' ').unwrap_or(&text).trim_end().to_owned();
+                        tokens.push(Token::new(TokenKind::DocComment(text), span));
                     }
                 }
             }
@@ -244,6 +517,9 @@ pub fn tokenize(source: Rc<InputSource>) -> (Vec<Token>, Vec<TokenizeError>) {
             ':' => {
                 tokens.push(Token::new(TokenKind::Colon, source.span(start_index, 1)))
             }
+            '?' => {
+                tokens.push(Token::new(TokenKind::Question, source.span(start_index, 1)))
+            }
 
             '+' => {
                 tokens.push(Token::new(TokenKind::Plus, source.span(start_index, 1)))
@@ -257,6 +533,22 @@ pub fn tokenize(source: Rc<InputSource>) -> (Vec<Token>, Vec<TokenizeError>) {
             '*' => {
                 tokens.push(Token::new(TokenKind::Star, source.span(start_index, 1)))
             }
+            '%' => {
+                tokens.push(Token::new(TokenKind::Percent, source.span(start_index, 1)))
+            }
+
+            // There are no bitwise operators, so a single `&` or `|` is an unexpected character
+            '&' if chars.peek().is_some_and(|(_, c)| *c == '&') => {
+                chars.next().unwrap();
+                tokens.push(Token::new(TokenKind::DoubleAmpersand, source.span(start_index, 2)))
+            }
+            '|' if chars.peek().is_some_and(|(_, c)| *c == '|') => {
+                chars.next().unwrap();
+                tokens.push(Token::new(TokenKind::DoublePipe, source.span(start_index, 2)))
+            }
+            '!' => {
+                tokens.push(Token::new(TokenKind::Exclamation, source.span(start_index, 1)))
+            }
 
             '=' => {
                 if chars.peek().is_some_and(|(_, c)| *c == '=') {
@@ -294,9 +586,31 @@ pub fn tokenize(source: Rc<InputSource>) -> (Vec<Token>, Vec<TokenizeError>) {
                 ))
             }
         }
+
+        if !keep_trivia {
+            continue;
+        }
+
+        // Anything which didn't become a token is trivia, which goes before the next token
+        if tokens.len() > tokens_before {
+            tokens[tokens_before].leading_trivia = mem::take(&mut trivia);
+            continue;
+        }
+        let end_index = chars.peek().map_or(char_count, |(index, _)| *index);
+        let kind = match (char, next_char) {
+            _ if char.is_whitespace() => TriviaKind::Whitespace,
+            ('/', Some('/')) => TriviaKind::LineComment,
+            ('/', Some('*')) => TriviaKind::BlockComment,
+            _ => TriviaKind::Skipped,
+        };
+        match trivia.last_mut() {
+            Some(last) if kind == TriviaKind::Whitespace && last.kind == TriviaKind::Whitespace =>
+                last.span = source.span(last.span.start, end_index - last.span.start),
+            _ => trivia.push(Trivia { kind, span: source.span(start_index, end_index - start_index) }),
+        }
     }
 
-    (tokens, errors)
+    (tokens, trivia, errors)
 }
 
 fn lookup_keyword(name: &str) -> Option<TokenKind> {
@@ -311,15 +625,20 @@ fn lookup_keyword(name: &str) -> Option<TokenKind> {
         "false" => Some(TokenKind::KwFalse),
         "null" => Some(TokenKind::KwNull),
         "undef" => Some(TokenKind::KwUndef),
+        "units" => Some(TokenKind::KwUnits),
+        "include" => Some(TokenKind::KwInclude),
+        "use" => Some(TokenKind::KwUse),
+        "set" => Some(TokenKind::KwSet),
+        "function" => Some(TokenKind::KwFunction),
         _ => None,
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::rc::Rc;
+    use std::{rc::Rc, time::{Duration, Instant}};
 
-    use crate::{InputSource, Token, TokenKind, tokenize};
+    use crate::{DEFAULT_MAX_TOKEN_LENGTH, InputSource, InputSourceSpan, Parser, Token, TokenKind, TokenizeError, TokenizeErrorKind, TriviaKind, tokenize, tokenize_with_max_length, tokenize_with_trivia};
 
     #[test]
     fn test_basic_tokenize() {
@@ -344,4 +663,199 @@ mod test {
             ]
         )
     }
+
+    #[test]
+    fn test_trailing_dot_tokenize() {
+        let kinds = |code: &str| {
+            let (tokens, errors) = tokenize(Rc::new(InputSource::new_string(code.to_owned())));
+            assert!(errors.is_empty());
+            tokens.into_iter().map(|token| token.kind).collect::<Vec<_>>()
+        };
+
+        // A dot is only part of a number if a digit follows it
+        assert_eq!(kinds("5."), vec![TokenKind::Number("5".to_owned()), TokenKind::Dot]);
+        assert_eq!(kinds("5.x"), vec![TokenKind::Number("5".to_owned()), TokenKind::Dot, TokenKind::Identifier("x".to_owned())]);
+        assert_eq!(kinds("1..5"), vec![TokenKind::Number("1".to_owned()), TokenKind::Dot, TokenKind::Dot, TokenKind::Number("5".to_owned())]);
+        assert_eq!(kinds("1.5"), vec![TokenKind::Number("1.5".to_owned())]);
+        assert_eq!(kinds("1.5.2"), vec![TokenKind::Number("1.5".to_owned()), TokenKind::Dot, TokenKind::Number("2".to_owned())]);
+    }
+
+    #[test]
+    fn test_string_tokenize() {
+        let source = Rc::new(InputSource::new_string(
+            r#"part("lid \"A\"\n");"#.to_owned()
+        ));
+        let (tokens, errors) = tokenize(source.clone());
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens,
+            vec![
+                Token::new(TokenKind::Identifier("part".to_owned()),     source.span(0, 4)),
+                Token::new(TokenKind::LParen,                            source.span(4, 1)),
+                Token::new(TokenKind::String("lid \"A\"\n".to_owned()), source.span(5, 13)),
+                Token::new(TokenKind::RParen,                            source.span(18, 1)),
+                Token::new(TokenKind::Semicolon,                         source.span(19, 1)),
+            ]
+        );
+
+        let (_, errors) = tokenize(Rc::new(InputSource::new_string(r#"x = "a\q"; y = "b"#.to_owned())));
+        assert_eq!(
+            errors.into_iter().map(|e| e.kind).collect::<Vec<_>>(),
+            vec![TokenizeErrorKind::UnknownEscape('q'), TokenizeErrorKind::UnterminatedString],
+        );
+    }
+
+    #[test]
+    fn test_logical_operator_tokenize() {
+        let source = Rc::new(InputSource::new_string("!a && b || c".to_owned()));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens.into_iter().map(|token| token.kind).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Exclamation,
+                TokenKind::Identifier("a".to_owned()),
+                TokenKind::DoubleAmpersand,
+                TokenKind::Identifier("b".to_owned()),
+                TokenKind::DoublePipe,
+                TokenKind::Identifier("c".to_owned()),
+            ],
+        );
+
+        // There are no bitwise operators
+        let source = Rc::new(InputSource::new_string("a & b | c".to_owned()));
+        let (_, errors) = tokenize(source.clone());
+        assert_eq!(
+            errors,
+            vec![
+                TokenizeError::new(TokenizeErrorKind::UnexpectedChar('&'), source.span(2, 1)),
+                TokenizeError::new(TokenizeErrorKind::UnexpectedChar('|'), source.span(6, 1)),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_separated_number_tokenize() {
+        let source = Rc::new(InputSource::new_string("cube(1_000); cube(10 000 0);".to_owned()));
+        let (_, errors) = tokenize(source.clone());
+        assert_eq!(
+            errors,
+            vec![
+                TokenizeError::new(TokenizeErrorKind::SeparatedNumber('_'), source.span(5, 5)),
+                TokenizeError::new(TokenizeErrorKind::SeparatedNumber(' '), source.span(18, 8)),
+            ],
+        );
+
+        // Separators which aren't between digits are fine
+        let (tokens, errors) = tokenize(Rc::new(InputSource::new_string("x = 10 ;y = 2 _a".to_owned())));
+        assert!(errors.is_empty());
+        assert_eq!(tokens.len(), 8);
+    }
+
+    #[test]
+    fn test_long_token_tokenize() {
+        let identifier = "a".repeat(1_000_000);
+        let source = Rc::new(InputSource::new_string(format!("x = {identifier}; y = 1;")));
+
+        let start = Instant::now();
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // The identifier is skipped, but the rest is still tokenized
+        let max = DEFAULT_MAX_TOKEN_LENGTH;
+        assert_eq!(
+            errors,
+            vec![TokenizeError::new(TokenizeErrorKind::TokenTooLong { token: "identifier", length: 1_000_000, max }, source.span(4, 1_000_000))],
+        );
+        assert_eq!(errors[0].to_string(), format!("identifier is 1000000 characters long, but the most allowed is {max}"));
+        assert_eq!(tokens.len(), 7);
+
+        // The limit applies to each kind of token with text
+        let (_, errors) = tokenize_with_max_length(Rc::new(InputSource::new_string(
+            "/// abcdef\nx = \"abcdef\" + 123456;\n// abcdef".to_owned()
+        )), 5);
+        assert_eq!(
+            errors.into_iter().map(|e| e.kind).collect::<Vec<_>>(),
+            vec![
+                TokenizeErrorKind::TokenTooLong { token: "doc comment", length: 7, max: 5 },
+                TokenizeErrorKind::TokenTooLong { token: "string", length: 6, max: 5 },
+                TokenizeErrorKind::TokenTooLong { token: "number", length: 6, max: 5 },
+            ],
+        );
+    }
+
+    #[test]
+    fn test_long_token_display() {
+        // Long identifiers are cut short when a parse error echoes them
+        let identifier = "a".repeat(DEFAULT_MAX_TOKEN_LENGTH);
+        let source = Rc::new(InputSource::new_string(format!("x = 1 {identifier};")));
+        let (tokens, errors) = tokenize(source.clone());
+        assert!(errors.is_empty());
+        let mut parser = Parser::new(source.clone(), tokens);
+        parser.parse_statements();
+        assert_eq!(
+            parser.errors[0].to_string(),
+            format!("unexpected identifier \"{}…\", expected semicolon or an operator", "a".repeat(64)),
+        );
+
+        assert_eq!(TokenKind::String("é".repeat(100)).to_string(), format!("string \"{}…\"", "é".repeat(64)));
+        assert_eq!(TokenKind::Number("123".to_owned()).to_string(), "number \"123\"");
+    }
+
+    #[test]
+    fn test_trivia_round_trip() {
+        let text = |span: &InputSourceSpan| span.source.content().chars().skip(span.start).take(span.length).collect::<String>();
+
+        for code in [
+            "cube(10);",
+            "// Lid\n/// The lid\nmodule lid() {\n    /* TODO: hinge */ cube([10, 10, 1]);   \n}\n",
+            "x = 1;\r\n// Windows\r\ny = \"a\\tb\";\r\n",
+            "  \n\t a = 5. // trailing   \n  /* unterminated",
+            "a = 1 # 2; b = \"unterminated\n",
+            "",
+        ] {
+            let source = Rc::new(InputSource::new_string(code.to_owned()));
+            let (tokens, trailing_trivia, _) = tokenize_with_trivia(source.clone());
+
+            let mut written = String::new();
+            for token in &tokens {
+                for trivia in &token.leading_trivia {
+                    written.push_str(&text(&trivia.span));
+                }
+                written.push_str(&text(&token.span));
+            }
+            for trivia in &trailing_trivia {
+                written.push_str(&text(&trivia.span));
+            }
+            assert_eq!(written, code);
+
+            // The tokens themselves are the same as usual
+            let (plain_tokens, _) = tokenize(source.clone());
+            assert_eq!(tokens.iter().map(|token| &token.kind).collect::<Vec<_>>(), plain_tokens.iter().map(|token| &token.kind).collect::<Vec<_>>());
+            assert!(plain_tokens.iter().all(|token| token.leading_trivia.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_trivia_kinds() {
+        let source = Rc::new(InputSource::new_string(" \n // a\n/* b */\t# x".to_owned()));
+        let (tokens, trailing_trivia, errors) = tokenize_with_trivia(source.clone());
+        assert_eq!(errors.len(), 1);
+
+        let [x] = &tokens[..] else { panic!("expected one token") };
+        assert_eq!(
+            x.leading_trivia.iter().map(|trivia| (trivia.kind, trivia.span.start, trivia.span.length)).collect::<Vec<_>>(),
+            vec![
+                (TriviaKind::Whitespace, 0, 3),
+                (TriviaKind::LineComment, 3, 4),
+                (TriviaKind::Whitespace, 7, 1),
+                (TriviaKind::BlockComment, 8, 7),
+                (TriviaKind::Whitespace, 15, 1),
+                (TriviaKind::Skipped, 16, 1),
+                (TriviaKind::Whitespace, 17, 1),
+            ],
+        );
+        assert!(trailing_trivia.is_empty());
+    }
 }
@@ -0,0 +1,127 @@
+//! Rough estimates of the material and time needed to 3D print a model, for comparing versions of
+//! it while iterating.
+//!
+//! These come from the model's volume and surface area alone, without slicing, so they can be far
+//! off for any particular printer and settings. The assumptions are the constants in this module,
+//! which describe typical settings for PLA on a hobbyist FDM printer.
+
+use std::{fmt::Display, time::Duration};
+
+/// The density of PLA, in g/cm³, which is the density used unless another is given.
+pub const PLA_DENSITY: f64 = 1.24;
+
+/// How thick the walls, floors and roofs of a print are assumed to be, in millimetres. This is
+/// three perimeters from a 0.4 mm nozzle.
+const SHELL_THICKNESS_MM: f64 = 1.2;
+
+/// The fraction of a print's inside, past its shell, which is assumed to be filled by infill.
+const INFILL_FRACTION: f64 = 0.2;
+
+/// How much plastic the printer is assumed to extrude each second, in mm³, averaged over a whole
+/// print including travel moves and slower perimeters.
+const EXTRUSION_RATE_MM3_PER_SECOND: f64 = 5.0;
+
+/// An estimate of what it would take to 3D print a model. See the [module documentation](self) for
+/// how rough this is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintEstimate {
+    /// The volume of the model, in cm³.
+    pub volume_cm3: f64,
+
+    /// The area of the model's surface, in cm².
+    pub surface_area_cm2: f64,
+
+    /// The density of the material, in g/cm³.
+    pub density: f64,
+
+    /// The mass of the model, in grams, if it was made entirely of the material.
+    pub mass_g: f64,
+
+    /// Roughly how long the model would take to print, with a shell and sparse infill.
+    pub print_time: Duration,
+}
+
+impl PrintEstimate {
+    /// Estimate printing a model with the given volume and surface area, in mm³ and mm², from a
+    /// material with `density` in g/cm³.
+    pub fn from_measurements(volume_mm3: f64, surface_area_mm2: f64, density: f64) -> Self {
+        let volume_cm3 = volume_mm3 / 1000.0;
+
+        // Thin parts are all shell, so the shell can't be more than the whole part
+        let shell_mm3 = (surface_area_mm2 * SHELL_THICKNESS_MM).min(volume_mm3);
+        let infill_mm3 = (volume_mm3 - shell_mm3) * INFILL_FRACTION;
+        let print_seconds = (shell_mm3 + infill_mm3) / EXTRUSION_RATE_MM3_PER_SECOND;
+
+        Self {
+            volume_cm3,
+            surface_area_cm2: surface_area_mm2 / 100.0,
+            density,
+            mass_g: volume_cm3 * density,
+            print_time: Duration::from_secs_f64(print_seconds.max(0.0)),
+        }
+    }
+
+    /// The print time as hours and minutes, like `~2 h 15 min`, rounded to the nearest minute.
+    pub fn format_print_time(&self) -> String {
+        let minutes = (self.print_time.as_secs_f64() / 60.0).round() as u64;
+        match (minutes / 60, minutes % 60) {
+            (0, minutes) => format!("~{minutes} min"),
+            (hours, 0) => format!("~{hours} h"),
+            (hours, minutes) => format!("~{hours} h {minutes} min"),
+        }
+    }
+}
+
+impl Display for PrintEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} cm³, {:.1} g at {} g/cm³, {} to print (rough estimate)",
+            self.volume_cm3, self.mass_g, self.density, self.format_print_time(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{PLA_DENSITY, PrintEstimate};
+
+    #[test]
+    fn test_cube_estimate() {
+        // A 20 mm cube, which is mostly inside rather than shell
+        let estimate = PrintEstimate::from_measurements(8000.0, 2400.0, PLA_DENSITY);
+        assert_eq!(estimate.volume_cm3, 8.0);
+        assert_eq!(estimate.surface_area_cm2, 24.0);
+        assert!((estimate.mass_g - 9.92).abs() < 1e-9);
+
+        // 2880 mm³ of shell, and 20% of the remaining 5120 mm³ as infill
+        assert_eq!(estimate.print_time, Duration::from_secs_f64((2880.0 + 1024.0) / 5.0));
+        assert_eq!(estimate.format_print_time(), "~13 min");
+        assert_eq!(estimate.to_string(), "8.00 cm³, 9.9 g at 1.24 g/cm³, ~13 min to print (rough estimate)");
+
+        // Density only affects the mass
+        let dense = PrintEstimate::from_measurements(8000.0, 2400.0, 2.0);
+        assert_eq!(dense.mass_g, 16.0);
+        assert_eq!(dense.print_time, estimate.print_time);
+    }
+
+    #[test]
+    fn test_thin_estimate() {
+        // A 100x100x1 mm plate is all shell, so it's printed solid
+        let estimate = PrintEstimate::from_measurements(10_000.0, 20_400.0, PLA_DENSITY);
+        assert_eq!(estimate.print_time, Duration::from_secs(2000));
+        assert_eq!(estimate.format_print_time(), "~33 min");
+    }
+
+    #[test]
+    fn test_format_print_time() {
+        let time = |seconds: f64| PrintEstimate { print_time: Duration::from_secs_f64(seconds), ..PrintEstimate::from_measurements(0.0, 0.0, PLA_DENSITY) }
+            .format_print_time();
+        assert_eq!(time(0.0), "~0 min");
+        assert_eq!(time(89.0), "~1 min");
+        assert_eq!(time(3600.0), "~1 h");
+        assert_eq!(time(3.0 * 3600.0 + 25.0 * 60.0), "~3 h 25 min");
+    }
+}
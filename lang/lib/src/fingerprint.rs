@@ -0,0 +1,96 @@
+//! Recording which source an exported file was built from.
+//!
+//! Exporters embed a [`BuildFingerprint`] wherever their format has room for it, so that a file can
+//! be traced back to the exact source and version of YASCAD which produced it.
+
+use std::{fmt::Display, path::PathBuf};
+
+use sha2::{Digest, Sha256};
+use yascad_frontend::InputSource;
+
+/// The version of YASCAD doing the building.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The largest header of a binary STL file.
+const BINARY_STL_HEADER_LENGTH: usize = 80;
+
+/// Identifies what a model was built from - the SHA-256 hash of its source and any files it read,
+/// and the version of YASCAD which built it.
+///
+/// This displays in a short form, like `sha256:1a2b3c4d v0.1.0`, for places with little room, such
+/// as STL solid names. Use [`Self::source_sha256`] for the full hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BuildFingerprint {
+    /// The SHA-256 hash of the source, along with the files it read - see [`Self::from_build`].
+    pub source_hash: [u8; 32],
+
+    /// The version of YASCAD which built the model.
+    pub version: &'static str,
+}
+
+impl BuildFingerprint {
+    /// The fingerprint of building `source` with this version of YASCAD.
+    ///
+    /// The hash is of the source's content, so it matches the hash of the file it was read from.
+    pub fn from_source(source: &InputSource) -> Self {
+        Self { source_hash: Sha256::digest(source.content()).into(), version: VERSION }
+    }
+
+    /// The fingerprint of building `source`, which read `files` (with the hash of each file's
+    /// contents) while it was being built, like with `include` or `surface`.
+    ///
+    /// If no files were read, this is the same as [`Self::from_source`]. Otherwise, the hash is of
+    /// the source's hash followed by each file's, so that changing a file changes the fingerprint
+    /// even though the source is the same.
+    pub fn from_build(source: &InputSource, files: &[(PathBuf, [u8; 32])]) -> Self {
+        let fingerprint = Self::from_source(source);
+        if files.is_empty() {
+            return fingerprint;
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(fingerprint.source_hash);
+        for (_, file_hash) in files {
+            hasher.update(file_hash);
+        }
+        Self { source_hash: hasher.finalize().into(), version: VERSION }
+    }
+
+    /// The fingerprint of several models combined into one export.
+    ///
+    /// A single fingerprint is returned as-is. Otherwise, the hash is of each model's hash in turn,
+    /// so it depends on every source and on their order.
+    pub fn combine(fingerprints: &[BuildFingerprint]) -> Self {
+        if let [fingerprint] = fingerprints {
+            return *fingerprint;
+        }
+
+        let mut hasher = Sha256::new();
+        for fingerprint in fingerprints {
+            hasher.update(fingerprint.source_hash);
+        }
+        Self { source_hash: hasher.finalize().into(), version: VERSION }
+    }
+
+    /// The full hash of the source, as 64 lowercase hex digits.
+    pub fn source_sha256(&self) -> String {
+        self.source_hash.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    /// The text to put in the 80-byte header of a binary STL file: `name` followed by this
+    /// fingerprint. The name is shortened if needed, so that the fingerprint always fits.
+    pub fn binary_stl_header(&self, name: &str) -> String {
+        let fingerprint = format!(" {self}");
+        let mut name_length = name.len().min(BINARY_STL_HEADER_LENGTH.saturating_sub(fingerprint.len()));
+        while !name.is_char_boundary(name_length) {
+            name_length -= 1;
+        }
+        format!("{}{fingerprint}", &name[..name_length])
+    }
+}
+
+impl Display for BuildFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sha256:{} v{}", &self.source_sha256()[..8], self.version)
+    }
+}
@@ -1,28 +1,446 @@
-use std::rc::Rc;
+//! Building YASCAD models, and getting their geometry back out.
+//!
+//! Everything needed to use the results is exported from here, including the geometry types from
+//! the Manifold bindings, so that applications don't need to depend on those directly:
+//!
+//! ```no_run
+//! use std::fs::File;
+//!
+//! use yascad_lang::{InputSource, build, export::{TextStlOptions, write_stl_from_meshgl}};
+//!
+//! let model = build(InputSource::new_string("cube(10); cylinder(20, 3);".to_owned())).unwrap();
+//! let manifold = model.manifold();
+//!
+//! let bounding_box = manifold.bounding_box();
+//! println!("{} tall", bounding_box.max_point().z - bounding_box.min_point().z);
+//!
+//! let mut file = File::create("model.stl").unwrap();
+//! write_stl_from_meshgl(&manifold.meshgl(), "model", &mut file, &TextStlOptions::default()).unwrap();
+//! ```
 
-use manifold_rs::Manifold;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+mod precision;
+pub use precision::{ExportPrecision, export_rescale_factor};
+
+mod fingerprint;
+pub use fingerprint::{BuildFingerprint, VERSION};
+
+mod estimate;
+pub use estimate::{PLA_DENSITY, PrintEstimate};
+
+use std::{rc::Rc, time::Duration};
+
+pub use manifold_rs::{BoundingBox, CrossSection, JoinType, Manifold, ManifoldError, Matrix3x4, MeshGL, MeshTriangle, Polygons, Rectangle, Vec2, Vec3};
 
 use yascad_backend::Interpreter;
-use yascad_frontend::{Parser, tokenize};
-pub use yascad_frontend::{InputSource, InputSourceOrigin, ParseError, TokenizeError};
-pub use yascad_backend::RuntimeError;
+use yascad_frontend::{Node, SourceFile};
+pub use yascad_frontend::{openscad, plan_rebuild, InputSource, InputSourceOrigin, InputSourceSpan, ParseError, PlainDiagnostic, RebuildPlan, Severity, SourceDiagnostic, TokenizeError, render_plain};
+pub use yascad_backend::{BuiltinDoc, BuiltinKind, DEFAULT_SHAPE_TOLERANCE, FileAccessPolicy, GeometryDisposition, ObjectSnapshot, PRELUDE_SOURCE, ParameterDoc, PreviewPart, Profile, ProfileEntry, ProfileEntryKind, RuntimeError, RuntimeWarning, RuntimeWarningKind, SceneEntry, SceneEntryGeometry, SceneGroup, SceneInstances, ScenePart, ScenePreview, StatementResults, Step, StepCapture, Unit, all_builtins, format_builtin_reference, format_profile, format_scene_dump, shape_difference};
+
+/// Writing geometry to files and preparing meshes for display.
+pub mod export {
+    pub use manifold_rs::ext::{MeshGLExt, PolyNode, Stl, TextStlOptions, ThreeMfObject, write_3mf, write_binary_stl_from_meshgls, write_stl_from_meshgl, write_svg};
+}
 
 #[derive(Debug, Clone)]
 pub enum LangError {
     Tokenize(Vec<TokenizeError>),
     Parser(Vec<ParseError>),
     Runtime(RuntimeError),
+
+    /// The source built, but noticed something suspicious while [`BuildOptions::deny_warnings`]
+    /// was set.
+    Warnings(Vec<RuntimeWarning>),
+}
+
+/// Settings which control how a source file is built.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildOptions {
+    /// Multiplies the number of segments used for curved geometry. Lower values build faster, at
+    /// the cost of accuracy. Segment counts never go below 3, however low this is.
+    pub segment_scale: f64,
+
+    /// How much to record about each operator applied while building, available afterwards from
+    /// [`BuiltModel::steps`].
+    pub step_capture: StepCapture,
+
+    /// Whether to time each top-level statement and each call to a user-defined module or
+    /// operator, available afterwards from [`BuiltModel::profile`].
+    pub profile: bool,
+
+    /// Whether to record what each top-level statement did, so that [`rebuild_with_options`] can
+    /// reuse it for statements which haven't changed.
+    pub record_statements: bool,
+
+    /// The longest which the geometry operations of any one built-in operator can take before the
+    /// build fails, or [`None`] for no limit. Limiting this makes building slower overall, so is
+    /// mostly useful to keep interactive previews responsive.
+    pub operation_timeout: Option<Duration>,
+
+    /// Roughly how much memory, in bytes, the model's geometry can take up before the build fails,
+    /// or [`None`] for no limit. Like [`Self::operation_timeout`], this makes building slower, but
+    /// stops huge models from running out of memory. The estimate is available afterwards from
+    /// [`BuiltModel::estimated_memory`].
+    pub memory_budget: Option<usize>,
+
+    /// Whether to warn about numbers which look like they were written with a comma as a decimal
+    /// separator, like `10,5`.
+    pub locale_hints: bool,
+
+    /// Whether to load the prelude, which defines modules like `hexagon` for every model. Its
+    /// source is [`PRELUDE_SOURCE`].
+    pub prelude: bool,
+
+    /// Which files builtins like `surface` are allowed to read. Models from untrusted sources
+    /// should be built with a stricter policy than the default, which allows any file.
+    pub file_access: FileAccessPolicy,
+
+    /// Whether to track which primitive each triangle of the model came from, for
+    /// [`BuiltModel::triangle_provenance`]. This makes booleans a little slower.
+    pub track_originals: bool,
+
+    /// Whether any warnings should fail the build, with [`LangError::Warnings`], rather than being
+    /// available from [`BuiltModel::warnings`]. Useful for CI, where nobody reads the warnings.
+    pub deny_warnings: bool,
+
+    /// Whether an undefined identifier error should list every binding in scope where it happened,
+    /// as well as suggesting the closest name.
+    pub verbose_errors: bool,
 }
 
+impl BuildOptions {
+    /// Options suited to an interactive preview, where building quickly is more important than
+    /// accuracy.
+    pub fn preview() -> Self {
+        Self { segment_scale: 0.5, ..Self::default() }
+    }
+}
+
+impl Default for BuildOptions {
+    /// Full-quality options, suitable for export.
+    fn default() -> Self {
+        Self { segment_scale: 1.0, step_capture: StepCapture::Off, profile: false, record_statements: false, operation_timeout: None, memory_budget: None, locale_hints: true, prelude: true, file_access: FileAccessPolicy::default(), track_originals: false, deny_warnings: false, verbose_errors: false }
+    }
+}
+
+/// Which axis points upwards in exported geometry.
+/// 
+/// Models are always built Z-up, as is usual for CAD and 3D printing, but some other tools (like
+/// game engines) expect Y-up instead. Converting between them happens at export time, so the model
+/// doesn't need to change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpAxis {
+    Y,
+    #[default]
+    Z,
+}
+
+impl UpAxis {
+    /// Look up an axis by its lowercase name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "y" => Some(UpAxis::Y),
+            "z" => Some(UpAxis::Z),
+            _ => None,
+        }
+    }
+
+    /// The transformation from the Z-up coordinates which models are built in, into coordinates
+    /// where this axis is up.
+    /// 
+    /// For Y-up, this is a quarter-turn around the X axis, so that the front of the model (towards
+    /// -Y) faces towards +Z.
+    pub fn transform(&self) -> Matrix3x4 {
+        match self {
+            UpAxis::Y => Matrix3x4::rotation(-90.0, 0.0, 0.0),
+            UpAxis::Z => Matrix3x4::identity(),
+        }
+    }
+
+    /// Convert a manifold from Z-up coordinates into coordinates where this axis is up.
+    pub fn apply(&self, manifold: &Manifold) -> Manifold {
+        match self {
+            UpAxis::Y => manifold.transform(&self.transform()),
+            UpAxis::Z => manifold.clone(),
+        }
+    }
+}
+
+/// Where to move a finished model before exporting it, so that it ends up near the origin however
+/// its parts were placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Centering {
+    /// Move the centre of the model's bounding box to the origin.
+    Center,
+
+    /// Centre the model's bounding box in X and Y, with its bottom at Z=0 - like it's sitting on a
+    /// print bed.
+    OnPlate,
+}
+
+impl Centering {
+    /// Look up a centering by its name, as given to the GUI's export command.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "center" => Some(Centering::Center),
+            "on-plate" => Some(Centering::OnPlate),
+            _ => None,
+        }
+    }
+
+    /// The translation which moves these manifolds, together, into place.
+    /// 
+    /// Empty manifolds have no position, so don't count. If there's no geometry at all, nothing
+    /// needs to move.
+    pub fn offset<'a>(&self, manifolds: impl IntoIterator<Item = &'a Manifold>) -> Vec3<f64> {
+        let bounds = manifolds.into_iter()
+            .filter(|manifold| !manifold.is_empty())
+            .map(|manifold| {
+                let bounding_box = manifold.bounding_box();
+                (bounding_box.min_point(), bounding_box.max_point())
+            })
+            .reduce(|(min_a, max_a), (min_b, max_b)| (
+                Vec3::new(min_a.x.min(min_b.x), min_a.y.min(min_b.y), min_a.z.min(min_b.z)),
+                Vec3::new(max_a.x.max(max_b.x), max_a.y.max(max_b.y), max_a.z.max(max_b.z)),
+            ));
+        let Some((min, max)) = bounds
+        else { return Vec3::zero() };
+
+        let z = match self {
+            Centering::Center => (min.z + max.z) / 2.0,
+            Centering::OnPlate => min.z,
+        };
+        Vec3::new(-(min.x + max.x) / 2.0, -(min.y + max.y) / 2.0, -z)
+    }
+}
+
+/// Everything done to a finished model to put it into place for exporting, without changing the
+/// model itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportPlacement {
+    pub centering: Option<Centering>,
+
+    /// A uniform scale for the exported coordinates, like 0.001 to export a millimetre model in
+    /// metres. This is applied after centering, so the model stays centred.
+    pub scale: f64,
+
+    pub up: UpAxis,
+}
+
+impl Default for ExportPlacement {
+    fn default() -> Self {
+        Self { centering: None, scale: 1.0, up: UpAxis::default() }
+    }
+}
+
+impl ExportPlacement {
+    /// Move these manifolds into place, all together. If they were centred, returns how far they
+    /// were moved.
+    pub fn apply(&self, manifolds: Vec<&mut Manifold>) -> Option<Vec3<f64>> {
+        let offset = self.centering.map(|centering| centering.offset(manifolds.iter().map(|manifold| &**manifold)));
+
+        for manifold in manifolds {
+            if let Some(offset) = offset {
+                *manifold = manifold.translate(offset.x, offset.y, offset.z);
+            }
+            if self.scale != 1.0 {
+                *manifold = manifold.scale(self.scale, self.scale, self.scale);
+            }
+            *manifold = self.up.apply(manifold);
+        }
+
+        offset
+    }
+}
+
+/// The result of successfully building a source file.
+/// 
+/// Geometry can be extracted from this in different forms, alongside any model-wide settings which
+/// the source declared.
+pub struct BuiltModel {
+    interpreter: Interpreter,
+    options: BuildOptions,
+
+    /// The parsed top-level statements, to compare against when rebuilding.
+    statements: Vec<Node>,
+
+    /// If this model was built by [`rebuild_with_options`], which of its statements were reused.
+    rebuild_plan: Option<RebuildPlan>,
+
+    fingerprint: BuildFingerprint,
+}
+
+impl BuiltModel {
+    /// A single manifold, containing all geometry in the scene.
+    pub fn manifold(&self) -> Manifold {
+        self.interpreter.build_top_level_manifold()
+    }
+
+    /// The geometry in the scene arranged for an interactive preview, where repeated geometry from
+    /// patterns is kept as instances of one manifold so that it can be drawn cheaply.
+    pub fn preview(&self) -> ScenePreview {
+        self.interpreter.build_top_level_preview()
+    }
+
+    /// Each physical geometry in the scene as a separate part.
+    /// 
+    /// See [`Interpreter::build_top_level_parts`] for details on the ordering of parts.
+    pub fn parts(&self) -> Vec<ScenePart> {
+        self.interpreter.build_top_level_parts()
+    }
+
+    /// The physical geometry in the scene, combined into one manifold for each name given by
+    /// `part`.
+    /// 
+    /// See [`Interpreter::build_top_level_groups`] for details on the ordering of groups.
+    pub fn groups(&self) -> Vec<SceneGroup> {
+        self.interpreter.build_top_level_groups()
+    }
+
+    /// A description of every entry in the geometry table, in the order it was added, for
+    /// debugging.
+    /// 
+    /// Use [`format_scene_dump`] to turn this into the text format printed by the CLI.
+    pub fn scene(&self) -> Vec<SceneEntry> {
+        self.interpreter.dump_scene()
+    }
+
+    /// The result of each operator applied while building, if enabled with
+    /// [`BuildOptions::step_capture`].
+    pub fn steps(&self) -> &[Step] {
+        self.interpreter.steps()
+    }
+
+    /// How long each part of the model took to build, if enabled with [`BuildOptions::profile`].
+    pub fn profile(&self) -> Option<&Profile> {
+        self.interpreter.profile()
+    }
+
+    /// Everything suspicious noticed while building, which didn't stop the model from building.
+    pub fn warnings(&self) -> &[RuntimeWarning] {
+        self.interpreter.warnings()
+    }
+
+    /// Roughly estimate what it would take to 3D print the whole model from a material with
+    /// `density`, in g/cm³. See [`PrintEstimate`] for how rough this is.
+    pub fn print_estimate(&self, density: f64) -> PrintEstimate {
+        let manifold = self.manifold();
+        let millimetres = self.units().in_millimetres();
+        PrintEstimate::from_measurements(
+            manifold.volume() * millimetres.powi(3),
+            manifold.surface_area() * millimetres.powi(2),
+            density,
+        )
+    }
+
+    /// Roughly how many bytes of memory the model's geometry takes up, if it was built with a
+    /// [`BuildOptions::memory_budget`]. Without one, this is always 0.
+    pub fn estimated_memory(&self) -> usize {
+        self.interpreter.estimated_memory()
+    }
+
+    /// The source code of the primitive which each triangle of `mesh` came from, in the same order
+    /// as its triangles. `mesh` must be of this model's geometry, like [`Self::manifold`].
+    /// 
+    /// The model must have been built with [`BuildOptions::track_originals`], or every triangle's
+    /// source is [`None`]. Only primitives like `cube` are tracked, so triangles first created by
+    /// other operations, like extrusions, have no source either.
+    pub fn triangle_provenance(&self, mesh: &MeshGL) -> Vec<Option<&InputSourceSpan>> {
+        if !self.options.track_originals {
+            return vec![None; mesh.count_triangles()];
+        }
+
+        mesh.triangle_original_ids()
+            .into_iter()
+            .map(|id| self.interpreter.original_provenance(id))
+            .collect()
+    }
+
+    /// Identifies the source which this model was built from, to embed in exported files.
+    pub fn fingerprint(&self) -> BuildFingerprint {
+        self.fingerprint
+    }
+
+    /// The base unit which the model's lengths are measured in.
+    pub fn units(&self) -> Unit {
+        self.interpreter.units()
+    }
+
+    /// If this model was built by [`rebuild_with_options`] from a previous model it could reuse,
+    /// which statements were reused.
+    pub fn rebuild_plan(&self) -> Option<&RebuildPlan> {
+        self.rebuild_plan.as_ref()
+    }
+}
+
+/// Build a source file, so that its geometry can be extracted.
+pub fn build(source: InputSource) -> Result<BuiltModel, LangError> {
+    build_with_options(source, BuildOptions::default())
+}
+
+/// Like [`build`], but with control over how the source is built.
+pub fn build_with_options(source: InputSource, options: BuildOptions) -> Result<BuiltModel, LangError> {
+    interpret_source(source, options, None)
+}
+
+/// Build a new version of a model's source, reusing the results of top-level statements which
+/// haven't changed since `previous` was built - and don't depend on anything which has.
+///
+/// Results can only be reused if `previous` was built with [`BuildOptions::record_statements`] and
+/// the same segment scale as `options`. Otherwise, this is the same as [`build_with_options`].
+/// Either way, the result is the same as building `source` from scratch.
+pub fn rebuild_with_options(previous: &BuiltModel, source: InputSource, options: BuildOptions) -> Result<BuiltModel, LangError> {
+    let previous = (previous.options.segment_scale == options.segment_scale && previous.options.track_originals == options.track_originals)
+        .then_some(previous)
+        .and_then(|previous| Some((previous.statements.as_slice(), previous.interpreter.statement_results()?)));
+    interpret_source(source, options, previous)
+}
+
+/// Build a source file into a single manifold, containing all geometry in the scene.
 pub fn build_model(source: InputSource) -> Result<Manifold, LangError> {
-    let source = Rc::new(source);
+    Ok(build(source)?.manifold())
+}
+
+/// Build a source file, but keep each physical geometry in the scene as a separate part rather than
+/// combining them into one manifold.
+/// 
+/// See [`Interpreter::build_top_level_parts`] for details on the ordering of parts.
+pub fn build_model_parts(source: InputSource) -> Result<Vec<ScenePart>, LangError> {
+    Ok(build(source)?.parts())
+}
+
+/// Combine the groups of several separately-built models into one assembly, moving each model by
+/// an offset first.
+///
+/// Groups with the same name in different models are combined, like groups within a model are.
+/// Groups are ordered by the first model they appear in.
+pub fn merge_groups(models: impl IntoIterator<Item = (Vec<SceneGroup>, Vec3<f64>)>) -> Vec<SceneGroup> {
+    let mut merged: Vec<SceneGroup> = vec![];
+
+    for (groups, offset) in models {
+        for group in groups {
+            let manifold = group.manifold.translate(offset.x, offset.y, offset.z);
+            if let Some(existing) = merged.iter_mut().find(|existing| existing.name == group.name) {
+                existing.manifold = existing.manifold.union(&manifold);
+            } else {
+                merged.push(SceneGroup { name: group.name, manifold });
+            }
+        }
+    }
+
+    merged
+}
+
+fn interpret_source(source: InputSource, options: BuildOptions, previous: Option<(&[Node], &StatementResults)>) -> Result<BuiltModel, LangError> {
+    let file = SourceFile::new(Rc::new(source));
 
-    let (tokens, errors) = tokenize(source.clone());
-    if !errors.is_empty() {
-        return Err(LangError::Tokenize(errors))
+    if !file.tokenize_errors().is_empty() {
+        return Err(LangError::Tokenize(file.tokenize_errors().to_vec()))
     }
 
-    let mut parser = Parser::new(source.clone(), tokens);
+    let mut parser = file.parser();
     let stmts = parser.parse_statements();
 
     if !parser.errors.is_empty() {
@@ -30,9 +448,31 @@ pub fn build_model(source: InputSource) -> Result<Manifold, LangError> {
     }
 
     let mut interpreter = Interpreter::new();
-    match interpreter.interpret_top_level(&stmts) {
+    interpreter.set_segment_scale(options.segment_scale);
+    interpreter.set_step_capture(options.step_capture);
+    interpreter.set_profiling(options.profile);
+    interpreter.set_statement_recording(options.record_statements);
+    interpreter.set_operation_timeout(options.operation_timeout);
+    interpreter.set_memory_budget(options.memory_budget);
+    interpreter.set_locale_hints(options.locale_hints);
+    interpreter.set_load_prelude(options.prelude);
+    interpreter.set_file_access_policy(options.file_access.clone());
+    interpreter.set_original_tracking(options.track_originals);
+    interpreter.set_verbose_errors(options.verbose_errors);
+
+    let rebuild_plan = previous.map(|(previous_stmts, _)| plan_rebuild(previous_stmts, &stmts));
+    let result = match (&rebuild_plan, previous) {
+        (Some(plan), Some((_, previous_results))) => interpreter.interpret_top_level_reusing(&stmts, plan, previous_results),
+        _ => interpreter.interpret_top_level(&stmts),
+    };
+
+    match result {
+        Ok(_) if options.deny_warnings && !interpreter.warnings().is_empty() => {
+            Err(LangError::Warnings(interpreter.warnings().to_vec()))
+        }
         Ok(_) => {
-            Ok(interpreter.build_top_level_manifold())
+            let fingerprint = BuildFingerprint::from_build(file.source(), interpreter.files_read());
+            Ok(BuiltModel { interpreter, options, statements: stmts, rebuild_plan, fingerprint })
         }
         Err(error) => {
             Err(LangError::Runtime(error))
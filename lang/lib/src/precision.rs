@@ -0,0 +1,93 @@
+//! Checking whether geometry survives being exported.
+//!
+//! Models are built with 64-bit coordinates, but meshes (and so STL and 3MF files) only have 32-bit
+//! ones. A float has the same number of significant digits whatever its size, so features which
+//! are tiny compared to how far they are from the origin can collapse, leaving cracks which
+//! slicers complain about.
+
+use manifold_rs::{Manifold, MeshGL, Vec3};
+
+/// How many times larger than the rounding error an edge must be to be safely exported. Smaller
+/// edges can be noticeably distorted, even if they don't collapse entirely.
+const SAFE_EDGE_TO_ERROR_RATIO: f64 = 100.0;
+
+/// How much exported coordinates might be damaged by rounding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportPrecision {
+    /// The largest absolute value of any coordinate.
+    pub largest_coordinate: f64,
+
+    /// The length of the shortest edge of any triangle, standing in for the size of the smallest
+    /// feature.
+    pub shortest_edge: f64,
+
+    /// The furthest which any coordinate might have moved when rounded.
+    pub rounding_error: f64,
+}
+
+impl ExportPrecision {
+    /// Measure a mesh which is about to be exported. If the coordinates will be written with a
+    /// limited number of decimal places, that rounding is accounted for too.
+    ///
+    /// Returns [`None`] for an empty mesh, which has nothing to damage.
+    pub fn measure(mesh: &MeshGL, decimal_places: Option<usize>) -> Option<Self> {
+        let positions = mesh.vertex_positions();
+        let triangles = mesh.triangle_vertex_data();
+        Self::measure_triangles(&positions, &triangles, decimal_places)
+    }
+
+    fn measure_triangles(positions: &[Vec3<f32>], triangles: &[usize], decimal_places: Option<usize>) -> Option<Self> {
+        let largest_coordinate = positions.iter()
+            .flat_map(|position| [position.x, position.y, position.z])
+            .map(|coordinate| (coordinate as f64).abs())
+            .reduce(f64::max)?;
+
+        // Each edge is shared by two triangles, so this measures them all twice, but that's
+        // cheaper than finding the unique ones
+        let shortest_edge = triangles.chunks_exact(3)
+            .flat_map(|triangle| [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])])
+            .map(|(a, b)| distance(positions[a], positions[b]))
+            .reduce(f64::min)?;
+
+        // The gap between adjacent 32-bit floats is at most this much, relative to their size
+        let float_error = largest_coordinate * f32::EPSILON as f64;
+        let decimal_error = decimal_places.map_or(0.0, |places| 0.5 * 10f64.powi(-(places as i32)));
+
+        Some(Self { largest_coordinate, shortest_edge, rounding_error: float_error.max(decimal_error) })
+    }
+
+    /// Whether the smallest feature is close enough to the rounding error that the exported mesh
+    /// could be distorted or cracked.
+    pub fn is_lossy(&self) -> bool {
+        self.shortest_edge < self.rounding_error * SAFE_EDGE_TO_ERROR_RATIO
+    }
+}
+
+/// The power of ten to scale these manifolds by, together, so that their largest coordinate is
+/// between 100 and 1000.
+///
+/// That keeps very small or very large models well within the range which can be written
+/// accurately with a few decimal places. Because floats have the same relative precision at any
+/// size, this can't help a feature which is tiny compared to the rest of the model - centering the
+/// model can, if it's far from the origin.
+pub fn export_rescale_factor<'a>(manifolds: impl IntoIterator<Item = &'a Manifold>) -> f64 {
+    let largest_coordinate = manifolds.into_iter()
+        .filter(|manifold| !manifold.is_empty())
+        .flat_map(|manifold| {
+            let bounding_box = manifold.bounding_box();
+            let (min, max) = (bounding_box.min_point(), bounding_box.max_point());
+            [min.x, min.y, min.z, max.x, max.y, max.z]
+        })
+        .map(f64::abs)
+        .reduce(f64::max);
+
+    match largest_coordinate {
+        Some(largest) if largest > 0.0 => 10f64.powi(2 - largest.log10().floor() as i32),
+        _ => 1.0,
+    }
+}
+
+fn distance(a: Vec3<f32>, b: Vec3<f32>) -> f64 {
+    let (dx, dy, dz) = (a.x as f64 - b.x as f64, a.y as f64 - b.y as f64, a.z as f64 - b.z as f64);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
@@ -0,0 +1,275 @@
+//! Helpers for testing models from Rust, like checking that a library's parts come out the right
+//! size in `#[test]` functions.
+//!
+//! Enabled by the `testing` feature. Every assertion panics with a description of the model when
+//! it fails, so that a failing test says what was actually built.
+//!
+//! All measurements are in the model's base unit, as set by its `units` declaration.
+
+use std::{fmt::Debug, ops::RangeBounds};
+
+use manifold_rs::{Manifold, Vec2, Vec3, ext::{Stl, TextStlOptions}};
+use sha2::{Digest, Sha256};
+
+use crate::{BuiltModel, DEFAULT_SHAPE_TOLERANCE, InputSource, InputSourceOrigin, LangError, RuntimeError, shape_difference};
+
+/// How far apart two coordinates can be while still counting as the same, to allow for floating
+/// point error.
+const COORDINATE_TOLERANCE: f64 = 1e-6;
+
+/// The grid which [`stl_digest`] snaps numbers to. It's a power of two, so that the snapped numbers
+/// are exact.
+const DIGEST_STEP: f32 = 1.0 / 1024.0;
+
+/// A successfully built model, with assertions about its geometry.
+pub struct TestModel {
+    model: BuiltModel,
+    manifold: Manifold,
+}
+
+/// Build some model source for testing, panicking if it has any errors.
+pub fn build(source: &str) -> TestModel {
+    match crate::build(InputSource::new_string(source.to_owned())) {
+        Ok(model) => TestModel { manifold: model.manifold(), model },
+        Err(error) => panic!("model failed to build:\n{}", describe_error(&error)),
+    }
+}
+
+/// Build some model source for testing, panicking unless it fails with a runtime error, which is
+/// returned.
+pub fn runtime_error(source: InputSource) -> RuntimeError {
+    let name = match source.origin() {
+        InputSourceOrigin::File(path) => path.display().to_string(),
+        _ => source.content().to_owned(),
+    };
+    match crate::build(source) {
+        Err(LangError::Runtime(error)) => error,
+        Err(error) => panic!("expected a runtime error from {name}, but got:\n{}", describe_error(&error)),
+        Ok(_) => panic!("expected a runtime error from {name}, but it built"),
+    }
+}
+
+/// Build some model source for testing, panicking unless it fails with a runtime error with the
+/// given message. The error is returned, for checking anything else about it.
+pub fn assert_runtime_error(source: &str, message: &str) -> RuntimeError {
+    let error = runtime_error(InputSource::new_string(source.to_owned()));
+    assert_eq!(error.kind.to_string(), message, "wrong error from {source}");
+    error
+}
+
+/// Measurements of a horizontal slice through a model, from [`TestModel::slice_at`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceMeasurements {
+    pub area: f64,
+
+    /// The number of separate outlines and holes in the slice.
+    pub polygon_count: usize,
+
+    /// The lower corner of the slice's bounding rectangle.
+    pub min: Vec2<f64>,
+
+    /// The upper corner of the slice's bounding rectangle.
+    pub max: Vec2<f64>,
+}
+
+impl TestModel {
+    /// All geometry in the model, combined into one manifold.
+    pub fn manifold(&self) -> &Manifold {
+        &self.manifold
+    }
+
+    /// The full result of building the model, for anything which the assertions don't cover.
+    pub fn model(&self) -> &BuiltModel {
+        &self.model
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.manifold.volume()
+    }
+
+    /// The number of separate parts in the model - see [`BuiltModel::parts`].
+    pub fn part_count(&self) -> usize {
+        self.model.parts().len()
+    }
+
+    /// A summary of the model, included in the message of any failed assertion.
+    pub fn stats(&self) -> String {
+        let bounding_box = self.manifold.bounding_box();
+        format!(
+            "volume {}, bounding box {} to {}, {} parts",
+            self.volume(), format_point(bounding_box.min_point()), format_point(bounding_box.max_point()), self.part_count(),
+        )
+    }
+
+    /// Assert that the model's volume is within a range.
+    pub fn assert_volume_within(&self, range: impl RangeBounds<f64> + Debug) -> &Self {
+        let volume = self.volume();
+        if !range.contains(&volume) {
+            self.fail(&format!("expected volume within {range:?}, but it was {volume}"));
+        }
+        self
+    }
+
+    /// Assert that the model's bounding box has exactly these lower and upper corners, allowing
+    /// for floating point error.
+    pub fn assert_bbox(&self, min: [f64; 3], max: [f64; 3]) -> &Self {
+        let bounding_box = self.manifold.bounding_box();
+        let (actual_min, actual_max) = (bounding_box.min_point(), bounding_box.max_point());
+
+        let close = |actual: Vec3<f64>, [x, y, z]: [f64; 3]|
+            (actual.x - x).abs() <= COORDINATE_TOLERANCE
+                && (actual.y - y).abs() <= COORDINATE_TOLERANCE
+                && (actual.z - z).abs() <= COORDINATE_TOLERANCE;
+        if !close(actual_min, min) || !close(actual_max, max) {
+            self.fail(&format!(
+                "expected bounding box {} to {}, but it was {} to {}",
+                format_point(Vec3::new(min[0], min[1], min[2])), format_point(Vec3::new(max[0], max[1], max[2])),
+                format_point(actual_min), format_point(actual_max),
+            ));
+        }
+        self
+    }
+
+    /// Assert that the model has this many separate parts.
+    pub fn assert_part_count(&self, count: usize) -> &Self {
+        let actual = self.part_count();
+        if actual != count {
+            self.fail(&format!("expected {count} parts, but there were {actual}"));
+        }
+        self
+    }
+
+    /// Assert that this model is the same shape as another, like after refactoring the source which
+    /// builds it. See [`Self::assert_same_shape_within`].
+    pub fn assert_same_shape(&self, other: &TestModel) -> &Self {
+        self.assert_same_shape_within(other, DEFAULT_SHAPE_TOLERANCE)
+    }
+
+    /// Assert that this model is the same shape as another, where the volume of their difference
+    /// relative to the larger volume is at most `tolerance`.
+    pub fn assert_same_shape_within(&self, other: &TestModel, tolerance: f64) -> &Self {
+        let difference = shape_difference(&self.manifold, &other.manifold);
+        if difference > tolerance {
+            self.fail(&format!(
+                "expected the same shape as a model with {}, but they differ by {difference} of the larger volume",
+                other.stats(),
+            ));
+        }
+        self
+    }
+
+    /// Measure the cross section where the model crosses the horizontal plane at height `z`.
+    pub fn slice_at(&self, z: f64) -> SliceMeasurements {
+        let slice = self.manifold.slice(z);
+        let bounds = slice.bounding_rectangle();
+        SliceMeasurements {
+            area: slice.area(),
+            polygon_count: slice.polygons().count_polygons(),
+            min: bounds.min_point(),
+            max: bounds.max_point(),
+        }
+    }
+
+    fn fail(&self, message: &str) -> ! {
+        panic!("{message}\nmodel has {}", self.stats())
+    }
+}
+
+/// A short, deterministic text summary of an STL model, for snapshot tests where the full model
+/// would be too big to keep around.
+///
+/// The summary has the model's triangle and vertex counts, its bounding box, and the SHA-256 hash
+/// of the model as a binary STL. The model is quantised and sorted first, so that floating point
+/// error which differs between platforms doesn't change the summary.
+pub fn stl_digest(stl: &Stl) -> String {
+    let mut stl = stl.clone();
+    stl.quantise(DIGEST_STEP);
+    stl.sort();
+
+    let mut bytes = vec![];
+    stl.write_binary_stl(&mut bytes).expect("writing to a vec can't fail");
+    let hash = Sha256::digest(&bytes).iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+
+    // Numbers are written with a fixed precision, rather than relying on how floats display
+    let options = TextStlOptions { precision: Some(3) };
+    let format_point = |point: Vec3<f32>| format!(
+        "[{}, {}, {}]",
+        options.format_number(point.x), options.format_number(point.y), options.format_number(point.z),
+    );
+    let bounds = match stl.bounds() {
+        Some((min, max)) => format!("{} to {}", format_point(min), format_point(max)),
+        None => "empty".to_owned(),
+    };
+
+    format!(
+        "triangles: {}\nvertices: {}\nbounding box: {bounds}\nsha256: {hash}",
+        stl.triangle_count(), stl.vertex_count(),
+    )
+}
+
+fn format_point(point: Vec3<f64>) -> String {
+    format!("[{}, {}, {}]", point.x, point.y, point.z)
+}
+
+fn describe_error(error: &LangError) -> String {
+    let messages = match error {
+        LangError::Tokenize(errors) => errors.iter().map(|error| (error.to_string(), error.span.clone())).collect::<Vec<_>>(),
+        LangError::Parser(errors) => errors.iter().map(|error| (error.to_string(), error.span.clone())).collect(),
+        LangError::Runtime(error) => vec![(error.to_string(), error.span.clone())],
+        LangError::Warnings(warnings) => warnings.iter().map(|warning| (warning.to_string(), warning.span.clone())).collect(),
+    };
+
+    messages.into_iter()
+        .map(|(message, span)| format!("line {}, column {}: {message}", span.start_line(), span.start_column()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use manifold_rs::{Vec3, ext::Stl};
+
+    use super::stl_digest;
+
+    fn triangle(offset: f32) -> Stl {
+        let mut stl = Stl::new("digest");
+        let points = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0), Vec3::new(0.0, 10.0, 0.0)];
+        stl.add_triangle(Vec3::new(0.0, 0.0, 1.0), points.map(|point| point.map(|value| value + offset)));
+        stl
+    }
+
+    #[test]
+    fn test_stl_digest() {
+        // The hash is of the exact bytes of a binary STL, so it must be the same on every platform
+        assert_eq!(stl_digest(&triangle(0.0)), "\
+triangles: 1
+vertices: 3
+bounding box: [0, 0, 0] to [10, 10, 0]
+sha256: d6dffcc8d36a9e5e922b9ea3f4c567801a8fb3f765b3906c6a109eeead8105c1");
+        assert_eq!(stl_digest(&Stl::new("digest")), "\
+triangles: 0
+vertices: 0
+bounding box: empty
+sha256: 5c59f58879b072ba583426e3e5872fd5a1515664a1d5c9b299cfef0a288f7c5c");
+    }
+
+    #[test]
+    fn test_stl_digest_stability() {
+        let digest = stl_digest(&triangle(0.0));
+
+        // Tiny floating point error, and negative zero, don't change the digest
+        assert_eq!(stl_digest(&triangle(0.00001)), digest);
+        assert_eq!(stl_digest(&triangle(-0.00001)), digest);
+
+        // Neither does the order of triangles
+        let mut first = triangle(0.0);
+        first.add_triangle(Vec3::new(0.0, 0.0, 1.0), [Vec3::new(20.0, 0.0, 0.0), Vec3::new(30.0, 0.0, 0.0), Vec3::new(20.0, 10.0, 0.0)]);
+        let mut second = Stl::new("digest");
+        second.add_triangle(Vec3::new(0.0, 0.0, 1.0), [Vec3::new(20.0, 0.0, 0.0), Vec3::new(30.0, 0.0, 0.0), Vec3::new(20.0, 10.0, 0.0)]);
+        second.add_triangle(Vec3::new(0.0, 0.0, 1.0), [Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0), Vec3::new(0.0, 10.0, 0.0)]);
+        assert_eq!(stl_digest(&first), stl_digest(&second));
+
+        // But a real change does
+        assert_ne!(stl_digest(&triangle(0.01)), digest);
+    }
+}
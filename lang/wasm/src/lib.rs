@@ -0,0 +1,146 @@
+//! WASM bindings for the parts of yascad which don't need geometry, for use in the browser or in
+//! other non-Rust hosts.
+//!
+//! Results are returned as JSON strings, so that they're easy to consume from any language.
+//! Spans are given in characters from the start of the source, with lines and columns starting
+//! from 1.
+
+use std::rc::Rc;
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use yascad_frontend::{DefinitionKind, InputSource, InputSourceSpan, Node, ParameterValue, SourceFile, extract_definitions, extract_parameters, format_source};
+
+#[derive(Serialize, Debug)]
+struct Diagnostic {
+    message: String,
+    #[serde(flatten)]
+    span: Span,
+}
+
+#[derive(Serialize, Debug)]
+struct Parameter {
+    name: String,
+    value: serde_json::Value,
+    #[serde(flatten)]
+    span: Span,
+}
+
+#[derive(Serialize, Debug)]
+struct Definition {
+    name: String,
+
+    /// Either `module` or `operator`.
+    kind: &'static str,
+    parameters: Vec<String>,
+    doc: Option<String>,
+    #[serde(flatten)]
+    span: Span,
+}
+
+#[derive(Serialize, Debug)]
+struct Span {
+    start: usize,
+    length: usize,
+    line: usize,
+    column: usize,
+}
+
+impl From<&InputSourceSpan> for Span {
+    fn from(span: &InputSourceSpan) -> Self {
+        Self {
+            start: span.start,
+            length: span.length,
+            line: span.start_line(),
+            column: span.start_column(),
+        }
+    }
+}
+
+/// Tokenize and parse the source, returning a JSON array of any errors found.
+///
+/// Runtime errors can't be detected, since this doesn't run the source.
+#[wasm_bindgen]
+pub fn diagnostics(source: &str) -> String {
+    let diagnostics = parse(source).err().unwrap_or_default();
+    serde_json::to_string(&diagnostics).unwrap()
+}
+
+/// Find the parameters which the source's model could be customised with, returning them as a JSON
+/// array. See [`extract_parameters`] for details.
+///
+/// If the source doesn't parse, there are no parameters.
+#[wasm_bindgen]
+pub fn parameters(source: &str) -> String {
+    let parameters = parse(source)
+        .map(|nodes| extract_parameters(&nodes))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|parameter| Parameter {
+            name: parameter.name,
+            value: parameter_value_to_json(parameter.value),
+            span: (&parameter.span).into(),
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string(&parameters).unwrap()
+}
+
+/// Find the modules, operators and functions which the source defines, with their doc comments, returning
+/// them as a JSON array.
+///
+/// If the source doesn't parse, there are no definitions.
+#[wasm_bindgen]
+pub fn definitions(source: &str) -> String {
+    let definitions = parse(source)
+        .map(|nodes| extract_definitions(&nodes))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|definition| Definition {
+            name: definition.name,
+            kind: match definition.kind {
+                DefinitionKind::Module => "module",
+                DefinitionKind::Operator => "operator",
+                DefinitionKind::Function => "function",
+            },
+            parameters: definition.parameters,
+            doc: definition.doc,
+            span: (&definition.span).into(),
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string(&definitions).unwrap()
+}
+
+/// Reformat the source, keeping its comments, or return `undefined` if it doesn't parse.
+#[wasm_bindgen]
+pub fn format(source: &str) -> Option<String> {
+    format_source(Rc::new(InputSource::new_string(source.to_owned())))
+}
+
+fn parse(source: &str) -> Result<Vec<Node>, Vec<Diagnostic>> {
+    let file = SourceFile::new(Rc::new(InputSource::new_string(source.to_owned())));
+
+    if !file.tokenize_errors().is_empty() {
+        return Err(file.tokenize_errors().iter()
+            .map(|error| Diagnostic { message: error.to_string(), span: (&error.span).into() })
+            .collect())
+    }
+
+    let mut parser = file.parser();
+    let nodes = parser.parse_statements();
+    if !parser.errors.is_empty() {
+        return Err(parser.errors.iter()
+            .map(|error| Diagnostic { message: error.to_string(), span: (&error.span).into() })
+            .collect())
+    }
+
+    Ok(nodes)
+}
+
+fn parameter_value_to_json(value: ParameterValue) -> serde_json::Value {
+    match value {
+        ParameterValue::Number(num) => num.into(),
+        ParameterValue::Boolean(bool) => bool.into(),
+        ParameterValue::String(string) => string.into(),
+        ParameterValue::Vector(items) => items.into_iter().map(parameter_value_to_json).collect(),
+    }
+}
@@ -0,0 +1,39 @@
+// These run natively with `cargo test`, or in a headless browser with `wasm-pack test --headless`
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+use yascad_wasm::{definitions, diagnostics, format, parameters};
+
+const SOURCE: &str = "width = 10;\nsize = [width, -2];\ncube([width,2,3]);\n";
+
+#[test]
+fn test_diagnostics() {
+    assert_eq!(diagnostics(SOURCE), "[]");
+    assert_eq!(
+        diagnostics("cube(1);\ncube(2) $;"),
+        r#"[{"message":"unexpected character $","start":17,"length":1,"line":2,"column":9}]"#,
+    );
+}
+
+#[test]
+fn test_parameters() {
+    assert_eq!(
+        parameters(SOURCE),
+        r#"[{"name":"width","value":10.0,"start":8,"length":2,"line":1,"column":9}]"#,
+    );
+    assert_eq!(parameters("width = ;"), "[]");
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(format(SOURCE).unwrap(), "width = 10;\nsize = [width, -2];\ncube([width, 2, 3]);\n");
+    assert_eq!(format("cube(;"), None);
+}
+
+#[test]
+fn test_definitions() {
+    assert_eq!(
+        definitions("/// Makes a peg.\nmodule peg(r, h = 2) {}"),
+        r#"[{"name":"peg","kind":"module","parameters":["r","h"],"doc":"Makes a peg.","start":17,"length":6,"line":2,"column":1}]"#,
+    );
+    assert_eq!(definitions("module ("), "[]");
+}
@@ -1,5 +1,11 @@
-use crate::{Vec3, raw};
+use std::os::raw::c_void;
 
+use crate::{Vec3, error::expect_alloc, raw};
+
+/// An axis-aligned box.
+/// 
+/// Every box is initialised as soon as it is created - there is no way to get hold of one whose
+/// memory is only allocated.
 pub struct BoundingBox {
     pub(crate) ptr: *mut raw::ManifoldBox,
 }
@@ -13,20 +19,32 @@ impl BoundingBox {
         Self { ptr }
     }
 
-    /// Allocate a zero-sized box.
+    /// Allocate an empty box.
     /// 
-    /// Safety: This is actually safe, as the FFI interface allocates a valid empty box.
-    /// However, further initialisation is probably intended. If you really want an empty box,
-    /// use [`BoundingBox::new`].
+    /// Safety: The returned box is not initialised, and must be initialised before further use.
     unsafe fn alloc() -> Self {
         unsafe {
-            Self::from_raw(raw::manifold_alloc_box())
+            Self::from_raw(expect_alloc(raw::manifold_alloc_box()))
         }
     }
 
-    /// Create an empty, zero-sized box.
-    pub fn new() -> Self {
-        unsafe { Self::alloc() }
+    /// Shorthand for `alloc` followed by an initialiser on the raw pointer.
+    /// 
+    /// Safety: `func` must initialise the pointee box.
+    pub(crate) unsafe fn alloc_build<R>(func: impl FnOnce(*mut c_void) -> R) -> Self {
+        unsafe {
+            let bbox = Self::alloc();
+            func(bbox.ptr as *mut c_void);
+            bbox
+        }
+    }
+
+    /// Create a box with two opposite corners at the given points.
+    pub fn new(a: Vec3<f64>, b: Vec3<f64>) -> Self {
+        unsafe {
+            Self::alloc_build(|ptr|
+                raw::manifold_box(ptr, a.x, a.y, a.z, b.x, b.y, b.z))
+        }
     }
 
     /// The lower point of the two which define the box.
@@ -51,6 +69,13 @@ impl BoundingBox {
     }
 }
 
+impl Default for BoundingBox {
+    /// A zero-sized box at the origin.
+    fn default() -> Self {
+        Self::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0))
+    }
+}
+
 impl Drop for BoundingBox {
     fn drop(&mut self) {
         unsafe {
@@ -58,3 +83,24 @@ impl Drop for BoundingBox {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{BoundingBox, Vec3};
+
+    #[test]
+    fn test_new() {
+        let bbox = BoundingBox::new(Vec3::new(3.0, -1.0, 2.0), Vec3::new(1.0, 4.0, 2.5));
+        assert_eq!(bbox.min_point(), Vec3::new(1.0, -1.0, 2.0));
+        assert_eq!(bbox.max_point(), Vec3::new(3.0, 4.0, 2.5));
+        assert_eq!(bbox.size(), Vec3::new(2.0, 5.0, 0.5));
+    }
+
+    #[test]
+    fn test_default() {
+        let bbox = BoundingBox::default();
+        assert_eq!(bbox.min_point(), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(bbox.max_point(), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(bbox.size(), Vec3::new(0.0, 0.0, 0.0));
+    }
+}
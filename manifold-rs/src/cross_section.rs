@@ -1,6 +1,30 @@
 use std::{fmt::Debug, os::raw::c_void};
 
-use crate::{Polygons, Rectangle, raw};
+use crate::{Polygons, Rectangle, error::expect_alloc, raw};
+
+/// How corners are joined when a cross section is grown or shrunk by [`CrossSection::offset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinType {
+    /// Corners are cut off flat.
+    Square,
+
+    /// Corners are rounded, with the offset distance as their radius.
+    Round,
+
+    /// Corners are extended to a point, unless the point would be further away than the miter
+    /// limit allows - then they're cut off flat, like [`JoinType::Square`].
+    Miter,
+}
+
+impl JoinType {
+    fn raw(self) -> raw::ManifoldJoinType {
+        match self {
+            JoinType::Square => raw::ManifoldJoinType_MANIFOLD_JOIN_TYPE_SQUARE,
+            JoinType::Round => raw::ManifoldJoinType_MANIFOLD_JOIN_TYPE_ROUND,
+            JoinType::Miter => raw::ManifoldJoinType_MANIFOLD_JOIN_TYPE_MITER,
+        }
+    }
+}
 
 pub struct CrossSection {
     pub(crate) ptr: *mut raw::ManifoldCrossSection,
@@ -21,7 +45,7 @@ impl CrossSection {
     /// further use.
     unsafe fn alloc() -> Self {
         unsafe {
-            Self::from_raw(raw::manifold_alloc_cross_section())
+            Self::from_raw(expect_alloc(raw::manifold_alloc_cross_section()))
         }
     }
 
@@ -44,6 +68,15 @@ impl CrossSection {
         }
     }
 
+    /// Create a cross section from a list of polygons, where the filled area is anywhere that
+    /// outlines (counter-clockwise) outnumber holes (clockwise).
+    pub fn from_polygons(polygons: &Polygons) -> Self {
+        unsafe {
+            Self::alloc_build(|ptr|
+                raw::manifold_cross_section_of_polygons(ptr, polygons.ptr, raw::ManifoldFillRule_MANIFOLD_FILL_RULE_POSITIVE))
+        }
+    }
+
     /// Create a cross section of a square.
     pub fn square(x: f64, y: f64, centre: bool) -> Self {
         unsafe {
@@ -109,25 +142,93 @@ impl CrossSection {
         }
     }
 
+    /// Create a new cross section containing only the area shared by this cross section and
+    /// another.
+    pub fn intersection(&self, other: &CrossSection) -> Self {
+        unsafe {
+            Self::alloc_build(|ptr|
+                raw::manifold_cross_section_intersection(ptr, self.ptr, other.ptr))
+        }
+    }
+
+    /// Create a new cross section by moving every edge of this one outwards by `delta`, or
+    /// inwards if it's negative.
+    /// 
+    /// The `miter_limit` is how far a [`JoinType::Miter`] corner can extend, as a multiple of
+    /// `delta`. Round corners use `segments` for a full circle.
+    pub fn offset(&self, delta: f64, join_type: JoinType, miter_limit: f64, segments: i32) -> Self {
+        unsafe {
+            Self::alloc_build(|ptr|
+                raw::manifold_cross_section_offset(ptr, self.ptr, delta, join_type.raw(), miter_limit, segments))
+        }
+    }
+
+    /// Create a new cross section which is the convex hull of this one - the smallest convex shape
+    /// which contains it.
+    pub fn hull(&self) -> Self {
+        unsafe {
+            Self::alloc_build(|ptr|
+                raw::manifold_cross_section_hull(ptr, self.ptr))
+        }
+    }
+
+    /// Create a new cross section which is the convex hull of all of the given cross sections
+    /// together, like [`crate::Manifold::hull_many`].
+    pub fn hull_many(cross_sections: &[CrossSection]) -> Self {
+        unsafe {
+            let vec = expect_alloc(raw::manifold_alloc_cross_section_vec());
+            raw::manifold_cross_section_empty_vec(vec as *mut c_void);
+            for cross_section in cross_sections {
+                // This copies the cross section into the vector
+                raw::manifold_cross_section_vec_push_back(vec, cross_section.ptr);
+            }
+
+            let result = Self::alloc_build(|ptr| raw::manifold_cross_section_batch_hull(ptr, vec));
+            raw::manifold_delete_cross_section_vec(vec);
+            result
+        }
+    }
+
     /// Get the polygons for this cross section. 
     pub fn polygons(&self) -> Polygons {
         unsafe {
-            let poly = Polygons::alloc();
-            raw::manifold_cross_section_to_polygons(poly.ptr as *mut c_void, self.ptr);
-            poly
+            Polygons::alloc_build(|ptr|
+                raw::manifold_cross_section_to_polygons(ptr, self.ptr))
         }
     }
 
     /// Get the bounding rectangle for this cross section.
     pub fn bounding_rectangle(&self) -> Rectangle {
         unsafe {
-            let rect = Rectangle::alloc();
-            raw::manifold_cross_section_bounds(rect.ptr as *mut c_void, self.ptr);
-            rect
+            Rectangle::alloc_build(|ptr|
+                raw::manifold_cross_section_bounds(ptr, self.ptr))
+        }
+    }
+
+    pub fn count_verts(&self) -> usize {
+        unsafe {
+            raw::manifold_cross_section_num_vert(self.ptr)
+        }
+    }
+
+    /// Whether this cross section contains no geometry at all.
+    pub fn is_empty(&self) -> bool {
+        unsafe {
+            raw::manifold_cross_section_is_empty(self.ptr) != 0
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        unsafe {
+            raw::manifold_cross_section_area(self.ptr)
         }
     }
 }
 
+// Safety: As for `Manifold` - a cross-section owns its pointer, and Manifold doesn't use
+// thread-local state. Cross-sections aren't `Sync` for the same reasons either.
+unsafe impl Send for CrossSection {}
+
 impl Clone for CrossSection {
     fn clone(&self) -> Self {
         unsafe {
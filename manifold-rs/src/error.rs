@@ -0,0 +1,103 @@
+use std::{error::Error, fmt::Display};
+
+use crate::raw;
+
+/// A failure reported by Manifold while creating geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifoldError {
+    /// Manifold couldn't allocate a new object.
+    AllocationFailed,
+
+    /// Manifold created an object, but it's invalid. Contains the status code which Manifold
+    /// reported, from the `ManifoldError` enum in its C interface.
+    InvalidGeometry(u32),
+}
+
+impl Display for ManifoldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifoldError::AllocationFailed => write!(f, "Manifold failed to allocate memory"),
+            ManifoldError::InvalidGeometry(status) => write!(f, "Manifold could not create valid geometry (status {status})"),
+        }
+    }
+}
+impl Error for ManifoldError {}
+
+impl ManifoldError {
+    /// Explain what's wrong with a mesh which Manifold couldn't create geometry from, like in
+    /// [`crate::Manifold::from_meshgl`], if the status is one which a bad mesh causes.
+    pub fn mesh_problem(&self) -> Option<&'static str> {
+        let ManifoldError::InvalidGeometry(status) = self else { return None };
+        let problems = [
+            (raw::ManifoldError_MANIFOLD_NON_FINITE_VERTEX, "a point isn't a finite number"),
+            (raw::ManifoldError_MANIFOLD_NOT_MANIFOLD, "the mesh isn't closed, or its faces don't join up - every edge must be shared by exactly two faces, going along it in opposite directions"),
+            (raw::ManifoldError_MANIFOLD_VERTEX_INDEX_OUT_OF_BOUNDS, "a face refers to a point which doesn't exist"),
+        ];
+
+        // The integer type which bindgen uses for C enums varies between platforms
+        #[allow(clippy::unnecessary_cast)]
+        problems.into_iter()
+            .find(|(problem_status, _)| *problem_status as u32 == *status)
+            .map(|(_, problem)| problem)
+    }
+}
+
+/// Check that a pointer returned by one of Manifold's `manifold_alloc_*` functions is usable.
+pub(crate) fn check_alloc<T>(ptr: *mut T) -> Result<*mut T, ManifoldError> {
+    if ptr.is_null() {
+        Err(ManifoldError::AllocationFailed)
+    } else {
+        Ok(ptr)
+    }
+}
+
+/// Like [`check_alloc`], but panics on failure, for types where allocation can't reasonably be
+/// recovered from. This is still better than carrying on with a null pointer.
+pub(crate) fn expect_alloc<T>(ptr: *mut T) -> *mut T {
+    check_alloc(ptr).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Convert a status reported by Manifold into a [`Result`].
+pub(crate) fn check_status(status: raw::ManifoldError) -> Result<(), ManifoldError> {
+    if status == raw::ManifoldError_MANIFOLD_NO_ERROR {
+        Ok(())
+    } else {
+        // The integer type which bindgen uses for C enums varies between platforms
+        #[allow(clippy::unnecessary_cast)]
+        let error = ManifoldError::InvalidGeometry(status as u32);
+        log::debug!("manifold operation failed: {error}");
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr;
+
+    use crate::{ManifoldError, error::{check_alloc, check_status}, raw};
+
+    #[test]
+    fn test_check_alloc() {
+        let mut value = 0;
+        assert_eq!(check_alloc(&mut value as *mut i32), Ok(&mut value as *mut i32));
+        assert_eq!(check_alloc(ptr::null_mut::<raw::ManifoldManifold>()), Err(ManifoldError::AllocationFailed));
+    }
+
+    #[test]
+    fn test_check_status() {
+        assert_eq!(check_status(raw::ManifoldError_MANIFOLD_NO_ERROR), Ok(()));
+
+        let error = check_status(2).unwrap_err();
+        assert_eq!(error, ManifoldError::InvalidGeometry(2));
+        assert_eq!(error.to_string(), "Manifold could not create valid geometry (status 2)");
+    }
+
+    #[test]
+    fn test_mesh_problem() {
+        let error = check_status(raw::ManifoldError_MANIFOLD_NOT_MANIFOLD).unwrap_err();
+        assert!(error.mesh_problem().unwrap().starts_with("the mesh isn't closed"));
+
+        assert_eq!(ManifoldError::AllocationFailed.mesh_problem(), None);
+        assert_eq!(check_status(raw::ManifoldError_MANIFOLD_NO_ERROR + 1000).unwrap_err().mesh_problem(), None);
+    }
+}
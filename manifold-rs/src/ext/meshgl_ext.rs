@@ -4,6 +4,14 @@ use crate::{MeshGL, Vec3, ext::Stl};
 pub trait MeshGLExt {
     /// Convert this mesh to an STL.
     fn to_stl(&self, name: &str) -> Stl;
+
+    /// Compute a normal for each corner of each triangle, in the same order as
+    /// [`MeshGL::triangle_vertex_data`]. This works for any mesh, even without a normal channel.
+    ///
+    /// Normals are smoothed across edges where faces meet at less than `min_sharp_angle` degrees,
+    /// and edges sharper than that stay flat. A vertex can need a different normal for each face it
+    /// belongs to (like a cube's corners), which is why normals are given per corner.
+    fn compute_corner_normals(&self, min_sharp_angle: f32) -> Vec<Vec3<f32>>;
 }
 
 impl MeshGLExt for MeshGL {
@@ -17,9 +25,45 @@ impl MeshGLExt for MeshGL {
 
         stl
     }
+
+    fn compute_corner_normals(&self, min_sharp_angle: f32) -> Vec<Vec3<f32>> {
+        corner_normals(&self.vertex_positions(), &self.triangle_vertex_data(), min_sharp_angle)
+    }
+}
+
+pub(crate) fn corner_normals(positions: &[Vec3<f32>], triangles: &[usize], min_sharp_angle: f32) -> Vec<Vec3<f32>> {
+    let face_normals = triangles.chunks_exact(3)
+        .map(|tri| triangle_normal(positions[tri[0]], positions[tri[1]], positions[tri[2]]))
+        .collect::<Vec<_>>();
+
+    // Find which faces use each vertex
+    let mut vertex_faces = vec![vec![]; positions.len()];
+    for (face, tri) in triangles.chunks_exact(3).enumerate() {
+        for &vertex in tri {
+            vertex_faces[vertex].push(face);
+        }
+    }
+
+    let min_smooth_cos = min_sharp_angle.to_radians().cos();
+    triangles.chunks_exact(3)
+        .enumerate()
+        .flat_map(|(face, tri)| {
+            let face_normal = face_normals[face];
+            let vertex_faces = &vertex_faces;
+            let face_normals = &face_normals;
+
+            tri.iter().map(move |&vertex| {
+                // Average this face with the neighbouring faces which aren't across a sharp edge
+                let sum = vertex_faces[vertex].iter()
+                    .filter(|&&other| other == face || dot(face_normal, face_normals[other]) >= min_smooth_cos)
+                    .fold(Vec3::zero(), |sum, &other| sum + face_normals[other]);
+                normalize(sum)
+            })
+        })
+        .collect()
 }
 
-fn triangle_normal(p1: Vec3<f32>, p2: Vec3<f32>, p3: Vec3<f32>) -> Vec3<f32> {
+pub(crate) fn triangle_normal(p1: Vec3<f32>, p2: Vec3<f32>, p3: Vec3<f32>) -> Vec3<f32> {
     // Edge vectors
     let u = p2 - p1;
     let v = p3 - p1;
@@ -31,11 +75,106 @@ fn triangle_normal(p1: Vec3<f32>, p2: Vec3<f32>, p3: Vec3<f32>) -> Vec3<f32> {
         u.x * v.y - u.y * v.x,
     ];
 
-    // Normalize
-    let len = (normal[0]*normal[0] + normal[1]*normal[1] + normal[2]*normal[2]).sqrt();
+    normalize(Vec3::new(normal[0], normal[1], normal[2]))
+}
+
+fn dot(a: Vec3<f32>, b: Vec3<f32>) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn normalize(v: Vec3<f32>) -> Vec3<f32> {
+    let len = dot(v, v).sqrt();
     if len > 0.0 {
-        Vec3::new(normal[0] / len, normal[1] / len, normal[2] / len)
+        v.map(|c| c / len)
     } else {
         Vec3::new(0.0, 0.0, 0.0) // Degenerate triangle
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::PI;
+
+    use crate::Vec3;
+
+    use super::{corner_normals, dot, normalize, triangle_normal};
+
+    /// A unit cube, sharing vertices between faces like Manifold does.
+    fn cube() -> (Vec<Vec3<f32>>, Vec<usize>) {
+        let positions = (0..8)
+            .map(|i| Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32))
+            .collect();
+        let triangles = vec![
+            0, 2, 3,  0, 3, 1, // Bottom
+            4, 5, 7,  4, 7, 6, // Top
+            0, 1, 5,  0, 5, 4, // Front
+            2, 6, 7,  2, 7, 3, // Back
+            0, 4, 6,  0, 6, 2, // Left
+            1, 3, 7,  1, 7, 5, // Right
+        ];
+        (positions, triangles)
+    }
+
+    /// A unit sphere made of `rings` horizontal bands of `segments` quads, with a single vertex at
+    /// each pole.
+    fn sphere(rings: usize, segments: usize) -> (Vec<Vec3<f32>>, Vec<usize>) {
+        let mut positions = vec![Vec3::new(0.0, 0.0, -1.0)];
+        for ring in 1..rings {
+            let (ring_sin, ring_cos) = (PI * ring as f32 / rings as f32).sin_cos();
+            for segment in 0..segments {
+                let (sin, cos) = (2.0 * PI * segment as f32 / segments as f32).sin_cos();
+                positions.push(Vec3::new(ring_sin * cos, ring_sin * sin, -ring_cos));
+            }
+        }
+        positions.push(Vec3::new(0.0, 0.0, 1.0));
+
+        let top = positions.len() - 1;
+        let ring_vertex = |ring: usize, segment: usize| 1 + (ring - 1) * segments + segment % segments;
+        let mut triangles = vec![];
+        for segment in 0..segments {
+            triangles.extend([0, ring_vertex(1, segment + 1), ring_vertex(1, segment)]);
+            for ring in 1..(rings - 1) {
+                let (a, b) = (ring_vertex(ring, segment), ring_vertex(ring, segment + 1));
+                let (c, d) = (ring_vertex(ring + 1, segment), ring_vertex(ring + 1, segment + 1));
+                triangles.extend([a, b, d, a, d, c]);
+            }
+            triangles.extend([top, ring_vertex(rings - 1, segment), ring_vertex(rings - 1, segment + 1)]);
+        }
+        (positions, triangles)
+    }
+
+    #[test]
+    fn test_cube_corner_normals_are_flat() {
+        let (positions, triangles) = cube();
+        let normals = corner_normals(&positions, &triangles, 60.0);
+        assert_eq!(normals.len(), triangles.len());
+
+        for (tri, normals) in triangles.chunks_exact(3).zip(normals.chunks_exact(3)) {
+            let face_normal = triangle_normal(positions[tri[0]], positions[tri[1]], positions[tri[2]]);
+            assert!(normals.iter().all(|&normal| normal == face_normal));
+        }
+    }
+
+    #[test]
+    fn test_sphere_corner_normals_are_smooth() {
+        let (positions, triangles) = sphere(16, 32);
+        let normals = corner_normals(&positions, &triangles, 60.0);
+        assert_eq!(normals.len(), triangles.len());
+
+        // On a unit sphere, the true normal at a point is the point itself
+        let corner_error = |(&vertex, &normal): (&usize, &Vec3<f32>)| 1.0 - dot(normal, normalize(positions[vertex]));
+        let smooth_error = triangles.iter().zip(&normals).map(corner_error).fold(0.0, f32::max);
+
+        let face_normals = triangles.chunks_exact(3)
+            .flat_map(|tri| [triangle_normal(positions[tri[0]], positions[tri[1]], positions[tri[2]]); 3])
+            .collect::<Vec<_>>();
+        let flat_error = triangles.iter().zip(&face_normals).map(corner_error).fold(0.0, f32::max);
+
+        assert!(smooth_error < 0.01, "smooth error {smooth_error}");
+        assert!(smooth_error < flat_error / 2.0, "smooth error {smooth_error}, flat error {flat_error}");
+
+        // With every edge sharp, the normals are just the face normals
+        let sharp_normals = corner_normals(&positions, &triangles, 0.0);
+        assert!(sharp_normals.iter().zip(&face_normals).all(|(&a, &b)| dot(a, b) > 0.9999));
+    }
+}
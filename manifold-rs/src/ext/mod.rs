@@ -3,5 +3,16 @@
 mod stl;
 pub use stl::*;
 
+mod svg;
+pub use svg::*;
+
+mod polygon_tree;
+pub use polygon_tree::*;
+
+mod threemf;
+pub use threemf::*;
+
+mod zip;
+
 mod meshgl_ext;
 pub use meshgl_ext::*;
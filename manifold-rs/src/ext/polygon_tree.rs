@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+
+use crate::{CrossSection, Vec2};
+
+/// A closed contour of a cross section, with the contours directly inside it.
+///
+/// The contours inside an outer boundary are its holes, and the contours inside a hole are islands
+/// within that hole, which can have holes of their own, and so on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolyNode {
+    /// The points of this contour. Outer boundaries and islands are counter-clockwise, and holes
+    /// are clockwise.
+    pub outline: Vec<Vec2<f64>>,
+
+    /// The contours directly inside this one.
+    pub holes: Vec<PolyNode>,
+}
+
+impl PolyNode {
+    /// Arrange a flat list of non-intersecting contours into trees, by which contours contain each
+    /// other. Returns the outermost contours.
+    ///
+    /// The direction of each contour doesn't matter, and is corrected to match its depth. Contours
+    /// with fewer than 3 points are dropped.
+    pub fn from_contours(contours: Vec<Vec<Vec2<f64>>>) -> Vec<PolyNode> {
+        // A contour can only be inside a larger one, so placing the largest first means that a
+        // contour's parent is always placed before it
+        let mut contours = contours.into_iter()
+            .filter(|contour| contour.len() >= 3)
+            .map(|contour| (signed_area(&contour), contour))
+            .collect::<Vec<_>>();
+        contours.sort_by(|(a, _), (b, _)| b.abs().partial_cmp(&a.abs()).unwrap_or(Ordering::Equal));
+
+        let mut roots = vec![];
+        for (area, contour) in contours {
+            insert(&mut roots, contour, area, 0);
+        }
+        roots
+    }
+
+    /// The number of contours in this tree, including this one.
+    pub fn count_contours(&self) -> usize {
+        1 + self.holes.iter().map(PolyNode::count_contours).sum::<usize>()
+    }
+
+    /// The depth of this tree - 1 for a contour with no holes.
+    pub fn depth(&self) -> usize {
+        1 + self.holes.iter().map(PolyNode::depth).max().unwrap_or(0)
+    }
+}
+
+impl CrossSection {
+    /// Get this cross section's contours, arranged into trees of outer boundaries and the holes
+    /// within them. See [`PolyNode`].
+    pub fn polygon_tree(&self) -> Vec<PolyNode> {
+        PolyNode::from_contours(self.polygons().to_points())
+    }
+}
+
+/// Add a contour within `siblings`, or within whichever of them contains it.
+fn insert(siblings: &mut Vec<PolyNode>, mut contour: Vec<Vec2<f64>>, area: f64, depth: usize) {
+    if let Some(parent) = siblings.iter_mut().find(|node| contains(&node.outline, &contour)) {
+        insert(&mut parent.holes, contour, area, depth + 1);
+        return;
+    }
+
+    let counter_clockwise = depth.is_multiple_of(2);
+    if (area > 0.0) != counter_clockwise {
+        contour.reverse();
+    }
+    siblings.push(PolyNode { outline: contour, holes: vec![] });
+}
+
+/// The area of a contour, which is positive if it's counter-clockwise.
+fn signed_area(contour: &[Vec2<f64>]) -> f64 {
+    let doubled = contour.iter()
+        .zip(contour.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f64>();
+    doubled / 2.0
+}
+
+/// Whether `inner` is inside `outer`, assuming that they don't cross.
+///
+/// Contours can touch at their points, so this decides by the first point of `inner` which isn't
+/// on the edge of `outer`.
+fn contains(outer: &[Vec2<f64>], inner: &[Vec2<f64>]) -> bool {
+    let edge_midpoints = inner.iter()
+        .zip(inner.iter().cycle().skip(1))
+        .map(|(a, b)| Vec2::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0));
+
+    inner.iter().copied()
+        .chain(edge_midpoints)
+        .find_map(|point| point_in_contour(point, outer))
+        .unwrap_or(false)
+}
+
+/// Whether a point is inside a contour, or [`None`] if it's exactly on its edge.
+fn point_in_contour(point: Vec2<f64>, contour: &[Vec2<f64>]) -> Option<bool> {
+    let mut inside = false;
+    for (a, b) in contour.iter().zip(contour.iter().cycle().skip(1)) {
+        let cross = (b.x - a.x) * (point.y - a.y) - (point.x - a.x) * (b.y - a.y);
+        let within_x = point.x >= a.x.min(b.x) && point.x <= a.x.max(b.x);
+        let within_y = point.y >= a.y.min(b.y) && point.y <= a.y.max(b.y);
+        if cross == 0.0 && within_x && within_y {
+            return None;
+        }
+
+        // Count crossings of a ray towards +X
+        if (a.y > point.y) != (b.y > point.y) {
+            let crossing_x = a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y);
+            if point.x < crossing_x {
+                inside = !inside;
+            }
+        }
+    }
+    Some(inside)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Vec2;
+
+    use super::{PolyNode, signed_area};
+
+    /// A counter-clockwise square.
+    fn square(min: f64, max: f64) -> Vec<Vec2<f64>> {
+        vec![Vec2::new(min, min), Vec2::new(max, min), Vec2::new(max, max), Vec2::new(min, max)]
+    }
+
+    fn clockwise(mut contour: Vec<Vec2<f64>>) -> Vec<Vec2<f64>> {
+        contour.reverse();
+        contour
+    }
+
+    #[test]
+    fn test_plate_with_island_in_hole() {
+        let plate = square(0.0, 100.0);
+        let hole = clockwise(square(20.0, 80.0));
+        let island = square(40.0, 60.0);
+        let island_hole = clockwise(square(45.0, 55.0));
+        let other = square(200.0, 210.0);
+
+        // Order shouldn't matter
+        let tree = PolyNode::from_contours(vec![island.clone(), other.clone(), island_hole.clone(), plate.clone(), hole.clone()]);
+        assert_eq!(tree, vec![
+            PolyNode {
+                outline: plate,
+                holes: vec![PolyNode {
+                    outline: hole,
+                    holes: vec![PolyNode {
+                        outline: island,
+                        holes: vec![PolyNode { outline: island_hole, holes: vec![] }],
+                    }],
+                }],
+            },
+            PolyNode { outline: other, holes: vec![] },
+        ]);
+        assert_eq!(tree[0].depth(), 4);
+        assert_eq!(tree[0].count_contours(), 4);
+    }
+
+    #[test]
+    fn test_directions_corrected() {
+        let tree = PolyNode::from_contours(vec![clockwise(square(0.0, 10.0)), square(2.0, 4.0), square(6.0, 8.0)]);
+
+        let [plate] = &tree[..] else { panic!("expected one outer boundary") };
+        assert!(signed_area(&plate.outline) > 0.0);
+        assert_eq!(plate.holes.len(), 2);
+        assert!(plate.holes.iter().all(|hole| signed_area(&hole.outline) < 0.0));
+    }
+
+    #[test]
+    fn test_touching_contours() {
+        // A hole which touches the corner of its plate, so that its first point is on the edge
+        let plate = square(0.0, 10.0);
+        let hole = vec![Vec2::new(0.0, 0.0), Vec2::new(2.0, 5.0), Vec2::new(5.0, 2.0)];
+
+        let tree = PolyNode::from_contours(vec![plate, hole.clone()]);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].holes, vec![PolyNode { outline: hole, holes: vec![] }]);
+    }
+
+    #[test]
+    fn test_degenerate_contours() {
+        assert_eq!(PolyNode::from_contours(vec![vec![], vec![Vec2::new(1.0, 1.0), Vec2::new(2.0, 2.0)]]), vec![]);
+    }
+}
@@ -1,6 +1,6 @@
-use std::io;
+use std::{collections::HashMap, fmt::Display, io, mem};
 
-use crate::Vec3;
+use crate::{MeshGL, Vec3, ext::triangle_normal};
 
 /// An STL model.
 #[derive(Debug, Clone, PartialEq)]
@@ -88,27 +88,514 @@ impl Stl {
             };
         }
     }
-    
-    /// Write out this STL in textual format.
-    pub fn write_text_stl<I: io::Write>(&self, writer: &mut I) -> io::Result<()>
+
+    /// Move every point and normal to the nearest multiple of `step`, so that models which only
+    /// differ by floating point error become identical.
+    ///
+    /// Use a power of two for `step`, so that the moved numbers are exact. Sort the model
+    /// afterwards if the order of its triangles might also differ.
+    pub fn quantise(&mut self, step: f32) {
+        let snap = |vector: Vec3<f32>| vector.map(|value| (value / step).round() * step);
+        for tri in &mut self.triangles {
+            *tri = StlTriangle {
+                normal: snap(tri.normal),
+                points: tri.points.map(snap),
+            }.normalise_zeroes();
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// The number of distinct points used by the model's triangles.
+    pub fn vertex_count(&self) -> usize {
+        let mut points = self.triangles.iter()
+            .flat_map(|tri| tri.points)
+            .map(|point| [point.x.to_bits(), point.y.to_bits(), point.z.to_bits()])
+            .collect::<Vec<_>>();
+        points.sort_unstable();
+        points.dedup();
+        points.len()
+    }
+
+    /// The lowest and highest corners of a box around every point in the model, or `None` if it
+    /// has no triangles.
+    pub fn bounds(&self) -> Option<(Vec3<f32>, Vec3<f32>)> {
+        let mut points = self.triangles.iter().flat_map(|tri| tri.points);
+        let first = points.next()?;
+        Some(points.fold((first, first), |(min, max), point| (
+            Vec3::new(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z)),
+            Vec3::new(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z)),
+        )))
+    }
+
+    /// Write out this STL in textual format, with numbers in their shortest form.
+    pub fn write_text_stl<I: io::Write>(&self, writer: &mut I) -> io::Result<()> {
+        self.write_text_stl_with_options(writer, &TextStlOptions::default())
+    }
+
+    /// Write out this STL in textual format, with control over how numbers are written.
+    pub fn write_text_stl_with_options<I: io::Write>(&self, writer: &mut I, options: &TextStlOptions) -> io::Result<()>
     {
         writeln!(writer, "solid {}", self.name)?;
 
         for tri in &self.triangles {
-            let StlTriangle { normal, points } = tri;
+            write_text_facet(writer, tri, options)?;
+        }
+
+        writeln!(writer, "endsolid {}", self.name)?;
+
+        Ok(())
+    }
+
+    /// Write out this STL in binary format, which is far smaller than the textual format and
+    /// keeps every number exactly.
+    /// 
+    /// The name is written into the 80-byte header, cut short if it doesn't fit.
+    pub fn write_binary_stl<I: io::Write>(&self, writer: &mut I) -> io::Result<()> {
+        write_binary_header(writer, &self.name, self.triangles.len())?;
+        for tri in &self.triangles {
+            write_binary_facet(writer, tri)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Why an STL file couldn't be read with [`Stl::read`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StlReadError {
+    pub reason: String,
+}
+
+impl Display for StlReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl Stl {
+    /// Read an STL file, in either the textual or binary format.
+    /// 
+    /// Some tools begin the header of a binary STL with `solid`, like a textual one, so a file is
+    /// read as binary whenever its length matches the number of triangles which its header gives.
+    pub fn read(bytes: &[u8]) -> Result<Stl, StlReadError> {
+        let error = |reason: String| StlReadError { reason };
+        let binary_count = bytes.get(80..84)
+            .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
 
-            writeln!(writer, "facet normal {} {} {}", normal.x, normal.y, normal.z)?;
-            writeln!(writer, "  outer loop")?;
-            for point in points {
-                writeln!(writer, "    vertex {} {} {}", point.x, point.y, point.z)?;
+        match binary_count {
+            Some(count) if bytes.len() == 84 + count * 50 => Ok(read_binary_stl(bytes)),
+            _ if bytes.starts_with(b"solid") => {
+                let text = std::str::from_utf8(bytes).map_err(|_| error("textual STL is not valid UTF-8".to_owned()))?;
+                read_text_stl(text).map_err(error)
+            },
+            Some(count) => Err(error(format!(
+                "not an STL file - a binary STL with {count} triangles would be {} bytes, but this is {}",
+                84 + count * 50, bytes.len(),
+            ))),
+            None => Err(error("not an STL file".to_owned())),
+        }
+    }
+
+    /// Convert this model into a mesh, joining up triangles at the corners they share.
+    /// 
+    /// STL stores each triangle separately, so the mesh is only manifold if the corners of
+    /// neighbouring triangles are exactly equal, as they are in STLs written by most tools. The
+    /// triangles' normals are ignored - the winding of their points is used instead.
+    pub fn to_meshgl(&self) -> MeshGL {
+        let mut vertices = vec![];
+        let mut vertex_indices = HashMap::new();
+        let mut triangles = vec![];
+
+        for tri in &self.triangles {
+            let tri = tri.normalise_zeroes();
+            let mut indices = [0; 3];
+            for (index, point) in indices.iter_mut().zip(tri.points) {
+                *index = *vertex_indices.entry([point.x.to_bits(), point.y.to_bits(), point.z.to_bits()])
+                    .or_insert_with(|| {
+                        vertices.push(point);
+                        vertices.len() as u32 - 1
+                    });
             }
-            writeln!(writer, "  endloop")?;
-            writeln!(writer, "endfacet")?;
+            triangles.push(indices);
+        }
+
+        MeshGL::from_vertices_and_triangles(&vertices, &triangles)
+    }
+}
+
+/// Read a binary STL, which [`Stl::read`] has already checked is the right length.
+fn read_binary_stl(bytes: &[u8]) -> Stl {
+    let header = &bytes[..80];
+    let name_len = header.iter().rposition(|byte| *byte != 0).map_or(0, |last| last + 1);
+    let mut stl = Stl::new(&String::from_utf8_lossy(&header[..name_len]));
+
+    for record in bytes[84..].chunks_exact(50) {
+        let value = |i: usize| f32::from_le_bytes(record[i * 4..i * 4 + 4].try_into().unwrap());
+        let vector = |i: usize| Vec3::new(value(i * 3), value(i * 3 + 1), value(i * 3 + 2));
+        stl.add_triangle(vector(0), [vector(1), vector(2), vector(3)]);
+    }
+
+    stl
+}
+
+/// Read a textual STL. Errors give the line they occurred on.
+fn read_text_stl(text: &str) -> Result<Stl, String> {
+    let mut lines = text.lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
 
+    let name = lines.next()
+        .and_then(|(_, line)| line.strip_prefix("solid"))
+        .unwrap_or_default()
+        .trim();
+    let mut stl = Stl::new(name);
+
+    let mut normal = None;
+    let mut points = vec![];
+    for (number, line) in lines {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("facet") if normal.is_none() => {
+                if words.next() != Some("normal") {
+                    return Err(format!("line {number}: expected `facet normal`"));
+                }
+                normal = Some(read_text_vector(words).map_err(|reason| format!("line {number}: {reason}"))?);
+            },
+            Some("vertex") if normal.is_some() => {
+                points.push(read_text_vector(words).map_err(|reason| format!("line {number}: {reason}"))?);
+            },
+            Some("endfacet") if normal.is_some() => {
+                let count = points.len();
+                let Ok(points) = <[_; 3]>::try_from(mem::take(&mut points)) else {
+                    return Err(format!("line {number}: facets need 3 vertices, but this one has {count}"));
+                };
+                stl.add_triangle(normal.take().unwrap(), points);
+            },
+            Some("outer" | "endloop") if normal.is_some() => (),
+            Some("endsolid") if normal.is_none() => return Ok(stl),
+            _ => return Err(format!("line {number}: unexpected `{line}`")),
         }
+    }
 
-        writeln!(writer, "endsolid {}", self.name)?;
+    Err("the file ends without `endsolid`".to_owned())
+}
 
-        Ok(())
+/// Read the three numbers after a `facet normal` or `vertex`.
+fn read_text_vector<'a>(words: impl Iterator<Item = &'a str>) -> Result<Vec3<f32>, String> {
+    let numbers = words
+        .map(|word| word.parse::<f32>().map_err(|_| format!("`{word}` is not a number")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [x, y, z] = numbers[..] else {
+        return Err(format!("expected 3 numbers, got {}", numbers.len()));
+    };
+    Ok(Vec3::new(x, y, z))
+}
+
+/// Write a mesh straight out as a textual STL, equivalent to writing the result of
+/// [`MeshGLExt::to_stl`](crate::ext::MeshGLExt::to_stl).
+/// 
+/// This doesn't build an intermediate [`Stl`], so is preferable for very large meshes. The only
+/// copies made are of the mesh's raw vertex and triangle buffers.
+/// 
+/// Unlike an [`Stl`], the output can't be sorted.
+pub fn write_stl_from_meshgl<I: io::Write>(mesh: &MeshGL, name: &str, writer: &mut I, options: &TextStlOptions) -> io::Result<()> {
+    writeln!(writer, "solid {name}")?;
+    for_each_meshgl_triangle(mesh, |tri| write_text_facet(writer, &tri, options))?;
+    writeln!(writer, "endsolid {name}")?;
+
+    Ok(())
+}
+
+/// Write meshes straight out together as one binary STL, without building an intermediate
+/// [`Stl`].
+/// 
+/// Binary STLs can only contain one solid, so the triangles of every mesh are written one after
+/// another, and `name` is written into the header.
+pub fn write_binary_stl_from_meshgls<I: io::Write>(meshes: &[MeshGL], name: &str, writer: &mut I) -> io::Result<()> {
+    let count = meshes.iter().map(MeshGL::count_triangles).sum();
+    write_binary_header(writer, name, count)?;
+    for mesh in meshes {
+        for_each_meshgl_triangle(mesh, |tri| write_binary_facet(writer, &tri))?;
+    }
+
+    Ok(())
+}
+
+/// Call `func` with each triangle of a mesh, with its normal, in order.
+fn for_each_meshgl_triangle(mesh: &MeshGL, mut func: impl FnMut(StlTriangle) -> io::Result<()>) -> io::Result<()> {
+    let stride = mesh.count_vertex_properties();
+    let mut verts = vec![];
+    mesh.vertex_property_data_into(&mut verts);
+    let mut tris = vec![];
+    mesh.triangle_vertex_data_into(&mut tris);
+
+    let point = |index: u32| {
+        let start = index as usize * stride;
+        Vec3::new(verts[start], verts[start + 1], verts[start + 2])
+    };
+
+    for tri in tris.chunks_exact(3) {
+        let points = [point(tri[0]), point(tri[1]), point(tri[2])];
+        let normal = triangle_normal(points[0], points[1], points[2]);
+        func(StlTriangle { normal, points })?;
+    }
+
+    Ok(())
+}
+
+fn write_text_facet<I: io::Write>(writer: &mut I, tri: &StlTriangle, options: &TextStlOptions) -> io::Result<()> {
+    let fmt = |value: f32| options.display_number(value);
+    let StlTriangle { normal, points } = tri;
+
+    writeln!(writer, "facet normal {} {} {}", fmt(normal.x), fmt(normal.y), fmt(normal.z))?;
+    writeln!(writer, "  outer loop")?;
+    for point in points {
+        writeln!(writer, "    vertex {} {} {}", fmt(point.x), fmt(point.y), fmt(point.z))?;
+    }
+    writeln!(writer, "  endloop")?;
+    writeln!(writer, "endfacet")?;
+
+    Ok(())
+}
+
+/// Write the header of a binary STL, which is 80 bytes of anything followed by the number of
+/// triangles.
+fn write_binary_header<I: io::Write>(writer: &mut I, name: &str, count: usize) -> io::Result<()> {
+    let count = u32::try_from(count)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("{count} triangles is too many for a binary STL")))?;
+
+    let mut header = [0u8; 80];
+    let name = &name.as_bytes()[..name.len().min(header.len())];
+    header[..name.len()].copy_from_slice(name);
+    writer.write_all(&header)?;
+    writer.write_all(&count.to_le_bytes())
+}
+
+/// Write one triangle of a binary STL, as its normal and points followed by an unused 2-byte
+/// attribute.
+fn write_binary_facet<I: io::Write>(writer: &mut I, tri: &StlTriangle) -> io::Result<()> {
+    let StlTriangle { normal, points } = tri;
+    for vector in [normal, &points[0], &points[1], &points[2]] {
+        for value in [vector.x, vector.y, vector.z] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    writer.write_all(&[0, 0])
+}
+
+/// Controls how numbers are written in a textual STL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextStlOptions {
+    /// The maximum number of decimal places to write. Trailing zeroes are omitted.
+    /// 
+    /// If [`None`], numbers are written in the shortest form which reads back as the same `f32`.
+    pub precision: Option<usize>,
+}
+
+impl TextStlOptions {
+    /// Format a number for writing. Negative zero, including values which round to it, is always
+    /// written as `0` so that the output doesn't depend on which one Manifold happened to produce.
+    pub fn format_number(&self, value: f32) -> String {
+        self.display_number(value).to_string()
+    }
+
+    fn display_number(&self, value: f32) -> DisplayNumber {
+        DisplayNumber { value, precision: self.precision }
+    }
+}
+
+/// Implements the formatting for [`TextStlOptions::format_number`].
+/// 
+/// With the default options, this writes without allocating, which matters when writing out very
+/// large meshes.
+struct DisplayNumber {
+    value: f32,
+    precision: Option<usize>,
+}
+
+impl Display for DisplayNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.precision {
+            // `f32`'s `Display` already produces the shortest round-trip representation
+            None if self.value == 0.0 => write!(f, "0"),
+            None => write!(f, "{}", self.value),
+
+            Some(precision) => {
+                let mut formatted = format!("{:.precision$}", self.value);
+                if formatted.contains('.') {
+                    let trimmed_len = formatted.trim_end_matches('0').trim_end_matches('.').len();
+                    formatted.truncate(trimmed_len);
+                }
+
+                if formatted == "-0" {
+                    write!(f, "0")
+                } else {
+                    write!(f, "{formatted}")
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Vec3, ext::triangle_normal};
+
+    use super::{Stl, StlTriangle, TextStlOptions};
+
+    /// A cube from the origin to (1, 2, 3), with outward-facing triangles.
+    fn cube() -> Stl {
+        let corner = |i: usize| Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32 * 2.0, ((i >> 2) & 1) as f32 * 3.0);
+        let faces = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+
+        let mut stl = Stl::new("cube");
+        for [a, b, c, d] in faces {
+            for points in [[corner(a), corner(b), corner(c)], [corner(a), corner(c), corner(d)]] {
+                stl.add_triangle(triangle_normal(points[0], points[1], points[2]), points);
+            }
+        }
+        stl
+    }
+
+    /// Read a binary STL's header and its triangles.
+    fn read_binary_stl(bytes: &[u8]) -> (&[u8], Vec<StlTriangle>) {
+        let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+        assert_eq!(bytes.len(), 84 + count * 50);
+
+        let triangles = bytes[84..].chunks_exact(50)
+            .map(|record| {
+                assert_eq!(&record[48..], &[0, 0]);
+                let value = |i: usize| f32::from_le_bytes(record[i * 4..i * 4 + 4].try_into().unwrap());
+                let vector = |i: usize| Vec3::new(value(i * 3), value(i * 3 + 1), value(i * 3 + 2));
+                StlTriangle { normal: vector(0), points: [vector(1), vector(2), vector(3)] }
+            })
+            .collect();
+        (&bytes[..80], triangles)
+    }
+
+    /// Read the numbers on the normal and vertex lines of a textual STL.
+    fn read_text_stl(text: &str) -> Vec<StlTriangle> {
+        let vectors = text.lines()
+            .filter_map(|line| line.trim().strip_prefix("facet normal ").or(line.trim().strip_prefix("vertex ")))
+            .map(|numbers| {
+                let numbers = numbers.split(' ').map(|n| n.parse().unwrap()).collect::<Vec<f32>>();
+                Vec3::new(numbers[0], numbers[1], numbers[2])
+            })
+            .collect::<Vec<_>>();
+        vectors.chunks_exact(4)
+            .map(|v| StlTriangle { normal: v[0], points: [v[1], v[2], v[3]] })
+            .collect()
+    }
+
+    #[test]
+    fn test_binary_stl() {
+        let stl = cube();
+        let mut bytes = vec![];
+        stl.write_binary_stl(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 84 + 12 * 50);
+
+        let (header, triangles) = read_binary_stl(&bytes);
+        assert_eq!(&header[..4], b"cube");
+        assert!(header[4..].iter().all(|byte| *byte == 0));
+        assert_eq!(triangles, stl.triangles);
+
+        // The first triangle is on the bottom face, so points down
+        assert_eq!(triangles[0].normal, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(&bytes[84..96], [0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x00, 0x80, 0xbf]);
+
+        // Same triangles, in the same order, as the textual form
+        let mut text = vec![];
+        stl.write_text_stl(&mut text).unwrap();
+        assert_eq!(read_text_stl(&String::from_utf8(text).unwrap()), triangles);
+    }
+
+    #[test]
+    fn test_binary_stl_header() {
+        let mut bytes = vec![];
+        Stl::new(&"x".repeat(100)).write_binary_stl(&mut bytes).unwrap();
+        assert_eq!(bytes, [[b'x'; 80].as_slice(), &[0, 0, 0, 0]].concat());
+    }
+
+    #[test]
+    fn test_read_stl() {
+        let stl = cube();
+
+        let mut binary = vec![];
+        stl.write_binary_stl(&mut binary).unwrap();
+        assert_eq!(Stl::read(&binary).unwrap(), stl);
+
+        let mut text = vec![];
+        stl.write_text_stl(&mut text).unwrap();
+        assert_eq!(Stl::read(&text).unwrap(), stl);
+
+        // A binary STL whose header happens to look textual is still read as binary
+        let mut header = b"solid cube".to_vec();
+        header.resize(80, 0);
+        let disguised = [header.as_slice(), &binary[80..]].concat();
+        assert_eq!(Stl::read(&disguised).unwrap().triangles, stl.triangles);
+
+        // Whitespace doesn't matter, and the name is optional
+        let loose = "solid\n\n  facet normal 0 0 1\nouter loop\n vertex 0 0 0\n vertex  1 0 0\n\tvertex 0 1 0\n endloop\nendfacet\nendsolid";
+        let read = Stl::read(loose.as_bytes()).unwrap();
+        assert_eq!(read.name, "");
+        assert_eq!(read.triangles[0].points[1], Vec3::new(1.0, 0.0, 0.0));
+
+        let error = |bytes: &[u8]| Stl::read(bytes).unwrap_err().to_string();
+        assert_eq!(error(b"hello"), "not an STL file");
+        assert_eq!(error(&binary[..200]), "not an STL file - a binary STL with 12 triangles would be 684 bytes, but this is 200");
+        assert_eq!(error(b"solid x\nfacet normal 0 0 1\nvertex 0 0\n"), "line 3: expected 3 numbers, got 2");
+        assert_eq!(error(b"solid x\nfacet normal 0 0 1\nvertex 0 0 a\n"), "line 3: `a` is not a number");
+        assert_eq!(error(b"solid x\nfacet normal 0 0 1\nvertex 0 0 0\nendfacet\n"), "line 4: facets need 3 vertices, but this one has 1");
+        assert_eq!(error(b"solid x\nvertex 0 0 0\n"), "line 2: unexpected `vertex 0 0 0`");
+        assert_eq!(error(b"solid x\n"), "the file ends without `endsolid`");
+    }
+
+    #[test]
+    fn test_quantise() {
+        let stl = cube();
+        assert_eq!(stl.triangle_count(), 12);
+        assert_eq!(stl.vertex_count(), 8);
+        assert_eq!(stl.bounds(), Some((Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0))));
+        assert_eq!(Stl::new("empty").bounds(), None);
+
+        // Floating point error, and negative zero, is snapped away
+        let mut jittered = Stl::new("cube");
+        for tri in &cube().triangles {
+            let jitter = |point: Vec3<f32>| point.map(|value| if value == 0.0 { -0.0001 } else { value + 0.0001 });
+            jittered.add_triangle(tri.normal, tri.points.map(jitter));
+        }
+        assert_ne!(jittered, stl);
+        jittered.quantise(1.0 / 1024.0);
+        assert_eq!(jittered, stl);
+        assert!(jittered.triangles.iter().all(|tri| tri.points.iter().all(|point| !point.x.is_sign_negative())));
+
+        // A coarse step can merge points together
+        let mut coarse = cube();
+        coarse.quantise(4.0);
+        assert_eq!(coarse.vertex_count(), 4);
+        assert_eq!(coarse.bounds(), Some((Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 4.0))));
+    }
+
+    #[test]
+    fn test_format_number() {
+        let shortest = TextStlOptions::default();
+        assert_eq!(shortest.format_number(0.3), "0.3");
+        assert_eq!(shortest.format_number(10.0), "10");
+        assert_eq!(shortest.format_number(-2.5), "-2.5");
+        assert_eq!(shortest.format_number(1.0 / 3.0), "0.33333334");
+        assert_eq!(shortest.format_number(-0.0), "0");
+
+        let fixed = TextStlOptions { precision: Some(3) };
+        assert_eq!(fixed.format_number(0.3), "0.3");
+        assert_eq!(fixed.format_number(10.0), "10");
+        assert_eq!(fixed.format_number(1.0 / 3.0), "0.333");
+        assert_eq!(fixed.format_number(2.99999), "3");
+        assert_eq!(fixed.format_number(-0.0001), "0");
+        assert_eq!(fixed.format_number(-0.0), "0");
+        assert_eq!(TextStlOptions { precision: Some(0) }.format_number(12.6), "13");
     }
 }
@@ -0,0 +1,56 @@
+use std::io;
+
+use crate::{CrossSection, ext::PolyNode};
+
+/// Write out a cross section as an SVG image, for use as a 2D outline in other tools.
+/// 
+/// One SVG user unit is one unit of the cross section, and the image is sized to match in
+/// millimetres. SVG's Y axis points down, so the cross section is flipped such that it appears the
+/// same way up as if looking down onto the X/Y plane.
+/// 
+/// Each outer boundary is followed by its holes, which go in the opposite direction, so that tools
+/// which look at path directions can tell them apart.
+pub fn write_svg<I: io::Write>(cross_section: &CrossSection, writer: &mut I) -> io::Result<()> {
+    let tree = cross_section.polygon_tree();
+
+    // Flip the Y axis, without introducing any -0s
+    let flip = |y: f64| if y == 0.0 { 0.0 } else { -y };
+
+    let (min_x, min_y, width, height) =
+        if tree.is_empty() {
+            (0.0, 0.0, 0.0, 0.0)
+        } else {
+            let rect = cross_section.bounding_rectangle();
+            let (min, size) = (rect.min_point(), rect.size());
+            (min.x, flip(rect.max_point().y), size.x, size.y)
+        };
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}mm" height="{height}mm" viewBox="{min_x} {min_y} {width} {height}">"#,
+    )?;
+
+    let mut path = String::new();
+    for node in &tree {
+        push_path(node, &mut path, &flip);
+    }
+    writeln!(writer, r#"  <path d="{}" fill="black" fill-rule="nonzero"/>"#, path.trim_end())?;
+
+    writeln!(writer, "</svg>")?;
+
+    Ok(())
+}
+
+/// Add a contour and everything inside it to an SVG path.
+fn push_path(node: &PolyNode, path: &mut String, flip: &impl Fn(f64) -> f64) {
+    for (i, point) in node.outline.iter().enumerate() {
+        let command = if i == 0 { "M" } else { "L" };
+        path.push_str(&format!("{command} {} {} ", point.x, flip(point.y)));
+    }
+    path.push('Z');
+    path.push(' ');
+
+    for hole in &node.holes {
+        push_path(hole, path, flip);
+    }
+}
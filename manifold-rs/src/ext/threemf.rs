@@ -0,0 +1,95 @@
+use std::io;
+
+use crate::{MeshGL, ext::zip::ZipArchive};
+
+/// One object to include in a 3MF file.
+pub struct ThreeMfObject<'a> {
+    /// Shown by slicers to identify the object.
+    pub name: Option<&'a str>,
+    pub mesh: &'a MeshGL,
+}
+
+/// Write out meshes as a 3MF file, with each as a separate named object.
+/// 
+/// `unit` is the 3MF name of the unit which the meshes are measured in, such as `"millimeter"` or
+/// `"inch"`.
+/// 
+/// Each of `metadata` is written as a `<metadata>` element, with a name and a value. The names
+/// should be ones defined by the 3MF specification, like `"Title"` or `"Application"`.
+pub fn write_3mf<I: io::Write>(objects: &[ThreeMfObject], unit: &str, metadata: &[(&str, &str)], writer: &mut I) -> io::Result<()> {
+    let mut archive = ZipArchive::new();
+    archive.add_file("[Content_Types].xml", CONTENT_TYPES.as_bytes())?;
+    archive.add_file("_rels/.rels", RELATIONSHIPS.as_bytes())?;
+
+    let mut model = vec![];
+    write_model_xml(objects, unit, metadata, &mut model)?;
+    archive.add_file("3D/3dmodel.model", &model)?;
+
+    archive.write(writer)
+}
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+  <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+  <Default Extension="model" ContentType="application/vnd.ms-package.3dmanufacturing-3dmodel+xml"/>
+</Types>
+"#;
+
+const RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Target="/3D/3dmodel.model" Id="rel0" Type="http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel"/>
+</Relationships>
+"#;
+
+fn write_model_xml<I: io::Write>(objects: &[ThreeMfObject], unit: &str, metadata: &[(&str, &str)], xml: &mut I) -> io::Result<()> {
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(xml, r#"<model unit="{}" xml:lang="en-US" xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02">"#, escape_xml(unit))?;
+    for (name, value) in metadata {
+        writeln!(xml, r#"  <metadata name="{}">{}</metadata>"#, escape_xml(name), escape_xml(value))?;
+    }
+    writeln!(xml, "  <resources>")?;
+
+    for (i, object) in objects.iter().enumerate() {
+        let name = object.name
+            .map(|name| format!(r#" name="{}""#, escape_xml(name)))
+            .unwrap_or_default();
+        writeln!(xml, r#"    <object id="{}" type="model"{name}>"#, i + 1)?;
+        writeln!(xml, "      <mesh>")?;
+
+        let stride = object.mesh.count_vertex_properties();
+        writeln!(xml, "        <vertices>")?;
+        for vertex in object.mesh.vertex_property_data().chunks_exact(stride) {
+            writeln!(xml, r#"          <vertex x="{}" y="{}" z="{}"/>"#, vertex[0], vertex[1], vertex[2])?;
+        }
+        writeln!(xml, "        </vertices>")?;
+
+        let mut triangles = vec![];
+        object.mesh.triangle_vertex_data_into(&mut triangles);
+        writeln!(xml, "        <triangles>")?;
+        for triangle in triangles.chunks_exact(3) {
+            writeln!(xml, r#"          <triangle v1="{}" v2="{}" v3="{}"/>"#, triangle[0], triangle[1], triangle[2])?;
+        }
+        writeln!(xml, "        </triangles>")?;
+
+        writeln!(xml, "      </mesh>")?;
+        writeln!(xml, "    </object>")?;
+    }
+
+    writeln!(xml, "  </resources>")?;
+    writeln!(xml, "  <build>")?;
+    for i in 0..objects.len() {
+        writeln!(xml, r#"    <item objectid="{}"/>"#, i + 1)?;
+    }
+    writeln!(xml, "  </build>")?;
+    writeln!(xml, "</model>")?;
+
+    Ok(())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
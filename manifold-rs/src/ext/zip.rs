@@ -0,0 +1,105 @@
+//! A minimal writer for ZIP archives, as needed for 3MF's container format.
+//! 
+//! Files are stored without compression, which every ZIP reader supports.
+
+use std::io;
+
+/// A ZIP archive being built in memory.
+pub struct ZipArchive {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    file_count: u16,
+}
+
+impl ZipArchive {
+    pub fn new() -> Self {
+        Self { data: vec![], central_directory: vec![], file_count: 0 }
+    }
+
+    /// Add a file to the archive.
+    pub fn add_file(&mut self, name: &str, contents: &[u8]) -> io::Result<()> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "too large for a ZIP archive without ZIP64");
+        let size = u32::try_from(contents.len()).map_err(|_| too_large())?;
+        let offset = u32::try_from(self.data.len()).map_err(|_| too_large())?;
+        let name_length = u16::try_from(name.len()).map_err(|_| too_large())?;
+        let crc = crc32(contents);
+
+        // Fields shared between the local file header and central directory, starting from
+        // "version needed to extract"
+        let mut common = vec![];
+        common.extend(20u16.to_le_bytes()); // Version 2.0
+        common.extend(0u16.to_le_bytes()); // Flags
+        common.extend(0u16.to_le_bytes()); // Stored, not compressed
+        common.extend(0u16.to_le_bytes()); // Modification time
+        common.extend(0x21u16.to_le_bytes()); // Modification date - 1980-01-01, the earliest possible
+        common.extend(crc.to_le_bytes());
+        common.extend(size.to_le_bytes()); // Compressed size
+        common.extend(size.to_le_bytes()); // Uncompressed size
+        common.extend(name_length.to_le_bytes());
+        common.extend(0u16.to_le_bytes()); // Extra field length
+
+        self.data.extend(0x04034b50u32.to_le_bytes());
+        self.data.extend(&common);
+        self.data.extend(name.as_bytes());
+        self.data.extend(contents);
+
+        self.central_directory.extend(0x02014b50u32.to_le_bytes());
+        self.central_directory.extend(20u16.to_le_bytes()); // Version made by
+        self.central_directory.extend(&common);
+        self.central_directory.extend(0u16.to_le_bytes()); // Comment length
+        self.central_directory.extend(0u16.to_le_bytes()); // Disk number
+        self.central_directory.extend(0u16.to_le_bytes()); // Internal attributes
+        self.central_directory.extend(0u32.to_le_bytes()); // External attributes
+        self.central_directory.extend(offset.to_le_bytes());
+        self.central_directory.extend(name.as_bytes());
+
+        self.file_count = self.file_count.checked_add(1).ok_or_else(too_large)?;
+        Ok(())
+    }
+
+    /// Write out the finished archive.
+    pub fn write<I: io::Write>(&self, writer: &mut I) -> io::Result<()> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "too large for a ZIP archive without ZIP64");
+        let directory_offset = u32::try_from(self.data.len()).map_err(|_| too_large())?;
+        let directory_size = u32::try_from(self.central_directory.len()).map_err(|_| too_large())?;
+
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.central_directory)?;
+
+        // End of central directory record
+        writer.write_all(&0x06054b50u32.to_le_bytes())?;
+        writer.write_all(&0u16.to_le_bytes())?; // Disk number
+        writer.write_all(&0u16.to_le_bytes())?; // Disk with central directory
+        writer.write_all(&self.file_count.to_le_bytes())?; // Entries on this disk
+        writer.write_all(&self.file_count.to_le_bytes())?; // Total entries
+        writer.write_all(&directory_size.to_le_bytes())?;
+        writer.write_all(&directory_offset.to_le_bytes())?;
+        writer.write_all(&0u16.to_le_bytes())?; // Comment length
+
+        Ok(())
+    }
+}
+
+/// Calculate the CRC-32 checksum used by ZIP.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::crc32;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+}
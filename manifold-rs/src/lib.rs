@@ -21,4 +21,10 @@ pub use bounding_box::*;
 mod common;
 pub use common::*;
 
+mod matrix;
+pub use matrix::*;
+
+mod error;
+pub use error::ManifoldError;
+
 pub mod ext;
@@ -1,6 +1,6 @@
 use std::{fmt::Debug, os::raw::c_void};
 
-use crate::{BoundingBox, Polygons, meshgl::MeshGL, raw};
+use crate::{BoundingBox, CrossSection, ManifoldError, Matrix3x4, Polygons, error::{check_alloc, check_status, expect_alloc}, meshgl::MeshGL, raw};
 
 pub struct Manifold {
     pub(crate) ptr: *mut raw::ManifoldManifold,
@@ -20,18 +20,32 @@ impl Manifold {
     /// Safety: This is actually safe, as the FFI interface allocates a valid empty manifold.
     /// However, further initialisation is probably intended. If you really want an empty manifold,
     /// use [`Manifold::new`].
-    unsafe fn alloc() -> Self {
+    unsafe fn alloc() -> Result<Self, ManifoldError> {
         unsafe {
-            Self::from_raw(raw::manifold_alloc_manifold())
+            Ok(Self::from_raw(check_alloc(raw::manifold_alloc_manifold())?))
         }
     }
 
-    /// Shorthand for `alloc` followed by an initialiser on the raw pointer.
+    /// Shorthand for `alloc` followed by an initialiser on the raw pointer, which then checks that
+    /// the initialised manifold is valid.
     /// 
     /// Safety: See `alloc`.
-    unsafe fn alloc_build<R>(func: impl FnOnce(*mut c_void) -> R) -> Self {
+    unsafe fn alloc_build<R>(func: impl FnOnce(*mut c_void) -> R) -> Result<Self, ManifoldError> {
         unsafe {
-            let manifold = Self::alloc();
+            let manifold = Self::alloc()?;
+            func(manifold.ptr as *mut c_void);
+            check_status(raw::manifold_status(manifold.ptr))?;
+            Ok(manifold)
+        }
+    }
+
+    /// Like `alloc_build`, but for operations on an existing manifold, which Manifold will always
+    /// turn into another valid manifold. The only possible failure is allocation, which panics.
+    /// 
+    /// Safety: See `alloc`.
+    unsafe fn alloc_derive<R>(func: impl FnOnce(*mut c_void) -> R) -> Self {
+        unsafe {
+            let manifold = Self::from_raw(expect_alloc(raw::manifold_alloc_manifold()));
             func(manifold.ptr as *mut c_void);
             manifold
         }
@@ -39,11 +53,11 @@ impl Manifold {
 
     /// Create an empty manifold.
     pub fn new() -> Self {
-        unsafe { Self::alloc() }
+        unsafe { Self::from_raw(expect_alloc(raw::manifold_alloc_manifold())) }
     }
 
     /// Create a manifold of a cube.
-    pub fn cube(x: f64, y: f64, z: f64, centre: bool) -> Self {
+    pub fn cube(x: f64, y: f64, z: f64, centre: bool) -> Result<Self, ManifoldError> {
         unsafe {
             Self::alloc_build(|ptr|
                 raw::manifold_cube(ptr, x, y, z, if centre { 1 } else { 0 }))
@@ -51,21 +65,28 @@ impl Manifold {
     }
 
     /// Create a new manifold of a cylinder.
-    pub fn cylinder(radius: f64, height: f64, segments: i32, centre: bool) -> Self {
+    pub fn cylinder(radius: f64, height: f64, segments: i32, centre: bool) -> Result<Self, ManifoldError> {
         Self::cone(radius, radius, height, segments, centre)
     }
 
     /// Create a new manifold of a cone.
-    pub fn cone(radius_low: f64, radius_high: f64, height: f64, segments: i32, centre: bool) -> Self {
+    pub fn cone(radius_low: f64, radius_high: f64, height: f64, segments: i32, centre: bool) -> Result<Self, ManifoldError> {
         unsafe {
             Self::alloc_build(|ptr|
                 raw::manifold_cylinder(ptr, height, radius_low, radius_high, segments, if centre { 1 } else { 0 }))
         }
     }
 
+    /// Create a new manifold of a sphere, centred on the origin.
+    pub fn sphere(radius: f64, segments: i32) -> Result<Self, ManifoldError> {
+        unsafe {
+            Self::alloc_build(|ptr| raw::manifold_sphere(ptr, radius, segments))
+        }
+    }
+
     /// Create a new manifold by extruding a list of [`Polygons`], usually obtained from a
     /// [`CrossSection`](`crate::CrossSection`).
-    pub fn extrude(polygons: Polygons, height: f64) -> Self {
+    pub fn extrude(polygons: Polygons, height: f64) -> Result<Self, ManifoldError> {
         // TODO: support twisty options too - using C++ defaults for now
         unsafe {
             Self::alloc_build(|ptr|
@@ -83,17 +104,27 @@ impl Manifold {
     /// 
     /// If the shape crosses into the negative X axis, the negative parts will be sliced off by
     /// the extrusion.
-    pub fn revolve(polygons: Polygons, segments: i32, degrees: f64) -> Self {
+    pub fn revolve(polygons: Polygons, segments: i32, degrees: f64) -> Result<Self, ManifoldError> {
         unsafe {
             Self::alloc_build(|ptr|
                 raw::manifold_revolve(ptr, polygons.ptr, segments, degrees))
         }
     }
 
+    /// Create a new manifold from a mesh, such as one built with
+    /// [`MeshGL::from_vertices_and_triangles`].
+    /// 
+    /// Fails if the mesh isn't a valid closed manifold.
+    pub fn from_meshgl(mesh: &MeshGL) -> Result<Self, ManifoldError> {
+        unsafe {
+            Self::alloc_build(|ptr| raw::manifold_of_meshgl(ptr, mesh.ptr))
+        }
+    }
+
     /// Create a new manifold which is a translation of this one.
     pub fn translate(&self, x: f64, y: f64, z: f64) -> Self {
         unsafe {
-            Self::alloc_build(|ptr|
+            Self::alloc_derive(|ptr|
                 raw::manifold_translate(ptr, self.ptr, x, y, z))
         }
     }
@@ -101,7 +132,7 @@ impl Manifold {
     /// Create a new manifold which is a rotation of this one.
     pub fn rotate(&self, x: f64, y: f64, z: f64) -> Self {
         unsafe {
-            Self::alloc_build(|ptr|
+            Self::alloc_derive(|ptr|
                 raw::manifold_rotate(ptr, self.ptr, x, y, z))
         }
     }
@@ -109,7 +140,7 @@ impl Manifold {
     /// Create a new manifold by scaling this one.
     pub fn scale(&self, x: f64, y: f64, z: f64) -> Self {
         unsafe {
-            Self::alloc_build(|ptr|
+            Self::alloc_derive(|ptr|
                 raw::manifold_scale(ptr, self.ptr, x, y, z))
         }
     }
@@ -118,15 +149,104 @@ impl Manifold {
     /// origin, which the manifold is mirrored "through".
     pub fn mirror(&self, x: f64, y: f64, z: f64) -> Self {
         unsafe {
-            Self::alloc_build(|ptr|
+            Self::alloc_derive(|ptr|
                 raw::manifold_mirror(ptr, self.ptr, x, y, z))
         }
     }
 
+    /// Create a new manifold by applying an arbitrary affine transformation to this one.
+    pub fn transform(&self, matrix: &Matrix3x4) -> Self {
+        let [c0, c1, c2, c3] = matrix.columns;
+        unsafe {
+            Self::alloc_derive(|ptr|
+                raw::manifold_transform(ptr, self.ptr, c0.x, c0.y, c0.z, c1.x, c1.y, c1.z, c2.x, c2.y, c2.z, c3.x, c3.y, c3.z))
+        }
+    }
+
+    /// Create a new manifold containing all of the given manifolds, without doing a union.
+    /// 
+    /// This is much faster than a union, but is only valid if none of the manifolds overlap -
+    /// otherwise the result isn't a proper manifold, and later operations on it may misbehave.
+    pub fn compose(manifolds: &[Manifold]) -> Self {
+        unsafe {
+            let vec = expect_alloc(raw::manifold_alloc_manifold_vec());
+            raw::manifold_manifold_empty_vec(vec as *mut c_void);
+            for manifold in manifolds {
+                // This copies the manifold into the vector
+                raw::manifold_manifold_vec_push_back(vec, manifold.ptr);
+            }
+
+            let result = Self::alloc_derive(|ptr| raw::manifold_compose(ptr, vec));
+            raw::manifold_delete_manifold_vec(vec);
+            result
+        }
+    }
+
+    /// Split this manifold into its disconnected components - the opposite of
+    /// [`Manifold::compose`].
+    pub fn decompose(&self) -> Vec<Manifold> {
+        unsafe {
+            let vec = expect_alloc(raw::manifold_alloc_manifold_vec());
+            raw::manifold_decompose(vec as *mut c_void, self.ptr);
+
+            let components = (0..raw::manifold_manifold_vec_length(vec))
+                .map(|i| Self::alloc_derive(|ptr| raw::manifold_manifold_vec_get(ptr, vec, i)))
+                .collect();
+            raw::manifold_delete_manifold_vec(vec);
+            components
+        }
+    }
+
+    /// Create a new manifold which is the convex hull of this one - the smallest convex shape
+    /// which contains it.
+    pub fn hull(&self) -> Self {
+        unsafe {
+            Self::alloc_derive(|ptr|
+                raw::manifold_hull(ptr, self.ptr))
+        }
+    }
+
+    /// Create a new manifold which is the convex hull of all of the given manifolds together.
+    /// 
+    /// This is the same as the hull of their union, but much faster, since the union is never
+    /// computed.
+    pub fn hull_many(manifolds: &[Manifold]) -> Self {
+        unsafe {
+            let vec = expect_alloc(raw::manifold_alloc_manifold_vec());
+            raw::manifold_manifold_empty_vec(vec as *mut c_void);
+            for manifold in manifolds {
+                // This copies the manifold into the vector
+                raw::manifold_manifold_vec_push_back(vec, manifold.ptr);
+            }
+
+            let result = Self::alloc_derive(|ptr| raw::manifold_batch_hull(ptr, vec));
+            raw::manifold_delete_manifold_vec(vec);
+            result
+        }
+    }
+
+    /// Create a new manifold with the given tolerance. Booleans between manifolds use the larger of
+    /// their tolerances, and features (like slivers) smaller than it may be removed.
+    /// 
+    /// The tolerance can only be increased - lower values than the current tolerance have no
+    /// effect.
+    pub fn set_tolerance(&self, tolerance: f64) -> Self {
+        unsafe {
+            Self::alloc_derive(|ptr|
+                raw::manifold_set_tolerance(ptr, self.ptr, tolerance))
+        }
+    }
+
+    pub fn tolerance(&self) -> f64 {
+        unsafe {
+            raw::manifold_get_tolerance(self.ptr)
+        }
+    }
+
     /// Create a new manifold by subtracting another manifold from this one.
     pub fn difference(&self, other: &Manifold) -> Self {
         unsafe {
-            Self::alloc_build(|ptr|
+            Self::alloc_derive(|ptr|
                 raw::manifold_difference(ptr, self.ptr, other.ptr))
         }
     }
@@ -134,21 +254,56 @@ impl Manifold {
     /// Create a new manifold which combines two others.
     pub fn union(&self, other: &Manifold) -> Self {
         unsafe {
-            Self::alloc_build(|ptr|
+            Self::alloc_derive(|ptr|
                 raw::manifold_union(ptr, self.ptr, other.ptr))
         }
     }
 
+    /// Create a new manifold containing only the volume shared by this manifold and another.
+    pub fn intersection(&self, other: &Manifold) -> Self {
+        unsafe {
+            Self::alloc_derive(|ptr|
+                raw::manifold_intersection(ptr, self.ptr, other.ptr))
+        }
+    }
+
+    /// Get the cross section where this manifold intersects the horizontal plane at height `z`.
+    pub fn slice(&self, z: f64) -> CrossSection {
+        let polygons = unsafe {
+            Polygons::alloc_build(|ptr|
+                raw::manifold_slice(ptr, self.ptr, z))
+        };
+        CrossSection::from_polygons(&polygons)
+    }
+
+    /// Create a new manifold with vertex normals stored in its properties, starting at property
+    /// `normal_channel` (counted from the first property after the position). Edges where faces
+    /// meet at more than `min_sharp_angle` degrees are kept sharp, and everywhere else is smoothed.
+    /// 
+    /// Use [`Manifold::meshgl_with_normals`] with the same channel to get a mesh which keeps them
+    /// up-to-date.
+    pub fn calculate_normals(&self, normal_channel: usize, min_sharp_angle: f64) -> Self {
+        unsafe {
+            Self::alloc_derive(|ptr|
+                raw::manifold_calculate_normals(ptr, self.ptr, normal_channel as i32, min_sharp_angle))
+        }
+    }
+
     /// Get a [`MeshGL`] for this manifold.
     pub fn meshgl(&self) -> MeshGL {
         MeshGL::from_manifold(self)
     }
 
+    /// Get a [`MeshGL`] for this manifold, where properties starting at `normal_channel` are
+    /// vertex normals - see [`Manifold::calculate_normals`].
+    pub fn meshgl_with_normals(&self, normal_channel: usize) -> MeshGL {
+        MeshGL::from_manifold_with_normals(self, normal_channel)
+    }
+
     pub fn bounding_box(&self) -> BoundingBox {
         unsafe {
-            let bbox = BoundingBox::new();
-            raw::manifold_bounding_box(bbox.ptr as *mut c_void, self.ptr);
-            bbox
+            BoundingBox::alloc_build(|ptr|
+                raw::manifold_bounding_box(ptr, self.ptr))
         }
     }
 
@@ -157,12 +312,85 @@ impl Manifold {
             raw::manifold_num_vert(self.ptr)
         }
     }
+
+    pub fn count_triangles(&self) -> usize {
+        unsafe {
+            raw::manifold_num_tri(self.ptr)
+        }
+    }
+
+    /// Compute the result of any operations which produced this manifold, and check that it's
+    /// valid.
+    /// 
+    /// Manifold defers boolean operations until their result is first needed, so that it can
+    /// batch them together. This forces the work to happen now - for example, on a thread which
+    /// can be abandoned if it takes too long.
+    pub fn evaluate(&self) -> Result<(), ManifoldError> {
+        unsafe {
+            check_status(raw::manifold_status(self.ptr))
+        }
+    }
+
+    /// Whether this manifold contains no geometry at all.
+    pub fn is_empty(&self) -> bool {
+        unsafe {
+            raw::manifold_is_empty(self.ptr) != 0
+        }
+    }
+
+    pub fn volume(&self) -> f64 {
+        unsafe {
+            raw::manifold_volume(self.ptr)
+        }
+    }
+
+    /// The total area of this manifold's surface.
+    pub fn surface_area(&self) -> f64 {
+        unsafe {
+            raw::manifold_surface_area(self.ptr)
+        }
+    }
+
+    /// The ID which Manifold gave this manifold when it became an original - see
+    /// [`Self::as_original`]. [`None`] if it isn't an original, such as the result of a boolean.
+    pub fn original_id(&self) -> Option<u32> {
+        unsafe {
+            u32::try_from(raw::manifold_original_id(self.ptr)).ok()
+        }
+    }
+
+    /// Create a copy of this manifold which is a new original, with a new [`Self::original_id`].
+    /// 
+    /// Manifold tracks which original each triangle came from through later operations, so the
+    /// triangles of a [`MeshGL`] can be traced back to it with [`MeshGL::triangle_original_ids`].
+    pub fn as_original(&self) -> Self {
+        unsafe {
+            Self::alloc_derive(|ptr|
+                raw::manifold_as_original(ptr, self.ptr))
+        }
+    }
+
+    /// The topological genus of this manifold - the number of "handles" or holes through it.
+    pub fn genus(&self) -> i32 {
+        unsafe {
+            raw::manifold_genus(self.ptr)
+        }
+    }
 }
 
+// Safety: A manifold owns its pointer, and Manifold doesn't use thread-local state, so it can be
+// used from any one thread at a time. Copies made by `clone` may share nodes of the deferred
+// operation tree, but Manifold reference-counts those atomically and guards their evaluation, so
+// copies on different threads don't interfere with each other.
+//
+// It isn't `Sync`, since nothing guarantees that Manifold's `const` methods are safe to call on the
+// same object from several threads at once.
+unsafe impl Send for Manifold {}
+
 impl Clone for Manifold {
     fn clone(&self) -> Self {
         unsafe {
-            Self::alloc_build(|ptr|
+            Self::alloc_derive(|ptr|
                 raw::manifold_copy(ptr, self.ptr))
         }
     }
@@ -0,0 +1,155 @@
+use crate::Vec3;
+
+/// An affine transformation in 3D, as accepted by [`crate::Manifold::transform`].
+///
+/// This is stored as four columns, like Manifold does. The first three columns are a linear
+/// transformation, and the last column is a translation applied afterwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix3x4 {
+    pub columns: [Vec3<f64>; 4],
+}
+
+impl Matrix3x4 {
+    /// A transformation which does nothing.
+    pub fn identity() -> Self {
+        Self {
+            columns: [
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 0.0, 0.0),
+            ],
+        }
+    }
+
+    /// A transformation which moves points by the given amounts.
+    pub fn translation(x: f64, y: f64, z: f64) -> Self {
+        let mut matrix = Self::identity();
+        matrix.columns[3] = Vec3::new(x, y, z);
+        matrix
+    }
+
+    /// A transformation which rotates points by the given number of degrees around the X, Y, and
+    /// then Z axes - the same as [`crate::Manifold::rotate`].
+    ///
+    /// Multiples of 90 degrees are exact.
+    pub fn rotation(x: f64, y: f64, z: f64) -> Self {
+        let (sx, cx) = sin_cos_degrees(x);
+        let (sy, cy) = sin_cos_degrees(y);
+        let (sz, cz) = sin_cos_degrees(z);
+
+        let rotate_x = Self::from_rows([1.0, 0.0, 0.0], [0.0, cx, -sx], [0.0, sx, cx]);
+        let rotate_y = Self::from_rows([cy, 0.0, sy], [0.0, 1.0, 0.0], [-sy, 0.0, cy]);
+        let rotate_z = Self::from_rows([cz, -sz, 0.0], [sz, cz, 0.0], [0.0, 0.0, 1.0]);
+        rotate_x.then(&rotate_y).then(&rotate_z)
+    }
+
+    /// A transformation which applies this one, and then `next`.
+    pub fn then(&self, next: &Matrix3x4) -> Self {
+        let [c0, c1, c2, c3] = self.columns;
+        Self {
+            columns: [
+                next.apply_linear(c0),
+                next.apply_linear(c1),
+                next.apply_linear(c2),
+                next.apply(c3),
+            ],
+        }
+    }
+
+    /// Transform a point.
+    pub fn apply(&self, point: Vec3<f64>) -> Vec3<f64> {
+        self.apply_linear(point) + self.columns[3]
+    }
+
+    /// Get this transformation as a 4x4 matrix in column-major order, as used by most graphics
+    /// libraries.
+    pub fn to_column_major_4x4(&self) -> [f64; 16] {
+        let [c0, c1, c2, c3] = self.columns;
+        [
+            c0.x, c0.y, c0.z, 0.0,
+            c1.x, c1.y, c1.z, 0.0,
+            c2.x, c2.y, c2.z, 0.0,
+            c3.x, c3.y, c3.z, 1.0,
+        ]
+    }
+
+    fn from_rows(r0: [f64; 3], r1: [f64; 3], r2: [f64; 3]) -> Self {
+        Self {
+            columns: [
+                Vec3::new(r0[0], r1[0], r2[0]),
+                Vec3::new(r0[1], r1[1], r2[1]),
+                Vec3::new(r0[2], r1[2], r2[2]),
+                Vec3::new(0.0, 0.0, 0.0),
+            ],
+        }
+    }
+
+    /// Transform a direction, ignoring the translation.
+    fn apply_linear(&self, v: Vec3<f64>) -> Vec3<f64> {
+        let [c0, c1, c2, _] = self.columns;
+        Vec3::new(
+            c0.x * v.x + c1.x * v.y + c2.x * v.z,
+            c0.y * v.x + c1.y * v.y + c2.y * v.z,
+            c0.z * v.x + c1.z * v.y + c2.z * v.z,
+        )
+    }
+}
+
+impl Default for Matrix3x4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Like [`f64::sin_cos`] but in degrees, and exact for multiples of 90 degrees so that right-angle
+/// rotations don't introduce rounding errors. Manifold does the same.
+fn sin_cos_degrees(degrees: f64) -> (f64, f64) {
+    let quarter_turns = degrees / 90.0;
+    if quarter_turns.fract() == 0.0 {
+        match quarter_turns.rem_euclid(4.0) as u8 {
+            0 => (0.0, 1.0),
+            1 => (1.0, 0.0),
+            2 => (0.0, -1.0),
+            _ => (-1.0, 0.0),
+        }
+    } else {
+        degrees.to_radians().sin_cos()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Matrix3x4, Vec3};
+
+    #[test]
+    fn test_rotation() {
+        let point = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(Matrix3x4::rotation(0.0, 0.0, 90.0).apply(point), Vec3::new(-2.0, 1.0, 3.0));
+        assert_eq!(Matrix3x4::rotation(0.0, 0.0, -270.0).apply(point), Vec3::new(-2.0, 1.0, 3.0));
+        assert_eq!(Matrix3x4::rotation(90.0, 0.0, 0.0).apply(point), Vec3::new(1.0, -3.0, 2.0));
+
+        // X is applied before Z
+        assert_eq!(Matrix3x4::rotation(90.0, 0.0, 90.0).apply(point), Vec3::new(3.0, 1.0, 2.0));
+
+        let rotated = Matrix3x4::rotation(0.0, 0.0, 45.0).apply(Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated.x - 0.5f64.sqrt()).abs() < 1e-12);
+        assert!((rotated.y - 0.5f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_then() {
+        let point = Vec3::new(1.0, 0.0, 0.0);
+        let translate_then_rotate = Matrix3x4::translation(1.0, 0.0, 0.0).then(&Matrix3x4::rotation(0.0, 0.0, 90.0));
+        let rotate_then_translate = Matrix3x4::rotation(0.0, 0.0, 90.0).then(&Matrix3x4::translation(1.0, 0.0, 0.0));
+
+        assert_eq!(translate_then_rotate.apply(point), Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(rotate_then_translate.apply(point), Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(Matrix3x4::identity().then(&rotate_then_translate), rotate_then_translate);
+
+        assert_eq!(
+            Matrix3x4::translation(1.0, 2.0, 3.0).to_column_major_4x4(),
+            [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 2.0, 3.0, 1.0],
+        );
+    }
+}
@@ -1,9 +1,13 @@
 use std::os::raw::c_void;
 
-use crate::{Vec3, manifold::Manifold, raw};
+use crate::{Vec3, error::expect_alloc, manifold::Manifold, raw};
 
 pub struct MeshGL {
-    ptr: *mut raw::ManifoldMeshGL,
+    pub(crate) ptr: *mut raw::ManifoldMeshGL,
+
+    /// The property index of the X component of the vertex normals, if this mesh has them. This
+    /// counts the position, unlike the channels passed to Manifold.
+    normal_property: Option<usize>,
 }
 
 impl MeshGL {
@@ -12,7 +16,7 @@ impl MeshGL {
     /// Safety: The pointer must be valid, unique, and point to an allocated manifold instance.
     /// The instance must be initialised, if not already, before further use.
     unsafe fn from_raw(ptr: *mut raw::ManifoldMeshGL) -> Self {
-        Self { ptr }
+        Self { ptr, normal_property: None }
     }
 
     /// Allocate an empty mesh.
@@ -22,7 +26,7 @@ impl MeshGL {
     /// use [`MeshGL::new`].
     unsafe fn alloc() -> Self {
         unsafe {
-            Self::from_raw(raw::manifold_alloc_meshgl())
+            Self::from_raw(expect_alloc(raw::manifold_alloc_meshgl()))
         }
     }
 
@@ -31,6 +35,31 @@ impl MeshGL {
         unsafe { Self::alloc() }
     }
 
+    /// Create a mesh from vertex positions, and triangles which are each three indices into
+    /// `vertices`, in counter-clockwise order when viewed from outside.
+    /// 
+    /// Turn this into a [`Manifold`] with [`Manifold::from_meshgl`], which checks that it's valid.
+    pub fn from_vertices_and_triangles(vertices: &[Vec3<f32>], triangles: &[[u32; 3]]) -> Self {
+        // Manifold copies these, but takes mutable pointers anyway
+        let mut vertex_properties = vertices.iter()
+            .flat_map(|vertex| [vertex.x, vertex.y, vertex.z])
+            .collect::<Vec<_>>();
+        let mut triangle_vertices = triangles.iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        unsafe {
+            let mesh = Self::alloc();
+            raw::manifold_meshgl(
+                mesh.ptr as *mut c_void,
+                vertex_properties.as_mut_ptr(), vertices.len(), POSITION_PROPERTIES,
+                triangle_vertices.as_mut_ptr(), triangles.len(),
+            );
+            mesh
+        }
+    }
+
     /// Create a mesh for a given manifold.
     pub fn from_manifold(manifold: &Manifold) -> Self {
         unsafe {
@@ -40,6 +69,24 @@ impl MeshGL {
         }
     }
 
+    /// Create a mesh for a given manifold, where properties starting at `normal_channel` are vertex
+    /// normals. Manifold keeps these normals correct as the manifold is transformed.
+    /// 
+    /// The normals can then be retrieved with [`Self::vertex_normals`].
+    pub fn from_manifold_with_normals(manifold: &Manifold, normal_channel: usize) -> Self {
+        unsafe {
+            let mut mesh = Self::alloc();
+            raw::manifold_get_meshgl_w_normals(mesh.ptr as *mut c_void, manifold.ptr, normal_channel as i32);
+
+            // Only trust the channel if Manifold actually kept enough properties for it
+            let normal_property = POSITION_PROPERTIES + normal_channel;
+            if normal_property + 3 <= mesh.count_vertex_properties() {
+                mesh.normal_property = Some(normal_property);
+            }
+            mesh
+        }
+    }
+
     /// Get the number of vertices in this mesh.
     pub fn count_vertices(&self) -> usize {
         unsafe {
@@ -63,6 +110,43 @@ impl MeshGL {
         }
     }
 
+    /// The number of properties which each vertex has, beyond its position.
+    pub fn count_extra_vertex_properties(&self) -> usize {
+        self.count_vertex_properties() - POSITION_PROPERTIES
+    }
+
+    /// The property index of the X component of the vertex normals, if this mesh has them - the Y
+    /// and Z components follow it. The index counts the position, so can be used directly with
+    /// [`Self::vertex_property_data`].
+    /// 
+    /// Manifold doesn't record what properties mean, so this is only known for meshes created with
+    /// [`Self::from_manifold_with_normals`].
+    pub fn normal_property(&self) -> Option<usize> {
+        self.normal_property
+    }
+
+    /// Get the normal of each vertex, in the same order as the vertices, if this mesh has them.
+    /// 
+    /// To get normals for a mesh without any, see [`crate::ext::MeshGLExt::compute_corner_normals`].
+    pub fn vertex_normals(&self) -> Option<Vec<Vec3<f32>>> {
+        let normal_property = self.normal_property?;
+        let vp = self.count_vertex_properties();
+
+        let normals = self.vertex_property_data()
+            .chunks_exact(vp)
+            .map(|props| Vec3::new(props[normal_property], props[normal_property + 1], props[normal_property + 2]))
+            .collect();
+        Some(normals)
+    }
+
+    /// Gets the position of each vertex, in the same order as the vertices.
+    pub fn vertex_positions(&self) -> Vec<Vec3<f32>> {
+        self.vertex_property_data()
+            .chunks_exact(self.count_vertex_properties())
+            .map(|props| Vec3::new(props[0], props[1], props[2]))
+            .collect()
+    }
+
     /// Gets raw vertex properties for this mesh.
     /// 
     /// All vertices have the same number of properties, but that could be any number >= 3.
@@ -74,14 +158,21 @@ impl MeshGL {
     /// [v1.x, v1.y, v1.z, v2.x, v2.y, v2.z, ...]
     /// ```
     pub fn vertex_property_data(&self) -> Vec<f32> {
+        let mut data = vec![];
+        self.vertex_property_data_into(&mut data);
+        data
+    }
+
+    /// Like [`Self::vertex_property_data`], but writes the data into an existing buffer, replacing
+    /// its contents. The buffer's allocation is reused if it's large enough.
+    pub fn vertex_property_data_into(&self, data: &mut Vec<f32>) {
         unsafe {
             let length = raw::manifold_meshgl_vert_properties_length(self.ptr);
-            let mut data = Vec::<f32>::with_capacity(length);
+            data.clear();
+            data.reserve(length);
 
             raw::manifold_meshgl_vert_properties(data.as_mut_ptr() as *mut c_void, self.ptr);
             data.set_len(length);
-
-            data
         }
     }
 
@@ -97,17 +188,70 @@ impl MeshGL {
     /// [t1.1, t1.2, t1.3, t2.1, t2.2, t2.3, ...]
     /// ```
     pub fn triangle_vertex_data(&self) -> Vec<usize> {
+        // Interface hard-codes the indices as u32.
+        // We'll convert to usize for convenience of indexing.
+        let mut data = vec![];
+        self.triangle_vertex_data_into(&mut data);
+        data.into_iter().map(|i| i as usize).collect()
+    }
+
+    /// Like [`Self::triangle_vertex_data`], but writes the indices into an existing buffer,
+    /// replacing its contents. The buffer's allocation is reused if it's large enough.
+    /// 
+    /// The indices are kept as the `u32`s provided by Manifold, which avoids needing another copy
+    /// of the data to convert them.
+    pub fn triangle_vertex_data_into(&self, data: &mut Vec<u32>) {
         unsafe {
-            // Interface hard-codes the indices as u32.
-            // We'll convert to usize later for convenience of indexing.
-            let length = self.count_triangles() * 3;
-            let mut data = Vec::<u32>::with_capacity(length);
+            let length = self.count_triangles() * VERTICES_IN_TRI;
+            data.clear();
+            data.reserve(length);
 
             raw::manifold_meshgl_tri_verts(data.as_mut_ptr() as *mut c_void, self.ptr);
             data.set_len(length);
+        }
+    }
+
+    /// Where each run of triangles starts, as an index into [`Self::triangle_vertex_data`] - so
+    /// each is a multiple of 3. This has one more element than [`Self::run_original_id_data`], the
+    /// last being the end of the final run.
+    /// 
+    /// A run is a group of consecutive triangles which all came from the same original manifold.
+    pub fn run_index_data(&self) -> Vec<u32> {
+        unsafe {
+            let length = raw::manifold_meshgl_run_index_length(self.ptr);
+            let mut data = Vec::with_capacity(length);
+            raw::manifold_meshgl_run_index(data.as_mut_ptr() as *mut c_void, self.ptr);
+            data.set_len(length);
+            data
+        }
+    }
+
+    /// The [`Manifold::original_id`] of the manifold which each run of triangles came from - see
+    /// [`Self::run_index_data`].
+    pub fn run_original_id_data(&self) -> Vec<u32> {
+        unsafe {
+            let length = raw::manifold_meshgl_run_original_id_length(self.ptr);
+            let mut data = Vec::with_capacity(length);
+            raw::manifold_meshgl_run_original_id(data.as_mut_ptr() as *mut c_void, self.ptr);
+            data.set_len(length);
+            data
+        }
+    }
+
+    /// The [`Manifold::original_id`] of the manifold which each triangle came from, in the same
+    /// order as the triangles.
+    /// 
+    /// Meshes which didn't come from a manifold have no runs, so this is empty for them.
+    pub fn triangle_original_ids(&self) -> Vec<u32> {
+        let run_index = self.run_index_data();
+        let run_original_id = self.run_original_id_data();
 
-            data.into_iter().map(|i| i as usize).collect()
+        let mut ids = Vec::with_capacity(self.count_triangles());
+        for (run, original_id) in run_original_id.iter().enumerate() {
+            let (start, end) = (run_index[run] as usize, run_index[run + 1] as usize);
+            ids.extend(std::iter::repeat_n(*original_id, (end - start) / VERTICES_IN_TRI));
         }
+        ids
     }
 
     /// Returns an iterator over high-level triangle data for this mesh.
@@ -115,8 +259,10 @@ impl MeshGL {
     /// Each item includes:
     ///   - The three vertex points which define the triangle
     ///   - Any additional arbitrary vertex properties
+    ///   - The vertex normals, if this mesh has them
     pub fn iter_triangles(&self) -> impl Iterator<Item = MeshTriangle> {
         let vp = self.count_vertex_properties();
+        let normal_property = self.normal_property;
         let verts = self.vertex_property_data();
         let tris = self.triangle_vertex_data();
 
@@ -124,7 +270,7 @@ impl MeshGL {
             // Find raw property data for each vertex
             let vert_indices = &tris[(tri_index * VERTICES_IN_TRI)..(tri_index * VERTICES_IN_TRI + VERTICES_IN_TRI)];
             let vert_props: [&[f32]; VERTICES_IN_TRI] = (0..VERTICES_IN_TRI)
-                .map(|i| &verts[(vert_indices[i] * vp)..(vert_indices[i] * vp + 3)])
+                .map(|i| &verts[(vert_indices[i] * vp)..(vert_indices[i] * vp + vp)])
                 .collect::<Vec<_>>()
                 .try_into()
                 .unwrap();
@@ -137,7 +283,11 @@ impl MeshGL {
                 triangle.points[i].z = props[2];
 
                 // Future props are arbitrary
-                triangle.properties[i] = props[3..].to_vec();
+                triangle.properties[i] = props[POSITION_PROPERTIES..].to_vec();
+            }
+
+            if let Some(n) = normal_property {
+                triangle.normals = Some(vert_props.map(|props| Vec3::new(props[n], props[n + 1], props[n + 2])));
             }
             
             triangle
@@ -155,8 +305,14 @@ impl Drop for MeshGL {
 
 const VERTICES_IN_TRI: usize = 3;
 
+/// The X, Y, Z position of a vertex always comes first in its properties.
+const POSITION_PROPERTIES: usize = 3;
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
 pub struct MeshTriangle {
     pub points: [Vec3<f32>; VERTICES_IN_TRI],
     pub properties: [Vec<f32>; VERTICES_IN_TRI],
+
+    /// The normal at each point, if the mesh has them - see [`MeshGL::normal_property`].
+    pub normals: Option<[Vec3<f32>; VERTICES_IN_TRI]>,
 }
@@ -1,5 +1,11 @@
-use crate::raw;
+use std::os::raw::c_void;
 
+use crate::{Vec2, error::expect_alloc, raw};
+
+/// A list of simple polygons, each of which is a loop of points.
+/// 
+/// Every list is initialised as soon as it is created - there is no way to get hold of one whose
+/// memory is only allocated.
 pub struct Polygons {
     pub(crate) ptr: *mut raw::ManifoldPolygons,
 }
@@ -9,7 +15,7 @@ impl Polygons {
     /// 
     /// Safety: The pointer must be valid, unique, and point to an allocated polygons instance.
     /// The instance must be initialised, if not already, before further use.
-    pub(crate) unsafe fn from_raw(ptr: *mut raw::ManifoldPolygons) -> Self {
+    unsafe fn from_raw(ptr: *mut raw::ManifoldPolygons) -> Self {
         Self { ptr }
     }
 
@@ -17,11 +23,95 @@ impl Polygons {
     /// 
     /// Safety: The returned set of polygons is not initialised, and must be initialised before
     /// further use.
-    pub(crate) unsafe fn alloc() -> Self {
+    unsafe fn alloc() -> Self {
+        unsafe {
+            Self::from_raw(expect_alloc(raw::manifold_alloc_polygons()))
+        }
+    }
+
+    /// Shorthand for `alloc` followed by an initialiser on the raw pointer.
+    /// 
+    /// Safety: `func` must initialise the pointee list of polygons.
+    pub(crate) unsafe fn alloc_build<R>(func: impl FnOnce(*mut c_void) -> R) -> Self {
+        unsafe {
+            let polygons = Self::alloc();
+            func(polygons.ptr as *mut c_void);
+            polygons
+        }
+    }
+
+    /// Create a list containing no polygons.
+    pub fn new() -> Self {
+        unsafe {
+            Self::alloc_build(|ptr|
+                raw::manifold_polygons(ptr, Vec::new().as_mut_ptr(), 0))
+        }
+    }
+
+    /// Create a list of simple polygons, from the points of each one.
+    /// 
+    /// When made into a [`crate::CrossSection`], outlines should be counter-clockwise, and holes
+    /// clockwise.
+    pub fn from_points(polygons: &[Vec<Vec2<f64>>]) -> Self {
+        unsafe {
+            // Manifold copies the points and the simple polygons, so these can be freed afterwards
+            let mut simple_polygons = polygons.iter()
+                .map(|points| {
+                    let mut points = points.iter()
+                        .map(|point| raw::ManifoldVec2 { x: point.x, y: point.y })
+                        .collect::<Vec<_>>();
+                    let simple_polygon = expect_alloc(raw::manifold_alloc_simple_polygon());
+                    raw::manifold_simple_polygon(simple_polygon as *mut c_void, points.as_mut_ptr(), points.len());
+                    simple_polygon
+                })
+                .collect::<Vec<_>>();
+
+            let polygons = Self::alloc_build(|ptr|
+                raw::manifold_polygons(ptr, simple_polygons.as_mut_ptr(), simple_polygons.len()));
+            for simple_polygon in simple_polygons {
+                raw::manifold_delete_simple_polygon(simple_polygon);
+            }
+            polygons
+        }
+    }
+
+    /// The number of simple polygons in this list.
+    pub fn count_polygons(&self) -> usize {
         unsafe {
-            Self::from_raw(raw::manifold_alloc_polygons())
+            raw::manifold_polygons_length(self.ptr)
         }
     }
+
+    /// The number of points in the simple polygon at the given index.
+    /// 
+    /// Panics if the index is out-of-bounds.
+    pub fn count_points(&self, polygon_index: usize) -> usize {
+        assert!(polygon_index < self.count_polygons(), "polygon index out-of-bounds");
+        unsafe {
+            raw::manifold_polygons_simple_length(self.ptr, polygon_index)
+        }
+    }
+
+    /// Get the points of every simple polygon in this list.
+    /// 
+    /// Outlines are counter-clockwise, and holes are clockwise.
+    pub fn to_points(&self) -> Vec<Vec<Vec2<f64>>> {
+        (0..self.count_polygons())
+            .map(|polygon_index| {
+                (0..self.count_points(polygon_index))
+                    .map(|point_index| unsafe {
+                        raw::manifold_polygons_get_point(self.ptr, polygon_index, point_index).into()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Default for Polygons {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Drop for Polygons {
@@ -31,3 +121,33 @@ impl Drop for Polygons {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{Polygons, Vec2};
+
+    #[test]
+    fn test_new() {
+        let polygons = Polygons::new();
+        assert_eq!(polygons.count_polygons(), 0);
+        assert_eq!(polygons.to_points(), Vec::<Vec<_>>::new());
+    }
+
+    #[test]
+    fn test_from_points() {
+        let points = vec![
+            vec![Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0), Vec2::new(10.0, 10.0)],
+            vec![Vec2::new(1.0, 1.0), Vec2::new(2.0, 2.0), Vec2::new(2.0, 1.0), Vec2::new(1.5, 0.5)],
+        ];
+        let polygons = Polygons::from_points(&points);
+        assert_eq!(polygons.count_polygons(), 2);
+        assert_eq!(polygons.count_points(1), 4);
+        assert_eq!(polygons.to_points(), points);
+    }
+
+    #[test]
+    #[should_panic(expected = "polygon index out-of-bounds")]
+    fn test_count_points_out_of_bounds() {
+        Polygons::new().count_points(0);
+    }
+}
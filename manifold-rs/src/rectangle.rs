@@ -1,5 +1,11 @@
-use crate::{Vec2, raw};
+use std::os::raw::c_void;
 
+use crate::{Vec2, error::expect_alloc, raw};
+
+/// An axis-aligned rectangle.
+/// 
+/// Every rectangle is initialised as soon as it is created - there is no way to get hold of one
+/// whose memory is only allocated.
 pub struct Rectangle {
     pub(crate) ptr: *mut raw::ManifoldRect,
 }
@@ -9,7 +15,7 @@ impl Rectangle {
     /// 
     /// Safety: The pointer must be valid, unique, and point to an allocated rectangle instance.
     /// The instance must be initialised, if not already, before further use.
-    pub(crate) unsafe fn from_raw(ptr: *mut raw::ManifoldRect) -> Self {
+    unsafe fn from_raw(ptr: *mut raw::ManifoldRect) -> Self {
         Self { ptr }
     }
 
@@ -17,9 +23,28 @@ impl Rectangle {
     /// 
     /// Safety: The returned rectangle is not initialised, and must be initialised before further
     /// use.
-    pub(crate) unsafe fn alloc() -> Self {
+    unsafe fn alloc() -> Self {
+        unsafe {
+            Self::from_raw(expect_alloc(raw::manifold_alloc_rect()))
+        }
+    }
+
+    /// Shorthand for `alloc` followed by an initialiser on the raw pointer.
+    /// 
+    /// Safety: `func` must initialise the pointee rectangle.
+    pub(crate) unsafe fn alloc_build<R>(func: impl FnOnce(*mut c_void) -> R) -> Self {
+        unsafe {
+            let rect = Self::alloc();
+            func(rect.ptr as *mut c_void);
+            rect
+        }
+    }
+
+    /// Create a rectangle with two opposite corners at the given points.
+    pub fn new(a: Vec2<f64>, b: Vec2<f64>) -> Self {
         unsafe {
-            Self::from_raw(raw::manifold_alloc_rect())
+            Self::alloc_build(|ptr|
+                raw::manifold_rect(ptr, a.x, a.y, b.x, b.y))
         }
     }
 
@@ -45,6 +70,13 @@ impl Rectangle {
     }
 }
 
+impl Default for Rectangle {
+    /// A zero-sized rectangle at the origin.
+    fn default() -> Self {
+        Self::new(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0))
+    }
+}
+
 impl Drop for Rectangle {
     fn drop(&mut self) {
         unsafe {
@@ -52,3 +84,24 @@ impl Drop for Rectangle {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{Rectangle, Vec2};
+
+    #[test]
+    fn test_new() {
+        let rect = Rectangle::new(Vec2::new(3.0, -1.0), Vec2::new(1.0, 4.0));
+        assert_eq!(rect.min_point(), Vec2::new(1.0, -1.0));
+        assert_eq!(rect.max_point(), Vec2::new(3.0, 4.0));
+        assert_eq!(rect.size(), Vec2::new(2.0, 5.0));
+    }
+
+    #[test]
+    fn test_default() {
+        let rect = Rectangle::default();
+        assert_eq!(rect.min_point(), Vec2::new(0.0, 0.0));
+        assert_eq!(rect.max_point(), Vec2::new(0.0, 0.0));
+        assert_eq!(rect.size(), Vec2::new(0.0, 0.0));
+    }
+}
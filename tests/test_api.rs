@@ -0,0 +1,1866 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use insta::assert_snapshot;
+use yascad_lang::{BuildFingerprint, BuildOptions, BuiltModel, Centering, ExportPlacement, ExportPrecision, FileAccessPolicy, InputSource, InputSourceOrigin, LangError, Manifold, ProfileEntryKind, SceneEntryGeometry, StepCapture, Unit, UpAxis, Vec2, Vec3, build, build_model, build_model_parts, build_with_options, export::{MeshGLExt, TextStlOptions, ThreeMfObject, write_3mf, write_binary_stl_from_meshgls, write_stl_from_meshgl}, export_rescale_factor, format_scene_dump, merge_groups, openscad, rebuild_with_options, testing};
+
+#[test]
+fn test_build_parts() {
+    let source = InputSource::new_string("
+        cube(10);
+
+        translate([20, 0, 0])
+        cube([5, 6, 7]);
+
+        translate([0, 30, 0])
+        cube(2);
+    ".to_owned());
+    let parts = build_model_parts(source).unwrap();
+
+    let bounds = parts.iter()
+        .map(|part| {
+            let bounding_box = part.manifold.bounding_box();
+            (bounding_box.min_point(), bounding_box.max_point())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(bounds, vec![
+        (Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 10.0, 10.0)),
+        (Vec3::new(20.0, 0.0, 0.0), Vec3::new(25.0, 6.0, 7.0)),
+        (Vec3::new(0.0, 30.0, 0.0), Vec3::new(2.0, 32.0, 2.0)),
+    ]);
+
+    let lines = parts.iter()
+        .map(|part| part.provenance.start_line())
+        .collect::<Vec<_>>();
+    assert_eq!(lines, vec![2, 4, 7]);
+}
+
+#[test]
+fn test_unit_conversion() {
+    assert_eq!(Unit::Inch.in_millimetres(), 25.4);
+    assert_eq!(Unit::Millimetre.convert_from(1.0, Unit::Inch), 25.4);
+    assert_eq!(Unit::Inch.convert_from(25.4, Unit::Millimetre), 1.0);
+    assert_eq!(Unit::Centimetre.convert_from(1.0, Unit::Inch), 2.54);
+    assert_eq!(Unit::Inch.convert_from(1.0, Unit::Inch), 1.0);
+}
+
+#[test]
+fn test_units_declaration() {
+    let model_size = |source: &str| {
+        let model = build(InputSource::new_string(source.to_owned())).unwrap();
+        let size = model.manifold().bounding_box().max_point();
+        (model.units(), size)
+    };
+
+    // Default is millimetres
+    assert_eq!(model_size("cube(inch(1));"), (Unit::Millimetre, Vec3::new(25.4, 25.4, 25.4)));
+    assert_eq!(model_size("cube(cm(2));"), (Unit::Millimetre, Vec3::new(20.0, 20.0, 20.0)));
+
+    // Declaration changes the base unit, wherever it appears at the top level
+    assert_eq!(model_size("units inch; cube(inch(1));"), (Unit::Inch, Vec3::new(1.0, 1.0, 1.0)));
+    assert_eq!(model_size("cube(mm(50.8)); units inch;"), (Unit::Inch, Vec3::new(2.0, 2.0, 2.0)));
+}
+
+#[test]
+fn test_units_declaration_errors() {
+    testing::assert_runtime_error("units furlong;", "unknown unit \"furlong\" - expected one of: mm, cm, inch");
+    testing::assert_runtime_error("units mm; units inch;", "`units` has already been declared");
+    testing::assert_runtime_error("if (true) { units inch; }", "`units` can only be declared at the top level");
+}
+
+#[test]
+fn test_dump_scene() {
+    let source = InputSource::new_string("
+        cube(10);
+
+        translate([5, 0])
+        square([2, 3]);
+    ".to_owned());
+    let model = build(source).unwrap();
+
+    let dump = format_scene_dump(&model.scene());
+    assert_snapshot!(dump);
+}
+
+#[test]
+fn test_build_converted_openscad() {
+    let build_converted = |scad: &str| {
+        let converted = openscad::convert_to_source(Rc::new(InputSource::new_string(scad.to_owned()))).unwrap();
+        let model = build_model(InputSource::new_string(converted)).unwrap();
+
+        let bounding_box = model.bounding_box();
+        (bounding_box.min_point(), bounding_box.max_point())
+    };
+
+    assert_eq!(
+        build_converted("
+            module plate(w, d, h = 2) { cube([w, d, h]); }
+            plate(10, 20);
+            translate([0, 0, 2]) plate(5, 5, h = 3);
+        "),
+        (Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 20.0, 5.0)),
+    );
+
+    assert_eq!(
+        build_converted("
+            function half(x) = x / 2;
+            size = [8, 6];
+            intersection() {
+                cube([size[0], size[1], 10]);
+                translate([half(size[0]), 0, 0]) cube(10);
+            }
+        "),
+        (Vec3::new(4.0, 0.0, 0.0), Vec3::new(8.0, 6.0, 10.0)),
+    );
+
+    assert_eq!(
+        build_converted("
+            for (i = [0 : 2]) {
+                if (i > 0) translate([i * 10, 0]) linear_extrude(height = 3) square([2, 2]);
+                else color(\"red\") cube(1);
+            }
+        "),
+        (Vec3::new(0.0, 0.0, 0.0), Vec3::new(22.0, 2.0, 3.0)),
+    );
+}
+
+#[test]
+fn test_children_count() {
+    let model_bounds = |usage: &str| {
+        let source = format!("
+            operator stack() {{
+                if (children_count() == 0) {{
+                    cube(1);
+                }} else {{
+                    first = children();
+                    translate([children_count(), 0, first.size.z])
+                    copy(first);
+                }}
+            }}
+
+            {usage}
+        ");
+        let bounding_box = build_model(InputSource::new_string(source)).unwrap().bounding_box();
+        (bounding_box.min_point(), bounding_box.max_point())
+    };
+
+    assert_eq!(model_bounds("stack() {}"), (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)));
+    assert_eq!(model_bounds("stack() cube(2);"), (Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 2.0, 4.0)));
+    assert_eq!(
+        model_bounds("stack() { cube(1); cube(2); cube(3); }"),
+        (Vec3::new(0.0, 0.0, 0.0), Vec3::new(6.0, 3.0, 6.0)),
+    );
+
+    testing::assert_runtime_error("cube(children_count());", "cannot use `children` outside of operator body");
+}
+
+#[test]
+fn test_child_access() {
+    // Nesting stacks more than two things
+    let model = testing::build("
+        // Sits the second child on top of the first, flush. The bounds are a vector of the lower
+        // and upper corners, so `.x` is the lower and `.y` the upper
+        operator stack() {
+            lower = child_bounds(0);
+            upper = child_bounds(1);
+
+            child(0);
+            translate([0, 0, lower.y.z - upper.x.z]) child(1);
+        }
+
+        stack() {
+            cube([10, 10, 4]);
+            stack() {
+                translate([0, 0, -7]) cylinder(2, 3);
+                cube([4, 4, 6]);
+            }
+        }
+    ");
+
+    model.assert_bbox([-3.0, -3.0, 0.0], [10.0, 10.0, 12.0]);
+    assert!((model.slice_at(2.0).area - 100.0).abs() < 1e-6);
+    assert_eq!(model.slice_at(5.0).polygon_count, 1);
+    assert!((model.slice_at(5.0).max.x - 3.0).abs() < 1e-6);
+    assert!((model.slice_at(9.0).area - 16.0).abs() < 1e-6);
+
+    testing::assert_runtime_error("operator o() { child(1); } o() cube(1);", "no child at index 1 - there is only 1 child, at index 0");
+    testing::assert_runtime_error("operator o() { child(2); } o() { cube(1); cube(2); }", "no child at index 2 - there are 2 children, numbered from 0");
+    testing::assert_runtime_error("operator o() { child(0.5); } o() cube(1);", "type error - expected whole number, got 0.5");
+}
+
+#[test]
+fn test_empty() {
+    let model = |source: &str| build(InputSource::new_string(source.to_owned())).unwrap();
+    let bounds = |source: &str| {
+        let bounding_box = model(source).manifold().bounding_box();
+        (bounding_box.min_point(), bounding_box.max_point())
+    };
+
+    assert_eq!(bounds("union() { empty(); cube(5); }"), (Vec3::new(0.0, 0.0, 0.0), Vec3::new(5.0, 5.0, 5.0)));
+    assert_eq!(bounds("union() { empty_2d(); cube(5); }"), (Vec3::new(0.0, 0.0, 0.0), Vec3::new(5.0, 5.0, 5.0)));
+    assert_eq!(bounds("difference() { cube(5); empty(); }"), (Vec3::new(0.0, 0.0, 0.0), Vec3::new(5.0, 5.0, 5.0)));
+    assert!(model("difference() { empty(); cube(5); }").manifold().is_empty());
+
+    // Empty geometry doesn't become a part of its own
+    assert_eq!(model("if (false) { cube(5); } else { empty(); }").parts().len(), 0);
+
+    assert_eq!(
+        bounds("
+            e = empty();
+            if (e.is_empty) {
+                if (e.volume == 0) {
+                    if (e.size == [0, 0, 0]) {
+                        cube(1);
+                    }
+                }
+            }
+        "),
+        (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)),
+    );
+}
+
+#[test]
+fn test_tolerance() {
+    let model = |cutter: &str| build_model(InputSource::new_string(format!("
+        difference() {{
+            cube(10);
+            {cutter}
+        }}
+    "))).unwrap();
+
+    // The cutter is flush with the top of the cube
+    let without_tolerance = model("translate([0, 0, 5]) cube([10, 10, 5]);");
+    let with_tolerance = model("tolerance(0.01) translate([0, 0, 5]) cube([10, 10, 5]);");
+
+    assert!((without_tolerance.volume() - 500.0).abs() < 1e-6);
+    assert!((with_tolerance.volume() - 500.0).abs() < 1e-6);
+    assert!(with_tolerance.tolerance() >= 0.01);
+
+    // The cutter stops just short of the top, leaving a sliver thinner than the tolerance, which is
+    // only cleaned up when the tolerance is applied
+    let without_tolerance = model("translate([0, 0, 5]) cube([10, 10, 4.9999]);");
+    let with_tolerance = model("tolerance(0.01) translate([0, 0, 5]) cube([10, 10, 4.9999]);");
+    assert!((without_tolerance.volume() - 500.01).abs() < 1e-6, "volume was {}", without_tolerance.volume());
+    assert!((with_tolerance.volume() - 500.0).abs() < 1e-3, "volume was {}", with_tolerance.volume());
+    assert!(without_tolerance.tolerance() < 0.01);
+
+    testing::assert_runtime_error("tolerance(0.01) square(1);", "this operation requires 3D geometry, but 2D was provided");
+
+    // Cutting away everything suggests how to cut flush instead
+    let model = build(InputSource::new_string("for (i = [1:3]) difference() { cube(10); cube(10); }".to_owned())).unwrap();
+    let [warning] = model.warnings() else { panic!("expected one warning") };
+    assert!(warning.to_string().starts_with("this difference removed everything from its first child - to cut flush with a surface, make the cutter slightly larger"));
+    assert!(build(InputSource::new_string("difference() { cube(10); cube(5); }".to_owned())).unwrap().warnings().is_empty());
+}
+
+#[test]
+fn test_bare_module_identifier_help() {
+    let runtime_error = |source: &str| {
+        let error = testing::runtime_error(InputSource::new_string(source.to_owned()));
+        (error.kind.to_string(), error.help.map(String::from))
+    };
+
+    assert_eq!(
+        runtime_error("operator o() { children; } o() cube(1);"),
+        (
+            "identifier \"children\" is a built-in module, which cannot be used here".to_owned(),
+            Some("did you mean `children()`?".to_owned()),
+        ),
+    );
+    assert_eq!(
+        runtime_error("module m(x) { cube(x); } m;"),
+        (
+            "identifier \"m\" is a user-defined module, which cannot be used here".to_owned(),
+            Some("modules must be called with parentheses, like `m(...)`".to_owned()),
+        ),
+    );
+    assert_eq!(
+        runtime_error("translate;"),
+        (
+            "identifier \"translate\" is a built-in operator, which cannot be used here".to_owned(),
+            Some("operators must be applied to children, like `translate(...) { ... }`".to_owned()),
+        ),
+    );
+
+    // Genuinely undefined identifiers are unaffected
+    assert_eq!(runtime_error("children_;"), ("undefined identifier \"children_\"".to_owned(), None));
+}
+
+#[test]
+fn test_slice() {
+    let model = build_model(InputSource::new_string("cube([10, 20, 30]);".to_owned())).unwrap();
+
+    let slice = model.slice(15.0);
+    let mut polygons = slice.polygons().to_points();
+    assert_eq!(polygons.len(), 1);
+
+    let mut square = polygons.remove(0);
+    square.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(square, vec![
+        Vec2::new(0.0, 0.0),
+        Vec2::new(0.0, 20.0),
+        Vec2::new(10.0, 0.0),
+        Vec2::new(10.0, 20.0),
+    ]);
+
+    let bounds = slice.bounding_rectangle();
+    assert_eq!((bounds.min_point(), bounds.max_point()), (Vec2::new(0.0, 0.0), Vec2::new(10.0, 20.0)));
+}
+
+#[test]
+fn test_segment_scale() {
+    let source = "
+        difference() {
+            cube(10);
+            translate([5, 5, -1]) cylinder(12, 2);
+        }
+    ";
+
+    let export = build(InputSource::new_string(source.to_owned())).unwrap().manifold();
+    let preview = build_with_options(InputSource::new_string(source.to_owned()), BuildOptions::preview())
+        .unwrap()
+        .manifold();
+
+    // The preview is coarser, but still the same shape
+    assert!(preview.count_triangles() < export.count_triangles());
+    assert_eq!((preview.genus(), export.genus()), (1, 1));
+    let preview_bounds = preview.bounding_box();
+    assert_eq!(
+        (preview_bounds.min_point(), preview_bounds.max_point()),
+        (Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 10.0, 10.0)),
+    );
+
+    // Segment counts are clamped to something which still makes a valid shape
+    let model = build_with_options(
+        InputSource::new_string("circle(1);".to_owned()),
+        BuildOptions { segment_scale: 0.001, ..BuildOptions::default() },
+    ).unwrap();
+    let [entry] = &model.scene()[..] else { panic!("expected one scene entry") };
+    assert!(matches!(entry.geometry, SceneEntryGeometry::CrossSection { points: 3, .. }));
+}
+
+#[test]
+fn test_segments() {
+    let points = |model: &BuiltModel| model.scene().iter()
+        .map(|entry| match entry.geometry {
+            SceneEntryGeometry::CrossSection { points, .. } => points,
+            SceneEntryGeometry::Manifold { triangles, .. } => triangles,
+        })
+        .collect::<Vec<_>>();
+
+    // Only geometry created after the setter is affected
+    let source = "circle(1); segments(64); translate([5, 0]) circle(1);";
+    let model = build(InputSource::new_string(source.to_owned())).unwrap();
+    assert_eq!(points(&model), vec![20, 64]);
+
+    let model = build(InputSource::new_string("cylinder(1, 1); segments(40); cylinder(1, 1);".to_owned())).unwrap();
+    let triangles = points(&model);
+    assert!(triangles[0] < triangles[1], "triangle counts were {triangles:?}");
+
+    // The segment scale still applies
+    let model = build_with_options(InputSource::new_string(source.to_owned()), BuildOptions::preview()).unwrap();
+    assert_eq!(points(&model), vec![10, 32]);
+
+    // Changing the setter changes curves which are otherwise reused
+    let options = BuildOptions { record_statements: true, ..BuildOptions::default() };
+    let original = build_with_options(InputSource::new_string(source.to_owned()), options.clone()).unwrap();
+    let rebuilt = rebuild_with_options(&original, InputSource::new_string("circle(1); segments(8); translate([5, 0]) circle(1);".to_owned()), options.clone()).unwrap();
+    assert_eq!(points(&rebuilt), vec![20, 8]);
+    let rebuilt = rebuild_with_options(&rebuilt, InputSource::new_string("circle(1); translate([5, 0]) circle(1);".to_owned()), options).unwrap();
+    assert_eq!(points(&rebuilt), vec![20, 20]);
+}
+
+#[test]
+fn test_part_ordering_and_ids() {
+    let source = "
+        module peg() { cube(1); }
+        cube(3);
+        peg();
+        part(\"lid\") translate([0, 10, 0]) cube(2);
+        translate([5, 0, 0]) peg();
+    ";
+    let summary = |source: &str| build(InputSource::new_string(source.to_owned())).unwrap()
+        .preview()
+        .parts
+        .iter()
+        .map(|part| (part.id, part.name.clone(), part.provenance.start_line()))
+        .collect::<Vec<_>>();
+
+    // The same every time, ordered by where the geometry was created, with the identical pegs
+    // told apart
+    let parts = summary(source);
+    assert_eq!(parts, summary(source));
+    assert_eq!(parts.iter().map(|(_, name, line)| (name.as_deref(), *line)).collect::<Vec<_>>(), vec![
+        (None, 2),
+        (None, 2),
+        (None, 3),
+        (Some("lid"), 5),
+    ]);
+    let ids = parts.iter().map(|(id, _, _)| *id).collect::<Vec<_>>();
+    assert!((0..ids.len()).all(|i| !ids[i + 1..].contains(&ids[i])), "IDs weren't unique: {ids:?}");
+
+    // Parts keep their IDs when other code is edited, and the preview agrees with the built parts
+    let edited = format!("cube(5);{}", source.replace("cube(2)", "cube(4)"));
+    let edited_parts = summary(&edited);
+    let edited_ids = edited_parts.iter().map(|(id, _, _)| *id).collect::<Vec<_>>();
+    assert_eq!(edited_ids[1..4], ids[..3]);
+    assert_ne!(edited_ids[4], ids[3]);
+    let built = build_model_parts(InputSource::new_string(edited.clone())).unwrap();
+    assert_eq!(built.iter().map(|part| part.id).collect::<Vec<_>>(), edited_ids);
+}
+
+#[test]
+fn test_step_capture() {
+    let source = "
+        translate([1, 0, 0])
+        rotate([0, 0, 90])
+        scale([2, 1, 1])
+        cube(1);
+    ";
+    let steps = |step_capture| {
+        let options = BuildOptions { step_capture, ..BuildOptions::default() };
+        let model = build_with_options(InputSource::new_string(source.to_owned()), options).unwrap();
+        model.steps().iter()
+            .map(|step| (step.operator.clone(), step.span.start_line(), step.mesh.is_some()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(steps(StepCapture::Off), vec![]);
+
+    // Inner operators finish first
+    assert_eq!(steps(StepCapture::Summary), vec![
+        ("scale".to_owned(), 4, false),
+        ("rotate".to_owned(), 3, false),
+        ("translate".to_owned(), 2, false),
+    ]);
+    assert_eq!(steps(StepCapture::Mesh { max_meshes: 2 }), vec![
+        ("scale".to_owned(), 4, true),
+        ("rotate".to_owned(), 3, true),
+        ("translate".to_owned(), 2, false),
+    ]);
+
+    let model = build_with_options(
+        InputSource::new_string(source.to_owned()),
+        BuildOptions { step_capture: StepCapture::Summary, ..BuildOptions::default() },
+    ).unwrap();
+    assert_eq!(model.steps()[0].geometry, SceneEntryGeometry::Manifold {
+        triangles: 12,
+        min: Vec3::new(0.0, 0.0, 0.0),
+        max: Vec3::new(2.0, 1.0, 1.0),
+    });
+}
+
+#[test]
+fn test_profile() {
+    let source = "
+        module peg() {
+            cylinder(1, 5);
+        }
+
+        cube(10);
+        translate([20, 0, 0]) peg();
+        translate([30, 0, 0]) peg();
+        x = 1;
+    ";
+
+    let model = build(InputSource::new_string(source.to_owned())).unwrap();
+    assert!(model.profile().is_none());
+
+    let options = BuildOptions { profile: true, ..BuildOptions::default() };
+    let model = build_with_options(InputSource::new_string(source.to_owned()), options).unwrap();
+    let entries = model.profile().unwrap().entries().iter()
+        .map(|entry| {
+            let kind = match &entry.kind {
+                ProfileEntryKind::Statement => "statement".to_owned(),
+                ProfileEntryKind::Call { name, .. } => name.clone(),
+            };
+            (kind, entry.span.start_line(), entry.count)
+        })
+        .collect::<Vec<_>>();
+
+    // Every top-level statement has an entry, including definitions which don't create geometry,
+    // and each call is recorded separately
+    assert_eq!(entries, vec![
+        ("statement".to_owned(), 2, 1),
+        ("statement".to_owned(), 6, 1),
+        ("peg".to_owned(), 7, 1),
+        ("statement".to_owned(), 7, 1),
+        ("peg".to_owned(), 8, 1),
+        ("statement".to_owned(), 8, 1),
+        ("statement".to_owned(), 9, 1),
+    ]);
+
+    let profile = model.profile().unwrap();
+    let cube = &profile.entries()[1];
+    assert!(cube.geometry_operations > 0);
+    assert_eq!(profile.entries()[6].geometry_operations, 0);
+}
+
+#[test]
+fn test_rebuild() {
+    let options = BuildOptions { record_statements: true, ..BuildOptions::default() };
+    let rebuild = |previous, source: &str| {
+        let rebuilt = rebuild_with_options(previous, InputSource::new_string(source.to_owned()), options.clone()).unwrap();
+        let fresh = build(InputSource::new_string(source.to_owned())).unwrap();
+
+        // Reused geometry should be indistinguishable from building from scratch
+        assert_eq!(format_scene_dump(&rebuilt.scene()), format_scene_dump(&fresh.scene()));
+        rebuilt
+    };
+
+    let original = build_with_options(InputSource::new_string("
+        size = 2;
+        cube(1);
+        translate([10, 0, 0]) cube(size);
+        translate([0, 10, 0]) sphere(3);
+    ".to_owned()), options.clone()).unwrap();
+    assert!(original.rebuild_plan().is_none());
+
+    // Only the edited statement is built
+    let edited_cube = rebuild(&original, "
+        size = 2;
+        cube(5);
+        translate([10, 0, 0]) cube(size);
+        translate([0, 10, 0]) sphere(3);
+    ");
+    assert_eq!(edited_cube.rebuild_plan().unwrap().rebuilt_statements(), vec![1]);
+
+    // Statements using an edited binding are built again, even if they haven't changed
+    let edited_binding = rebuild(&edited_cube, "
+        size = 4;
+
+        cube(5);
+        translate([10, 0, 0]) cube(size);
+        translate([0, 10, 0]) sphere(3);
+    ");
+    assert_eq!(edited_binding.rebuild_plan().unwrap().rebuilt_statements(), vec![0, 2]);
+
+    // Without recorded results, there's nothing to reuse
+    let unrecorded = build(InputSource::new_string("cube(1);".to_owned())).unwrap();
+    let rebuilt = rebuild_with_options(&unrecorded, InputSource::new_string("cube(1);".to_owned()), options.clone()).unwrap();
+    assert!(rebuilt.rebuild_plan().is_none());
+
+    // Errors about the elements of a reused vector point at where it is now
+    let vector = build_with_options(InputSource::new_string("v = [1, \"a\", 1];".to_owned()), options.clone()).unwrap();
+    let moved_vector = InputSource::new_string("\nv = [1, \"a\", 1];\ncube(v);".to_owned());
+    let Err(LangError::Runtime(error)) = rebuild_with_options(&vector, moved_vector, options)
+    else { panic!("expected a runtime error") };
+    assert_eq!((error.span.start_line(), error.span.start_column()), (2, 9));
+}
+
+#[test]
+fn test_merge_groups() {
+    let base = build(InputSource::new_string("cube(10);".to_owned())).unwrap();
+    let lid = build(InputSource::new_string("part(\"lid\") cube([10, 10, 2]);".to_owned())).unwrap();
+
+    let merged = merge_groups([
+        (base.groups(), Vec3::new(0.0, 0.0, 0.0)),
+        (lid.groups(), Vec3::new(20.0, 0.0, 5.0)),
+    ]);
+    let summary = merged.iter()
+        .map(|group| {
+            let bounding_box = group.manifold.bounding_box();
+            (group.name.as_deref(), bounding_box.min_point(), bounding_box.max_point())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(summary, vec![
+        (None, Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 10.0, 10.0)),
+        (Some("lid"), Vec3::new(20.0, 0.0, 5.0), Vec3::new(30.0, 10.0, 7.0)),
+    ]);
+
+    // Unnamed geometry from each model ends up together
+    let merged = merge_groups([
+        (base.groups(), Vec3::new(0.0, 0.0, 0.0)),
+        (base.groups(), Vec3::new(-20.0, 0.0, 0.0)),
+    ]);
+    assert_eq!(merged.len(), 1);
+    let bounding_box = merged[0].manifold.bounding_box();
+    assert_eq!(bounding_box.min_point(), Vec3::new(-20.0, 0.0, 0.0));
+    assert_eq!(bounding_box.max_point(), Vec3::new(10.0, 10.0, 10.0));
+}
+
+#[test]
+fn test_part_groups() {
+    let model = build(InputSource::new_string("
+        module lid() {
+            part(\"lid\") translate([0, 0, 12]) cube([10, 10, 2]);
+        }
+
+        part(\"body\") {
+            cube(10);
+            translate([0, 0, 10]) cube([10, 10, 1]);
+        }
+        translate([20, 0, 0]) lid();
+        cube(1);
+    ".to_owned())).unwrap();
+
+    let groups = model.groups();
+    let summary = groups.iter()
+        .map(|group| {
+            let bounding_box = group.manifold.bounding_box();
+            (group.name.as_deref(), bounding_box.min_point(), bounding_box.max_point())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(summary, vec![
+        (Some("body"), Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 10.0, 11.0)),
+        (Some("lid"), Vec3::new(20.0, 0.0, 12.0), Vec3::new(30.0, 10.0, 14.0)),
+        (None, Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)),
+    ]);
+
+    // Names survive into exported files
+    let mut stl = vec![];
+    for group in &groups {
+        let name = group.name.as_deref().unwrap_or("unnamed");
+        write_stl_from_meshgl(&group.manifold.meshgl(), name, &mut stl, &TextStlOptions::default()).unwrap();
+    }
+    let stl = String::from_utf8(stl).unwrap();
+    let solids = stl.lines()
+        .filter(|line| line.starts_with("solid "))
+        .collect::<Vec<_>>();
+    assert_eq!(solids, vec!["solid body", "solid lid", "solid unnamed"]);
+
+    let meshes = groups.iter().map(|group| group.manifold.meshgl()).collect::<Vec<_>>();
+    let objects = groups.iter().zip(&meshes)
+        .map(|(group, mesh)| ThreeMfObject { name: group.name.as_deref(), mesh })
+        .collect::<Vec<_>>();
+    let mut three_mf = vec![];
+    write_3mf(&objects, "millimeter", &[("Application", "YASCAD & co")], &mut three_mf).unwrap();
+
+    // Files are stored uncompressed, so the model XML can be found directly
+    let three_mf = String::from_utf8_lossy(&three_mf);
+    assert!(three_mf.contains(r#"<metadata name="Application">YASCAD &amp; co</metadata>"#));
+    assert!(three_mf.contains(r#"<object id="1" type="model" name="body">"#));
+    assert!(three_mf.contains(r#"<object id="2" type="model" name="lid">"#));
+    assert!(three_mf.contains(r#"<object id="3" type="model">"#));
+}
+
+#[test]
+fn test_error_statement_label() {
+    let source = InputSource::new_string("translate([0, 0, 1])\n    cube(size);\n".to_owned());
+    let error = testing::runtime_error(source);
+
+    let mut report = String::new();
+    miette::GraphicalReportHandler::new_themed(miette::GraphicalTheme::unicode_nocolor())
+        .render_report(&mut report, &error)
+        .unwrap();
+    assert_snapshot!(report);
+}
+
+#[test]
+fn test_error_definition_label() {
+    let source = InputSource::new_string("module peg(radius, height = 5) {\n    cylinder(radius, height);\n}\n\npeg(2, 10, 1);\n".to_owned());
+    let error = testing::runtime_error(source);
+
+    let mut report = String::new();
+    miette::GraphicalReportHandler::new_themed(miette::GraphicalTheme::unicode_nocolor())
+        .render_report(&mut report, &error)
+        .unwrap();
+    assert_snapshot!(report);
+}
+
+#[test]
+fn test_forward_definitions() {
+    // Modules and operators can be used before they're defined
+    testing::build("
+        peg(10);
+        module peg(height, radius = 2) { cylinder(height, r = radius); }
+    ").assert_bbox([-2.0, -2.0, 0.0], [2.0, 2.0, 10.0]);
+    testing::build("
+        raise() cube(1);
+        operator raise(by = 5) { translate([0, 0, by]) children(); }
+    ").assert_bbox([0.0, 0.0, 5.0], [1.0, 1.0, 6.0]);
+
+    let runtime_error = |source: &str| testing::runtime_error(InputSource::new_string(source.to_owned()));
+
+    // Bindings are still in order, so definitions with defaults which refer to them are too
+    assert_eq!(runtime_error("cube(size); size = 10;").kind.to_string(), "undefined identifier \"size\"");
+    let error = testing::assert_runtime_error(
+        "peg();\nsize = 10;\nmodule peg(height = size) { cube(height); }",
+        "undefined identifier \"peg\"",
+    );
+    assert_eq!(
+        error.help.as_deref(),
+        Some("`peg` is defined later in the file, but can't be used before its definition because its parameter defaults refer to other names"),
+    );
+    assert_eq!(error.context.and_then(|context| context.definition).map(|definition| definition.start_line()), Some(3));
+    testing::build("size = 10; module peg(height = size) { cube(height); } peg();")
+        .assert_volume_within(999.999..1000.001);
+
+    // Duplicates are found before anything is built, pointing at both definitions
+    let error = runtime_error("cube(1);\nmodule m() {}\nmodule m() {}\n");
+    assert_eq!(error.kind.to_string(), "name \"m\" is already defined");
+    assert_eq!(error.span.start_line(), 3);
+    assert_eq!(error.context.and_then(|context| context.definition).map(|definition| definition.start_line()), Some(2));
+}
+
+#[test]
+fn test_error_mixed_dimensions_label() {
+    // A common mistake - forgetting to extrude a 2D cutter
+    let source = InputSource::new_string("difference() {\n    cube(10);\n    circle(5);\n}\n".to_owned());
+    let error = testing::runtime_error(source);
+
+    let mut report = String::new();
+    miette::GraphicalReportHandler::new_themed(miette::GraphicalTheme::unicode_nocolor())
+        .render_report(&mut report, &error)
+        .unwrap();
+    assert_snapshot!(report);
+}
+
+#[test]
+fn test_operation_timeout() {
+    let source = "
+        difference() {
+            cylinder(10, 5);
+            cylinder(20, 2);
+        }
+    ";
+
+    // Far too short for anything to finish
+    let options = BuildOptions { operation_timeout: Some(Duration::from_nanos(1)), ..BuildOptions::default() };
+    let Err(LangError::Runtime(error)) = build_with_options(InputSource::new_string(source.to_owned()), options)
+    else { panic!("expected runtime error") };
+    assert_eq!(error.to_string(), "operator \"difference\" was stopped after taking longer than 1ns");
+    assert_eq!(error.span.start_line(), 2);
+
+    // 2D operations are computed straight away, within the operator, but they're limited too
+    for (source, operator) in [
+        ("linear_extrude(2) offset(r = 1) square(10);", "offset"),
+        ("difference() { square(10); circle(2); }", "difference"),
+        ("hull() { circle(2); translate([10, 0]) circle(2); }", "hull"),
+    ] {
+        let options = BuildOptions { operation_timeout: Some(Duration::from_nanos(1)), ..BuildOptions::default() };
+        let Err(LangError::Runtime(error)) = build_with_options(InputSource::new_string(source.to_owned()), options)
+        else { panic!("expected runtime error from {source}") };
+        assert_eq!(error.to_string(), format!("operator \"{operator}\" was stopped after taking longer than 1ns"));
+    }
+
+    // A generous timeout doesn't change the result
+    let options = BuildOptions { operation_timeout: Some(Duration::from_secs(60)), ..BuildOptions::default() };
+    let timed = build_with_options(InputSource::new_string(source.to_owned()), options).unwrap();
+    let untimed = build(InputSource::new_string(source.to_owned())).unwrap();
+    assert_eq!(format_scene_dump(&timed.scene()), format_scene_dump(&untimed.scene()));
+}
+
+#[test]
+fn test_memory_budget() {
+    let source = "
+        for (i = [0:1000]) {
+            translate([i * 20, 0, 0]) cylinder(10, 5);
+        }
+    ";
+
+    // Far too small for the loop to finish
+    let options = BuildOptions { memory_budget: Some(100_000), ..BuildOptions::default() };
+    let Err(LangError::Runtime(error)) = build_with_options(InputSource::new_string(source.to_owned()), options)
+    else { panic!("expected runtime error") };
+    assert!(error.to_string().ends_with("of memory, which is over the budget of 100.0 KB"));
+    assert_eq!(error.span.start_line(), 3);
+
+    // A generous budget doesn't change the result, and gives an estimate
+    let options = BuildOptions { memory_budget: Some(1_000_000_000), ..BuildOptions::default() };
+    let budgeted = build_with_options(InputSource::new_string(source.to_owned()), options).unwrap();
+    let unbudgeted = build(InputSource::new_string(source.to_owned())).unwrap();
+    assert_eq!(format_scene_dump(&budgeted.scene()), format_scene_dump(&unbudgeted.scene()));
+    assert!(budgeted.estimated_memory() > 0);
+    assert_eq!(unbudgeted.estimated_memory(), 0);
+}
+
+#[test]
+fn test_if_chain_under_operator() {
+    let model = build_model(InputSource::new_string("
+        x = 2;
+        translate([10, 0, 0]) {
+            if (x == 1) { y = 1; }
+            else if (x == 2) { cube(1); }
+            else { cube(5); }
+        }
+    ".to_owned())).unwrap();
+
+    let bbox = model.bounding_box();
+    assert_eq!(bbox.min_point(), Vec3::new(10.0, 0.0, 0.0));
+    assert_eq!(bbox.max_point(), Vec3::new(11.0, 1.0, 1.0));
+
+    // Branches without geometry are fine on their own, just not as the only child of an operator
+    let model = build_model(InputSource::new_string("if (true) { y = 1; } cube(1);".to_owned())).unwrap();
+    assert_eq!(model.bounding_box().max_point(), Vec3::new(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn test_centering() {
+    let model = build_model(InputSource::new_string("translate([50, 20, 5]) cube([4, 6, 8]);".to_owned())).unwrap();
+
+    let offset = Centering::OnPlate.offset([&model]);
+    assert_eq!(offset, Vec3::new(-52.0, -23.0, -5.0));
+    let on_plate = model.translate(offset.x, offset.y, offset.z).bounding_box();
+    assert_eq!(on_plate.min_point(), Vec3::new(-2.0, -3.0, 0.0));
+    assert_eq!(on_plate.max_point(), Vec3::new(2.0, 3.0, 8.0));
+
+    assert_eq!(Centering::Center.offset([&model]), Vec3::new(-52.0, -23.0, -9.0));
+
+    // Combined geometry moves together, and empty geometry doesn't count
+    let other = build_model(InputSource::new_string("cube(2);".to_owned())).unwrap();
+    assert_eq!(Centering::OnPlate.offset([&model, &other, &Manifold::new()]), Vec3::new(-27.0, -13.0, 0.0));
+    assert_eq!(Centering::Center.offset([&Manifold::new()]), Vec3::new(0.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_binary_stl() {
+    let model = build(InputSource::new_string("cube(1); translate([5, 0, 0]) cube(2);".to_owned())).unwrap();
+    let meshes = model.groups().iter().map(|group| group.manifold.meshgl()).collect::<Vec<_>>();
+    let triangles = meshes.iter().map(|mesh| mesh.count_triangles()).sum::<usize>();
+
+    let mut stl = vec![];
+    write_binary_stl_from_meshgls(&meshes, "model", &mut stl).unwrap();
+    assert_eq!(&stl[..5], b"model");
+    assert_eq!(u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize, triangles);
+    assert_eq!(stl.len(), 84 + triangles * 50);
+
+    // Every point is within one of the cubes
+    let points = stl[84..].chunks_exact(50)
+        .flat_map(|record| (1..4).map(move |i| {
+            let value = |j: usize| f32::from_le_bytes(record[(i * 3 + j) * 4..(i * 3 + j) * 4 + 4].try_into().unwrap());
+            Vec3::new(value(0), value(1), value(2))
+        }))
+        .collect::<Vec<_>>();
+    assert!(points.iter().all(|point| point.x <= 1.0 || point.x >= 5.0));
+    assert!(points.iter().any(|point| *point == Vec3::new(7.0, 2.0, 2.0)));
+}
+
+#[test]
+fn test_export_placement() {
+    let model = build_model(InputSource::new_string("translate([50, 20, 5]) cube([4, 6, 8]);".to_owned())).unwrap();
+
+    // Millimetres to metres
+    let mut scaled = model.clone();
+    let offset = ExportPlacement { scale: 0.001, ..ExportPlacement::default() }.apply(vec![&mut scaled]);
+    assert_eq!(offset, None);
+    let bounding_box = scaled.bounding_box();
+    assert_eq!(bounding_box.min_point(), Vec3::new(50.0 * 0.001, 20.0 * 0.001, 5.0 * 0.001));
+    assert_eq!(bounding_box.max_point(), Vec3::new(54.0 * 0.001, 26.0 * 0.001, 13.0 * 0.001));
+
+    // Scaling happens after centering, so the model stays on the plate
+    let (mut first, mut second) = (model.clone(), model.translate(10.0, 0.0, 0.0));
+    let placement = ExportPlacement { centering: Some(Centering::OnPlate), scale: 2.0, up: UpAxis::Z };
+    assert_eq!(placement.apply(vec![&mut first, &mut second]), Some(Vec3::new(-57.0, -23.0, -5.0)));
+    assert_eq!(first.bounding_box().min_point(), Vec3::new(-14.0, -6.0, 0.0));
+    assert_eq!(second.bounding_box().max_point(), Vec3::new(14.0, 6.0, 16.0));
+
+    // The default changes nothing
+    let mut unchanged = model.clone();
+    ExportPlacement::default().apply(vec![&mut unchanged]);
+    assert_eq!(unchanged.bounding_box().max_point(), Vec3::new(54.0, 26.0, 13.0));
+}
+
+#[test]
+fn test_export_precision() {
+    let precision = |source: &str, decimal_places| {
+        let model = build_model(InputSource::new_string(source.to_owned())).unwrap();
+        ExportPrecision::measure(&model.meshgl(), decimal_places).unwrap()
+    };
+
+    // A feature far smaller than the gap between floats this far from the origin collapses
+    let collapsed = precision("translate([100000, 0, 0]) cube([0.0000001, 1, 1]);", None);
+    assert!(collapsed.is_lossy(), "{collapsed:?}");
+    assert_eq!(collapsed.largest_coordinate, 100000.0);
+
+    // Precision is relative to distance from the origin, so a thin wall is fine near it but not
+    // far away
+    assert!(!precision("cube([0.001, 1, 1]);", None).is_lossy());
+    assert!(precision("translate([100000, 0, 0]) cube([0.001, 1, 1]);", None).is_lossy());
+
+    // Limiting the decimal places written adds its own rounding error
+    assert!(!precision("cube(0.01);", None).is_lossy());
+    assert!(precision("cube(0.01);", Some(3)).is_lossy());
+    assert!(!precision("cube(10);", Some(3)).is_lossy());
+
+    assert_eq!(ExportPrecision::measure(&Manifold::new().meshgl(), None), None);
+}
+
+#[test]
+fn test_export_rescale_factor() {
+    let factor = |source: &str| export_rescale_factor([&build_model(InputSource::new_string(source.to_owned())).unwrap()]);
+
+    assert_eq!(factor("cube(0.01);"), 10000.0);
+    assert_eq!(factor("cube(250);"), 1.0);
+    assert_eq!(factor("translate([-20000, 0, 0]) cube(1);"), 0.01);
+    assert_eq!(export_rescale_factor([&Manifold::new()]), 1.0);
+}
+
+#[test]
+fn test_up_axis() {
+    let model = build_model(InputSource::new_string("cube([1, 2, 3]);".to_owned())).unwrap();
+
+    let z_up = UpAxis::Z.apply(&model).bounding_box();
+    assert_eq!(z_up.min_point(), Vec3::new(0.0, 0.0, 0.0));
+    assert_eq!(z_up.max_point(), Vec3::new(1.0, 2.0, 3.0));
+
+    // The height ends up along Y, and the depth extends backwards along -Z
+    let y_up = UpAxis::Y.apply(&model).bounding_box();
+    assert_eq!(y_up.min_point(), Vec3::new(0.0, 0.0, -2.0));
+    assert_eq!(y_up.max_point(), Vec3::new(1.0, 3.0, 0.0));
+
+    assert_eq!(UpAxis::from_name("y"), Some(UpAxis::Y));
+    assert_eq!(UpAxis::from_name("x"), None);
+}
+
+#[test]
+fn test_mesh_normals() {
+    let dot = |a: Vec3<f32>, b: Vec3<f32>| a.x * b.x + a.y * b.y + a.z * b.z;
+    let length = |v: Vec3<f32>| dot(v, v).sqrt();
+
+    // Computed on demand - a cube's normals all point along an axis
+    let cube = Manifold::cube(2.0, 2.0, 2.0, true).unwrap().meshgl();
+    assert_eq!(cube.normal_property(), None);
+    assert!(cube.vertex_normals().is_none());
+    let normals = cube.compute_corner_normals(60.0);
+    assert_eq!(normals.len(), cube.count_triangles() * 3);
+    assert!(normals.iter().all(|n| [n.x, n.y, n.z].iter().filter(|c| c.abs() > 0.9999).count() == 1));
+
+    // Stored by Manifold - a sphere's normals point away from its centre
+    let sphere = Manifold::sphere(5.0, 64).unwrap().calculate_normals(0, 60.0).meshgl_with_normals(0);
+    assert_eq!(sphere.normal_property(), Some(3));
+    assert_eq!(sphere.count_extra_vertex_properties(), 3);
+    let normals = sphere.vertex_normals().unwrap();
+    assert_eq!(normals.len(), sphere.count_vertices());
+    for (position, normal) in sphere.vertex_positions().into_iter().zip(normals) {
+        assert!(dot(position.map(|c| c / length(position)), normal) > 0.99);
+    }
+    assert!(sphere.iter_triangles().all(|tri| tri.normals.is_some() && tri.properties[0].len() == 3));
+}
+
+/// A pattern of screw-like parts, built with a pattern operator or with a loop.
+fn screws(instanced: bool) -> String {
+    let screw = "union() { cylinder(6, 1); translate([0, 0, 6]) cylinder(1, 2); }";
+    if instanced {
+        format!("linear_pattern(200, [5, 0, 0]) {screw}")
+    } else {
+        format!("for (i = [0 : 199]) {{ translate([i * 5, 0, 0]) {screw} }}")
+    }
+}
+
+#[test]
+fn test_instancing() {
+    let build_source = |source: &str| build(InputSource::new_string(source.to_owned())).unwrap();
+
+    // Instances give the same result as building each copy separately
+    let instanced = build_source(&screws(true));
+    let naive = build_source(&screws(false));
+    let (instanced_manifold, naive_manifold) = (instanced.manifold(), naive.manifold());
+    assert_eq!(instanced_manifold.count_triangles(), naive_manifold.count_triangles());
+    assert!((instanced_manifold.volume() - naive_manifold.volume()).abs() < 1e-6);
+    let (instanced_bounds, naive_bounds) = (instanced_manifold.bounding_box(), naive_manifold.bounding_box());
+    assert_eq!(
+        (instanced_bounds.min_point(), instanced_bounds.max_point()),
+        (naive_bounds.min_point(), naive_bounds.max_point()),
+    );
+
+    // The preview keeps them as instances of one manifold
+    let preview = instanced.preview();
+    assert!(preview.manifold.is_empty());
+    assert_eq!(preview.instances.len(), 1);
+    assert_eq!(preview.instances[0].transforms.len(), 200);
+    assert!(naive.preview().instances.is_empty());
+
+    // Moving and rotating keeps instances, and patterns of patterns multiply
+    let preview = build_source("
+        rotate([0, 0, 90])
+        translate([0, 0, 10])
+        circular_pattern(6)
+        linear_pattern(3, [5, 0, 0])
+        translate([10, 0, 0])
+        cube(1);
+    ").preview();
+    assert_eq!(preview.instances.len(), 1);
+    assert_eq!(preview.instances[0].transforms.len(), 18);
+
+    // Overlapping copies are unioned, rather than composed into an invalid manifold
+    let overlapping = build_source("circular_pattern(4, 360) cube([10, 1, 1], true);").manifold();
+    let naive_overlapping = build_source("
+        cube([10, 1, 1], true);
+        rotate([0, 0, 90]) cube([10, 1, 1], true);
+    ").manifold();
+    assert!((overlapping.volume() - naive_overlapping.volume()).abs() < 1e-6);
+
+    // Anything else realises the instances
+    let model = build_source("difference() { linear_pattern(3, [5, 0, 0]) cube(2); cube(1); }");
+    assert!(model.preview().instances.is_empty());
+    assert!((model.manifold().volume() - 23.0).abs() < 1e-6);
+
+    // 2D patterns are built directly
+    let model = build_source("linear_extrude(1) linear_pattern(3, [5, 0]) square(2);");
+    assert!((model.manifold().volume() - 12.0).abs() < 1e-6);
+
+    testing::assert_runtime_error("linear_pattern(0, [1, 0, 0]) cube(1);", "type error - expected positive whole number, got 0");
+}
+
+/// Compares building a pattern of 200 parts with and without instancing.
+/// 
+/// Run with `cargo test --release -- --ignored --nocapture bench_instancing`.
+#[test]
+#[ignore]
+fn bench_instancing() {
+    for instanced in [false, true] {
+        let start = std::time::Instant::now();
+        let model = build(InputSource::new_string(screws(instanced))).unwrap();
+        let built = start.elapsed();
+        let triangles = model.manifold().count_triangles();
+        let total = start.elapsed();
+
+        println!("instanced: {instanced}, interpreted in {built:?}, built {triangles} triangles in {total:?}");
+    }
+}
+
+
+#[test]
+fn test_locale_hints() {
+    let source = "cylinder(10,5);";
+
+    let model = build(InputSource::new_string(source.to_owned())).unwrap();
+    let [warning] = model.warnings() else { panic!("expected one warning") };
+    assert!(warning.to_string().starts_with("\"10,5\" is two numbers"));
+
+    let options = BuildOptions { locale_hints: false, ..BuildOptions::default() };
+    let model = build_with_options(InputSource::new_string(source.to_owned()), options).unwrap();
+    assert!(model.warnings().is_empty());
+
+    let model = build(InputSource::new_string("cylinder(10, 5);".to_owned())).unwrap();
+    assert!(model.warnings().is_empty());
+
+    // Only warned about once, however many times it runs
+    let model = build(InputSource::new_string("for (i = [0:3]) cube([1,5, 1]);".to_owned())).unwrap();
+    assert_eq!(model.warnings().len(), 1);
+}
+
+#[test]
+fn test_deny_warnings() {
+    let deny = BuildOptions { deny_warnings: true, ..BuildOptions::default() };
+
+    match build_with_options(InputSource::new_string("cylinder(10,5);".to_owned()), deny.clone()) {
+        Err(LangError::Warnings(warnings)) => {
+            let [warning] = &warnings[..] else { panic!("expected one warning") };
+            assert!(warning.to_string().starts_with("\"10,5\" is two numbers"));
+            assert_eq!((warning.span.start, warning.span.length), (9, 4));
+        },
+        _ => panic!("expected the warning to fail the build"),
+    }
+
+    // Without warnings, it makes no difference
+    let model = build_with_options(InputSource::new_string("cylinder(10, 5);".to_owned()), deny.clone()).unwrap();
+    assert!(model.warnings().is_empty());
+
+    // Warnings which are turned off can't fail the build
+    let options = BuildOptions { locale_hints: false, ..deny };
+    assert!(build_with_options(InputSource::new_string("cylinder(10,5);".to_owned()), options).is_ok());
+}
+
+/// Captures log records, separately for each thread so that tests running in parallel don't see
+/// each other's records.
+struct TestLogger;
+
+thread_local! {
+    static LOG_RECORDS: RefCell<Vec<(log::Level, String)>> = const { RefCell::new(vec![]) };
+}
+
+impl log::Log for TestLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        LOG_RECORDS.with_borrow_mut(|records| records.push((record.level(), record.args().to_string())));
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn test_debug_logging() {
+    // Only the first test to set a logger succeeds, but they're all the same
+    let _ = log::set_logger(&TestLogger);
+    log::set_max_level(log::LevelFilter::Trace);
+
+    build(InputSource::new_string("__debug(5); __debug(\"hello\");".to_owned())).unwrap();
+
+    let records = LOG_RECORDS.take();
+    let info = records.iter()
+        .filter(|(level, _)| *level == log::Level::Info)
+        .map(|(_, message)| message.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(info, vec!["Number(\n    5.0,\n)", "String(\n    \"hello\",\n)"]);
+}
+
+#[test]
+fn test_testing_assertions_pass() {
+    let model = testing::build("cube([10, 20, 30]); translate([20, 0, 0]) cube(5);");
+    model
+        .assert_volume_within(6124.0..6126.0)
+        .assert_bbox([0.0, 0.0, 0.0], [25.0, 20.0, 30.0])
+        .assert_part_count(2);
+
+    let slice = model.slice_at(2.0);
+    assert!((slice.area - 225.0).abs() < 1e-6);
+    assert_eq!(slice.polygon_count, 2);
+    assert_eq!(slice.min, Vec2::new(0.0, 0.0));
+    assert_eq!(slice.max, Vec2::new(25.0, 20.0));
+
+    // Above the small cube, only the large one is cut
+    let slice = model.slice_at(10.0);
+    assert_eq!(slice.polygon_count, 1);
+    assert_eq!(slice.max, Vec2::new(10.0, 20.0));
+}
+
+#[test]
+#[should_panic(expected = "expected volume within 0.0..100.0, but it was 6000\nmodel has volume 6000, bounding box [0, 0, 0] to [10, 20, 30], 1 parts")]
+fn test_testing_assert_volume_within_fails() {
+    testing::build("cube([10, 20, 30]);").assert_volume_within(0.0..100.0);
+}
+
+#[test]
+#[should_panic(expected = "expected bounding box [0, 0, 0] to [10, 20, 31], but it was [0, 0, 0] to [10, 20, 30]")]
+fn test_testing_assert_bbox_fails() {
+    testing::build("cube([10, 20, 30]);").assert_bbox([0.0, 0.0, 0.0], [10.0, 20.0, 31.0]);
+}
+
+#[test]
+#[should_panic(expected = "expected 2 parts, but there were 1")]
+fn test_testing_assert_part_count_fails() {
+    testing::build("cube([10, 20, 30]);").assert_part_count(2);
+}
+
+#[test]
+#[should_panic(expected = "model failed to build:\nline 1, column 1: ")]
+fn test_testing_build_fails() {
+    testing::build("nonexistent();");
+}
+
+#[test]
+fn test_testing_assert_same_shape() {
+    let original = testing::build("cube(10);");
+    testing::build("translate([-3, 0, 0]) translate([3, 0, 0]) cube(10);").assert_same_shape(&original);
+
+    // 10% bigger along one axis is a 1/11 difference relative to the bigger cube
+    let stretched = testing::build("scale([1.1, 1, 1]) cube(10);");
+    stretched.assert_same_shape_within(&original, 0.1);
+}
+
+#[test]
+#[should_panic(expected = "expected the same shape as a model with volume 1000, bounding box [0, 0, 0] to [10, 10, 10], 1 parts, but they differ by")]
+fn test_testing_assert_same_shape_fails() {
+    let original = testing::build("cube(10);");
+    testing::build("scale([1.01, 1.01, 1.01]) cube(10);").assert_same_shape(&original);
+}
+
+#[test]
+fn test_print_clearance_widens_cutters() {
+    let plate = |clearance: f64| testing::build(&format!("
+        print_clearance({clearance});
+
+        module peg_hole() {{
+            translate([0, 0, -1]) cylinder(12, d = hole_d(5));
+        }}
+
+        difference() {{
+            translate([-10, -10, 0]) cube([20, 20, 10]);
+            peg_hole();
+        }}
+    "));
+
+    let tight = plate(0.0).slice_at(5.0);
+    let loose = plate(0.4).slice_at(5.0);
+    assert!(loose.area < tight.area, "hole didn't grow: {} then {}", tight.area, loose.area);
+
+    let cutter = |clearance: f64| testing::build(&format!("print_clearance({clearance}); cylinder(1, d = hole_d(5));"));
+    cutter(0.0).assert_bbox([-2.5, -2.5, 0.0], [2.5, 2.5, 1.0]);
+    cutter(0.4).assert_bbox([-2.7, -2.7, 0.0], [2.7, 2.7, 1.0]);
+    cutter(-0.2).assert_bbox([-2.4, -2.4, 0.0], [2.4, 2.4, 1.0]);
+}
+
+#[test]
+fn test_prelude() {
+    let hexagon = testing::build("linear_extrude(1) hexagon(10);");
+    let circumradius = 5.0 / 30f64.to_radians().cos();
+    hexagon.assert_bbox([-5.0, -circumradius, 0.0], [5.0, circumradius, 1.0]);
+    assert!((hexagon.slice_at(0.5).area - 150.0 * 30f64.to_radians().tan()).abs() < 1e-6);
+
+    // Triangles have one flat side along +X, and a corner pointing along -X
+    testing::build("linear_extrude(1) ngon(3, 1);")
+        .assert_bbox([-2.0, -3f64.sqrt(), 0.0], [1.0, 3f64.sqrt(), 1.0]);
+
+    // The straight sides meet the corners exactly, so the bounds match a plain rectangle
+    let rounded = testing::build("linear_extrude(1) rounded_rect([10, 6], 1);");
+    rounded.assert_bbox([0.0, 0.0, 0.0], [10.0, 6.0, 1.0]);
+    let area = rounded.slice_at(0.5).area;
+    assert!(area < 60.0 && area > 60.0 - (4.0 - std::f64::consts::PI) - 0.1, "rounded_rect area was {area}");
+
+    let washer = testing::build("washer(10, 6, 2);");
+    let slice = washer.slice_at(1.0);
+    assert_eq!(slice.polygon_count, 2);
+    assert!((slice.area - std::f64::consts::PI * 16.0).abs() < 1.0, "washer area was {}", slice.area);
+    washer.assert_volume_within(98.0..101.0);
+}
+
+#[test]
+fn test_prelude_shadowing() {
+    // Definitions of any kind replace prelude modules with the same name
+    testing::build("module hexagon(d) { square(d); } linear_extrude(1) hexagon(2);")
+        .assert_bbox([0.0, 0.0, 0.0], [2.0, 2.0, 1.0]);
+    testing::build("operator washer() { children(); } washer() cube(3);")
+        .assert_bbox([0.0, 0.0, 0.0], [3.0, 3.0, 3.0]);
+    testing::build("rounded_rect = 4; cube(rounded_rect);")
+        .assert_bbox([0.0, 0.0, 0.0], [4.0, 4.0, 4.0]);
+
+    // Replacing `ngon` changes `hexagon`, which uses it
+    testing::build("module ngon(sides, apothem) { square(apothem); } linear_extrude(1) hexagon(4);")
+        .assert_bbox([0.0, 0.0, 0.0], [2.0, 2.0, 1.0]);
+
+    let options = BuildOptions { prelude: false, ..BuildOptions::default() };
+    let result = build_with_options(InputSource::new_string("linear_extrude(1) hexagon(10);".to_owned()), options);
+    assert!(matches!(result, Err(LangError::Runtime(error)) if error.kind.to_string() == "undefined identifier \"hexagon\""));
+}
+
+#[test]
+fn test_center() {
+    testing::build("cube(2);").assert_bbox([0.0, 0.0, 0.0], [2.0, 2.0, 2.0]);
+    testing::build("cube(2, center = true);").assert_bbox([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+    testing::build("cube([2, 4, 6], center = false);").assert_bbox([0.0, 0.0, 0.0], [2.0, 4.0, 6.0]);
+
+    testing::build("linear_extrude(1) square([2, 4], center = true);")
+        .assert_bbox([-1.0, -2.0, 0.0], [1.0, 2.0, 1.0]);
+
+    // Cylinders are always centred on X and Y, so this only moves them on Z
+    testing::build("cylinder(4, 1, center = true);").assert_bbox([-1.0, -1.0, -2.0], [1.0, 1.0, 2.0]);
+    testing::build("cylinder(4, d = 2, center = true);").assert_bbox([-1.0, -1.0, -2.0], [1.0, 1.0, 2.0]);
+}
+
+#[test]
+fn test_file_access_policy() {
+    let build_surface = |policy: FileAccessPolicy| {
+        let options = BuildOptions { file_access: policy, ..BuildOptions::default() };
+        match build_with_options(InputSource::new_string("surface(\"/etc/passwd\");".to_owned()), options) {
+            Err(LangError::Runtime(error)) => error.kind.to_string(),
+            _ => panic!("expected a runtime error"),
+        }
+    };
+
+    assert_eq!(build_surface(FileAccessPolicy::DenyAll), "not allowed to read \"/etc/passwd\", because file access is disabled");
+    assert_eq!(
+        build_surface(FileAccessPolicy::AllowList(vec!["/home/user/models".into()])),
+        "not allowed to read \"/etc/passwd\", because only files within \"/home/user/models\" are allowed",
+    );
+}
+
+#[test]
+fn test_original_tracking() {
+    // Manifold keeps track of which original each triangle came from through booleans
+    let a = Manifold::cube(2.0, 2.0, 2.0, false).unwrap().as_original();
+    let b = Manifold::cube(2.0, 2.0, 2.0, false).unwrap().translate(1.0, 1.0, 1.0).as_original();
+    let (a_id, b_id) = (a.original_id().unwrap(), b.original_id().unwrap());
+    assert_ne!(a_id, b_id);
+    assert_eq!(a.union(&b).original_id(), None);
+
+    let mesh = a.union(&b).meshgl();
+    let ids = mesh.triangle_original_ids();
+    assert_eq!(ids.len(), mesh.count_triangles());
+    assert!(ids.iter().all(|id| *id == a_id || *id == b_id));
+    assert!(ids.contains(&a_id) && ids.contains(&b_id));
+    assert_eq!(mesh.run_index_data().len(), mesh.run_original_id_data().len() + 1);
+
+    // Built models can trace triangles back to the primitive's source
+    let code = "union() {\n    cube(2);\n    translate([1, 1, 1]) cube(2);\n}";
+    let options = BuildOptions { track_originals: true, ..BuildOptions::default() };
+    let model = build_with_options(InputSource::new_string(code.to_owned()), options).unwrap();
+    let mesh = model.manifold().meshgl();
+    let lines = model.triangle_provenance(&mesh)
+        .into_iter()
+        .map(|provenance| provenance.expect("triangle has no provenance").start_line())
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), mesh.count_triangles());
+    assert!(lines.iter().all(|line| *line == 2 || *line == 3));
+    assert!(lines.contains(&2) && lines.contains(&3));
+
+    // Without tracking, nothing is traced
+    let model = build(InputSource::new_string(code.to_owned())).unwrap();
+    assert!(model.triangle_provenance(&model.manifold().meshgl()).iter().all(Option::is_none));
+}
+
+#[test]
+fn test_build_fingerprint() {
+    let fingerprint = |source: &str| BuildFingerprint::from_source(&InputSource::new_string(source.to_owned()));
+
+    // The hash is of the source alone, so it matches hashing the file
+    let cube = fingerprint("cube(10);");
+    assert_eq!(cube, fingerprint("cube(10);"));
+    assert_eq!(BuildFingerprint::from_source(&InputSource::new_string(String::new())).source_sha256(), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(cube.to_string(), format!("sha256:{} v{}", &cube.source_sha256()[..8], yascad_lang::VERSION));
+
+    // Any change to the source changes the hash
+    assert_ne!(cube.source_hash, fingerprint("cube(11);").source_hash);
+    assert_ne!(cube.source_hash, fingerprint("cube(10); ").source_hash);
+
+    // Built models carry the fingerprint of their source
+    assert_eq!(build(InputSource::new_string("cube(10);".to_owned())).unwrap().fingerprint(), cube);
+
+    // ...and of the files it read, so changing an included file changes the fingerprint too
+    let dir = std::env::temp_dir().join(format!("yascad-test-fingerprint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = format!("include \"{}\"; cube(size);", dir.join("size.yascad").display());
+    let included_fingerprint = |size: &str| {
+        std::fs::write(dir.join("size.yascad"), size).unwrap();
+        build(InputSource::new_string(source.clone())).unwrap().fingerprint()
+    };
+    let small = included_fingerprint("size = 1;");
+    assert_eq!(small, included_fingerprint("size = 1;"));
+    assert_ne!(small, included_fingerprint("size = 2;"));
+    assert_ne!(small, fingerprint(&source));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Combining depends on every fingerprint and their order, but leaves a single one alone
+    let cylinder = fingerprint("cylinder(10, 2);");
+    assert_eq!(BuildFingerprint::combine(&[cube]), cube);
+    assert_ne!(BuildFingerprint::combine(&[cube, cylinder]), BuildFingerprint::combine(&[cylinder, cube]));
+    assert_ne!(BuildFingerprint::combine(&[cube, cylinder]).source_hash, cube.source_hash);
+
+    // Binary STL headers keep the whole fingerprint within 80 bytes, however long the name
+    assert_eq!(cube.binary_stl_header("YASCADExport"), format!("YASCADExport {cube}"));
+    for name in ["", "a very long name for a part, which would take up the whole header by itself", &"é".repeat(50)] {
+        let header = cube.binary_stl_header(name);
+        assert!(header.len() <= 80, "header was {} bytes", header.len());
+        assert!(header.ends_with(&format!(" {cube}")));
+    }
+
+    let mut stl = vec![];
+    let meshes = [build(InputSource::new_string("cube(10);".to_owned())).unwrap().manifold().meshgl()];
+    write_binary_stl_from_meshgls(&meshes, &cube.binary_stl_header(&"x".repeat(100)), &mut stl).unwrap();
+    assert!(String::from_utf8_lossy(&stl[..80]).contains(&cube.to_string()));
+}
+
+#[test]
+fn test_vector_argument_geometry() {
+    // Geometry only used to work out a vector argument doesn't end up in the model
+    testing::build("translate([cube(2).size.x, 0, 0]) cube(1);")
+        .assert_bbox([2.0, 0.0, 0.0], [3.0, 1.0, 1.0]);
+
+    let error = testing::assert_runtime_error("translate([10, true, 3]) cube(1);", "type error - expected element 1 of vector to be number, got boolean");
+    assert_eq!((error.span.start, error.span.length), (15, 4));
+}
+
+#[test]
+fn test_empty_for_loop() {
+    // Looping over nothing adds nothing, rather than being an error
+    testing::build("
+        holes = [];
+        difference() {
+            cube(10);
+            for (hole = holes) {
+                translate(hole) cylinder(10, r = 1);
+            }
+        }
+        for (i = []) { cube(100); }
+    ").assert_volume_within(999.999..1000.001);
+}
+
+#[test]
+fn test_reassignment_in_for_loop() {
+    // Laying out parts side by side, each after the ones before it
+    testing::build("
+        x = 0;
+        for (width = [1, 2, 3]) {
+            translate([x, 0, 0]) cube([width, 1, 1]);
+            set x = x + width + 1;
+        }
+        translate([x, 0, 0]) cube(1);
+    ")
+        .assert_bbox([0.0, 0.0, 0.0], [10.0, 1.0, 1.0])
+        .assert_part_count(4);
+
+    // A loop which only changes bindings adds no geometry
+    testing::build("
+        total = 0;
+        for (i = [1:4]) { set total = total + i; }
+        cube(total);
+    ").assert_bbox([0.0, 0.0, 0.0], [10.0, 10.0, 10.0]);
+}
+
+#[test]
+fn test_functions() {
+    // Functions can be used before they're defined, like modules, and in their arguments
+    testing::build("
+        module rack(teeth) {
+            cube([rack_length(teeth), 4, 2]);
+        }
+        function rack_length(teeth, pitch = 3) = teeth * pitch;
+
+        rack(10);
+    ").assert_bbox([0.0, 0.0, 0.0], [30.0, 4.0, 2.0]);
+
+    // A function which returns geometry can still be the child of an operator
+    testing::build("
+        function unit() = cube(1);
+        translate([1, 0, 0]) unit();
+    ").assert_bbox([1.0, 0.0, 0.0], [2.0, 1.0, 1.0]);
+}
+
+#[test]
+fn test_it_in_arguments() {
+    // Named arguments can use `it` just like positional ones
+    testing::build("translate(v = [-it.size.x / 2, 0, 0]) cube(10);")
+        .assert_bbox([-5.0, 0.0, 0.0], [5.0, 10.0, 10.0]);
+    testing::build("translate([0, -it.size.y / 2, max(it.size.z, 10)]) cube(4);")
+        .assert_bbox([0.0, -2.0, 10.0], [4.0, 2.0, 14.0]);
+
+    // Modules called from an argument don't see `it`, since they're not arguments themselves
+    testing::assert_runtime_error("
+        module width() { it.size.x; }
+        translate([width(), 0, 0]) cube(1);
+    ", "cannot use `it` outside of operator target arguments");
+}
+
+#[test]
+fn test_math_placement() {
+    // Cylinders spaced evenly around a circle, none of which overlap
+    testing::build("
+        for (i = [0:5]) {
+            translate([10 * cos(i * 60), 10 * sin(i * 60), 0]) cylinder(2, r = 1);
+        }
+    ")
+        .assert_part_count(6)
+        .assert_volume_within(36.0..37.8);
+}
+
+#[test]
+fn test_difference_keep() {
+    // A block with a pocket cut into its top, and a boss added back in the middle of the pocket
+    let pocketed = "
+        difference() {
+            cube([20, 20, 10]);
+            translate([5, 5, 5]) cube([10, 10, 10]);
+            keep() translate([9, 9, 0]) cube([2, 2, 10]);
+        }
+    ";
+    let model = testing::build(pocketed);
+    model
+        .assert_volume_within(3519.999..3520.001)
+        .assert_bbox([0.0, 0.0, 0.0], [20.0, 20.0, 10.0]);
+
+    // Through the pocket, there's the block's outline, the pocket, and the boss
+    let slice = model.slice_at(7.5);
+    assert!((slice.area - 304.0).abs() < 1e-6);
+    assert_eq!(slice.polygon_count, 3);
+
+    // Kept children can come first, and there can be several
+    testing::build("
+        difference() {
+            keep() translate([0, 0, 10]) cube(1);
+            cube(10);
+            translate([5, 0, 0]) cube(10);
+            keep() translate([8, 0, 0]) cube(1);
+        }
+    ").assert_volume_within(501.999..502.001);
+
+    // There must still be something to subtract from
+    testing::assert_runtime_error("difference() keep() cube(1);", "this operation requires at least one child");
+}
+
+#[test]
+fn test_hull() {
+    // Two cylinders hull into a slot, with rounded ends and straight sides between them
+    testing::build("hull() { cylinder(2, r = 5); translate([20, 0, 0]) cylinder(2, r = 5); }")
+        .assert_bbox([-5.0, -5.0, 0.0], [25.0, 5.0, 2.0])
+        .assert_volume_within(550.0..558.0)
+        .assert_part_count(1);
+
+    // 2D children hull into a cross-section
+    testing::build("linear_extrude(1) hull() { square(10); translate([20, 0]) square(10); }")
+        .assert_volume_within(299.999..300.001);
+
+    // Empty children add nothing
+    testing::build("hull() { cube(10); empty_2d(); }")
+        .assert_volume_within(999.999..1000.001);
+
+    for (source, message) in [
+        ("hull() {}", "this operation requires at least one child"),
+        ("hull() { cube(10); square(10); }", "this operation tried to mix 2D and 3D geometry"),
+    ] {
+        testing::assert_runtime_error(source, message);
+    }
+}
+
+#[test]
+fn test_module_returning_parts() {
+    let hinge = "
+        module hinge() {
+            leaf_a = cube([10, 20, 2]);
+            leaf_b = translate([10, 0, 0]) cube([10, 20, 2]);
+            [leaf_a, leaf_b];
+        }
+    ";
+
+    // Each part can be placed separately
+    testing::build(&format!("{hinge}
+        leaves = hinge();
+        translate([0, 0, 10]) leaves.x;
+        translate([30, 0, 0]) leaves.y;
+    "))
+        .assert_bbox([0.0, 0.0, 0.0], [50.0, 20.0, 12.0])
+        .assert_volume_within(799.999..800.001)
+        .assert_part_count(2);
+
+    // Or iterated over
+    testing::build(&format!("{hinge}
+        for (leaf = hinge()) translate([0, 0, 5]) leaf;
+    "))
+        .assert_bbox([0.0, 0.0, 5.0], [20.0, 20.0, 7.0]);
+
+    // Vectors of anything else don't change how modules combine their geometry
+    testing::build("module m() { cube(1); [1, 2]; } translate([5, 0, 0]) m();")
+        .assert_bbox([5.0, 0.0, 0.0], [6.0, 1.0, 1.0]);
+
+    // Geometry which isn't in the vector has nowhere to go
+    let error = testing::assert_runtime_error(
+        "module m() { cube(1); a = cube(2); [a]; } m();",
+        "this geometry isn't part of the vector which the module returns, so would be lost",
+    );
+    assert_eq!(error.span.start_column(), 14);
+}
+
+#[test]
+fn test_operator_over_parts() {
+    let hinge = "
+        module hinge() {
+            leaf_a = cube([10, 20, 2]);
+            leaf_b = translate([10, 0, 0]) cube([10, 20, 2]);
+            [leaf_a, leaf_b];
+        }
+    ";
+
+    // An operator applied to a vector is applied to each element, and the parts stay separate
+    testing::build(&format!("{hinge}
+        leaves = translate([0, 0, 5]) hinge();
+        translate([0, 0, 10]) leaves.x;
+        translate([30, 0, 0]) leaves.y;
+    "))
+        .assert_bbox([0.0, 0.0, 5.0], [50.0, 20.0, 17.0])
+        .assert_volume_within(799.999..800.001)
+        .assert_part_count(2);
+
+    // The results are still geometry which the build collects
+    testing::build(&format!("{hinge} leaves = hinge(); translate([0, 0, 5]) leaves;"))
+        .assert_bbox([0.0, 0.0, 5.0], [20.0, 20.0, 7.0]);
+
+    // Alongside other children, the elements are combined like any other children
+    testing::build(&format!("{hinge} translate([0, 0, 5]) {{ hinge(); cube(1); }}"))
+        .assert_bbox([0.0, 0.0, 5.0], [20.0, 20.0, 7.0])
+        .assert_part_count(1);
+
+    // Other vectors aren't children, including vectors of geometry which are being bound for later
+    testing::build("translate([0, 0, 5]) { pos = [1, 2, 3]; translate(pos) cube(1); }")
+        .assert_bbox([1.0, 2.0, 8.0], [2.0, 3.0, 9.0]);
+    testing::build("translate([0, 0, 5]) { cube(1); [1, 2]; spare = [cube(2)]; }")
+        .assert_bbox([0.0, 0.0, 0.0], [2.0, 2.0, 6.0]);
+
+    // So they don't count as geometry when a branch had none
+    testing::assert_runtime_error("translate([0, 0, 5]) { pos = [1, 2]; if (false) { cube(1); } }", "the selected branch produced no geometry for this operator to apply to");
+}
+
+#[test]
+fn test_geometry_binops() {
+    // Each form builds the same geometry as the operator it stands for
+    for (shorthand, operator) in [
+        ("cube(10) - cylinder(20, 2);", "difference() { cube(10); cylinder(20, 2); }"),
+        ("cube(10) + translate([5, 0, 0]) cube(10);", "union() { cube(10); translate([5, 0, 0]) cube(10); }"),
+        ("cube(10) * translate([5, 5, 5]) cube(10);", "intersection() { cube(10); translate([5, 5, 5]) cube(10); }"),
+        ("square(10) - circle(2);", "difference() { square(10); circle(2); }"),
+        ("a = cube(10); b = cylinder(20, 2); c = cube(3); a - b - c;", "difference() { difference() { cube(10); cylinder(20, 2); } cube(3); }"),
+    ] {
+        let shorthand_manifold = build_model(InputSource::new_string(shorthand.to_owned())).unwrap();
+        let operator_manifold = build_model(InputSource::new_string(operator.to_owned())).unwrap();
+        assert_eq!(shorthand_manifold.volume(), operator_manifold.volume(), "{shorthand}");
+        assert_eq!(shorthand_manifold.count_triangles(), operator_manifold.count_triangles(), "{shorthand}");
+        let (shorthand_box, operator_box) = (shorthand_manifold.bounding_box(), operator_manifold.bounding_box());
+        assert_eq!((shorthand_box.min_point(), shorthand_box.max_point()), (operator_box.min_point(), operator_box.max_point()), "{shorthand}");
+    }
+
+    for (source, message) in [
+        ("cube(10) - square(10);", "this operation tried to mix 2D and 3D geometry"),
+        ("a = cube(10); a - a;", "the same geometry cannot be used on both sides of an operator"),
+        ("a = cube(10); b = cube(5); a - b; a + cube(1);", "this geometry no longer exists - it has already been used up, like by being the child of an operator"),
+        ("cube(10) / cube(5);", "type error - expected number, got 3D manifold"),
+    ] {
+        testing::assert_runtime_error(source, message);
+    }
+}
+
+#[test]
+fn test_resize() {
+    testing::build("resize([30, 10, 5]) translate([1, 1, 1]) cube([10, 20, 5]);")
+        .assert_bbox([3.0, 0.5, 1.0], [33.0, 10.5, 6.0]);
+
+    // Zero sizes leave that axis alone, unless `auto` scales it in proportion
+    testing::build("resize([30, 0, 0]) cube([10, 20, 5]);")
+        .assert_bbox([0.0, 0.0, 0.0], [30.0, 20.0, 5.0]);
+    testing::build("resize([30, 0, 0], auto = true) cube([10, 20, 5]);")
+        .assert_bbox([0.0, 0.0, 0.0], [30.0, 60.0, 15.0]);
+    testing::build("resize([0, 10, 0], auto = true) cube([10, 20, 5]);")
+        .assert_bbox([0.0, 0.0, 0.0], [5.0, 10.0, 2.5]);
+
+    // 2D children take a 2D size
+    testing::build("linear_extrude(1) resize([4, 6]) square([2, 2]);")
+        .assert_bbox([0.0, 0.0, 0.0], [4.0, 6.0, 1.0]);
+
+    for (source, message) in [
+        ("resize([10, -1, 0]) cube(1);", "size along Y must be positive, or 0 to leave it as it is, got -1"),
+        ("resize([10, 10, 10]) empty();", "cannot resize along X, because the geometry has no size along it"),
+        ("resize([10, 10]) empty_2d();", "cannot resize along X, because the geometry has no size along it"),
+        ("resize([10, 10, 10]) square(1);", "incorrect vector length - expected 2, got 3"),
+    ] {
+        testing::assert_runtime_error(source, message);
+    }
+}
+
+#[test]
+fn test_rotate_extrude() {
+    // A circle away from the axis revolves into a torus, of volume 2π²Rr² for a perfect circle
+    testing::build("rotate_extrude() translate([10, 0]) circle(2);")
+        .assert_volume_within(740.0..790.0)
+        .assert_part_count(1);
+
+    // Part of a revolution makes part of a torus
+    testing::build("rotate_extrude(180) translate([10, 0]) circle(2);")
+        .assert_volume_within(370.0..395.0);
+
+    for (source, message) in [
+        ("rotate_extrude(0) square(1);", "angle must be more than 0 and at most 360, got 0"),
+        ("rotate_extrude(-90) square(1);", "angle must be more than 0 and at most 360, got -90"),
+        ("rotate_extrude(400) square(1);", "angle must be more than 0 and at most 360, got 400"),
+        ("rotate_extrude() cube(1);", "this operation requires 2D geometry, but 3D was provided"),
+    ] {
+        testing::assert_runtime_error(source, message);
+    }
+}
+
+#[test]
+fn test_offset() {
+    // Rounding grows each side by the radius, with a quarter-circle at each corner
+    testing::build("linear_extrude(1) offset(r = 1) square(10);")
+        .assert_bbox([-1.0, -1.0, 0.0], [11.0, 11.0, 1.0])
+        .assert_volume_within(142.0..143.2);
+
+    // A negative delta shrinks it back down, keeping the corners sharp
+    testing::build("linear_extrude(1) offset(delta = -1) square(10);")
+        .assert_bbox([1.0, 1.0, 0.0], [9.0, 9.0, 1.0])
+        .assert_volume_within(63.999..64.001);
+
+    // Chamfering cuts a small triangle off each corner of what would otherwise be a 12x12 square
+    testing::build("linear_extrude(1) offset(delta = 1, chamfer = true) square(10);")
+        .assert_bbox([-1.0, -1.0, 0.0], [11.0, 11.0, 1.0])
+        .assert_volume_within(142.0..143.9);
+
+    for (source, message) in [
+        ("offset(r = 1) cube(10);", "this operation requires 2D geometry, but 3D was provided"),
+        ("offset() square(10);", "neither \"r\" nor \"delta\" argument is given, but one must be specified"),
+        ("offset(1, delta = 1) square(10);", "both \"r\" and \"delta\" arguments are given, but only one must be specified"),
+    ] {
+        testing::assert_runtime_error(source, message);
+    }
+}
+
+#[test]
+fn test_assert() {
+    // Assertions which pass don't affect the model
+    testing::build("
+        module plate(width, holes) {
+            assert(width > 0);
+            assert(holes >= 2, \"need at least two holes\");
+            cube([width, 10, 2]);
+        }
+
+        plate(20, 2);
+    ").assert_bbox([0.0, 0.0, 0.0], [20.0, 10.0, 2.0]);
+}
+
+#[test]
+fn test_assert_same_shape() {
+    // The compared geometry is used up, so only the last cube is left in the scene
+    testing::build("
+        assert_same_shape(translate([0, 0, -5]) translate([0, 0, 5]) cube(10), cube(10));
+        assert_same_shape(rotate([0, 0, 90]) cube(10, center = true), cube(10, center = true));
+        assert_same_shape(scale([1.1, 1, 1]) cube(10), cube(10), 0.1);
+        translate([20, 0, 0]) cube(1);
+    ")
+        .assert_bbox([20.0, 0.0, 0.0], [21.0, 1.0, 1.0])
+        .assert_part_count(1);
+
+    for (source, message) in [
+        ("assert_same_shape(scale([1.01, 1.01, 1.01]) cube(10), cube(10));", "assertion failed: shapes differ by"),
+        ("assert_same_shape(cube(10), cube(10), -1);", "tolerance must not be negative, got -1"),
+        ("a = cube(10); assert_same_shape(a, a);", "the same geometry cannot be compared with itself"),
+        ("assert_same_shape(square(10), square(10));", "type error - expected 3D manifold, got 2D cross-section"),
+    ] {
+        let error = testing::runtime_error(InputSource::new_string(source.to_owned()));
+        assert!(error.kind.to_string().starts_with(message), "{}", error.kind);
+    }
+}
+
+#[test]
+fn test_transform_argument_errors() {
+    // Errors about the transform point at its argument, whichever dimension the children are
+    for (source, message, column) in [
+        ("translate([1, 2, 3]) square(5);", "incorrect vector length - expected 2, got 3", 11),
+        ("translate([1]) cube(5);", "incorrect vector length - expected 2-3, got 1", 11),
+        ("rotate([0, 0, 90]) square(5);", "type error - expected number, got vector", 8),
+        ("mirror(v = [1, 0, 0, 0]) cube(5);", "incorrect vector length - expected 2-3, got 4", 12),
+        ("scale(2) linear_pattern(3, [10, 0, 0]) cube(5);", "type error - expected vector, got number", 7),
+        ("resize([-1, 5]) square(5);", "size along X must be positive, or 0 to leave it as it is, got -1", 8),
+    ] {
+        let error = testing::assert_runtime_error(source, message);
+        assert_eq!(error.span.start_column(), column, "{source}");
+    }
+}
+
+#[test]
+fn test_polyhedron() {
+    let points = "[[0, 0, 0], [10, 0, 0], [0, 10, 0], [0, 0, 10]]";
+    testing::build(&format!("polyhedron({points}, [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]]);"))
+        .assert_bbox([0.0, 0.0, 0.0], [10.0, 10.0, 10.0])
+        .assert_volume_within(166.66..166.67);
+
+    // Faces with more than three points are split into triangles
+    testing::build("
+        polyhedron(
+            points = [[0, 0, 0], [10, 0, 0], [10, 10, 0], [0, 10, 0], [5, 5, 6]],
+            faces = [[0, 3, 2, 1], [0, 1, 4], [1, 2, 4], [2, 3, 4], [3, 0, 4]],
+        );
+    ")
+        .assert_bbox([0.0, 0.0, 0.0], [10.0, 10.0, 6.0])
+        .assert_volume_within(199.99..200.01);
+
+    for (source, message) in [
+        // Missing its slanted face, so it isn't closed
+        (format!("polyhedron({points}, [[0, 2, 1], [0, 1, 3], [0, 3, 2]]);"), "invalid mesh: the mesh isn't closed, or its faces don't join up - every edge must be shared by exactly two faces, going along it in opposite directions"),
+        (format!("polyhedron({points}, [[0, 2, 1], [0, 1, 4]]);"), "faces must refer to points by their index, from 0 to 3, got 4"),
+        (format!("polyhedron({points}, [[0, 2, 1], [0, 1]]);"), "each face needs at least 3 points, but this one has 2"),
+        ("polyhedron([[0, 0, 0], [1, 0]], []);".to_owned(), "incorrect vector length - expected 3, got 2"),
+        ("polyhedron([[0, 0, \"z\"]], []);".to_owned(), "type error - expected element 2 of vector to be number, got string"),
+    ] {
+        testing::assert_runtime_error(&source, message);
+    }
+}
+
+#[test]
+fn test_import() {
+    let dir = std::env::temp_dir().join(format!("yascad-test-import-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // Round-trip a cube through both STL formats
+    let cube = build_model(InputSource::new_string("cube([10, 20, 30]);".to_owned())).unwrap().meshgl();
+    let mut binary = vec![];
+    cube.to_stl("cube").write_binary_stl(&mut binary).unwrap();
+    std::fs::write(dir.join("binary.stl"), binary).unwrap();
+    let mut text = vec![];
+    cube.to_stl("cube").write_text_stl(&mut text).unwrap();
+    std::fs::write(dir.join("text.stl"), text).unwrap();
+
+    // Paths are relative to the source file
+    let model_file = |source: &str| {
+        std::fs::write(dir.join("model.yascad"), source).unwrap();
+        InputSource::new_file(dir.join("model.yascad")).unwrap()
+    };
+    for file in ["binary.stl", "text.stl"] {
+        let imported = build_model(model_file(&format!("translate([5, 0, 0]) import(\"{file}\");"))).unwrap();
+        let bounding_box = imported.bounding_box();
+        assert_eq!((bounding_box.min_point(), bounding_box.max_point()), (Vec3::new(5.0, 0.0, 0.0), Vec3::new(15.0, 20.0, 30.0)), "{file}");
+        assert_eq!(imported.volume(), 6000.0, "{file}");
+    }
+
+    // Imported geometry can be used like any other
+    let drilled = build_model(model_file("difference() { import(\"binary.stl\"); cylinder(50, 2); }")).unwrap();
+    assert!(drilled.volume() < 6000.0);
+
+    std::fs::write(dir.join("malformed.stl"), "solid broken\nfacet normal 0 0 1\n").unwrap();
+    std::fs::write(dir.join("open.stl"), "solid open\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid open\n").unwrap();
+    for (source, message) in [
+        ("import(\"malformed.stl\");", "invalid STL file: the file ends without `endsolid`".to_owned()),
+        ("import(\"open.stl\");", "invalid mesh: the mesh isn't closed, or its faces don't join up - every edge must be shared by exactly two faces, going along it in opposite directions".to_owned()),
+        ("import(\"missing.stl\");", format!("could not read \"{}\"", dir.join("missing.stl").display())),
+    ] {
+        let error = testing::runtime_error(model_file(&format!("cube(1);\n{source}")));
+        assert!(error.kind.to_string().starts_with(&message), "{source}: {}", error.kind);
+        assert_eq!((error.span.start_line(), error.span.start_column()), (2, 1), "{source}");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_include() {
+    let dir = std::env::temp_dir().join(format!("yascad-test-include-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/plates.yascad"), "\
+module plate(width = 10) { cube([width, width, 1]); }
+module broken() { cube(missing); }
+plate_count = 2;
+translate([0, 0, -5]) plate(5);
+").unwrap();
+    std::fs::write(dir.join("lib/unfinished.yascad"), "cube(1").unwrap();
+    std::fs::write(dir.join("lib/units.yascad"), "units cm;").unwrap();
+    std::fs::write(dir.join("lib/a.yascad"), "include \"b.yascad\";").unwrap();
+    std::fs::write(dir.join("lib/b.yascad"), "use \"a.yascad\";").unwrap();
+    std::fs::write(dir.join("lib/shapes.yascad"), "module shape() { cube(1); }").unwrap();
+    std::fs::write(dir.join("lib/left.yascad"), "use \"shapes.yascad\"; module left() { shape(); }").unwrap();
+    std::fs::write(dir.join("lib/right.yascad"), "use \"shapes.yascad\"; module right() { translate([2, 0, 0]) shape(); }").unwrap();
+    let model_file = |source: &str| {
+        std::fs::write(dir.join("model.yascad"), source).unwrap();
+        InputSource::new_file(dir.join("model.yascad")).unwrap()
+    };
+
+    // `include` runs everything in the file, as if it were written in place
+    let included = build_model(model_file("include \"lib/plates.yascad\"; for (i = [1:plate_count]) translate([0, 0, i * 2]) plate();")).unwrap();
+    let bounding_box = included.bounding_box();
+    assert_eq!((bounding_box.min_point(), bounding_box.max_point()), (Vec3::new(0.0, 0.0, -5.0), Vec3::new(10.0, 10.0, 5.0)));
+
+    // `use` only takes the definitions
+    let used = build_model(model_file("use \"lib/plates.yascad\"; plate();")).unwrap();
+    let bounding_box = used.bounding_box();
+    assert_eq!((bounding_box.min_point(), bounding_box.max_point()), (Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 10.0, 1.0)));
+
+    // A library used by two files is only loaded once, so using it again doesn't undo the model
+    // replacing its definitions in between (this `shape` isn't hoisted, because its default refers
+    // to `size`)
+    let shared = build_model(model_file("use \"lib/left.yascad\"; left(); use \"lib/right.yascad\"; right();")).unwrap();
+    let bounding_box = shared.bounding_box();
+    assert_eq!((bounding_box.min_point(), bounding_box.max_point()), (Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 1.0, 1.0)));
+    let replaced = build_model(model_file("\
+use \"lib/left.yascad\";
+size = 2;
+module shape(width = size) { cube(width); }
+use \"lib/right.yascad\";
+right();
+")).unwrap();
+    let bounding_box = replaced.bounding_box();
+    assert_eq!((bounding_box.min_point(), bounding_box.max_point()), (Vec3::new(2.0, 0.0, 0.0), Vec3::new(4.0, 2.0, 2.0)));
+
+    // Errors within the included file point into it
+    let error = testing::runtime_error(model_file("include \"lib/plates.yascad\";\nbroken();"));
+    assert_eq!(error.kind.to_string(), "undefined identifier \"missing\"");
+    assert_eq!(error.span.source.origin(), &InputSourceOrigin::File(dir.join("lib/plates.yascad")));
+    assert_eq!((error.span.start_line(), error.span.start_column()), (2, 24));
+
+    let error = testing::runtime_error(model_file("plate_count = 1;\ninclude \"lib/unfinished.yascad\";"));
+    assert_eq!(error.kind.to_string(), "unexpected end-of-file");
+    assert_eq!(error.span.source.origin(), &InputSourceOrigin::File(dir.join("lib/unfinished.yascad")));
+    assert_eq!(error.trace.iter().map(ToString::to_string).collect::<Vec<_>>(), ["in \"lib/unfinished.yascad\", included from line 2"]);
+
+    for (source, message) in [
+        ("use \"lib/plates.yascad\"; plate_count;", "undefined identifier \"plate_count\""),
+        ("module m() { include \"lib/plates.yascad\"; } m();", "`include` can only be used at the top level"),
+        ("include \"lib/a.yascad\";", "\"a.yascad\" is already being included, so including it again would never finish"),
+        ("include \"model.yascad\";", "\"model.yascad\" is already being included, so including it again would never finish"),
+        ("include \"lib/units.yascad\";", "`units` can only be declared at the top level"),
+    ] {
+        assert_eq!(testing::runtime_error(model_file(source)).kind.to_string(), message, "{source}");
+    }
+
+    let error = testing::runtime_error(model_file("include \"lib/missing.yascad\";"));
+    assert!(error.kind.to_string().starts_with(&format!("could not read \"{}\"", dir.join("lib/missing.yascad").display())), "{}", error.kind);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
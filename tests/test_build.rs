@@ -1,8 +1,12 @@
 use std::error::Error;
 
 use insta::{assert_binary_snapshot, assert_snapshot, glob, with_settings};
-use manifold_rs::ext::MeshGLExt;
-use yascad_lang::{InputSource, LangError, build_model};
+use yascad_lang::{InputSource, LangError, build_model, export::MeshGLExt, testing};
+
+/// Models whose textual STL is bigger than this are snapshotted as a digest from
+/// [`testing::stl_digest`] instead, so that big fixtures don't bloat the repository. Smaller ones
+/// keep the full STL, since its diff shows what changed.
+const MAX_STL_SNAPSHOT_SIZE: usize = 64 * 1024;
 
 #[test]
 fn test_build() {
@@ -19,7 +23,12 @@ fn test_build() {
         let mut text_stl = Vec::new();
         stl.write_text_stl(&mut text_stl).unwrap();
 
-        assert_binary_snapshot!(".stl", text_stl);
+        if text_stl.len() > MAX_STL_SNAPSHOT_SIZE {
+            let digest = testing::stl_digest(&stl);
+            assert_snapshot!(digest);
+        } else {
+            assert_binary_snapshot!(".stl", text_stl);
+        }
     });
 }
 
@@ -40,7 +49,18 @@ fn test_error() {
         let errors = match error {
             LangError::Tokenize(errors) => flatten_errors(errors),
             LangError::Parser(errors) => flatten_errors(errors),
-            LangError::Runtime(error) => flatten_errors(vec![error]),
+            LangError::Runtime(error) => {
+                let frames = flatten_errors(error.trace.to_vec());
+                let help = error.help.as_ref().map(|help| format!("\nhelp: {help}")).unwrap_or_default();
+                let definition = error.context.as_ref()
+                    .and_then(|context| context.definition.as_ref())
+                    .map(|definition| format!("\ndefined on line {}", definition.start_line()))
+                    .unwrap_or_default();
+                let error = flatten_errors(vec![error]);
+                let error = format!("{error}{help}{definition}");
+                if frames.is_empty() { error } else { format!("{error}\n{frames}") }
+            },
+            LangError::Warnings(warnings) => flatten_errors(warnings),
         };
 
         assert_snapshot!(errors);
@@ -0,0 +1,83 @@
+//! Checks the memory behaviour of writing large meshes. This is its own test binary because it
+//! replaces the global allocator.
+
+use std::{alloc::{GlobalAlloc, Layout, System}, cell::Cell, io};
+
+use yascad_lang::{Manifold, export::{MeshGLExt, TextStlOptions, write_stl_from_meshgl}};
+
+/// Wraps the system allocator, counting allocations made on threads which have asked for it.
+struct CountingAllocator;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static ALLOCATED_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn record_allocation(size: usize) {
+    if COUNTING.get() {
+        ALLOCATIONS.set(ALLOCATIONS.get() + 1);
+        ALLOCATED_BYTES.set(ALLOCATED_BYTES.get() + size);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record_allocation(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record_allocation(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Run a function, returning the number of allocations it made and the total size of them.
+fn count_allocations(func: impl FnOnce()) -> (usize, usize) {
+    ALLOCATIONS.set(0);
+    ALLOCATED_BYTES.set(0);
+
+    COUNTING.set(true);
+    func();
+    COUNTING.set(false);
+
+    (ALLOCATIONS.get(), ALLOCATED_BYTES.get())
+}
+
+#[test]
+fn test_write_stl_from_meshgl_allocations() {
+    let mesh = Manifold::sphere(10.0, 512).unwrap().meshgl();
+    let triangles = mesh.count_triangles();
+    assert!(triangles > 100_000);
+
+    let (allocations, bytes) = count_allocations(|| {
+        write_stl_from_meshgl(&mesh, "Sphere", &mut io::sink(), &TextStlOptions::default()).unwrap();
+    });
+
+    // Only the copies of the vertex and triangle buffers should be allocated, not anything for
+    // each triangle
+    let buffer_bytes = (mesh.count_vertices() * mesh.count_vertex_properties() + triangles * 3) * 4;
+    assert_eq!(allocations, 2);
+    assert_eq!(bytes, buffer_bytes);
+}
+
+#[test]
+fn test_write_stl_from_meshgl_matches_stl() {
+    let mesh = Manifold::sphere(5.0, 32).unwrap().meshgl();
+
+    let mut direct = vec![];
+    write_stl_from_meshgl(&mesh, "Sphere", &mut direct, &TextStlOptions::default()).unwrap();
+
+    let mut via_stl = vec![];
+    mesh.to_stl("Sphere").write_text_stl(&mut via_stl).unwrap();
+
+    assert_eq!(String::from_utf8(direct).unwrap(), String::from_utf8(via_stl).unwrap());
+}